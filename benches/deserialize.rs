@@ -0,0 +1,137 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use apca::data::v2::bars;
+use apca::data::v2::quotes;
+use apca::data::v2::stream::DataMessage;
+use apca::data::v2::trades;
+
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::BatchSize;
+use criterion::Criterion;
+use criterion::Throughput;
+
+use http_endpoint::Endpoint;
+
+use serde_json::from_str as json_from_str;
+
+
+/// The number of items to include in a single page of data.
+///
+/// This value corresponds to the maximum page size that the Alpaca API
+/// supports.
+const PAGE_SIZE: usize = 10000;
+
+
+/// Create a JSON page of `count` items as returned by the API, with
+/// each item being produced by `item`.
+fn make_page<F>(key: &str, count: usize, item: F) -> String
+where
+  F: Fn(usize) -> String,
+{
+  let items = (0..count).map(item).collect::<Vec<_>>().join(",");
+  format!(
+    r#"{{"{}":[{}],"symbol":"AAPL","next_page_token":"QUFQTHwyMDIyLTA0LTExVDEyOjAwOjM2LjAwMjk1MTk0Nlp8Vg=="}}"#,
+    key, items
+  )
+}
+
+fn trade(i: usize) -> String {
+  format!(
+    r#"{{"t":"2022-04-11T12:00:{:02}.002951946Z","x":"V","p":168.{:02},"s":{},"c":["@","T","I"],"i":{},"z":"C"}}"#,
+    i % 60,
+    i % 100,
+    i,
+    i
+  )
+}
+
+fn quote(i: usize) -> String {
+  format!(
+    r#"{{"t":"2022-04-11T12:00:{:02}.002951946Z","ax":"C","ap":168.{:02},"as":{},"bx":"N","bp":167.{:02},"bs":{},"c":["R"]}}"#,
+    i % 60,
+    i % 100,
+    i,
+    i % 100,
+    i
+  )
+}
+
+fn bar(i: usize) -> String {
+  format!(
+    r#"{{"t":"2021-02-01T16:{:02}:00Z","o":133.{:02},"h":134.{:02},"l":132.{:02},"c":133.{:02},"v":{}}}"#,
+    i % 60,
+    i % 100,
+    i % 100,
+    i % 100,
+    i % 100,
+    i
+  )
+}
+
+fn stream_trade(i: usize) -> String {
+  format!(
+    r#"{{"T":"t","i":{},"S":"AAPL","x":"D","p":126.{:02},"s":{},"t":"2021-02-22T15:51:44.208Z","c":["@","I"],"z":"C"}}"#,
+    i,
+    i % 100,
+    i
+  )
+}
+
+
+/// Benchmark parsing of a single large page of data using the provided
+/// endpoint's parser.
+fn bench_page<E>(c: &mut Criterion, name: &str, page: String)
+where
+  E: Endpoint,
+  E::ConversionError: std::fmt::Debug,
+{
+  let mut group = c.benchmark_group(name);
+  let _ = group.throughput(Throughput::Bytes(page.len() as u64));
+  let _ = group.bench_function("parse", |b| {
+    b.iter_batched(
+      || page.as_bytes(),
+      |page| E::parse(page).unwrap(),
+      BatchSize::SmallInput,
+    )
+  });
+  group.finish();
+}
+
+fn parse_trades(c: &mut Criterion) {
+  let page = make_page("trades", PAGE_SIZE, trade);
+  bench_page::<trades::Get>(c, "trades", page)
+}
+
+fn parse_quotes(c: &mut Criterion) {
+  let page = make_page("quotes", PAGE_SIZE, quote);
+  bench_page::<quotes::Get>(c, "quotes", page)
+}
+
+fn parse_bars(c: &mut Criterion) {
+  let page = make_page("bars", PAGE_SIZE, bar);
+  bench_page::<bars::Get>(c, "bars", page)
+}
+
+fn parse_stream_messages(c: &mut Criterion) {
+  let messages = (0..1000).map(stream_trade).collect::<Vec<_>>().join(",");
+  let messages = format!("[{}]", messages);
+
+  let mut group = c.benchmark_group("stream");
+  let _ = group.throughput(Throughput::Bytes(messages.len() as u64));
+  let _ = group.bench_function("parse", |b| {
+    b.iter(|| json_from_str::<Vec<DataMessage>>(&messages).unwrap())
+  });
+  group.finish();
+}
+
+
+criterion_group!(
+  benches,
+  parse_trades,
+  parse_quotes,
+  parse_bars,
+  parse_stream_messages
+);
+criterion_main!(benches);
@@ -0,0 +1,89 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use serde_urlencoded::to_string as to_query;
+
+use crate::api::v2::asset::Asset;
+use crate::api::v2::asset::Symbol;
+use crate::api::v2::assets::AssetsReq;
+use crate::api::BROKER_API_BASE_URL;
+use crate::Str;
+
+
+Endpoint! {
+  /// The representation of a GET request to the /v1/assets endpoint.
+  pub List(AssetsReq),
+  Ok => Vec<Asset>, [
+    /// The list of assets was retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => ListError, []
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1/assets".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the /v1/assets/<symbol>
+  /// endpoint.
+  pub Get(Symbol),
+  Ok => Asset, [
+    /// The asset object for the given symbol was retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetError, [
+    /// No asset was found for the given symbol.
+    /* 404 */ NOT_FOUND => NotFound,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(input: &Self::Input) -> Str {
+    format!("/v1/assets/{}", input).into()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use http_endpoint::Endpoint as _;
+
+  use test_log::test;
+
+  use crate::api::v2::assets::AssetsReqInit;
+
+
+  /// Check that we create the expected paths and queries.
+  #[test]
+  fn endpoint_paths() {
+    let request = AssetsReqInit::default().init();
+    assert_eq!(List::path(&request), "/v1/assets");
+    assert_eq!(
+      List::query(&request).unwrap().unwrap(),
+      "status=active&asset_class=us_equity"
+    );
+
+    let symbol = Symbol::from_str("AAPL").unwrap();
+    assert_eq!(Get::path(&symbol), "/v1/assets/AAPL");
+  }
+}
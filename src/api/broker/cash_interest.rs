@@ -0,0 +1,384 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+#[cfg(feature = "extra-fields")]
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use http::Method;
+use http_endpoint::Bytes;
+
+use futures::TryStreamExt as _;
+
+use num_decimal::Num;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::from_slice as from_json;
+use serde_json::to_vec as to_json;
+#[cfg(feature = "extra-fields")]
+use serde_json::Value;
+use serde_urlencoded::to_string as to_query;
+
+use crate::api::v2::account;
+use crate::api::v2::account_activities;
+use crate::api::v2::account_activities::Activity;
+use crate::api::v2::account_activities::ActivityReq;
+use crate::api::v2::account_activities::ActivityType;
+use crate::api::v2::account_activities::Direction;
+use crate::api::BROKER_API_BASE_URL;
+use crate::Client;
+use crate::Paginated;
+use crate::RequestError;
+use crate::Str;
+
+
+/// A tier of a cash interest program, determining the annual
+/// percentage rate (APR) at which interest accrues on cash balances.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct AprTier {
+  /// The tier's name.
+  #[serde(rename = "name")]
+  pub name: String,
+  /// The annual percentage rate of the tier, in percent.
+  #[serde(rename = "apr")]
+  pub apr: Num,
+  /// Any fields reported by the API that are not (yet) modeled
+  /// explicitly, indexed by name.
+  #[cfg(feature = "extra-fields")]
+  #[serde(flatten, deserialize_with = "crate::schema::capture::<AprTier, _>")]
+  pub extra: BTreeMap<String, Value>,
+}
+
+
+/// The response of the /v1/cash_interest/apr_tiers endpoint.
+#[derive(Deserialize)]
+struct AprTiers {
+  /// The available tiers.
+  #[serde(rename = "apr_tiers")]
+  apr_tiers: Vec<AprTier>,
+}
+
+
+EndpointNoParse! {
+  /// The representation of a GET request to the
+  /// /v1/cash_interest/apr_tiers endpoint.
+  pub ListTiers(()),
+  Ok => Vec<AprTier>, [
+    /// The APR tiers were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => ListTiersError, []
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1/cash_interest/apr_tiers".into()
+  }
+
+  fn parse(body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
+    let tiers = from_json::<AprTiers>(body)?;
+    Ok(tiers.apr_tiers)
+  }
+
+  fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
+    from_json::<Self::ApiError>(body).map_err(|_| body.to_vec())
+  }
+}
+
+
+/// The body of a request enrolling an account in an APR tier.
+#[derive(Serialize)]
+struct TierReq<'n> {
+  /// The name of the tier.
+  #[serde(rename = "apr_tier_name")]
+  apr_tier_name: &'n str,
+}
+
+
+EndpointNoParse! {
+  /// The representation of a PATCH request to the
+  /// /v1/accounts/<account-id>/cash_interest endpoint, enrolling the
+  /// account in the APR tier with the given name.
+  pub PatchTier((account::Id, String)),
+  Ok => (), [
+    /// The account's APR tier was updated successfully.
+    /* 200 */ OK,
+    /// The account's APR tier was updated successfully.
+    /* 204 */ NO_CONTENT,
+  ],
+  Err => PatchTierError, [
+    /// No account was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+    /// The tier name is not valid.
+    /* 422 */ UNPROCESSABLE_ENTITY => InvalidInput,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn method() -> Method {
+    Method::PATCH
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    let (account_id, _) = input;
+    format!("/v1/accounts/{}/cash_interest", account_id.as_hyphenated()).into()
+  }
+
+  fn body(input: &Self::Input) -> Result<Option<Bytes>, Self::ConversionError> {
+    let (_, name) = input;
+    let json = to_json(&TierReq { apr_tier_name: name })?;
+    let bytes = Bytes::from(json);
+    Ok(Some(bytes))
+  }
+
+  #[inline]
+  fn parse(_body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
+    Ok(())
+  }
+
+  fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
+    from_json::<Self::ApiError>(body).map_err(|_| body.to_vec())
+  }
+}
+
+
+/// A GET request to be made to the /v1/accounts/activities endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ActivitiesReq {
+  /// The account to retrieve activities for.
+  #[serde(rename = "account_id")]
+  pub account_id: account::Id,
+  /// The filter and paging parameters, as used for the account's own
+  /// activities.
+  #[serde(flatten)]
+  pub activities: ActivityReq,
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the
+  /// /v1/accounts/activities endpoint.
+  pub ListActivities(ActivitiesReq),
+  Ok => Vec<Activity>, [
+    /// The activities were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => ListActivitiesError, []
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1/accounts/activities".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+}
+
+impl Paginated for ListActivities {
+  type Item = Activity;
+  type State = ();
+
+  fn paginate(
+    mut input: Self::Input,
+    output: Self::Output,
+    state: &mut Self::State,
+  ) -> (Vec<Self::Item>, Option<Self::Input>) {
+    let (items, next) = account_activities::Get::paginate(input.activities, output, state);
+    match next {
+      Some(activities) => {
+        input.activities = activities;
+        (items, Some(input))
+      },
+      None => (items, None),
+    }
+  }
+}
+
+
+/// The cash interest and rebates credited to an account.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Accrual {
+  /// The interest credited, net of any tax withheld.
+  pub interest: Num,
+  /// The rebates credited.
+  pub rebates: Num,
+}
+
+impl Accrual {
+  /// Accumulate the interest and rebates reported by the given
+  /// activities, ignoring all other activities.
+  pub fn from_activities<'a, I>(activities: I) -> Self
+  where
+    I: IntoIterator<Item = &'a Activity>,
+  {
+    let mut accrual = Self::default();
+    for activity in activities {
+      if let Activity::NonTrade(activity) = activity {
+        match activity.type_ {
+          ActivityType::Interest
+          | ActivityType::InterestAdjustedNraWithheld
+          | ActivityType::InterestAdjustedTefraWithheld => accrual.interest += &activity.net_amount,
+          ActivityType::PassThruRebate => accrual.rebates += &activity.net_amount,
+          _ => (),
+        }
+      }
+    }
+    accrual
+  }
+}
+
+
+/// Retrieve the cash interest and rebates credited to an account in
+/// the given time window.
+pub async fn accrual(
+  client: &Client,
+  account_id: account::Id,
+  window: Range<DateTime<Utc>>,
+) -> Result<Accrual, RequestError<ListActivitiesError>> {
+  let request = ActivitiesReq {
+    account_id,
+    activities: ActivityReq {
+      types: vec![
+        ActivityType::Interest,
+        ActivityType::InterestAdjustedNraWithheld,
+        ActivityType::InterestAdjustedTefraWithheld,
+        ActivityType::PassThruRebate,
+      ],
+      direction: Direction::Ascending,
+      until: Some(window.end),
+      after: Some(window.start),
+      ..Default::default()
+    },
+  };
+
+  let activities = client
+    .issue_paged::<ListActivities>(request)
+    .try_collect::<Vec<_>>()
+    .await?;
+  Ok(Accrual::from_activities(&activities))
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::from_utf8;
+  use std::str::FromStr as _;
+
+  use http_endpoint::Endpoint as _;
+
+  use serde_json::from_value;
+  use serde_json::json;
+
+  use test_log::test;
+
+  use uuid::Uuid;
+
+
+  /// Check that we can parse the APR tiers response.
+  #[test]
+  fn parse_apr_tiers() {
+    let body = br#"{"apr_tiers":[{"name":"standard","apr":"4.5","id":"3d4b4b59"}]}"#;
+    let tiers = ListTiers::parse(body).unwrap();
+    assert_eq!(tiers.len(), 1);
+    assert_eq!(tiers[0].name, "standard");
+    assert_eq!(tiers[0].apr, Num::new(45, 10));
+    #[cfg(feature = "extra-fields")]
+    assert_eq!(tiers[0].extra["id"], json!("3d4b4b59"));
+  }
+
+  /// Check that we serialize a tier change request as expected.
+  #[test]
+  fn serialize_patch_tier() {
+    let account_id = Uuid::from_str("b0b6dd9d-8b9b-48a9-ba46-b9d54906e415").unwrap();
+    let input = (account::Id(account_id), "premium".to_string());
+
+    assert_eq!(
+      PatchTier::path(&input),
+      "/v1/accounts/b0b6dd9d-8b9b-48a9-ba46-b9d54906e415/cash_interest"
+    );
+    let body = PatchTier::body(&input).unwrap().unwrap();
+    assert_eq!(from_utf8(&body).unwrap(), r#"{"apr_tier_name":"premium"}"#);
+  }
+
+  /// Check that we serialize an activities request for an account as
+  /// expected, including the parameters shared with the account's own
+  /// activities.
+  #[test]
+  fn serialize_list_activities() {
+    let account_id = Uuid::from_str("b0b6dd9d-8b9b-48a9-ba46-b9d54906e415").unwrap();
+    let input = ActivitiesReq {
+      account_id: account::Id(account_id),
+      activities: ActivityReq {
+        types: vec![ActivityType::Interest, ActivityType::PassThruRebate],
+        page_token: Some("20220701000000000::1".to_string()),
+        ..Default::default()
+      },
+    };
+
+    let query = ListActivities::query(&input).unwrap().unwrap();
+    assert_eq!(
+      query,
+      "account_id=b0b6dd9d-8b9b-48a9-ba46-b9d54906e415&activity_types=INT%2CPTR\
+       &direction=desc&page_token=20220701000000000%3A%3A1"
+    );
+  }
+
+  /// Check that we accumulate interest and rebates.
+  #[test]
+  fn accumulate_accrual() {
+    let activities = from_value::<Vec<Activity>>(json!([
+      {
+        "id": "20220701000000000::1",
+        "activity_type": "INT",
+        "date": "2022-07-01",
+        "net_amount": "1.25",
+      },
+      {
+        "id": "20220701000000000::2",
+        "activity_type": "INTNRA",
+        "date": "2022-07-01",
+        "net_amount": "-0.37",
+      },
+      {
+        "id": "20220701000000000::3",
+        "activity_type": "PTR",
+        "date": "2022-07-01",
+        "net_amount": "0.10",
+      },
+      {
+        "id": "20220701000000000::4",
+        "activity_type": "DIV",
+        "date": "2022-07-01",
+        "net_amount": "3.00",
+        "symbol": "T",
+      },
+    ]))
+    .unwrap();
+
+    let accrual = Accrual::from_activities(&activities);
+    assert_eq!(accrual.interest, Num::new(88, 100));
+    assert_eq!(accrual.rebates, Num::new(1, 10));
+  }
+}
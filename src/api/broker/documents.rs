@@ -0,0 +1,461 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::ops::Deref;
+
+use chrono::NaiveDate;
+
+use futures::Stream;
+
+use http::header::LOCATION;
+use http::Method;
+use http::StatusCode;
+use http_endpoint::Bytes;
+use http_endpoint::Endpoint as _;
+
+use hyper::body::to_bytes;
+use hyper::body::Bytes as BodyBytes;
+use hyper::Body;
+use hyper::Error as HyperError;
+use hyper::Response;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde::Serializer;
+use serde_json::to_vec as to_json;
+use serde_urlencoded::to_string as to_query;
+
+use uuid::Uuid;
+
+use crate::api::v2::account;
+use crate::api::BROKER_API_BASE_URL;
+use crate::Client;
+use crate::RequestError;
+use crate::Str;
+
+
+/// A type representing a document ID.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Id(pub Uuid);
+
+impl Deref for Id {
+  type Target = Uuid;
+
+  #[inline]
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+
+/// The type of a document provided by Alpaca.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[non_exhaustive]
+pub enum Type {
+  /// A periodic account statement.
+  #[serde(rename = "account_statement")]
+  AccountStatement,
+  /// A confirmation of a trade.
+  #[serde(rename = "trade_confirmation")]
+  TradeConfirmation,
+  /// The account application.
+  #[serde(rename = "account_application")]
+  AccountApplication,
+  /// A tax statement.
+  #[serde(rename = "tax_statement")]
+  TaxStatement,
+  /// The details for a 1099-B tax form.
+  #[serde(rename = "tax_1099_b_details")]
+  Tax1099BDetails,
+  /// A 1099-B tax form.
+  #[serde(rename = "tax_1099_b_form")]
+  Tax1099BForm,
+  /// The details for a 1099-DIV tax form.
+  #[serde(rename = "tax_1099_div_details")]
+  Tax1099DivDetails,
+  /// A 1099-DIV tax form.
+  #[serde(rename = "tax_1099_div_form")]
+  Tax1099DivForm,
+  /// The details for a 1099-INT tax form.
+  #[serde(rename = "tax_1099_int_details")]
+  Tax1099IntDetails,
+  /// A 1099-INT tax form.
+  #[serde(rename = "tax_1099_int_form")]
+  Tax1099IntForm,
+  /// A W-8 tax form.
+  #[serde(rename = "tax_w8")]
+  TaxW8,
+  /// Any other document type that we have not accounted for.
+  #[serde(other, skip_serializing)]
+  Unknown,
+}
+
+
+/// A document as returned by the
+/// /v1/accounts/<account-id>/documents endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Document {
+  /// The document's ID.
+  #[serde(rename = "id")]
+  pub id: Id,
+  /// The document's name.
+  #[serde(rename = "name", default)]
+  pub name: String,
+  /// The type of document.
+  #[serde(rename = "type")]
+  pub type_: Type,
+  /// The sub-type of the document, if any.
+  #[serde(rename = "sub_type", default)]
+  pub sub_type: Option<String>,
+  /// The date the document refers to.
+  #[serde(rename = "date")]
+  pub date: NaiveDate,
+}
+
+
+/// A GET request to be made to the /v1/accounts/<account-id>/documents
+/// endpoint.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+pub struct ListReq {
+  /// The type of documents to retrieve. If not set, documents of all
+  /// types are retrieved.
+  #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+  pub type_: Option<Type>,
+  /// The (inclusive) start of the date range to retrieve documents
+  /// for.
+  #[serde(rename = "start_date", skip_serializing_if = "Option::is_none")]
+  pub start: Option<NaiveDate>,
+  /// The (inclusive) end of the date range to retrieve documents for.
+  #[serde(rename = "end_date", skip_serializing_if = "Option::is_none")]
+  pub end: Option<NaiveDate>,
+  #[doc(hidden)]
+  #[serde(skip)]
+  pub _non_exhaustive: (),
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the
+  /// /v1/accounts/<account-id>/documents endpoint.
+  pub List((account::Id, ListReq)),
+  Ok => Vec<Document>, [
+    /// The documents were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => ListError, [
+    /// No account was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    let (account_id, _) = input;
+    format!("/v1/accounts/{}/documents", account_id.as_hyphenated()).into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    let (_, request) = input;
+    Ok(Some(to_query(request)?.into()))
+  }
+}
+
+
+/// The type of a document uploaded for an account.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[non_exhaustive]
+pub enum UploadType {
+  /// A document verifying the account owner's identity.
+  #[serde(rename = "identity_verification")]
+  IdentityVerification,
+  /// A document verifying the account owner's address.
+  #[serde(rename = "address_verification")]
+  AddressVerification,
+  /// A document verifying the account owner's date of birth.
+  #[serde(rename = "date_of_birth_verification")]
+  DateOfBirthVerification,
+  /// A document verifying the account owner's tax ID.
+  #[serde(rename = "tax_id_verification")]
+  TaxIdVerification,
+  /// A letter approving the account.
+  #[serde(rename = "account_approval_letter")]
+  AccountApprovalLetter,
+  /// A W-8BEN tax form.
+  #[serde(rename = "w8ben")]
+  W8Ben,
+  /// The result of a customer identification program check.
+  #[serde(rename = "cip_result")]
+  CipResult,
+}
+
+
+/// Serialize binary data as base64 encoded string.
+fn to_base64<S>(content: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+where
+  S: Serializer,
+{
+  serializer.serialize_str(&base64::encode(content))
+}
+
+
+/// A document to upload as part of a POST request to the
+/// /v1/accounts/<account-id>/documents/upload endpoint.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct Upload {
+  /// The type of document.
+  #[serde(rename = "document_type")]
+  pub type_: UploadType,
+  /// The sub-type of the document, e.g., `passport`.
+  #[serde(rename = "document_sub_type", skip_serializing_if = "Option::is_none")]
+  pub sub_type: Option<String>,
+  /// The document's content.
+  #[serde(rename = "content", serialize_with = "to_base64")]
+  pub content: Vec<u8>,
+  /// The document's MIME type, e.g., `application/pdf`.
+  #[serde(rename = "mime_type")]
+  pub mime_type: String,
+}
+
+
+Endpoint! {
+  /// The representation of a POST request to the
+  /// /v1/accounts/<account-id>/documents/upload endpoint.
+  pub Post((account::Id, Vec<Upload>)),
+  Ok => (), [
+    /// The documents were uploaded successfully.
+    /* 204 */ NO_CONTENT,
+  ],
+  Err => PostError, [
+    /// Some of the provided data was invalid.
+    /* 400 */ BAD_REQUEST => InvalidInput,
+    /// No account was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn method() -> Method {
+    Method::POST
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    let (account_id, _) = input;
+    format!("/v1/accounts/{}/documents/upload", account_id.as_hyphenated()).into()
+  }
+
+  fn body(input: &Self::Input) -> Result<Option<Bytes>, Self::ConversionError> {
+    let (_, uploads) = input;
+    let json = to_json(uploads)?;
+    let bytes = Bytes::from(json);
+    Ok(Some(bytes))
+  }
+}
+
+
+EndpointNoParse! {
+  /// The representation of a GET request to the
+  /// /v1/accounts/<account-id>/documents/<document-id>/download
+  /// endpoint.
+  ///
+  /// The endpoint redirects to the document's content, which may be
+  /// large. Use [`download`] for streaming it.
+  pub Download((account::Id, Id)),
+  Ok => (), [
+    /// The document's content was retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => DownloadError, [
+    /// No account or document was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    let (account_id, document_id) = input;
+    format!(
+      "/v1/accounts/{}/documents/{}/download",
+      account_id.as_hyphenated(),
+      document_id.as_hyphenated()
+    )
+    .into()
+  }
+
+  #[inline]
+  fn parse(_body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
+    Ok(())
+  }
+
+  fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
+    ::serde_json::from_slice::<Self::ApiError>(body).map_err(|_| body.to_vec())
+  }
+}
+
+
+/// Retrieve the body of a response to a download request, or the error
+/// it reports.
+async fn body(response: Response<Body>) -> Result<Body, RequestError<DownloadError>> {
+  let status = response.status();
+  if status == StatusCode::OK {
+    return Ok(response.into_body())
+  }
+
+  let bytes = to_bytes(response.into_body()).await?;
+  let error = DownloadError::UnexpectedStatus(status, Err(bytes.to_vec()));
+  let result = Download::evaluate(status, &bytes).and(Err(error));
+  result.map_err(RequestError::Endpoint)
+}
+
+
+/// Download the content of a document, e.g., a PDF file.
+///
+/// The content is provided as a stream of chunks, so that it does not
+/// have to be held in memory in its entirety.
+pub async fn download(
+  client: &Client,
+  account_id: account::Id,
+  document_id: Id,
+) -> Result<impl Stream<Item = Result<BodyBytes, HyperError>>, RequestError<DownloadError>> {
+  let response = client
+    .issue_stream::<Download>(&(account_id, document_id))
+    .await?;
+
+  if response.status().is_redirection() {
+    let location = response
+      .headers()
+      .get(LOCATION)
+      .and_then(|location| location.to_str().ok());
+
+    if let Some(location) = location {
+      // The content is typically served from a pre-signed URL, which
+      // must not be sent our credentials.
+      let response = client.get_stream::<DownloadError>(location).await?;
+      return body(response).await
+    }
+  }
+  body(response).await
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use futures::TryStreamExt as _;
+
+  use serde_json::from_str as from_json;
+  use serde_json::to_string as to_json_string;
+
+  use test_log::test;
+
+  use crate::api::API_BASE_URL;
+  use crate::api_info::ApiInfo;
+  use crate::test_util::MockServer;
+  use crate::test_util::Response;
+
+
+  /// Check that we can deserialize a reference document.
+  #[test]
+  fn deserialize_reference_document() {
+    let json = r#"{
+  "id": "f2d6c2a9-96f9-4a3b-9a6e-7e3a7a6b3a11",
+  "name": "Account Statement - Jan 2022",
+  "type": "account_statement",
+  "sub_type": "",
+  "date": "2022-01-31"
+}"#;
+
+    let document = from_json::<Document>(json).unwrap();
+    assert_eq!(document.type_, Type::AccountStatement);
+    assert_eq!(document.name, "Account Statement - Jan 2022");
+    assert_eq!(document.date, NaiveDate::from_ymd_opt(2022, 1, 31).unwrap());
+
+    let json =
+      r#"{"id":"f2d6c2a9-96f9-4a3b-9a6e-7e3a7a6b3a11","type":"tax_1042s","date":"2022-01-31"}"#;
+    let document = from_json::<Document>(json).unwrap();
+    assert_eq!(document.type_, Type::Unknown);
+  }
+
+  /// Check that we serialize a document list request as expected.
+  #[test]
+  fn serialize_list_request() {
+    let request = ListReq {
+      type_: Some(Type::TradeConfirmation),
+      start: NaiveDate::from_ymd_opt(2022, 1, 1),
+      ..Default::default()
+    };
+    assert_eq!(
+      to_query(request).unwrap(),
+      "type=trade_confirmation&start_date=2022-01-01"
+    );
+  }
+
+  /// Check that document contents get base64 encoded for upload.
+  #[test]
+  fn serialize_upload() {
+    let upload = Upload {
+      type_: UploadType::IdentityVerification,
+      sub_type: Some("passport".to_string()),
+      content: b"%PDF".to_vec(),
+      mime_type: "application/pdf".to_string(),
+    };
+
+    let json = to_json_string(&upload).unwrap();
+    let expected = r#"{"document_type":"identity_verification","document_sub_type":"passport","content":"JVBERg==","mime_type":"application/pdf"}"#;
+    assert_eq!(json, expected);
+  }
+
+  /// Check that we follow the redirect of a download request without
+  /// sending along credentials and stream the document's content.
+  #[test(tokio::test)]
+  async fn download_document() {
+    let content = "%PDF-1.4 ...";
+    let server = MockServer::start(2, move |request| {
+      let response = if request.line.contains("/download ") {
+        let location = format!("http://{}/document.pdf", request.header("host").unwrap());
+        Response::new(StatusCode::MOVED_PERMANENTLY).header("location", location)
+      } else {
+        Response::new(StatusCode::OK)
+          .header("content-type", "application/pdf")
+          .body(content)
+      };
+      Some(response)
+    });
+
+    let api_info = ApiInfo::from_parts(API_BASE_URL, "key", "secret")
+      .unwrap()
+      .with_broker_base_url(server.url())
+      .unwrap();
+    let client = Client::new(api_info);
+    let account_id = account::Id(Uuid::from_str("b0b6dd9d-8b9b-48a9-ba46-b9d54906e415").unwrap());
+    let document_id = Id(Uuid::from_str("f2d6c2a9-96f9-4a3b-9a6e-7e3a7a6b3a11").unwrap());
+
+    let stream = download(&client, account_id, document_id).await.unwrap();
+    let chunks = stream.try_collect::<Vec<_>>().await.unwrap();
+    assert_eq!(chunks.concat(), content.as_bytes());
+
+    let requests = server.join();
+    assert!(requests[0].line.starts_with(
+      "GET /v1/accounts/b0b6dd9d-8b9b-48a9-ba46-b9d54906e415/documents/f2d6c2a9-96f9-4a3b-9a6e-7e3a7a6b3a11/download"
+    ));
+    assert!(requests[0]
+      .header("authorization")
+      .unwrap()
+      .starts_with("Basic"));
+    assert!(requests[1].line.starts_with("GET /document.pdf"));
+    assert_eq!(requests[1].header("authorization"), None);
+  }
+}
@@ -0,0 +1,515 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+#[cfg(feature = "extra-fields")]
+use std::collections::BTreeMap;
+use std::ops::Deref;
+
+use chrono::DateTime;
+use chrono::NaiveDate;
+use chrono::Utc;
+
+use http::Method;
+use http_endpoint::Bytes;
+
+use num_decimal::Num;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::to_vec as to_json;
+#[cfg(feature = "extra-fields")]
+use serde_json::Value;
+use serde_urlencoded::to_string as to_query;
+
+use uuid::Uuid;
+
+use crate::api::BROKER_API_BASE_URL;
+use crate::util::datetime_from_str;
+use crate::util::optional_num_from_str_or_null;
+use crate::util::string_slice_to_str;
+use crate::Str;
+
+
+/// An ID uniquely identifying an instant funding transfer.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Id(pub Uuid);
+
+impl Deref for Id {
+  type Target = Uuid;
+
+  #[inline]
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+
+/// An ID uniquely identifying a settlement of instant funding
+/// transfers.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct SettlementId(pub Uuid);
+
+impl Deref for SettlementId {
+  type Target = Uuid;
+
+  #[inline]
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+
+/// The status of an instant funding transfer or a settlement.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[non_exhaustive]
+pub enum Status {
+  /// The transfer or settlement is pending.
+  #[serde(rename = "PENDING")]
+  Pending,
+  /// The transfer or settlement has been executed.
+  #[serde(rename = "EXECUTED")]
+  Executed,
+  /// The transfer or settlement has been completed.
+  #[serde(rename = "COMPLETED")]
+  Completed,
+  /// The transfer has been canceled.
+  #[serde(rename = "CANCELED")]
+  Canceled,
+  /// The transfer or settlement failed.
+  #[serde(rename = "FAILED")]
+  Failed,
+  /// Any other status that we have not accounted for.
+  #[serde(other, skip_serializing)]
+  Unknown,
+}
+
+
+/// An instant funding transfer, crediting buying power to an account
+/// ahead of the settlement of the actual funds.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Transfer {
+  /// The transfer's ID.
+  #[serde(rename = "id")]
+  pub id: Id,
+  /// The number of the account credited.
+  #[serde(rename = "account_no")]
+  pub account_no: String,
+  /// The number of the account the funds are provided by, typically
+  /// the broker partner's settlement account.
+  #[serde(rename = "source_account_no")]
+  pub source_account_no: String,
+  /// The amount credited.
+  #[serde(rename = "amount")]
+  pub amount: Num,
+  /// The amount that still needs to be settled.
+  #[serde(
+    rename = "remaining_payable",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
+  pub remaining_payable: Option<Num>,
+  /// The total interest charged for the transfer so far.
+  #[serde(
+    rename = "total_interest",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
+  pub total_interest: Option<Num>,
+  /// The transfer's status.
+  #[serde(rename = "status")]
+  pub status: Status,
+  /// The business date of the transfer.
+  #[serde(rename = "system_date", default)]
+  pub system_date: Option<NaiveDate>,
+  /// The date by which the transfer needs to be settled.
+  #[serde(rename = "deadline", default)]
+  pub deadline: Option<NaiveDate>,
+  /// The time the transfer was created at.
+  #[serde(rename = "created_at", deserialize_with = "datetime_from_str")]
+  pub created_at: DateTime<Utc>,
+  /// Any fields reported by the API that are not (yet) modeled
+  /// explicitly, indexed by name.
+  #[cfg(feature = "extra-fields")]
+  #[serde(flatten, deserialize_with = "crate::schema::capture::<Transfer, _>")]
+  pub extra: BTreeMap<String, Value>,
+}
+
+
+/// A POST request to be made to the /v1/instant_funding endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TransferReq {
+  /// The number of the account to credit.
+  #[serde(rename = "account_no")]
+  pub account_no: String,
+  /// The number of the account providing the funds.
+  #[serde(rename = "source_account_no")]
+  pub source_account_no: String,
+  /// The amount to credit.
+  #[serde(rename = "amount")]
+  pub amount: Num,
+}
+
+
+Endpoint! {
+  /// The representation of a POST request to the /v1/instant_funding
+  /// endpoint.
+  pub Post(TransferReq),
+  Ok => Transfer, [
+    /// The transfer was created successfully.
+    /* 200 */ OK,
+  ],
+  Err => PostError, [
+    /// Some of the provided data was invalid, e.g., the amount exceeds
+    /// the available limit.
+    /* 400 */ BAD_REQUEST => InvalidInput,
+    /// The transfer is not permitted, e.g., because instant funding is
+    /// not enabled for the broker partner.
+    /* 403 */ FORBIDDEN => NotPermitted,
+    /// Some of the provided data was invalid.
+    /* 422 */ UNPROCESSABLE_ENTITY => Unprocessable,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn method() -> Method {
+    Method::POST
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1/instant_funding".into()
+  }
+
+  fn body(input: &Self::Input) -> Result<Option<Bytes>, Self::ConversionError> {
+    let json = to_json(input)?;
+    let bytes = Bytes::from(json);
+    Ok(Some(bytes))
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the
+  /// /v1/instant_funding/<instant-funding-id> endpoint.
+  pub Get(Id),
+  Ok => Transfer, [
+    /// The transfer was retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetError, [
+    /// No transfer was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    format!("/v1/instant_funding/{}", input.as_hyphenated()).into()
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a DELETE request to the
+  /// /v1/instant_funding/<instant-funding-id> endpoint.
+  pub Delete(Id),
+  Ok => (), [
+    /// The transfer was canceled successfully.
+    /* 204 */ NO_CONTENT,
+  ],
+  Err => DeleteError, [
+    /// No transfer was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+    /// The transfer can no longer be canceled.
+    /* 422 */ UNPROCESSABLE_ENTITY => NotCancelable,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn method() -> Method {
+    Method::DELETE
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    format!("/v1/instant_funding/{}", input.as_hyphenated()).into()
+  }
+}
+
+
+/// The instant funding limits of a broker partner or an account.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Limits {
+  /// The number of the account the limits apply to, if they are
+  /// account specific.
+  #[serde(rename = "account_no", default)]
+  pub account_no: Option<String>,
+  /// The total amount that can be extended.
+  #[serde(rename = "amount_limit")]
+  pub amount_limit: Num,
+  /// The amount currently extended and not yet settled.
+  #[serde(rename = "amount_in_use")]
+  pub amount_in_use: Num,
+  /// The amount still available for instant funding.
+  #[serde(rename = "amount_available")]
+  pub amount_available: Num,
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the
+  /// /v1/instant_funding/limits endpoint, retrieving the limits of the
+  /// broker partner.
+  pub GetLimits(()),
+  Ok => Limits, [
+    /// The limits were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetLimitsError, []
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1/instant_funding/limits".into()
+  }
+}
+
+
+/// A GET request to be made to the
+/// /v1/accounts/instant_funding_limits endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AccountLimitsReq {
+  /// The numbers of the accounts to retrieve limits for.
+  #[serde(rename = "account_numbers", serialize_with = "string_slice_to_str")]
+  pub account_numbers: Vec<String>,
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the
+  /// /v1/accounts/instant_funding_limits endpoint, retrieving the
+  /// limits of individual accounts.
+  pub ListAccountLimits(AccountLimitsReq),
+  Ok => Vec<Limits>, [
+    /// The limits were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => ListAccountLimitsError, []
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1/accounts/instant_funding_limits".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+}
+
+
+/// A settlement of instant funding transfers.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Settlement {
+  /// The settlement's ID.
+  #[serde(rename = "id")]
+  pub id: SettlementId,
+  /// The number of the account the settled funds are provided by.
+  #[serde(rename = "source_account_no")]
+  pub source_account_no: String,
+  /// The total amount settled.
+  #[serde(rename = "total_amount")]
+  pub total_amount: Num,
+  /// The settlement's status.
+  #[serde(rename = "status")]
+  pub status: Status,
+  /// The time the settlement was created at.
+  #[serde(rename = "created_at", deserialize_with = "datetime_from_str")]
+  pub created_at: DateTime<Utc>,
+  /// Any fields reported by the API that are not (yet) modeled
+  /// explicitly, indexed by name.
+  #[cfg(feature = "extra-fields")]
+  #[serde(flatten, deserialize_with = "crate::schema::capture::<Settlement, _>")]
+  pub extra: BTreeMap<String, Value>,
+}
+
+
+/// A POST request to be made to the /v1/instant_funding/settlements
+/// endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SettlementReq {
+  /// The number of the account providing the funds.
+  #[serde(rename = "source_account_no")]
+  pub source_account_no: String,
+  /// The total amount to settle, which has to match the sum of the
+  /// remaining payable amounts of the transfers.
+  #[serde(rename = "total_amount")]
+  pub total_amount: Num,
+  /// The transfers to settle.
+  #[serde(rename = "instant_transfers")]
+  pub transfers: Vec<Id>,
+}
+
+
+Endpoint! {
+  /// The representation of a POST request to the
+  /// /v1/instant_funding/settlements endpoint.
+  pub PostSettlement(SettlementReq),
+  Ok => Settlement, [
+    /// The settlement was created successfully.
+    /* 200 */ OK,
+  ],
+  Err => PostSettlementError, [
+    /// Some of the provided data was invalid, e.g., the total amount
+    /// does not match the transfers.
+    /* 400 */ BAD_REQUEST => InvalidInput,
+    /// Some of the provided data was invalid.
+    /* 422 */ UNPROCESSABLE_ENTITY => Unprocessable,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn method() -> Method {
+    Method::POST
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1/instant_funding/settlements".into()
+  }
+
+  fn body(input: &Self::Input) -> Result<Option<Bytes>, Self::ConversionError> {
+    let json = to_json(input)?;
+    let bytes = Bytes::from(json);
+    Ok(Some(bytes))
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the
+  /// /v1/instant_funding/settlements/<settlement-id> endpoint.
+  pub GetSettlement(SettlementId),
+  Ok => Settlement, [
+    /// The settlement was retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetSettlementError, [
+    /// No settlement was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    format!("/v1/instant_funding/settlements/{}", input.as_hyphenated()).into()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::from_utf8;
+  use std::str::FromStr as _;
+
+  use http_endpoint::Endpoint as _;
+
+  use serde_json::from_str;
+
+  use test_log::test;
+
+
+  /// Check that we can deserialize a reference transfer.
+  #[test]
+  fn deserialize_reference_transfer() {
+    let json = r#"{
+  "id": "a7c8a6b0-2f2a-4a8e-9d9c-1c6f4a0f2e4d",
+  "account_no": "920964623",
+  "source_account_no": "SI1234",
+  "amount": "20",
+  "remaining_payable": "20",
+  "total_interest": "0",
+  "status": "EXECUTED",
+  "system_date": "2024-05-21",
+  "deadline": "2024-05-23",
+  "created_at": "2024-05-21T10:39:15.000000Z",
+  "fees": [],
+  "interests": []
+}"#;
+
+    let transfer = from_str::<Transfer>(json).unwrap();
+    assert_eq!(transfer.account_no, "920964623");
+    assert_eq!(transfer.amount, Num::from(20));
+    assert_eq!(transfer.status, Status::Executed);
+    assert_eq!(
+      transfer.deadline,
+      Some(NaiveDate::from_ymd_opt(2024, 5, 23).unwrap())
+    );
+    #[cfg(feature = "extra-fields")]
+    assert!(transfer.extra.contains_key("fees"));
+  }
+
+  /// Check that we can deserialize instant funding limits.
+  #[test]
+  fn deserialize_limits() {
+    let json = r#"{"amount_available":"750","amount_in_use":"250","amount_limit":"1000"}"#;
+    let limits = from_str::<Limits>(json).unwrap();
+    assert_eq!(limits.account_no, None);
+    assert_eq!(limits.amount_available, Num::from(750));
+    assert_eq!(limits.amount_limit, Num::from(1000));
+  }
+
+  /// Check that we serialize requests as expected.
+  #[test]
+  fn serialize_requests() {
+    let request = AccountLimitsReq {
+      account_numbers: vec!["920964623".to_string(), "920964624".to_string()],
+    };
+    assert_eq!(
+      ListAccountLimits::query(&request).unwrap().unwrap(),
+      "account_numbers=920964623%2C920964624"
+    );
+
+    let id = Id(Uuid::from_str("a7c8a6b0-2f2a-4a8e-9d9c-1c6f4a0f2e4d").unwrap());
+    let request = SettlementReq {
+      source_account_no: "SI1234".to_string(),
+      total_amount: Num::from(20),
+      transfers: vec![id],
+    };
+    let body = PostSettlement::body(&request).unwrap().unwrap();
+    assert_eq!(
+      from_utf8(&body).unwrap(),
+      r#"{"source_account_no":"SI1234","total_amount":"20","instant_transfers":["a7c8a6b0-2f2a-4a8e-9d9c-1c6f4a0f2e4d"]}"#
+    );
+  }
+}
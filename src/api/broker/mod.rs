@@ -0,0 +1,16 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+/// Definitions pertaining assets tradable through the Broker API.
+pub mod assets;
+/// Definitions pertaining cash interest programs and the interest
+/// and rebates credited to accounts.
+pub mod cash_interest;
+/// Definitions pertaining documents of accounts, e.g., statements,
+/// trade confirmations, and tax forms.
+pub mod documents;
+/// Definitions pertaining instant funding of accounts and its
+/// settlement.
+pub mod instant_funding;
+/// Definitions pertaining watchlists of accounts.
+pub mod watchlists;
@@ -0,0 +1,328 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use http::Method;
+use http_endpoint::Bytes;
+
+use serde::Serialize;
+use serde_json::to_vec as to_json;
+
+use crate::api::v2::account;
+use crate::api::v2::watchlist;
+use crate::api::v2::watchlist::CreateReq;
+use crate::api::v2::watchlist::Watchlist;
+use crate::api::v2::watchlists::WatchlistItem;
+use crate::api::BROKER_API_BASE_URL;
+use crate::Str;
+
+
+/// Create the path to the watchlists of the given account.
+fn watchlists_path(account_id: &account::Id) -> String {
+  format!(
+    "/v1/trading/accounts/{}/watchlists",
+    account_id.as_hyphenated()
+  )
+}
+
+/// Create the path to a watchlist of the given account.
+fn watchlist_path(account_id: &account::Id, watchlist_id: &watchlist::Id) -> String {
+  format!(
+    "{}/{}",
+    watchlists_path(account_id),
+    watchlist_id.as_hyphenated()
+  )
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the
+  /// /v1/trading/accounts/<account-id>/watchlists endpoint.
+  pub List(account::Id),
+  Ok => Vec<WatchlistItem>, [
+    /// The list of watchlists was retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => ListError, [
+    /// No account was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    watchlists_path(input).into()
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a POST request to the
+  /// /v1/trading/accounts/<account-id>/watchlists endpoint.
+  pub Post((account::Id, CreateReq)),
+  Ok => Watchlist, [
+    /// The watchlist was created successfully.
+    /* 200 */ OK,
+  ],
+  Err => PostError, [
+    /// No account was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+    /// The watchlist name was not unique or other parts of the input
+    /// are not valid.
+    /* 422 */ UNPROCESSABLE_ENTITY => InvalidInput,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn method() -> Method {
+    Method::POST
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    let (account_id, _) = input;
+    watchlists_path(account_id).into()
+  }
+
+  fn body(input: &Self::Input) -> Result<Option<Bytes>, Self::ConversionError> {
+    let (_, request) = input;
+    let json = to_json(request)?;
+    let bytes = Bytes::from(json);
+    Ok(Some(bytes))
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the
+  /// /v1/trading/accounts/<account-id>/watchlists/<watchlist-id>
+  /// endpoint.
+  pub Get((account::Id, watchlist::Id)),
+  Ok => Watchlist, [
+    /// The watchlist with the given ID was retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetError, [
+    /// No account or watchlist was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    let (account_id, watchlist_id) = input;
+    watchlist_path(account_id, watchlist_id).into()
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a PUT request to the
+  /// /v1/trading/accounts/<account-id>/watchlists/<watchlist-id>
+  /// endpoint, replacing the watchlist's name and symbols.
+  pub Put((account::Id, watchlist::Id, CreateReq)),
+  Ok => Watchlist, [
+    /// The watchlist was updated successfully.
+    /* 200 */ OK,
+  ],
+  Err => PutError, [
+    /// No account or watchlist was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+    /// The watchlist name was not unique or other parts of the input
+    /// are not valid.
+    /* 422 */ UNPROCESSABLE_ENTITY => InvalidInput,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn method() -> Method {
+    Method::PUT
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    let (account_id, watchlist_id, _) = input;
+    watchlist_path(account_id, watchlist_id).into()
+  }
+
+  fn body(input: &Self::Input) -> Result<Option<Bytes>, Self::ConversionError> {
+    let (_, _, request) = input;
+    let json = to_json(request)?;
+    let bytes = Bytes::from(json);
+    Ok(Some(bytes))
+  }
+}
+
+
+/// The body of a request adding a symbol to a watchlist.
+#[derive(Serialize)]
+struct SymbolReq<'s> {
+  /// The symbol to add.
+  #[serde(rename = "symbol")]
+  symbol: &'s str,
+}
+
+
+Endpoint! {
+  /// The representation of a POST request to the
+  /// /v1/trading/accounts/<account-id>/watchlists/<watchlist-id>
+  /// endpoint, adding a symbol to the watchlist.
+  pub PostSymbol((account::Id, watchlist::Id, String)),
+  Ok => Watchlist, [
+    /// The symbol was added successfully.
+    /* 200 */ OK,
+  ],
+  Err => PostSymbolError, [
+    /// No account or watchlist was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+    /// The symbol is not valid.
+    /* 422 */ UNPROCESSABLE_ENTITY => InvalidInput,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn method() -> Method {
+    Method::POST
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    let (account_id, watchlist_id, _) = input;
+    watchlist_path(account_id, watchlist_id).into()
+  }
+
+  fn body(input: &Self::Input) -> Result<Option<Bytes>, Self::ConversionError> {
+    let (_, _, symbol) = input;
+    let json = to_json(&SymbolReq { symbol })?;
+    let bytes = Bytes::from(json);
+    Ok(Some(bytes))
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a DELETE request to the
+  /// /v1/trading/accounts/<account-id>/watchlists/<watchlist-id>/<symbol>
+  /// endpoint, removing a symbol from the watchlist.
+  pub DeleteSymbol((account::Id, watchlist::Id, String)),
+  Ok => Watchlist, [
+    /// The symbol was removed successfully.
+    /* 200 */ OK,
+  ],
+  Err => DeleteSymbolError, [
+    /// No account or watchlist was found with the given ID or the
+    /// symbol is not part of the watchlist.
+    /* 404 */ NOT_FOUND => NotFound,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn method() -> Method {
+    Method::DELETE
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    let (account_id, watchlist_id, symbol) = input;
+    format!("{}/{}", watchlist_path(account_id, watchlist_id), symbol).into()
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a DELETE request to the
+  /// /v1/trading/accounts/<account-id>/watchlists/<watchlist-id>
+  /// endpoint.
+  pub Delete((account::Id, watchlist::Id)),
+  Ok => (), [
+    /// The watchlist was deleted successfully.
+    /* 204 */ NO_CONTENT,
+  ],
+  Err => DeleteError, [
+    /// No account or watchlist was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+  ]
+
+  #[inline]
+  fn base_url() -> Option<Str> {
+    Some(BROKER_API_BASE_URL.into())
+  }
+
+  #[inline]
+  fn method() -> Method {
+    Method::DELETE
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    let (account_id, watchlist_id) = input;
+    watchlist_path(account_id, watchlist_id).into()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::from_utf8;
+  use std::str::FromStr as _;
+
+  use http_endpoint::Endpoint as _;
+
+  use test_log::test;
+
+  use uuid::Uuid;
+
+
+  /// Create the IDs of an account and a watchlist.
+  fn ids() -> (account::Id, watchlist::Id) {
+    let account_id = Uuid::from_str("b0b6dd9d-8b9b-48a9-ba46-b9d54906e415").unwrap();
+    let watchlist_id = Uuid::from_str("3174d6df-7726-44b4-a5bd-7fda5ae6e009").unwrap();
+    (account::Id(account_id), watchlist::Id(watchlist_id))
+  }
+
+
+  /// Check that we create the expected paths for the various
+  /// endpoints.
+  #[test]
+  fn endpoint_paths() {
+    let (account_id, watchlist_id) = ids();
+    let base = "/v1/trading/accounts/b0b6dd9d-8b9b-48a9-ba46-b9d54906e415/watchlists";
+    let watchlist = format!("{}/3174d6df-7726-44b4-a5bd-7fda5ae6e009", base);
+
+    assert_eq!(List::path(&account_id), base);
+    assert_eq!(Get::path(&(account_id, watchlist_id)), watchlist);
+    assert_eq!(Delete::path(&(account_id, watchlist_id)), watchlist);
+    assert_eq!(
+      DeleteSymbol::path(&(account_id, watchlist_id, "AAPL".to_string())),
+      format!("{}/AAPL", watchlist)
+    );
+  }
+
+  /// Check that we serialize the body of a request adding a symbol to
+  /// a watchlist as expected.
+  #[test]
+  fn serialize_post_symbol() {
+    let (account_id, watchlist_id) = ids();
+    let input = (account_id, watchlist_id, "AAPL".to_string());
+    let body = PostSymbol::body(&input).unwrap().unwrap();
+    assert_eq!(from_utf8(&body).unwrap(), r#"{"symbol":"AAPL"}"#);
+  }
+}
@@ -1,11 +1,15 @@
 // Copyright (C) 2019-2020 The apca Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+/// Definitions for the Alpaca Broker API.
+pub mod broker;
 /// Definitions for the second version of the Alpaca API.
 pub mod v2;
 
 /// The API base URL used for paper trading.
 pub(crate) const API_BASE_URL: &str = "https://paper-api.alpaca.markets";
+/// The API base URL used for the Broker API sandbox.
+pub(crate) const BROKER_API_BASE_URL: &str = "https://broker-api.sandbox.alpaca.markets";
 /// The HTTP header representing the key ID.
 pub(crate) const HDR_KEY_ID: &str = "APCA-API-KEY-ID";
 /// The HTTP header representing the secret key.
@@ -33,6 +33,8 @@ use websocket_util::wrap::Wrapper;
 
 use crate::api::v2::order;
 use crate::api_info::ApiInfo;
+use crate::data::v2::stream::StreamError;
+use crate::metric;
 use crate::subscribable::Subscribable;
 use crate::websocket::connect;
 use crate::websocket::MessageResult;
@@ -299,7 +301,7 @@ where
       Some(response) => match response {
         Ok(ControlMessage::AuthenticationMessage(authentication)) => {
           if authentication.status != AuthenticationStatus::Authorized {
-            return Ok(Err(Error::Str("authentication not successful".into())))
+            return Ok(Err(Error::Stream(StreamError::AuthFailed)))
           }
           Ok(Ok(()))
         },
@@ -367,9 +369,12 @@ impl Subscribable for OrderUpdates {
 
   async fn connect(api_info: &Self::Input) -> Result<(Self::Stream, Self::Subscription), Error> {
     fn map(result: Result<wrap::Message, WebSocketError>) -> ParsedMessage {
-      MessageResult::from(result.map(|message| match message {
-        wrap::Message::Text(string) => json_from_str::<OrderMessage>(&string),
-        wrap::Message::Binary(data) => json_from_slice::<OrderMessage>(&data),
+      MessageResult::from(result.map(|message| {
+        metric::stream_messages("crypto_trade_updates", "message", 1);
+        match message {
+          wrap::Message::Text(string) => json_from_str::<OrderMessage>(&string),
+          wrap::Message::Binary(data) => json_from_slice::<OrderMessage>(&data),
+        }
       }))
     }
 
@@ -432,6 +437,7 @@ mod tests {
   use crate::api::v2::order;
   use crate::api::v2::order_util::order_aapl;
   use crate::api::API_BASE_URL;
+  use crate::live;
   use crate::websocket::test::mock_stream;
   use crate::Client;
   use crate::Error;
@@ -731,13 +737,13 @@ mod tests {
   /// Test the end-to-end workflow of streaming an order update for a
   /// newly created order.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn stream_order_events() {
     // TODO: There may be something amiss here. If we don't cancel the
     //       order we never get an event about a new order. That does
     //       not seem to be in our code, though, as the behavior is the
     //       same when streaming events using Alpaca's Python client.
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let (stream, _subscription) = client.subscribe::<OrderUpdates>().await.unwrap();
 
     let order = order_aapl(&client).await.unwrap();
@@ -769,6 +775,7 @@ mod tests {
   /// Test that we fail as expected when attempting to authenticate for
   /// order updates using invalid credentials.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn stream_with_invalid_credentials() {
     let api_info = ApiInfo::from_parts(API_BASE_URL, "invalid", "invalid-too").unwrap();
 
@@ -776,7 +783,7 @@ mod tests {
     let err = client.subscribe::<OrderUpdates>().await.unwrap_err();
 
     match err {
-      Error::Str(ref e) if e == "authentication not successful" => (),
+      Error::Stream(StreamError::AuthFailed) => (),
       e => panic!("received unexpected error: {}", e),
     }
   }
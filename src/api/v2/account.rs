@@ -1,6 +1,8 @@
 // Copyright (C) 2019-2022 The apca Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+#[cfg(feature = "extra-fields")]
+use std::collections::BTreeMap;
 use std::ops::Deref;
 
 use chrono::DateTime;
@@ -10,9 +12,13 @@ use num_decimal::Num;
 
 use serde::Deserialize;
 use serde::Serialize;
+#[cfg(feature = "extra-fields")]
+use serde_json::Value;
 
 use uuid::Uuid;
 
+use crate::util::datetime_from_str;
+use crate::util::optional_flattened;
 use crate::Str;
 
 
@@ -62,6 +68,33 @@ pub enum Status {
 }
 
 
+/// Balances of an account using local currency trading, denominated
+/// in the account's local currency.
+///
+/// The local currency itself is reported as [`Account::currency`].
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct LocalCurrency {
+  /// The cash balance.
+  #[serde(rename = "cash_lct", default)]
+  pub cash: Option<Num>,
+  /// The account's equity.
+  #[serde(rename = "equity_lct", default)]
+  pub equity: Option<Num>,
+  /// The account's equity as of the previous trading day.
+  #[serde(rename = "last_equity_lct", default)]
+  pub last_equity: Option<Num>,
+  /// The currently available buying power.
+  #[serde(rename = "buying_power_lct", default)]
+  pub buying_power: Option<Num>,
+  /// Real-time mark-to-market value of all long positions.
+  #[serde(rename = "long_market_value_lct", default)]
+  pub market_value_long: Option<Num>,
+  /// Real-time mark-to-market value of all short positions.
+  #[serde(rename = "short_market_value_lct", default)]
+  pub market_value_short: Option<Num>,
+}
+
+
 /// An object as returned by the /v2/account endpoint.
 // TODO: The `sma` field is not yet hooked up.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -95,7 +128,7 @@ pub struct Account {
   #[serde(rename = "account_blocked")]
   pub account_blocked: bool,
   /// Timestamp this account was created at.
-  #[serde(rename = "created_at")]
+  #[serde(rename = "created_at", deserialize_with = "datetime_from_str")]
   pub created_at: DateTime<Utc>,
   /// Flag to denote whether or not the account is permitted to short.
   #[serde(rename = "shorting_enabled")]
@@ -141,6 +174,15 @@ pub struct Account {
   /// five trading days (including today).
   #[serde(rename = "daytrade_count")]
   pub daytrade_count: u64,
+  /// Balances in the local currency, for accounts using local
+  /// currency trading.
+  #[serde(flatten, deserialize_with = "optional_flattened")]
+  pub local_currency: Option<LocalCurrency>,
+  /// Any fields reported by the API that are not (yet) modeled
+  /// explicitly, indexed by name.
+  #[cfg(feature = "extra-fields")]
+  #[serde(flatten, deserialize_with = "crate::schema::capture::<Account, _>")]
+  pub extra: BTreeMap<String, Value>,
 }
 
 
@@ -174,6 +216,7 @@ mod tests {
   use crate::api::API_BASE_URL;
   use crate::api_info::ApiInfo;
   use crate::Client;
+  use crate::live;
   use crate::RequestError;
 
 
@@ -225,13 +268,57 @@ mod tests {
     assert_eq!(acc.last_equity, Num::from(5000));
     assert_eq!(acc.maintenance_margin, Num::from(3000));
     assert_eq!(acc.daytrade_count, 0);
+    assert_eq!(acc.local_currency, None);
+  }
+
+  /// Check that we can deserialize an account using local currency
+  /// trading.
+  #[test]
+  fn deserialize_local_currency_account() {
+    let json = r#"{
+  "id": "904837e3-3b76-47ec-b432-046db621571b",
+  "status": "ACTIVE",
+  "currency": "JPY",
+  "buying_power": "1000.00",
+  "buying_power_lct": "136500.00",
+  "cash": "1000.00",
+  "cash_lct": "136500.00",
+  "pattern_day_trader": false,
+  "trade_suspended_by_user": false,
+  "trading_blocked": false,
+  "transfers_blocked": false,
+  "account_blocked": false,
+  "created_at": "2018-10-01T13:35:25Z",
+  "shorting_enabled": false,
+  "multiplier": "1",
+  "long_market_value": "0",
+  "short_market_value": "0",
+  "equity": "1000.00",
+  "equity_lct": "136500.00",
+  "last_equity": "1000.00",
+  "initial_margin": "0",
+  "maintenance_margin": "0",
+  "daytrade_count": 0
+}"#;
+
+    let acc =
+      from_json::<Account>(&to_json(&from_json::<Account>(json).unwrap()).unwrap()).unwrap();
+    assert_eq!(acc.currency, "JPY");
+
+    let local = acc.local_currency.unwrap();
+    assert_eq!(local.cash, Some(Num::from(136500)));
+    assert_eq!(local.equity, Some(Num::from(136500)));
+    assert_eq!(local.buying_power, Some(Num::from(136500)));
+    assert_eq!(local.last_equity, None);
+    #[cfg(feature = "extra-fields")]
+    assert!(acc.extra.is_empty(), "{:?}", acc.extra);
   }
 
   /// Test that we can retrieve information about the account.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn request_account() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let account = client.issue::<Get>(&()).await.unwrap();
 
     assert_eq!(account.currency, "USD");
@@ -16,7 +16,10 @@ use crate::api::v2::de::ContentDeserializer;
 use crate::api::v2::de::TaggedContentVisitor;
 use crate::api::v2::order;
 use crate::util::abs_num_from_str;
+use crate::util::datetime_from_str;
 use crate::util::enum_slice_to_str;
+use crate::util::optional_num_from_str_or_null;
+use crate::Paginated;
 use crate::Str;
 
 
@@ -32,6 +35,7 @@ where
 
 /// An enum representing the various non-trade activities.
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[non_exhaustive]
 pub enum ActivityType {
   /// Order fills (both partial and full fills).
   ///
@@ -125,6 +129,9 @@ pub enum ActivityType {
   /// SEC and FINRA fees.
   #[serde(rename = "FEE")]
   Fee,
+  /// Fees for crypto trades, charged in the traded asset.
+  #[serde(rename = "CFEE")]
+  CryptoFee,
   /// Reorg CA.
   #[serde(rename = "REORG")]
   Reorg,
@@ -170,7 +177,7 @@ pub struct TradeActivity {
   #[serde(rename = "id")]
   pub id: String,
   /// The time at which the execution occurred.
-  #[serde(rename = "transaction_time")]
+  #[serde(rename = "transaction_time", deserialize_with = "datetime_from_str")]
   pub transaction_time: DateTime<Utc>,
   /// The traded symbol.
   #[serde(rename = "symbol")]
@@ -229,13 +236,30 @@ pub struct NonTradeActivityImpl<T> {
   #[serde(rename = "symbol")]
   pub symbol: Option<String>,
   /// For dividend activities, the number of shares that contributed to
-  /// the payment. Not present for other activity types.
-  #[serde(rename = "qty")]
+  /// the payment. For crypto fee activities, the (negative) quantity of
+  /// the asset charged. Not present for other activity types.
+  #[serde(
+    rename = "qty",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub quantity: Option<Num>,
   /// For dividend activities, the average amount paid per share. Not
   /// present for other activity types.
-  #[serde(rename = "per_share_amount")]
+  #[serde(
+    rename = "per_share_amount",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub per_share_amount: Option<Num>,
+  /// For crypto fee activities, the price of the asset charged. Not
+  /// present for other activity types.
+  #[serde(
+    rename = "price",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
+  pub price: Option<Num>,
   /// A description of the activity.
   #[serde(rename = "description")]
   pub description: Option<String>,
@@ -250,6 +274,7 @@ impl<T> NonTradeActivityImpl<T> {
       symbol,
       quantity,
       per_share_amount,
+      price,
       description,
       ..
     } = self;
@@ -262,6 +287,7 @@ impl<T> NonTradeActivityImpl<T> {
       symbol,
       quantity,
       per_share_amount,
+      price,
       description,
     }
   }
@@ -346,7 +372,7 @@ impl<'de> Deserialize<'de> for Activity {
 
 
 /// The direction in which account activities are reported.
-#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
 pub enum Direction {
   /// Report account activity in descending order, i.e., from more
   /// recent activities to older ones.
@@ -366,6 +392,11 @@ impl Default for Direction {
 }
 
 
+/// The number of activities reported by the /v2/account/activities
+/// endpoint if no page size is provided.
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+
 /// A GET request to be made to the /v2/account/activities endpoint.
 #[derive(Clone, Debug, Default, PartialEq, Serialize)]
 pub struct ActivityReq {
@@ -414,6 +445,27 @@ Endpoint! {
   }
 }
 
+/// Account activities are paginated by ID: the next page starts after
+/// the last activity of the current one.
+impl Paginated for Get {
+  type Item = Activity;
+  type State = ();
+
+  fn paginate(
+    mut input: Self::Input,
+    output: Self::Output,
+    _state: &mut Self::State,
+  ) -> (Vec<Self::Item>, Option<Self::Input>) {
+    let page_size = input.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+    if output.len() < page_size {
+      return (output, None)
+    }
+
+    input.page_token = output.last().map(|activity| activity.id().to_string());
+    (output, Some(input))
+  }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -427,8 +479,7 @@ mod tests {
 
   use uuid::Uuid;
 
-  use crate::api_info::ApiInfo;
-  use crate::Client;
+  use crate::live;
 
 
   #[test]
@@ -462,6 +513,45 @@ mod tests {
     assert_eq!(trade.price, Num::new(163, 100));
   }
 
+  /// Check that we paginate account activities by ID.
+  #[test]
+  fn paginate_activities() {
+    let response = r#"[{
+  "activity_type": "DIV",
+  "id": "20190801011955195::5f596936-6f23-4cef-bdf1-3806aae57dbf",
+  "date": "2019-08-01",
+  "net_amount": "1.02",
+  "symbol": "T",
+  "per_share_amount": "0.51"
+}, {
+  "activity_type": "DIV",
+  "id": "20190802011955195::2e6c43d5-7f4b-4f6b-9d0c-8d9e5f3b8a1e",
+  "date": "2019-08-02",
+  "net_amount": "2.04",
+  "symbol": "T",
+  "per_share_amount": "0.51"
+}]"#;
+    let activities = from_json::<Vec<Activity>>(response).unwrap();
+
+    let request = ActivityReq {
+      page_size: Some(2),
+      ..Default::default()
+    };
+    let (page, next) = Get::paginate(request.clone(), activities.clone(), &mut ());
+    assert_eq!(page, activities);
+    assert_eq!(
+      next.unwrap().page_token.as_deref(),
+      Some("20190802011955195::2e6c43d5-7f4b-4f6b-9d0c-8d9e5f3b8a1e")
+    );
+
+    let request = ActivityReq {
+      page_size: None,
+      ..request
+    };
+    let (_, next) = Get::paginate(request, activities, &mut ());
+    assert_eq!(next, None);
+  }
+
   #[test]
   fn parse_reference_non_trade_activity() {
     let response = r#"{
@@ -518,9 +608,9 @@ mod tests {
   }
 
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn retrieve_some_activities() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let request = ActivityReq {
       types: vec![
         ActivityType::Fill,
@@ -549,9 +639,9 @@ mod tests {
   }
 
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn retrieve_trade_activities() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let request = ActivityReq {
       types: vec![ActivityType::Fill],
       ..Default::default()
@@ -571,9 +661,9 @@ mod tests {
   }
 
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn retrieve_all_activities() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let request = ActivityReq {
       direction: Direction::Ascending,
       ..Default::default()
@@ -596,9 +686,9 @@ mod tests {
 
   /// Check that paging works properly.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn page_activities() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let mut request = ActivityReq {
       page_size: Some(1),
       ..Default::default()
@@ -623,9 +713,9 @@ mod tests {
 
   /// Verify that the `after` request argument is honored properly.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn retrieve_after() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let mut request = ActivityReq {
       direction: Direction::Ascending,
       page_size: Some(1),
@@ -654,9 +744,9 @@ mod tests {
 
   /// Verify that the `until` request argument is honored properly.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn retrieve_until() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let mut request = ActivityReq {
       direction: Direction::Ascending,
       page_size: Some(2),
@@ -96,8 +96,7 @@ mod tests {
 
   use test_log::test;
 
-  use crate::api_info::ApiInfo;
-  use crate::Client;
+  use crate::live;
 
 
   #[test]
@@ -116,9 +115,9 @@ mod tests {
   }
 
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn retrieve_and_update_configuration() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let config = client.issue::<Get>(&()).await.unwrap();
 
     // We invert the trade confirmation strategy, which should be a
@@ -0,0 +1,312 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::BTreeSet;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::api::v2::account_activities::Activity;
+use crate::api::v2::account_activities::ActivityReq;
+use crate::api::v2::account_activities::ActivityType;
+use crate::api::v2::account_activities::Direction;
+use crate::api::v2::account_activities::Get;
+use crate::api::v2::account_activities::GetError;
+use crate::Client;
+use crate::RequestError;
+
+
+/// The maximum number of activities the /v2/account/activities endpoint
+/// reports per page.
+const MAX_PAGE_SIZE: usize = 100;
+
+
+/// Retrieve the timestamp portion of an activity ID.
+///
+/// Activity IDs are of the form `<timestamp>::<uuid>`, with the
+/// timestamp being a fixed-width sequence of digits. Comparing
+/// timestamps lexicographically hence orders them chronologically.
+fn timestamp(id: &str) -> &str {
+  id.split_once("::")
+    .map(|(timestamp, _)| timestamp)
+    .unwrap_or(id)
+}
+
+
+/// The position of an [`ActivityCursor`], suitable for persisting it
+/// between runs.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CursorState {
+  /// The ID of the most recent activity seen.
+  pub last_id: Option<String>,
+  /// The ID of the most recent activity seen with a timestamp strictly
+  /// older than that of `last_id`, from which pages are requested.
+  pub anchor_id: Option<String>,
+  /// The IDs of all activities seen that share the timestamp of
+  /// `last_id`.
+  pub boundary: BTreeSet<String>,
+}
+
+impl CursorState {
+  /// Check whether the activity with the given ID has been seen
+  /// already.
+  fn is_seen(&self, id: &str) -> bool {
+    match &self.last_id {
+      Some(last_id) => {
+        let (time, last_time) = (timestamp(id), timestamp(last_id));
+        time < last_time || (time == last_time && self.boundary.contains(id))
+      },
+      None => false,
+    }
+  }
+
+  /// Record the activity with the given ID as seen.
+  fn see(&mut self, id: String) {
+    let newer = self
+      .last_id
+      .as_deref()
+      .map(|last_id| timestamp(&id) > timestamp(last_id))
+      .unwrap_or(true);
+    if newer {
+      self.anchor_id = self.last_id.take();
+      let () = self.boundary.clear();
+    }
+    let _inserted = self.boundary.insert(id.clone());
+    self.last_id = Some(id);
+  }
+}
+
+
+/// A cursor for walking account activities in chronological order,
+/// page by page, without duplicates or gaps.
+///
+/// Pages are requested in ascending order. Because multiple activities
+/// may share the same timestamp and the API orders them by ID, an
+/// activity sharing the timestamp of the most recent one seen may still
+/// show up before it. Pages are hence requested starting after the last
+/// activity with a strictly older timestamp, and the cursor remembers
+/// all activities seen with the most recent timestamp, to filter them
+/// out as they are reported again.
+///
+/// The cursor's position can be persisted by means of its
+/// [`CursorState`] (see [`ActivityCursor::state`]) and resumed later on
+/// via [`ActivityCursor::resume`], allowing for incremental syncs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ActivityCursor {
+  /// The types of activities to retrieve. If empty, all activities are
+  /// retrieved.
+  types: Vec<ActivityType>,
+  /// The number of activities to request per page.
+  page_size: usize,
+  /// The cursor's position.
+  state: CursorState,
+}
+
+impl ActivityCursor {
+  /// Create a cursor starting at the very first activity.
+  pub fn new<I>(types: I) -> Self
+  where
+    I: IntoIterator<Item = ActivityType>,
+  {
+    Self::resume(types, CursorState::default())
+  }
+
+  /// Create a cursor continuing from the given state.
+  pub fn resume<I>(types: I, state: CursorState) -> Self
+  where
+    I: IntoIterator<Item = ActivityType>,
+  {
+    Self {
+      types: types.into_iter().collect(),
+      page_size: MAX_PAGE_SIZE,
+      state,
+    }
+  }
+
+  /// Set the number of activities to request per page.
+  ///
+  /// The value is clamped to the range supported by the API.
+  #[inline]
+  pub fn with_page_size(mut self, page_size: usize) -> Self {
+    self.page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+    self
+  }
+
+  /// Retrieve the ID of the last activity seen, if any.
+  #[inline]
+  pub fn last_id(&self) -> Option<&str> {
+    self.state.last_id.as_deref()
+  }
+
+  /// Retrieve the cursor's current state.
+  #[inline]
+  pub fn state(&self) -> &CursorState {
+    &self.state
+  }
+
+  /// Create the request for retrieving the next page of activities.
+  pub fn request(&self) -> ActivityReq {
+    // If the activities sharing the most recent timestamp fill an
+    // entire page, re-requesting them would not get us anywhere and we
+    // have to continue after the last one instead.
+    let page_token = if self.state.boundary.len() >= self.page_size {
+      self.state.last_id.clone()
+    } else {
+      self.state.anchor_id.clone()
+    };
+
+    ActivityReq {
+      types: self.types.clone(),
+      direction: Direction::Ascending,
+      page_size: Some(self.page_size),
+      page_token,
+      ..Default::default()
+    }
+  }
+
+  /// Advance the cursor past a page of activities as retrieved using
+  /// the request created by [`ActivityCursor::request`].
+  ///
+  /// Reported are all activities of the page that had not been seen
+  /// before, in the order in which they were reported, along with an
+  /// indication of whether further pages may be available.
+  pub fn advance(&mut self, page: Vec<Activity>) -> (Vec<Activity>, bool) {
+    let full = page.len() >= self.page_size;
+    let mut new = Vec::with_capacity(page.len());
+
+    for activity in page {
+      if self.state.is_seen(activity.id()) {
+        continue
+      }
+
+      let () = self.state.see(activity.id().to_string());
+      let () = new.push(activity);
+    }
+
+    // A page consisting of nothing but activities seen already means
+    // that we are not making progress, so we stop here to not request
+    // the same page over and over again.
+    let more = full && !new.is_empty();
+    (new, more)
+  }
+
+  /// Retrieve all activities not seen so far, advancing the cursor
+  /// accordingly.
+  ///
+  /// The method can be invoked repeatedly to retrieve activities as
+  /// they occur.
+  pub async fn sync(&mut self, client: &Client) -> Result<Vec<Activity>, RequestError<GetError>> {
+    let mut activities = Vec::new();
+    loop {
+      let page = client.issue::<Get>(&self.request()).await?;
+      let (new, more) = self.advance(page);
+      let () = activities.extend(new);
+
+      if !more {
+        break Ok(activities)
+      }
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::from_slice as from_json;
+  use serde_json::from_value;
+  use serde_json::json;
+  use serde_json::to_vec as to_json;
+
+  use test_log::test;
+
+
+  /// Create a dividend activity with the given ID.
+  fn activity(id: &str) -> Activity {
+    from_value(json!({
+      "activity_type": "DIV",
+      "id": id,
+      "date": "2019-08-01",
+      "net_amount": "1.02",
+      "symbol": "T",
+      "per_share_amount": "0.51"
+    }))
+    .unwrap()
+  }
+
+  /// Retrieve the IDs of the given activities.
+  fn ids(activities: &[Activity]) -> Vec<&str> {
+    activities.iter().map(Activity::id).collect()
+  }
+
+  /// Serve a page of the given activities the way the API does: ordered
+  /// by ID and starting after the one identified by the page token.
+  fn serve(ids: &[&str], request: &ActivityReq) -> Vec<Activity> {
+    let mut ids = ids.to_vec();
+    let () = ids.sort();
+    ids
+      .into_iter()
+      .filter(|id| match &request.page_token {
+        Some(token) => *id > token.as_str(),
+        None => true,
+      })
+      .take(request.page_size.unwrap())
+      .map(activity)
+      .collect()
+  }
+
+
+  /// Check that we walk all activities without duplicates.
+  #[test]
+  fn advance_without_duplicates() {
+    let all = [
+      "20190801::a",
+      "20190801::b",
+      "20190802::a",
+      "20190802::b",
+      "20190803::a",
+    ];
+    let mut cursor = ActivityCursor::new([ActivityType::Dividend]).with_page_size(2);
+    assert_eq!(cursor.request().page_token, None);
+    assert_eq!(cursor.request().direction, Direction::Ascending);
+
+    let mut seen = Vec::new();
+    loop {
+      let page = serve(&all, &cursor.request());
+      let (new, more) = cursor.advance(page);
+      let () = seen.extend(new);
+      if !more {
+        break
+      }
+    }
+
+    assert_eq!(ids(&seen), all);
+    assert_eq!(cursor.last_id(), Some("20190803::a"));
+    assert_eq!(cursor.state().anchor_id.as_deref(), Some("20190802::b"));
+  }
+
+  /// Check that a resumed cursor reports an activity that shows up
+  /// later on with the timestamp of the last one seen, but ordered
+  /// before it.
+  #[test]
+  fn resume_with_same_timestamp() {
+    let mut cursor = ActivityCursor::new(Vec::new());
+    let page = serve(&["20190801::a", "20190802::b"], &cursor.request());
+    let (new, more) = cursor.advance(page);
+    assert_eq!(ids(&new), vec!["20190801::a", "20190802::b"]);
+    assert!(!more);
+
+    let state = to_json(cursor.state()).unwrap();
+    let state = from_json::<CursorState>(&state).unwrap();
+    let mut cursor = ActivityCursor::resume(Vec::new(), state);
+    assert_eq!(cursor.request().page_token.as_deref(), Some("20190801::a"));
+    assert_eq!(cursor.request().page_size, Some(MAX_PAGE_SIZE));
+
+    let all = ["20190801::a", "20190802::a", "20190802::b", "20190803::a"];
+    let page = serve(&all, &cursor.request());
+    let (new, more) = cursor.advance(page);
+    assert_eq!(ids(&new), vec!["20190802::a", "20190803::a"]);
+    assert!(!more);
+  }
+}
@@ -0,0 +1,427 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use chrono::NaiveDate;
+
+use num_decimal::Num;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_urlencoded::to_string as to_query;
+
+use crate::util::enum_slice_to_str;
+use crate::util::optional_num_from_str_or_null;
+use crate::Str;
+
+
+/// An enumeration of the different corporate action types.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[non_exhaustive]
+pub enum Type {
+  /// A cash or stock dividend.
+  #[serde(rename = "dividend")]
+  Dividend,
+  /// A merger of two companies.
+  #[serde(rename = "merger")]
+  Merger,
+  /// A spin-off of a part of a company.
+  #[serde(rename = "spinoff")]
+  Spinoff,
+  /// A stock split (or reverse split).
+  #[serde(rename = "split")]
+  Split,
+}
+
+
+/// The details of a corporate action, specific to its type.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(tag = "ca_type")]
+#[non_exhaustive]
+pub enum Action {
+  /// A cash or stock dividend.
+  ///
+  /// Cash dividends report the amount paid per share, while stock
+  /// dividends report the rate at which shares are distributed.
+  #[serde(rename = "dividend")]
+  Dividend {
+    /// The cash amount paid per share.
+    #[serde(
+      rename = "cash",
+      default,
+      deserialize_with = "optional_num_from_str_or_null"
+    )]
+    cash: Option<Num>,
+    /// The number of shares before the corporate action.
+    #[serde(
+      rename = "old_rate",
+      default,
+      deserialize_with = "optional_num_from_str_or_null"
+    )]
+    old_rate: Option<Num>,
+    /// The number of shares after the corporate action.
+    #[serde(
+      rename = "new_rate",
+      default,
+      deserialize_with = "optional_num_from_str_or_null"
+    )]
+    new_rate: Option<Num>,
+  },
+  /// A merger of two companies, with the initiating company acquiring
+  /// the target.
+  #[serde(rename = "merger")]
+  Merger {
+    /// The symbol of the company that is the target of the corporate
+    /// action, if known.
+    #[serde(rename = "target_symbol", default)]
+    target_symbol: Option<String>,
+    /// The cash amount paid per share.
+    #[serde(
+      rename = "cash",
+      default,
+      deserialize_with = "optional_num_from_str_or_null"
+    )]
+    cash: Option<Num>,
+    /// The number of shares before the corporate action.
+    #[serde(
+      rename = "old_rate",
+      default,
+      deserialize_with = "optional_num_from_str_or_null"
+    )]
+    old_rate: Option<Num>,
+    /// The number of shares after the corporate action.
+    #[serde(
+      rename = "new_rate",
+      default,
+      deserialize_with = "optional_num_from_str_or_null"
+    )]
+    new_rate: Option<Num>,
+  },
+  /// A spin-off of the target company from the initiating one.
+  #[serde(rename = "spinoff")]
+  Spinoff {
+    /// The symbol of the company that is the target of the corporate
+    /// action, if known.
+    #[serde(rename = "target_symbol", default)]
+    target_symbol: Option<String>,
+    /// The number of shares before the corporate action.
+    #[serde(
+      rename = "old_rate",
+      default,
+      deserialize_with = "optional_num_from_str_or_null"
+    )]
+    old_rate: Option<Num>,
+    /// The number of shares after the corporate action.
+    #[serde(
+      rename = "new_rate",
+      default,
+      deserialize_with = "optional_num_from_str_or_null"
+    )]
+    new_rate: Option<Num>,
+  },
+  /// A stock split (or reverse split).
+  #[serde(rename = "split")]
+  Split {
+    /// The number of shares before the corporate action.
+    #[serde(
+      rename = "old_rate",
+      default,
+      deserialize_with = "optional_num_from_str_or_null"
+    )]
+    old_rate: Option<Num>,
+    /// The number of shares after the corporate action.
+    #[serde(
+      rename = "new_rate",
+      default,
+      deserialize_with = "optional_num_from_str_or_null"
+    )]
+    new_rate: Option<Num>,
+  },
+}
+
+impl Action {
+  /// Retrieve the type of the corporate action.
+  pub fn type_(&self) -> Type {
+    match self {
+      Self::Dividend { .. } => Type::Dividend,
+      Self::Merger { .. } => Type::Merger,
+      Self::Spinoff { .. } => Type::Spinoff,
+      Self::Split { .. } => Type::Split,
+    }
+  }
+
+  /// Retrieve the symbol of the company that is the target of the
+  /// corporate action, if any.
+  pub fn target_symbol(&self) -> Option<&str> {
+    match self {
+      Self::Merger { target_symbol, .. } | Self::Spinoff { target_symbol, .. } => {
+        target_symbol.as_deref()
+      },
+      Self::Dividend { .. } | Self::Split { .. } => None,
+    }
+  }
+}
+
+
+/// A corporate action announcement as returned by the
+/// /v2/corporate_actions/announcements endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Announcement {
+  /// The announcement's ID.
+  #[serde(rename = "id")]
+  pub id: String,
+  /// The ID of the corporate action the announcement relates to.
+  #[serde(rename = "corporate_action_id")]
+  pub corporate_action_id: String,
+  /// The type specific details of the corporate action.
+  #[serde(flatten)]
+  pub action: Action,
+  /// The sub-type of the corporate action, e.g., `cash` or
+  /// `reverse_split`.
+  #[serde(rename = "ca_sub_type")]
+  pub sub_type: String,
+  /// The symbol of the company initiating the corporate action.
+  #[serde(rename = "initiating_symbol")]
+  pub initiating_symbol: String,
+  /// The date on which the corporate action was declared.
+  #[serde(rename = "declaration_date", default)]
+  pub declaration_date: Option<NaiveDate>,
+  /// The first date on which the security trades without the
+  /// corporate action applied.
+  #[serde(rename = "ex_date", default)]
+  pub ex_date: Option<NaiveDate>,
+  /// The date by which shares need to be held to be eligible.
+  #[serde(rename = "record_date", default)]
+  pub record_date: Option<NaiveDate>,
+  /// The date on which the corporate action is paid out.
+  #[serde(rename = "payable_date", default)]
+  pub payable_date: Option<NaiveDate>,
+}
+
+impl Announcement {
+  /// Retrieve the type of the corporate action.
+  #[inline]
+  pub fn type_(&self) -> Type {
+    self.action.type_()
+  }
+}
+
+
+/// A GET request to be made to the /v2/corporate_actions/announcements
+/// endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct AnnouncementsReq {
+  /// The types of corporate actions to retrieve announcements for.
+  #[serde(rename = "ca_types", serialize_with = "enum_slice_to_str")]
+  pub types: Vec<Type>,
+  /// The (inclusive) start of the date range to retrieve announcements
+  /// for.
+  #[serde(rename = "since")]
+  pub since: NaiveDate,
+  /// The (inclusive) end of the date range to retrieve announcements
+  /// for. The range may span at most 90 days.
+  #[serde(rename = "until")]
+  pub until: NaiveDate,
+  /// The symbol to retrieve announcements for.
+  #[serde(rename = "symbol", skip_serializing_if = "Option::is_none")]
+  pub symbol: Option<String>,
+}
+
+
+/// A helper for initializing [`AnnouncementsReq`] objects.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AnnouncementsReqInit {
+  /// See `AnnouncementsReq::symbol`.
+  pub symbol: Option<String>,
+  #[doc(hidden)]
+  pub _non_exhaustive: (),
+}
+
+impl AnnouncementsReqInit {
+  /// Create an [`AnnouncementsReq`] from an `AnnouncementsReqInit`.
+  #[inline]
+  pub fn init<I>(self, types: I, since: NaiveDate, until: NaiveDate) -> AnnouncementsReq
+  where
+    I: IntoIterator<Item = Type>,
+  {
+    AnnouncementsReq {
+      types: types.into_iter().collect(),
+      since,
+      until,
+      symbol: self.symbol,
+    }
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the
+  /// /v2/corporate_actions/announcements endpoint.
+  pub Get(AnnouncementsReq),
+  Ok => Vec<Announcement>, [
+    /// The announcements were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetError, [
+    /// Some of the provided data was invalid, e.g., the date range
+    /// spans more than 90 days.
+    /* 422 */ UNPROCESSABLE_ENTITY => InvalidInput,
+  ]
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v2/corporate_actions/announcements".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::from_str as from_json;
+
+  use test_log::test;
+
+  use crate::live;
+  use crate::RequestError;
+
+
+  /// Check that we can serialize an announcements request as expected.
+  #[test]
+  fn serialize_request() {
+    let since = NaiveDate::from_ymd_opt(2020, 8, 1).unwrap();
+    let until = NaiveDate::from_ymd_opt(2020, 9, 1).unwrap();
+    let request = AnnouncementsReqInit {
+      symbol: Some("AAPL".to_string()),
+      ..Default::default()
+    }
+    .init([Type::Dividend, Type::Split], since, until);
+
+    assert_eq!(
+      to_query(&request).unwrap(),
+      "ca_types=dividend%2Csplit&since=2020-08-01&until=2020-09-01&symbol=AAPL"
+    );
+  }
+
+  /// Check that we can deserialize a reference announcement.
+  #[test]
+  fn deserialize_reference_announcement() {
+    let json = r#"{
+  "id": "be3c368a-4c7c-4384-808e-f02c9f5a8afe",
+  "corporate_action_id": "F58684224_XY37",
+  "ca_type": "split",
+  "ca_sub_type": "stock_split",
+  "initiating_symbol": "AAPL",
+  "initiating_original_cusip": "037833100",
+  "target_symbol": null,
+  "target_original_cusip": null,
+  "declaration_date": "2020-07-30",
+  "ex_date": "2020-08-31",
+  "record_date": "2020-08-24",
+  "payable_date": "2020-08-28",
+  "cash": "0",
+  "old_rate": "1",
+  "new_rate": "4"
+}"#;
+
+    let announcement = from_json::<Announcement>(json).unwrap();
+    assert_eq!(announcement.type_(), Type::Split);
+    assert_eq!(announcement.sub_type, "stock_split");
+    assert_eq!(announcement.initiating_symbol, "AAPL");
+    assert_eq!(announcement.action.target_symbol(), None);
+    assert_eq!(
+      announcement.ex_date,
+      Some(NaiveDate::from_ymd_opt(2020, 8, 31).unwrap())
+    );
+    assert_eq!(
+      announcement.action,
+      Action::Split {
+        old_rate: Some(Num::from(1)),
+        new_rate: Some(Num::from(4)),
+      }
+    );
+  }
+
+  /// Check that we can deserialize a reference merger announcement,
+  /// retaining only the fields relevant to mergers.
+  #[test]
+  fn deserialize_reference_merger() {
+    let json = r#"{
+  "id": "e8ab0c5c-7e5c-4a2f-9c3d-0f1c5b2f9d11",
+  "corporate_action_id": "G19340281_AB12",
+  "ca_type": "merger",
+  "ca_sub_type": "merger_completion",
+  "initiating_symbol": "MSFT",
+  "initiating_original_cusip": "594918104",
+  "target_symbol": "ATVI",
+  "target_original_cusip": "00507V109",
+  "declaration_date": "2022-01-18",
+  "ex_date": null,
+  "record_date": null,
+  "payable_date": "2023-10-13",
+  "cash": "95",
+  "old_rate": null,
+  "new_rate": null
+}"#;
+
+    let announcement = from_json::<Announcement>(json).unwrap();
+    assert_eq!(announcement.type_(), Type::Merger);
+    assert_eq!(announcement.action.target_symbol(), Some("ATVI"));
+    match announcement.action {
+      Action::Merger {
+        cash,
+        old_rate,
+        new_rate,
+        ..
+      } => {
+        assert_eq!(cash, Some(Num::from(95)));
+        assert_eq!(old_rate, None);
+        assert_eq!(new_rate, None);
+      },
+      action => panic!("encountered unexpected action: {:?}", action),
+    }
+  }
+
+  /// Check that we can retrieve corporate action announcements.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn request_announcements() {
+    let client = live::client().unwrap();
+
+    let since = NaiveDate::from_ymd_opt(2020, 8, 1).unwrap();
+    let until = NaiveDate::from_ymd_opt(2020, 9, 1).unwrap();
+    let request = AnnouncementsReqInit {
+      symbol: Some("AAPL".to_string()),
+      ..Default::default()
+    }
+    .init([Type::Split], since, until);
+
+    let announcements = client.issue::<Get>(&request).await.unwrap();
+    assert!(announcements
+      .iter()
+      .any(|announcement| announcement.type_() == Type::Split));
+  }
+
+  /// Verify that we report an error when the requested date range is
+  /// too large.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn request_with_invalid_range() {
+    let client = live::client().unwrap();
+
+    let since = NaiveDate::from_ymd_opt(2020, 1, 1).unwrap();
+    let until = NaiveDate::from_ymd_opt(2020, 12, 31).unwrap();
+    let request = AnnouncementsReqInit::default().init([Type::Dividend], since, until);
+
+    let err = client.issue::<Get>(&request).await.unwrap_err();
+    match err {
+      RequestError::Endpoint(GetError::InvalidInput(_)) => (),
+      _ => panic!("Received unexpected error: {:?}", err),
+    };
+  }
+}
@@ -1,6 +1,8 @@
 // Copyright (C) 2019-2022 The apca Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+#[cfg(feature = "extra-fields")]
+use std::collections::BTreeMap;
 use std::convert::TryFrom;
 use std::fmt::Display;
 use std::fmt::Formatter;
@@ -11,6 +13,8 @@ use std::str::FromStr;
 use serde::Deserialize;
 use serde::Serialize;
 use serde::Serializer;
+#[cfg(feature = "extra-fields")]
+use serde_json::Value;
 
 use uuid::Error as UuidError;
 use uuid::Uuid;
@@ -329,6 +333,12 @@ pub struct Asset {
   /// The asset's symbol.
   #[serde(rename = "symbol")]
   pub symbol: String,
+  /// The asset's name, typically the name of the issuing company.
+  #[serde(rename = "name", default)]
+  pub name: String,
+  /// The asset's CUSIP, if reported.
+  #[serde(rename = "cusip", default)]
+  pub cusip: Option<String>,
   /// The asset's status.
   #[serde(rename = "status")]
   pub status: Status,
@@ -349,6 +359,11 @@ pub struct Asset {
   /// Whether the asset is fractionable or not.
   #[serde(rename = "fractionable")]
   pub fractionable: bool,
+  /// Any fields reported by the API that are not (yet) modeled
+  /// explicitly, indexed by name.
+  #[cfg(feature = "extra-fields")]
+  #[serde(flatten, deserialize_with = "crate::schema::capture::<Asset, _>")]
+  pub extra: BTreeMap<String, Value>,
 }
 
 
@@ -382,8 +397,7 @@ mod tests {
 
   use uuid::Uuid;
 
-  use crate::api_info::ApiInfo;
-  use crate::Client;
+  use crate::live;
 
 
   /// Verify that we can parse various symbols.
@@ -499,12 +513,38 @@ mod tests {
     assert_eq!(asset.exchange, Exchange::Unknown);
   }
 
+  /// Verify that unknown fields of an asset object are captured.
+  #[cfg(feature = "extra-fields")]
+  #[test]
+  fn parse_with_unknown_field() {
+    let response = r#"{
+  "id": "904837e3-3b76-47ec-b432-046db621571b",
+  "class": "us_equity",
+  "exchange": "NASDAQ",
+  "symbol": "AAPL",
+  "status": "active",
+  "tradable": true,
+  "marginable": true,
+  "shortable": true,
+  "easy_to_borrow": true,
+  "fractionable": true,
+  "maintenance_margin_requirement": 30
+}"#;
+
+    let asset = from_json::<Asset>(response).unwrap();
+    assert_eq!(asset.extra.len(), 1);
+    assert_eq!(
+      asset.extra.get("maintenance_margin_requirement"),
+      Some(&Value::from(30))
+    );
+  }
+
   /// Check that we can retrieve information about an asset.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn retrieve_asset() {
     async fn test(symbol: Symbol) {
-      let api_info = ApiInfo::from_env().unwrap();
-      let client = Client::new(api_info);
+      let client = live::client().unwrap();
       let asset = client.issue::<Get>(&symbol).await.unwrap();
 
       // The AAPL asset ID, retrieved out-of-band.
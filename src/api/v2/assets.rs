@@ -1,12 +1,23 @@
 // Copyright (C) 2019-2022 The apca Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use http::HeaderMap;
+use http::StatusCode;
+use http_endpoint::Endpoint as _;
+
+use hyper::body::Bytes;
+
+use serde::de::Error as _;
 use serde::Serialize;
+use serde_json::Deserializer;
+use serde_json::Error as JsonError;
 use serde_urlencoded::to_string as to_query;
 
 use crate::api::v2::asset::Asset;
 use crate::api::v2::asset::Class;
 use crate::api::v2::asset::Status;
+use crate::Client;
+use crate::RequestError;
 use crate::Str;
 
 
@@ -65,22 +76,202 @@ Endpoint! {
 }
 
 
+/// An iterator over the assets contained in a response of the
+/// /v2/assets endpoint, parsing them one by one as they are requested.
+///
+/// As opposed to retrieving the list of assets via [`Get`], parsed assets
+/// are never collected in their entirety. Callers can filter them using
+/// [`Iterator::filter`] and stop early once they found what they were
+/// looking for.
+#[derive(Debug)]
+pub struct AssetIter {
+  /// The raw response body.
+  body: Bytes,
+  /// The offset into `body` from which to continue parsing.
+  offset: usize,
+  /// Whether we already consumed the opening bracket.
+  started: bool,
+  /// Whether we reached the end of the list or encountered an error.
+  done: bool,
+}
+
+impl AssetIter {
+  /// Create an `AssetIter` over the JSON array of assets in `body`.
+  #[inline]
+  pub fn new(body: Bytes) -> Self {
+    Self {
+      body,
+      offset: 0,
+      started: false,
+      done: false,
+    }
+  }
+
+  /// Advance past any whitespace and peek at the next byte.
+  fn peek(&mut self) -> Option<u8> {
+    while let Some(byte) = self.body.get(self.offset) {
+      if !byte.is_ascii_whitespace() {
+        return Some(*byte)
+      }
+      self.offset += 1;
+    }
+    None
+  }
+
+  /// Parse the next asset, if any.
+  fn parse_next(&mut self) -> Result<Option<Asset>, JsonError> {
+    if !self.started {
+      if self.peek() != Some(b'[') {
+        return Err(JsonError::custom("expected `[`"))
+      }
+      self.offset += 1;
+      self.started = true;
+
+      if self.peek() == Some(b']') {
+        self.offset += 1;
+        return Ok(None)
+      }
+    } else {
+      match self.peek() {
+        Some(b',') => self.offset += 1,
+        Some(b']') => {
+          self.offset += 1;
+          return Ok(None)
+        },
+        _ => return Err(JsonError::custom("expected `,` or `]`")),
+      }
+    }
+
+    let mut stream = Deserializer::from_slice(&self.body[self.offset..]).into_iter::<Asset>();
+    match stream.next() {
+      Some(Ok(asset)) => {
+        self.offset += stream.byte_offset();
+        Ok(Some(asset))
+      },
+      Some(Err(err)) => Err(err),
+      None => Err(JsonError::custom("unexpected end of asset list")),
+    }
+  }
+}
+
+impl Iterator for AssetIter {
+  type Item = Result<Asset, JsonError>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if self.done {
+      return None
+    }
+
+    let result = self.parse_next();
+    if !matches!(result, Ok(Some(_))) {
+      self.done = true;
+    }
+    result.transpose()
+  }
+}
+
+
+/// Retrieve the assets matching the given request, returning an
+/// iterator that parses them lazily.
+///
+/// Note that the response is received in its entirety before the
+/// iterator is returned. Only the parsing of individual assets is
+/// deferred, which avoids materializing the full list of assets when
+/// just a few of them are of interest.
+pub async fn iter(
+  client: &Client,
+  request: &AssetsReq,
+) -> Result<AssetIter, RequestError<GetError>> {
+  let (status, _, body) = client.issue_raw::<Get>(request, HeaderMap::new()).await?;
+  if status != StatusCode::OK {
+    // Let the endpoint take care of reporting the error.
+    let _ = Get::evaluate(status, &body).map_err(RequestError::Endpoint)?;
+  }
+  Ok(AssetIter::new(body))
+}
+
+
 #[cfg(test)]
 mod tests {
   use super::*;
 
   use test_log::test;
 
+  use serde_json::from_str as from_json;
+
   use crate::api::v2::asset::Exchange;
-  use crate::api_info::ApiInfo;
-  use crate::Client;
+  use crate::live;
+
+
+  /// Check that `AssetIter` yields the same assets as parsing the
+  /// list in its entirety.
+  #[test]
+  fn iterate_assets() {
+    let response = r#"[
+  {
+    "id": "904837e3-3b76-47ec-b432-046db621571b",
+    "class": "us_equity",
+    "exchange": "NASDAQ",
+    "symbol": "AAPL",
+    "status": "active",
+    "tradable": true,
+    "marginable": true,
+    "shortable": true,
+    "easy_to_borrow": true,
+    "fractionable": true
+  },
+  {
+    "id": "b0b6dd9d-8b9b-48a9-ba46-b9d54906e415",
+    "class": "us_equity",
+    "exchange": "NASDAQ",
+    "symbol": "MSFT",
+    "status": "active",
+    "tradable": true,
+    "marginable": true,
+    "shortable": true,
+    "easy_to_borrow": true,
+    "fractionable": true
+  }
+]"#;
+
+    let expected = from_json::<Vec<Asset>>(response).unwrap();
+    let assets = AssetIter::new(Bytes::from(response))
+      .collect::<Result<Vec<_>, _>>()
+      .unwrap();
+    assert_eq!(assets, expected);
+
+    let mut iter = AssetIter::new(Bytes::from(response)).filter_map(Result::ok);
+    let asset = iter.find(|asset| asset.symbol == "MSFT").unwrap();
+    assert_eq!(asset.symbol, "MSFT");
+    assert_eq!(iter.next(), None);
+
+    let mut iter = AssetIter::new(Bytes::from(" [ ] "));
+    assert!(iter.next().is_none());
+  }
+
+  /// Check that `AssetIter` reports malformed input and stops
+  /// afterwards.
+  #[test]
+  fn iterate_malformed_assets() {
+    let mut iter = AssetIter::new(Bytes::from("{}"));
+    assert!(iter.next().unwrap().is_err());
+    assert!(iter.next().is_none());
+
+    let mut iter = AssetIter::new(Bytes::from("[{}]"));
+    assert!(iter.next().unwrap().is_err());
+    assert!(iter.next().is_none());
+
+    let mut iter = AssetIter::new(Bytes::from("["));
+    assert!(iter.next().unwrap().is_err());
+    assert!(iter.next().is_none());
+  }
 
 
   /// Make sure that we can list available US stock assets.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn list_us_stock_assets() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let request = AssetsReqInit::default().init();
     let assets = client.issue::<Get>(&request).await.unwrap();
 
@@ -90,12 +281,29 @@ mod tests {
     assert_eq!(asset.status, Status::Active);
   }
 
+  /// Make sure that we can lazily iterate over available US stock
+  /// assets.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn iterate_us_stock_assets() {
+    let client = live::client().unwrap();
+    let request = AssetsReqInit::default().init();
+    let mut assets = iter(&client, &request).await.unwrap();
+
+    let asset = assets
+      .find(|x| matches!(x, Ok(asset) if asset.symbol == "AAPL"))
+      .unwrap()
+      .unwrap();
+    assert_eq!(asset.class, Class::UsEquity);
+    assert_eq!(asset.exchange, Exchange::Nasdaq);
+  }
+
 
   /// Make sure that we can list available crypto currency assets.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn list_crypto_assets() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let request = AssetsReqInit {
       class: Class::Crypto,
       ..Default::default()
@@ -0,0 +1,79 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use futures::stream::iter;
+use futures::StreamExt as _;
+
+use crate::api::v2::order;
+use crate::Client;
+use crate::RequestError;
+
+
+/// The result of submitting a single order as part of a batch.
+pub type SubmitResult = Result<order::Order, RequestError<order::PostError>>;
+
+
+/// Submit multiple orders, with at most `concurrency` requests in
+/// flight at any time.
+///
+/// The submission of one order failing does not affect the others. The
+/// result of each submission is reported at the same index as the
+/// corresponding request, i.e., in input order.
+pub async fn submit_orders<'r, I>(
+  client: &Client,
+  requests: I,
+  concurrency: usize,
+) -> Vec<SubmitResult>
+where
+  I: IntoIterator<Item = &'r order::OrderReq>,
+{
+  iter(requests)
+    .map(|request| client.issue::<order::Post>(request))
+    .buffered(concurrency.max(1))
+    .collect()
+    .await
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use num_decimal::Num;
+
+  use test_log::test;
+
+  use crate::api::v2::order::Amount;
+  use crate::api::v2::order::Side;
+  use crate::api::v2::order::Type;
+  use crate::live;
+
+
+  /// Check that we can submit a batch of orders, with failures being
+  /// reported for individual ones.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn submit_order_batch() {
+    let request = |symbol: &str| {
+      order::OrderReqInit {
+        type_: Type::Limit,
+        limit_price: Some(Num::from(1)),
+        ..Default::default()
+      }
+      .init(symbol, Side::Buy, Amount::quantity(1))
+    };
+    let requests = [request("AAPL"), request("ABC123"), request("SPY")];
+
+    let client = live::client().unwrap();
+    let results = submit_orders(&client, &requests, 2).await;
+    assert_eq!(results.len(), 3);
+
+    for order in results.iter().flatten() {
+      let () = client.issue::<order::Delete>(&order.id).await.unwrap();
+    }
+
+    assert_eq!(results[0].as_ref().unwrap().symbol, "AAPL");
+    assert!(results[1].is_err());
+    assert_eq!(results[2].as_ref().unwrap().symbol, "SPY");
+  }
+}
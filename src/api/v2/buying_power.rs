@@ -0,0 +1,229 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use num_decimal::Num;
+
+use thiserror::Error;
+
+use crate::api::v2::account;
+use crate::api::v2::account::Account;
+use crate::api::v2::asset::Symbol;
+use crate::api::v2::order::Amount;
+use crate::api::v2::order::OrderReq;
+use crate::api::v2::order::Side;
+use crate::api::v2::order::Type;
+use crate::data::v2::last_quote;
+use crate::data::v2::last_quote::LastQuoteReqInit;
+use crate::data::v2::last_quote::Quote;
+use crate::money;
+use crate::Client;
+use crate::RequestError;
+
+
+/// The verdict of a buying power check for an order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Verdict {
+  /// The order is expected to fit within the available buying power.
+  Sufficient {
+    /// The estimated cost of the order.
+    cost: Num,
+    /// The estimated buying power remaining after the order filled.
+    remaining: Num,
+  },
+  /// The order is expected to exceed the available buying power.
+  Insufficient {
+    /// The estimated cost of the order.
+    cost: Num,
+    /// The amount by which the cost exceeds the buying power.
+    shortfall: Num,
+  },
+  /// The cost of the order could not be estimated, e.g., because no
+  /// quote was available.
+  Undetermined,
+}
+
+impl Verdict {
+  /// Check whether the order is expected to fit within the available
+  /// buying power.
+  #[inline]
+  pub fn is_sufficient(&self) -> bool {
+    matches!(self, Self::Sufficient { .. })
+  }
+}
+
+
+/// An error as reported by [`check`].
+#[derive(Debug, Error)]
+pub enum CheckError {
+  /// The account information could not be retrieved.
+  #[error("failed to retrieve account information")]
+  Account(#[source] RequestError<account::GetError>),
+  /// The latest quote could not be retrieved.
+  #[error("failed to retrieve latest quote")]
+  Quote(#[source] RequestError<last_quote::GetError>),
+}
+
+
+/// Estimate the cost of an order based on the provided quote.
+///
+/// Limit and stop limit orders are estimated using their limit price.
+/// All other orders are estimated using the ask price for buys and the
+/// bid price for sells.
+fn estimate_cost(quote: &Quote, request: &OrderReq) -> Option<Num> {
+  let quantity = match &request.amount {
+    Amount::Notional { notional } => return Some(notional.clone()),
+    Amount::Quantity { quantity } => quantity,
+  };
+
+  let price = match (request.type_, &request.limit_price) {
+    (Type::Limit, Some(limit)) | (Type::StopLimit, Some(limit)) => limit,
+    _ => match request.side {
+      Side::Buy => &quote.ask_price,
+      Side::Sell => &quote.bid_price,
+    },
+  };
+
+  if price.is_positive() {
+    Some(money::notional(price, quantity))
+  } else {
+    None
+  }
+}
+
+
+/// Estimate whether an order fits within the account's buying power,
+/// based on the provided latest quote for the order's symbol.
+///
+/// Note that sell orders closing an existing long position do not
+/// consume buying power. The check is only meaningful for sell orders
+/// that open (or extend) a short position.
+pub fn estimate(account: &Account, quote: &Quote, request: &OrderReq) -> Verdict {
+  match estimate_cost(quote, request) {
+    Some(cost) if cost <= account.buying_power => Verdict::Sufficient {
+      remaining: &account.buying_power - &cost,
+      cost,
+    },
+    Some(cost) => Verdict::Insufficient {
+      shortfall: &cost - &account.buying_power,
+      cost,
+    },
+    None => Verdict::Undetermined,
+  }
+}
+
+
+/// Check whether an order fits within the account's current buying
+/// power, retrieving the account information as well as the latest
+/// quote for the order's symbol.
+///
+/// Orders referencing an asset by ID result in a
+/// [`Verdict::Undetermined`] verdict. See [`estimate`] for additional
+/// details.
+pub async fn check(client: &Client, request: &OrderReq) -> Result<Verdict, CheckError> {
+  let symbol = match &request.symbol {
+    Symbol::Sym(symbol) | Symbol::SymExchg(symbol, ..) | Symbol::SymExchgCls(symbol, ..) => {
+      symbol.clone()
+    },
+    Symbol::Id(..) => return Ok(Verdict::Undetermined),
+  };
+
+  let account = client
+    .issue::<account::Get>(&())
+    .await
+    .map_err(CheckError::Account)?;
+  let quote_req = LastQuoteReqInit::default().init(symbol);
+  let quote = client
+    .issue::<last_quote::Get>(&quote_req)
+    .await
+    .map_err(CheckError::Quote)?;
+
+  Ok(estimate(&account, &quote, request))
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::from_str as from_json;
+  use serde_json::json;
+
+  use test_log::test;
+
+  use crate::api::v2::order::OrderReqInit;
+  use crate::live;
+  use crate::test_util;
+
+
+  /// Create an `Account` object with the given buying power.
+  fn account(buying_power: u64) -> Account {
+    test_util::account(json!({"buying_power": buying_power.to_string()}))
+  }
+
+  /// Create a `Quote` object with the given bid and ask prices.
+  fn quote(bid: u64, ask: u64) -> Quote {
+    let json = json!({
+      "t": "2021-02-06T13:35:08.946977536Z",
+      "ax": "C",
+      "ap": ask,
+      "as": 1,
+      "bx": "H",
+      "bp": bid,
+      "bs": 1,
+      "c": ["R"],
+    });
+    from_json(&json.to_string()).unwrap()
+  }
+
+
+  /// Check that we estimate the buying power requirements of orders
+  /// correctly.
+  #[test]
+  fn estimate_orders() {
+    let account = account(1000);
+    let spy = quote(99, 101);
+
+    let request = OrderReqInit::default().init("SPY", Side::Buy, Amount::quantity(9));
+    let expected = Verdict::Sufficient {
+      cost: Num::from(909),
+      remaining: Num::from(91),
+    };
+    assert_eq!(estimate(&account, &spy, &request), expected);
+
+    let request = OrderReqInit::default().init("SPY", Side::Sell, Amount::quantity(11));
+    let expected = Verdict::Insufficient {
+      cost: Num::from(1089),
+      shortfall: Num::from(89),
+    };
+    assert_eq!(estimate(&account, &spy, &request), expected);
+
+    let request = OrderReqInit {
+      type_: Type::Limit,
+      limit_price: Some(Num::from(90)),
+      ..Default::default()
+    }
+    .init("SPY", Side::Buy, Amount::quantity(11));
+    assert!(estimate(&account, &spy, &request).is_sufficient());
+
+    let request = OrderReqInit::default().init("SPY", Side::Buy, Amount::notional(1001));
+    assert!(!estimate(&account, &spy, &request).is_sufficient());
+
+    let request = OrderReqInit::default().init("SPY", Side::Buy, Amount::quantity(1));
+    assert_eq!(
+      estimate(&account, &quote(0, 0), &request),
+      Verdict::Undetermined
+    );
+  }
+
+  /// Check that we can check an order against the account's buying
+  /// power.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn check_order() {
+    let client = live::client().unwrap();
+
+    let request = OrderReqInit::default().init("SPY", Side::Buy, Amount::quantity(1_000_000));
+    let verdict = check(&client, &request).await.unwrap();
+    assert!(!verdict.is_sufficient());
+  }
+}
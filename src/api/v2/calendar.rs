@@ -94,13 +94,12 @@ Endpoint! {
 mod tests {
   use super::*;
 
-  use crate::api_info::ApiInfo;
-  use crate::Client;
-
   use serde_json::from_str as from_json;
 
   use test_log::test;
 
+  use crate::live;
+
 
   /// Check that we error out as expected when failing to parse an
   /// `OpenClose` object because the time format is unexpected.
@@ -130,9 +129,9 @@ mod tests {
   /// Check that we can retrieve the market calendar for a specific time
   /// frame.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn get() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let start = NaiveDate::from_ymd(2020, 4, 6);
     let end = NaiveDate::from_ymd(2020, 4, 10);
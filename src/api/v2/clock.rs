@@ -2,11 +2,15 @@
 // SPDX-License-Identifier: GPL-3.0-or-later
 
 use chrono::DateTime;
+use chrono::Duration;
 use chrono::Utc;
 
 use serde::Deserialize;
 use serde::Serialize;
 
+use crate::util::datetime_from_str;
+use crate::Client;
+use crate::RequestError;
 use crate::Str;
 
 
@@ -17,13 +21,13 @@ pub struct Clock {
   #[serde(rename = "is_open")]
   pub open: bool,
   /// The current time.
-  #[serde(rename = "timestamp")]
+  #[serde(rename = "timestamp", deserialize_with = "datetime_from_str")]
   pub current: DateTime<Utc>,
   /// The next market opening time stamp.
-  #[serde(rename = "next_open")]
+  #[serde(rename = "next_open", deserialize_with = "datetime_from_str")]
   pub next_open: DateTime<Utc>,
   /// The next market closing time stamp.
-  #[serde(rename = "next_close")]
+  #[serde(rename = "next_close", deserialize_with = "datetime_from_str")]
   pub next_close: DateTime<Utc>,
 }
 
@@ -44,6 +48,60 @@ Endpoint! {
 }
 
 
+/// An estimate of the offset between the local clock and Alpaca's.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Skew {
+  /// The offset to add to the local time to get Alpaca's time, i.e.,
+  /// positive if the local clock is behind.
+  pub offset: Duration,
+  /// The round trip time of the request the estimate is based on.
+  pub round_trip: Duration,
+}
+
+impl Skew {
+  /// Estimate the skew from a server time stamp and the local times at
+  /// which the request was sent and the response received.
+  fn estimate(sent: DateTime<Utc>, received: DateTime<Utc>, server: DateTime<Utc>) -> Self {
+    let round_trip = received - sent;
+    // We assume that the server took its time stamp half way through
+    // the round trip.
+    let local = sent + round_trip / 2;
+
+    Self {
+      offset: server - local,
+      round_trip,
+    }
+  }
+}
+
+
+/// Measure the offset between the local clock and Alpaca's, by
+/// querying the market clock the given number of times.
+///
+/// The estimate with the lowest round trip time is reported, as it is
+/// least affected by network delays. Note that the precision is
+/// limited by that of the time stamps reported by Alpaca.
+pub async fn measure_skew(client: &Client, samples: usize) -> Result<Skew, RequestError<GetError>> {
+  async fn sample(client: &Client) -> Result<Skew, RequestError<GetError>> {
+    let sent = Utc::now();
+    let clock = client.issue::<Get>(&()).await?;
+    let received = Utc::now();
+
+    Ok(Skew::estimate(sent, received, clock.current))
+  }
+
+  let mut best = sample(client).await?;
+  for _ in 1..samples {
+    let skew = sample(client).await?;
+    if skew.round_trip < best.round_trip {
+      best = skew
+    }
+  }
+  Ok(best)
+}
+
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -60,6 +118,7 @@ mod tests {
   use crate::api::API_BASE_URL;
   use crate::api_info::ApiInfo;
   use crate::Client;
+  use crate::live;
   use crate::RequestError;
 
 
@@ -82,11 +141,27 @@ mod tests {
     );
   }
 
+  /// Check that we estimate clock skew as expected.
+  #[test]
+  fn estimate_skew() {
+    let sent = DateTime::<Utc>::from_str("2022-04-01T12:00:00Z").unwrap();
+    let received = sent + Duration::milliseconds(100);
+    let server = sent + Duration::seconds(2);
+
+    let skew = Skew::estimate(sent, received, server);
+    assert_eq!(skew.round_trip, Duration::milliseconds(100));
+    assert_eq!(skew.offset, Duration::milliseconds(1950));
+
+    let server = sent - Duration::seconds(2);
+    let skew = Skew::estimate(sent, received, server);
+    assert_eq!(skew.offset, Duration::milliseconds(-2050));
+  }
+
   /// Verify that we can retrieve the current market clock.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn current_market_clock() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let clock = client.issue::<Get>(&()).await.unwrap();
 
     // We want to sanitize the current time being reported at least to a
@@ -108,6 +183,18 @@ mod tests {
     }
   }
 
+  /// Check that we can measure the skew between the local clock and
+  /// Alpaca's.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn measure_clock_skew() {
+    let client = live::client().unwrap();
+    let skew = measure_skew(&client, 3).await.unwrap();
+
+    assert!(skew.round_trip >= Duration::zero());
+    assert!(skew.offset.num_hours().abs() < 1);
+  }
+
   /// Check that we get back the expected error when requesting the
   /// market clock with invalid credentials.
   #[test(tokio::test)]
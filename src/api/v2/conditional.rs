@@ -0,0 +1,163 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use http::header::ETAG;
+use http::header::IF_NONE_MATCH;
+use http::HeaderMap;
+use http::HeaderValue;
+use http::StatusCode;
+use http_endpoint::Endpoint;
+
+use hyper::body::Bytes;
+
+use tracing::debug;
+
+use crate::api::v2::assets;
+use crate::api::v2::calendar;
+use crate::Client;
+use crate::RequestError;
+
+
+/// A trait for endpoints whose data change rarely and that support
+/// conditional requests based on entity tags (ETags).
+pub trait Conditional: Endpoint {}
+
+impl Conditional for assets::Get {}
+
+impl Conditional for calendar::Get {}
+
+
+/// A cached response along with the entity tag it was reported with.
+#[derive(Debug)]
+struct Entry {
+  /// The entity tag of the response.
+  etag: HeaderValue,
+  /// The raw response body.
+  body: Bytes,
+}
+
+
+/// A client-side cache for responses of [`Conditional`] endpoints.
+///
+/// Requests issued through the cache carry an `If-None-Match` header
+/// with the entity tag of a previously retrieved response, if any. If
+/// the server reports that the data did not change (status 304), the
+/// previously retrieved response is evaluated instead, sparing the
+/// transfer of, for example, the full asset list.
+#[derive(Debug, Default)]
+pub struct ETagCache {
+  /// Cached responses, keyed by request path and query.
+  entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl ETagCache {
+  /// Create a new, empty `ETagCache`.
+  #[inline]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Create the key under which the response to a request is cached.
+  fn key<R>(input: &R::Input) -> Result<String, R::ConversionError>
+  where
+    R: Endpoint,
+  {
+    let path = R::path(input);
+    let query = R::query(input)?.unwrap_or_default();
+    Ok(format!("{}?{}", path, query))
+  }
+
+  /// Issue a request using the provided client, serving the response
+  /// from the cache if the server reports that it is unchanged.
+  pub async fn issue<R>(
+    &self,
+    client: &Client,
+    input: &R::Input,
+  ) -> Result<R::Output, RequestError<R::Error>>
+  where
+    R: Conditional,
+  {
+    let key = Self::key::<R>(input).map_err(|err| RequestError::Endpoint(err.into()))?;
+
+    let mut headers = HeaderMap::new();
+    if let Some(entry) = self.entries.lock().unwrap().get(&key) {
+      let _ = headers.insert(IF_NONE_MATCH, entry.etag.clone());
+    }
+
+    let (status, headers, body) = client.issue_raw::<R>(input, headers).await?;
+    if status == StatusCode::NOT_MODIFIED {
+      if let Some(entry) = self.entries.lock().unwrap().get(&key) {
+        debug!(key = key.as_str(), "response not modified");
+        return R::evaluate(StatusCode::OK, &entry.body).map_err(RequestError::Endpoint)
+      }
+    }
+
+    let output = R::evaluate(status, &body).map_err(RequestError::Endpoint)?;
+    if let Some(etag) = headers.get(ETAG) {
+      let entry = Entry {
+        etag: etag.clone(),
+        body,
+      };
+      let _ = self.entries.lock().unwrap().insert(key, entry);
+    }
+    Ok(output)
+  }
+
+  /// Remove all cached responses.
+  #[inline]
+  pub fn clear(&self) {
+    self.entries.lock().unwrap().clear()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use chrono::NaiveDate;
+
+  use http::StatusCode;
+
+  use test_log::test;
+
+  use crate::test_util::MockServer;
+  use crate::test_util::Response;
+
+
+  /// Check that we send the entity tag of a previous response along
+  /// and serve the cached response when the server reports it as
+  /// unmodified.
+  #[test(tokio::test)]
+  async fn serve_not_modified() {
+    let body = r#"[{"date":"2020-04-09","open":"09:30","close":"16:00"}]"#;
+    let server = MockServer::serve([
+      Response::json(body).header("etag", "\"abc\""),
+      Response::new(StatusCode::NOT_MODIFIED).header("etag", "\"abc\""),
+    ]);
+    let client = server.client();
+    let cache = ETagCache::new();
+
+    let start = NaiveDate::from_ymd_opt(2020, 4, 9).unwrap();
+    let end = NaiveDate::from_ymd_opt(2020, 4, 10).unwrap();
+    let request = calendar::CalendarReq::from(start..end);
+
+    let first = cache
+      .issue::<calendar::Get>(&client, &request)
+      .await
+      .unwrap();
+    let second = cache
+      .issue::<calendar::Get>(&client, &request)
+      .await
+      .unwrap();
+    assert_eq!(first.len(), 1);
+    assert_eq!(first, second);
+
+    let requests = server.join();
+    assert_eq!(requests[0].header("if-none-match"), None);
+    assert_eq!(requests[1].header("if-none-match"), Some("\"abc\""));
+  }
+}
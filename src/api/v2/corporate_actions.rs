@@ -0,0 +1,442 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashSet;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::time::Duration as StdDuration;
+
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::Utc;
+
+use num_decimal::Num;
+
+use futures::stream::unfold;
+use futures::Stream;
+
+use thiserror::Error;
+
+use tokio::time::sleep;
+
+use crate::api::v2::announcements;
+use crate::api::v2::announcements::Action;
+use crate::api::v2::announcements::Announcement;
+use crate::api::v2::announcements::AnnouncementsReqInit;
+use crate::api::v2::announcements::Type;
+use crate::api::v2::position::Position;
+use crate::api::v2::position::Side;
+use crate::api::v2::positions;
+use crate::Client;
+use crate::RequestError;
+
+
+/// The maximum number of days the announcements endpoint allows us to
+/// query at once.
+const MAX_HORIZON_DAYS: i64 = 90;
+
+
+/// A notice about a corporate action affecting a held position.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Notice {
+  /// The symbol of the held position affected.
+  pub symbol: String,
+  /// The announcement of the corporate action.
+  pub announcement: Announcement,
+}
+
+impl Notice {
+  /// Retrieve the date on which the corporate action takes effect, if
+  /// known.
+  pub fn effective_date(&self) -> Option<NaiveDate> {
+    self.announcement.ex_date.or(self.announcement.payable_date)
+  }
+}
+
+impl Display for Notice {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+    let announcement = &self.announcement;
+
+    match &announcement.action {
+      Action::Split { old_rate, new_rate } => {
+        let verb = if announcement.sub_type.contains("reverse") {
+          "reverse splits"
+        } else {
+          "splits"
+        };
+        match (new_rate, old_rate) {
+          (Some(new), Some(old)) => write!(fmt, "{} {} {}:{}", self.symbol, verb, new, old)?,
+          _ => write!(fmt, "{} {}", self.symbol, verb)?,
+        }
+      },
+      Action::Merger { target_symbol, .. } if self.symbol == announcement.initiating_symbol => {
+        let target = target_symbol.as_deref().unwrap_or("?");
+        write!(fmt, "{} is acquiring {}", self.symbol, target)?
+      },
+      Action::Merger { .. } => write!(
+        fmt,
+        "{} is being acquired by {}",
+        self.symbol, announcement.initiating_symbol
+      )?,
+      Action::Spinoff { target_symbol, .. } => {
+        let target = target_symbol.as_deref().unwrap_or("?");
+        write!(fmt, "{} spins off {}", self.symbol, target)?
+      },
+      Action::Dividend { cash, .. } => match cash {
+        Some(cash) => write!(fmt, "{} pays a dividend of {} per share", self.symbol, cash)?,
+        None => write!(fmt, "{} pays a dividend", self.symbol)?,
+      },
+    }
+
+    match self.effective_date() {
+      Some(date) => write!(fmt, " effective {}", date.format("%A, %Y-%m-%d")),
+      None => Ok(()),
+    }
+  }
+}
+
+
+/// Create notices for all announcements affecting any of the given
+/// positions.
+///
+/// An announcement affects a position if the position's symbol is
+/// either the initiating or the target symbol of the corporate action.
+/// Notices are ordered by effective date.
+pub fn notices(positions: &[Position], announcements: &[Announcement]) -> Vec<Notice> {
+  let held = positions
+    .iter()
+    .map(|position| position.symbol.as_str())
+    .collect::<HashSet<_>>();
+
+  let mut notices = announcements
+    .iter()
+    .flat_map(|announcement| {
+      let initiating = Some(announcement.initiating_symbol.as_str());
+      let target = announcement
+        .action
+        .target_symbol()
+        .filter(|target| Some(*target) != initiating);
+
+      initiating
+        .into_iter()
+        .chain(target)
+        .filter(|symbol| held.contains(symbol))
+        .map(move |symbol| Notice {
+          symbol: symbol.to_string(),
+          announcement: announcement.clone(),
+        })
+    })
+    .collect::<Vec<_>>();
+
+  notices.sort_by_key(|notice| (notice.effective_date(), notice.symbol.clone()));
+  notices
+}
+
+
+/// An upcoming dividend payment for a held position.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Dividend {
+  /// The symbol of the held position.
+  pub symbol: String,
+  /// The number of shares held.
+  pub quantity: Num,
+  /// The first date on which the security trades without the dividend.
+  pub ex_date: Option<NaiveDate>,
+  /// The date by which shares need to be held to be eligible.
+  pub record_date: Option<NaiveDate>,
+  /// The date on which the dividend is paid out.
+  pub payable_date: Option<NaiveDate>,
+  /// The cash amount paid per share, if any.
+  pub cash: Option<Num>,
+  /// The estimated amount received for the position, based on the
+  /// number of shares currently held. The amount is negative for short
+  /// positions, which are charged the dividend.
+  pub estimated_amount: Option<Num>,
+}
+
+
+/// Create a dividend calendar for the given positions from the given
+/// announcements.
+///
+/// Entries are ordered by ex-date.
+pub fn dividends(positions: &[Position], announcements: &[Announcement]) -> Vec<Dividend> {
+  let mut dividends = announcements
+    .iter()
+    .filter_map(|announcement| {
+      let cash = match &announcement.action {
+        Action::Dividend { cash, .. } => cash,
+        _ => return None,
+      };
+      let position = positions
+        .iter()
+        .find(|position| position.symbol == announcement.initiating_symbol)?;
+      let estimated_amount = cash.as_ref().map(|cash| {
+        let amount = cash * &position.quantity;
+        match position.side {
+          Side::Long => amount,
+          Side::Short => -amount,
+        }
+      });
+
+      Some(Dividend {
+        symbol: position.symbol.clone(),
+        quantity: position.quantity.clone(),
+        ex_date: announcement.ex_date,
+        record_date: announcement.record_date,
+        payable_date: announcement.payable_date,
+        cash: cash.clone(),
+        estimated_amount,
+      })
+    })
+    .collect::<Vec<_>>();
+
+  dividends.sort_by_key(|dividend| (dividend.ex_date, dividend.symbol.clone()));
+  dividends
+}
+
+
+/// An error as reported by [`report`], [`watch`], and
+/// [`fetch_dividends`].
+#[derive(Debug, Error)]
+pub enum NoticeError {
+  /// The held positions could not be retrieved.
+  #[error("failed to retrieve positions")]
+  Positions(#[source] RequestError<positions::GetError>),
+  /// The corporate action announcements could not be retrieved.
+  #[error("failed to retrieve corporate action announcements")]
+  Announcements(#[source] RequestError<announcements::GetError>),
+}
+
+
+/// Retrieve notices about corporate actions affecting currently held
+/// positions that were announced for the period from today until
+/// `horizon` from now.
+///
+/// The horizon is capped at 90 days, the maximum range supported by the
+/// announcements endpoint.
+pub async fn report(client: &Client, horizon: Duration) -> Result<Vec<Notice>, NoticeError> {
+  let types = [Type::Dividend, Type::Merger, Type::Spinoff, Type::Split];
+  let (positions, announcements) = fetch(client, types, horizon).await?;
+  Ok(notices(&positions, &announcements))
+}
+
+
+/// Retrieve the dividend calendar for currently held positions for the
+/// period from today until `horizon` from now.
+///
+/// See [`report`] for a description of `horizon`.
+pub async fn fetch_dividends(
+  client: &Client,
+  horizon: Duration,
+) -> Result<Vec<Dividend>, NoticeError> {
+  let (positions, announcements) = fetch(client, [Type::Dividend], horizon).await?;
+  Ok(dividends(&positions, &announcements))
+}
+
+
+/// Retrieve the currently held positions along with announcements of
+/// the given types for the period from today until `horizon` from now.
+async fn fetch<I>(
+  client: &Client,
+  types: I,
+  horizon: Duration,
+) -> Result<(Vec<Position>, Vec<Announcement>), NoticeError>
+where
+  I: IntoIterator<Item = Type>,
+{
+  let positions = client
+    .issue::<positions::Get>(&())
+    .await
+    .map_err(NoticeError::Positions)?;
+
+  if positions.is_empty() {
+    return Ok((positions, Vec::new()))
+  }
+
+  let since = Utc::now().naive_utc().date();
+  let until = since + horizon.min(Duration::days(MAX_HORIZON_DAYS));
+  let request = AnnouncementsReqInit::default().init(types, since, until);
+  let announcements = client
+    .issue::<announcements::Get>(&request)
+    .await
+    .map_err(NoticeError::Announcements)?;
+
+  Ok((positions, announcements))
+}
+
+
+/// Poll for corporate actions affecting currently held positions every
+/// `interval`, reporting each notice once.
+///
+/// See [`report`] for a description of `horizon`. Errors are reported
+/// as part of the stream and polling resumes after the next interval.
+pub fn watch(
+  client: &Client,
+  interval: StdDuration,
+  horizon: Duration,
+) -> impl Stream<Item = Result<Notice, NoticeError>> + '_ {
+  let state = (HashSet::new(), VecDeque::new(), true);
+
+  unfold(
+    state,
+    move |(mut seen, mut pending, mut first)| async move {
+      loop {
+        if let Some(notice) = pending.pop_front() {
+          return Some((Ok(notice), (seen, pending, first)))
+        }
+
+        if !first {
+          let () = sleep(interval).await;
+        }
+        first = false;
+
+        match report(client, horizon).await {
+          Ok(notices) => {
+            pending.extend(notices.into_iter().filter(|notice| {
+              seen.insert((notice.announcement.id.clone(), notice.symbol.clone()))
+            }))
+          },
+          Err(err) => return Some((Err(err), (seen, pending, first))),
+        }
+      }
+    },
+  )
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::from_value;
+  use serde_json::json;
+
+  use test_log::test;
+
+
+  /// Create a `Position` for the given symbol.
+  fn position(symbol: &str) -> Position {
+    from_value(json!({
+      "asset_id": "904837e3-3b76-47ec-b432-046db621571b",
+      "symbol": symbol,
+      "exchange": "NASDAQ",
+      "asset_class": "us_equity",
+      "avg_entry_price": "100.0",
+      "qty": "5",
+      "side": "long",
+      "market_value": "600.0",
+      "cost_basis": "500.0",
+      "unrealized_pl": "100.0",
+      "unrealized_plpc": "0.20",
+      "unrealized_intraday_pl": "10.0",
+      "unrealized_intraday_plpc": "0.0084",
+      "current_price": "120.0",
+      "lastday_price": "119.0",
+      "change_today": "0.0084",
+    }))
+    .unwrap()
+  }
+
+  /// Create an `Announcement` from the given data.
+  fn announcement(
+    type_: &str,
+    sub_type: &str,
+    initiating: &str,
+    target: Option<&str>,
+    ex_date: &str,
+  ) -> Announcement {
+    from_value(json!({
+      "id": format!("{}-{}", initiating, type_),
+      "corporate_action_id": "F58684224_XY37",
+      "ca_type": type_,
+      "ca_sub_type": sub_type,
+      "initiating_symbol": initiating,
+      "target_symbol": target,
+      "ex_date": ex_date,
+      "cash": "0.23",
+      "old_rate": "1",
+      "new_rate": "4",
+    }))
+    .unwrap()
+  }
+
+
+  /// Check that we only create notices for held positions.
+  #[test]
+  fn create_notices() {
+    let positions = [position("AAPL"), position("ATVI")];
+    let announcements = [
+      announcement("split", "stock_split", "AAPL", None, "2020-08-31"),
+      announcement("split", "stock_split", "TSLA", None, "2020-08-31"),
+      announcement(
+        "merger",
+        "merger_update",
+        "MSFT",
+        Some("ATVI"),
+        "2022-01-18",
+      ),
+      announcement("dividend", "cash", "AAPL", None, "2020-08-07"),
+    ];
+
+    let notices = notices(&positions, &announcements);
+    let messages = notices.iter().map(ToString::to_string).collect::<Vec<_>>();
+    assert_eq!(
+      messages,
+      vec![
+        "AAPL pays a dividend of 0.23 per share effective Friday, 2020-08-07",
+        "AAPL splits 4:1 effective Monday, 2020-08-31",
+        "ATVI is being acquired by MSFT effective Tuesday, 2022-01-18",
+      ]
+    );
+  }
+
+  /// Check that acquirers are notified about mergers as well.
+  #[test]
+  fn notify_acquirer() {
+    let positions = [position("MSFT")];
+    let announcements = [announcement(
+      "merger",
+      "merger_update",
+      "MSFT",
+      Some("ATVI"),
+      "2022-01-18",
+    )];
+
+    let notices = notices(&positions, &announcements);
+    assert_eq!(notices.len(), 1);
+    assert_eq!(
+      notices[0].to_string(),
+      "MSFT is acquiring ATVI effective Tuesday, 2022-01-18"
+    );
+  }
+
+  /// Check that we create a dividend calendar for held positions.
+  #[test]
+  fn create_dividend_calendar() {
+    let mut short = position("T");
+    short.side = Side::Short;
+    let positions = [position("AAPL"), short];
+    let announcements = [
+      announcement("dividend", "cash", "T", None, "2020-10-08"),
+      announcement("dividend", "cash", "AAPL", None, "2020-08-07"),
+      announcement("dividend", "cash", "MSFT", None, "2020-08-19"),
+      announcement("split", "stock_split", "AAPL", None, "2020-08-31"),
+    ];
+
+    let dividends = dividends(&positions, &announcements);
+    assert_eq!(dividends.len(), 2);
+    assert_eq!(dividends[0].symbol, "AAPL");
+    assert_eq!(
+      dividends[0].ex_date,
+      Some(NaiveDate::from_ymd_opt(2020, 8, 7).unwrap())
+    );
+    assert_eq!(dividends[0].cash, Some(Num::new(23, 100)));
+    assert_eq!(dividends[0].estimated_amount, Some(Num::new(115, 100)));
+    assert_eq!(dividends[1].symbol, "T");
+    assert_eq!(dividends[1].estimated_amount, Some(-Num::new(115, 100)));
+  }
+}
@@ -0,0 +1,364 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::convert::Infallible;
+use std::time::Duration as StdDuration;
+
+use chrono::DateTime;
+use chrono::Datelike as _;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::Utc;
+use chrono::Weekday;
+
+use num_decimal::Num;
+
+use thiserror::Error;
+
+use tokio::time::sleep;
+
+use crate::api::v2::calendar;
+use crate::api::v2::clock;
+use crate::api::v2::order;
+use crate::api::v2::order::Amount;
+use crate::api::v2::order::Order;
+use crate::api::v2::order::OrderReq;
+use crate::api::v2::order::OrderReqInit;
+use crate::api::v2::order::Side;
+use crate::Client;
+use crate::RequestError;
+
+
+/// The number of days to look back for the previous trading session.
+/// This should cover any combination of weekends and holidays.
+const LOOKBACK_DAYS: i64 = 14;
+
+
+/// The frequency at which a [`Schedule`] places orders.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Frequency {
+  /// Place an order every trading day.
+  Daily,
+  /// Place an order every week on the given day.
+  Weekly(Weekday),
+  /// Place an order every month on the given day. Days past the end of
+  /// a month refer to its last day.
+  Monthly(u32),
+}
+
+impl Frequency {
+  /// Check whether the given calendar day is a nominal order date.
+  fn matches(&self, date: NaiveDate) -> bool {
+    match self {
+      Self::Daily => true,
+      Self::Weekly(weekday) => date.weekday() == *weekday,
+      Self::Monthly(day) => {
+        let last = last_day_of_month(date);
+        date.day() == (*day).clamp(1, last)
+      },
+    }
+  }
+}
+
+
+/// Retrieve the last day of the month the given date belongs to.
+fn last_day_of_month(date: NaiveDate) -> u32 {
+  let (year, month) = if date.month() == 12 {
+    (date.year() + 1, 1)
+  } else {
+    (date.year(), date.month() + 1)
+  };
+  let first = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+  (first - Duration::days(1)).day()
+}
+
+
+/// A recurring buy order for a fixed notional amount, as used for
+/// dollar-cost averaging.
+///
+/// Orders are placed at the market open of the trading session on a
+/// nominal order date. If the market is closed on a nominal date, e.g.,
+/// because of a weekend or holiday, the order is placed in the next
+/// session instead.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schedule {
+  /// An identifier for the schedule.
+  ///
+  /// The identifier is used for deriving the client order IDs of the
+  /// orders placed, which is what makes order submission idempotent.
+  /// It should be unique among all schedules and stay the same across
+  /// restarts of the program. Given the maximum length of client order
+  /// IDs, it should not exceed 35 characters.
+  pub id: String,
+  /// The symbol of the asset to buy.
+  pub symbol: String,
+  /// The dollar amount to buy.
+  pub notional: Num,
+  /// The frequency at which to place orders.
+  pub frequency: Frequency,
+}
+
+impl Schedule {
+  /// Check whether an order is due in the trading session on
+  /// `session`, given that the previous one was on `previous`.
+  pub fn is_due(&self, previous: NaiveDate, session: NaiveDate) -> bool {
+    let mut date = previous + Duration::days(1);
+    while date <= session {
+      if self.frequency.matches(date) {
+        return true
+      }
+      date += Duration::days(1);
+    }
+    false
+  }
+
+  /// Retrieve the client order ID of the order placed in the trading
+  /// session on the given date.
+  pub fn client_order_id(&self, session: NaiveDate) -> String {
+    format!("dca-{}-{}", self.id, session.format("%Y%m%d"))
+  }
+
+  /// Create the order request for the trading session on the given
+  /// date.
+  pub fn order(&self, session: NaiveDate) -> OrderReq {
+    OrderReqInit {
+      client_order_id: Some(self.client_order_id(session)),
+      ..Default::default()
+    }
+    .init(
+      self.symbol.clone(),
+      Side::Buy,
+      Amount::notional(self.notional.clone()),
+    )
+  }
+}
+
+
+/// An error as reported by [`execute`].
+#[derive(Debug, Error)]
+pub enum ExecuteError {
+  /// Checking for a previously submitted order failed.
+  #[error("failed to look up previously submitted order")]
+  Lookup(#[source] RequestError<order::GetByClientIdError>),
+  /// The order could not be submitted.
+  #[error("failed to submit order")]
+  Submit(#[source] RequestError<order::PostError>),
+}
+
+
+/// An error as reported by [`run`].
+#[derive(Debug, Error)]
+pub enum RunError {
+  /// The market clock could not be retrieved.
+  #[error("failed to retrieve market clock")]
+  Clock(#[source] RequestError<clock::GetError>),
+  /// The market calendar could not be retrieved.
+  #[error("failed to retrieve market calendar")]
+  Calendar(#[source] RequestError<calendar::GetError>),
+}
+
+
+/// Place the order of a schedule for the trading session on the given
+/// date, unless it has been placed already.
+///
+/// If an order with the schedule's client order ID for the session
+/// exists already, it is reported instead of submitting a new one.
+pub async fn execute(
+  client: &Client,
+  schedule: &Schedule,
+  session: NaiveDate,
+) -> Result<Order, ExecuteError> {
+  let client_order_id = schedule.client_order_id(session);
+  match client.issue::<order::GetByClientId>(&client_order_id).await {
+    Ok(order) => return Ok(order),
+    Err(RequestError::Endpoint(order::GetByClientIdError::NotFound(_))) => (),
+    Err(err) => return Err(ExecuteError::Lookup(err)),
+  }
+
+  client
+    .issue::<order::Post>(&schedule.order(session))
+    .await
+    .map_err(ExecuteError::Submit)
+}
+
+
+/// Convert the time until `until` into a duration to sleep for.
+fn duration_until(now: DateTime<Utc>, until: DateTime<Utc>) -> StdDuration {
+  // We sleep for at least a second, so that we do not spin should
+  // the market clock lag behind slightly.
+  (until - now)
+    .to_std()
+    .unwrap_or_default()
+    .max(StdDuration::from_secs(1))
+}
+
+
+/// Retrieve the date of the trading session preceding the one on the
+/// given date.
+async fn previous_session(client: &Client, session: NaiveDate) -> Result<NaiveDate, RunError> {
+  let request = calendar::CalendarReq {
+    start: session - Duration::days(LOOKBACK_DAYS),
+    end: session,
+  };
+  let calendar = client
+    .issue::<calendar::Get>(&request)
+    .await
+    .map_err(RunError::Calendar)?;
+
+  let previous = calendar
+    .iter()
+    .map(|open_close| open_close.date)
+    .filter(|date| *date < session)
+    .max()
+    .unwrap_or_else(|| session - Duration::days(1));
+  Ok(previous)
+}
+
+
+/// Run the given schedules, placing their orders as they become due.
+///
+/// The function waits for the next market open, places all due orders,
+/// and repeats. The outcome of each order placement is reported via
+/// `report`. Order placement is idempotent (see [`execute`]), meaning
+/// that a program restarted during a trading session will not place
+/// duplicate orders. Orders due in sessions that passed while the
+/// program was not running are not placed.
+///
+/// The function only returns if the market clock or calendar could not
+/// be retrieved.
+pub async fn run<F>(
+  client: &Client,
+  schedules: &[Schedule],
+  mut report: F,
+) -> Result<Infallible, RunError>
+where
+  F: FnMut(&Schedule, Result<Order, ExecuteError>),
+{
+  loop {
+    let clock = client
+      .issue::<clock::Get>(&())
+      .await
+      .map_err(RunError::Clock)?;
+
+    if clock.open {
+      // Regular trading hours of US exchanges fall on the same day in
+      // UTC as in exchange local time.
+      let session = clock.current.naive_utc().date();
+      let previous = previous_session(client, session).await?;
+
+      for schedule in schedules {
+        if schedule.is_due(previous, session) {
+          let result = execute(client, schedule, session).await;
+          report(schedule, result);
+        }
+      }
+    }
+
+    // While the market is open, the next open refers to the following
+    // trading session.
+    let () = sleep(duration_until(clock.current, clock.next_open)).await;
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use test_log::test;
+
+  use crate::live;
+
+
+  /// Create a `NaiveDate` from the given year, month, and day.
+  fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+  }
+
+  /// Create a `Schedule` with the given frequency.
+  fn schedule(frequency: Frequency) -> Schedule {
+    Schedule {
+      id: "voo".to_string(),
+      symbol: "VOO".to_string(),
+      notional: Num::from(100),
+      frequency,
+    }
+  }
+
+
+  /// Check that weekly orders are placed in the next session if the
+  /// market is closed on the nominal date.
+  #[test]
+  fn weekly_schedule() {
+    let schedule = schedule(Frequency::Weekly(Weekday::Mon));
+
+    // Monday, 2022-06-13.
+    assert!(schedule.is_due(date(2022, 6, 10), date(2022, 6, 13)));
+    assert!(!schedule.is_due(date(2022, 6, 13), date(2022, 6, 14)));
+    // Monday, 2022-09-05, was Labor Day.
+    assert!(schedule.is_due(date(2022, 9, 2), date(2022, 9, 6)));
+    assert!(!schedule.is_due(date(2022, 9, 6), date(2022, 9, 7)));
+  }
+
+  /// Check that monthly orders refer to the last day of shorter months.
+  #[test]
+  fn monthly_schedule() {
+    let schedule = schedule(Frequency::Monthly(31));
+
+    assert!(schedule.is_due(date(2022, 1, 28), date(2022, 1, 31)));
+    // Monday, 2022-02-28.
+    assert!(!schedule.is_due(date(2022, 2, 24), date(2022, 2, 25)));
+    assert!(schedule.is_due(date(2022, 2, 25), date(2022, 2, 28)));
+    // Saturday, 2022-04-30.
+    assert!(schedule.is_due(date(2022, 4, 29), date(2022, 5, 2)));
+    assert!(!schedule.is_due(date(2022, 4, 28), date(2022, 4, 29)));
+    // Saturday, 2022-12-31.
+    assert!(schedule.is_due(date(2022, 12, 30), date(2023, 1, 3)));
+  }
+
+  /// Check that daily orders are placed once per session.
+  #[test]
+  fn daily_schedule() {
+    let schedule = schedule(Frequency::Daily);
+
+    assert!(schedule.is_due(date(2022, 6, 10), date(2022, 6, 13)));
+    assert!(schedule.is_due(date(2022, 6, 13), date(2022, 6, 14)));
+    assert!(!schedule.is_due(date(2022, 6, 14), date(2022, 6, 14)));
+  }
+
+  /// Check that client order IDs are derived from the schedule and
+  /// session.
+  #[test]
+  fn schedule_client_order_id() {
+    let schedule = schedule(Frequency::Daily);
+    let order = schedule.order(date(2022, 6, 13));
+
+    assert_eq!(order.client_order_id.as_deref(), Some("dca-voo-20220613"));
+    assert_eq!(order.amount, Amount::notional(100));
+    assert_eq!(order.side, Side::Buy);
+  }
+
+  /// Check that executing a schedule twice for the same session only
+  /// places a single order.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn execute_idempotently() {
+    let schedule = Schedule {
+      id: Utc::now().format("%s%f").to_string(),
+      symbol: "SPY".to_string(),
+      notional: Num::from(1),
+      frequency: Frequency::Daily,
+    };
+    let session = Utc::now().naive_utc().date();
+
+    let client = live::client().unwrap();
+    let first = execute(&client, &schedule, session).await.unwrap();
+    let second = execute(&client, &schedule, session).await.unwrap();
+    // The order may have been filled already, in which case we cannot
+    // cancel it.
+    let _ = client.issue::<order::Delete>(&first.id).await;
+
+    assert_eq!(first.id, second.id);
+    assert_eq!(first.client_order_id, schedule.client_order_id(session));
+  }
+}
@@ -0,0 +1,148 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::time::Duration;
+
+use thiserror::Error;
+
+use tokio::time::timeout;
+
+use tracing::warn;
+
+use uuid::Uuid;
+
+use crate::api::v2::order;
+use crate::Client;
+use crate::RequestError;
+
+
+/// The outcome of submitting an order subject to a deadline, as
+/// produced by [`submit_order`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum Submission {
+  /// The order was submitted before the deadline elapsed.
+  Submitted(order::Order),
+  /// The deadline elapsed, but the order was found to have reached
+  /// the server nonetheless.
+  Late(order::Order),
+  /// The deadline elapsed and no order with the contained client order
+  /// ID was found.
+  ///
+  /// Note that the canceled request may still have been in transit at
+  /// the time of the lookup. Callers that have to be certain may want
+  /// to check again later, using the client order ID.
+  NotFound(String),
+}
+
+impl Submission {
+  /// Retrieve the order that reached the server, if any.
+  pub fn into_order(self) -> Option<order::Order> {
+    match self {
+      Self::Submitted(order) | Self::Late(order) => Some(order),
+      Self::NotFound(..) => None,
+    }
+  }
+}
+
+
+/// An error as reported by [`submit_order`].
+#[derive(Debug, Error)]
+pub enum SubmitError {
+  /// The order could not be submitted.
+  #[error("failed to submit order")]
+  Post(#[source] RequestError<order::PostError>),
+  /// The deadline elapsed and looking up the order by its client order
+  /// ID failed.
+  #[error("failed to look up order {0} after the deadline elapsed")]
+  GetByClientId(String, #[source] RequestError<order::GetByClientIdError>),
+}
+
+
+/// Submit an order, canceling the request if it did not complete
+/// within `deadline`.
+///
+/// Once the deadline has elapsed it is unclear whether the order
+/// reached the exchange. To find out, the order is looked up by its
+/// client order ID right away. If the request does not carry a client
+/// order ID, a random one is assigned.
+pub async fn submit_order(
+  client: &Client,
+  request: &order::OrderReq,
+  deadline: Duration,
+) -> Result<Submission, SubmitError> {
+  let client_order_id = request
+    .client_order_id
+    .clone()
+    .unwrap_or_else(|| Uuid::new_v4().as_simple().to_string());
+  let request = order::OrderReq {
+    client_order_id: Some(client_order_id.clone()),
+    ..request.clone()
+  };
+
+  match timeout(deadline, client.issue::<order::Post>(&request)).await {
+    Ok(result) => result.map(Submission::Submitted).map_err(SubmitError::Post),
+    Err(_elapsed) => {
+      warn!(
+        "submission of order {} exceeded deadline of {:?}",
+        client_order_id, deadline
+      );
+
+      match client.issue::<order::GetByClientId>(&client_order_id).await {
+        Ok(order) => Ok(Submission::Late(order)),
+        Err(RequestError::Endpoint(order::GetByClientIdError::NotFound(..))) => {
+          Ok(Submission::NotFound(client_order_id))
+        },
+        Err(err) => Err(SubmitError::GetByClientId(client_order_id, err)),
+      }
+    },
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::json;
+
+  use test_log::test;
+
+  use crate::api::v2::order::Amount;
+  use crate::api::v2::order::Side;
+  use crate::test_util::order_json;
+  use crate::test_util::MockServer;
+  use crate::test_util::Response;
+
+
+  /// Check that an order submission exceeding its deadline is followed
+  /// up by a lookup of the order.
+  #[test(tokio::test)]
+  async fn look_up_late_order() {
+    // Accept the submission but never respond to it.
+    let server = MockServer::start(2, |request| {
+      if request.line.starts_with("POST /v2/orders ") {
+        None
+      } else {
+        let order = order_json(json!({"client_order_id": "abc"}));
+        Some(Response::json(order.to_string()))
+      }
+    });
+    let client = server.client();
+    let request = order::OrderReqInit {
+      client_order_id: Some("abc".to_string()),
+      ..Default::default()
+    }
+    .init("AAPL", Side::Buy, Amount::quantity(1));
+
+    let submission = submit_order(&client, &request, Duration::from_millis(50))
+      .await
+      .unwrap();
+    match submission {
+      Submission::Late(order) => assert_eq!(order.client_order_id, "abc"),
+      submission => panic!("unexpected submission outcome: {:?}", submission),
+    }
+    let requests = server.join();
+    let line = &requests[1].line;
+    assert!(line.contains("client_order_id=abc"), "{}", line);
+  }
+}
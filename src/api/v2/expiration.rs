@@ -0,0 +1,249 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::BTreeSet;
+use std::ops::Bound;
+use std::ops::Range;
+
+use chrono::Datelike as _;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::Weekday;
+
+use crate::api::v2::calendar::OpenClose;
+
+
+/// The number of days by which an expiration may be moved ahead of
+/// its nominal Friday because of market holidays, i.e., to as early as
+/// the Monday of the same week.
+const MAX_HOLIDAY_SHIFT: i64 = 4;
+
+
+/// An option expiration cycle.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Cycle {
+  /// Standard monthly expirations, on the third Friday of each month.
+  Monthly,
+  /// Weekly expirations, on every Friday. This cycle includes the
+  /// monthly expirations.
+  Weekly,
+}
+
+impl Cycle {
+  /// Check whether the given Friday is a nominal expiration date of
+  /// the cycle.
+  fn matches(self, friday: NaiveDate) -> bool {
+    match self {
+      Self::Monthly => (15..=21).contains(&friday.day()),
+      Self::Weekly => true,
+    }
+  }
+}
+
+
+/// Retrieve the first Friday on or after the given date.
+fn next_friday(date: NaiveDate) -> NaiveDate {
+  let friday = Weekday::Fri.num_days_from_monday();
+  let offset = (7 + friday - date.weekday().num_days_from_monday()) % 7;
+  date + Duration::days(i64::from(offset))
+}
+
+
+/// Retrieve the nominal monthly expiration date, i.e., the third
+/// Friday, of the given month.
+pub fn monthly_expiration(year: i32, month: u32) -> Option<NaiveDate> {
+  let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+  Some(next_friday(first) + Duration::days(14))
+}
+
+
+/// The set of trading days, as used for determining option expiration
+/// dates and the time remaining until expiration.
+///
+/// A `TradingCalendar` is created from the market calendar as retrieved
+/// via the [`calendar`][crate::api::v2::calendar] endpoint. All queries
+/// are answered based on the covered range only, so the calendar should
+/// span all dates of interest.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TradingCalendar {
+  /// The trading days.
+  days: BTreeSet<NaiveDate>,
+}
+
+impl TradingCalendar {
+  /// Check whether the market is open on the given date.
+  #[inline]
+  pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+    self.days.contains(&date)
+  }
+
+  /// Move a nominal expiration date to the preceding trading day of
+  /// the same week if the market is closed on it.
+  fn adjust(&self, nominal: NaiveDate) -> Option<NaiveDate> {
+    let earliest = nominal - Duration::days(MAX_HOLIDAY_SHIFT);
+    self.days.range(earliest..=nominal).next_back().copied()
+  }
+
+  /// Enumerate the expiration dates of the given cycle in the provided
+  /// range, in chronological order.
+  ///
+  /// Expirations falling on a market holiday are moved to the
+  /// preceding trading day.
+  pub fn expirations(&self, cycle: Cycle, range: Range<NaiveDate>) -> Vec<NaiveDate> {
+    let mut expirations = Vec::new();
+    let mut friday = next_friday(range.start);
+    let last = range.end + Duration::days(MAX_HOLIDAY_SHIFT);
+
+    while friday < last {
+      if cycle.matches(friday) {
+        if let Some(date) = self.adjust(friday) {
+          if range.contains(&date) {
+            let () = expirations.push(date);
+          }
+        }
+      }
+      friday += Duration::weeks(1);
+    }
+    expirations
+  }
+
+  /// Calculate the number of trading days after `date` up to and
+  /// including `expiration`.
+  pub fn days_to_expiry(&self, date: NaiveDate, expiration: NaiveDate) -> usize {
+    if expiration <= date {
+      return 0
+    }
+
+    self
+      .days
+      .range((Bound::Excluded(date), Bound::Included(expiration)))
+      .count()
+  }
+
+  /// Find the nearest expiration of the given cycle that is at least
+  /// `min_days` trading days after `date`.
+  ///
+  /// `None` is returned if no such expiration is covered by the
+  /// calendar.
+  pub fn nearest_expiration(
+    &self,
+    cycle: Cycle,
+    date: NaiveDate,
+    min_days: usize,
+  ) -> Option<NaiveDate> {
+    let end = *self.days.iter().next_back()? + Duration::days(1);
+    self
+      .expirations(cycle, date..end)
+      .into_iter()
+      .find(|expiration| self.days_to_expiry(date, *expiration) >= min_days)
+  }
+}
+
+impl FromIterator<OpenClose> for TradingCalendar {
+  fn from_iter<I>(iter: I) -> Self
+  where
+    I: IntoIterator<Item = OpenClose>,
+  {
+    Self {
+      days: iter.into_iter().map(|open_close| open_close.date).collect(),
+    }
+  }
+}
+
+impl FromIterator<NaiveDate> for TradingCalendar {
+  fn from_iter<I>(iter: I) -> Self
+  where
+    I: IntoIterator<Item = NaiveDate>,
+  {
+    Self {
+      days: iter.into_iter().collect(),
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use test_log::test;
+
+
+  /// Create a date from its constituents.
+  fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+  }
+
+  /// Create a trading calendar for the first half of 2024, with all
+  /// weekdays except for the given holidays being trading days.
+  fn calendar(holidays: &[NaiveDate]) -> TradingCalendar {
+    let mut day = date(2024, 1, 1);
+    let mut days = Vec::new();
+    while day < date(2024, 7, 1) {
+      let weekend = matches!(day.weekday(), Weekday::Sat | Weekday::Sun);
+      if !weekend && !holidays.contains(&day) {
+        let () = days.push(day);
+      }
+      day += Duration::days(1);
+    }
+    days.into_iter().collect()
+  }
+
+
+  /// Check that we compute nominal monthly expirations correctly.
+  #[test]
+  fn third_friday() {
+    assert_eq!(monthly_expiration(2024, 1), Some(date(2024, 1, 19)));
+    assert_eq!(monthly_expiration(2024, 3), Some(date(2024, 3, 15)));
+    assert_eq!(monthly_expiration(2024, 11), Some(date(2024, 11, 15)));
+    assert_eq!(monthly_expiration(2023, 9), Some(date(2023, 9, 15)));
+    assert_eq!(monthly_expiration(2024, 13), None);
+  }
+
+  /// Check that we enumerate expirations of the different cycles,
+  /// accounting for holidays.
+  #[test]
+  fn enumerate_expirations() {
+    // Good Friday.
+    let calendar = calendar(&[date(2024, 3, 29)]);
+
+    let monthly = calendar.expirations(Cycle::Monthly, date(2024, 1, 1)..date(2024, 4, 1));
+    assert_eq!(
+      monthly,
+      vec![date(2024, 1, 19), date(2024, 2, 16), date(2024, 3, 15)]
+    );
+
+    let weekly = calendar.expirations(Cycle::Weekly, date(2024, 3, 20)..date(2024, 4, 10));
+    assert_eq!(
+      weekly,
+      vec![date(2024, 3, 22), date(2024, 3, 28), date(2024, 4, 5)]
+    );
+  }
+
+  /// Check that we count trading days until expiration and pick the
+  /// nearest expiration sufficiently far out.
+  #[test]
+  fn nearest_expiration() {
+    let calendar = calendar(&[date(2024, 3, 29)]);
+    let today = date(2024, 3, 25);
+
+    assert_eq!(calendar.days_to_expiry(today, date(2024, 3, 28)), 3);
+    assert_eq!(calendar.days_to_expiry(today, date(2024, 4, 5)), 8);
+    assert_eq!(calendar.days_to_expiry(today, today), 0);
+
+    assert_eq!(
+      calendar.nearest_expiration(Cycle::Weekly, today, 0),
+      Some(date(2024, 3, 28))
+    );
+    assert_eq!(
+      calendar.nearest_expiration(Cycle::Weekly, today, 5),
+      Some(date(2024, 4, 5))
+    );
+    assert_eq!(
+      calendar.nearest_expiration(Cycle::Monthly, today, 5),
+      Some(date(2024, 4, 19))
+    );
+    let expiration = calendar.nearest_expiration(Cycle::Monthly, today, 100);
+    assert_eq!(expiration, None);
+  }
+}
@@ -0,0 +1,308 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::ops::Range;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use futures::TryStreamExt as _;
+
+use num_decimal::Num;
+
+use crate::api::v2::account_activities;
+use crate::api::v2::account_activities::ActivityReq;
+use crate::api::v2::account_activities::ActivityType;
+use crate::api::v2::account_activities::Direction;
+use crate::api::v2::account_activities::NonTradeActivity;
+use crate::Client;
+use crate::RequestError;
+
+
+/// The kind of a fee.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum Kind {
+  /// The SEC transaction fee charged on sales of equities.
+  Sec,
+  /// The FINRA trading activity fee charged on sales of equities.
+  Taf,
+  /// Any other regulatory fee.
+  Regulatory,
+  /// A fee for a crypto trade, charged in the traded asset.
+  Crypto,
+  /// A pass-through charge or rebate.
+  PassThrough,
+}
+
+
+/// A single fee charged to the account.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Fee {
+  /// The ID of the activity reporting the fee.
+  pub id: String,
+  /// The date the fee was charged on.
+  pub date: DateTime<Utc>,
+  /// The kind of fee.
+  pub kind: Kind,
+  /// The symbol of the asset the fee is associated with, if any.
+  pub symbol: Option<String>,
+  /// The amount charged in USD. Charges are positive and rebates
+  /// negative.
+  ///
+  /// For fees charged in an asset other than USD, this is the value of
+  /// the charged quantity at the price reported.
+  pub amount: Num,
+  /// The quantity of the asset charged, for fees not charged in USD.
+  pub quantity: Option<Num>,
+  /// A description of the fee.
+  pub description: Option<String>,
+}
+
+impl TryFrom<NonTradeActivity> for Fee {
+  type Error = NonTradeActivity;
+
+  /// Convert a non-trade activity into a `Fee`, if it reports one.
+  fn try_from(other: NonTradeActivity) -> Result<Self, Self::Error> {
+    let kind = match other.type_ {
+      ActivityType::Fee | ActivityType::DividendFee => {
+        let description = other.description.as_deref().unwrap_or_default();
+        let description = description.to_ascii_uppercase();
+        if description.contains("TAF") {
+          Kind::Taf
+        } else if description.contains("REG") || description.contains("SEC") {
+          Kind::Sec
+        } else {
+          Kind::Regulatory
+        }
+      },
+      ActivityType::CryptoFee => Kind::Crypto,
+      ActivityType::PassThruCharge | ActivityType::PassThruRebate => Kind::PassThrough,
+      _ => return Err(other),
+    };
+
+    let (amount, quantity) = match (kind, &other.quantity, &other.price) {
+      (Kind::Crypto, Some(quantity), Some(price)) => {
+        let quantity = if quantity.is_negative() {
+          -quantity.clone()
+        } else {
+          quantity.clone()
+        };
+        (&quantity * price, Some(quantity))
+      },
+      _ => (-other.net_amount.clone(), None),
+    };
+
+    Ok(Self {
+      id: other.id,
+      date: other.date,
+      kind,
+      symbol: other.symbol,
+      amount,
+      quantity,
+      description: other.description,
+    })
+  }
+}
+
+
+/// An aggregate of fees.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Summary {
+  /// The number of fees.
+  pub count: usize,
+  /// The total amount charged, in USD.
+  pub total: Num,
+  /// The amount charged by kind of fee, in USD.
+  pub by_kind: BTreeMap<Kind, Num>,
+}
+
+impl Summary {
+  /// Add a fee to the summary.
+  fn add(&mut self, fee: &Fee) {
+    self.count += 1;
+    self.total += &fee.amount;
+    *self.by_kind.entry(fee.kind).or_default() += &fee.amount;
+  }
+}
+
+
+/// Summarize the fees charged in the given time window, per symbol.
+///
+/// Fees not associated with any symbol are summarized under `None`.
+pub fn summarize<'f, I>(fees: I, window: Range<DateTime<Utc>>) -> BTreeMap<Option<String>, Summary>
+where
+  I: IntoIterator<Item = &'f Fee>,
+{
+  let mut summaries = BTreeMap::<_, Summary>::new();
+  for fee in fees {
+    if window.contains(&fee.date) {
+      summaries.entry(fee.symbol.clone()).or_default().add(fee)
+    }
+  }
+  summaries
+}
+
+
+/// Retrieve all fees charged in the given time window, ordered from
+/// oldest to most recent.
+pub async fn fees(
+  client: &Client,
+  window: Range<DateTime<Utc>>,
+) -> Result<Vec<Fee>, RequestError<account_activities::GetError>> {
+  let request = ActivityReq {
+    types: vec![
+      ActivityType::Fee,
+      ActivityType::DividendFee,
+      ActivityType::CryptoFee,
+      ActivityType::PassThruCharge,
+      ActivityType::PassThruRebate,
+    ],
+    direction: Direction::Ascending,
+    after: Some(window.start),
+    until: Some(window.end),
+    ..Default::default()
+  };
+
+  let activities = client
+    .issue_paged::<account_activities::Get>(request)
+    .try_collect::<Vec<_>>()
+    .await?;
+
+  let fees = activities
+    .into_iter()
+    .filter_map(|activity| activity.into_non_trade().ok())
+    .filter_map(|activity| Fee::try_from(activity).ok())
+    .filter(|fee| window.contains(&fee.date))
+    .collect();
+  Ok(fees)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use serde_json::from_value;
+  use serde_json::json;
+  use serde_json::Value;
+
+  use test_log::test;
+
+  use crate::api::v2::account_activities::Activity;
+
+
+  /// Parse a `Fee` from a JSON encoded activity.
+  fn fee(json: Value) -> Fee {
+    let activity = from_value::<Activity>(json)
+      .unwrap()
+      .into_non_trade()
+      .unwrap();
+    Fee::try_from(activity).unwrap()
+  }
+
+
+  /// Check that we can parse regulatory fees.
+  #[test]
+  fn parse_regulatory_fees() {
+    let sec = fee(json!({
+      "id": "20220103000000000::0",
+      "activity_type": "FEE",
+      "date": "2022-01-03",
+      "net_amount": "-0.02",
+      "description": "REG fee",
+      "symbol": "AAPL",
+    }));
+    assert_eq!(sec.kind, Kind::Sec);
+    assert_eq!(sec.amount, Num::new(2, 100));
+    assert_eq!(sec.quantity, None);
+
+    let taf = fee(json!({
+      "id": "20220103000000000::1",
+      "activity_type": "FEE",
+      "date": "2022-01-03",
+      "net_amount": "-0.01",
+      "description": "TAF fee",
+      "symbol": "AAPL",
+    }));
+    assert_eq!(taf.kind, Kind::Taf);
+  }
+
+  /// Check that we can parse crypto fees.
+  #[test]
+  fn parse_crypto_fee() {
+    let fee = fee(json!({
+      "id": "20220812000000000::2",
+      "activity_type": "CFEE",
+      "date": "2022-08-12",
+      "net_amount": "0",
+      "description": "Coinbase Transaction Fee",
+      "symbol": "ETHUSD",
+      "qty": "-0.0005",
+      "price": "1800",
+    }));
+    assert_eq!(fee.kind, Kind::Crypto);
+    assert_eq!(fee.symbol.as_deref(), Some("ETHUSD"));
+    assert_eq!(fee.quantity, Some(Num::new(5, 10000)));
+    assert_eq!(fee.amount, Num::new(9, 10));
+  }
+
+  /// Check that we reject activities not reporting fees.
+  #[test]
+  fn reject_non_fee() {
+    let activity = from_value::<Activity>(json!({
+      "id": "20220103000000000::3",
+      "activity_type": "DIV",
+      "date": "2022-01-03",
+      "net_amount": "1.02",
+      "symbol": "T",
+    }))
+    .unwrap()
+    .into_non_trade()
+    .unwrap();
+    assert!(Fee::try_from(activity).is_err());
+  }
+
+  /// Check that we summarize fees per symbol.
+  #[test]
+  fn summarize_fees() {
+    let activity = |id: &str, date: &str, description: &str, symbol: Option<&str>| {
+      fee(json!({
+        "id": id,
+        "activity_type": "FEE",
+        "date": date,
+        "net_amount": "-0.05",
+        "description": description,
+        "symbol": symbol,
+      }))
+    };
+    let fees = [
+      activity("1", "2022-01-03", "REG fee", Some("AAPL")),
+      activity("2", "2022-01-03", "TAF fee", Some("AAPL")),
+      activity("3", "2022-01-04", "REG fee", Some("SPY")),
+      activity("4", "2022-01-05", "ADR fee", None),
+      activity("5", "2022-02-01", "REG fee", Some("AAPL")),
+    ];
+
+    let start = DateTime::from_str("2022-01-01T00:00:00Z").unwrap();
+    let end = DateTime::from_str("2022-02-01T00:00:00Z").unwrap();
+    let summaries = summarize(&fees, start..end);
+    assert_eq!(summaries.len(), 3);
+
+    let aapl = &summaries[&Some("AAPL".to_string())];
+    assert_eq!(aapl.count, 2);
+    assert_eq!(aapl.total, Num::new(1, 10));
+    assert_eq!(aapl.by_kind[&Kind::Sec], Num::new(5, 100));
+    assert_eq!(aapl.by_kind[&Kind::Taf], Num::new(5, 100));
+
+    let other = &summaries[&None];
+    assert_eq!(other.count, 1);
+    assert_eq!(other.by_kind[&Kind::Regulatory], Num::new(5, 100));
+  }
+}
@@ -0,0 +1,198 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+
+use futures::pin_mut;
+use futures::TryStreamExt as _;
+
+use num_decimal::Num;
+
+use thiserror::Error;
+
+use crate::api::v2::account_activities;
+use crate::api::v2::account_activities::ActivityReq;
+use crate::api::v2::account_activities::ActivityType;
+use crate::api::v2::account_activities::Direction;
+use crate::api::v2::account_activities::TradeActivity;
+use crate::api::v2::order;
+use crate::Client;
+use crate::RequestError;
+
+
+/// A single, possibly partial, fill of an order.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Fill {
+  /// The ID of the activity reporting the fill.
+  pub id: String,
+  /// The time at which the fill occurred.
+  pub time: DateTime<Utc>,
+  /// The per-share price at which the fill was executed.
+  pub price: Num,
+  /// The number of shares filled.
+  pub quantity: Num,
+  /// The number of shares of the order filled so far, including this
+  /// fill.
+  pub cumulative_quantity: Num,
+  /// The number of shares of the order left to be filled after this
+  /// fill.
+  pub unfilled_quantity: Num,
+}
+
+impl From<TradeActivity> for Fill {
+  fn from(other: TradeActivity) -> Self {
+    Self {
+      id: other.id,
+      time: other.transaction_time,
+      price: other.price,
+      quantity: other.quantity,
+      cumulative_quantity: other.cumulative_quantity,
+      unfilled_quantity: other.unfilled_quantity,
+    }
+  }
+}
+
+
+/// An error as reported by [`order_fills`].
+#[derive(Debug, Error)]
+pub enum FillsError {
+  /// The order could not be retrieved.
+  #[error("failed to retrieve order")]
+  Order(#[source] RequestError<order::GetError>),
+  /// The account activities could not be retrieved.
+  #[error("failed to retrieve account activities")]
+  Activities(#[source] RequestError<account_activities::GetError>),
+}
+
+
+/// Assemble the fill history of the order with the given ID from a set
+/// of trade activities, ordered from oldest to most recent fill.
+pub fn fills_from_activities<I>(order_id: order::Id, activities: I) -> Vec<Fill>
+where
+  I: IntoIterator<Item = TradeActivity>,
+{
+  let mut fills = activities
+    .into_iter()
+    .filter(|activity| activity.order_id == order_id)
+    .map(Fill::from)
+    .collect::<Vec<_>>();
+
+  fills.sort_by(|first, second| {
+    first
+      .time
+      .cmp(&second.time)
+      .then_with(|| first.cumulative_quantity.cmp(&second.cumulative_quantity))
+  });
+  fills
+}
+
+
+/// Retrieve the fill history of the order with the given ID, ordered
+/// from oldest to most recent fill.
+///
+/// The history is assembled from the account's fill activities
+/// reported since the order got created.
+pub async fn order_fills(client: &Client, order_id: order::Id) -> Result<Vec<Fill>, FillsError> {
+  let order = client
+    .issue::<order::Get>(&order_id)
+    .await
+    .map_err(FillsError::Order)?;
+
+  if order.filled_quantity.is_zero() {
+    return Ok(Vec::new())
+  }
+
+  let request = ActivityReq {
+    types: vec![ActivityType::Fill],
+    direction: Direction::Ascending,
+    // Be a bit generous with the start time, to account for any
+    // rounding on the server side.
+    after: Some(order.created_at - Duration::seconds(1)),
+    ..Default::default()
+  };
+
+  let stream = client.issue_paged::<account_activities::Get>(request);
+  pin_mut!(stream);
+
+  let mut activities = Vec::new();
+  let mut filled = Num::from(0);
+  // Stop once we have seen all fills of the order or there are no more
+  // activities.
+  while filled < order.filled_quantity {
+    let activity = match stream.try_next().await.map_err(FillsError::Activities)? {
+      Some(activity) => activity,
+      None => break,
+    };
+
+    if let Ok(activity) = activity.into_trade() {
+      if activity.order_id == order_id {
+        filled += &activity.quantity;
+        activities.push(activity);
+      }
+    }
+  }
+
+  Ok(fills_from_activities(order_id, activities))
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::from_value;
+  use serde_json::json;
+
+  use test_log::test;
+
+  use uuid::Uuid;
+
+
+  /// Create a fill activity for the given order.
+  fn activity(
+    order_id: &str,
+    time: &str,
+    qty: &str,
+    cum_qty: &str,
+    leaves_qty: &str,
+  ) -> TradeActivity {
+    from_value(json!({
+      "activity_type": "FILL",
+      "id": format!("{}::{}", time, order_id),
+      "transaction_time": time,
+      "symbol": "AAPL",
+      "order_id": order_id,
+      "side": "buy",
+      "qty": qty,
+      "cum_qty": cum_qty,
+      "leaves_qty": leaves_qty,
+      "price": "150.25"
+    }))
+    .unwrap()
+  }
+
+
+  /// Check that we assemble the fill history of an order correctly.
+  #[test]
+  fn assemble_fills() {
+    let id = "904837e3-3b76-47ec-b432-046db621571b";
+    let other = "b0b6dd9d-8b9b-48a9-ba46-b9d54906e415";
+    let activities = [
+      activity(id, "2022-03-01T14:30:02Z", "3", "10", "0"),
+      activity(other, "2022-03-01T14:30:01Z", "5", "5", "0"),
+      activity(id, "2022-03-01T14:30:00Z", "7", "7", "3"),
+    ];
+
+    let order_id = order::Id(Uuid::parse_str(id).unwrap());
+    let fills = fills_from_activities(order_id, activities);
+    assert_eq!(fills.len(), 2);
+    assert_eq!(fills[0].quantity, Num::from(7));
+    assert_eq!(fills[0].unfilled_quantity, Num::from(3));
+    assert_eq!(fills[1].quantity, Num::from(3));
+    assert_eq!(fills[1].cumulative_quantity, Num::from(10));
+    assert_eq!(fills[1].price, Num::new(15025, 100));
+  }
+}
@@ -0,0 +1,267 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use futures::pin_mut;
+use futures::Stream;
+use futures::StreamExt as _;
+
+use serde_json::Error as JsonError;
+
+use thiserror::Error;
+
+use tokio::time::timeout_at;
+use tokio::time::Instant;
+
+use tracing::debug;
+use tracing::warn;
+
+use websocket_util::tungstenite::Error as WebSocketError;
+
+use crate::api::v2::order;
+use crate::api::v2::orders;
+use crate::api::v2::orders::CancelStatus;
+use crate::api::v2::positions;
+use crate::api::v2::positions::LiquidationStatus;
+use crate::api::v2::updates::OrderStatus;
+use crate::api::v2::updates::OrderUpdate;
+use crate::api::v2::updates::OrderUpdates;
+use crate::Client;
+use crate::Error;
+use crate::RequestError;
+
+
+/// The report of an attempt to flatten the account, as produced by
+/// [`flatten_all`].
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Report {
+  /// The orders confirmed to no longer be open, along with the event
+  /// that concluded them. Note that orders may have filled before
+  /// they could be canceled.
+  pub closed: Vec<(order::Id, OrderStatus)>,
+  /// The orders whose cancellation was refused, along with the HTTP
+  /// status code reported.
+  pub cancel_failures: Vec<CancelStatus>,
+  /// The orders for which no confirmation was received before the
+  /// timeout elapsed.
+  pub unconfirmed: Vec<order::Id>,
+  /// The outcomes of the attempts to close each open position.
+  pub liquidations: Vec<LiquidationStatus>,
+}
+
+impl Report {
+  /// Check whether all orders were confirmed closed and all positions
+  /// were closed successfully.
+  pub fn is_complete(&self) -> bool {
+    self.cancel_failures.is_empty()
+      && self.unconfirmed.is_empty()
+      && self
+        .liquidations
+        .iter()
+        .all(|liquidation| (200..300).contains(&liquidation.status))
+  }
+}
+
+
+/// An error as reported by [`flatten_all`].
+#[derive(Debug, Error)]
+pub enum FlattenError {
+  /// The order update stream could not be subscribed to.
+  #[error("failed to subscribe to order updates")]
+  Subscribe(#[source] Error),
+  /// Open orders could not be canceled.
+  #[error("failed to cancel open orders")]
+  CancelOrders(#[source] RequestError<orders::DeleteError>),
+  /// Open positions could not be closed.
+  #[error("failed to close open positions")]
+  ClosePositions(#[source] RequestError<positions::DeleteError>),
+}
+
+
+/// Wait for order updates moving the pending orders into a terminal
+/// state, until `deadline` is reached.
+///
+/// Return the orders that were confirmed closed along with the
+/// concluding event, as well as the ones that were not.
+async fn confirm<S>(
+  stream: S,
+  mut pending: HashSet<order::Id>,
+  deadline: Instant,
+) -> (Vec<(order::Id, OrderStatus)>, Vec<order::Id>)
+where
+  S: Stream<Item = Result<Result<OrderUpdate, JsonError>, WebSocketError>>,
+{
+  pin_mut!(stream);
+
+  let mut closed = Vec::new();
+  while !pending.is_empty() {
+    let update = match timeout_at(deadline, stream.next()).await {
+      Ok(Some(Ok(Ok(update)))) => update,
+      Ok(Some(Ok(Err(err)))) => {
+        warn!("failed to parse order update: {}", err);
+        continue
+      },
+      Ok(Some(Err(err))) => {
+        warn!("order update stream failed: {}", err);
+        break
+      },
+      Ok(None) => {
+        warn!("order update stream ended unexpectedly");
+        break
+      },
+      Err(_elapsed) => break,
+    };
+
+    if update.event.is_terminal() && pending.remove(&update.order.id) {
+      debug!(
+        "order {} concluded: {:?}",
+        update.order.id.as_hyphenated(),
+        update.event
+      );
+      closed.push((update.order.id, update.event));
+    }
+  }
+
+  let mut unconfirmed = pending.into_iter().collect::<Vec<_>>();
+  unconfirmed.sort_by_key(|id| id.0);
+  (closed, unconfirmed)
+}
+
+
+/// Flatten the account: cancel all open orders, wait for the
+/// cancellations to be confirmed, and then close all open positions.
+///
+/// Confirmations are awaited through the order update stream for at
+/// most `timeout`; orders not confirmed by then are reported as such,
+/// but positions are closed regardless. Note that positions are closed
+/// by means of market orders, which only fill while the market is open.
+pub async fn flatten_all(client: &Client, timeout: Duration) -> Result<Report, FlattenError> {
+  // Subscribe before canceling anything, so that we cannot miss any
+  // of the updates we are waiting for.
+  let (stream, _subscription) = client
+    .subscribe::<OrderUpdates>()
+    .await
+    .map_err(FlattenError::Subscribe)?;
+
+  let deadline = Instant::now() + timeout;
+  let statuses = client
+    .issue::<orders::Delete>(&())
+    .await
+    .map_err(FlattenError::CancelOrders)?;
+
+  let (pending, cancel_failures) = statuses
+    .into_iter()
+    .partition::<Vec<_>, _>(|status| (200..300).contains(&status.status));
+  let pending = pending.into_iter().map(|status| status.id).collect();
+  let (closed, unconfirmed) = confirm(stream, pending, deadline).await;
+
+  // Should any orders still be open, make sure to cancel them as part
+  // of closing the positions.
+  let request = positions::DeleteReq {
+    cancel_orders: true,
+  };
+  let liquidations = client
+    .issue::<positions::Delete>(&request)
+    .await
+    .map_err(FlattenError::ClosePositions)?;
+
+  Ok(Report {
+    closed,
+    cancel_failures,
+    unconfirmed,
+    liquidations,
+  })
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use futures::stream::iter;
+  use futures::stream::pending;
+
+  use serde_json::json;
+
+  use test_log::test;
+
+  use uuid::Uuid;
+
+  use crate::live;
+  use crate::test_util::order;
+
+
+  /// Create an order update for the order with the given ID.
+  fn update(id: order::Id, event: OrderStatus) -> OrderUpdate {
+    let order = order(json!({
+      "id": id,
+      "created_at": "2022-07-01T14:30:00Z",
+      "submitted_at": "2022-07-01T14:30:00Z",
+      "type": "limit",
+      "limit_price": "100",
+      "status": "canceled",
+    }));
+    OrderUpdate { event, order }
+  }
+
+
+  /// Check that we pick up confirmations for pending orders and report
+  /// the ones that were not confirmed in time.
+  #[test(tokio::test)]
+  async fn confirm_cancellations() {
+    let id1 = order::Id(Uuid::new_v4());
+    let id2 = order::Id(Uuid::new_v4());
+    let id3 = order::Id(Uuid::new_v4());
+    let other = order::Id(Uuid::new_v4());
+
+    let updates = vec![
+      Ok(Ok(update(other, OrderStatus::Canceled))),
+      Ok(Ok(update(id1, OrderStatus::PendingCancel))),
+      Ok(Ok(update(id1, OrderStatus::Canceled))),
+      Ok(Ok(update(id2, OrderStatus::Filled))),
+    ];
+    // Keep the stream open after the canned updates, so that we hit
+    // the timeout.
+    let stream = iter(updates).chain(pending());
+    let pending = [id1, id2, id3].into_iter().collect();
+    let deadline = Instant::now() + Duration::from_millis(10);
+
+    let (closed, unconfirmed) = confirm(stream, pending, deadline).await;
+    assert_eq!(
+      closed,
+      vec![(id1, OrderStatus::Canceled), (id2, OrderStatus::Filled)]
+    );
+    assert_eq!(unconfirmed, vec![id3]);
+  }
+
+  /// Check that we stop waiting once all orders are confirmed.
+  #[test(tokio::test)]
+  async fn confirm_all() {
+    let id = order::Id(Uuid::new_v4());
+    let updates = vec![Ok(Ok(update(id, OrderStatus::Expired)))];
+    let stream = iter(updates).chain(pending());
+    let pending = [id].into_iter().collect();
+    // The deadline is far out; we must not wait for it.
+    let deadline = Instant::now() + Duration::from_secs(3600);
+
+    let (closed, unconfirmed) = confirm(stream, pending, deadline).await;
+    assert_eq!(closed, vec![(id, OrderStatus::Expired)]);
+    assert_eq!(unconfirmed, Vec::new());
+  }
+
+  /// Check that we can flatten the paper trading account.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn flatten_paper_account() {
+    let client = live::client().unwrap();
+    let report = client.flatten_all(Duration::from_secs(10)).await.unwrap();
+    assert_eq!(report.unconfirmed, Vec::new());
+
+    let request = orders::OrdersReq::default();
+    let orders = client.issue::<orders::Get>(&request).await.unwrap();
+    assert_eq!(orders, Vec::new());
+  }
+}
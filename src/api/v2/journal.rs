@@ -0,0 +1,539 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::error::Error as StdError;
+use std::io::BufRead;
+use std::io::Cursor;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use http::HeaderMap;
+
+use http_endpoint::Endpoint;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::from_str as from_json;
+use serde_json::to_vec as to_json;
+
+use thiserror::Error;
+
+use tokio::fs::File;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncReadExt as _;
+use tokio::io::AsyncWrite;
+use tokio::io::AsyncWriteExt as _;
+use tokio::sync::Mutex;
+
+use tracing::warn;
+
+use crate::api::v2::order;
+use crate::api::v2::order::ChangeReq;
+use crate::api::v2::order::Order;
+use crate::api::v2::order::OrderReq;
+use crate::api::v2::updates::OrderUpdate;
+use crate::Client;
+use crate::RequestError;
+
+
+/// The name of the header in which Alpaca reports the ID it assigned to
+/// a request.
+const HDR_REQUEST_ID: &str = "x-request-id";
+
+
+/// An event recorded in a [`Journal`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Event {
+  /// An order is about to be submitted.
+  Submit {
+    /// The order request.
+    request: Box<OrderReq>,
+  },
+  /// An order is about to be amended.
+  Amend {
+    /// The ID of the order being amended.
+    order_id: order::Id,
+    /// The change request.
+    request: Box<ChangeReq>,
+  },
+  /// An order is about to be canceled.
+  Cancel {
+    /// The ID of the order being canceled.
+    order_id: order::Id,
+  },
+  /// The response to a previously recorded submission, amendment, or
+  /// cancellation was received.
+  Response {
+    /// The sequence number of the entry recording the request.
+    intent: u64,
+    /// The ID Alpaca assigned to the request, if reported.
+    request_id: Option<String>,
+    /// The outcome of the request.
+    outcome: Outcome,
+  },
+  /// An order update was received, e.g., through the
+  /// [`OrderUpdates`][crate::api::v2::updates::OrderUpdates] stream.
+  Update {
+    /// The update.
+    update: Box<OrderUpdate>,
+  },
+}
+
+
+/// The outcome of a request recorded in a [`Journal`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+  /// The request succeeded and reported the given order.
+  Order(Box<Order>),
+  /// The cancellation request succeeded.
+  Canceled,
+  /// The request failed with the given error.
+  Error(String),
+}
+
+
+/// A single entry in a [`Journal`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Entry {
+  /// The entry's sequence number, unique within a journal.
+  pub sequence: u64,
+  /// The time at which the entry was recorded.
+  pub time: DateTime<Utc>,
+  /// The recorded event.
+  #[serde(flatten)]
+  pub event: Event,
+}
+
+
+/// An error as reported by the request issuing methods of a
+/// [`Journal`].
+#[derive(Debug, Error)]
+pub enum JournalError<E>
+where
+  E: StdError + 'static,
+{
+  /// An entry could not be written to the journal.
+  ///
+  /// If this error is reported after the request was issued, the
+  /// request's effect has taken place nevertheless. The entry recording
+  /// the intent will already be part of the journal in that case.
+  #[error("failed to write journal entry")]
+  Write(#[source] IoError),
+  /// The request failed.
+  #[error("failed to issue request")]
+  Request(#[source] RequestError<E>),
+}
+
+
+/// The mutable state of a [`Journal`].
+#[derive(Debug)]
+struct Inner<W> {
+  /// The writer entries are written to.
+  writer: W,
+  /// The sequence number of the next entry.
+  sequence: u64,
+}
+
+
+/// An append-only journal of all trading actions, for compliance and
+/// post-mortem debugging.
+///
+/// Order submissions, amendments, and cancellations issued through a
+/// journal are recorded before the request is sent and again once the
+/// response was received, along with the ID Alpaca assigned to the
+/// request. Order updates can be recorded via [`Journal::update`].
+/// Entries are written as one JSON object per line.
+#[derive(Debug)]
+pub struct Journal<W> {
+  inner: Mutex<Inner<W>>,
+}
+
+impl Journal<File> {
+  /// Open the journal at the given path for appending, creating it if
+  /// it does not exist.
+  ///
+  /// Sequence numbers continue from the last entry already present.
+  ///
+  /// A partial last line, as left behind by a crash while an entry was
+  /// being written, is truncated with a warning. Entries failing to
+  /// parse otherwise make the open fail.
+  pub async fn open<P>(path: P) -> Result<Self, IoError>
+  where
+    P: AsRef<Path>,
+  {
+    let mut file = OpenOptions::new()
+      .read(true)
+      .append(true)
+      .create(true)
+      .open(path)
+      .await?;
+
+    let mut data = Vec::new();
+    let _count = file.read_to_end(&mut data).await?;
+
+    if !data.is_empty() && !data.ends_with(b"\n") {
+      let len = data
+        .iter()
+        .rposition(|byte| *byte == b'\n')
+        .map(|index| index + 1)
+        .unwrap_or(0);
+      warn!(
+        "truncating partially written journal entry ({} bytes)",
+        data.len() - len
+      );
+      let () = file.set_len(len as u64).await?;
+      let () = data.truncate(len);
+    }
+
+    let mut sequence = 0;
+    for entry in entries(Cursor::new(data)) {
+      sequence = entry?.sequence + 1;
+    }
+
+    Ok(Self {
+      inner: Mutex::new(Inner {
+        writer: file,
+        sequence,
+      }),
+    })
+  }
+}
+
+impl<W> Journal<W>
+where
+  W: AsyncWrite + Unpin,
+{
+  /// Create a journal writing entries to the provided writer.
+  pub fn new(writer: W) -> Self {
+    Self {
+      inner: Mutex::new(Inner {
+        writer,
+        sequence: 0,
+      }),
+    }
+  }
+
+  /// Record the given event, returning the sequence number of the
+  /// entry.
+  ///
+  /// The writer is flushed after each entry.
+  pub async fn record(&self, event: Event) -> Result<u64, IoError> {
+    let mut inner = self.inner.lock().await;
+    let entry = Entry {
+      sequence: inner.sequence,
+      time: Utc::now(),
+      event,
+    };
+
+    let mut line = to_json(&entry)?;
+    line.push(b'\n');
+    let () = inner.writer.write_all(&line).await?;
+    let () = inner.writer.flush().await?;
+
+    inner.sequence += 1;
+    Ok(entry.sequence)
+  }
+
+  /// Record a received order update.
+  #[inline]
+  pub async fn update(&self, update: &OrderUpdate) -> Result<u64, IoError> {
+    self
+      .record(Event::Update {
+        update: Box::new(update.clone()),
+      })
+      .await
+  }
+
+  /// Issue a request to the given endpoint, recording its response as
+  /// completing the entry with the given sequence number.
+  async fn issue<R, F>(
+    &self,
+    client: &Client,
+    sequence: u64,
+    input: &R::Input,
+    outcome: F,
+  ) -> Result<R::Output, JournalError<R::Error>>
+  where
+    R: Endpoint,
+    F: FnOnce(&R::Output) -> Outcome,
+  {
+    let result = client.issue_raw::<R>(input, HeaderMap::new()).await;
+    let (request_id, result) = match result {
+      Ok((status, headers, body)) => {
+        let request_id = headers
+          .get(HDR_REQUEST_ID)
+          .and_then(|value| value.to_str().ok())
+          .map(str::to_string);
+        let result = R::evaluate(status, &body).map_err(RequestError::Endpoint);
+        (request_id, result)
+      },
+      Err(err) => (None, Err(err)),
+    };
+
+    let outcome = match &result {
+      Ok(output) => outcome(output),
+      Err(err) => Outcome::Error(err.to_string()),
+    };
+    let event = Event::Response {
+      intent: sequence,
+      request_id,
+      outcome,
+    };
+    let _sequence = self.record(event).await.map_err(JournalError::Write)?;
+    result.map_err(JournalError::Request)
+  }
+
+  /// Submit an order, recording the request and its outcome.
+  pub async fn submit(
+    &self,
+    client: &Client,
+    request: &OrderReq,
+  ) -> Result<Order, JournalError<order::PostError>> {
+    let event = Event::Submit {
+      request: Box::new(request.clone()),
+    };
+    let sequence = self.record(event).await.map_err(JournalError::Write)?;
+    self
+      .issue::<order::Post, _>(client, sequence, request, |order| {
+        Outcome::Order(Box::new(order.clone()))
+      })
+      .await
+  }
+
+  /// Amend an order, recording the request and its outcome.
+  pub async fn amend(
+    &self,
+    client: &Client,
+    order_id: order::Id,
+    request: &ChangeReq,
+  ) -> Result<Order, JournalError<order::PatchError>> {
+    let event = Event::Amend {
+      order_id,
+      request: Box::new(request.clone()),
+    };
+    let sequence = self.record(event).await.map_err(JournalError::Write)?;
+    let input = (order_id, request.clone());
+    self
+      .issue::<order::Patch, _>(client, sequence, &input, |order| {
+        Outcome::Order(Box::new(order.clone()))
+      })
+      .await
+  }
+
+  /// Cancel an order, recording the request and its outcome.
+  pub async fn cancel(
+    &self,
+    client: &Client,
+    order_id: order::Id,
+  ) -> Result<(), JournalError<order::DeleteError>> {
+    let event = Event::Cancel { order_id };
+    let sequence = self.record(event).await.map_err(JournalError::Write)?;
+    self
+      .issue::<order::Delete, _>(client, sequence, &order_id, |()| Outcome::Canceled)
+      .await
+  }
+
+  /// Destroy the journal, returning the underlying writer.
+  #[inline]
+  pub fn into_inner(self) -> W {
+    self.inner.into_inner().writer
+  }
+}
+
+
+/// Read the entries of a journal, e.g., for post-mortem analysis.
+pub fn entries<R>(reader: R) -> impl Iterator<Item = Result<Entry, IoError>>
+where
+  R: BufRead,
+{
+  reader
+    .lines()
+    .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+    .map(|line| {
+      let line = line?;
+      from_json::<Entry>(&line).map_err(|err| IoError::new(ErrorKind::InvalidData, err))
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::io::BufReader;
+  use std::str::FromStr as _;
+
+  use http::StatusCode;
+
+  use num_decimal::Num;
+
+  use serde_json::json;
+
+  use test_log::test;
+
+  use uuid::Uuid;
+
+  use crate::api::v2::order::Amount;
+  use crate::api::v2::order::ChangeReqInit;
+  use crate::api::v2::order::OrderReqInit;
+  use crate::api::v2::order::Side;
+  use crate::test_util::order_json;
+  use crate::test_util::MockServer;
+  use crate::test_util::Response;
+
+
+  /// Check that we can record events and read them back.
+  #[test(tokio::test)]
+  async fn record_and_read() {
+    let journal = Journal::new(Vec::new());
+    let order_id = order::Id(Uuid::from_str("904837e3-3b76-47ec-b432-046db621571b").unwrap());
+
+    let sequence = journal.record(Event::Cancel { order_id }).await.unwrap();
+    assert_eq!(sequence, 0);
+    let event = Event::Response {
+      intent: sequence,
+      request_id: Some("1d8a7ba1".to_string()),
+      outcome: Outcome::Canceled,
+    };
+    assert_eq!(journal.record(event).await.unwrap(), 1);
+
+    let data = journal.into_inner();
+    let entries = entries(Cursor::new(data))
+      .collect::<Result<Vec<_>, _>>()
+      .unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].event, Event::Cancel { order_id });
+    assert_eq!(entries[1].sequence, 1);
+    assert!(matches!(
+      &entries[1].event,
+      Event::Response {
+        intent: 0,
+        request_id: Some(id),
+        outcome: Outcome::Canceled,
+      } if id == "1d8a7ba1"
+    ));
+  }
+
+  /// Check that sequence numbers continue when reopening a journal.
+  #[test(tokio::test)]
+  async fn reopen_journal() {
+    let path = std::env::temp_dir().join(format!("apca-journal-{}.jsonl", Uuid::new_v4()));
+    let order_id = order::Id(Uuid::new_v4());
+
+    let journal = Journal::open(&path).await.unwrap();
+    let event = Event::Cancel { order_id };
+    assert_eq!(journal.record(event).await.unwrap(), 0);
+    drop(journal);
+
+    let journal = Journal::open(&path).await.unwrap();
+    let event = Event::Cancel { order_id };
+    assert_eq!(journal.record(event).await.unwrap(), 1);
+    drop(journal);
+
+    let file = BufReader::new(std::fs::File::open(&path).unwrap());
+    assert_eq!(entries(file).count(), 2);
+    let () = std::fs::remove_file(&path).unwrap();
+  }
+
+  /// Check that a partially written last entry is truncated when
+  /// opening a journal, while corruption elsewhere is reported.
+  #[test(tokio::test)]
+  async fn reopen_torn_journal() {
+    let path = std::env::temp_dir().join(format!("apca-journal-{}.jsonl", Uuid::new_v4()));
+    let order_id = order::Id(Uuid::new_v4());
+
+    let journal = Journal::open(&path).await.unwrap();
+    let event = Event::Cancel { order_id };
+    assert_eq!(journal.record(event).await.unwrap(), 0);
+    drop(journal);
+
+    let mut data = std::fs::read(&path).unwrap();
+    let torn = data[..data.len() / 2].to_vec();
+    let () = data.extend_from_slice(&torn);
+    let () = std::fs::write(&path, &data).unwrap();
+
+    let journal = Journal::open(&path).await.unwrap();
+    let event = Event::Cancel { order_id };
+    assert_eq!(journal.record(event).await.unwrap(), 1);
+    drop(journal);
+
+    let file = BufReader::new(std::fs::File::open(&path).unwrap());
+    let entries = entries(file).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[1].sequence, 1);
+
+    let mut data = torn;
+    let () = data.push(b'\n');
+    let () = data.extend_from_slice(&std::fs::read(&path).unwrap());
+    let () = std::fs::write(&path, &data).unwrap();
+
+    let err = Journal::open(&path).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    let () = std::fs::remove_file(&path).unwrap();
+  }
+
+  /// Check that submissions are recorded along with the request ID
+  /// and their outcome.
+  #[test(tokio::test)]
+  async fn record_submission() {
+    let err = r#"{"code":40410000,"message":"order not found"}"#;
+    let server = MockServer::serve([
+      Response::json(order_json(json!({"updated_at": null})).to_string())
+        .header("x-request-id", "1d8a7ba1"),
+      Response::new(StatusCode::NOT_FOUND)
+        .header("x-request-id", "2e9b8cb2")
+        .body(err),
+    ]);
+    let client = server.client();
+    let journal = Journal::new(Vec::new());
+
+    let request = OrderReqInit::default().init("AAPL", Side::Buy, Amount::quantity(1));
+    let order = journal.submit(&client, &request).await.unwrap();
+
+    let change = ChangeReqInit {
+      quantity: Num::from(2),
+      ..Default::default()
+    }
+    .init();
+    let result = journal.amend(&client, order.id, &change).await;
+    assert!(matches!(result, Err(JournalError::Request(_))));
+
+    let data = journal.into_inner();
+    let entries = entries(Cursor::new(data))
+      .collect::<Result<Vec<_>, _>>()
+      .unwrap();
+    assert_eq!(entries.len(), 4);
+    let request = Box::new(request);
+    assert_eq!(entries[0].event, Event::Submit { request });
+    match &entries[1].event {
+      Event::Response {
+        intent,
+        request_id,
+        outcome: Outcome::Order(recorded),
+      } => {
+        assert_eq!(*intent, 0);
+        assert_eq!(request_id.as_deref(), Some("1d8a7ba1"));
+        assert_eq!(**recorded, order);
+      },
+      event => panic!("unexpected event: {:?}", event),
+    }
+    match &entries[3].event {
+      Event::Response {
+        intent,
+        request_id,
+        outcome: Outcome::Error(..),
+      } => {
+        assert_eq!(*intent, 2);
+        assert_eq!(request_id.as_deref(), Some("2e9b8cb2"));
+      },
+      event => panic!("unexpected event: {:?}", event),
+    }
+  }
+}
@@ -0,0 +1,465 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::BTreeMap;
+
+use chrono::DateTime;
+use chrono::Datelike as _;
+use chrono::NaiveDate;
+use chrono::Utc;
+
+use num_decimal::Num;
+
+use thiserror::Error;
+
+use crate::api::v2::account_activities::Side as ActivitySide;
+use crate::api::v2::account_activities::TradeActivity;
+use crate::api::v2::fills::Fill;
+use crate::api::v2::order::Side;
+
+
+/// The method used for matching sales to the lots they close.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Method {
+  /// Close the oldest lots first ("first in, first out").
+  Fifo,
+  /// Close the most recent lots first ("last in, first out").
+  Lifo,
+}
+
+
+/// The selection of lots to close in a simulated sale.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Selection {
+  /// Close lots as per the given method.
+  Method(Method),
+  /// Close the lots with the given IDs, in the given order.
+  Specific(Vec<String>),
+}
+
+impl From<Method> for Selection {
+  #[inline]
+  fn from(method: Method) -> Self {
+    Self::Method(method)
+  }
+}
+
+
+/// The holding period of a closed lot, as relevant for capital gains
+/// taxation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Term {
+  /// The lot was held for one year or less.
+  Short,
+  /// The lot was held for more than one year.
+  Long,
+}
+
+impl Term {
+  /// Determine the holding period of a lot acquired and sold at the
+  /// given times.
+  fn from_dates(acquired: DateTime<Utc>, sold: DateTime<Utc>) -> Self {
+    let acquired = acquired.naive_utc().date();
+    // A lot acquired on February 29th is held for a year on
+    // March 1st of the following year.
+    let anniversary =
+      NaiveDate::from_ymd_opt(acquired.year() + 1, acquired.month(), acquired.day())
+        .or_else(|| NaiveDate::from_ymd_opt(acquired.year() + 1, 3, 1))
+        .unwrap();
+
+    if sold.naive_utc().date() > anniversary {
+      Self::Long
+    } else {
+      Self::Short
+    }
+  }
+}
+
+
+/// An open tax lot, i.e., shares acquired in a single fill.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Lot {
+  /// The ID of the lot, i.e., that of the activity or fill it was
+  /// acquired in.
+  pub id: String,
+  /// The time the lot was acquired at.
+  pub acquired: DateTime<Utc>,
+  /// The number of shares remaining open.
+  pub quantity: Num,
+  /// The per-share price paid.
+  pub price: Num,
+}
+
+impl Lot {
+  /// Retrieve the cost basis of the shares remaining open.
+  #[inline]
+  pub fn cost_basis(&self) -> Num {
+    &self.quantity * &self.price
+  }
+}
+
+
+/// A (partial) closure of a lot in a simulated sale.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Closure {
+  /// The ID of the lot closed.
+  pub lot_id: String,
+  /// The number of shares closed.
+  pub quantity: Num,
+  /// The cost basis of the shares closed.
+  pub cost_basis: Num,
+  /// The proceeds of the shares closed.
+  pub proceeds: Num,
+  /// The holding period of the shares closed.
+  pub term: Term,
+}
+
+impl Closure {
+  /// Retrieve the capital gain (or loss, if negative) realized.
+  #[inline]
+  pub fn gain(&self) -> Num {
+    &self.proceeds - &self.cost_basis
+  }
+}
+
+
+/// An error as reported by [`Lots::simulate_close`].
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum LotsError {
+  /// More shares were to be closed than are held.
+  #[error("cannot close {requested} shares of {symbol}: only {available} are held")]
+  InsufficientQuantity {
+    /// The symbol.
+    symbol: String,
+    /// The number of shares held in the selected lots.
+    available: Num,
+    /// The number of shares to close.
+    requested: Num,
+  },
+  /// A selected lot does not exist.
+  #[error("no open lot with ID {0}")]
+  UnknownLot(String),
+}
+
+
+/// The open tax lots of long positions, as reconstructed from fills.
+///
+/// Fills have to be added in chronological order. Sales close lots
+/// according to the configured [`Method`], which should match the one
+/// used by the broker (typically FIFO). Short sales are not tracked.
+#[derive(Clone, Debug)]
+pub struct Lots {
+  /// The method used for matching sales to lots.
+  method: Method,
+  /// The open lots, by symbol, ordered by acquisition.
+  lots: BTreeMap<String, Vec<Lot>>,
+}
+
+impl Lots {
+  /// Create a new `Lots` object without any lots, matching sales to
+  /// lots using the given method.
+  #[inline]
+  pub fn new(method: Method) -> Self {
+    Self {
+      method,
+      lots: BTreeMap::new(),
+    }
+  }
+
+  /// Reconstruct the open lots from the given trade activities, which
+  /// need not be ordered.
+  pub fn from_activities<I>(method: Method, activities: I) -> Self
+  where
+    I: IntoIterator<Item = TradeActivity>,
+  {
+    let mut activities = activities.into_iter().collect::<Vec<_>>();
+    activities.sort_by_key(|activity| activity.transaction_time);
+
+    let mut lots = Self::new(method);
+    activities
+      .iter()
+      .for_each(|activity| lots.add_activity(activity));
+    lots
+  }
+
+  /// Account for a trade activity.
+  pub fn add_activity(&mut self, activity: &TradeActivity) {
+    let side = match activity.side {
+      ActivitySide::Buy => Side::Buy,
+      ActivitySide::Sell => Side::Sell,
+      ActivitySide::ShortSell => return,
+    };
+
+    self.add(
+      &activity.symbol,
+      side,
+      &activity.id,
+      activity.transaction_time,
+      &activity.quantity,
+      &activity.price,
+    )
+  }
+
+  /// Account for a fill of an order for the given symbol on the given
+  /// side, e.g., as retrieved by
+  /// [`order_fills`][crate::api::v2::fills::order_fills].
+  #[inline]
+  pub fn add_fill(&mut self, symbol: &str, side: Side, fill: &Fill) {
+    self.add(
+      symbol,
+      side,
+      &fill.id,
+      fill.time,
+      &fill.quantity,
+      &fill.price,
+    )
+  }
+
+  /// Account for a fill on the given side.
+  fn add(
+    &mut self,
+    symbol: &str,
+    side: Side,
+    id: &str,
+    time: DateTime<Utc>,
+    quantity: &Num,
+    price: &Num,
+  ) {
+    let lots = self.lots.entry(symbol.to_string()).or_default();
+    match side {
+      Side::Buy => lots.push(Lot {
+        id: id.to_string(),
+        acquired: time,
+        quantity: quantity.clone(),
+        price: price.clone(),
+      }),
+      Side::Sell => {
+        let mut remaining = quantity.clone();
+        while remaining.is_positive() {
+          let index = match self.method {
+            Method::Fifo if !lots.is_empty() => 0,
+            Method::Lifo if !lots.is_empty() => lots.len() - 1,
+            // Any shares sold beyond what we know of are ignored.
+            _ => break,
+          };
+
+          let lot = &mut lots[index];
+          if lot.quantity <= remaining {
+            remaining -= &lot.quantity;
+            let _ = lots.remove(index);
+          } else {
+            lot.quantity -= &remaining;
+            break
+          }
+        }
+      },
+    }
+
+    if lots.is_empty() {
+      let _ = self.lots.remove(symbol);
+    }
+  }
+
+  /// Retrieve the open lots for the given symbol, ordered by
+  /// acquisition.
+  pub fn open(&self, symbol: &str) -> &[Lot] {
+    self.lots.get(symbol).map(Vec::as_slice).unwrap_or_default()
+  }
+
+  /// Retrieve the symbols with open lots.
+  pub fn symbols(&self) -> impl Iterator<Item = &str> {
+    self.lots.keys().map(String::as_str)
+  }
+
+  /// Simulate the sale of the given number of shares of a symbol at the
+  /// given price and time, reporting the lots that would be closed.
+  ///
+  /// The lots themselves are not changed.
+  #[allow(clippy::result_large_err)]
+  pub fn simulate_close(
+    &self,
+    symbol: &str,
+    quantity: &Num,
+    price: &Num,
+    time: DateTime<Utc>,
+    selection: &Selection,
+  ) -> Result<Vec<Closure>, LotsError> {
+    let open = self.open(symbol);
+    let lots = match selection {
+      Selection::Method(Method::Fifo) => open.iter().collect::<Vec<_>>(),
+      Selection::Method(Method::Lifo) => open.iter().rev().collect(),
+      Selection::Specific(ids) => ids
+        .iter()
+        .map(|id| {
+          open
+            .iter()
+            .find(|lot| &lot.id == id)
+            .ok_or_else(|| LotsError::UnknownLot(id.clone()))
+        })
+        .collect::<Result<_, _>>()?,
+    };
+
+    let available = lots
+      .iter()
+      .fold(Num::from(0), |sum, lot| sum + &lot.quantity);
+    if &available < quantity {
+      return Err(LotsError::InsufficientQuantity {
+        symbol: symbol.to_string(),
+        available,
+        requested: quantity.clone(),
+      })
+    }
+
+    let mut remaining = quantity.clone();
+    let mut closures = Vec::new();
+    for lot in lots {
+      if !remaining.is_positive() {
+        break
+      }
+
+      let closed = if lot.quantity < remaining {
+        lot.quantity.clone()
+      } else {
+        remaining.clone()
+      };
+      remaining -= &closed;
+
+      closures.push(Closure {
+        lot_id: lot.id.clone(),
+        cost_basis: &closed * &lot.price,
+        proceeds: &closed * price,
+        quantity: closed,
+        term: Term::from_dates(lot.acquired, time),
+      });
+    }
+    Ok(closures)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use serde_json::from_value;
+  use serde_json::json;
+
+  use test_log::test;
+
+
+  /// Create a fill activity for AAPL.
+  fn activity(id: &str, time: &str, side: &str, qty: &str, price: &str) -> TradeActivity {
+    from_value(json!({
+      "id": id,
+      "activity_type": "FILL",
+      "transaction_time": time,
+      "type": "fill",
+      "price": price,
+      "qty": qty,
+      "side": side,
+      "symbol": "AAPL",
+      "leaves_qty": "0",
+      "order_id": "904837e3-3b76-47ec-b432-046db621571b",
+      "cum_qty": qty,
+    }))
+    .unwrap()
+  }
+
+  /// Create a set of activities, with fills for three lots being
+  /// partially closed by a sale.
+  fn activities() -> Vec<TradeActivity> {
+    vec![
+      activity("3", "2022-03-01T15:00:00Z", "buy", "10", "120"),
+      activity("1", "2021-01-04T15:00:00Z", "buy", "10", "100"),
+      activity("4", "2022-04-01T15:00:00Z", "sell", "15", "130"),
+      activity("2", "2021-06-01T15:00:00Z", "buy", "10", "110"),
+    ]
+  }
+
+
+  /// Check that we reconstruct lots using the FIFO method.
+  #[test]
+  fn reconstruct_fifo() {
+    let lots = Lots::from_activities(Method::Fifo, activities());
+    let open = lots.open("AAPL");
+    assert_eq!(open.len(), 2);
+    assert_eq!(open[0].id, "2");
+    assert_eq!(open[0].quantity, Num::from(5));
+    assert_eq!(open[0].cost_basis(), Num::from(550));
+    assert_eq!(open[1].id, "3");
+    assert_eq!(open[1].quantity, Num::from(10));
+    assert_eq!(lots.symbols().collect::<Vec<_>>(), vec!["AAPL"]);
+  }
+
+  /// Check that we reconstruct lots using the LIFO method.
+  #[test]
+  fn reconstruct_lifo() {
+    let lots = Lots::from_activities(Method::Lifo, activities());
+    let open = lots.open("AAPL");
+    assert_eq!(open.len(), 2);
+    assert_eq!(open[0].id, "1");
+    assert_eq!(open[0].quantity, Num::from(10));
+    assert_eq!(open[1].id, "2");
+    assert_eq!(open[1].quantity, Num::from(5));
+    assert!(lots.open("SPY").is_empty());
+  }
+
+  /// Check that we can simulate closing positions.
+  #[test]
+  fn simulate_close() {
+    let lots = Lots::from_activities(Method::Lifo, activities());
+    let time = DateTime::from_str("2022-06-02T15:00:00Z").unwrap();
+    let price = Num::from(105);
+
+    let closures = lots
+      .simulate_close("AAPL", &Num::from(12), &price, time, &Method::Fifo.into())
+      .unwrap();
+    assert_eq!(closures.len(), 2);
+    assert_eq!(closures[0].lot_id, "1");
+    assert_eq!(closures[0].gain(), Num::from(50));
+    assert_eq!(closures[0].term, Term::Long);
+    assert_eq!(closures[1].lot_id, "2");
+    assert_eq!(closures[1].quantity, Num::from(2));
+    assert_eq!(closures[1].gain(), Num::from(-10));
+    // The lot was acquired on 2021-06-01 and so held for exactly one
+    // year and a day.
+    assert_eq!(closures[1].term, Term::Long);
+
+    let selection = Selection::Specific(vec!["2".to_string()]);
+    let closures = lots
+      .simulate_close("AAPL", &Num::from(5), &price, time, &selection)
+      .unwrap();
+    assert_eq!(closures.len(), 1);
+    assert_eq!(closures[0].lot_id, "2");
+
+    let err = lots
+      .simulate_close("AAPL", &Num::from(6), &price, time, &selection)
+      .unwrap_err();
+    assert!(matches!(err, LotsError::InsufficientQuantity { .. }));
+
+    let selection = Selection::Specific(vec!["3".to_string()]);
+    let err = lots
+      .simulate_close("AAPL", &Num::from(1), &price, time, &selection)
+      .unwrap_err();
+    assert_eq!(err, LotsError::UnknownLot("3".to_string()));
+  }
+
+  /// Check that we correctly determine the holding period of lots.
+  #[test]
+  fn holding_term() {
+    let time = |s| DateTime::from_str(s).unwrap();
+
+    let term = Term::from_dates(time("2021-03-01T15:00:00Z"), time("2022-03-01T15:00:00Z"));
+    assert_eq!(term, Term::Short);
+    let term = Term::from_dates(time("2021-03-01T15:00:00Z"), time("2022-03-02T15:00:00Z"));
+    assert_eq!(term, Term::Long);
+    let term = Term::from_dates(time("2020-02-29T15:00:00Z"), time("2021-03-01T15:00:00Z"));
+    assert_eq!(term, Term::Short);
+    let term = Term::from_dates(time("2020-02-29T15:00:00Z"), time("2021-03-02T15:00:00Z"));
+    assert_eq!(term, Term::Long);
+  }
+}
@@ -7,26 +7,85 @@ pub mod account;
 pub mod account_activities;
 /// Definitions pertaining the user's account configuration.
 pub mod account_config;
+/// Functionality for walking account activities without duplicates
+/// or gaps.
+pub mod activity_cursor;
+/// Functionality for retrieving corporate action announcements.
+pub mod announcements;
 /// Definitions surrounding assets.
 pub mod asset;
 /// Functionality for listing available assets.
 pub mod assets;
+/// Functionality for submitting multiple orders at once.
+pub mod batch;
+/// Functionality for checking orders against the available buying
+/// power.
+pub mod buying_power;
 /// Functionality for retrieving market open/close timing information
 /// for specific dates.
 pub mod calendar;
 /// Functionality for retrieving market open/close timing information
 /// for the current trading day.
 pub mod clock;
+/// Functionality for conditional requests to endpoints whose data
+/// change rarely.
+pub mod conditional;
+/// Functionality for notifying about corporate actions affecting held
+/// positions.
+pub mod corporate_actions;
+/// Functionality for submitting orders subject to a deadline.
+pub mod deadline;
+/// Functionality for placing recurring orders, e.g., for dollar-cost
+/// averaging.
+pub mod dca;
+/// Functionality for determining option expiration dates based on
+/// the trading calendar.
+pub mod expiration;
+/// Functionality for summarizing fees charged to the account.
+pub mod fees;
+/// Functionality for assembling the fill history of orders.
+pub mod fills;
+/// Functionality for flattening the account in an emergency.
+pub mod flatten;
+/// Functionality for journaling all trading actions.
+pub mod journal;
+/// Functionality for tracking tax lots.
+pub mod lots;
 /// Definitions surrounding orders.
 pub mod order;
 /// Functionality for listing orders.
 pub mod orders;
+/// Functionality for resetting paper trading accounts.
+pub mod paper;
+/// Functionality for guarding against pattern day trader violations.
+pub mod pdt;
+/// Functionality for attributing profit and loss to symbols and
+/// periods.
+pub mod pnl;
 /// Definitions surrounding open positions.
 pub mod position;
 /// Functionality for listing open positions.
 pub mod positions;
+/// Functionality for rebalancing a portfolio to target weights.
+pub mod rebalance;
+/// Functionality for simulating order fills locally based on market
+/// data.
+pub mod simulator;
+/// Functionality for looking up assets by ID, symbol, CUSIP, and
+/// name.
+pub mod symbol_index;
+/// Functionality for incrementally syncing account activities and
+/// orders.
+pub mod sync;
+/// Functionality for defining reusable order templates.
+pub mod template;
+/// Functionality for maintaining client-side trailing stops.
+pub mod trailing;
 /// Definitions for trade related updates.
 pub mod updates;
+/// Functionality for crypto funding wallets, transfers, and
+/// whitelisted addresses.
+pub mod wallets;
 /// Definitions surrounding watchlists.
 pub mod watchlist;
 /// Functionality for listing watchlists.
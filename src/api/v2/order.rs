@@ -1,6 +1,8 @@
 // Copyright (C) 2019-2022 The apca Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+#[cfg(feature = "extra-fields")]
+use std::collections::BTreeMap;
 use std::ops::Deref;
 use std::ops::Not;
 
@@ -16,13 +18,20 @@ use serde::de::IntoDeserializer;
 use serde::Deserialize;
 use serde::Deserializer;
 use serde::Serialize;
-use serde_json::from_slice as from_json;
 use serde_json::to_vec as to_json;
+#[cfg(feature = "extra-fields")]
+use serde_json::Value;
 use serde_urlencoded::to_string as to_query;
 
 use uuid::Uuid;
 
 use crate::api::v2::asset;
+use crate::data::price::AssetKind;
+use crate::money;
+use crate::util::datetime_from_str;
+use crate::util::optional_datetime_from_str;
+use crate::util::optional_flattened;
+use crate::util::optional_num_from_str_or_null;
 use crate::util::vec_from_str;
 use crate::Str;
 
@@ -133,6 +142,13 @@ impl Status {
       Self::Replaced | Self::Filled | Self::Canceled | Self::Expired | Self::Rejected
     )
   }
+
+  /// Check whether the status is active, i.e., the associated order is
+  /// open and may still be filled or otherwise change.
+  #[inline]
+  pub fn is_active(self) -> bool {
+    !self.is_terminal() && !matches!(self, Self::Unknown)
+  }
 }
 
 
@@ -160,6 +176,42 @@ impl Not for Side {
 }
 
 
+/// The intent of an order with respect to the position it affects.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum PositionIntent {
+  /// Buy to open a new or increase an existing long position.
+  #[serde(rename = "buy_to_open")]
+  BuyToOpen,
+  /// Buy to close or reduce an existing short position.
+  #[serde(rename = "buy_to_close")]
+  BuyToClose,
+  /// Sell to open a new or increase an existing short position.
+  #[serde(rename = "sell_to_open")]
+  SellToOpen,
+  /// Sell to close or reduce an existing long position.
+  #[serde(rename = "sell_to_close")]
+  SellToClose,
+}
+
+impl PositionIntent {
+  /// Retrieve the side an order with this intent is on.
+  #[inline]
+  pub fn side(self) -> Side {
+    match self {
+      Self::BuyToOpen | Self::BuyToClose => Side::Buy,
+      Self::SellToOpen | Self::SellToClose => Side::Sell,
+    }
+  }
+
+  /// Check whether the intent is to open (or increase) a position, as
+  /// opposed to closing (or reducing) one.
+  #[inline]
+  pub fn is_opening(self) -> bool {
+    matches!(self, Self::BuyToOpen | Self::SellToOpen)
+  }
+}
+
+
 /// The class an order belongs to.
 #[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
 pub enum Class {
@@ -194,7 +246,6 @@ impl Default for Class {
 
 
 /// The type of an order.
-// Note that we currently do not support `stop_limit` orders.
 #[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
 pub enum Type {
   /// A market order.
@@ -224,6 +275,7 @@ impl Default for Type {
 
 /// A description of the time for which an order is valid.
 #[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq)]
+#[non_exhaustive]
 pub enum TimeInForce {
   /// The order is good for the day, and it will be canceled
   /// automatically at the end of Regular Trading Hours if unfilled.
@@ -240,6 +292,42 @@ pub enum TimeInForce {
   /// auction. Any unfilled orders after the close will be canceled.
   #[serde(rename = "cls")]
   UntilMarketClose,
+  /// The order requires all or part of it to be executed immediately.
+  /// Any unfilled portion of the order is canceled.
+  #[serde(rename = "ioc")]
+  ImmediateOrCancel,
+  /// The order is only executed if the entire quantity can be filled
+  /// immediately, otherwise it is canceled.
+  #[serde(rename = "fok")]
+  FillOrKill,
+}
+
+impl TimeInForce {
+  /// Check whether the time in force is permitted for an order of the
+  /// given type and class.
+  ///
+  /// Market and limit orders support all times in force, whereas stop,
+  /// stop limit, and trailing stop orders can only be good for the day
+  /// or until canceled. The same restriction applies to all advanced
+  /// order classes (bracket, one-cancels-other, and
+  /// one-triggers-other).
+  pub fn is_permitted(self, type_: Type, class: Class) -> bool {
+    let day_or_gtc = matches!(self, Self::Day | Self::UntilCanceled);
+
+    match class {
+      Class::Simple => (),
+      Class::Bracket | Class::OneCancelsOther | Class::OneTriggersOther => {
+        if !day_or_gtc {
+          return false
+        }
+      },
+    }
+
+    match type_ {
+      Type::Market | Type::Limit => true,
+      Type::Stop | Type::StopLimit | Type::TrailingStop => day_or_gtc,
+    }
+  }
 }
 
 impl Default for TimeInForce {
@@ -329,9 +417,22 @@ impl From<StopLoss> for StopLossSerde {
 }
 
 
+#[derive(Debug, Deserialize)]
+struct AmountSerde {
+  #[serde(rename = "qty", default)]
+  quantity: Option<Num>,
+  #[serde(rename = "notional", default)]
+  notional: Option<Num>,
+}
+
+
 /// An abstraction to be able to handle orders in both notional and quantity units.
+///
+/// Order responses report both a `qty` and a `notional` field, only one
+/// of which is set. Upon deserialization they are folded into the
+/// respective variant.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-#[serde(untagged)]
+#[serde(untagged, try_from = "AmountSerde")]
 pub enum Amount {
   /// Wrapper for the quantity field.
   Quantity {
@@ -349,6 +450,18 @@ pub enum Amount {
   },
 }
 
+impl TryFrom<AmountSerde> for Amount {
+  type Error = &'static str;
+
+  fn try_from(other: AmountSerde) -> Result<Self, Self::Error> {
+    match (other.quantity, other.notional) {
+      (Some(quantity), _) => Ok(Self::Quantity { quantity }),
+      (None, Some(notional)) => Ok(Self::Notional { notional }),
+      (None, None) => Err("amount has neither `qty` nor `notional` set"),
+    }
+  }
+}
+
 impl Amount {
   /// Helper method to initialize a quantity.
   #[inline]
@@ -393,6 +506,8 @@ pub struct OrderReqInit {
   pub extended_hours: bool,
   /// See `OrderReq::client_order_id`.
   pub client_order_id: Option<String>,
+  /// See `OrderReq::position_intent`.
+  pub position_intent: Option<PositionIntent>,
   #[doc(hidden)]
   pub _non_exhaustive: (),
 }
@@ -423,6 +538,7 @@ impl OrderReqInit {
       client_order_id: self.client_order_id,
       trail_price: self.trail_price,
       trail_percent: self.trail_percent,
+      position_intent: self.position_intent,
     }
   }
 }
@@ -482,6 +598,120 @@ pub struct OrderReq {
   /// The documented maximum length is 48 characters.
   #[serde(rename = "client_order_id")]
   pub client_order_id: Option<String>,
+  /// The intent of the order with respect to the position it affects.
+  ///
+  /// The intent has to be consistent with the order's side. It is
+  /// required for some options orders, but otherwise inferred by the
+  /// server if not provided.
+  #[serde(
+    rename = "position_intent",
+    default,
+    skip_serializing_if = "Option::is_none"
+  )]
+  pub position_intent: Option<PositionIntent>,
+}
+
+
+impl OrderReq {
+  /// Create a request for a market order.
+  #[inline]
+  pub fn market<S>(symbol: S, side: Side, amount: Amount) -> Self
+  where
+    S: Into<String>,
+  {
+    OrderReqInit::default().init(symbol, side, amount)
+  }
+
+  /// Create a request for a limit order.
+  pub fn limit<S>(symbol: S, side: Side, amount: Amount, limit_price: Num) -> Self
+  where
+    S: Into<String>,
+  {
+    OrderReqInit {
+      type_: Type::Limit,
+      limit_price: Some(limit_price),
+      ..Default::default()
+    }
+    .init(symbol, side, amount)
+  }
+
+  /// Create a request for a stop order.
+  pub fn stop<S>(symbol: S, side: Side, amount: Amount, stop_price: Num) -> Self
+  where
+    S: Into<String>,
+  {
+    OrderReqInit {
+      type_: Type::Stop,
+      stop_price: Some(stop_price),
+      ..Default::default()
+    }
+    .init(symbol, side, amount)
+  }
+
+  /// Create a request for a stop limit order, which turns into a limit
+  /// order at `limit_price` once `stop_price` has been reached.
+  pub fn stop_limit<S>(
+    symbol: S,
+    side: Side,
+    amount: Amount,
+    stop_price: Num,
+    limit_price: Num,
+  ) -> Self
+  where
+    S: Into<String>,
+  {
+    OrderReqInit {
+      type_: Type::StopLimit,
+      stop_price: Some(stop_price),
+      limit_price: Some(limit_price),
+      ..Default::default()
+    }
+    .init(symbol, side, amount)
+  }
+
+  /// Round all prices of the order to the nearest valid tick for an
+  /// asset of the given class, so that the order does not get rejected
+  /// for using sub-penny increments.
+  ///
+  /// See [`money::tick_size`] for details on the increments used.
+  pub fn round_to_tick(&mut self, class: asset::Class) {
+    let round = |price: &mut Num| *price = money::round_to_tick(price, class);
+
+    self.limit_price.iter_mut().for_each(round);
+    self.stop_price.iter_mut().for_each(round);
+    self.trail_price.iter_mut().for_each(round);
+
+    match &mut self.take_profit {
+      Some(TakeProfit::Limit(limit)) => round(limit),
+      None => (),
+    }
+    match &mut self.stop_loss {
+      Some(StopLoss::Stop(stop)) => round(stop),
+      Some(StopLoss::StopLimit(stop, limit)) => {
+        round(stop);
+        round(limit);
+      },
+      None => (),
+    }
+  }
+
+  /// Infer the class of the asset the order is for from its symbol.
+  ///
+  /// `asset::Class::Unknown` is reported for assets identified by ID
+  /// as well as for option contracts.
+  fn asset_class(&self) -> asset::Class {
+    let symbol = match &self.symbol {
+      asset::Symbol::Sym(symbol) | asset::Symbol::SymExchg(symbol, _) => symbol,
+      asset::Symbol::SymExchgCls(_, _, class) => return *class,
+      asset::Symbol::Id(..) => return asset::Class::Unknown,
+    };
+
+    match AssetKind::infer(symbol) {
+      AssetKind::Equity => asset::Class::UsEquity,
+      AssetKind::Crypto => asset::Class::Crypto,
+      AssetKind::Option => asset::Class::Unknown,
+    }
+  }
 }
 
 
@@ -554,6 +784,51 @@ where
 }
 
 
+/// Order properties denominated in the local currency, for accounts
+/// using local currency trading.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct LocalCurrency {
+  /// The local currency's code, e.g., `JPY`.
+  #[serde(rename = "currency", default)]
+  pub currency: Option<String>,
+  /// The notional amount being requested.
+  #[serde(
+    rename = "notional_lct",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
+  pub notional: Option<Num>,
+  /// The limit price.
+  #[serde(
+    rename = "limit_price_lct",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
+  pub limit_price: Option<Num>,
+  /// The stop price.
+  #[serde(
+    rename = "stop_price_lct",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
+  pub stop_price: Option<Num>,
+  /// The average price at which the order was filled.
+  #[serde(
+    rename = "filled_avg_price_lct",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
+  pub average_fill_price: Option<Num>,
+  /// The rate used for converting between USD and the local currency.
+  #[serde(
+    rename = "swap_rate",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
+  pub swap_rate: Option<Num>,
+}
+
+
 /// A single order as returned by the /v2/orders endpoint on a GET
 /// request.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -568,22 +843,42 @@ pub struct Order {
   #[serde(rename = "status")]
   pub status: Status,
   /// Timestamp this order was created at.
-  #[serde(rename = "created_at")]
+  #[serde(rename = "created_at", deserialize_with = "datetime_from_str")]
   pub created_at: DateTime<Utc>,
   /// Timestamp this order was updated at last.
-  #[serde(rename = "updated_at")]
+  #[serde(
+    rename = "updated_at",
+    default,
+    deserialize_with = "optional_datetime_from_str"
+  )]
   pub updated_at: Option<DateTime<Utc>>,
   /// Timestamp this order was submitted at.
-  #[serde(rename = "submitted_at")]
+  #[serde(
+    rename = "submitted_at",
+    default,
+    deserialize_with = "optional_datetime_from_str"
+  )]
   pub submitted_at: Option<DateTime<Utc>>,
   /// Timestamp this order was filled at.
-  #[serde(rename = "filled_at")]
+  #[serde(
+    rename = "filled_at",
+    default,
+    deserialize_with = "optional_datetime_from_str"
+  )]
   pub filled_at: Option<DateTime<Utc>>,
   /// Timestamp this order expired at.
-  #[serde(rename = "expired_at")]
+  #[serde(
+    rename = "expired_at",
+    default,
+    deserialize_with = "optional_datetime_from_str"
+  )]
   pub expired_at: Option<DateTime<Utc>>,
   /// Timestamp this order expired at.
-  #[serde(rename = "canceled_at")]
+  #[serde(
+    rename = "canceled_at",
+    default,
+    deserialize_with = "optional_datetime_from_str"
+  )]
   pub canceled_at: Option<DateTime<Utc>>,
   /// The order's asset class.
   #[serde(rename = "asset_class")]
@@ -613,19 +908,39 @@ pub struct Order {
   #[serde(rename = "time_in_force")]
   pub time_in_force: TimeInForce,
   /// The limit price.
-  #[serde(rename = "limit_price")]
+  #[serde(
+    rename = "limit_price",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub limit_price: Option<Num>,
   /// The stop price.
-  #[serde(rename = "stop_price")]
+  #[serde(
+    rename = "stop_price",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub stop_price: Option<Num>,
   /// The dollar value away from the high water mark.
-  #[serde(rename = "trail_price")]
+  #[serde(
+    rename = "trail_price",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub trail_price: Option<Num>,
   /// The percent value away from the high water mark.
-  #[serde(rename = "trail_percent")]
+  #[serde(
+    rename = "trail_percent",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub trail_percent: Option<Num>,
   /// The average price at which the order was filled.
-  #[serde(rename = "filled_avg_price")]
+  #[serde(
+    rename = "filled_avg_price",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub average_fill_price: Option<Num>,
   /// If true, the order is eligible for execution outside regular
   /// trading hours.
@@ -637,6 +952,66 @@ pub struct Order {
   /// take profit part of a bracket-style order.
   #[serde(rename = "legs", deserialize_with = "vec_from_str")]
   pub legs: Vec<Order>,
+  /// The ID of the order that replaced this one, if any.
+  #[serde(rename = "replaced_by")]
+  pub replaced_by: Option<Id>,
+  /// The ID of the order this one replaced, if any.
+  #[serde(rename = "replaces")]
+  pub replaces: Option<Id>,
+  /// The intent of the order with respect to the position it affects,
+  /// if reported.
+  #[serde(rename = "position_intent", default)]
+  pub position_intent: Option<PositionIntent>,
+  /// Properties in the local currency, for accounts using local
+  /// currency trading.
+  #[serde(flatten, deserialize_with = "optional_flattened")]
+  pub local_currency: Option<LocalCurrency>,
+  /// Any fields reported by the API that are not (yet) modeled
+  /// explicitly, indexed by name.
+  #[cfg(feature = "extra-fields")]
+  #[serde(flatten, deserialize_with = "crate::schema::capture::<Order, _>")]
+  pub extra: BTreeMap<String, Value>,
+}
+
+impl Order {
+  /// Retrieve the quantity that remains to be filled.
+  ///
+  /// `None` is returned for orders placed for a notional amount, as
+  /// the quantity they will ultimately fill is not known upfront.
+  pub fn remaining_quantity(&self) -> Option<Num> {
+    match &self.amount {
+      Amount::Quantity { quantity } => Some(quantity - &self.filled_quantity),
+      Amount::Notional { .. } => None,
+    }
+  }
+
+  /// Retrieve the notional value filled so far, i.e., the filled
+  /// quantity times the average fill price.
+  pub fn filled_notional(&self) -> Option<Num> {
+    self
+      .average_fill_price
+      .as_ref()
+      .map(|price| &self.filled_quantity * price)
+  }
+
+  /// Retrieve the take profit leg of a bracket-style order, if any.
+  ///
+  /// Legs are only reported when orders were listed with nesting
+  /// enabled or when retrieving the order directly.
+  pub fn take_profit_leg(&self) -> Option<&Order> {
+    self.legs.iter().find(|leg| leg.type_ == Type::Limit)
+  }
+
+  /// Retrieve the stop loss leg of a bracket-style order, if any.
+  ///
+  /// Legs are only reported when orders were listed with nesting
+  /// enabled or when retrieving the order directly.
+  pub fn stop_loss_leg(&self) -> Option<&Order> {
+    self
+      .legs
+      .iter()
+      .find(|leg| matches!(leg.type_, Type::Stop | Type::StopLimit))
+  }
 }
 
 
@@ -726,7 +1101,12 @@ Endpoint! {
   }
 
   fn body(input: &Self::Input) -> Result<Option<Bytes>, Self::ConversionError> {
-    let json = to_json(input)?;
+    // Prices not on a valid tick are rejected by the server, so round
+    // them before submission.
+    let mut request = input.clone();
+    let () = request.round_to_tick(input.asset_class());
+
+    let json = to_json(&request)?;
     let bytes = Bytes::from(json);
     Ok(Some(bytes))
   }
@@ -775,7 +1155,7 @@ Endpoint! {
 }
 
 
-EndpointNoParse! {
+Endpoint! {
   /// The representation of a DELETE request to the /v2/orders/<order-id>
   /// endpoint.
   pub Delete(Id),
@@ -798,16 +1178,6 @@ EndpointNoParse! {
   fn path(input: &Self::Input) -> Str {
     format!("/v2/orders/{}", input.as_simple()).into()
   }
-
-  #[inline]
-  fn parse(body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
-    debug_assert_eq!(body, b"");
-    Ok(())
-  }
-
-  fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
-    from_json::<Self::ApiError>(body).map_err(|_| body.to_vec())
-  }
 }
 
 
@@ -819,7 +1189,11 @@ mod tests {
 
   use futures::TryFutureExt;
 
+  use http_endpoint::Endpoint as _;
+
   use serde_json::from_slice as from_json;
+  use serde_json::json;
+  use serde_json::Value;
 
   use test_log::test;
 
@@ -829,11 +1203,113 @@ mod tests {
   use crate::api::v2::asset::Exchange;
   use crate::api::v2::asset::Symbol;
   use crate::api::v2::order_util::order_aapl;
-  use crate::api_info::ApiInfo;
-  use crate::Client;
+  use crate::live;
+  use crate::test_util::order;
+  use crate::test_util::order_json;
   use crate::RequestError;
 
 
+  /// Check that we classify order statuses correctly.
+  #[test]
+  fn classify_status() {
+    assert!(Status::Filled.is_terminal());
+    assert!(!Status::Filled.is_active());
+    assert!(!Status::PartiallyFilled.is_terminal());
+    assert!(Status::PartiallyFilled.is_active());
+    assert!(Status::Held.is_active());
+    assert!(!Status::Unknown.is_terminal());
+    assert!(!Status::Unknown.is_active());
+  }
+
+  /// Check that we round the prices of an order request to valid
+  /// ticks.
+  #[test]
+  fn round_order_prices_to_tick() {
+    let mut request = OrderReqInit {
+      class: Class::Bracket,
+      type_: Type::Limit,
+      limit_price: Some(Num::new(1001234, 10000)),
+      take_profit: Some(TakeProfit::Limit(Num::new(1100001, 10000))),
+      stop_loss: Some(StopLoss::StopLimit(
+        Num::new(905, 10),
+        Num::new(90049, 1000),
+      )),
+      ..Default::default()
+    }
+    .init("SPY", Side::Buy, Amount::quantity(1));
+
+    request.round_to_tick(asset::Class::UsEquity);
+    assert_eq!(request.limit_price, Some(Num::new(10012, 100)));
+    assert_eq!(request.stop_price, None);
+    assert_eq!(request.take_profit, Some(TakeProfit::Limit(Num::from(110))));
+    assert_eq!(
+      request.stop_loss,
+      Some(StopLoss::StopLimit(Num::new(905, 10), Num::new(9005, 100)))
+    );
+
+    let mut request = OrderReqInit {
+      type_: Type::TrailingStop,
+      trail_price: Some(Num::new(1234, 1000)),
+      ..Default::default()
+    }
+    .init("SPY", Side::Sell, Amount::quantity(1));
+
+    request.round_to_tick(asset::Class::UsEquity);
+    assert_eq!(request.trail_price, Some(Num::new(123, 100)));
+  }
+
+  /// Check that prices are rounded to valid ticks when submitting an
+  /// order.
+  #[test]
+  fn round_submitted_order_prices() {
+    let price = Num::new(1001234, 10000);
+    let request = OrderReq::limit("SPY", Side::Buy, Amount::quantity(1), price);
+    let body = Post::body(&request).unwrap().unwrap();
+    let json = from_json::<Value>(&body).unwrap();
+    assert_eq!(json["limit_price"], json!("100.12"));
+
+    let price = Num::new(1001234, 10000);
+    let request = OrderReq::limit("BTC/USD", Side::Buy, Amount::quantity(1), price);
+    let body = Post::body(&request).unwrap().unwrap();
+    let json = from_json::<Value>(&body).unwrap();
+    assert_eq!(json["limit_price"], json!("100.1234"));
+  }
+
+  /// Check that the order type specific constructors set the type
+  /// along with the matching prices.
+  #[test]
+  fn construct_typed_orders() {
+    let request = OrderReq::market("SPY", Side::Buy, Amount::quantity(1));
+    assert_eq!(request.type_, Type::Market);
+    assert_eq!(request.limit_price, None);
+    assert_eq!(request.stop_price, None);
+
+    let request = OrderReq::limit("SPY", Side::Buy, Amount::quantity(1), Num::from(100));
+    assert_eq!(request.type_, Type::Limit);
+    assert_eq!(request.limit_price, Some(Num::from(100)));
+    assert_eq!(request.stop_price, None);
+
+    let request = OrderReq::stop("SPY", Side::Sell, Amount::quantity(1), Num::from(90));
+    assert_eq!(request.type_, Type::Stop);
+    assert_eq!(request.limit_price, None);
+    assert_eq!(request.stop_price, Some(Num::from(90)));
+
+    let request = OrderReq::stop_limit(
+      "SPY",
+      Side::Sell,
+      Amount::quantity(1),
+      Num::from(90),
+      Num::from(89),
+    );
+    assert_eq!(request.type_, Type::StopLimit);
+    assert_eq!(request.side, Side::Sell);
+    assert_eq!(request.stop_price, Some(Num::from(90)));
+    assert_eq!(request.limit_price, Some(Num::from(89)));
+    assert!(request
+      .time_in_force
+      .is_permitted(request.type_, request.class));
+  }
+
   /// Check that we can serialize a [`Side`] object.
   #[test]
   fn emit_side() {
@@ -856,6 +1332,65 @@ mod tests {
     assert_eq!(to_json(&Type::Stop).unwrap(), br#""stop""#);
   }
 
+  /// Check that position intents are emitted only when set and agree
+  /// with their side.
+  #[test]
+  fn emit_position_intent() {
+    let request = OrderReqInit::default().init("SPY", Side::Buy, Amount::quantity(1));
+    let json = String::from_utf8(to_json(&request).unwrap()).unwrap();
+    assert!(!json.contains("position_intent"), "{}", json);
+
+    let request = OrderReqInit {
+      position_intent: Some(PositionIntent::SellToClose),
+      ..Default::default()
+    }
+    .init("SPY", Side::Sell, Amount::quantity(1));
+    let json = to_json(&request).unwrap();
+    let request = from_json::<OrderReq>(&json).unwrap();
+    assert_eq!(request.position_intent, Some(PositionIntent::SellToClose));
+
+    for intent in [PositionIntent::BuyToOpen, PositionIntent::BuyToClose] {
+      assert_eq!(intent.side(), Side::Buy);
+    }
+    assert!(PositionIntent::SellToOpen.is_opening());
+    assert!(!PositionIntent::SellToClose.is_opening());
+  }
+
+  /// Check that we can serialize and deserialize [`TimeInForce`]
+  /// objects.
+  #[test]
+  fn serialize_deserialize_time_in_force() {
+    let variants = [
+      (TimeInForce::Day, "day"),
+      (TimeInForce::UntilCanceled, "gtc"),
+      (TimeInForce::UntilMarketOpen, "opg"),
+      (TimeInForce::UntilMarketClose, "cls"),
+      (TimeInForce::ImmediateOrCancel, "ioc"),
+      (TimeInForce::FillOrKill, "fok"),
+    ];
+
+    for (time_in_force, expected) in variants {
+      let json = to_json(&time_in_force).unwrap();
+      assert_eq!(json, format!(r#""{}""#, expected).into_bytes());
+      assert_eq!(from_json::<TimeInForce>(&json).unwrap(), time_in_force);
+    }
+  }
+
+  /// Check that we correctly determine which times in force are
+  /// permitted for which orders.
+  #[test]
+  fn permitted_time_in_force() {
+    assert!(TimeInForce::FillOrKill.is_permitted(Type::Market, Class::Simple));
+    assert!(TimeInForce::ImmediateOrCancel.is_permitted(Type::Limit, Class::Simple));
+    assert!(TimeInForce::UntilMarketOpen.is_permitted(Type::Limit, Class::Simple));
+    assert!(TimeInForce::UntilCanceled.is_permitted(Type::Stop, Class::Simple));
+    assert!(!TimeInForce::UntilMarketClose.is_permitted(Type::StopLimit, Class::Simple));
+    assert!(!TimeInForce::ImmediateOrCancel.is_permitted(Type::TrailingStop, Class::Simple));
+    assert!(TimeInForce::Day.is_permitted(Type::Limit, Class::Bracket));
+    assert!(!TimeInForce::FillOrKill.is_permitted(Type::Market, Class::OneCancelsOther));
+    assert!(!TimeInForce::UntilMarketOpen.is_permitted(Type::Limit, Class::OneTriggersOther));
+  }
+
   /// Make sure that we can serialize and deserialize order legs.
   #[test]
   fn serialize_deserialize_legs() {
@@ -896,6 +1431,62 @@ mod tests {
     assert_eq!(amount, Amount::notional(Num::from_str("15.12").unwrap()));
   }
 
+  /// Check that we can parse amounts with the respective other field
+  /// explicitly set to `null`, as reported in order responses.
+  #[test]
+  fn parse_amount_with_null_field() {
+    let serialized = br#"{"qty": "15", "notional": null}"#;
+    let amount = from_json::<Amount>(serialized).unwrap();
+    assert_eq!(amount, Amount::quantity(15));
+
+    let serialized = br#"{"qty": null, "notional": "15.12"}"#;
+    let amount = from_json::<Amount>(serialized).unwrap();
+    assert_eq!(amount, Amount::notional(Num::from_str("15.12").unwrap()));
+
+    let serialized = br#"{"qty": null, "notional": null}"#;
+    let err = from_json::<Amount>(serialized).unwrap_err();
+    assert!(err.to_string().contains("neither `qty` nor `notional`"));
+  }
+
+  /// Verify that we can deserialize an order placed for a notional
+  /// amount.
+  #[test]
+  fn deserialize_notional_order() {
+    let json = order_json(json!({"qty": null, "notional": "500", "order_class": ""}));
+    let json = json.to_string();
+    let order = from_json::<Order>(json.as_bytes()).unwrap();
+    assert_eq!(order.amount, Amount::notional(500));
+    assert_eq!(order.remaining_quantity(), None);
+  }
+
+  /// Verify that we can deserialize an order placed by an account using
+  /// local currency trading.
+  #[test]
+  fn deserialize_local_currency_order() {
+    let json = order_json(json!({
+      "qty": "2",
+      "type": "limit",
+      "order_class": "",
+      "limit_price": "150",
+      "limit_price_lct": "20475",
+      "stop_price_lct": null,
+      "filled_avg_price_lct": null,
+      "currency": "JPY",
+      "swap_rate": "136.5",
+    }))
+    .to_string();
+    let json = json.as_bytes();
+
+    let order = from_json::<Order>(&to_json(&from_json::<Order>(json).unwrap()).unwrap()).unwrap();
+    let local = order.local_currency.unwrap();
+    assert_eq!(local.currency.as_deref(), Some("JPY"));
+    assert_eq!(local.limit_price, Some(Num::from(20475)));
+    assert_eq!(local.stop_price, None);
+    assert_eq!(local.swap_rate, Some(Num::new(1365, 10)));
+    #[cfg(feature = "extra-fields")]
+    assert!(order.extra.is_empty(), "{:?}", order.extra);
+  }
+
   /// Verify that we can deserialize and serialize a reference order.
   #[test]
   fn deserialize_serialize_reference_order() {
@@ -921,6 +1512,7 @@ mod tests {
     "limit_price": "107.00",
     "stop_price": "106.00",
     "filled_avg_price": "106.25",
+    "position_intent": "buy_to_open",
     "status": "accepted",
     "extended_hours": false,
     "legs": null
@@ -941,6 +1533,13 @@ mod tests {
     assert_eq!(order.limit_price, Some(Num::from(107)));
     assert_eq!(order.stop_price, Some(Num::from(106)));
     assert_eq!(order.average_fill_price, Some(Num::new(10625, 100)));
+    assert_eq!(order.local_currency, None);
+    assert_eq!(order.position_intent, Some(PositionIntent::BuyToOpen));
+    #[cfg(feature = "extra-fields")]
+    assert_eq!(
+      order.extra.get("failed_at"),
+      Some(&Value::from("2018-10-05T05:48:59Z"))
+    );
   }
 
   /// Verify that we can deserialize an order with an empty order class.
@@ -980,6 +1579,61 @@ mod tests {
     assert_eq!(order.class, Class::Simple);
   }
 
+  /// Check that we compute the fill related properties of an order
+  /// correctly.
+  #[test]
+  fn compute_fill_properties() {
+    let mut order = order(json!({
+      "qty": "15",
+      "filled_qty": "4",
+      "filled_avg_price": "106.25",
+      "status": "partially_filled",
+    }));
+    assert_eq!(order.remaining_quantity(), Some(Num::from(11)));
+    assert_eq!(order.filled_notional(), Some(Num::from(425)));
+
+    order.amount = Amount::notional(500);
+    assert_eq!(order.remaining_quantity(), None);
+
+    order.average_fill_price = None;
+    assert_eq!(order.filled_notional(), None);
+  }
+
+  /// Check that we can navigate the legs of a bracket order.
+  #[test]
+  fn navigate_bracket_legs() {
+    let leg = |id: &str, type_: &str, legs: Value| {
+      order_json(json!({
+        "id": id,
+        "client_order_id": id,
+        "updated_at": null,
+        "submitted_at": null,
+        "type": type_,
+        "order_class": "bracket",
+        "time_in_force": "gtc",
+        "status": "held",
+        "legs": legs,
+      }))
+    };
+
+    let take_profit = leg("57c0b4bd-d66d-4ef4-8e8a-2d5e9c9f1a3b", "limit", Value::Null);
+    let stop_loss = leg("6a2f9b2e-3b1a-4c64-bd1b-6a6f1b0b9c4d", "stop", Value::Null);
+    let legs = json!([take_profit, stop_loss]);
+    let json = leg("904837e3-3b76-47ec-b432-046db621571b", "market", legs).to_string();
+
+    let order = from_json::<Order>(json.as_bytes()).unwrap();
+    assert_eq!(order.legs.len(), 2);
+    assert_eq!(
+      order.take_profit_leg().unwrap().id.to_string(),
+      "57c0b4bd-d66d-4ef4-8e8a-2d5e9c9f1a3b"
+    );
+    assert_eq!(
+      order.stop_loss_leg().unwrap().id.to_string(),
+      "6a2f9b2e-3b1a-4c64-bd1b-6a6f1b0b9c4d"
+    );
+    assert_eq!(order.legs[0].take_profit_leg(), None);
+  }
+
   /// Check that we can serialize and deserialize an [`OrderReq`].
   #[test]
   fn serialize_deserialize_order_request() {
@@ -1011,6 +1665,7 @@ mod tests {
 
   /// Verify that we can submit a limit order.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn submit_limit_order() {
     async fn test(extended_hours: bool) -> Result<(), RequestError<PostError>> {
       let symbol = Symbol::SymExchgCls("SPY".to_string(), Exchange::Arca, asset::Class::UsEquity);
@@ -1029,10 +1684,10 @@ mod tests {
         stop_loss: None,
         extended_hours,
         client_order_id: None,
+        position_intent: None,
       };
 
-      let api_info = ApiInfo::from_env().unwrap();
-      let client = Client::new(api_info);
+      let client = live::client().unwrap();
 
       let order = client.issue::<Post>(&request).await?;
       client.issue::<Delete>(&order.id).await.unwrap();
@@ -1066,6 +1721,7 @@ mod tests {
 
   /// Check that we can properly submit a trailing stop price order.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn submit_trailing_stop_price_order() {
     let request = OrderReqInit {
       type_: Type::TrailingStop,
@@ -1074,8 +1730,7 @@ mod tests {
     }
     .init("SPY", Side::Buy, Amount::quantity(1));
 
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let order = client.issue::<Post>(&request).await.unwrap();
     client.issue::<Delete>(&order.id).await.unwrap();
@@ -1094,6 +1749,7 @@ mod tests {
 
   /// Check that we can properly submit a trailing stop percent order.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn submit_trailing_stop_percent_order() {
     let request = OrderReqInit {
       type_: Type::TrailingStop,
@@ -1102,8 +1758,7 @@ mod tests {
     }
     .init("SPY", Side::Buy, Amount::quantity(1));
 
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let order = client.issue::<Post>(&request).await.unwrap();
     client.issue::<Delete>(&order.id).await.unwrap();
@@ -1121,6 +1776,7 @@ mod tests {
   }
 
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn submit_bracket_order() {
     let request = OrderReqInit {
       class: Class::Bracket,
@@ -1132,8 +1788,7 @@ mod tests {
     }
     .init("SPY", Side::Buy, Amount::quantity(1));
 
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let order = client.issue::<Post>(&request).await.unwrap();
     client.issue::<Delete>(&order.id).await.unwrap();
@@ -1157,6 +1812,7 @@ mod tests {
   }
 
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn submit_one_triggers_other_order() {
     let request = OrderReqInit {
       class: Class::OneTriggersOther,
@@ -1167,8 +1823,7 @@ mod tests {
     }
     .init("SPY", Side::Buy, Amount::quantity(1));
 
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let order = client.issue::<Post>(&request).await.unwrap();
     client.issue::<Delete>(&order.id).await.unwrap();
@@ -1192,10 +1847,10 @@ mod tests {
 
   /// Test submission of market open and market close orders.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn submit_other_order_types() {
     async fn test(time_in_force: TimeInForce) {
-      let api_info = ApiInfo::from_env().unwrap();
-      let client = Client::new(api_info);
+      let client = live::client().unwrap();
 
       let request = OrderReqInit {
         type_: Type::Limit,
@@ -1226,9 +1881,9 @@ mod tests {
   /// Check that we see the expected error being reported when
   /// attempting to submit an unsatisfiable order.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn submit_unsatisfiable_order() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let request = OrderReqInit {
       type_: Type::Limit,
@@ -1248,12 +1903,12 @@ mod tests {
 
   /// Test that we can submit an order with a notional amount.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn submit_unsatisfiable_notional_order() {
     let request =
       OrderReqInit::default().init("SPY", Side::Buy, Amount::notional(Num::new(10_000_000, 3)));
 
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let result = client.issue::<Post>(&request).await;
     let err = result.unwrap_err();
@@ -1266,12 +1921,12 @@ mod tests {
 
   /// Test that we can submit an order with a fractional quantity.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn submit_unsatisfiable_fractional_order() {
     let qty = Num::from(1_000_000) + Num::new(1, 2);
     let request = OrderReqInit::default().init("SPY", Side::Buy, Amount::quantity(qty));
 
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let result = client.issue::<Post>(&request).await;
     let err = result.unwrap_err();
@@ -1285,10 +1940,10 @@ mod tests {
   /// Check that we get back the expected error when attempting to
   /// cancel an invalid (non-existent) order.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn cancel_invalid_order() {
     let id = Id(Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap());
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let result = client.issue::<Delete>(&id).await;
     let err = result.unwrap_err();
 
@@ -1300,9 +1955,9 @@ mod tests {
 
   /// Check that we can retrieve an order given its ID.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn retrieve_order_by_id() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let posted = order_aapl(&client).await.unwrap();
     let result = client.issue::<Get>(&posted.id).await;
     client.issue::<Delete>(&posted.id).await.unwrap();
@@ -1321,10 +1976,10 @@ mod tests {
   }
 
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn retrieve_non_existent_order() {
     let id = Id(Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap());
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let result = client.issue::<Get>(&id).await;
     let err = result.unwrap_err();
 
@@ -1335,6 +1990,7 @@ mod tests {
   }
 
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn extended_hours_market_order() {
     let request = OrderReqInit {
       extended_hours: true,
@@ -1342,8 +1998,7 @@ mod tests {
     }
     .init("SPY", Side::Buy, Amount::quantity(1));
 
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     // We are submitting a market order with extended_hours, that is
     // invalid as per the Alpaca documentation.
@@ -1358,6 +2013,7 @@ mod tests {
 
   /// Check that we can change an existing order.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn change_order() {
     let request = OrderReqInit {
       type_: Type::Limit,
@@ -1366,8 +2022,7 @@ mod tests {
     }
     .init("AAPL", Side::Buy, Amount::quantity(1));
 
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let order = client.issue::<Post>(&request).await.unwrap();
 
     let request = ChangeReqInit {
@@ -1406,6 +2061,7 @@ mod tests {
 
   /// Test changing of a trailing stop order.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn change_trail_stop_order() {
     let request = OrderReqInit {
       type_: Type::TrailingStop,
@@ -1414,8 +2070,7 @@ mod tests {
     }
     .init("SPY", Side::Buy, Amount::quantity(1));
 
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let order = client.issue::<Post>(&request).await.unwrap();
     assert_eq!(order.trail_price, Some(Num::from(20)));
 
@@ -1444,6 +2099,7 @@ mod tests {
   }
 
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn with_client_order_id() {
     // We need a truly random identifier here, because Alpaca will never
     // forget any client order ID and any ID previously used one cannot
@@ -1458,8 +2114,7 @@ mod tests {
     }
     .init("SPY", Side::Buy, Amount::quantity(1));
 
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let (issued, retrieved) = client
       .issue::<Post>(&request)
@@ -1,13 +1,24 @@
 // Copyright (C) 2019-2022 The apca Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+
+use http::Method;
+
 use serde::Deserialize;
 use serde::Serialize;
 use serde_urlencoded::to_string as to_query;
 
+use tracing::warn;
+
+use crate::api::v2::account_activities::Direction;
+use crate::api::v2::order;
 use crate::api::v2::order::Order;
 use crate::util::string_slice_to_str;
 use crate::util::vec_from_comma_separated_str;
+use crate::Paginated;
 use crate::Str;
 
 /// The status of orders to list.
@@ -25,6 +36,11 @@ pub enum Status {
 }
 
 
+/// The number of orders reported by the /v2/orders endpoint if no
+/// limit is provided.
+const DEFAULT_LIMIT: usize = 50;
+
+
 /// A GET request to be made to the /v2/orders endpoint.
 // Note that we do not expose or supply all parameters that the Alpaca
 // API supports.
@@ -49,6 +65,17 @@ pub struct OrdersReq {
   /// legs field of the primary order.
   #[serde(rename = "nested")]
   pub nested: bool,
+  /// The response will contain only orders submitted until this time
+  /// (exclusive).
+  #[serde(rename = "until", skip_serializing_if = "Option::is_none")]
+  pub until: Option<DateTime<Utc>>,
+  /// The response will contain only orders submitted after this time
+  /// (exclusive).
+  #[serde(rename = "after", skip_serializing_if = "Option::is_none")]
+  pub after: Option<DateTime<Utc>>,
+  /// The chronological order in which to report orders.
+  #[serde(rename = "direction", default)]
+  pub direction: Direction,
 }
 
 impl Default for OrdersReq {
@@ -61,6 +88,9 @@ impl Default for OrdersReq {
       // returned. As such, having them included is very non-intrusive
       // and should be a reasonable default.
       nested: true,
+      until: None,
+      after: None,
+      direction: Direction::Descending,
     }
   }
 }
@@ -85,6 +115,100 @@ Endpoint! {
   }
 }
 
+/// Orders are paginated by time of submission: the next page contains
+/// the orders submitted at or before (or after, when listing in
+/// ascending order) the last one of the current page.
+///
+/// Because the time bounds are exclusive on the server side, they are
+/// widened by a microsecond, so that orders submitted at the exact same
+/// time as the last one of a page are not skipped. Orders reported on
+/// the previous page already, as tracked by the pagination state, are
+/// filtered out by ID.
+///
+/// Should more orders than fit on a page share a submission time, the
+/// remaining ones can't be retrieved this way. Pagination stops with a
+/// warning in this case.
+impl Paginated for Get {
+  type Item = Order;
+  type State = Vec<order::Id>;
+
+  fn paginate(
+    mut input: Self::Input,
+    output: Self::Output,
+    seen: &mut Self::State,
+  ) -> (Vec<Self::Item>, Option<Self::Input>) {
+    let limit = input.limit.unwrap_or(DEFAULT_LIMIT);
+    let full = output.len() >= limit;
+    let ids = output.iter().map(|order| order.id).collect::<Vec<_>>();
+    let page = output
+      .into_iter()
+      .filter(|order| !seen.contains(&order.id))
+      .collect::<Vec<_>>();
+
+    if !full {
+      return (page, None)
+    }
+
+    // If the page contained nothing new we would not make any progress
+    // by continuing from it.
+    let time = match page.last() {
+      Some(order) => order.submitted_at.unwrap_or(order.created_at),
+      None => {
+        warn!(
+          "more than {} orders share a submission time; not all orders were listed",
+          limit
+        );
+        return (page, None)
+      },
+    };
+    match input.direction {
+      Direction::Descending => input.until = Some(time + Duration::microseconds(1)),
+      Direction::Ascending => input.after = Some(time - Duration::microseconds(1)),
+    }
+    *seen = ids;
+    (page, Some(input))
+  }
+}
+
+
+/// The outcome of the attempt to cancel a single order, as reported by
+/// a DELETE request to the /v2/orders endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct CancelStatus {
+  /// The ID of the order.
+  #[serde(rename = "id")]
+  pub id: order::Id,
+  /// The HTTP status code reported for the cancellation of the order.
+  #[serde(rename = "status")]
+  pub status: u16,
+}
+
+
+Endpoint! {
+  /// The representation of a DELETE request to the /v2/orders endpoint,
+  /// canceling all open orders.
+  pub Delete(()),
+  Ok => Vec<CancelStatus>, [
+    /// The cancellation of all open orders was attempted. The
+    /// individual outcomes are reported per order.
+    /* 207 */ MULTI_STATUS,
+    /// There were no open orders to cancel.
+    /* 200 */ OK,
+  ],
+  Err => DeleteError, []
+
+  #[inline]
+  fn method() -> Method {
+    Method::DELETE
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v2/orders".into()
+  }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -95,21 +219,24 @@ mod tests {
   use futures::StreamExt;
   use futures::TryStreamExt;
 
+  use http_endpoint::Endpoint;
+
   use num_decimal::Num;
 
   use serde_json::from_slice as from_json;
+  use serde_json::json;
   use serde_json::to_vec as to_json;
   use serde_urlencoded::from_str as from_query;
   use serde_urlencoded::to_string as to_query;
 
   use test_log::test;
 
-  use crate::api::v2::order;
   use crate::api::v2::order_util::order_aapl;
   use crate::api::v2::order_util::order_stock;
   use crate::api::v2::updates;
-  use crate::api_info::ApiInfo;
   use crate::Client;
+  use crate::live;
+  use crate::test_util::order_json;
 
 
   /// Make sure that we can serialize and deserialize an `OrdersReq`.
@@ -120,6 +247,7 @@ mod tests {
       status: Status::Closed,
       limit: Some(42),
       nested: true,
+      ..Default::default()
     };
 
     let json = to_json(&request).unwrap();
@@ -139,6 +267,7 @@ mod tests {
       status: Status::Closed,
       limit: Some(42),
       nested: true,
+      ..Default::default()
     };
 
     let query = to_query(&request).unwrap();
@@ -149,6 +278,87 @@ mod tests {
     assert_eq!(from_query::<OrdersReq>(&query).unwrap(), request);
   }
 
+  /// Check that we paginate orders by time of submission.
+  #[test]
+  fn paginate_orders() {
+    let order = |id: &str, time: &str| {
+      order_json(json!({
+        "id": id,
+        "client_order_id": id,
+        "created_at": time,
+        "updated_at": null,
+        "submitted_at": time,
+        "status": "canceled",
+      }))
+    };
+    let json = json!([
+      order(
+        "4c6a9b8e-5f2d-4b3e-9a1c-7d8e6f5a4b3c",
+        "2022-02-02T10:00:00Z"
+      ),
+      order(
+        "904837e3-3b76-47ec-b432-046db621571b",
+        "2022-02-01T10:00:00Z"
+      ),
+    ])
+    .to_string();
+    let orders = <Get as Endpoint>::parse(json.as_bytes()).unwrap();
+    let time = orders[1].created_at;
+
+    let request = OrdersReq {
+      status: Status::All,
+      limit: Some(2),
+      ..Default::default()
+    };
+    let mut seen = Vec::new();
+    let (page, next) = Get::paginate(request.clone(), orders.clone(), &mut seen);
+    assert_eq!(page, orders);
+    let next = next.unwrap();
+    assert_eq!(next.until, Some(time + Duration::microseconds(1)));
+    assert_eq!(next.after, None);
+
+    // An order submitted at the same time as the last one of the
+    // previous page is reported, while those reported already are
+    // skipped.
+    let json = json!([
+      order(
+        "904837e3-3b76-47ec-b432-046db621571b",
+        "2022-02-01T10:00:00Z"
+      ),
+      order(
+        "d6a4e2f0-9c1b-4a7e-8f3d-2b5c6e7a8d9f",
+        "2022-02-01T10:00:00Z"
+      ),
+    ])
+    .to_string();
+    let same_time = <Get as Endpoint>::parse(json.as_bytes()).unwrap();
+    let (page, next) = Get::paginate(next, same_time.clone(), &mut seen);
+    assert_eq!(page, same_time[1..].to_vec());
+
+    // A full page containing nothing new, because more orders than
+    // fit on it share a submission time, is the last one.
+    let (page, next) = Get::paginate(next.unwrap(), same_time, &mut seen);
+    assert_eq!(page, Vec::new());
+    assert_eq!(next, None);
+
+    let request = OrdersReq {
+      direction: Direction::Ascending,
+      ..request
+    };
+    let (_, next) = Get::paginate(request.clone(), orders.clone(), &mut Vec::new());
+    let next = next.unwrap();
+    assert_eq!(next.until, None);
+    assert_eq!(next.after, Some(time - Duration::microseconds(1)));
+
+    // A page that is not full is the last one.
+    let request = OrdersReq {
+      limit: Some(3),
+      ..request
+    };
+    let (_, next) = Get::paginate(request, orders, &mut Vec::new());
+    assert_eq!(next, None);
+  }
+
   /// Cancel an order and wait for the corresponding cancellation event
   /// to arrive.
   async fn cancel_order(client: &Client, id: order::Id) {
@@ -177,10 +387,10 @@ mod tests {
 
   /// Check that we can list existing orders.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn list_orders() {
     async fn test(status: Status) {
-      let api_info = ApiInfo::from_env().unwrap();
-      let client = Client::new(api_info);
+      let client = live::client().unwrap();
       let request = OrdersReq {
         status,
         ..Default::default()
@@ -217,8 +427,23 @@ mod tests {
     test(Status::All).await;
   }
 
+  /// Check that we can parse the response to a request canceling all
+  /// orders.
+  #[test]
+  fn parse_cancel_statuses() {
+    let response = br#"[
+  {"id": "904837e3-3b76-47ec-b432-046db621571b", "status": 200},
+  {"id": "4c6a9b8e-5f2d-4b3e-9a1c-7d8e6f5a4b3c", "status": 500}
+]"#;
+    let statuses = <Delete as Endpoint>::parse(response).unwrap();
+    assert_eq!(statuses.len(), 2);
+    assert_eq!(statuses[0].status, 200);
+    assert_eq!(statuses[1].status, 500);
+  }
+
   /// Verify that we can list nested orders.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn list_nested_order() {
     let request = order::OrderReqInit {
       class: order::Class::OneTriggersOther,
@@ -229,8 +454,7 @@ mod tests {
     }
     .init("SPY", order::Side::Buy, order::Amount::quantity(1));
 
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let order = client.issue::<order::Post>(&request).await.unwrap();
     assert_eq!(order.legs.len(), 1);
@@ -245,15 +469,17 @@ mod tests {
     let mut filtered = list.into_iter().filter(|o| o.id == order.id);
     let listed = filtered.next().unwrap();
     assert_eq!(listed.legs.len(), 1);
+    assert!(listed.take_profit_leg().is_some());
+    assert_eq!(listed.stop_loss_leg(), None);
     // There shouldn't be any other orders with the given ID.
     assert_eq!(filtered.next(), None);
   }
 
   /// Test that orders can be correctly filtered by a list of symbols.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn symbol_filter_orders() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     // Get the number of current open orders and the number of open GOOG
     // orders. This allows the test to function based on the current
     // state of the account rather than requiring preconditions to be
@@ -0,0 +1,99 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use thiserror::Error;
+
+use url::Url;
+
+use crate::api::v2::orders;
+use crate::api::v2::positions;
+use crate::api::v2::positions::LiquidationStatus;
+use crate::live::is_paper;
+use crate::Client;
+use crate::RequestError;
+
+
+/// An error as reported by [`reset`].
+#[derive(Debug, Error)]
+pub enum ResetError {
+  /// The client is not configured to use the paper trading API.
+  #[error("refusing to reset account: {0} is not the paper trading API")]
+  NotPaper(Url),
+  /// Open orders could not be canceled.
+  #[error("failed to cancel open orders")]
+  CancelOrders(#[source] RequestError<orders::DeleteError>),
+  /// Open positions could not be liquidated.
+  #[error("failed to liquidate open positions")]
+  LiquidatePositions(#[source] RequestError<positions::DeleteError>),
+}
+
+
+/// Reset a paper trading account to a clean state, by canceling all
+/// open orders and liquidating all open positions.
+///
+/// This function refuses to operate on anything but the paper trading
+/// API. Note that Alpaca does not offer a public API for resetting the
+/// cash balance of a paper account; that still has to happen through
+/// the web dashboard. Also note that liquidation happens by means of
+/// market orders, which only fill while the market is open.
+pub async fn reset(client: &Client) -> Result<Vec<LiquidationStatus>, ResetError> {
+  if !is_paper(&client.api_info()) {
+    return Err(ResetError::NotPaper(
+      client.api_info().api_base_url.clone(),
+    ))
+  }
+
+  let _ = client
+    .issue::<orders::Delete>(&())
+    .await
+    .map_err(ResetError::CancelOrders)?;
+
+  let request = positions::DeleteReq {
+    cancel_orders: true,
+  };
+  let statuses = client
+    .issue::<positions::Delete>(&request)
+    .await
+    .map_err(ResetError::LiquidatePositions)?;
+
+  Ok(statuses)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use test_log::test;
+
+  use crate::api_info::ApiInfo;
+  use crate::live;
+
+
+  /// Check that we refuse to reset a live account.
+  #[test(tokio::test)]
+  async fn refuse_live_reset() {
+    let api_info =
+      ApiInfo::from_parts("https://api.alpaca.markets", "invalid", "invalid-too").unwrap();
+    let client = Client::new(api_info);
+
+    let err = reset(&client).await.unwrap_err();
+    match err {
+      ResetError::NotPaper(url) => assert_eq!(url.as_str(), "https://api.alpaca.markets/"),
+      _ => panic!("Received unexpected error: {:?}", err),
+    }
+  }
+
+  /// Check that we can reset the paper trading account.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn reset_paper_account() {
+    let client = live::client().unwrap();
+
+    let _ = reset(&client).await.unwrap();
+
+    let request = orders::OrdersReq::default();
+    let orders = client.issue::<orders::Get>(&request).await.unwrap();
+    assert_eq!(orders, Vec::new());
+  }
+}
@@ -0,0 +1,426 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::cmp::max;
+use std::cmp::min;
+use std::collections::HashMap;
+
+use num_decimal::Num;
+
+use crate::api::v2::account::Account;
+use crate::api::v2::account_activities;
+use crate::api::v2::account_activities::TradeActivity;
+use crate::api::v2::order;
+use crate::api::v2::order::Side;
+use crate::api::v2::position;
+use crate::api::v2::position::Position;
+
+
+/// The minimum equity (in USD) an account needs to have for it to be
+/// exempt from pattern day trader restrictions.
+pub const PDT_EQUITY_THRESHOLD: u64 = 25000;
+
+/// The maximum number of day trades an account below the equity
+/// threshold may perform within five business days without being
+/// flagged as a pattern day trader.
+pub const MAX_DAY_TRADES: u64 = 3;
+
+
+/// The verdict of a [`PdtGuard`] on a prospective order.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Verdict {
+  /// The order would not constitute a day trade.
+  NoDayTrade,
+  /// The order would constitute a day trade, but is permitted. The
+  /// contained value is the day trade count after the order filled.
+  DayTrade(u64),
+  /// The order would constitute a day trade that would cause (or, if
+  /// the account is already flagged, would be) a pattern day trader
+  /// violation.
+  Violation,
+}
+
+impl Verdict {
+  /// Check whether the verdict permits submission of the order.
+  #[inline]
+  pub fn is_allowed(self) -> bool {
+    !matches!(self, Self::Violation)
+  }
+}
+
+
+/// Retrieve the quantity of a fill on the given side, signed such that
+/// buys are positive and sells are negative.
+fn signed(side: Side, quantity: &Num) -> Num {
+  match side {
+    Side::Buy => quantity.clone(),
+    Side::Sell => -quantity,
+  }
+}
+
+
+/// Retrieve the absolute value of a number.
+fn abs(num: &Num) -> Num {
+  if num.is_negative() {
+    -num
+  } else {
+    num.clone()
+  }
+}
+
+
+/// The trading of a single symbol on the current trading day.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Holding {
+  /// The quantity held, negative for short positions.
+  quantity: Num,
+  /// The part of `quantity` opened on the current trading day.
+  opened: Num,
+  /// The orders whose fills have been counted as day trades already.
+  counted: Vec<order::Id>,
+}
+
+impl Holding {
+  /// Apply a fill of the given order with the given signed quantity,
+  /// returning whether it constituted a day trade not counted so far.
+  ///
+  /// Only closing quantity opened on the current trading day makes for
+  /// a day trade, with quantity held overnight being closed first. All
+  /// fills of a single order count as at most one day trade.
+  fn fill(&mut self, order_id: order::Id, quantity: &Num) -> bool {
+    let zero = Num::from(0);
+    let closing = if self.quantity.is_positive() == quantity.is_positive() {
+      zero.clone()
+    } else {
+      min(abs(quantity), abs(&self.quantity))
+    };
+    let overnight = abs(&(&self.quantity - &self.opened));
+    let closed_today = max(&closing - overnight, zero);
+
+    if closing.is_zero() {
+      self.opened += quantity;
+    } else if abs(quantity) > closing {
+      // The position flipped sides and what remains was opened just
+      // now.
+      self.opened = &self.quantity + quantity;
+    } else if self.quantity.is_negative() {
+      self.opened += &closed_today;
+    } else {
+      self.opened -= &closed_today;
+    }
+    self.quantity += quantity;
+
+    if closed_today.is_zero() || self.counted.contains(&order_id) {
+      false
+    } else {
+      let () = self.counted.push(order_id);
+      true
+    }
+  }
+
+  /// Check whether an order on the given side may close quantity
+  /// opened on the current trading day.
+  fn may_close_today(&self, side: Side) -> bool {
+    match side {
+      Side::Buy => self.opened.is_negative(),
+      Side::Sell => self.opened.is_positive(),
+    }
+  }
+}
+
+
+/// A guard keeping track of day trades performed on the current
+/// trading day, flagging orders that would result in a pattern day
+/// trader violation.
+///
+/// A day trade is the closing of (part of) a position opened on the
+/// same trading day. Positions held overnight can be closed without
+/// that counting as a day trade, and all fills of a single closing
+/// order count as a single day trade.
+///
+/// A guard is meant to be used for a single trading day. It should be
+/// created from the current [`Account`] state, the current positions,
+/// as well as the fills of the current trading day (as reported by the
+/// account activities endpoint) and subsequently be informed about
+/// additional fills, for example as reported by trade updates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PdtGuard {
+  /// The number of day trades performed within the last five business
+  /// days.
+  daytrade_count: u64,
+  /// Whether the account is flagged as a pattern day trader.
+  day_trader: bool,
+  /// The account's equity.
+  equity: Num,
+  /// The trading of the current trading day, by symbol.
+  holdings: HashMap<String, Holding>,
+}
+
+impl PdtGuard {
+  /// Create a new `PdtGuard` from the account state, the current
+  /// positions, and the trade activities of the current trading day,
+  /// in chronological order.
+  ///
+  /// The positions held at the start of the trading day are inferred
+  /// by reverting the provided activities, which hence should be
+  /// retrieved together with the positions. The account's
+  /// `daytrade_count` already accounts for day trades made as part of
+  /// the activities and so they are only used for determining which
+  /// positions have been opened on the day.
+  pub fn new<'a, P, I>(account: &Account, positions: P, activities: I) -> Self
+  where
+    P: IntoIterator<Item = &'a Position>,
+    I: IntoIterator<Item = &'a TradeActivity>,
+  {
+    let mut holdings = HashMap::<String, Holding>::new();
+    for position in positions {
+      let quantity = match position.side {
+        position::Side::Long => position.quantity.clone(),
+        position::Side::Short => -&position.quantity,
+      };
+      let _prev = holdings.insert(
+        position.symbol.clone(),
+        Holding {
+          quantity,
+          ..Default::default()
+        },
+      );
+    }
+
+    let activities = activities
+      .into_iter()
+      .map(|activity| {
+        let side = match activity.side {
+          account_activities::Side::Buy => Side::Buy,
+          account_activities::Side::Sell | account_activities::Side::ShortSell => Side::Sell,
+        };
+        (activity, signed(side, &activity.quantity))
+      })
+      .collect::<Vec<_>>();
+
+    for (activity, quantity) in &activities {
+      let holding = holdings.entry(activity.symbol.clone()).or_default();
+      holding.quantity -= quantity;
+    }
+
+    for (activity, quantity) in &activities {
+      let holding = holdings.entry(activity.symbol.clone()).or_default();
+      let _day_trade = holding.fill(activity.order_id, quantity);
+    }
+
+    Self {
+      daytrade_count: account.daytrade_count,
+      day_trader: account.day_trader,
+      equity: account.equity.clone(),
+      holdings,
+    }
+  }
+
+  /// Check whether the account is subject to pattern day trader
+  /// restrictions, i.e., whether its equity is below the threshold.
+  #[inline]
+  pub fn is_restricted(&self) -> bool {
+    self.equity < Num::from(PDT_EQUITY_THRESHOLD)
+  }
+
+  /// Retrieve the current day trade count.
+  #[inline]
+  pub fn daytrade_count(&self) -> u64 {
+    self.daytrade_count
+  }
+
+  /// Evaluate an order for the given symbol on the given side.
+  ///
+  /// An order is considered a day trade if it may close quantity
+  /// opened on the current trading day.
+  pub fn check(&self, symbol: &str, side: Side) -> Verdict {
+    let is_day_trade = self
+      .holdings
+      .get(symbol)
+      .map(|holding| holding.may_close_today(side))
+      .unwrap_or(false);
+
+    if !is_day_trade {
+      Verdict::NoDayTrade
+    } else if self.is_restricted() && (self.day_trader || self.daytrade_count >= MAX_DAY_TRADES)
+    {
+      Verdict::Violation
+    } else {
+      Verdict::DayTrade(self.daytrade_count + 1)
+    }
+  }
+
+  /// Record a fill of the given quantity for the order with the given
+  /// ID, for the given symbol on the given side, updating the day trade
+  /// count as necessary.
+  pub fn record_fill(&mut self, order_id: order::Id, symbol: &str, side: Side, quantity: &Num) {
+    let holding = self.holdings.entry(symbol.to_string()).or_default();
+    if holding.fill(order_id, &signed(side, quantity)) {
+      self.daytrade_count += 1;
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::from_str as from_json;
+  use serde_json::json;
+
+  use uuid::Uuid;
+
+  use crate::test_util;
+
+
+  /// Create an `Account` object with the given properties.
+  fn account(equity: u64, daytrade_count: u64, day_trader: bool) -> Account {
+    test_util::account(json!({
+      "cash": equity.to_string(),
+      "equity": equity.to_string(),
+      "last_equity": equity.to_string(),
+      "pattern_day_trader": day_trader,
+      "daytrade_count": daytrade_count,
+    }))
+  }
+
+  /// Create an order ID with the given suffix.
+  fn order_id(id: u8) -> order::Id {
+    let id = format!("904837e3-3b76-47ec-b432-046db62157{:02}", id);
+    order::Id(Uuid::parse_str(&id).unwrap())
+  }
+
+  /// Create a `TradeActivity` for a fill of the given order for the
+  /// given symbol, side, and quantity.
+  fn activity(order: u8, symbol: &str, side: &str, quantity: u64) -> TradeActivity {
+    let json = json!({
+      "id": "20190801011955195::5f596936-6f23-4cef-bdf1-3806aae57dbf",
+      "activity_type": "FILL",
+      "transaction_time": "2019-08-01T14:19:55.195Z",
+      "type": "fill",
+      "price": "100",
+      "qty": quantity.to_string(),
+      "side": side,
+      "symbol": symbol,
+      "leaves_qty": "0",
+      "order_id": order_id(order),
+      "cum_qty": quantity.to_string(),
+    });
+    from_json(&json.to_string()).unwrap()
+  }
+
+  /// Create a `Position` in the given symbol.
+  fn position(symbol: &str, side: &str, quantity: u64) -> Position {
+    let json = json!({
+      "asset_id": "904837e3-3b76-47ec-b432-046db621571b",
+      "symbol": symbol,
+      "exchange": "NASDAQ",
+      "asset_class": "us_equity",
+      "avg_entry_price": "100.0",
+      "qty": quantity.to_string(),
+      "side": side,
+      "market_value": "100.0",
+      "cost_basis": "100.0",
+      "unrealized_pl": "0",
+      "unrealized_plpc": "0",
+      "unrealized_intraday_pl": "0",
+      "unrealized_intraday_plpc": "0",
+      "current_price": "100.0",
+      "lastday_price": "100.0",
+      "change_today": "0"
+    });
+    from_json(&json.to_string()).unwrap()
+  }
+
+
+  /// Check that we correctly identify day trades.
+  #[test]
+  fn identify_day_trades() {
+    let account = account(10000, 0, false);
+    let positions = [position("AAPL", "long", 1), position("SPY", "short", 1)];
+    let activities = [
+      activity(1, "AAPL", "buy", 1),
+      activity(2, "SPY", "sell_short", 1),
+    ];
+    let mut guard = PdtGuard::new(&account, &positions, &activities);
+
+    assert_eq!(guard.check("MSFT", Side::Buy), Verdict::NoDayTrade);
+    assert_eq!(guard.check("AAPL", Side::Buy), Verdict::NoDayTrade);
+    assert_eq!(guard.check("AAPL", Side::Sell), Verdict::DayTrade(1));
+    assert_eq!(guard.check("SPY", Side::Buy), Verdict::DayTrade(1));
+    assert_eq!(guard.check("SPY", Side::Sell), Verdict::NoDayTrade);
+
+    guard.record_fill(order_id(3), "MSFT", Side::Buy, &Num::from(1));
+    assert_eq!(guard.daytrade_count(), 0);
+    guard.record_fill(order_id(4), "AAPL", Side::Sell, &Num::from(1));
+    assert_eq!(guard.daytrade_count(), 1);
+    assert_eq!(guard.check("AAPL", Side::Sell), Verdict::NoDayTrade);
+    assert_eq!(guard.check("SPY", Side::Buy), Verdict::DayTrade(2));
+  }
+
+  /// Check that all fills of a closing order count as a single day
+  /// trade.
+  #[test]
+  fn count_partial_fills_once() {
+    let account = account(10000, 0, false);
+    let positions = [position("AAPL", "long", 3)];
+    let activities = [activity(1, "AAPL", "buy", 3)];
+    let mut guard = PdtGuard::new(&account, &positions, &activities);
+
+    for _ in 0..3 {
+      guard.record_fill(order_id(2), "AAPL", Side::Sell, &Num::from(1));
+    }
+    assert_eq!(guard.daytrade_count(), 1);
+    assert_eq!(guard.check("AAPL", Side::Sell), Verdict::NoDayTrade);
+
+    // Another fill of the same order does not count again either.
+    guard.record_fill(order_id(3), "AAPL", Side::Buy, &Num::from(1));
+    guard.record_fill(order_id(2), "AAPL", Side::Sell, &Num::from(1));
+    assert_eq!(guard.daytrade_count(), 1);
+  }
+
+  /// Check that closing a position held overnight and entering it
+  /// again is not considered a day trade.
+  #[test]
+  fn close_overnight_position() {
+    let account = account(10000, 0, false);
+    let activities = [activity(1, "AAPL", "sell", 2)];
+    let mut guard = PdtGuard::new(&account, [], &activities);
+
+    assert_eq!(guard.check("AAPL", Side::Buy), Verdict::NoDayTrade);
+    guard.record_fill(order_id(2), "AAPL", Side::Buy, &Num::from(2));
+    assert_eq!(guard.daytrade_count(), 0);
+
+    // Selling beyond the overnight quantity closes the part bought
+    // today, though.
+    let positions = [position("AAPL", "long", 3)];
+    let mut guard = PdtGuard::new(&account, &positions, []);
+    guard.record_fill(order_id(3), "AAPL", Side::Buy, &Num::from(1));
+    assert_eq!(guard.check("AAPL", Side::Sell), Verdict::DayTrade(1));
+    guard.record_fill(order_id(4), "AAPL", Side::Sell, &Num::from(3));
+    assert_eq!(guard.daytrade_count(), 0);
+    guard.record_fill(order_id(4), "AAPL", Side::Sell, &Num::from(1));
+    assert_eq!(guard.daytrade_count(), 1);
+  }
+
+  /// Check that we flag orders that would result in a violation.
+  #[test]
+  fn flag_violations() {
+    let positions = [position("AAPL", "long", 1)];
+    let activities = [activity(1, "AAPL", "buy", 1)];
+
+    let guard = PdtGuard::new(&account(10000, 3, false), &positions, &activities);
+    assert!(guard.is_restricted());
+    assert_eq!(guard.check("AAPL", Side::Sell), Verdict::Violation);
+    assert!(!guard.check("AAPL", Side::Sell).is_allowed());
+    assert!(guard.check("AAPL", Side::Buy).is_allowed());
+
+    let guard = PdtGuard::new(&account(10000, 0, true), &positions, &activities);
+    assert_eq!(guard.check("AAPL", Side::Sell), Verdict::Violation);
+
+    let guard = PdtGuard::new(&account(30000, 5, true), &positions, &activities);
+    assert!(!guard.is_restricted());
+    assert_eq!(guard.check("AAPL", Side::Sell), Verdict::DayTrade(6));
+  }
+}
@@ -0,0 +1,595 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::error::Error as StdError;
+use std::fmt::Write as _;
+use std::ops::Range;
+
+use chrono::Datelike as _;
+use chrono::Duration;
+use chrono::NaiveDate;
+use chrono::TimeZone as _;
+use chrono::Utc;
+
+use futures::TryStreamExt as _;
+
+use num_decimal::Num;
+
+use serde::Serialize;
+use serde_json::to_string as to_json;
+use serde_json::Error as JsonError;
+
+use thiserror::Error;
+
+use crate::api::v2::account_activities;
+use crate::api::v2::account_activities::Activity;
+use crate::api::v2::account_activities::ActivityReq;
+use crate::api::v2::account_activities::ActivityType;
+use crate::api::v2::account_activities::Direction;
+use crate::api::v2::account_activities::Side;
+use crate::data::v2::bars::BarsReqInit;
+use crate::data::v2::bars::TimeFrame;
+use crate::data::v2::source::DataSource;
+use crate::Client;
+use crate::RequestError;
+
+
+/// The number of days to look back for a closing price, accounting for
+/// weekends and holidays.
+const PRICE_LOOKBACK_DAYS: i64 = 10;
+
+
+/// The length of the periods a [`Report`] is broken down into.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Period {
+  /// A calendar day.
+  Day,
+  /// A calendar month.
+  Month,
+  /// A calendar year.
+  Year,
+}
+
+impl Period {
+  /// Retrieve the first day of the period containing the given date.
+  fn start(self, date: NaiveDate) -> NaiveDate {
+    match self {
+      Self::Day => date,
+      Self::Month => date.with_day(1).unwrap(),
+      Self::Year => date.with_ordinal(1).unwrap(),
+    }
+  }
+
+  /// Retrieve the first day of the period following the one starting
+  /// at the given date.
+  fn next(self, start: NaiveDate) -> NaiveDate {
+    match self {
+      Self::Day => start + Duration::days(1),
+      Self::Month if start.month() == 12 => {
+        NaiveDate::from_ymd_opt(start.year() + 1, 1, 1).unwrap()
+      },
+      Self::Month => NaiveDate::from_ymd_opt(start.year(), start.month() + 1, 1).unwrap(),
+      Self::Year => NaiveDate::from_ymd_opt(start.year() + 1, 1, 1).unwrap(),
+    }
+  }
+}
+
+
+/// The profit and loss attributed to a symbol in a single period.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[non_exhaustive]
+pub struct Row {
+  /// The first day of the period.
+  pub start: NaiveDate,
+  /// The last day of the period, i.e., the day positions are valued
+  /// at. It may lie before the end of the period if the report does
+  /// not cover it entirely.
+  pub end: NaiveDate,
+  /// The symbol, or `None` for account level items, e.g., fees not
+  /// associated with any asset.
+  pub symbol: Option<String>,
+  /// The position quantity held at the end of the period. Short
+  /// positions are reported as negative quantities.
+  pub quantity: Num,
+  /// The cost basis of the position held at the end of the period,
+  /// based on the average price paid. The cost basis of short positions
+  /// is negative.
+  pub cost_basis: Num,
+  /// The profit or loss realized by closing positions in the period.
+  pub realized: Num,
+  /// The income received in the period, i.e., dividends, capital gain
+  /// distributions, and interest.
+  pub income: Num,
+  /// The fees charged in the period, net of rebates. Charges are
+  /// negative.
+  pub fees: Num,
+  /// The unrealized profit or loss of the position held at the end of
+  /// the period, or `None` if it could not be valued.
+  pub unrealized: Option<Num>,
+}
+
+
+/// A profit and loss report, broken down by period and symbol.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Report {
+  /// The rows of the report, ordered by period and then symbol.
+  pub rows: Vec<Row>,
+}
+
+impl Report {
+  /// Value the open positions of the report, i.e., compute their
+  /// unrealized profit or loss.
+  ///
+  /// `prices` maps symbols and dates to the closing price of the
+  /// respective asset on that date. Rows with open positions are
+  /// valued at the price on the last day of their period (see
+  /// [`Row::end`]).
+  pub fn value(&mut self, prices: &BTreeMap<(String, NaiveDate), Num>) {
+    for row in &mut self.rows {
+      if let Some(symbol) = &row.symbol {
+        if !row.quantity.is_zero() {
+          row.unrealized = prices
+            .get(&(symbol.clone(), row.end))
+            .map(|price| price * &row.quantity - &row.cost_basis);
+        }
+      }
+    }
+  }
+
+  /// Export the report in CSV format, including a header.
+  ///
+  /// Positions that could not be valued have an empty `unrealized`
+  /// field.
+  pub fn to_csv(&self) -> String {
+    let mut csv =
+      String::from("start,end,symbol,quantity,cost_basis,realized,income,fees,unrealized\n");
+    for row in &self.rows {
+      let _ = writeln!(
+        csv,
+        "{},{},{},{},{},{},{},{},{}",
+        row.start,
+        row.end,
+        escape(row.symbol.as_deref().unwrap_or_default()),
+        row.quantity,
+        row.cost_basis,
+        row.realized,
+        row.income,
+        row.fees,
+        row
+          .unrealized
+          .as_ref()
+          .map(ToString::to_string)
+          .unwrap_or_default(),
+      );
+    }
+    csv
+  }
+
+  /// Export the report in JSON format.
+  pub fn to_json(&self) -> Result<String, JsonError> {
+    to_json(self)
+  }
+}
+
+
+/// Escape a field for inclusion in a CSV record.
+fn escape(field: &str) -> String {
+  if field.contains(&[',', '"', '\n'][..]) {
+    format!("\"{}\"", field.replace('"', "\"\""))
+  } else {
+    field.to_string()
+  }
+}
+
+
+/// The state of a single symbol in a single period.
+#[derive(Clone, Debug, Default)]
+struct Entry {
+  /// The position quantity held at the end of the period.
+  quantity: Num,
+  /// The cost basis of the position at the end of the period.
+  cost_basis: Num,
+  /// The profit or loss realized in the period.
+  realized: Num,
+  /// The income received in the period.
+  income: Num,
+  /// The fees charged in the period.
+  fees: Num,
+}
+
+impl Entry {
+  /// Create an `Entry` for a new period, carrying over the position of
+  /// the previous one.
+  fn carry(&self) -> Self {
+    Self {
+      quantity: self.quantity.clone(),
+      cost_basis: self.cost_basis.clone(),
+      ..Default::default()
+    }
+  }
+
+  /// Check whether the entry has any profit or loss to report.
+  fn is_empty(&self) -> bool {
+    self.quantity.is_zero()
+      && self.realized.is_zero()
+      && self.income.is_zero()
+      && self.fees.is_zero()
+  }
+
+  /// Account for a fill of the given (signed) quantity at the given
+  /// price, using the average cost method.
+  fn fill(&mut self, quantity: Num, price: &Num) {
+    let opening = self.quantity.is_zero() || self.quantity.is_positive() == quantity.is_positive();
+    let remainder = if opening {
+      quantity
+    } else {
+      let abs = |num: &Num| {
+        if num.is_negative() {
+          -num.clone()
+        } else {
+          num.clone()
+        }
+      };
+      let closing = if abs(&quantity) < abs(&self.quantity) {
+        quantity.clone()
+      } else {
+        -self.quantity.clone()
+      };
+      let average = &self.cost_basis / &self.quantity;
+      self.realized -= &closing * (price - &average);
+      self.quantity += &closing;
+      self.cost_basis = &average * &self.quantity;
+      quantity - closing
+    };
+
+    self.cost_basis += price * &remainder;
+    self.quantity += remainder;
+  }
+}
+
+
+/// Check whether a non-trade activity of the given type is income.
+fn is_income(type_: ActivityType) -> bool {
+  matches!(
+    type_,
+    ActivityType::Dividend
+      | ActivityType::CapitalGainLongTerm
+      | ActivityType::CapitalGainShortTerm
+      | ActivityType::DividendAdjusted
+      | ActivityType::DividendAdjustedNraWithheld
+      | ActivityType::DividendReturnOfCapital
+      | ActivityType::DividendAdjustedTefraWithheld
+      | ActivityType::DividendTaxExtempt
+      | ActivityType::Interest
+      | ActivityType::InterestAdjustedNraWithheld
+      | ActivityType::InterestAdjustedTefraWithheld
+  )
+}
+
+/// Check whether a non-trade activity of the given type is a fee.
+fn is_fee(type_: ActivityType) -> bool {
+  matches!(
+    type_,
+    ActivityType::Fee
+      | ActivityType::DividendFee
+      | ActivityType::CryptoFee
+      | ActivityType::PassThruCharge
+      | ActivityType::PassThruRebate
+  )
+}
+
+
+/// Build a profit and loss report from the given account activities,
+/// covering the given (half-open) date range.
+///
+/// Positions are accounted for using the average cost method. To get
+/// their cost basis right, activities should be provided going back to
+/// when the positions were opened; activities before the start of the
+/// range contribute to the cost basis but are not reported otherwise.
+/// Activities other than fills, income, and fees, e.g., cash transfers
+/// or stock splits, are ignored.
+///
+/// Open positions are not valued. Use [`Report::value`] to do so.
+pub fn report<'a, I>(activities: I, period: Period, range: Range<NaiveDate>) -> Report
+where
+  I: IntoIterator<Item = &'a Activity>,
+{
+  let mut activities = activities
+    .into_iter()
+    .filter(|activity| activity.time().naive_utc().date() < range.end)
+    .collect::<Vec<_>>();
+  activities.sort_by_key(|activity| *activity.time());
+
+  let mut entries = BTreeMap::<Option<String>, BTreeMap<NaiveDate, Entry>>::new();
+  for activity in activities {
+    let start = period.start(activity.time().naive_utc().date());
+    let symbol = match activity {
+      Activity::Trade(trade) => Some(trade.symbol.clone()),
+      Activity::NonTrade(non_trade) if is_income(non_trade.type_) || is_fee(non_trade.type_) => {
+        non_trade.symbol.clone()
+      },
+      Activity::NonTrade(..) => continue,
+    };
+
+    let periods = entries.entry(symbol).or_default();
+    let carried = periods
+      .values()
+      .next_back()
+      .map(Entry::carry)
+      .unwrap_or_default();
+    let entry = periods.entry(start).or_insert(carried);
+
+    match activity {
+      Activity::Trade(trade) => {
+        let quantity = match trade.side {
+          Side::Buy => trade.quantity.clone(),
+          Side::Sell | Side::ShortSell => -trade.quantity.clone(),
+        };
+        entry.fill(quantity, &trade.price)
+      },
+      Activity::NonTrade(non_trade) if is_income(non_trade.type_) => {
+        entry.income += &non_trade.net_amount
+      },
+      Activity::NonTrade(non_trade) => entry.fees += &non_trade.net_amount,
+    }
+  }
+
+  let mut rows = Vec::new();
+  let mut start = period.start(range.start);
+  while start < range.end {
+    let next = period.next(start);
+    let end = next.min(range.end) - Duration::days(1);
+
+    for (symbol, periods) in &entries {
+      let entry = match periods.get(&start) {
+        Some(entry) => entry.clone(),
+        None => match periods.range(..start).next_back() {
+          Some((_, entry)) => entry.carry(),
+          None => continue,
+        },
+      };
+      if entry.is_empty() {
+        continue
+      }
+
+      rows.push(Row {
+        start,
+        end,
+        symbol: symbol.clone(),
+        unrealized: entry.quantity.is_zero().then(|| Num::from(0)),
+        quantity: entry.quantity,
+        cost_basis: entry.cost_basis,
+        realized: entry.realized,
+        income: entry.income,
+        fees: entry.fees,
+      });
+    }
+    start = next;
+  }
+
+  Report { rows }
+}
+
+
+/// An error as reported by [`fetch_report`].
+#[derive(Debug, Error)]
+pub enum ReportError<E>
+where
+  E: StdError + 'static,
+{
+  /// The account activities could not be retrieved.
+  #[error("failed to retrieve account activities")]
+  Activities(#[source] RequestError<account_activities::GetError>),
+  /// The price of an asset could not be retrieved.
+  #[error("failed to retrieve price of {0}")]
+  Price(String, #[source] E),
+}
+
+
+/// Retrieve the closing price of the given symbol on the given date, or
+/// the most recent one before it.
+async fn closing_price<D>(
+  source: &D,
+  symbol: &str,
+  date: NaiveDate,
+) -> Result<Option<Num>, D::Error>
+where
+  D: DataSource,
+{
+  let end = date + Duration::days(1);
+  let start = date - Duration::days(PRICE_LOOKBACK_DAYS);
+  let to_time = |date: NaiveDate| Utc.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap());
+  let request =
+    BarsReqInit::default().init(symbol, to_time(start), to_time(end), TimeFrame::OneDay);
+
+  let bars = source.get_bars(&request).await?;
+  Ok(bars.bars.last().map(|bar| bar.close.clone()))
+}
+
+
+/// Build a profit and loss report for the account, covering the given
+/// (half-open) date range.
+///
+/// All account activities up to the end of the range are retrieved and
+/// open positions are valued at the daily closing prices reported by
+/// the provided data source. See [`report`] for details.
+pub async fn fetch_report<D>(
+  client: &Client,
+  source: &D,
+  period: Period,
+  range: Range<NaiveDate>,
+) -> Result<Report, ReportError<D::Error>>
+where
+  D: DataSource,
+  D::Error: StdError + 'static,
+{
+  let request = ActivityReq {
+    direction: Direction::Ascending,
+    until: Some(Utc.from_utc_datetime(&range.end.and_hms_opt(0, 0, 0).unwrap())),
+    ..Default::default()
+  };
+
+  let activities = client
+    .issue_paged::<account_activities::Get>(request)
+    .try_collect::<Vec<_>>()
+    .await
+    .map_err(ReportError::Activities)?;
+
+  let mut report = report(&activities, period, range);
+  let open = report
+    .rows
+    .iter()
+    .filter(|row| !row.quantity.is_zero())
+    .filter_map(|row| row.symbol.clone().map(|symbol| (symbol, row.end)))
+    .collect::<BTreeSet<_>>();
+
+  let mut prices = BTreeMap::new();
+  for (symbol, date) in open {
+    let price = closing_price(source, &symbol, date)
+      .await
+      .map_err(|err| ReportError::Price(symbol.clone(), err))?;
+    if let Some(price) = price {
+      let _ = prices.insert((symbol, date), price);
+    }
+  }
+
+  report.value(&prices);
+  Ok(report)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::from_value;
+  use serde_json::json;
+
+  use test_log::test;
+
+
+  /// Create a fill activity.
+  fn fill(time: &str, symbol: &str, side: &str, qty: &str, price: &str) -> Activity {
+    from_value(json!({
+      "id": format!("{}::{}", time, symbol),
+      "activity_type": "FILL",
+      "transaction_time": time,
+      "type": "fill",
+      "price": price,
+      "qty": qty,
+      "side": side,
+      "symbol": symbol,
+      "leaves_qty": "0",
+      "order_id": "904837e3-3b76-47ec-b432-046db621571b",
+      "cum_qty": qty,
+    }))
+    .unwrap()
+  }
+
+  /// Create a non-trade activity.
+  fn non_trade(date: &str, type_: &str, symbol: Option<&str>, amount: &str) -> Activity {
+    from_value(json!({
+      "id": format!("{}::{}", date, type_),
+      "activity_type": type_,
+      "date": date,
+      "net_amount": amount,
+      "symbol": symbol,
+    }))
+    .unwrap()
+  }
+
+  /// Create a `NaiveDate` from the given year, month, and day.
+  fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+  }
+
+
+  /// Check that we correctly attribute profit and loss to symbols and
+  /// periods.
+  #[test]
+  fn attribute_profit_and_loss() {
+    let activities = [
+      fill("2022-01-10T15:00:00Z", "AAPL", "buy", "10", "100"),
+      fill("2022-01-20T15:00:00Z", "AAPL", "buy", "10", "110"),
+      non_trade("2022-02-01", "DIV", Some("AAPL"), "4.4"),
+      fill("2022-02-15T15:00:00Z", "AAPL", "sell", "5", "120"),
+      fill("2022-02-16T15:00:00Z", "SPY", "sell_short", "2", "400"),
+      non_trade("2022-02-28", "FEE", None, "-1.5"),
+      non_trade("2022-02-28", "CFEE", None, "-0.5"),
+      fill("2022-03-01T15:00:00Z", "SPY", "buy", "3", "390"),
+    ];
+
+    let range = date(2022, 2, 1)..date(2022, 3, 15);
+    let mut report = report(&activities, Period::Month, range);
+    let prices = BTreeMap::from([
+      (("AAPL".to_string(), date(2022, 2, 28)), Num::from(100)),
+      (("AAPL".to_string(), date(2022, 3, 14)), Num::from(125)),
+      (("SPY".to_string(), date(2022, 2, 28)), Num::from(410)),
+    ]);
+    report.value(&prices);
+
+    let rows = &report.rows;
+    assert_eq!(rows.len(), 5);
+
+    // February: the account level fees are reported first.
+    assert_eq!(rows[0].symbol, None);
+    assert_eq!(rows[0].fees, Num::from(-2));
+
+    // 5 of 20 AAPL shares, at an average price of $105, were sold for
+    // $120.
+    assert_eq!(rows[1].symbol.as_deref(), Some("AAPL"));
+    assert_eq!(rows[1].end, date(2022, 2, 28));
+    assert_eq!(rows[1].quantity, Num::from(15));
+    assert_eq!(rows[1].cost_basis, Num::from(1575));
+    assert_eq!(rows[1].realized, Num::from(75));
+    assert_eq!(rows[1].income, Num::new(44, 10));
+    assert_eq!(rows[1].unrealized, Some(Num::from(-75)));
+
+    assert_eq!(rows[2].symbol.as_deref(), Some("SPY"));
+    assert_eq!(rows[2].quantity, Num::from(-2));
+    assert_eq!(rows[2].cost_basis, Num::from(-800));
+    assert_eq!(rows[2].unrealized, Some(Num::from(-20)));
+
+    // March: the AAPL position is carried over and valued at the end
+    // of the range.
+    assert_eq!(rows[3].symbol.as_deref(), Some("AAPL"));
+    assert_eq!(rows[3].end, date(2022, 3, 14));
+    assert_eq!(rows[3].realized, Num::from(0));
+    assert_eq!(rows[3].unrealized, Some(Num::from(300)));
+
+    // The short SPY position got covered and a long one opened, but no
+    // price is available for valuing it.
+    assert_eq!(rows[4].symbol.as_deref(), Some("SPY"));
+    assert_eq!(rows[4].quantity, Num::from(1));
+    assert_eq!(rows[4].cost_basis, Num::from(390));
+    assert_eq!(rows[4].realized, Num::from(20));
+    assert_eq!(rows[4].unrealized, None);
+  }
+
+  /// Check that we can export a report as CSV and JSON.
+  #[test]
+  fn export_report() {
+    let activities = [
+      fill("2022-01-10T15:00:00Z", "AAPL", "buy", "1", "100"),
+      fill("2022-01-11T15:00:00Z", "AAPL", "sell", "1", "101.5"),
+    ];
+    let report = report(
+      &activities,
+      Period::Year,
+      date(2022, 1, 1)..date(2023, 1, 1),
+    );
+
+    let expected = "\
+start,end,symbol,quantity,cost_basis,realized,income,fees,unrealized
+2022-01-01,2022-12-31,AAPL,0,0,1.5,0,0,0
+";
+    assert_eq!(report.to_csv(), expected);
+
+    let json = report.to_json().unwrap();
+    assert!(json.contains(r#""symbol":"AAPL""#), "{}", json);
+    assert!(json.contains(r#""realized":"1.5""#), "{}", json);
+    assert_eq!(escape("a,\"b\""), r#""a,""b""""#);
+  }
+}
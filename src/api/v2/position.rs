@@ -1,6 +1,8 @@
 // Copyright (C) 2019-2022 The apca Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+#[cfg(feature = "extra-fields")]
+use std::collections::BTreeMap;
 use std::ops::Not;
 
 use http::Method;
@@ -9,10 +11,14 @@ use num_decimal::Num;
 
 use serde::Deserialize;
 use serde::Serialize;
+#[cfg(feature = "extra-fields")]
+use serde_json::Value;
+use serde_urlencoded::to_string as to_query;
 
 use crate::api::v2::asset;
 use crate::api::v2::order;
 use crate::util::abs_num_from_str;
+use crate::util::optional_num_from_str_or_null;
 use crate::Str;
 
 
@@ -27,6 +33,39 @@ pub enum Side {
   Short,
 }
 
+impl Side {
+  /// Infer the side of a position from its signed quantity, with
+  /// negative quantities denoting short positions.
+  ///
+  /// `None` is returned for a quantity of zero.
+  pub fn from_signed_quantity(quantity: &Num) -> Option<Self> {
+    if quantity.is_positive() {
+      Some(Self::Long)
+    } else if quantity.is_negative() {
+      Some(Self::Short)
+    } else {
+      None
+    }
+  }
+
+  /// Retrieve the side of orders opening or extending a position on
+  /// this side.
+  #[inline]
+  pub fn opening_side(self) -> order::Side {
+    match self {
+      Self::Long => order::Side::Buy,
+      Self::Short => order::Side::Sell,
+    }
+  }
+
+  /// Retrieve the side of orders closing or reducing a position on
+  /// this side.
+  #[inline]
+  pub fn closing_side(self) -> order::Side {
+    !self.opening_side()
+  }
+}
+
 impl Not for Side {
   type Output = Self;
 
@@ -66,32 +105,113 @@ pub struct Position {
   #[serde(rename = "side")]
   pub side: Side,
   /// The total dollar amount of the position.
-  #[serde(rename = "market_value")]
+  #[serde(
+    rename = "market_value",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub market_value: Option<Num>,
   /// The total cost basis in dollar.
   #[serde(rename = "cost_basis")]
   pub cost_basis: Num,
   /// The total unrealized profit/loss in dollar.
-  #[serde(rename = "unrealized_pl")]
+  #[serde(
+    rename = "unrealized_pl",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub unrealized_gain_total: Option<Num>,
   /// The total unrealized profit/loss percent (as a factor of 1).
-  #[serde(rename = "unrealized_plpc")]
+  #[serde(
+    rename = "unrealized_plpc",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub unrealized_gain_total_percent: Option<Num>,
   /// The unrealized profit/loss in dollar for the day.
-  #[serde(rename = "unrealized_intraday_pl")]
+  #[serde(
+    rename = "unrealized_intraday_pl",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub unrealized_gain_today: Option<Num>,
   /// The unrealized profit/loss percent for the day (as a factor of 1).
-  #[serde(rename = "unrealized_intraday_plpc")]
+  #[serde(
+    rename = "unrealized_intraday_plpc",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub unrealized_gain_today_percent: Option<Num>,
   /// The current asset price per share.
-  #[serde(rename = "current_price")]
+  #[serde(
+    rename = "current_price",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub current_price: Option<Num>,
   /// The last day's asset price per share.
-  #[serde(rename = "lastday_price")]
+  #[serde(
+    rename = "lastday_price",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub last_day_price: Option<Num>,
   /// The percent change from last day price (as a factor of 1).
-  #[serde(rename = "change_today")]
+  #[serde(
+    rename = "change_today",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
   pub change_today: Option<Num>,
+  /// Any fields reported by the API that are not (yet) modeled
+  /// explicitly, indexed by name.
+  #[cfg(feature = "extra-fields")]
+  #[serde(flatten, deserialize_with = "crate::schema::capture::<Position, _>")]
+  pub extra: BTreeMap<String, Value>,
+}
+
+
+impl Position {
+  /// Retrieve the quantity of the position signed according to its
+  /// side, i.e., negative for short positions.
+  ///
+  /// Note that `quantity` itself is always positive, with the side
+  /// being reported separately.
+  pub fn signed_quantity(&self) -> Num {
+    match self.side {
+      Side::Long => self.quantity.clone(),
+      Side::Short => -self.quantity.clone(),
+    }
+  }
+
+  /// Retrieve the side of orders closing or reducing the position.
+  #[inline]
+  pub fn closing_side(&self) -> order::Side {
+    self.side.closing_side()
+  }
+
+  /// Create a request for a market order closing the position in its
+  /// entirety.
+  ///
+  /// In contrast to a [`Delete`] request, the returned order can be
+  /// adjusted before being submitted, e.g., to turn it into a limit
+  /// order.
+  pub fn to_closing_order(&self) -> order::OrderReq {
+    let intent = match self.side {
+      Side::Long => order::PositionIntent::SellToClose,
+      Side::Short => order::PositionIntent::BuyToClose,
+    };
+
+    order::OrderReqInit {
+      position_intent: Some(intent),
+      ..Default::default()
+    }
+    .init(
+      self.symbol.clone(),
+      self.closing_side(),
+      order::Amount::quantity(self.quantity.clone()),
+    )
+  }
 }
 
 
@@ -115,10 +235,63 @@ Endpoint! {
 }
 
 
+/// The amount of a position to close.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CloseAmount {
+  /// Close the given number of shares.
+  Quantity(Num),
+  /// Close the given percentage of the position (a value between 0
+  /// and 100).
+  Percentage(Num),
+}
+
+
+/// A DELETE request to be made to the /v2/positions/<symbol> endpoint.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeleteReq {
+  /// Symbol or asset ID identifying the position to close.
+  pub symbol: asset::Symbol,
+  /// The amount of the position to close. The entire position is
+  /// closed if `None`.
+  pub amount: Option<CloseAmount>,
+}
+
+impl From<asset::Symbol> for DeleteReq {
+  #[inline]
+  fn from(symbol: asset::Symbol) -> Self {
+    Self {
+      symbol,
+      amount: None,
+    }
+  }
+}
+
+
+/// A helper for initializing [`DeleteReq`] objects.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeleteReqInit {
+  /// See `DeleteReq::amount`.
+  pub amount: Option<CloseAmount>,
+  #[doc(hidden)]
+  pub _non_exhaustive: (),
+}
+
+impl DeleteReqInit {
+  /// Create a [`DeleteReq`] from a `DeleteReqInit`.
+  #[inline]
+  pub fn init(self, symbol: asset::Symbol) -> DeleteReq {
+    DeleteReq {
+      symbol,
+      amount: self.amount,
+    }
+  }
+}
+
+
 Endpoint! {
   /// The representation of a DELETE request to the
   /// /v2/positions/<symbol> endpoint.
-  pub Delete(asset::Symbol),
+  pub Delete(DeleteReq),
   Ok => order::Order, [
     /// The position was liquidated successfully.
     /* 200 */ OK,
@@ -126,6 +299,8 @@ Endpoint! {
   Err => DeleteError, [
     /// No position was found for the given symbol/asset ID.
     /* 404 */ NOT_FOUND => NotFound,
+    /// The provided quantity or percentage was invalid.
+    /* 422 */ UNPROCESSABLE_ENTITY => InvalidInput,
   ]
 
   #[inline]
@@ -135,7 +310,30 @@ Endpoint! {
 
   #[inline]
   fn path(input: &Self::Input) -> Str {
-    format!("/v2/positions/{}", input).into()
+    format!("/v2/positions/{}", input.symbol).into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    #[derive(Serialize)]
+    struct Amount<'n> {
+      #[serde(rename = "qty", skip_serializing_if = "Option::is_none")]
+      quantity: Option<&'n Num>,
+      #[serde(rename = "percentage", skip_serializing_if = "Option::is_none")]
+      percentage: Option<&'n Num>,
+    }
+
+    let amount = match &input.amount {
+      None => return Ok(None),
+      Some(CloseAmount::Quantity(quantity)) => Amount {
+        quantity: Some(quantity),
+        percentage: None,
+      },
+      Some(CloseAmount::Percentage(percentage)) => Amount {
+        quantity: None,
+        percentage: Some(percentage),
+      },
+    };
+    Ok(Some(to_query(amount)?.into()))
   }
 }
 
@@ -144,13 +342,14 @@ Endpoint! {
 mod tests {
   use super::*;
 
+  use http_endpoint::Endpoint;
+
   use serde_json::from_str as from_json;
   use serde_json::to_string as to_json;
 
   use test_log::test;
 
-  use crate::api_info::ApiInfo;
-  use crate::Client;
+  use crate::live;
   use crate::RequestError;
 
 
@@ -161,6 +360,22 @@ mod tests {
     assert_eq!(!Side::Short, Side::Long);
   }
 
+  /// Check that we infer position and order sides as expected.
+  #[test]
+  fn infer_sides() {
+    assert_eq!(Side::from_signed_quantity(&Num::from(3)), Some(Side::Long));
+    assert_eq!(
+      Side::from_signed_quantity(&Num::from(-3)),
+      Some(Side::Short)
+    );
+    assert_eq!(Side::from_signed_quantity(&Num::from(0)), None);
+
+    assert_eq!(Side::Long.opening_side(), order::Side::Buy);
+    assert_eq!(Side::Long.closing_side(), order::Side::Sell);
+    assert_eq!(Side::Short.opening_side(), order::Side::Sell);
+    assert_eq!(Side::Short.closing_side(), order::Side::Buy);
+  }
+
   /// Make sure that we can deserialize and serialize a `Position`
   /// object.
   #[test]
@@ -203,6 +418,37 @@ mod tests {
     assert_eq!(pos.change_today, Some(Num::new(84, 10000)));
   }
 
+  /// Check that we create the expected order for closing a short
+  /// position.
+  #[test]
+  fn close_short_position() {
+    let json = r#"{
+    "asset_id": "904837e3-3b76-47ec-b432-046db621571b",
+    "symbol": "AAPL",
+    "exchange": "NASDAQ",
+    "asset_class": "us_equity",
+    "avg_entry_price": "100.0",
+    "qty": "-7",
+    "side": "short",
+    "cost_basis": "-700.0"
+}"#;
+
+    let pos = from_json::<Position>(json).unwrap();
+    assert_eq!(pos.quantity, Num::from(7));
+    assert_eq!(pos.signed_quantity(), Num::from(-7));
+    assert_eq!(pos.closing_side(), order::Side::Buy);
+
+    let request = pos.to_closing_order();
+    assert_eq!(request.symbol, asset::Symbol::Sym("AAPL".to_string()));
+    assert_eq!(request.side, order::Side::Buy);
+    assert_eq!(request.amount, order::Amount::quantity(7));
+    assert_eq!(request.type_, order::Type::Market);
+    assert_eq!(
+      request.position_intent,
+      Some(order::PositionIntent::BuyToClose)
+    );
+  }
+
   /// Check that we can parse a position with a fractional quantity.
   #[test]
   fn parse_fractional_position() {
@@ -270,11 +516,53 @@ mod tests {
     assert_eq!(pos.quantity, Num::from(24));
   }
 
+  /// Check that we serialize the amount of a position to close
+  /// correctly.
+  #[test]
+  fn serialize_delete_query() {
+    let symbol = asset::Symbol::Sym("SPY".to_string());
+    let request = DeleteReq::from(symbol.clone());
+    assert_eq!(Delete::query(&request).unwrap(), None);
+
+    let request = DeleteReqInit {
+      amount: Some(CloseAmount::Quantity(Num::new(3, 2))),
+      ..Default::default()
+    }
+    .init(symbol.clone());
+    assert_eq!(Delete::query(&request).unwrap().unwrap(), "qty=1.5");
+
+    let request = DeleteReqInit {
+      amount: Some(CloseAmount::Percentage(Num::from(50))),
+      ..Default::default()
+    }
+    .init(symbol);
+    assert_eq!(Delete::query(&request).unwrap().unwrap(), "percentage=50");
+  }
+
+  /// Check that we report the expected error when attempting to close
+  /// a position that does not exist.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn close_nonexistent_position() {
+    let client = live::client().unwrap();
+    let request = DeleteReqInit {
+      amount: Some(CloseAmount::Percentage(Num::from(50))),
+      ..Default::default()
+    }
+    .init(asset::Symbol::Sym("ABC123".to_string()));
+
+    let err = client.issue::<Delete>(&request).await.unwrap_err();
+    match err {
+      RequestError::Endpoint(DeleteError::NotFound(..)) => (),
+      _ => panic!("Received unexpected error: {:?}", err),
+    }
+  }
+
   /// Check that we can retrieve an open position, if one exists.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn retrieve_position() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let symbol = asset::Symbol::Sym("SPY".to_string());
     let result = client.issue::<Get>(&symbol).await;
 
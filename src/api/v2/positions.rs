@@ -1,10 +1,39 @@
-// Copyright (C) 2019-2021 The apca Developers
+// Copyright (C) 2019-2022 The apca Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use http::Method;
+
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use serde::Deserializer;
+use serde::Serialize;
+use serde_urlencoded::to_string as to_query;
+
+use crate::api::v2::order::Order;
 use crate::api::v2::position::Position;
 use crate::Str;
 
 
+/// Deserialize the body of a liquidation status, which may either be
+/// an order or an error.
+fn order_from_body<'de, D>(deserializer: D) -> Result<Option<Order>, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  #[derive(Deserialize)]
+  #[serde(untagged)]
+  enum Body {
+    Order(Box<Order>),
+    Other(IgnoredAny),
+  }
+
+  match Body::deserialize(deserializer)? {
+    Body::Order(order) => Ok(Some(*order)),
+    Body::Other(..) => Ok(None),
+  }
+}
+
+
 Endpoint! {
   /// The representation of a GET request to the /v2/positions endpoint.
   pub Get(()),
@@ -21,28 +50,113 @@ Endpoint! {
 }
 
 
-// TODO: There is the possibility to issue a DELETE against the
-//       /v2/positions endpoint in order to liquidate all open
-//       positions, which may be interesting to use. However, that
-//       requires support for multi-status HTTP responses.
+/// A DELETE request to be made to the /v2/positions endpoint.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct DeleteReq {
+  /// Whether to cancel all open orders before liquidating positions.
+  #[serde(rename = "cancel_orders")]
+  pub cancel_orders: bool,
+}
+
+
+/// The outcome of the attempt to liquidate a single position, as
+/// reported by a DELETE request to the /v2/positions endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct LiquidationStatus {
+  /// The symbol of the position.
+  #[serde(rename = "symbol")]
+  pub symbol: String,
+  /// The HTTP status code reported for the liquidation of the
+  /// position.
+  #[serde(rename = "status")]
+  pub status: u16,
+  /// The order created for liquidating the position, if any.
+  #[serde(rename = "body", default, deserialize_with = "order_from_body")]
+  pub order: Option<Order>,
+}
+
+
+Endpoint! {
+  /// The representation of a DELETE request to the /v2/positions
+  /// endpoint, liquidating all open positions.
+  pub Delete(DeleteReq),
+  Ok => Vec<LiquidationStatus>, [
+    /// The liquidation of all open positions was attempted. The
+    /// individual outcomes are reported per position.
+    /* 207 */ MULTI_STATUS,
+    /// There were no open positions to liquidate.
+    /* 200 */ OK,
+  ],
+  Err => DeleteError, []
+
+  #[inline]
+  fn method() -> Method {
+    Method::DELETE
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v2/positions".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+}
 
 
 #[cfg(test)]
 mod tests {
   use super::*;
 
+  use http_endpoint::Endpoint;
+
+  use serde_json::json;
+
   use test_log::test;
 
-  use crate::api_info::ApiInfo;
-  use crate::Client;
+  use crate::live;
+  use crate::test_util::order_json;
 
 
+  /// Check that we can parse the response to a request liquidating all
+  /// positions.
+  #[test]
+  fn parse_liquidation_statuses() {
+    let order = order_json(json!({
+      "updated_at": null,
+      "submitted_at": null,
+      "qty": "15",
+      "side": "sell",
+    }));
+    let response = json!([
+      {
+        "symbol": "AAPL",
+        "status": 200,
+        "body": order,
+      },
+      {
+        "symbol": "SPY",
+        "status": 403,
+        "body": {"code": 40310000, "message": "insufficient qty available for order"}
+      }
+    ])
+    .to_string();
+    let statuses = <Delete as Endpoint>::parse(response.as_bytes()).unwrap();
+    assert_eq!(statuses.len(), 2);
+    assert_eq!(statuses[0].symbol, "AAPL");
+    assert_eq!(statuses[0].order.as_ref().unwrap().symbol, "AAPL");
+    assert_eq!(statuses[1].status, 403);
+    assert_eq!(statuses[1].order, None);
+  }
+
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn list_positions() {
     // We can't do much here except check that the request is not
     // reporting any errors.
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let _ = client.issue::<Get>(&()).await.unwrap();
   }
 }
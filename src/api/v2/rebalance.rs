@@ -0,0 +1,506 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use num_decimal::Num;
+
+use thiserror::Error;
+
+use crate::api::v2::account;
+use crate::api::v2::asset;
+use crate::api::v2::batch::submit_orders;
+use crate::api::v2::batch::SubmitResult;
+use crate::api::v2::order::Amount;
+use crate::api::v2::order::OrderReq;
+use crate::api::v2::order::OrderReqInit;
+use crate::api::v2::order::Side;
+use crate::api::v2::position::Position;
+use crate::api::v2::positions;
+use crate::data::v2::last_quote;
+use crate::data::v2::last_quote::LastQuoteReqInit;
+use crate::money;
+use crate::Client;
+use crate::RequestError;
+
+
+/// Information about an asset that is relevant for rebalancing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instrument {
+  /// The price at which to value the asset.
+  pub price: Num,
+  /// Whether the asset can be traded in fractional quantities.
+  pub fractionable: bool,
+}
+
+
+/// A set of orders rebalancing a portfolio.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Plan {
+  /// The sell orders to submit, reducing or closing positions.
+  pub sells: Vec<OrderReq>,
+  /// The buy orders to submit, opening or extending positions.
+  ///
+  /// These orders are funded by the available cash as well as the
+  /// estimated proceeds of the sell orders.
+  pub buys: Vec<OrderReq>,
+}
+
+impl Plan {
+  /// Check whether the portfolio is balanced already, i.e., no orders
+  /// are necessary.
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.sells.is_empty() && self.buys.is_empty()
+  }
+}
+
+
+/// An error as reported by [`plan`].
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum PlanError {
+  /// Target weights were negative or summed up to more than one.
+  #[error("target weights must not be negative and must sum up to at most one")]
+  InvalidWeights,
+  /// No instrument information was provided for a symbol.
+  #[error("no instrument information available for {0}")]
+  MissingInstrument(String),
+  /// The price of an instrument was not positive.
+  #[error("price of {0} is not positive")]
+  InvalidPrice(String),
+}
+
+
+/// An error as reported by [`rebalance`].
+#[derive(Debug, Error)]
+pub enum RebalanceError {
+  /// The account information could not be retrieved.
+  #[error("failed to retrieve account information")]
+  Account(#[source] RequestError<account::GetError>),
+  /// The open positions could not be retrieved.
+  #[error("failed to retrieve open positions")]
+  Positions(#[source] RequestError<positions::GetError>),
+  /// The asset information for a symbol could not be retrieved.
+  #[error("failed to retrieve asset information for {0}")]
+  Asset(String, #[source] RequestError<asset::GetError>),
+  /// The latest quote for a symbol could not be retrieved.
+  #[error("failed to retrieve latest quote for {0}")]
+  Quote(String, #[source] RequestError<last_quote::GetError>),
+  /// The rebalancing orders could not be planned.
+  #[error("failed to plan rebalancing orders")]
+  Plan(#[source] PlanError),
+}
+
+
+/// Truncate a dollar amount to full cents.
+fn truncate_cents(amount: &Num) -> Num {
+  (amount * 100).trunc() / 100
+}
+
+
+/// Create a market order for the given symbol.
+fn order(symbol: &str, side: Side, amount: Amount) -> OrderReq {
+  OrderReqInit::default().init(symbol, side, amount)
+}
+
+
+/// Compute the orders necessary to rebalance a portfolio to the given
+/// target weights.
+///
+/// `targets` maps symbols to their target weight, as a factor of the
+/// total portfolio value (the value of all positions plus `cash`).
+/// Positions without target weight are liquidated and short positions
+/// without target weight covered. `instruments` has to contain
+/// information about all symbols of positions and targets.
+///
+/// The plan respects the following constraints:
+/// - assets that are not fractionable are only traded in whole shares
+/// - orders below `min_notional` are omitted, unless they liquidate or
+///   cover a position
+/// - buy orders do not exceed `cash` plus the estimated proceeds of
+///   the sell orders; if they would, the largest deficits are funded
+///   first (covering short positions is not subject to this limit)
+///
+/// All orders are market orders. Orders for fractionable assets are
+/// expressed as a notional amount, except for liquidations.
+pub fn plan(
+  positions: &[Position],
+  targets: &BTreeMap<String, Num>,
+  instruments: &BTreeMap<String, Instrument>,
+  cash: &Num,
+  min_notional: &Num,
+) -> Result<Plan, PlanError> {
+  let total_weight = targets
+    .values()
+    .fold(Num::from(0), |sum, weight| sum + weight);
+  if targets.values().any(Num::is_negative) || total_weight > Num::from(1) {
+    return Err(PlanError::InvalidWeights)
+  }
+
+  let mut holdings = BTreeMap::<&str, Num>::new();
+  for position in positions {
+    let holding = holdings
+      .entry(&position.symbol)
+      .or_insert_with(|| Num::from(0));
+    *holding += position.signed_quantity();
+  }
+  for symbol in targets.keys() {
+    let _ = holdings
+      .entry(symbol.as_str())
+      .or_insert_with(|| Num::from(0));
+  }
+
+  let mut total = cash.clone();
+  for (symbol, quantity) in &holdings {
+    let instrument = instruments
+      .get(*symbol)
+      .ok_or_else(|| PlanError::MissingInstrument(symbol.to_string()))?;
+    if !instrument.price.is_positive() {
+      return Err(PlanError::InvalidPrice(symbol.to_string()))
+    }
+    total += money::notional(&instrument.price, quantity);
+  }
+
+  let mut plan = Plan::default();
+  let mut budget = cash.clone();
+  let mut deficits = Vec::new();
+
+  for (symbol, quantity) in &holdings {
+    let instrument = &instruments[*symbol];
+    let weight = targets.get(*symbol).cloned().unwrap_or_default();
+    let value = money::notional(&instrument.price, quantity);
+    let delta = &weight * &total - &value;
+
+    if weight.is_zero() && quantity.is_negative() {
+      // Covering short positions is exact as well and takes precedence
+      // over funding any deficits.
+      budget += &value;
+      let amount = Amount::quantity(-quantity);
+      plan.buys.push(order(symbol, Side::Buy, amount));
+    } else if delta.is_negative() {
+      let excess = -delta;
+      let amount = if weight.is_zero() && quantity.is_positive() {
+        // Liquidations are exact and not subject to the minimum
+        // notional.
+        budget += &value;
+        Amount::quantity(quantity.clone())
+      } else if instrument.fractionable {
+        let notional = truncate_cents(&excess);
+        if notional.is_zero() || &notional < min_notional {
+          continue
+        }
+        budget += &notional;
+        Amount::notional(notional)
+      } else {
+        let shares = (&excess / &instrument.price).trunc();
+        let proceeds = money::notional(&instrument.price, &shares);
+        if shares.is_zero() || &proceeds < min_notional {
+          continue
+        }
+        budget += proceeds;
+        Amount::quantity(shares)
+      };
+      plan.sells.push(order(symbol, Side::Sell, amount));
+    } else if delta.is_positive() {
+      deficits.push((*symbol, instrument, delta));
+    }
+  }
+
+  // Fund the largest deficits first. The sort is stable, so that equal
+  // deficits are handled in symbol order.
+  deficits.sort_by(|(_, _, lhs), (_, _, rhs)| rhs.partial_cmp(lhs).unwrap_or(Ordering::Equal));
+
+  for (symbol, instrument, deficit) in deficits {
+    let available = if deficit < budget {
+      deficit
+    } else {
+      budget.clone()
+    };
+    let (amount, cost) = if instrument.fractionable {
+      let notional = truncate_cents(&available);
+      (Amount::notional(notional.clone()), notional)
+    } else {
+      let shares = (&available / &instrument.price).trunc();
+      let cost = money::notional(&instrument.price, &shares);
+      (Amount::quantity(shares), cost)
+    };
+
+    if cost.is_zero() || &cost < min_notional {
+      continue
+    }
+    budget -= &cost;
+    plan.buys.push(order(symbol, Side::Buy, amount));
+  }
+
+  Ok(plan)
+}
+
+
+/// Retrieve the price at which to value an asset not currently held,
+/// based on its latest quote.
+async fn quote_price(client: &Client, symbol: &str) -> Result<Num, RebalanceError> {
+  let request = LastQuoteReqInit::default().init(symbol);
+  let quote = client
+    .issue::<last_quote::Get>(&request)
+    .await
+    .map_err(|err| RebalanceError::Quote(symbol.to_string(), err))?;
+
+  let price = match (quote.bid_price.is_positive(), quote.ask_price.is_positive()) {
+    (true, true) => (&quote.bid_price + &quote.ask_price) / 2,
+    (false, true) => quote.ask_price,
+    _ => quote.bid_price,
+  };
+  Ok(price)
+}
+
+
+/// Compute the orders necessary to rebalance the account's portfolio to
+/// the given target weights, retrieving all required information.
+///
+/// Positions are valued at their current price, other assets at the
+/// midpoint of their latest quote. The cash available for investment
+/// is capped by the account's buying power, so that no margin is used.
+/// See [`plan`] for details.
+pub async fn rebalance(
+  client: &Client,
+  targets: &BTreeMap<String, Num>,
+  min_notional: &Num,
+) -> Result<Plan, RebalanceError> {
+  let account = client
+    .issue::<account::Get>(&())
+    .await
+    .map_err(RebalanceError::Account)?;
+  let positions = client
+    .issue::<positions::Get>(&())
+    .await
+    .map_err(RebalanceError::Positions)?;
+
+  let symbols = positions
+    .iter()
+    .map(|position| position.symbol.as_str())
+    .chain(targets.keys().map(String::as_str))
+    .collect::<Vec<_>>();
+
+  let mut instruments = BTreeMap::new();
+  for symbol in symbols {
+    if instruments.contains_key(symbol) {
+      continue
+    }
+
+    let asset = client
+      .issue::<asset::Get>(&asset::Symbol::Sym(symbol.to_string()))
+      .await
+      .map_err(|err| RebalanceError::Asset(symbol.to_string(), err))?;
+    let current_price = positions
+      .iter()
+      .find(|position| position.symbol == symbol)
+      .and_then(|position| position.current_price.clone());
+    let price = match current_price {
+      Some(price) => price,
+      None => quote_price(client, symbol).await?,
+    };
+
+    let instrument = Instrument {
+      price,
+      fractionable: asset.fractionable,
+    };
+    let _ = instruments.insert(symbol.to_string(), instrument);
+  }
+
+  let cash = if account.cash < account.buying_power {
+    account.cash
+  } else {
+    account.buying_power
+  };
+  plan(&positions, targets, &instruments, &cash, min_notional).map_err(RebalanceError::Plan)
+}
+
+
+/// Submit the orders of a rebalancing plan, with at most `concurrency`
+/// requests in flight at any time.
+///
+/// Sell orders are submitted before buy orders. Results are reported
+/// in the same order, i.e., those of the sell orders first. Note that
+/// buy orders relying on the proceeds of sell orders may be rejected
+/// if the latter did not fill yet.
+pub async fn submit(client: &Client, plan: &Plan, concurrency: usize) -> Vec<SubmitResult> {
+  let mut results = submit_orders(client, &plan.sells, concurrency).await;
+  let buys = submit_orders(client, &plan.buys, concurrency).await;
+  results.extend(buys);
+  results
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::from_value;
+  use serde_json::json;
+
+  use test_log::test;
+
+  use crate::api::v2::position;
+
+
+  /// Create a long `Position` with the given symbol and quantity.
+  fn position(symbol: &str, quantity: u64) -> Position {
+    let json = json!({
+      "asset_id": "904837e3-3b76-47ec-b432-046db621571b",
+      "symbol": symbol,
+      "exchange": "NASDAQ",
+      "asset_class": "us_equity",
+      "avg_entry_price": "100.0",
+      "qty": quantity.to_string(),
+      "side": "long",
+      "market_value": null,
+      "cost_basis": "0",
+      "unrealized_pl": null,
+      "unrealized_plpc": null,
+      "unrealized_intraday_pl": null,
+      "unrealized_intraday_plpc": null,
+      "current_price": null,
+      "lastday_price": null,
+      "change_today": null,
+    });
+    from_value(json).unwrap()
+  }
+
+  /// Create a short `Position` with the given symbol and quantity.
+  fn short_position(symbol: &str, quantity: u64) -> Position {
+    let mut position = position(symbol, quantity);
+    position.side = position::Side::Short;
+    position
+  }
+
+  /// Create an `Instrument` with the given price.
+  fn instrument(price: u64, fractionable: bool) -> Instrument {
+    Instrument {
+      price: Num::from(price),
+      fractionable,
+    }
+  }
+
+
+  /// Check that we plan the expected orders for rebalancing.
+  #[test]
+  fn plan_rebalance() {
+    // 10 shares of AAPL at $100, 5 of SPY at $200, 1 of XYZ at $50, as
+    // well as $950 in cash, for a total of $3000.
+    let positions = [position("AAPL", 10), position("SPY", 5), position("XYZ", 1)];
+    let instruments = BTreeMap::from([
+      ("AAPL".to_string(), instrument(100, true)),
+      ("SPY".to_string(), instrument(200, false)),
+      ("VOO".to_string(), instrument(300, false)),
+      ("XYZ".to_string(), instrument(50, false)),
+    ]);
+    let targets = BTreeMap::from([
+      ("AAPL".to_string(), Num::new(1, 10)),
+      ("SPY".to_string(), Num::new(1, 2)),
+      ("VOO".to_string(), Num::new(2, 5)),
+    ]);
+
+    let result = plan(
+      &positions,
+      &targets,
+      &instruments,
+      &Num::from(950),
+      &Num::from(1),
+    )
+    .unwrap();
+
+    // AAPL is reduced to $300 and XYZ liquidated.
+    let expected = vec![
+      order("AAPL", Side::Sell, Amount::notional(700)),
+      order("XYZ", Side::Sell, Amount::quantity(1)),
+    ];
+    assert_eq!(result.sells, expected);
+
+    // VOO (deficit of $1200) is funded before SPY (deficit of $500).
+    // Both are not fractionable.
+    let expected = vec![
+      order("VOO", Side::Buy, Amount::quantity(4)),
+      order("SPY", Side::Buy, Amount::quantity(2)),
+    ];
+    assert_eq!(result.buys, expected);
+  }
+
+  /// Check that buy orders do not exceed the available cash.
+  #[test]
+  fn plan_within_budget() {
+    let instruments = BTreeMap::from([
+      ("AAPL".to_string(), instrument(100, true)),
+      ("SPY".to_string(), instrument(200, true)),
+    ]);
+    let targets = BTreeMap::from([
+      ("AAPL".to_string(), Num::new(1, 2)),
+      ("SPY".to_string(), Num::new(1, 2)),
+    ]);
+
+    let initial = plan(&[], &targets, &instruments, &Num::from(1000), &Num::from(1)).unwrap();
+    assert_eq!(initial.sells, Vec::new());
+    assert_eq!(
+      initial.buys,
+      vec![
+        order("AAPL", Side::Buy, Amount::notional(500)),
+        order("SPY", Side::Buy, Amount::notional(500)),
+      ]
+    );
+
+    // A balanced portfolio does not need any orders.
+    let positions = [position("AAPL", 5), position("SPY", 5)];
+    let instruments = BTreeMap::from([
+      ("AAPL".to_string(), instrument(200, true)),
+      ("SPY".to_string(), instrument(200, true)),
+    ]);
+    let balanced = plan(
+      &positions,
+      &targets,
+      &instruments,
+      &Num::from(0),
+      &Num::from(1),
+    )
+    .unwrap();
+    assert!(balanced.is_empty());
+  }
+
+  /// Check that short positions without target weight are covered
+  /// exactly, irrespective of the available cash.
+  #[test]
+  fn plan_cover_short() {
+    // 10 shares of TSLA at $100 are sold short, with the price having
+    // risen to the point that the $400 in cash do not suffice for
+    // covering them.
+    let positions = [short_position("TSLA", 10)];
+    let instruments = BTreeMap::from([("TSLA".to_string(), instrument(100, true))]);
+    let targets = BTreeMap::new();
+
+    let result = plan(
+      &positions,
+      &targets,
+      &instruments,
+      &Num::from(400),
+      &Num::from(1),
+    )
+    .unwrap();
+    assert_eq!(result.sells, Vec::new());
+    assert_eq!(
+      result.buys,
+      vec![order("TSLA", Side::Buy, Amount::quantity(10))]
+    );
+  }
+
+  /// Check that we report invalid input as errors.
+  #[test]
+  fn plan_invalid_input() {
+    let instruments = BTreeMap::from([("AAPL".to_string(), instrument(100, true))]);
+    let targets = BTreeMap::from([("AAPL".to_string(), Num::new(3, 2))]);
+    let err = plan(&[], &targets, &instruments, &Num::from(0), &Num::from(1)).unwrap_err();
+    assert_eq!(err, PlanError::InvalidWeights);
+
+    let targets = BTreeMap::from([("SPY".to_string(), Num::new(1, 2))]);
+    let err = plan(&[], &targets, &instruments, &Num::from(0), &Num::from(1)).unwrap_err();
+    assert_eq!(err, PlanError::MissingInstrument("SPY".to_string()));
+  }
+}
@@ -0,0 +1,669 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+#[cfg(feature = "extra-fields")]
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use num_decimal::Num;
+
+use thiserror::Error;
+
+use uuid::Uuid;
+
+use crate::api::v2::asset;
+use crate::api::v2::order::Amount;
+use crate::api::v2::order::ChangeReq;
+use crate::api::v2::order::Class;
+use crate::api::v2::order::Id;
+use crate::api::v2::order::Order;
+use crate::api::v2::order::OrderReq;
+use crate::api::v2::order::Side;
+use crate::api::v2::order::Status;
+use crate::api::v2::order::TimeInForce;
+use crate::api::v2::order::Type;
+use crate::data::v2::bars;
+use crate::data::v2::stream;
+use crate::money;
+
+
+/// An error as reported by the [`Simulator`].
+#[derive(Clone, Copy, Debug, Eq, Error, PartialEq)]
+pub enum SimulatorError {
+  /// No order was found with the given ID.
+  #[error("order {} not found", .0.as_simple())]
+  NotFound(Id),
+  /// The order is no longer open and can't be changed or canceled.
+  #[error("order {} is no longer open", .0.as_simple())]
+  NotOpen(Id),
+  /// The request makes use of functionality the simulator does not
+  /// support.
+  #[error("unsupported order request: {0}")]
+  Unsupported(&'static str),
+  /// Some data in the request was invalid.
+  #[error("invalid order request: {0}")]
+  InvalidInput(&'static str),
+}
+
+
+/// The prices at which market data allow for an order to be filled.
+struct Prices {
+  /// The price at which a fill is assumed to happen, absent any
+  /// constraints imposed by the order.
+  reference: Num,
+  /// The lowest price seen.
+  low: Num,
+  /// The highest price seen.
+  high: Num,
+}
+
+impl Prices {
+  /// Create a `Prices` object for a single price.
+  fn at(price: &Num) -> Self {
+    Self {
+      reference: price.clone(),
+      low: price.clone(),
+      high: price.clone(),
+    }
+  }
+}
+
+
+/// A local order fill simulator.
+///
+/// The simulator accepts the same request types as the order endpoints
+/// and reports orders using the same [`Order`] type, allowing
+/// strategies to be developed without submitting orders to even the
+/// paper trading API. Orders are filled based on market data fed into
+/// it, be it from a realtime stream (see [`Simulator::on_data`]) or
+/// from historical bars (see [`Simulator::on_bar`]).
+///
+/// The simulator supports simple market, limit, stop, and stop limit
+/// orders. Fills happen in full and without slippage, at the price
+/// reported by the market data that cause them. Orders with a time in
+/// force of [`ImmediateOrCancel`][TimeInForce::ImmediateOrCancel] or
+/// [`FillOrKill`][TimeInForce::FillOrKill] are canceled if the first
+/// market data for their symbol do not fill them. Other orders stay
+/// open until filled or canceled.
+#[derive(Debug)]
+pub struct Simulator {
+  /// The available cash.
+  cash: Num,
+  /// All orders submitted, in the order they were submitted.
+  orders: Vec<Order>,
+  /// The IDs of stop limit orders that have been triggered.
+  triggered: HashSet<Id>,
+  /// The position quantities, indexed by symbol.
+  positions: HashMap<String, Num>,
+  /// The time stamp of the most recent market data seen.
+  now: Option<DateTime<Utc>>,
+  /// The number of orders submitted so far, used for generating IDs.
+  count: u128,
+}
+
+impl Simulator {
+  /// Create a new `Simulator` with the given amount of cash and no
+  /// open positions.
+  pub fn new(cash: impl Into<Num>) -> Self {
+    Self {
+      cash: cash.into(),
+      orders: Vec::new(),
+      triggered: HashSet::new(),
+      positions: HashMap::new(),
+      now: None,
+      count: 0,
+    }
+  }
+
+  /// Retrieve the current time, as defined by the market data seen.
+  fn now(&self) -> DateTime<Utc> {
+    self.now.unwrap_or_else(Utc::now)
+  }
+
+  /// Find the index of the order with the given ID.
+  fn index(&self, id: Id) -> Result<usize, SimulatorError> {
+    self
+      .orders
+      .iter()
+      .position(|order| order.id == id)
+      .ok_or(SimulatorError::NotFound(id))
+  }
+
+  /// Submit an order, akin to [`order::Post`][crate::api::v2::order::Post].
+  pub fn submit(&mut self, request: &OrderReq) -> Result<Order, SimulatorError> {
+    if request.class != Class::Simple {
+      return Err(SimulatorError::Unsupported("only simple orders are supported"))
+    }
+
+    let symbol = match &request.symbol {
+      asset::Symbol::Sym(symbol)
+      | asset::Symbol::SymExchg(symbol, ..)
+      | asset::Symbol::SymExchgCls(symbol, ..) => symbol.clone(),
+      asset::Symbol::Id(..) => {
+        return Err(SimulatorError::Unsupported(
+          "orders need to reference assets by symbol",
+        ))
+      },
+    };
+
+    match request.type_ {
+      Type::Market => (),
+      Type::Limit if request.limit_price.is_none() => {
+        return Err(SimulatorError::InvalidInput("limit orders require a limit price"))
+      },
+      Type::Stop if request.stop_price.is_none() => {
+        return Err(SimulatorError::InvalidInput("stop orders require a stop price"))
+      },
+      Type::StopLimit if request.limit_price.is_none() || request.stop_price.is_none() => {
+        return Err(SimulatorError::InvalidInput(
+          "stop limit orders require a stop and a limit price",
+        ))
+      },
+      Type::Limit | Type::Stop | Type::StopLimit => (),
+      Type::TrailingStop => {
+        return Err(SimulatorError::Unsupported("trailing stop orders are not supported"))
+      },
+    }
+
+    check_ticks(&request.limit_price, &request.stop_price)?;
+
+    let amount_valid = match &request.amount {
+      Amount::Quantity { quantity } => quantity.is_positive(),
+      Amount::Notional { notional } => notional.is_positive(),
+    };
+    if !amount_valid {
+      return Err(SimulatorError::InvalidInput("order amount needs to be positive"))
+    }
+
+    self.count += 1;
+    let id = Id(Uuid::from_u128(self.count));
+    let now = self.now();
+    let order = Order {
+      id,
+      client_order_id: request
+        .client_order_id
+        .clone()
+        .unwrap_or_else(|| id.as_simple().to_string()),
+      status: Status::New,
+      created_at: now,
+      updated_at: Some(now),
+      submitted_at: Some(now),
+      filled_at: None,
+      expired_at: None,
+      canceled_at: None,
+      asset_class: asset::Class::UsEquity,
+      asset_id: asset::Id(Uuid::nil()),
+      symbol,
+      amount: request.amount.clone(),
+      filled_quantity: Num::from(0),
+      type_: request.type_,
+      class: request.class,
+      side: request.side,
+      time_in_force: request.time_in_force,
+      limit_price: request.limit_price.clone(),
+      stop_price: request.stop_price.clone(),
+      trail_price: None,
+      trail_percent: None,
+      average_fill_price: None,
+      extended_hours: request.extended_hours,
+      legs: Vec::new(),
+      replaced_by: None,
+      replaces: None,
+      position_intent: request.position_intent,
+      local_currency: None,
+      #[cfg(feature = "extra-fields")]
+      extra: BTreeMap::new(),
+    };
+
+    self.orders.push(order.clone());
+    Ok(order)
+  }
+
+  /// Retrieve an open order for modification.
+  fn open_order_mut(&mut self, id: Id) -> Result<&mut Order, SimulatorError> {
+    let index = self.index(id)?;
+    let order = &mut self.orders[index];
+    if order.status.is_terminal() {
+      return Err(SimulatorError::NotOpen(id))
+    }
+    Ok(order)
+  }
+
+  /// Change an open order, akin to
+  /// [`order::Patch`][crate::api::v2::order::Patch].
+  pub fn change(&mut self, id: Id, request: &ChangeReq) -> Result<Order, SimulatorError> {
+    if !request.quantity.is_positive() {
+      return Err(SimulatorError::InvalidInput("order quantity needs to be positive"))
+    }
+
+    check_ticks(&request.limit_price, &request.stop_price)?;
+
+    let now = self.now();
+    let order = self.open_order_mut(id)?;
+    order.amount = Amount::quantity(request.quantity.clone());
+    order.time_in_force = request.time_in_force;
+    if request.limit_price.is_some() {
+      order.limit_price = request.limit_price.clone();
+    }
+    if request.stop_price.is_some() {
+      order.stop_price = request.stop_price.clone();
+    }
+    order.updated_at = Some(now);
+    Ok(order.clone())
+  }
+
+  /// Cancel an open order, akin to
+  /// [`order::Delete`][crate::api::v2::order::Delete].
+  pub fn cancel(&mut self, id: Id) -> Result<(), SimulatorError> {
+    let now = self.now();
+    let order = self.open_order_mut(id)?;
+    order.status = Status::Canceled;
+    order.canceled_at = Some(now);
+    order.updated_at = Some(now);
+    Ok(())
+  }
+
+  /// Retrieve an order, akin to [`order::Get`][crate::api::v2::order::Get].
+  pub fn order(&self, id: Id) -> Result<&Order, SimulatorError> {
+    self.index(id).map(|index| &self.orders[index])
+  }
+
+  /// Retrieve all orders that are still open, in the order they were
+  /// submitted.
+  pub fn open_orders(&self) -> impl Iterator<Item = &Order> {
+    self
+      .orders
+      .iter()
+      .filter(|order| !order.status.is_terminal())
+  }
+
+  /// Retrieve the available cash.
+  #[inline]
+  pub fn cash(&self) -> &Num {
+    &self.cash
+  }
+
+  /// Retrieve the position quantity held in the given symbol.
+  ///
+  /// Short positions are reported as negative quantities.
+  pub fn position(&self, symbol: &str) -> Num {
+    self
+      .positions
+      .get(symbol)
+      .cloned()
+      .unwrap_or_else(|| Num::from(0))
+  }
+
+  /// Feed realtime market data into the simulator, returning all
+  /// orders that got filled or canceled as a result.
+  pub fn on_data(&mut self, data: &stream::Data) -> Vec<Order> {
+    match data {
+      stream::Data::Trade(trade) => self.process(
+        &trade.symbol,
+        &trade.timestamp,
+        |_| Prices::at(&trade.trade_price),
+      ),
+      stream::Data::Quote(quote) => {
+        self.process(&quote.symbol, &quote.timestamp, |side| match side {
+          Side::Buy => Prices::at(&quote.ask_price),
+          Side::Sell => Prices::at(&quote.bid_price),
+        })
+      },
+      stream::Data::Bar(bar) => self.process(&bar.symbol, &bar.timestamp, |_| Prices {
+        reference: bar.open_price.clone(),
+        low: bar.low_price.clone(),
+        high: bar.high_price.clone(),
+      }),
+      _ => Vec::new(),
+    }
+  }
+
+  /// Feed a historical bar for the given symbol into the simulator,
+  /// returning all orders that got filled or canceled as a result.
+  ///
+  /// Orders that are filled by the bar are assumed to fill at the
+  /// bar's open price or the order's limit or stop price, whichever is
+  /// less favorable.
+  pub fn on_bar(&mut self, symbol: &str, bar: &bars::Bar) -> Vec<Order> {
+    self.process(symbol, &bar.time, |_| Prices {
+      reference: bar.open.clone(),
+      low: bar.low.clone(),
+      high: bar.high.clone(),
+    })
+  }
+
+  /// Process market data for the given symbol, filling or canceling
+  /// open orders as appropriate.
+  fn process<F>(&mut self, symbol: &str, time: &DateTime<Utc>, prices: F) -> Vec<Order>
+  where
+    F: Fn(Side) -> Prices,
+  {
+    self.now = Some(*time);
+
+    let indices = (0..self.orders.len())
+      .filter(|index| {
+        let order = &self.orders[*index];
+        order.symbol == symbol && !order.status.is_terminal()
+      })
+      .collect::<Vec<_>>();
+
+    let mut updated = Vec::new();
+    for index in indices {
+      let order = &self.orders[index];
+      let id = order.id;
+      let prices = prices(order.side);
+      let triggered = self.triggered.contains(&id) || is_triggered(order, &prices);
+      let price = if triggered {
+        fill_price(order, &prices)
+      } else {
+        None
+      };
+
+      if let Some(price) = price {
+        self.fill(index, price, time);
+      } else if matches!(
+        order.time_in_force,
+        TimeInForce::ImmediateOrCancel | TimeInForce::FillOrKill
+      ) {
+        let order = &mut self.orders[index];
+        order.status = Status::Canceled;
+        order.canceled_at = Some(*time);
+        order.updated_at = Some(*time);
+      } else {
+        if triggered && order.type_ == Type::StopLimit {
+          let _ = self.triggered.insert(id);
+        }
+        continue
+      }
+
+      let _ = self.triggered.remove(&id);
+      updated.push(self.orders[index].clone());
+    }
+    updated
+  }
+
+  /// Fill the order at the given index in full at the given price.
+  fn fill(&mut self, index: usize, price: Num, time: &DateTime<Utc>) {
+    let order = &mut self.orders[index];
+    let quantity = match &order.amount {
+      Amount::Quantity { quantity } => quantity.clone(),
+      Amount::Notional { notional } => notional / &price,
+    };
+    let notional = money::notional(&price, &quantity);
+
+    let position = self
+      .positions
+      .entry(order.symbol.clone())
+      .or_insert_with(|| Num::from(0));
+    match order.side {
+      Side::Buy => {
+        *position += &quantity;
+        self.cash -= &notional;
+      },
+      Side::Sell => {
+        *position -= &quantity;
+        self.cash += &notional;
+      },
+    }
+
+    order.status = Status::Filled;
+    order.filled_quantity = quantity;
+    order.average_fill_price = Some(price);
+    order.filled_at = Some(*time);
+    order.updated_at = Some(*time);
+  }
+}
+
+
+/// Check that the provided order prices are valid tick increments.
+fn check_ticks(limit: &Option<Num>, stop: &Option<Num>) -> Result<(), SimulatorError> {
+  let on_tick = [limit, stop]
+    .into_iter()
+    .flatten()
+    .all(|price| money::is_on_tick(price, asset::Class::UsEquity));
+  if !on_tick {
+    return Err(SimulatorError::InvalidInput(
+      "order prices need to be valid tick increments",
+    ))
+  }
+  Ok(())
+}
+
+
+/// Check whether the given prices trigger the stop of an order, if
+/// any.
+fn is_triggered(order: &Order, prices: &Prices) -> bool {
+  match (&order.type_, &order.stop_price) {
+    (Type::Stop | Type::StopLimit, Some(stop)) => match order.side {
+      Side::Buy => &prices.high >= stop,
+      Side::Sell => &prices.low <= stop,
+    },
+    _ => true,
+  }
+}
+
+
+/// Determine the price at which an order with a triggered stop, if any,
+/// would fill given the provided prices.
+fn fill_price(order: &Order, prices: &Prices) -> Option<Num> {
+  let reference = &prices.reference;
+  match order.type_ {
+    Type::Market => Some(reference.clone()),
+    Type::Stop => {
+      let stop = order.stop_price.as_ref()?;
+      match order.side {
+        Side::Buy if reference < stop => Some(stop.clone()),
+        Side::Sell if reference > stop => Some(stop.clone()),
+        _ => Some(reference.clone()),
+      }
+    },
+    Type::Limit | Type::StopLimit => {
+      let limit = order.limit_price.as_ref()?;
+      match order.side {
+        Side::Buy if reference <= limit => Some(reference.clone()),
+        Side::Buy if &prices.low <= limit => Some(limit.clone()),
+        Side::Sell if reference >= limit => Some(reference.clone()),
+        Side::Sell if &prices.high >= limit => Some(limit.clone()),
+        _ => None,
+      }
+    },
+    Type::TrailingStop => None,
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use chrono::TimeZone as _;
+
+  use crate::api::v2::order::ChangeReqInit;
+  use crate::api::v2::order::OrderReqInit;
+
+
+  /// Create a streamed trade for the given symbol at the given price.
+  fn trade(symbol: &str, price: Num) -> stream::Data {
+    stream::Data::Trade(stream::Trade {
+      symbol: symbol.to_string(),
+      trade_id: 1,
+      exchange: 'V',
+      trade_price: price,
+      trade_size: 100,
+      timestamp: Utc.with_ymd_and_hms(2022, 3, 1, 15, 0, 0).unwrap(),
+      trade_conditions: Vec::new(),
+      tape: 'C',
+    })
+  }
+
+  /// Create a historical bar with the given prices.
+  fn bar(open: i32, high: i32, low: i32, close: i32) -> bars::Bar {
+    bars::Bar {
+      time: Utc.with_ymd_and_hms(2022, 3, 1, 15, 0, 0).unwrap(),
+      open: Num::from(open),
+      close: Num::from(close),
+      high: Num::from(high),
+      low: Num::from(low),
+      volume: 1000,
+      vwap: None,
+      trade_count: None,
+    }
+  }
+
+  /// Check that market orders fill at the next trade's price.
+  #[test]
+  fn fill_market_order() {
+    let mut simulator = Simulator::new(1000);
+    let request = OrderReqInit::default().init("SPY", Side::Buy, Amount::quantity(2));
+    let order = simulator.submit(&request).unwrap();
+    assert_eq!(order.status, Status::New);
+    assert_eq!(simulator.open_orders().count(), 1);
+
+    let updated = simulator.on_data(&trade("AAPL", Num::from(150)));
+    assert!(updated.is_empty());
+
+    let updated = simulator.on_data(&trade("SPY", Num::from(400)));
+    assert_eq!(updated.len(), 1);
+    assert_eq!(updated[0].id, order.id);
+    assert_eq!(updated[0].status, Status::Filled);
+    assert_eq!(updated[0].filled_quantity, Num::from(2));
+    assert_eq!(updated[0].average_fill_price, Some(Num::from(400)));
+    assert_eq!(simulator.position("SPY"), Num::from(2));
+    assert_eq!(simulator.cash(), &Num::from(200));
+    assert_eq!(simulator.open_orders().count(), 0);
+  }
+
+  /// Check that limit orders only fill once the market reaches the
+  /// limit price.
+  #[test]
+  fn fill_limit_order() {
+    let mut simulator = Simulator::new(0);
+    let request = OrderReqInit {
+      type_: Type::Limit,
+      limit_price: Some(Num::from(105)),
+      ..Default::default()
+    }
+    .init("SPY", Side::Sell, Amount::quantity(1));
+    let order = simulator.submit(&request).unwrap();
+
+    assert!(simulator.on_bar("SPY", &bar(100, 104, 99, 102)).is_empty());
+
+    let updated = simulator.on_bar("SPY", &bar(102, 107, 101, 106));
+    assert_eq!(updated.len(), 1);
+    assert_eq!(updated[0].average_fill_price, Some(Num::from(105)));
+    assert_eq!(simulator.position("SPY"), Num::from(-1));
+    assert_eq!(simulator.cash(), &Num::from(105));
+    assert_eq!(simulator.order(order.id).unwrap().status, Status::Filled);
+  }
+
+  /// Check that stop limit orders stay triggered until their limit
+  /// condition is met.
+  #[test]
+  fn fill_stop_limit_order() {
+    let mut simulator = Simulator::new(1000);
+    let request = OrderReqInit {
+      type_: Type::StopLimit,
+      stop_price: Some(Num::from(110)),
+      limit_price: Some(Num::from(108)),
+      ..Default::default()
+    }
+    .init("SPY", Side::Buy, Amount::quantity(1));
+    let _ = simulator.submit(&request).unwrap();
+
+    assert!(simulator.on_data(&trade("SPY", Num::from(111))).is_empty());
+    let updated = simulator.on_data(&trade("SPY", Num::from(107)));
+    assert_eq!(updated.len(), 1);
+    assert_eq!(updated[0].average_fill_price, Some(Num::from(107)));
+  }
+
+  /// Check that immediate-or-cancel orders get canceled when they can't
+  /// be filled right away.
+  #[test]
+  fn cancel_unfilled_ioc_order() {
+    let mut simulator = Simulator::new(1000);
+    let request = OrderReqInit {
+      type_: Type::Limit,
+      limit_price: Some(Num::from(90)),
+      time_in_force: TimeInForce::ImmediateOrCancel,
+      ..Default::default()
+    }
+    .init("SPY", Side::Buy, Amount::quantity(1));
+    let _ = simulator.submit(&request).unwrap();
+
+    let updated = simulator.on_data(&trade("SPY", Num::from(100)));
+    assert_eq!(updated.len(), 1);
+    assert_eq!(updated[0].status, Status::Canceled);
+    assert_eq!(simulator.cash(), &Num::from(1000));
+  }
+
+  /// Check that we can change and cancel open orders.
+  #[test]
+  fn change_and_cancel_order() {
+    let mut simulator = Simulator::new(1000);
+    let request = OrderReqInit {
+      type_: Type::Limit,
+      limit_price: Some(Num::from(90)),
+      ..Default::default()
+    }
+    .init("SPY", Side::Buy, Amount::quantity(1));
+    let order = simulator.submit(&request).unwrap();
+
+    let request = ChangeReqInit {
+      quantity: Num::from(3),
+      limit_price: Some(Num::from(95)),
+      ..Default::default()
+    }
+    .init();
+    let changed = simulator.change(order.id, &request).unwrap();
+    assert_eq!(changed.amount, Amount::quantity(3));
+    assert_eq!(changed.limit_price, Some(Num::from(95)));
+
+    simulator.cancel(order.id).unwrap();
+    assert_eq!(
+      simulator.cancel(order.id).unwrap_err(),
+      SimulatorError::NotOpen(order.id)
+    );
+    assert!(simulator.on_data(&trade("SPY", Num::from(80))).is_empty());
+
+    let id = Id(Uuid::nil());
+    assert_eq!(simulator.cancel(id).unwrap_err(), SimulatorError::NotFound(id));
+  }
+
+  /// Check that we reject unsupported order requests.
+  #[test]
+  fn reject_unsupported_orders() {
+    let mut simulator = Simulator::new(1000);
+    let request = OrderReqInit {
+      type_: Type::TrailingStop,
+      trail_price: Some(Num::from(1)),
+      ..Default::default()
+    }
+    .init("SPY", Side::Sell, Amount::quantity(1));
+    let err = simulator.submit(&request).unwrap_err();
+    assert!(matches!(err, SimulatorError::Unsupported(..)));
+
+    let request = OrderReqInit {
+      type_: Type::Limit,
+      ..Default::default()
+    }
+    .init("SPY", Side::Buy, Amount::quantity(1));
+    let err = simulator.submit(&request).unwrap_err();
+    assert!(matches!(err, SimulatorError::InvalidInput(..)));
+
+    let mut request = OrderReqInit {
+      type_: Type::Limit,
+      limit_price: Some(Num::new(100005, 1000)),
+      ..Default::default()
+    }
+    .init("SPY", Side::Buy, Amount::quantity(1));
+    let err = simulator.submit(&request).unwrap_err();
+    assert!(matches!(err, SimulatorError::InvalidInput(..)));
+
+    request.round_to_tick(asset::Class::UsEquity);
+    let _ = simulator.submit(&request).unwrap();
+  }
+}
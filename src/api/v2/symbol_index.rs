@@ -0,0 +1,295 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use crate::api::v2::asset::Asset;
+use crate::api::v2::asset::Id;
+use crate::api::v2::assets;
+use crate::api::v2::assets::AssetsReq;
+use crate::Client;
+use crate::RequestError;
+
+
+/// Normalize a name for matching purposes, lower casing it and
+/// replacing all non alpha-numeric characters with spaces.
+fn normalize(name: &str) -> String {
+  let normalized = name
+    .chars()
+    .map(|c| {
+      if c.is_alphanumeric() {
+        c.to_ascii_lowercase()
+      } else {
+        ' '
+      }
+    })
+    .collect::<String>();
+
+  normalized.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+
+/// Check whether all characters of `needle` appear in `haystack` in
+/// order.
+fn is_subsequence(needle: &str, haystack: &str) -> bool {
+  let mut haystack = haystack.chars();
+  needle
+    .chars()
+    .all(|c| haystack.by_ref().any(|other| other == c))
+}
+
+
+/// Score how well the given (normalized) query matches an asset. A
+/// score of zero indicates no match.
+fn score(query: &str, asset: &Asset, name: &str) -> u8 {
+  if asset.symbol.eq_ignore_ascii_case(query) {
+    100
+  } else if name == query {
+    90
+  } else if name.starts_with(query) {
+    80
+  } else if query
+    .split(' ')
+    .all(|word| name.split(' ').any(|other| other.starts_with(word)))
+  {
+    60
+  } else if name.contains(query) {
+    40
+  } else if is_subsequence(query, name) {
+    20
+  } else {
+    0
+  }
+}
+
+
+/// A client-side index over assets, allowing for lookup by ID, symbol,
+/// and CUSIP as well as fuzzy search by name.
+///
+/// The index is a snapshot of the assets at the time it was built and
+/// needs to be refreshed explicitly to pick up changes.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolIndex {
+  /// All indexed assets.
+  assets: Vec<Asset>,
+  /// The normalized names of the assets, in the same order.
+  names: Vec<String>,
+  /// A mapping from asset ID to index into `assets`.
+  ids: HashMap<Id, usize>,
+  /// A mapping from symbol to index into `assets`.
+  symbols: HashMap<String, usize>,
+  /// A mapping from CUSIP to index into `assets`.
+  cusips: HashMap<String, usize>,
+}
+
+impl SymbolIndex {
+  /// Create a `SymbolIndex` over the provided assets.
+  pub fn new<I>(assets: I) -> Self
+  where
+    I: IntoIterator<Item = Asset>,
+  {
+    let assets = assets.into_iter().collect::<Vec<_>>();
+    let names = assets
+      .iter()
+      .map(|asset| normalize(&asset.name))
+      .collect();
+    let mut ids = HashMap::with_capacity(assets.len());
+    let mut symbols = HashMap::with_capacity(assets.len());
+    let mut cusips = HashMap::new();
+
+    for (idx, asset) in assets.iter().enumerate() {
+      let _ = ids.insert(asset.id, idx);
+      let _ = symbols.insert(asset.symbol.clone(), idx);
+      if let Some(cusip) = &asset.cusip {
+        let _ = cusips.insert(cusip.clone(), idx);
+      }
+    }
+
+    Self {
+      assets,
+      names,
+      ids,
+      symbols,
+      cusips,
+    }
+  }
+
+  /// Build a `SymbolIndex` over the assets matching the given request.
+  pub async fn load(
+    client: &Client,
+    request: &AssetsReq,
+  ) -> Result<Self, RequestError<assets::GetError>> {
+    let assets = client.issue::<assets::Get>(request).await?;
+    Ok(Self::new(assets))
+  }
+
+  /// Refresh the index with the assets matching the given request.
+  ///
+  /// On error, the index is left untouched.
+  pub async fn refresh(
+    &mut self,
+    client: &Client,
+    request: &AssetsReq,
+  ) -> Result<(), RequestError<assets::GetError>> {
+    *self = Self::load(client, request).await?;
+    Ok(())
+  }
+
+  /// Look up an asset by its ID.
+  #[inline]
+  pub fn by_id(&self, id: &Id) -> Option<&Asset> {
+    self.ids.get(id).map(|idx| &self.assets[*idx])
+  }
+
+  /// Look up an asset by its symbol.
+  #[inline]
+  pub fn by_symbol(&self, symbol: &str) -> Option<&Asset> {
+    self.symbols.get(symbol).map(|idx| &self.assets[*idx])
+  }
+
+  /// Look up an asset by its CUSIP.
+  #[inline]
+  pub fn by_cusip(&self, cusip: &str) -> Option<&Asset> {
+    self.cusips.get(cusip).map(|idx| &self.assets[*idx])
+  }
+
+  /// Search for assets by (company) name, returning at most `limit`
+  /// of them, best matches first.
+  ///
+  /// The search is case insensitive and tolerant to punctuation. Exact
+  /// symbol matches rank highest, followed by exact name matches,
+  /// prefix matches, word prefix matches, substring matches, and,
+  /// finally, names merely containing all characters of the query in
+  /// order.
+  pub fn search(&self, query: &str, limit: usize) -> Vec<&Asset> {
+    let query = normalize(query);
+    if query.is_empty() {
+      return Vec::new()
+    }
+
+    let mut matches = self
+      .assets
+      .iter()
+      .zip(&self.names)
+      .filter_map(|(asset, name)| match score(&query, asset, name) {
+        0 => None,
+        score => Some((score, asset, name)),
+      })
+      .collect::<Vec<_>>();
+
+    matches.sort_by_key(|(score, asset, name)| (Reverse(*score), name.len(), &asset.symbol));
+    matches
+      .into_iter()
+      .take(limit)
+      .map(|(_, asset, _)| asset)
+      .collect()
+  }
+
+  /// Retrieve the number of indexed assets.
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.assets.len()
+  }
+
+  /// Check whether the index is empty.
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.assets.is_empty()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::from_value;
+  use serde_json::json;
+
+  use test_log::test;
+
+  use uuid::Uuid;
+
+
+  /// Create an asset with the given properties.
+  fn asset(id: &str, symbol: &str, name: &str, cusip: Option<&str>) -> Asset {
+    from_value(json!({
+      "id": id,
+      "class": "us_equity",
+      "exchange": "NASDAQ",
+      "symbol": symbol,
+      "name": name,
+      "cusip": cusip,
+      "status": "active",
+      "tradable": true,
+      "marginable": true,
+      "shortable": true,
+      "easy_to_borrow": true,
+      "fractionable": true
+    }))
+    .unwrap()
+  }
+
+  /// Create an index over a few assets.
+  fn index() -> SymbolIndex {
+    SymbolIndex::new([
+      asset(
+        "904837e3-3b76-47ec-b432-046db621571b",
+        "AAPL",
+        "Apple Inc. Common Stock",
+        Some("037833100"),
+      ),
+      asset(
+        "b0b6dd9d-8b9b-48a9-ba46-b9d54906e415",
+        "MSFT",
+        "Microsoft Corporation Common Stock",
+        Some("594918104"),
+      ),
+      asset(
+        "4ce9353c-66d1-46c2-898f-fce867ab0247",
+        "APLE",
+        "Apple Hospitality REIT, Inc. Common Shares",
+        None,
+      ),
+    ])
+  }
+
+
+  /// Check that we can look up assets by ID, symbol, and CUSIP.
+  #[test]
+  fn exact_lookup() {
+    let index = index();
+    assert_eq!(index.len(), 3);
+
+    let id = Id(Uuid::parse_str("b0b6dd9d-8b9b-48a9-ba46-b9d54906e415").unwrap());
+    assert_eq!(index.by_id(&id).unwrap().symbol, "MSFT");
+    assert_eq!(index.by_symbol("APLE").unwrap().symbol, "APLE");
+    assert_eq!(index.by_cusip("037833100").unwrap().symbol, "AAPL");
+    assert!(index.by_cusip("000000000").is_none());
+    assert!(index.by_symbol("GOOG").is_none());
+  }
+
+  /// Check that fuzzy search by name ranks results as expected.
+  #[test]
+  fn fuzzy_search() {
+    let index = index();
+
+    let symbols = |query| {
+      index
+        .search(query, 10)
+        .into_iter()
+        .map(|asset| asset.symbol.as_str())
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(symbols("apple"), vec!["AAPL", "APLE"]);
+    assert_eq!(symbols("aple"), vec!["APLE", "AAPL"]);
+    assert_eq!(symbols("apple hosp"), vec!["APLE"]);
+    assert_eq!(symbols("Microsoft Corp."), vec!["MSFT"]);
+    assert_eq!(symbols("mcrsft"), vec!["MSFT"]);
+    assert_eq!(symbols("google"), Vec::<&str>::new());
+    assert_eq!(symbols("  "), Vec::<&str>::new());
+    assert_eq!(index.search("apple", 1).len(), 1);
+  }
+}
@@ -0,0 +1,543 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+use std::io::Error as IoError;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+
+use futures::TryStreamExt as _;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::from_slice as from_json;
+use serde_json::to_vec as to_json;
+use serde_json::Error as JsonError;
+
+use thiserror::Error;
+
+use crate::api::v2::account_activities;
+use crate::api::v2::account_activities::Activity;
+use crate::api::v2::account_activities::ActivityType;
+use crate::api::v2::account_activities::Direction;
+use crate::api::v2::activity_cursor::ActivityCursor;
+use crate::api::v2::activity_cursor::CursorState;
+use crate::api::v2::order;
+use crate::api::v2::order::Order;
+use crate::api::v2::orders;
+use crate::api::v2::orders::OrdersReq;
+use crate::state::StateStore;
+use crate::Client;
+use crate::RequestError;
+
+
+/// The number of orders to request per page.
+const ORDERS_PAGE_SIZE: usize = 500;
+
+
+/// The point in time up to which orders have been seen.
+///
+/// Because many orders may share a timestamp, the IDs of those seen at
+/// exactly that time are kept as well, so that others sharing it are
+/// not mistaken for having been seen.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Watermark {
+  /// The most recent time seen.
+  pub time: Option<DateTime<Utc>>,
+  /// The IDs of the orders seen at exactly `time`.
+  pub ids: Vec<order::Id>,
+}
+
+impl Watermark {
+  /// Check whether the order with the given ID and time has been seen.
+  fn is_seen(&self, time: DateTime<Utc>, id: order::Id) -> bool {
+    match self.time {
+      Some(last) => time < last || (time == last && self.ids.contains(&id)),
+      None => false,
+    }
+  }
+
+  /// Advance the watermark past the order with the given ID and time.
+  fn see(&mut self, time: DateTime<Utc>, id: order::Id) {
+    match self.time {
+      Some(last) if time < last => (),
+      Some(last) if time == last => {
+        if !self.ids.contains(&id) {
+          let () = self.ids.push(id);
+        }
+      },
+      _ => {
+        self.time = Some(time);
+        self.ids = vec![id];
+      },
+    }
+  }
+}
+
+
+/// The state of the account as seen by an [`AccountSync`], as persisted
+/// between runs.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Checkpoint {
+  /// The position of the cursor walking account activities.
+  pub activities: CursorState,
+  /// The most recent order update seen.
+  pub order_updates: Watermark,
+  /// The most recent order submission seen.
+  pub order_submissions: Watermark,
+  /// The IDs of all orders that were open as of the last sync.
+  pub open_orders: Vec<order::Id>,
+}
+
+impl Checkpoint {
+  /// Merge a set of retrieved orders into the checkpoint.
+  ///
+  /// Reported are all orders submitted or updated since the last sync,
+  /// in the order in which they were updated. Of duplicate orders, the
+  /// one retrieved last takes precedence.
+  fn merge<I>(&mut self, orders: I) -> Vec<Order>
+  where
+    I: IntoIterator<Item = Order>,
+  {
+    let orders = orders
+      .into_iter()
+      .map(|order| (order.id, order))
+      .collect::<HashMap<_, _>>();
+
+    self.open_orders = orders
+      .values()
+      .filter(|order| !order.status.is_terminal())
+      .map(|order| order.id)
+      .collect();
+
+    let mut updated = orders
+      .into_values()
+      .filter(|order| {
+        let id = order.id;
+        let updated = self.order_updates.is_seen(updated_at(order), id);
+        let submitted = self.order_submissions.is_seen(submitted_at(order), id);
+        !(updated && submitted)
+      })
+      .collect::<Vec<_>>();
+    let () = updated.sort_by_key(updated_at);
+
+    for order in &updated {
+      let () = self.order_updates.see(updated_at(order), order.id);
+      let () = self.order_submissions.see(submitted_at(order), order.id);
+    }
+    updated
+  }
+}
+
+
+/// Retrieve the time at which an order was last updated.
+fn updated_at(order: &Order) -> DateTime<Utc> {
+  order.updated_at.unwrap_or(order.created_at)
+}
+
+
+/// Retrieve the time at which an order was submitted.
+fn submitted_at(order: &Order) -> DateTime<Utc> {
+  order.submitted_at.unwrap_or(order.created_at)
+}
+
+
+/// The changes to the account since the last sync.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Delta {
+  /// The account activities that occurred, in chronological order.
+  pub activities: Vec<Activity>,
+  /// The orders that were submitted or updated, in their most recent
+  /// state and ordered by the time of their last update.
+  pub orders: Vec<Order>,
+}
+
+
+/// An error as reported by an [`AccountSync`].
+#[derive(Debug, Error)]
+pub enum SyncError {
+  /// The checkpoint could not be loaded or persisted.
+  #[error("failed to access persisted checkpoint")]
+  Store(#[source] IoError),
+  /// The persisted checkpoint could not be parsed.
+  #[error("persisted checkpoint is invalid")]
+  State(#[source] JsonError),
+  /// Account activities could not be retrieved.
+  #[error("failed to retrieve account activities")]
+  Activities(#[source] RequestError<account_activities::GetError>),
+  /// Orders could not be listed.
+  #[error("failed to list orders")]
+  Orders(#[source] RequestError<orders::GetError>),
+  /// An order could not be retrieved.
+  #[error("failed to retrieve order")]
+  Order(#[source] RequestError<order::GetError>),
+  /// The persisted checkpoint was changed by someone else in the
+  /// meantime.
+  #[error("persisted checkpoint was changed concurrently")]
+  Conflict,
+}
+
+
+/// A component for keeping track of account state incrementally.
+///
+/// Each sync retrieves only the account activities and orders that
+/// changed since the previous one and persists a [`Checkpoint`] in a
+/// [`StateStore`] afterwards, under the [`AccountSync::KEY`] key. That
+/// way, long running applications can resume quickly after a restart,
+/// without downloading the entire account history again. The very
+/// first sync retrieves the full history, though.
+///
+/// Checkpoints are persisted by means of a compare-and-swap operation,
+/// so that multiple instances sharing a store can not silently
+/// overwrite each other's progress.
+///
+/// The checkpoint is only persisted once all changes have been
+/// retrieved. Should a sync fail or the application crash before the
+/// reported changes have been processed, they are reported again by
+/// the next sync.
+#[derive(Debug)]
+pub struct AccountSync<S> {
+  /// The store used for persisting the checkpoint.
+  store: S,
+  /// The types of activities to retrieve. If empty, all activities are
+  /// retrieved.
+  types: Vec<ActivityType>,
+  /// The current checkpoint.
+  checkpoint: Checkpoint,
+  /// The checkpoint as last persisted.
+  state: Option<Vec<u8>>,
+}
+
+impl<S> AccountSync<S>
+where
+  S: StateStore,
+{
+  /// The key under which the checkpoint is persisted.
+  pub const KEY: &'static str = "account-sync";
+
+  /// Create a new `AccountSync` for the given activity types,
+  /// restoring any checkpoint persisted in the given store.
+  pub async fn new<I>(store: S, types: I) -> Result<Self, SyncError>
+  where
+    I: IntoIterator<Item = ActivityType>,
+  {
+    let state = store.get(Self::KEY).await.map_err(SyncError::Store)?;
+    let checkpoint = match &state {
+      Some(state) => from_json(state).map_err(SyncError::State)?,
+      None => Checkpoint::default(),
+    };
+
+    Ok(Self {
+      store,
+      types: types.into_iter().collect(),
+      checkpoint,
+      state,
+    })
+  }
+
+  /// Retrieve the current checkpoint.
+  #[inline]
+  pub fn checkpoint(&self) -> &Checkpoint {
+    &self.checkpoint
+  }
+
+  /// Retrieve all account activities that occurred since the last
+  /// sync.
+  async fn sync_activities(
+    &self,
+    client: &Client,
+  ) -> Result<(Vec<Activity>, CursorState), SyncError> {
+    let types = self.types.iter().copied();
+    let state = self.checkpoint.activities.clone();
+    let mut cursor = ActivityCursor::resume(types, state);
+
+    let activities = cursor.sync(client).await.map_err(SyncError::Activities)?;
+    Ok((activities, cursor.state().clone()))
+  }
+
+  /// Retrieve all orders that may have changed since the last sync.
+  ///
+  /// These are orders submitted since then as well as orders that
+  /// were open at the time, as only those can have seen updates.
+  async fn sync_orders(&self, client: &Client) -> Result<Vec<Order>, SyncError> {
+    let request = OrdersReq {
+      status: orders::Status::Open,
+      limit: Some(ORDERS_PAGE_SIZE),
+      ..Default::default()
+    };
+    let mut orders = client
+      .issue_paged::<orders::Get>(request)
+      .try_collect::<Vec<_>>()
+      .await
+      .map_err(SyncError::Orders)?;
+
+    // Orders that were open but no longer are have to be retrieved
+    // individually to learn about their final state.
+    for id in &self.checkpoint.open_orders {
+      if orders.iter().any(|order| order.id == *id) {
+        continue
+      }
+
+      match client.issue::<order::Get>(id).await {
+        Ok(order) => orders.push(order),
+        Err(RequestError::Endpoint(order::GetError::NotFound(_))) => (),
+        Err(err) => return Err(SyncError::Order(err)),
+      }
+    }
+
+    // Orders submitted since the last sync are listed last, so that
+    // none submitted in the meantime can slip through. The bound is
+    // exclusive on the server side, but more orders may have been
+    // submitted at the very time of the last one seen, so we widen it
+    // and filter out the ones seen already when merging.
+    let request = OrdersReq {
+      status: orders::Status::All,
+      limit: Some(ORDERS_PAGE_SIZE),
+      after: self
+        .checkpoint
+        .order_submissions
+        .time
+        .map(|time| time - Duration::microseconds(1)),
+      direction: Direction::Ascending,
+      ..Default::default()
+    };
+    let submitted = client
+      .issue_paged::<orders::Get>(request)
+      .try_collect::<Vec<_>>()
+      .await
+      .map_err(SyncError::Orders)?;
+    let () = orders.extend(submitted);
+    Ok(orders)
+  }
+
+  /// Retrieve all changes to the account since the last sync and
+  /// persist the updated checkpoint.
+  pub async fn sync(&mut self, client: &Client) -> Result<Delta, SyncError> {
+    let (activities, cursor) = self.sync_activities(client).await?;
+    let orders = self.sync_orders(client).await?;
+
+    let mut checkpoint = self.checkpoint.clone();
+    let orders = checkpoint.merge(orders);
+    checkpoint.activities = cursor;
+
+    let state = to_json(&checkpoint).map_err(SyncError::State)?;
+    let swapped = self
+      .store
+      .compare_and_swap(Self::KEY, self.state.as_deref(), &state)
+      .await
+      .map_err(SyncError::Store)?;
+    if !swapped {
+      return Err(SyncError::Conflict)
+    }
+
+    self.checkpoint = checkpoint;
+    self.state = Some(state);
+
+    Ok(Delta { activities, orders })
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use serde_json::json;
+  use serde_json::Value;
+
+  use test_log::test;
+
+  use crate::api::v2::order::Status;
+  use crate::state::MemoryStore;
+  use crate::test_util;
+  use crate::test_util::MockServer;
+  use crate::test_util::Response;
+
+
+  /// Create the JSON representation of an order with the given ID
+  /// suffix, status, and update time.
+  fn order_json(id: u8, status: &str, updated_at: &str) -> Value {
+    test_util::order_json(json!({
+      "id": format!("904837e3-3b76-47ec-b432-046db62157{:02}", id),
+      "updated_at": updated_at,
+      "status": status,
+    }))
+  }
+
+  /// Create an order with the given ID suffix, status, and update
+  /// time.
+  fn order(id: u8, status: &str, updated_at: &str) -> Order {
+    test_util::order(order_json(id, status, updated_at))
+  }
+
+
+  /// Check that merging orders reports only those updated since the
+  /// last sync and keeps track of open ones.
+  #[test]
+  fn merge_updated_orders() {
+    let mut checkpoint = Checkpoint::default();
+    let orders = vec![
+      order(2, "filled", "2022-01-03T10:00:00Z"),
+      order(1, "new", "2022-01-02T10:00:00Z"),
+      order(1, "new", "2022-01-02T10:00:00Z"),
+    ];
+    let updated = checkpoint.merge(orders);
+    assert_eq!(updated.len(), 2);
+    assert_eq!(updated[0].status, Status::New);
+    assert_eq!(updated[1].status, Status::Filled);
+    assert_eq!(checkpoint.open_orders, vec![updated[0].id]);
+
+    let last = DateTime::from_str("2022-01-03T10:00:00Z").unwrap();
+    assert_eq!(checkpoint.order_updates.time, Some(last));
+    assert_eq!(checkpoint.order_updates.ids, vec![updated[1].id]);
+
+    let orders = vec![
+      order(1, "canceled", "2022-01-04T10:00:00Z"),
+      order(2, "filled", "2022-01-03T10:00:00Z"),
+    ];
+    let updated = checkpoint.merge(orders);
+    assert_eq!(updated.len(), 1);
+    assert_eq!(updated[0].status, Status::Canceled);
+    assert_eq!(checkpoint.open_orders, Vec::new());
+  }
+
+  /// Check that orders submitted at the same time as the most recent
+  /// one seen are reported, even if updated before the most recent
+  /// update seen.
+  #[test]
+  fn merge_submitted_orders() {
+    let mut checkpoint = Checkpoint::default();
+    let orders = vec![order(1, "filled", "2022-01-03T10:00:00Z")];
+    let updated = checkpoint.merge(orders);
+    assert_eq!(updated.len(), 1);
+
+    let orders = vec![
+      order(1, "filled", "2022-01-03T10:00:00Z"),
+      order(2, "new", "2022-01-02T10:00:00Z"),
+    ];
+    let updated = checkpoint.merge(orders);
+    assert_eq!(updated.len(), 1);
+    assert_eq!(updated[0].status, Status::New);
+    assert_eq!(checkpoint.order_submissions.ids.len(), 2);
+  }
+
+  /// Check that an `AccountSync` restores a persisted checkpoint.
+  #[test(tokio::test)]
+  async fn restore_checkpoint() {
+    let store = MemoryStore::default();
+    let sync = AccountSync::new(&store, []).await.unwrap();
+    assert_eq!(sync.checkpoint(), &Checkpoint::default());
+
+    let checkpoint = Checkpoint {
+      activities: CursorState {
+        last_id: Some("20220103::abc".to_string()),
+        ..Default::default()
+      },
+      order_updates: Watermark {
+        time: Some(DateTime::from_str("2022-01-03T10:00:00Z").unwrap()),
+        ids: Vec::new(),
+      },
+      ..Default::default()
+    };
+    let state = to_json(&checkpoint).unwrap();
+    let () = store
+      .put(AccountSync::<&MemoryStore>::KEY, &state)
+      .await
+      .unwrap();
+
+    let sync = AccountSync::new(&store, [ActivityType::Fill])
+      .await
+      .unwrap();
+    assert_eq!(sync.checkpoint(), &checkpoint);
+
+    let () = store
+      .put(AccountSync::<&MemoryStore>::KEY, b"{")
+      .await
+      .unwrap();
+    let err = AccountSync::new(&store, []).await.unwrap_err();
+    assert!(matches!(err, SyncError::State(..)));
+  }
+
+  /// Check that an `AccountSync` retrieves changed activities and
+  /// orders and picks up where it left off.
+  #[test(tokio::test)]
+  async fn sync_incrementally() {
+    let activities = json!([{
+      "activity_type": "DIV",
+      "id": "20220103::a",
+      "date": "2022-01-03",
+      "net_amount": "1.02",
+      "symbol": "T",
+      "per_share_amount": "0.51"
+    }]);
+    let open = json!([order_json(1, "new", "2022-01-03T10:00:00Z")]);
+    let submitted = json!([
+      order_json(1, "new", "2022-01-03T10:00:00Z"),
+      order_json(2, "filled", "2022-01-03T11:00:00Z"),
+    ]);
+    let canceled = order_json(1, "canceled", "2022-01-03T12:00:00Z");
+    // An order submitted at the same time as the others but not
+    // reported earlier.
+    let submitted_late = json!([
+      canceled,
+      order_json(2, "filled", "2022-01-03T11:00:00Z"),
+      order_json(3, "new", "2022-01-02T10:00:00Z"),
+    ]);
+
+    let server = MockServer::serve([
+      Response::json(activities.to_string()),
+      Response::json(open.to_string()),
+      Response::json(submitted.to_string()),
+      Response::json("[]"),
+      Response::json("[]"),
+      Response::json(canceled.to_string()),
+      Response::json(submitted_late.to_string()),
+    ]);
+    let client = server.client();
+    let store = MemoryStore::default();
+
+    let mut sync = AccountSync::new(&store, []).await.unwrap();
+    let delta = sync.sync(&client).await.unwrap();
+    assert_eq!(delta.activities.len(), 1);
+    assert_eq!(delta.orders.len(), 2);
+    assert_eq!(delta.orders[0].status, Status::New);
+    assert_eq!(delta.orders[1].status, Status::Filled);
+
+    // A new `AccountSync` resumes from the persisted checkpoint.
+    let mut sync = AccountSync::new(&store, []).await.unwrap();
+    let delta = sync.sync(&client).await.unwrap();
+    assert_eq!(delta.activities, Vec::new());
+    assert_eq!(delta.orders.len(), 2);
+    assert_eq!(delta.orders[0].status, Status::New);
+    assert_eq!(delta.orders[1].status, Status::Canceled);
+    assert_eq!(sync.checkpoint().open_orders, vec![delta.orders[0].id]);
+
+    let requests = server.join();
+    let lines = requests
+      .iter()
+      .map(|request| request.line.as_str())
+      .collect::<Vec<_>>();
+    let activities = "GET /v2/account/activities";
+    assert!(lines[0].starts_with(activities), "{}", lines[0]);
+    assert!(lines[1].contains("status=open"), "{}", lines[1]);
+    assert!(lines[2].contains("status=all"), "{}", lines[2]);
+    assert!(!lines[2].contains("after="), "{}", lines[2]);
+    assert!(lines[3].starts_with(activities), "{}", lines[3]);
+    assert!(lines[4].contains("status=open"), "{}", lines[4]);
+    assert!(
+      lines[5].starts_with("GET /v2/orders/904837e33b7647ecb432046db6215701 "),
+      "{}",
+      lines[5]
+    );
+    assert!(
+      lines[6].contains("after=2018-10-05T05%3A48%3A58.999999Z"),
+      "{}",
+      lines[6]
+    );
+  }
+}
@@ -0,0 +1,203 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use num_decimal::Num;
+
+use crate::api::v2::asset;
+use crate::api::v2::order::Amount;
+use crate::api::v2::order::Class;
+use crate::api::v2::order::OrderReq;
+use crate::api::v2::order::OrderReqInit;
+use crate::api::v2::order::Side;
+use crate::api::v2::order::StopLoss;
+use crate::api::v2::order::TakeProfit;
+use crate::api::v2::order::TimeInForce;
+use crate::api::v2::order::Type;
+
+
+/// The number of basis points that make up a whole.
+const BPS_PER_WHOLE: i32 = 10000;
+
+
+/// Offset `price` by the given number of basis points, in the direction
+/// indicated by `up`.
+fn offset(price: &Num, bps: &Num, up: bool) -> Num {
+  let factor = if up {
+    Num::from(BPS_PER_WHOLE) + bps
+  } else {
+    Num::from(BPS_PER_WHOLE) - bps
+  };
+  price * factor / BPS_PER_WHOLE
+}
+
+
+/// A reusable set of order properties, capturing the conventions of a
+/// strategy in a single place.
+///
+/// A template is instantiated into an [`OrderReq`] for a concrete
+/// symbol, side, and amount. The exit legs of bracket-style orders are
+/// expressed as offsets, in basis points, relative to a reference
+/// price provided upon instantiation, so that the same template can be
+/// used for any asset.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OrderTemplate {
+  /// The order class.
+  pub class: Class,
+  /// The type of the order.
+  ///
+  /// Trailing stop orders are not supported by templates and are
+  /// instantiated without a trail.
+  pub type_: Type,
+  /// How long the order will be valid.
+  pub time_in_force: TimeInForce,
+  /// Whether or not the order is eligible to execute during
+  /// pre-market/after hours.
+  pub extended_hours: bool,
+  /// The offset of the take profit limit price from the reference
+  /// price, in basis points, in the direction of profit.
+  pub take_profit_bps: Option<Num>,
+  /// The offset of the stop loss stop price from the reference price,
+  /// in basis points, in the direction of loss.
+  pub stop_loss_bps: Option<Num>,
+  /// The offset of the stop loss limit price from the reference price,
+  /// in basis points, in the direction of loss. Only used in
+  /// conjunction with `stop_loss_bps`.
+  pub stop_loss_limit_bps: Option<Num>,
+  /// The class of the assets the template is used for, determining the
+  /// tick size that prices are rounded to.
+  pub asset_class: asset::Class,
+  #[doc(hidden)]
+  pub _non_exhaustive: (),
+}
+
+impl OrderTemplate {
+  /// Create an order request for the given symbol, side, and amount
+  /// based on this template.
+  ///
+  /// `price` is the reference price of the order. It is used as the
+  /// limit price of limit orders, the stop price of stop orders, and
+  /// both of stop limit orders. The prices of any exit legs are
+  /// derived from it as well. All prices are rounded to valid ticks.
+  pub fn instantiate<S>(&self, symbol: S, side: Side, amount: Amount, price: &Num) -> OrderReq
+  where
+    S: Into<String>,
+  {
+    let (limit_price, stop_price) = match self.type_ {
+      Type::Market | Type::TrailingStop => (None, None),
+      Type::Limit => (Some(price.clone()), None),
+      Type::Stop => (None, Some(price.clone())),
+      Type::StopLimit => (Some(price.clone()), Some(price.clone())),
+    };
+
+    // A buy order profits from rising prices, a sell order from
+    // falling ones.
+    let profit_up = side == Side::Buy;
+    let take_profit = self
+      .take_profit_bps
+      .as_ref()
+      .map(|bps| TakeProfit::Limit(offset(price, bps, profit_up)));
+    let stop_loss = self.stop_loss_bps.as_ref().map(|bps| {
+      let stop = offset(price, bps, !profit_up);
+      match &self.stop_loss_limit_bps {
+        Some(limit) => StopLoss::StopLimit(stop, offset(price, limit, !profit_up)),
+        None => StopLoss::Stop(stop),
+      }
+    });
+
+    let mut request = OrderReqInit {
+      class: self.class,
+      type_: self.type_,
+      time_in_force: self.time_in_force,
+      limit_price,
+      stop_price,
+      take_profit,
+      stop_loss,
+      extended_hours: self.extended_hours,
+      ..Default::default()
+    }
+    .init(symbol, side, amount);
+
+    let () = request.round_to_tick(self.asset_class);
+    request
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Create a template for bracket limit orders with the given exit
+  /// offsets.
+  fn bracket() -> OrderTemplate {
+    OrderTemplate {
+      class: Class::Bracket,
+      type_: Type::Limit,
+      time_in_force: TimeInForce::UntilCanceled,
+      take_profit_bps: Some(Num::from(200)),
+      stop_loss_bps: Some(Num::from(100)),
+      stop_loss_limit_bps: Some(Num::from(150)),
+      ..Default::default()
+    }
+  }
+
+
+  /// Check that we instantiate a bracket template for a buy order as
+  /// expected.
+  #[test]
+  fn instantiate_buy_bracket() {
+    let request = bracket().instantiate("SPY", Side::Buy, Amount::quantity(10), &Num::from(100));
+    assert_eq!(request.symbol, asset::Symbol::Sym("SPY".to_string()));
+    assert_eq!(request.class, Class::Bracket);
+    assert_eq!(request.type_, Type::Limit);
+    assert_eq!(request.time_in_force, TimeInForce::UntilCanceled);
+    assert_eq!(request.limit_price, Some(Num::from(100)));
+    assert_eq!(request.stop_price, None);
+    assert_eq!(request.take_profit, Some(TakeProfit::Limit(Num::from(102))));
+    assert_eq!(
+      request.stop_loss,
+      Some(StopLoss::StopLimit(Num::from(99), Num::new(985, 10)))
+    );
+  }
+
+  /// Check that exit legs are mirrored for sell orders and that prices
+  /// get rounded to valid ticks.
+  #[test]
+  fn instantiate_sell_bracket() {
+    let template = OrderTemplate {
+      stop_loss_limit_bps: None,
+      ..bracket()
+    };
+    let price = Num::new(12345, 100);
+    let request = template.instantiate("SPY", Side::Sell, Amount::quantity(10), &price);
+    assert_eq!(request.limit_price, Some(price));
+    // 123.45 * 0.98 = 120.981
+    assert_eq!(
+      request.take_profit,
+      Some(TakeProfit::Limit(Num::new(12098, 100)))
+    );
+    // 123.45 * 1.01 = 124.6845
+    assert_eq!(
+      request.stop_loss,
+      Some(StopLoss::Stop(Num::new(12468, 100)))
+    );
+  }
+
+  /// Check that a simple market template does not set any prices.
+  #[test]
+  fn instantiate_market() {
+    let template = OrderTemplate {
+      extended_hours: true,
+      ..Default::default()
+    };
+    let request = template.instantiate("AAPL", Side::Buy, Amount::notional(500), &Num::from(150));
+    assert_eq!(request.class, Class::Simple);
+    assert_eq!(request.type_, Type::Market);
+    assert!(request.extended_hours);
+    assert_eq!(request.limit_price, None);
+    assert_eq!(request.stop_price, None);
+    assert_eq!(request.take_profit, None);
+    assert_eq!(request.stop_loss, None);
+  }
+}
@@ -0,0 +1,650 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::io::Error as IoError;
+
+use futures::pin_mut;
+use futures::Stream;
+use futures::StreamExt as _;
+
+use num_decimal::Num;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::from_slice as from_json;
+use serde_json::to_vec as to_json;
+use serde_json::Error as JsonError;
+
+use thiserror::Error;
+
+use tracing::warn;
+
+use websocket_util::tungstenite::Error as WebSocketError;
+
+use crate::api::v2::asset;
+use crate::api::v2::order;
+use crate::api::v2::order::Amount;
+use crate::api::v2::order::ChangeReqInit;
+use crate::api::v2::order::Order;
+use crate::api::v2::order::OrderReqInit;
+use crate::api::v2::order::Side;
+use crate::api::v2::order::Status;
+use crate::api::v2::order::TimeInForce;
+use crate::api::v2::order::Type;
+use crate::data::v2::stream::Data;
+use crate::money;
+use crate::state::StateStore;
+use crate::Client;
+use crate::RequestError;
+
+
+/// The distance at which a trailing stop follows the price.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Trail {
+  /// A fixed dollar amount.
+  Price(Num),
+  /// A percentage of the price.
+  Percent(Num),
+}
+
+impl Trail {
+  /// Compute the stop price of a stop order on the given side, trailing
+  /// the given mark.
+  ///
+  /// The stop price is rounded to a valid tick for US equities.
+  pub fn stop_price(&self, side: Side, mark: &Num) -> Num {
+    let distance = match self {
+      Self::Price(price) => price.clone(),
+      Self::Percent(percent) => mark * percent / 100,
+    };
+    let stop = match side {
+      Side::Buy => mark + distance,
+      Side::Sell => mark - distance,
+    };
+    money::round_to_tick(&stop, asset::Class::UsEquity)
+  }
+}
+
+
+/// A client-side trailing stop, backed by a resting stop order.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TrailingStop {
+  /// The ID of the stop order currently backing the trailing stop.
+  pub order_id: order::Id,
+  /// The symbol of the asset the stop is for.
+  pub symbol: String,
+  /// The side of the stop order, i.e., `Sell` for protecting a long
+  /// position and `Buy` for protecting a short one.
+  pub side: Side,
+  /// The quantity of the stop order.
+  pub quantity: Num,
+  /// How long the stop order is valid.
+  pub time_in_force: TimeInForce,
+  /// The distance at which the stop follows the price.
+  pub trail: Trail,
+  /// The most favorable price seen so far, i.e., the highest one for
+  /// sell stops and the lowest one for buy stops.
+  pub mark: Num,
+  /// The stop price of the stop order.
+  pub stop_price: Num,
+}
+
+impl TrailingStop {
+  /// Evaluate a new price without changing the trailing stop.
+  ///
+  /// If the price is more favorable than the mark, the new mark is
+  /// reported, along with the new stop price if it moved by at least a
+  /// tick.
+  fn evaluate(&self, price: &Num) -> Option<(Num, Option<Num>)> {
+    let improved = match self.side {
+      Side::Buy => price < &self.mark,
+      Side::Sell => price > &self.mark,
+    };
+    if !improved {
+      return None
+    }
+
+    let stop_price = self.trail.stop_price(self.side, price);
+    let moved = match self.side {
+      Side::Buy => stop_price < self.stop_price,
+      Side::Sell => stop_price > self.stop_price,
+    };
+    Some((price.clone(), moved.then_some(stop_price)))
+  }
+
+  /// Update the trailing stop with a new price.
+  ///
+  /// If the price moved the stop price by at least a tick, the new stop
+  /// price the stop order should be amended to is reported. Note that
+  /// `stop_price` is not changed by this method.
+  pub fn update(&mut self, price: &Num) -> Option<Num> {
+    let (mark, stop_price) = self.evaluate(price)?;
+    self.mark = mark;
+    stop_price
+  }
+}
+
+
+/// An error as reported by a [`Manager`].
+#[derive(Debug, Error)]
+pub enum TrailingError {
+  /// The state could not be loaded or persisted.
+  #[error("failed to access persisted state")]
+  Store(#[source] IoError),
+  /// The persisted state could not be parsed.
+  #[error("persisted state is invalid")]
+  State(#[source] JsonError),
+  /// An order can not back a trailing stop.
+  #[error("order {} is not an open stop order for a quantity", .0.as_simple())]
+  InvalidOrder(order::Id),
+  /// An order could not be retrieved.
+  #[error("failed to retrieve order")]
+  Get(#[source] RequestError<order::GetError>),
+  /// A stop order could not be submitted.
+  #[error("failed to submit stop order")]
+  Post(#[source] RequestError<order::PostError>),
+  /// A stop order could not be amended.
+  #[error("failed to amend stop order")]
+  Patch(#[source] RequestError<order::PatchError>),
+  /// A stop order could not be canceled.
+  #[error("failed to cancel stop order")]
+  Delete(#[source] RequestError<order::DeleteError>),
+  /// Market data could not be received.
+  #[error("failed to receive market data")]
+  Stream(#[source] WebSocketError),
+}
+
+
+/// Retrieve the order that currently represents the one with the given
+/// ID, following replacements.
+///
+/// `None` is returned if the order is no longer open.
+async fn resolve(client: &Client, mut id: order::Id) -> Result<Option<Order>, TrailingError> {
+  loop {
+    let order = match client.issue::<order::Get>(&id).await {
+      Ok(order) => order,
+      Err(RequestError::Endpoint(order::GetError::NotFound(_))) => return Ok(None),
+      Err(err) => return Err(TrailingError::Get(err)),
+    };
+
+    match (order.status, order.replaced_by) {
+      (Status::Replaced, Some(replacement)) => id = replacement,
+      (status, _) if status.is_terminal() => return Ok(None),
+      _ => return Ok(Some(order)),
+    }
+  }
+}
+
+
+/// A manager for client-side trailing stops.
+///
+/// Each trailing stop is backed by a resting stop order, which the
+/// manager amends whenever the price moves in a favorable direction.
+/// That way, stops are enforced server-side even while the manager is
+/// not running, and trailing stops can be used where server-side ones
+/// are not available or suitable. This functionality is meant for US
+/// equities.
+///
+/// The manager persists its state in a [`StateStore`] whenever it
+/// changes, under the [`Manager::KEY`] key.
+/// After a restart, [`Manager::recover`] reconciles that state with the
+/// actual orders.
+#[derive(Debug)]
+pub struct Manager<S> {
+  /// The store used for persisting state.
+  store: S,
+  /// The trailing stops being managed.
+  stops: Vec<TrailingStop>,
+}
+
+impl<S> Manager<S>
+where
+  S: StateStore,
+{
+  /// The key under which the state is persisted.
+  pub const KEY: &'static str = "trailing-stops";
+
+  /// Create a new `Manager`, restoring any state persisted in the
+  /// given store.
+  pub async fn new(store: S) -> Result<Self, TrailingError> {
+    let state = store.get(Self::KEY).await.map_err(TrailingError::Store)?;
+    let stops = match state {
+      Some(state) => from_json(&state).map_err(TrailingError::State)?,
+      None => Vec::new(),
+    };
+
+    Ok(Self { store, stops })
+  }
+
+  /// Retrieve the trailing stops being managed.
+  #[inline]
+  pub fn stops(&self) -> &[TrailingStop] {
+    &self.stops
+  }
+
+  /// Persist the current state.
+  async fn persist(&self) -> Result<(), TrailingError> {
+    let state = to_json(&self.stops).map_err(TrailingError::State)?;
+    let result = self.store.put(Self::KEY, &state).await;
+    result.map_err(TrailingError::Store)
+  }
+
+  /// Submit a stop order for the given symbol and manage it as a
+  /// trailing stop, starting out at the given mark price.
+  pub async fn place(
+    &mut self,
+    client: &Client,
+    symbol: &str,
+    side: Side,
+    quantity: Num,
+    trail: Trail,
+    mark: Num,
+  ) -> Result<&TrailingStop, TrailingError> {
+    let stop_price = trail.stop_price(side, &mark);
+    let request = OrderReqInit {
+      type_: Type::Stop,
+      time_in_force: TimeInForce::UntilCanceled,
+      stop_price: Some(stop_price),
+      ..Default::default()
+    }
+    .init(symbol, side, Amount::quantity(quantity));
+
+    let order = client
+      .issue::<order::Post>(&request)
+      .await
+      .map_err(TrailingError::Post)?;
+    let () = self.track(&order, trail, mark).await?;
+    Ok(self.stops.last().unwrap())
+  }
+
+  /// Manage an existing stop order as a trailing stop, starting out at
+  /// the given mark price.
+  pub async fn track(
+    &mut self,
+    order: &Order,
+    trail: Trail,
+    mark: Num,
+  ) -> Result<(), TrailingError> {
+    let (quantity, stop_price) = match (&order.amount, &order.stop_price) {
+      (Amount::Quantity { quantity }, Some(stop_price))
+        if order.type_ == Type::Stop && !order.status.is_terminal() =>
+      {
+        (quantity.clone(), stop_price.clone())
+      },
+      _ => return Err(TrailingError::InvalidOrder(order.id)),
+    };
+
+    self.stops.push(TrailingStop {
+      order_id: order.id,
+      symbol: order.symbol.clone(),
+      side: order.side,
+      quantity,
+      time_in_force: order.time_in_force,
+      trail,
+      mark,
+      stop_price,
+    });
+    self.persist().await
+  }
+
+  /// Cancel the stop order backing a trailing stop and stop managing
+  /// it.
+  pub async fn cancel(&mut self, client: &Client, id: order::Id) -> Result<(), TrailingError> {
+    if let Some(index) = self.stops.iter().position(|stop| stop.order_id == id) {
+      let () = client
+        .issue::<order::Delete>(&id)
+        .await
+        .map_err(TrailingError::Delete)?;
+      let _ = self.stops.remove(index);
+      let () = self.persist().await?;
+    }
+    Ok(())
+  }
+
+  /// Reconcile the managed trailing stops with the actual orders, e.g.,
+  /// after a restart.
+  ///
+  /// Trailing stops whose orders got filled, canceled, or otherwise
+  /// closed are no longer managed. Replacements of orders, e.g.,
+  /// because the program terminated after amending an order but before
+  /// persisting the change, are followed.
+  pub async fn recover(&mut self, client: &Client) -> Result<(), TrailingError> {
+    // The stops are only replaced once all got resolved, so that they
+    // are retained should we fail midway.
+    let mut stops = Vec::with_capacity(self.stops.len());
+    for stop in &self.stops {
+      if let Some(order) = resolve(client, stop.order_id).await? {
+        let mut stop = stop.clone();
+        stop.order_id = order.id;
+        if let Some(stop_price) = order.stop_price {
+          stop.stop_price = stop_price;
+        }
+        stops.push(stop)
+      }
+    }
+
+    self.stops = stops;
+    self.persist().await
+  }
+
+  /// Update the trailing stops for the given symbol with a new price,
+  /// amending their stop orders as necessary.
+  pub async fn on_price(
+    &mut self,
+    client: &Client,
+    symbol: &str,
+    price: &Num,
+  ) -> Result<(), TrailingError> {
+    let mut changed = false;
+    let mut index = 0;
+
+    while index < self.stops.len() {
+      let stop = &mut self.stops[index];
+      let update = if stop.symbol == symbol {
+        stop.evaluate(price)
+      } else {
+        None
+      };
+      // The mark is only advanced along with the stop price once the
+      // order got amended, so that a failed amendment is retried on the
+      // next price at the same level.
+      let (mark, stop_price) = match update {
+        Some((mark, Some(stop_price))) => (mark, stop_price),
+        Some((mark, None)) => {
+          stop.mark = mark;
+          index += 1;
+          continue
+        },
+        None => {
+          index += 1;
+          continue
+        },
+      };
+
+      let request = ChangeReqInit {
+        quantity: stop.quantity.clone(),
+        time_in_force: stop.time_in_force,
+        stop_price: Some(stop_price.clone()),
+        ..Default::default()
+      }
+      .init();
+
+      match client
+        .issue::<order::Patch>(&(stop.order_id, request))
+        .await
+      {
+        Ok(order) => {
+          stop.order_id = order.id;
+          stop.mark = mark;
+          stop.stop_price = stop_price;
+          changed = true;
+        },
+        // The order may have been triggered or canceled in the
+        // meantime, in which case we stop managing it.
+        Err(RequestError::Endpoint(err)) => match resolve(client, stop.order_id).await? {
+          Some(_) => return Err(TrailingError::Patch(RequestError::Endpoint(err))),
+          None => {
+            let _ = self.stops.remove(index);
+            changed = true;
+            continue
+          },
+        },
+        Err(err) => return Err(TrailingError::Patch(err)),
+      }
+      index += 1;
+    }
+
+    if changed {
+      let () = self.persist().await?;
+    }
+    Ok(())
+  }
+
+  /// Update the trailing stops with trades received over the provided
+  /// realtime market data stream, until it is exhausted.
+  ///
+  /// Data failing to parse are logged and skipped. On error, the
+  /// manager's state remains consistent and this method can just be
+  /// invoked again, possibly after [`recover`][Manager::recover].
+  pub async fn run<T>(&mut self, client: &Client, stream: T) -> Result<(), TrailingError>
+  where
+    T: Stream<Item = Result<Result<Data, JsonError>, WebSocketError>>,
+  {
+    pin_mut!(stream);
+
+    while let Some(result) = stream.next().await {
+      match result.map_err(TrailingError::Stream)? {
+        Ok(Data::Trade(trade)) => {
+          let () = self
+            .on_price(client, &trade.symbol, &trade.trade_price)
+            .await?;
+        },
+        Ok(_) => (),
+        Err(err) => warn!("failed to parse market data: {}", err),
+      }
+    }
+    Ok(())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use test_log::test;
+
+  use uuid::Uuid;
+
+  use serde_json::json;
+  use serde_json::Value;
+
+  use crate::api::v2::simulator::Simulator;
+  use crate::live;
+  use crate::state::MemoryStore;
+  use crate::test_util::order;
+  use crate::test_util::order_json;
+  use crate::test_util::MockServer;
+  use crate::test_util::Response;
+
+
+  /// Create a `TrailingStop` for the given side and trail, starting out
+  /// at a mark of $100.
+  fn trailing_stop(side: Side, trail: Trail) -> TrailingStop {
+    let mark = Num::from(100);
+    TrailingStop {
+      order_id: order::Id(Uuid::nil()),
+      symbol: "SPY".to_string(),
+      side,
+      quantity: Num::from(1),
+      time_in_force: TimeInForce::UntilCanceled,
+      stop_price: trail.stop_price(side, &mark),
+      trail,
+      mark,
+    }
+  }
+
+
+  /// Check that sell stops follow rising prices.
+  #[test]
+  fn trail_sell_stop() {
+    let mut stop = trailing_stop(Side::Sell, Trail::Price(Num::from(1)));
+    assert_eq!(stop.stop_price, Num::from(99));
+
+    assert_eq!(stop.update(&Num::new(995, 10)), None);
+    assert_eq!(stop.update(&Num::from(101)), Some(Num::from(100)));
+    assert_eq!(stop.mark, Num::from(101));
+    // The stop price is only updated once the order got amended.
+    assert_eq!(stop.update(&Num::new(1005, 10)), None);
+    assert_eq!(
+      stop.update(&Num::new(10105, 100)),
+      Some(Num::new(10005, 100))
+    );
+  }
+
+  /// Check that buy stops follow falling prices.
+  #[test]
+  fn trail_buy_stop() {
+    let mut stop = trailing_stop(Side::Buy, Trail::Percent(Num::from(10)));
+    assert_eq!(stop.stop_price, Num::from(110));
+
+    assert_eq!(stop.update(&Num::from(101)), None);
+    assert_eq!(stop.update(&Num::from(90)), Some(Num::from(99)));
+    assert_eq!(stop.mark, Num::from(90));
+  }
+
+  /// Check that stop prices are rounded to a valid tick.
+  #[test]
+  fn round_stop_price() {
+    let trail = Trail::Percent(Num::from(3));
+    let stop_price = trail.stop_price(Side::Sell, &Num::new(3333, 100));
+    assert_eq!(stop_price, Num::new(3233, 100));
+  }
+
+  /// Check that a `Manager` restores its state from a `StateStore`.
+  #[test(tokio::test)]
+  async fn restore_state() {
+    let mut simulator = Simulator::new(1000);
+    let request = OrderReqInit {
+      type_: Type::Stop,
+      stop_price: Some(Num::from(90)),
+      ..Default::default()
+    }
+    .init("SPY", Side::Sell, Amount::quantity(2));
+    let order = simulator.submit(&request).unwrap();
+
+    let store = MemoryStore::default();
+    let mut manager = Manager::new(&store).await.unwrap();
+    let () = manager
+      .track(&order, Trail::Price(Num::from(10)), Num::from(100))
+      .await
+      .unwrap();
+    let stops = manager.stops().to_vec();
+    assert_eq!(stops.len(), 1);
+    assert_eq!(stops[0].order_id, order.id);
+    assert_eq!(stops[0].quantity, Num::from(2));
+
+    let manager = Manager::new(&store).await.unwrap();
+    assert_eq!(manager.stops(), stops);
+
+    let request = OrderReqInit::default().init("SPY", Side::Buy, Amount::quantity(1));
+    let order = simulator.submit(&request).unwrap();
+    let mut manager = Manager::new(&store).await.unwrap();
+    let err = manager
+      .track(&order, Trail::Price(Num::from(1)), Num::from(100))
+      .await
+      .unwrap_err();
+    assert!(matches!(err, TrailingError::InvalidOrder(id) if id == order.id));
+  }
+
+  /// Create the JSON representation of an open sell stop order with
+  /// the given ID suffix and stop price.
+  fn stop_order_json(id: u8, stop_price: u64) -> Value {
+    order_json(json!({
+      "id": format!("904837e3-3b76-47ec-b432-046db62157{:02}", id),
+      "type": "stop",
+      "side": "sell",
+      "status": "new",
+      "stop_price": stop_price.to_string(),
+    }))
+  }
+
+  /// Create a response that fails to parse, because of conflicting
+  /// content lengths.
+  fn invalid_response() -> Response {
+    Response::json("{}").header("content-length", "42")
+  }
+
+
+  /// Check that a failed amendment of a stop order leaves the trailing
+  /// stop unchanged, so that it is retried on the next price.
+  #[test(tokio::test)]
+  async fn retry_failed_amendment() {
+    let server = MockServer::serve([
+      invalid_response(),
+      Response::json(stop_order_json(2, 91).to_string()),
+    ]);
+    let client = server.client();
+    let store = MemoryStore::default();
+    let mut manager = Manager::new(&store).await.unwrap();
+    let order = order(stop_order_json(1, 90));
+    let () = manager
+      .track(&order, Trail::Price(Num::from(10)), Num::from(100))
+      .await
+      .unwrap();
+
+    let err = manager
+      .on_price(&client, "AAPL", &Num::from(101))
+      .await
+      .unwrap_err();
+    assert!(matches!(err, TrailingError::Patch(..)), "{:?}", err);
+    assert_eq!(manager.stops()[0].mark, Num::from(100));
+    assert_eq!(manager.stops()[0].stop_price, Num::from(90));
+
+    let () = manager
+      .on_price(&client, "AAPL", &Num::from(101))
+      .await
+      .unwrap();
+    assert_eq!(manager.stops()[0].mark, Num::from(101));
+    assert_eq!(manager.stops()[0].stop_price, Num::from(91));
+    assert_ne!(manager.stops()[0].order_id, order.id);
+  }
+
+  /// Check that a failed recovery retains all trailing stops.
+  #[test(tokio::test)]
+  async fn retain_stops_on_failed_recovery() {
+    let server = MockServer::serve([
+      Response::json(stop_order_json(1, 90).to_string()),
+      invalid_response(),
+    ]);
+    let client = server.client();
+    let store = MemoryStore::default();
+    let mut manager = Manager::new(&store).await.unwrap();
+    for id in [1, 2] {
+      let order = order(stop_order_json(id, 90));
+      let () = manager
+        .track(&order, Trail::Price(Num::from(10)), Num::from(100))
+        .await
+        .unwrap();
+    }
+    let stops = manager.stops().to_vec();
+
+    let err = manager.recover(&client).await.unwrap_err();
+    assert!(matches!(err, TrailingError::Get(..)), "{:?}", err);
+    assert_eq!(manager.stops(), stops);
+  }
+
+  /// Check that we can place a trailing stop and recover it.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn place_and_recover_stop() {
+    let client = live::client().unwrap();
+    let store = MemoryStore::default();
+    let mut manager = Manager::new(&store).await.unwrap();
+    // A buy stop far above the market price, which should not trigger.
+    let stop = manager
+      .place(
+        &client,
+        "SPY",
+        Side::Buy,
+        Num::from(1),
+        Trail::Percent(Num::from(50)),
+        Num::from(1000),
+      )
+      .await
+      .unwrap()
+      .clone();
+    assert_eq!(stop.stop_price, Num::from(1500));
+
+    let mut manager = Manager::new(&store).await.unwrap();
+    let () = manager.recover(&client).await.unwrap();
+    assert_eq!(manager.stops().to_vec(), vec![stop.clone()]);
+
+    let () = manager.cancel(&client, stop.order_id).await.unwrap();
+    assert!(manager.stops().is_empty());
+
+    let mut manager = Manager::new(&store).await.unwrap();
+    let () = manager.recover(&client).await.unwrap();
+    assert!(manager.stops().is_empty());
+  }
+}
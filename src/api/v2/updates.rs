@@ -33,9 +33,12 @@ use websocket_util::wrap::Wrapper;
 
 use crate::api::v2::order;
 use crate::api_info::ApiInfo;
+use crate::data::v2::stream::StreamError;
+use crate::metric;
 use crate::subscribable::Subscribable;
 use crate::websocket::connect;
 use crate::websocket::MessageResult;
+use crate::websocket::SharedSink;
 use crate::Error;
 
 
@@ -107,6 +110,40 @@ pub enum OrderStatus {
   Unknown,
 }
 
+impl OrderStatus {
+  /// Retrieve the order status corresponding to this event.
+  ///
+  /// Rejections of cancel or replace requests do not change the status
+  /// of the order and so `None` is returned for them (as well as for
+  /// unknown events).
+  pub fn status(self) -> Option<order::Status> {
+    match self {
+      Self::New => Some(order::Status::New),
+      Self::Replaced => Some(order::Status::Replaced),
+      Self::PartialFill => Some(order::Status::PartiallyFilled),
+      Self::Filled => Some(order::Status::Filled),
+      Self::DoneForDay => Some(order::Status::DoneForDay),
+      Self::Canceled => Some(order::Status::Canceled),
+      Self::Expired => Some(order::Status::Expired),
+      Self::PendingCancel => Some(order::Status::PendingCancel),
+      Self::Stopped => Some(order::Status::Stopped),
+      Self::Rejected => Some(order::Status::Rejected),
+      Self::Suspended => Some(order::Status::Suspended),
+      Self::PendingNew => Some(order::Status::PendingNew),
+      Self::PendingReplace => Some(order::Status::PendingReplace),
+      Self::Calculated => Some(order::Status::Calculated),
+      Self::ReplaceRejected | Self::CancelRejected | Self::Unknown => None,
+    }
+  }
+
+  /// Check whether the event moves the order into a terminal state,
+  /// i.e., no more changes will occur to the associated order.
+  #[inline]
+  pub fn is_terminal(self) -> bool {
+    self.status().map(order::Status::is_terminal).unwrap_or(false)
+  }
+}
+
 
 /// An enumeration of the different event streams.
 #[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
@@ -272,7 +309,14 @@ impl subscribe::Message for ParsedMessage {
 /// A subscription allowing certain control operations pertaining order
 /// update retrieval.
 #[derive(Debug)]
-pub struct Subscription<S>(subscribe::Subscription<S, ParsedMessage, wrap::Message>);
+pub struct Subscription<S> {
+  /// Our internally used subscription object for sending control
+  /// messages.
+  subscription: subscribe::Subscription<S, ParsedMessage, wrap::Message>,
+  /// A handle to the sink used by `subscription`, allowing us to close
+  /// the connection.
+  sink: S,
+}
 
 impl<S> Subscription<S>
 where
@@ -293,13 +337,13 @@ where
       Err(err) => return Ok(Err(Error::Json(err))),
     };
     let message = wrap::Message::Text(json);
-    let response = self.0.send(message).await?;
+    let response = self.subscription.send(message).await?;
 
     match response {
       Some(response) => match response {
         Ok(ControlMessage::AuthenticationMessage(authentication)) => {
           if authentication.status != AuthenticationStatus::Authorized {
-            return Ok(Err(Error::Str("authentication not successful".into())))
+            return Ok(Err(Error::Stream(StreamError::AuthFailed)))
           }
           Ok(Ok(()))
         },
@@ -323,7 +367,7 @@ where
       Err(err) => return Ok(Err(Error::Json(err))),
     };
     let message = wrap::Message::Text(json);
-    let response = self.0.send(message).await?;
+    let response = self.subscription.send(message).await?;
 
     match response {
       Some(response) => match response {
@@ -349,6 +393,19 @@ where
   }
 }
 
+impl<S> Subscription<SharedSink<S>>
+where
+  S: Sink<wrap::Message> + Unpin,
+{
+  /// Gracefully shut down the connection.
+  ///
+  /// A close frame is sent to the server. The associated stream will be
+  /// exhausted once the server acknowledged the close.
+  pub async fn close(&mut self) -> Result<(), S::Error> {
+    self.sink.close().await
+  }
+}
+
 
 type Stream = Map<Wrapper<WebSocketStream<MaybeTlsStream<TcpStream>>>, MapFn>;
 type MapFn = fn(Result<wrap::Message, WebSocketError>) -> ParsedMessage;
@@ -362,14 +419,17 @@ pub enum OrderUpdates {}
 #[async_trait]
 impl Subscribable for OrderUpdates {
   type Input = ApiInfo;
-  type Subscription = Subscription<SplitSink<Stream, wrap::Message>>;
+  type Subscription = Subscription<SharedSink<SplitSink<Stream, wrap::Message>>>;
   type Stream = Fuse<MessageStream<SplitStream<Stream>, ParsedMessage>>;
 
   async fn connect(api_info: &Self::Input) -> Result<(Self::Stream, Self::Subscription), Error> {
     fn map(result: Result<wrap::Message, WebSocketError>) -> ParsedMessage {
-      MessageResult::from(result.map(|message| match message {
-        wrap::Message::Text(string) => json_from_str::<OrderMessage>(&string),
-        wrap::Message::Binary(data) => json_from_slice::<OrderMessage>(&data),
+      MessageResult::from(result.map(|message| {
+        metric::stream_messages("trade_updates", "message", 1);
+        match message {
+          wrap::Message::Text(string) => json_from_str::<OrderMessage>(&string),
+          wrap::Message::Binary(data) => json_from_slice::<OrderMessage>(&data),
+        }
       }))
     }
 
@@ -382,10 +442,14 @@ impl Subscribable for OrderUpdates {
 
     let stream = connect(url).await?.map(map as MapFn);
     let (send, recv) = stream.split();
-    let (stream, subscription) = subscribe::subscribe(recv, send);
+    let send = SharedSink::new(send);
+    let (stream, subscription) = subscribe::subscribe(recv, send.clone());
     let mut stream = stream.fuse();
 
-    let mut subscription = Subscription(subscription);
+    let mut subscription = Subscription {
+      subscription,
+      sink: send,
+    };
     let authenticate = subscription.authenticate(key_id, secret).boxed().fuse();
     let () = subscribe::drive::<ParsedMessage, _, _>(authenticate, &mut stream)
       .await
@@ -415,6 +479,8 @@ impl Subscribable for OrderUpdates {
 mod tests {
   use super::*;
 
+  use std::time::Duration;
+
   use futures::channel::oneshot::channel;
   use futures::future::ok;
   use futures::future::ready;
@@ -425,6 +491,8 @@ mod tests {
 
   use test_log::test;
 
+  use tokio::time::timeout;
+
   use websocket_util::test::WebSocketStream;
   use websocket_util::tungstenite::error::ProtocolError;
   use websocket_util::tungstenite::Message;
@@ -432,6 +500,7 @@ mod tests {
   use crate::api::v2::order;
   use crate::api::v2::order_util::order_aapl;
   use crate::api::API_BASE_URL;
+  use crate::live;
   use crate::websocket::test::mock_stream;
   use crate::Client;
   use crate::Error;
@@ -497,12 +566,26 @@ mod tests {
     match message {
       OrderMessage::OrderUpdate(update) => {
         assert_eq!(update.event, OrderStatus::New);
+        assert_eq!(update.event.status(), Some(update.order.status));
         assert_eq!(update.order.side, order::Side::Buy);
       },
       _ => panic!("Decoded unexpected message variant: {:?}", message),
     }
   }
 
+  /// Check that we map order events to order statuses as expected.
+  #[test]
+  fn map_order_status() {
+    assert_eq!(
+      OrderStatus::PartialFill.status(),
+      Some(order::Status::PartiallyFilled)
+    );
+    assert_eq!(OrderStatus::CancelRejected.status(), None);
+    assert!(OrderStatus::Filled.is_terminal());
+    assert!(!OrderStatus::PartialFill.is_terminal());
+    assert!(!OrderStatus::ReplaceRejected.is_terminal());
+  }
+
   /// Verify that we can decode a authentication control message.
   #[test]
   fn decode_authentication() {
@@ -566,6 +649,27 @@ mod tests {
     }
   }
 
+  /// Check that we report rejected credentials as an authentication
+  /// failure.
+  #[test(tokio::test)]
+  async fn reject_credentials() {
+    async fn test(mut stream: WebSocketStream) -> Result<(), WebSocketError> {
+      assert_eq!(
+        stream.next().await.unwrap()?,
+        Message::Text(AUTH_REQ.to_string()),
+      );
+      let response =
+        r#"{"stream":"authorization","data":{"action":"authenticate","status":"unauthorized"}}"#;
+      stream.send(Message::Text(response.to_string())).await?;
+      // Wait for the client to hang up.
+      let _next = stream.next().await;
+      Ok(())
+    }
+
+    let err = mock_stream::<OrderUpdates, _, _>(test).await.unwrap_err();
+    assert!(err.is_auth_failure(), "{}", err);
+  }
+
   /// Test that we handle an early connection close during subscription
   /// correctly.
   #[test(tokio::test)]
@@ -728,16 +832,51 @@ mod tests {
       .unwrap();
   }
 
+  /// Check that we can gracefully close the order update stream.
+  #[test(tokio::test)]
+  async fn close_stream() {
+    async fn test(mut stream: WebSocketStream) -> Result<(), WebSocketError> {
+      // Authentication.
+      assert_eq!(
+        stream.next().await.unwrap()?,
+        Message::Text(AUTH_REQ.to_string()),
+      );
+      stream.send(Message::Text(AUTH_RESP.to_string())).await?;
+
+      // Subscription.
+      assert_eq!(
+        stream.next().await.unwrap()?,
+        Message::Text(STREAM_REQ.to_string()),
+      );
+      stream.send(Message::Text(STREAM_RESP.to_string())).await?;
+
+      assert!(matches!(stream.next().await.unwrap()?, Message::Close(..)));
+      // Drive the stream to complete the closing handshake.
+      assert!(stream.next().await.is_none());
+      Ok(())
+    }
+
+    let (stream, mut subscription) = mock_stream::<OrderUpdates, _, _>(test).await.unwrap();
+    let () = subscription.close().await.unwrap();
+
+    let result = timeout(
+      Duration::from_secs(5),
+      stream.map_err(Error::from).try_for_each(|_| ready(Ok(()))),
+    )
+    .await;
+    assert!(matches!(result, Ok(Ok(()))), "{:?}", result);
+  }
+
   /// Test the end-to-end workflow of streaming an order update for a
   /// newly created order.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn stream_order_events() {
     // TODO: There may be something amiss here. If we don't cancel the
     //       order we never get an event about a new order. That does
     //       not seem to be in our code, though, as the behavior is the
     //       same when streaming events using Alpaca's Python client.
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let (stream, _subscription) = client.subscribe::<OrderUpdates>().await.unwrap();
 
     let order = order_aapl(&client).await.unwrap();
@@ -769,6 +908,7 @@ mod tests {
   /// Test that we fail as expected when attempting to authenticate for
   /// order updates using invalid credentials.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn stream_with_invalid_credentials() {
     let api_info = ApiInfo::from_parts(API_BASE_URL, "invalid", "invalid-too").unwrap();
 
@@ -776,7 +916,7 @@ mod tests {
     let err = client.subscribe::<OrderUpdates>().await.unwrap_err();
 
     match err {
-      Error::Str(ref e) if e == "authentication not successful" => (),
+      Error::Stream(StreamError::AuthFailed) => (),
       e => panic!("received unexpected error: {}", e),
     }
   }
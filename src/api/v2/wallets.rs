@@ -0,0 +1,569 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::ops::Deref;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use http::Method;
+use http_endpoint::Bytes;
+
+use num_decimal::Num;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::from_slice as from_json;
+use serde_json::to_vec as to_json;
+use serde_urlencoded::to_string as to_query;
+
+use uuid::Uuid;
+
+use crate::api::v2::asset;
+use crate::util::datetime_from_str;
+use crate::util::optional_num_from_str_or_null;
+use crate::Str;
+
+
+/// An ID uniquely identifying a crypto transfer.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct TransferId(pub Uuid);
+
+impl Deref for TransferId {
+  type Target = Uuid;
+
+  #[inline]
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+
+/// An ID uniquely identifying a whitelisted address.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct WhitelistId(pub Uuid);
+
+impl Deref for WhitelistId {
+  type Target = Uuid;
+
+  #[inline]
+  fn deref(&self) -> &Self::Target {
+    &self.0
+  }
+}
+
+
+/// A crypto funding wallet.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Wallet {
+  /// The ID of the asset the wallet holds.
+  #[serde(rename = "asset_id")]
+  pub asset_id: asset::Id,
+  /// The wallet's address, to which funds can be deposited.
+  #[serde(rename = "address")]
+  pub address: String,
+  /// Timestamp this wallet was created at.
+  #[serde(rename = "created_at", deserialize_with = "datetime_from_str")]
+  pub created_at: DateTime<Utc>,
+}
+
+
+/// The direction of a crypto transfer.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+pub enum Direction {
+  /// A transfer into the account.
+  #[serde(rename = "INCOMING")]
+  Incoming,
+  /// A transfer out of the account.
+  #[serde(rename = "OUTGOING")]
+  Outgoing,
+}
+
+
+/// The status of a crypto transfer.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+pub enum TransferStatus {
+  /// The transfer is being processed.
+  #[serde(rename = "PROCESSING")]
+  Processing,
+  /// The transfer failed.
+  #[serde(rename = "FAILED")]
+  Failed,
+  /// The transfer completed successfully.
+  #[serde(rename = "COMPLETE")]
+  Complete,
+  /// Any other transfer status that we have not accounted for.
+  ///
+  /// Note that having any such status should be considered a bug.
+  #[serde(other)]
+  Unknown,
+}
+
+
+/// A transfer of crypto currency into or out of the account.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Transfer {
+  /// The transfer's ID.
+  #[serde(rename = "id")]
+  pub id: TransferId,
+  /// The hash of the on-chain transaction, if already available.
+  #[serde(rename = "tx_hash")]
+  pub tx_hash: Option<String>,
+  /// The direction of the transfer.
+  #[serde(rename = "direction")]
+  pub direction: Direction,
+  /// The transfer's status.
+  #[serde(rename = "status")]
+  pub status: TransferStatus,
+  /// The amount being transferred.
+  #[serde(rename = "amount")]
+  pub amount: Num,
+  /// The value of the transferred amount in USD.
+  #[serde(
+    rename = "usd_value",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
+  pub usd_value: Option<Num>,
+  /// The network fee charged for the transfer.
+  #[serde(
+    rename = "network_fee",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
+  pub network_fee: Option<Num>,
+  /// The fees charged by Alpaca for the transfer.
+  #[serde(
+    rename = "fees",
+    default,
+    deserialize_with = "optional_num_from_str_or_null"
+  )]
+  pub fees: Option<Num>,
+  /// The chain the transfer is happening on.
+  #[serde(rename = "chain")]
+  pub chain: String,
+  /// The symbol of the transferred asset.
+  #[serde(rename = "asset")]
+  pub asset: String,
+  /// The address funds are transferred from.
+  #[serde(rename = "from_address")]
+  pub from_address: Option<String>,
+  /// The address funds are transferred to.
+  #[serde(rename = "to_address")]
+  pub to_address: Option<String>,
+  /// Timestamp this transfer was created at.
+  #[serde(rename = "created_at", deserialize_with = "datetime_from_str")]
+  pub created_at: DateTime<Utc>,
+}
+
+
+/// The status of a whitelisted address.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+pub enum WhitelistStatus {
+  /// The address is pending approval.
+  #[serde(rename = "PENDING")]
+  Pending,
+  /// The address was approved and can be withdrawn to.
+  #[serde(rename = "APPROVED")]
+  Approved,
+  /// Any other status that we have not accounted for.
+  ///
+  /// Note that having any such status should be considered a bug.
+  #[serde(other)]
+  Unknown,
+}
+
+
+/// An address that funds may be withdrawn to.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct WhitelistedAddress {
+  /// The whitelisted address's ID.
+  #[serde(rename = "id")]
+  pub id: WhitelistId,
+  /// The chain the address belongs to.
+  #[serde(rename = "chain")]
+  pub chain: String,
+  /// The symbol of the asset the address is whitelisted for.
+  #[serde(rename = "asset")]
+  pub asset: String,
+  /// The address.
+  #[serde(rename = "address")]
+  pub address: String,
+  /// The address's whitelisting status.
+  #[serde(rename = "status")]
+  pub status: WhitelistStatus,
+  /// Timestamp this address was whitelisted at.
+  #[serde(rename = "created_at", deserialize_with = "datetime_from_str")]
+  pub created_at: DateTime<Utc>,
+}
+
+
+/// An estimate of the network fee for a transfer.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct FeeEstimate {
+  /// The estimated fee.
+  #[serde(rename = "fee")]
+  pub fee: Num,
+}
+
+
+/// A GET request to be made to the /v2/wallets endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct WalletReq {
+  /// The symbol of the asset to retrieve the wallet for, e.g., `BTC`.
+  #[serde(rename = "asset")]
+  pub asset: String,
+}
+
+
+/// A POST request to be made to the /v2/wallets/transfers endpoint,
+/// requesting a withdrawal.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TransferReq {
+  /// The amount to withdraw.
+  #[serde(rename = "amount")]
+  pub amount: Num,
+  /// The address to withdraw to. The address needs to be whitelisted.
+  #[serde(rename = "address")]
+  pub address: String,
+  /// The symbol of the asset to withdraw, e.g., `BTC`.
+  #[serde(rename = "asset")]
+  pub asset: String,
+}
+
+
+/// A POST request to be made to the /v2/wallets/whitelists endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct WhitelistReq {
+  /// The address to whitelist.
+  #[serde(rename = "address")]
+  pub address: String,
+  /// The symbol of the asset to whitelist the address for, e.g., `BTC`.
+  #[serde(rename = "asset")]
+  pub asset: String,
+}
+
+
+/// A GET request to be made to the /v2/wallets/fees/estimate endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FeeEstimateReq {
+  /// The symbol of the asset to transfer, e.g., `BTC`.
+  #[serde(rename = "asset")]
+  pub asset: String,
+  /// The address funds are transferred from.
+  #[serde(rename = "from_address")]
+  pub from_address: String,
+  /// The address funds are transferred to.
+  #[serde(rename = "to_address")]
+  pub to_address: String,
+  /// The amount to transfer.
+  #[serde(rename = "amount")]
+  pub amount: Num,
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the /v2/wallets endpoint.
+  pub Get(WalletReq),
+  Ok => Wallet, [
+    /// The wallet was retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetError, [
+    /// The provided asset was invalid or does not support transfers.
+    /* 422 */ UNPROCESSABLE_ENTITY => InvalidInput,
+  ]
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v2/wallets".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the /v2/wallets/transfers
+  /// endpoint.
+  pub ListTransfers(()),
+  Ok => Vec<Transfer>, [
+    /// The transfers were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => ListTransfersError, []
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v2/wallets/transfers".into()
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the
+  /// /v2/wallets/transfers/<transfer-id> endpoint.
+  pub GetTransfer(TransferId),
+  Ok => Transfer, [
+    /// The transfer was retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetTransferError, [
+    /// No transfer was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+  ]
+
+  fn path(input: &Self::Input) -> Str {
+    format!("/v2/wallets/transfers/{}", input.as_hyphenated()).into()
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a POST request to the /v2/wallets/transfers
+  /// endpoint.
+  pub PostTransfer(TransferReq),
+  Ok => Transfer, [
+    /// The withdrawal was requested successfully.
+    /* 200 */ OK,
+  ],
+  Err => PostTransferError, [
+    /// The account does not hold enough of the asset.
+    /* 403 */ FORBIDDEN => NotPermitted,
+    /// Some of the provided data was invalid, e.g., the address is not
+    /// whitelisted.
+    /* 422 */ UNPROCESSABLE_ENTITY => InvalidInput,
+  ]
+
+  #[inline]
+  fn method() -> Method {
+    Method::POST
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v2/wallets/transfers".into()
+  }
+
+  fn body(input: &Self::Input) -> Result<Option<Bytes>, Self::ConversionError> {
+    let json = to_json(input)?;
+    let bytes = Bytes::from(json);
+    Ok(Some(bytes))
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the /v2/wallets/whitelists
+  /// endpoint.
+  pub ListWhitelists(()),
+  Ok => Vec<WhitelistedAddress>, [
+    /// The whitelisted addresses were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => ListWhitelistsError, []
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v2/wallets/whitelists".into()
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a POST request to the /v2/wallets/whitelists
+  /// endpoint.
+  pub PostWhitelist(WhitelistReq),
+  Ok => WhitelistedAddress, [
+    /// The address was submitted for whitelisting successfully.
+    /* 200 */ OK,
+  ],
+  Err => PostWhitelistError, [
+    /// Some of the provided data was invalid.
+    /* 422 */ UNPROCESSABLE_ENTITY => InvalidInput,
+  ]
+
+  #[inline]
+  fn method() -> Method {
+    Method::POST
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v2/wallets/whitelists".into()
+  }
+
+  fn body(input: &Self::Input) -> Result<Option<Bytes>, Self::ConversionError> {
+    let json = to_json(input)?;
+    let bytes = Bytes::from(json);
+    Ok(Some(bytes))
+  }
+}
+
+
+EndpointNoParse! {
+  /// The representation of a DELETE request to the
+  /// /v2/wallets/whitelists/<whitelist-id> endpoint.
+  pub DeleteWhitelist(WhitelistId),
+  Ok => (), [
+    /// The whitelisted address was removed successfully.
+    /* 200 */ OK,
+    /// The whitelisted address was removed successfully.
+    /* 204 */ NO_CONTENT,
+  ],
+  Err => DeleteWhitelistError, [
+    /// No whitelisted address was found with the given ID.
+    /* 404 */ NOT_FOUND => NotFound,
+  ]
+
+  #[inline]
+  fn method() -> Method {
+    Method::DELETE
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    format!("/v2/wallets/whitelists/{}", input.as_hyphenated()).into()
+  }
+
+  #[inline]
+  fn parse(_body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
+    Ok(())
+  }
+
+  fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
+    from_json::<Self::ApiError>(body).map_err(|_| body.to_vec())
+  }
+}
+
+
+Endpoint! {
+  /// The representation of a GET request to the
+  /// /v2/wallets/fees/estimate endpoint.
+  pub GetFeeEstimate(FeeEstimateReq),
+  Ok => FeeEstimate, [
+    /// The fee was estimated successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetFeeEstimateError, [
+    /// Some of the provided data was invalid.
+    /* 422 */ UNPROCESSABLE_ENTITY => InvalidInput,
+  ]
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v2/wallets/fees/estimate".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use http_endpoint::Endpoint;
+
+  use test_log::test;
+
+  use crate::live;
+  use crate::RequestError;
+
+
+  /// Check that we can parse a reference transfer.
+  #[test]
+  fn parse_reference_transfer() {
+    let response = br#"{
+  "id": "6f5b7b3a-a0f3-4bd1-a5a4-3b9b2e8d5d02",
+  "tx_hash": null,
+  "direction": "OUTGOING",
+  "status": "PROCESSING",
+  "amount": "0.01",
+  "usd_value": "213.07",
+  "network_fee": "0.0001",
+  "fees": "0",
+  "chain": "BTC",
+  "asset": "BTC",
+  "from_address": null,
+  "to_address": "bc1qxy2kgdygjrsqtzq2n0yrf2493p83kkfjhx0wlh",
+  "created_at": "2022-10-06T17:31:54.727611Z"
+}"#;
+
+    let transfer = PostTransfer::parse(response).unwrap();
+    assert_eq!(transfer.tx_hash, None);
+    assert_eq!(transfer.direction, Direction::Outgoing);
+    assert_eq!(transfer.status, TransferStatus::Processing);
+    assert_eq!(transfer.amount, Num::new(1, 100));
+    assert_eq!(transfer.network_fee, Some(Num::new(1, 10000)));
+    assert_eq!(transfer.asset, "BTC");
+  }
+
+  /// Check that we can parse a reference whitelisted address.
+  #[test]
+  fn parse_reference_whitelisted_address() {
+    let response = br#"[{
+  "id": "1e9d5f07-4e3f-4e85-b2b2-6d6a3b5a2e1c",
+  "chain": "ETH",
+  "asset": "USDT",
+  "address": "0x71C7656EC7ab88b098defB751B7401B5f6d8976F",
+  "status": "PENDING",
+  "created_at": "2022-10-06T17:31:54.727611Z"
+}]"#;
+
+    let addresses = ListWhitelists::parse(response).unwrap();
+    assert_eq!(addresses.len(), 1);
+    assert_eq!(addresses[0].asset, "USDT");
+    assert_eq!(addresses[0].status, WhitelistStatus::Pending);
+  }
+
+  /// Check that we serialize a fee estimate request as expected.
+  #[test]
+  fn serialize_fee_estimate_request() {
+    let request = FeeEstimateReq {
+      asset: "ETH".to_string(),
+      from_address: "0xabc".to_string(),
+      to_address: "0xdef".to_string(),
+      amount: Num::new(1, 2),
+    };
+    let query = GetFeeEstimate::query(&request).unwrap().unwrap();
+    assert_eq!(
+      query,
+      "asset=ETH&from_address=0xabc&to_address=0xdef&amount=0.5"
+    );
+  }
+
+  /// Check that we can list transfers.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn list_transfers() {
+    let client = live::client().unwrap();
+    let transfers = client.issue::<ListTransfers>(&()).await.unwrap();
+
+    for transfer in transfers {
+      assert!(!transfer.asset.is_empty());
+    }
+  }
+
+  /// Verify that we report the expected error when requesting a
+  /// non-existent transfer.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn get_nonexistent_transfer() {
+    let client = live::client().unwrap();
+    let id = TransferId(Uuid::new_v4());
+    let err = client.issue::<GetTransfer>(&id).await.unwrap_err();
+
+    match err {
+      RequestError::Endpoint(GetTransferError::NotFound(_)) => (),
+      _ => panic!("Received unexpected error: {:?}", err),
+    };
+  }
+}
@@ -12,13 +12,13 @@ use http_endpoint::Bytes;
 use serde::Deserialize;
 use serde::Serialize;
 
-use serde_json::from_slice as from_json;
 use serde_json::to_vec as to_json;
 
 use uuid::Uuid;
 
 use crate::api::v2::account;
 use crate::api::v2::asset;
+use crate::util::datetime_from_str;
 use crate::Str;
 
 
@@ -46,10 +46,10 @@ pub struct Watchlist {
   #[serde(rename = "account_id")]
   pub account_id: account::Id,
   /// Timestamp this watchlist was created at.
-  #[serde(rename = "created_at")]
+  #[serde(rename = "created_at", deserialize_with = "datetime_from_str")]
   pub created_at: DateTime<Utc>,
   /// Timestamp this watchlist was last updated at.
-  #[serde(rename = "updated_at")]
+  #[serde(rename = "updated_at", deserialize_with = "datetime_from_str")]
   pub updated_at: DateTime<Utc>,
   /// The list of watched assets.
   #[serde(rename = "assets")]
@@ -119,7 +119,7 @@ Endpoint! {
 }
 
 
-EndpointNoParse! {
+Endpoint! {
   /// The representation of a DELETE request to the
   /// /v2/watchlists/<watchlist-id> endpoint.
   pub Delete(Id),
@@ -140,16 +140,6 @@ EndpointNoParse! {
   fn path(input: &Self::Input) -> Str {
     format!("/v2/watchlists/{}", input.as_simple()).into()
   }
-
-  #[inline]
-  fn parse(body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
-    debug_assert_eq!(body, b"");
-    Ok(())
-  }
-
-  fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
-    from_json::<Self::ApiError>(body).map_err(|_| body.to_vec())
-  }
 }
 
 
@@ -158,8 +148,7 @@ mod tests {
   use super::*;
 
   use crate::api::v2::account;
-  use crate::api_info::ApiInfo;
-  use crate::Client;
+  use crate::live;
   use crate::RequestError;
 
   use test_log::test;
@@ -167,9 +156,9 @@ mod tests {
 
   /// Check that we can create, retrieve, and delete a watchlist.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn create_get_delete() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let expected_symbols = vec!["AAPL".to_string(), "AMZN".to_string()];
     let created = client
       .issue::<Post>(&CreateReq {
@@ -197,9 +186,9 @@ mod tests {
   /// Check that we get back the expected error when attempting to
   /// create a watchlist with a name that is already taken.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn create_duplicate_name() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let name = "the-name";
     let created = client
@@ -229,9 +218,9 @@ mod tests {
   /// Verify that we report the appropriate error when attempting to
   /// retrieve a watchlist that does not exist.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn get_non_existent() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let created = client
       .issue::<Post>(&CreateReq {
         name: Uuid::new_v4().to_string(),
@@ -251,9 +240,9 @@ mod tests {
   /// Verify that we report the appropriate error when attempting to
   /// delete a watchlist that does not exist.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn delete_non_existent() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let id = Id(Uuid::parse_str("00000000-0000-0000-0000-000000000000").unwrap());
     let err = client.issue::<Delete>(&id).await.unwrap_err();
@@ -8,6 +8,7 @@ use serde::Deserialize;
 
 use crate::api::v2::account;
 use crate::api::v2::watchlist;
+use crate::util::datetime_from_str;
 use crate::Str;
 
 
@@ -21,10 +22,10 @@ pub struct WatchlistItem {
   #[serde(rename = "account_id")]
   pub account_id: account::Id,
   /// Timestamp this watchlist was created at.
-  #[serde(rename = "created_at")]
+  #[serde(rename = "created_at", deserialize_with = "datetime_from_str")]
   pub created_at: DateTime<Utc>,
   /// Timestamp this watchlist was last updated at.
-  #[serde(rename = "updated_at")]
+  #[serde(rename = "updated_at", deserialize_with = "datetime_from_str")]
   pub updated_at: DateTime<Utc>,
 }
 
@@ -53,16 +54,16 @@ mod tests {
 
   use crate::api::v2::watchlist;
   use crate::api::v2::watchlist::CreateReq;
-  use crate::api_info::ApiInfo;
-  use crate::Client;
   use test_log::test;
 
+  use crate::live;
+
 
   /// Check that we can list existing watchlists.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn list_watchlists() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let created = client
       .issue::<watchlist::Post>(&CreateReq {
         name: Uuid::new_v4().to_string(),
@@ -7,6 +7,7 @@ use std::ffi::OsString;
 use url::Url;
 
 use crate::api::API_BASE_URL;
+use crate::api::BROKER_API_BASE_URL;
 use crate::data::DATA_BASE_URL;
 use crate::data::DATA_STREAM_BASE_URL;
 use crate::Error;
@@ -15,6 +16,12 @@ use crate::Error;
 const ENV_API_BASE_URL: &str = "APCA_API_BASE_URL";
 /// The URL of the websocket stream portion of the Trading API to use.
 const ENV_API_STREAM_URL: &str = "APCA_API_STREAM_URL";
+/// The base URL of the Broker API to use.
+const ENV_BROKER_API_BASE_URL: &str = "APCA_BROKER_API_URL";
+/// The base URL of the Data API to use.
+const ENV_DATA_BASE_URL: &str = "APCA_API_DATA_URL";
+/// The base URL of the websocket stream portion of the Data API to use.
+const ENV_DATA_STREAM_BASE_URL: &str = "APCA_API_DATA_STREAM_URL";
 /// The environment variable representing the key ID.
 const ENV_KEY_ID: &str = "APCA_API_KEY_ID";
 /// The environment variable representing the secret key.
@@ -23,6 +30,7 @@ const ENV_SECRET: &str = "APCA_API_SECRET_KEY";
 
 /// Convert a Trading API base URL into the corresponding one for
 /// websocket streaming.
+#[allow(clippy::result_large_err)]
 fn make_api_stream_url(base_url: Url) -> Result<Url, Error> {
   let mut url = base_url;
   url.set_scheme("wss").map_err(|()| {
@@ -33,6 +41,21 @@ fn make_api_stream_url(base_url: Url) -> Result<Url, Error> {
 }
 
 
+/// Retrieve a URL from the environment variable with the given name,
+/// falling back to the provided default if it is not set.
+#[allow(clippy::result_large_err)]
+fn url_from_env(name: &str, default: &str) -> Result<Url, Error> {
+  let url = var_os(name)
+    .unwrap_or_else(|| OsString::from(default))
+    .into_string()
+    .map_err(|_| {
+      Error::Str(format!("{} environment variable is not a valid string", name).into())
+    })?;
+  let url = Url::parse(&url)?;
+  Ok(url)
+}
+
+
 /// An object encapsulating the information used for working with the
 /// Alpaca API.
 #[derive(Clone, Debug, PartialEq)]
@@ -46,6 +69,8 @@ pub struct ApiInfo {
   pub data_base_url: Url,
   /// The websocket base URL for streaming of data.
   pub data_stream_base_url: Url,
+  /// The base URL for the Broker API.
+  pub broker_base_url: Url,
   /// The key ID to use for authentication.
   pub key_id: String,
   /// The secret to use for authentication.
@@ -76,6 +101,7 @@ impl ApiInfo {
       // fine.
       data_base_url: Url::parse(DATA_BASE_URL).unwrap(),
       data_stream_base_url: Url::parse(DATA_STREAM_BASE_URL).unwrap(),
+      broker_base_url: Url::parse(BROKER_API_BASE_URL).unwrap(),
       key_id: key_id.to_string(),
       secret: secret.to_string(),
     })
@@ -90,29 +116,23 @@ impl ApiInfo {
   ///   `APCA_API_BASE_URL` variable
   /// - the Alpaca Trading API stream URL is retrieved from the
   ///   `APCA_API_STREAM_URL` variable
+  /// - the Alpaca Data API base URL is retrieved from the
+  ///   `APCA_API_DATA_URL` variable
+  /// - the Alpaca Data API stream base URL is retrieved from the
+  ///   `APCA_API_DATA_STREAM_URL` variable
+  /// - the Alpaca Broker API base URL is retrieved from the
+  ///   `APCA_BROKER_API_URL` variable
   /// - the Alpaca account key ID is retrieved from the
   ///   `APCA_API_KEY_ID` variable
   /// - the Alpaca account secret is retrieved from the
   ///   `APCA_API_SECRET_KEY` variable
   ///
   /// # Notes
-  /// - Neither of the two data APIs can be configured via the
-  ///   environment currently; defaults will be used
+  /// - If not set, the two data API URLs default to the official
+  ///   endpoints and the Broker API URL defaults to the sandbox
   #[allow(unused_qualifications)]
   pub fn from_env() -> Result<Self, Error> {
-    let api_base_url = var_os(ENV_API_BASE_URL)
-      .unwrap_or_else(|| OsString::from(API_BASE_URL))
-      .into_string()
-      .map_err(|_| {
-        Error::Str(
-          format!(
-            "{} environment variable is not a valid string",
-            ENV_API_BASE_URL
-          )
-          .into(),
-        )
-      })?;
-    let api_base_url = Url::parse(&api_base_url)?;
+    let api_base_url = url_from_env(ENV_API_BASE_URL, API_BASE_URL)?;
 
     let api_stream_url = var_os(ENV_API_STREAM_URL)
       .map(Result::<_, Error>::Ok)
@@ -148,18 +168,63 @@ impl ApiInfo {
         Error::Str(format!("{} environment variable is not a valid string", ENV_SECRET).into())
       })?;
 
+    let data_base_url = url_from_env(ENV_DATA_BASE_URL, DATA_BASE_URL)?;
+    let data_stream_base_url = url_from_env(ENV_DATA_STREAM_BASE_URL, DATA_STREAM_BASE_URL)?;
+    let broker_base_url = url_from_env(ENV_BROKER_API_BASE_URL, BROKER_API_BASE_URL)?;
+
     Ok(Self {
       api_base_url,
       api_stream_url,
-      // We basically only work with statically defined URL parts here
-      // which we know can be parsed successfully, so unwrapping is
-      // fine.
-      data_base_url: Url::parse(DATA_BASE_URL).unwrap(),
-      data_stream_base_url: Url::parse(DATA_STREAM_BASE_URL).unwrap(),
+      data_base_url,
+      data_stream_base_url,
+      broker_base_url,
       key_id,
       secret,
     })
   }
+
+  /// Set the base URL to use for the Data API.
+  ///
+  /// # Errors
+  /// - [`Error::Url`](crate::Error::Url) If `data_base_url` cannot be
+  ///   parsed into a [`url::Url`](url::Url).
+  #[allow(clippy::result_large_err)]
+  pub fn with_data_base_url(mut self, data_base_url: impl AsRef<str>) -> Result<Self, Error> {
+    self.data_base_url = Url::parse(data_base_url.as_ref())?;
+    Ok(self)
+  }
+
+  /// Set the base URL to use for streaming data via the Data API.
+  ///
+  /// # Errors
+  /// - [`Error::Url`](crate::Error::Url) If `data_stream_base_url`
+  ///   cannot be parsed into a [`url::Url`](url::Url).
+  #[allow(clippy::result_large_err)]
+  pub fn with_data_stream_base_url(
+    mut self,
+    data_stream_base_url: impl AsRef<str>,
+  ) -> Result<Self, Error> {
+    self.data_stream_base_url = Url::parse(data_stream_base_url.as_ref())?;
+    Ok(self)
+  }
+
+  /// Set the base URL to use for the Broker API.
+  ///
+  /// # Errors
+  /// - [`Error::Url`](crate::Error::Url) If `broker_base_url` cannot be
+  ///   parsed into a [`url::Url`](url::Url).
+  #[allow(clippy::result_large_err)]
+  pub fn with_broker_base_url(mut self, broker_base_url: impl AsRef<str>) -> Result<Self, Error> {
+    self.broker_base_url = Url::parse(broker_base_url.as_ref())?;
+    Ok(self)
+  }
+
+  /// Retrieve the value of the `Authorization` header to use for
+  /// requests to the Broker API, which uses HTTP basic authentication.
+  pub(crate) fn broker_authorization(&self) -> String {
+    let credentials = format!("{}:{}", self.key_id, self.secret);
+    format!("Basic {}", base64::encode(credentials))
+  }
 }
 
 
@@ -181,4 +246,37 @@ mod tests {
     assert_eq!(api_info.key_id, key_id);
     assert_eq!(api_info.secret, secret);
   }
+
+  /// Check that we can override the data URLs of an [`ApiInfo`]
+  /// object.
+  #[test]
+  fn override_data_urls() {
+    let api_info = ApiInfo::from_parts(API_BASE_URL, "key", "secret")
+      .unwrap()
+      .with_data_base_url("http://localhost:8080/")
+      .unwrap()
+      .with_data_stream_base_url("ws://localhost:8081/")
+      .unwrap();
+
+    assert_eq!(api_info.data_base_url.as_str(), "http://localhost:8080/");
+    assert_eq!(api_info.data_stream_base_url.as_str(), "ws://localhost:8081/");
+
+    let result = ApiInfo::from_parts(API_BASE_URL, "key", "secret")
+      .unwrap()
+      .with_data_base_url("not a url");
+    assert!(result.is_err());
+  }
+
+  /// Check that we can override the Broker API URL of an [`ApiInfo`]
+  /// object and that we create the expected credentials for it.
+  #[test]
+  fn override_broker_url() {
+    let api_info = ApiInfo::from_parts(API_BASE_URL, "key", "secret")
+      .unwrap()
+      .with_broker_base_url("http://localhost:8082/")
+      .unwrap();
+
+    assert_eq!(api_info.broker_base_url.as_str(), "http://localhost:8082/");
+    assert_eq!(api_info.broker_authorization(), "Basic a2V5OnNlY3JldA==");
+  }
 }
@@ -0,0 +1,232 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+use tokio::time::Instant;
+
+use tracing::info;
+use tracing::warn;
+
+
+/// The number of state change events buffered for slow receivers.
+const EVENT_CAPACITY: usize = 64;
+
+
+/// The state of the circuit breaker guarding an endpoint.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum CircuitState {
+  /// Requests are issued as usual.
+  Closed,
+  /// The endpoint failed repeatedly and requests to it fail fast until
+  /// the cooldown elapsed.
+  Open,
+  /// The cooldown elapsed and a trial request is permitted, to check
+  /// whether the endpoint recovered.
+  HalfOpen,
+}
+
+
+/// An event signaling a change of the state of the circuit breaker
+/// guarding an endpoint.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct CircuitEvent {
+  /// The endpoint affected, e.g., `api::v2::order::Post`.
+  pub endpoint: &'static str,
+  /// The new state of the circuit breaker.
+  pub state: CircuitState,
+}
+
+
+/// The state of the circuit guarding a single endpoint.
+#[derive(Debug)]
+struct Circuit {
+  /// The current state.
+  state: CircuitState,
+  /// The number of consecutive failures observed.
+  failures: u32,
+  /// The point in time until which requests are held back while
+  /// `Open` or `HalfOpen`.
+  until: Instant,
+}
+
+impl Default for Circuit {
+  fn default() -> Self {
+    Self {
+      state: CircuitState::Closed,
+      failures: 0,
+      until: Instant::now(),
+    }
+  }
+}
+
+
+/// A circuit breaker failing requests to endpoints fast after they
+/// failed repeatedly.
+#[derive(Debug)]
+pub(crate) struct CircuitBreaker {
+  /// The number of consecutive failures after which to open the
+  /// circuit of an endpoint.
+  threshold: u32,
+  /// The time for which a circuit stays open.
+  cooldown: Duration,
+  /// The circuits of all endpoints, indexed by endpoint.
+  circuits: Mutex<HashMap<&'static str, Circuit>>,
+  /// The sender used for publishing state changes.
+  events: broadcast::Sender<CircuitEvent>,
+}
+
+impl CircuitBreaker {
+  /// Create a new `CircuitBreaker` opening the circuit of an endpoint
+  /// after `threshold` consecutive failures, for `cooldown`.
+  pub(crate) fn new(threshold: u32, cooldown: Duration) -> Self {
+    let (events, _) = broadcast::channel(EVENT_CAPACITY);
+    Self {
+      threshold: threshold.max(1),
+      cooldown,
+      circuits: Mutex::new(HashMap::new()),
+      events,
+    }
+  }
+
+  /// Subscribe to state change events.
+  pub(crate) fn subscribe(&self) -> broadcast::Receiver<CircuitEvent> {
+    self.events.subscribe()
+  }
+
+  /// Transition the circuit of the given endpoint into a new state.
+  fn transition(&self, endpoint: &'static str, circuit: &mut Circuit, state: CircuitState) {
+    match state {
+      CircuitState::Open => warn!(endpoint, "circuit breaker opened"),
+      CircuitState::HalfOpen => info!(endpoint, "circuit breaker half-open"),
+      CircuitState::Closed => info!(endpoint, "circuit breaker closed"),
+    }
+
+    circuit.state = state;
+    // There may not be any receivers, which is fine.
+    let _result = self.events.send(CircuitEvent { endpoint, state });
+  }
+
+  /// Check whether a request to the given endpoint may be issued.
+  pub(crate) fn admit(&self, endpoint: &'static str) -> bool {
+    let now = Instant::now();
+    let mut circuits = self.circuits.lock().unwrap();
+    let circuit = circuits.entry(endpoint).or_default();
+
+    match circuit.state {
+      CircuitState::Closed => true,
+      // While half-open we only admit a single trial request per
+      // cooldown period. Should that one never report back (e.g.,
+      // because it got dropped), another one is admitted after the
+      // fact.
+      CircuitState::Open | CircuitState::HalfOpen if now >= circuit.until => {
+        circuit.until = now + self.cooldown;
+        if circuit.state != CircuitState::HalfOpen {
+          self.transition(endpoint, circuit, CircuitState::HalfOpen);
+        }
+        true
+      },
+      CircuitState::Open | CircuitState::HalfOpen => false,
+    }
+  }
+
+  /// Report the outcome of a request to the given endpoint.
+  pub(crate) fn report(&self, endpoint: &'static str, success: bool) {
+    let mut circuits = self.circuits.lock().unwrap();
+    let circuit = circuits.entry(endpoint).or_default();
+
+    if success {
+      circuit.failures = 0;
+      if circuit.state != CircuitState::Closed {
+        self.transition(endpoint, circuit, CircuitState::Closed);
+      }
+      return
+    }
+
+    circuit.failures = circuit.failures.saturating_add(1);
+    match circuit.state {
+      CircuitState::Closed if circuit.failures >= self.threshold => {
+        circuit.until = Instant::now() + self.cooldown;
+        self.transition(endpoint, circuit, CircuitState::Open);
+      },
+      CircuitState::HalfOpen => {
+        circuit.until = Instant::now() + self.cooldown;
+        self.transition(endpoint, circuit, CircuitState::Open);
+      },
+      CircuitState::Closed | CircuitState::Open => (),
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use test_log::test;
+
+  use tokio::time::advance;
+  use tokio::time::pause;
+
+
+  /// Check that a circuit opens after repeated failures and closes
+  /// again after a successful trial request.
+  #[test(tokio::test)]
+  async fn open_and_close() {
+    pause();
+
+    let breaker = CircuitBreaker::new(2, Duration::from_secs(10));
+    let mut events = breaker.subscribe();
+
+    assert!(breaker.admit("a"));
+    breaker.report("a", false);
+    assert!(breaker.admit("a"));
+    breaker.report("a", false);
+    assert_eq!(
+      events.try_recv().unwrap(),
+      CircuitEvent {
+        endpoint: "a",
+        state: CircuitState::Open,
+      }
+    );
+
+    // Other endpoints are not affected.
+    assert!(breaker.admit("b"));
+    assert!(!breaker.admit("a"));
+
+    advance(Duration::from_secs(10)).await;
+    assert!(breaker.admit("a"));
+    assert_eq!(events.try_recv().unwrap().state, CircuitState::HalfOpen);
+    // Only a single trial request is admitted.
+    assert!(!breaker.admit("a"));
+
+    breaker.report("a", true);
+    assert_eq!(events.try_recv().unwrap().state, CircuitState::Closed);
+    assert!(breaker.admit("a"));
+  }
+
+  /// Check that a failed trial request opens the circuit again.
+  #[test(tokio::test)]
+  async fn failed_trial() {
+    pause();
+
+    let breaker = CircuitBreaker::new(1, Duration::from_secs(10));
+    breaker.report("a", false);
+    assert!(!breaker.admit("a"));
+
+    advance(Duration::from_secs(10)).await;
+    assert!(breaker.admit("a"));
+    breaker.report("a", false);
+    assert!(!breaker.admit("a"));
+
+    advance(Duration::from_secs(5)).await;
+    assert!(!breaker.admit("a"));
+    advance(Duration::from_secs(5)).await;
+    assert!(breaker.admit("a"));
+  }
+}
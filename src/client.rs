@@ -1,45 +1,93 @@
 // Copyright (C) 2019-2022 The apca Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
-use std::borrow::Cow;
 use std::fmt::Debug;
 use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
 use std::future::Future;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
 use std::str::from_utf8;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::Instant;
 
+use futures::future::select;
+use futures::future::Either;
+use futures::pin_mut;
+use futures::stream::try_unfold;
+use futures::Stream;
+
+use http::header::ACCEPT_ENCODING;
+use http::header::AUTHORIZATION;
+use http::header::CONTENT_TYPE;
+use http::header::RETRY_AFTER;
 use http::request::Builder as HttpRequestBuilder;
+use http::uri::PathAndQuery;
 use http::HeaderMap;
 use http::HeaderValue;
+use http::Method;
 use http::Request;
 use http::Response;
+use http::StatusCode;
+use http::Uri;
 use http_endpoint::Endpoint;
 
 use hyper::body::to_bytes;
 use hyper::body::Bytes;
 use hyper::client::Builder as HttpClientBuilder;
-use hyper::client::HttpConnector;
 use hyper::Body;
 use hyper::Client as HttpClient;
 use hyper::Error as HyperError;
-use hyper_tls::HttpsConnector;
+
+use tokio::sync::broadcast;
+use tokio::sync::Notify;
 
 use tracing::debug;
 use tracing::field::debug;
 use tracing::field::DebugValue;
+use tracing::info;
 use tracing::instrument;
 use tracing::span;
 use tracing::trace;
+use tracing::warn;
 use tracing::Level;
 use tracing_futures::Instrument;
 
-use url::Url;
-
+use crate::api::v2::flatten;
+use crate::api::v2::flatten::FlattenError;
+use crate::api::v2::flatten::Report;
 use crate::api::HDR_KEY_ID;
 use crate::api::HDR_SECRET;
 use crate::api_info::ApiInfo;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::circuit_breaker::CircuitEvent;
+use crate::coalesce;
+use crate::coalesce::Coalescer;
+use crate::coalesce::Role;
+use crate::connector::Connector;
+use crate::data::price;
+use crate::data::price::LatestPriceError;
+use crate::data::price::Price;
+use crate::data::v2::entitlements;
+use crate::data::v2::entitlements::Entitlements;
+use crate::data::v2::last_quote;
+use crate::data::DATA_BASE_URL;
+use crate::dry_run;
+use crate::dry_run::Simulation;
 use crate::error::RequestError;
+use crate::metric;
+use crate::paginated::Paginated;
+use crate::paginated::Prefetch;
+use crate::sans_io;
+use crate::scheduler::Priority;
+use crate::scheduler::Scheduler;
 use crate::subscribable::Subscribable;
+use crate::tls;
 use crate::Error;
 
 
@@ -55,7 +103,7 @@ impl<'h> Debug for DebugHeaders<'h> {
 
     f.debug_map()
       .entries(self.headers.iter().map(|(k, v)| {
-        if k == HDR_KEY_ID || k == HDR_SECRET {
+        if k == HDR_KEY_ID || k == HDR_SECRET || k == AUTHORIZATION {
           (k, &MASKED)
         } else {
           (k, v)
@@ -97,10 +145,100 @@ fn debug_request(request: &Request<Body>) -> DebugValue<DebugRequest<'_>> {
 }
 
 
+/// The value of the `Accept-Encoding` header we send along with each
+/// request, reflecting the enabled compression features.
+#[cfg(all(feature = "gzip", feature = "deflate"))]
+const ACCEPT_ENCODINGS: &str = "gzip, deflate";
+#[cfg(all(feature = "gzip", not(feature = "deflate")))]
+const ACCEPT_ENCODINGS: &str = "gzip";
+#[cfg(all(not(feature = "gzip"), feature = "deflate"))]
+const ACCEPT_ENCODINGS: &str = "deflate";
+
+
+/// Decompress an HTTP body according to the provided
+/// `Content-Encoding` header value, if any.
+///
+/// Bodies with an encoding that we do not know how to handle are passed
+/// through as-is.
+#[cfg(any(feature = "gzip", feature = "deflate"))]
+async fn decode_body(encoding: Option<&HeaderValue>, bytes: Bytes) -> Result<Bytes, IoError> {
+  use futures::AsyncReadExt as _;
+
+  let bytes = match encoding {
+    #[cfg(feature = "gzip")]
+    Some(value) if value == "gzip" => {
+      use async_compression::futures::bufread::GzipDecoder;
+
+      let mut buffer = Vec::new();
+      let _count = GzipDecoder::new(&*bytes).read_to_end(&mut buffer).await?;
+      buffer.into()
+    },
+    // Note that the "deflate" content encoding, despite its name,
+    // refers to zlib wrapped deflate data.
+    #[cfg(feature = "deflate")]
+    Some(value) if value == "deflate" => {
+      use async_compression::futures::bufread::ZlibDecoder;
+
+      let mut buffer = Vec::new();
+      let _count = ZlibDecoder::new(&*bytes).read_to_end(&mut buffer).await?;
+      buffer.into()
+    },
+    _ => bytes,
+  };
+
+  Ok(bytes)
+}
+
+
+/// Check whether a response denotes a maintenance or outage page
+/// instead of an actual API response.
+///
+/// All of Alpaca's APIs speak JSON. When they are down, however, an
+/// HTML page may be served in its place, or a "503 Service
+/// Unavailable" with a body that is not a JSON object at all.
+fn is_maintenance(status: StatusCode, headers: &HeaderMap, body: &[u8]) -> bool {
+  let html = headers
+    .get(CONTENT_TYPE)
+    .and_then(|value| value.to_str().ok())
+    .map(|value| {
+      value
+        .trim_start()
+        .to_ascii_lowercase()
+        .starts_with("text/html")
+    })
+    .unwrap_or(false);
+  let start = body.iter().find(|byte| !byte.is_ascii_whitespace());
+
+  html
+    || start == Some(&b'<')
+    || (status == StatusCode::SERVICE_UNAVAILABLE && start != Some(&b'{'))
+}
+
+
+/// Extract the delay indicated by a `Retry-After` header, if any.
+///
+/// Only the delay-seconds form is supported.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+  headers
+    .get(RETRY_AFTER)?
+    .to_str()
+    .ok()?
+    .trim()
+    .parse::<u64>()
+    .ok()
+    .map(Duration::from_secs)
+}
+
+
 /// A builder for creating customized `Client` objects.
 #[derive(Debug)]
 pub struct Builder {
   builder: HttpClientBuilder,
+  connector: Option<Connector>,
+  requests_per_minute: Option<u32>,
+  circuit_breaker: Option<(u32, Duration)>,
+  dry_run: bool,
+  coalesce: bool,
 }
 
 impl Builder {
@@ -111,12 +249,92 @@ impl Builder {
     self
   }
 
+  /// Set the connector used for establishing connections, instead of
+  /// connecting via TCP and TLS.
+  #[inline]
+  pub fn connector(&mut self, connector: Connector) -> &mut Self {
+    self.connector = Some(connector);
+    self
+  }
+
+  /// Limit the number of requests issued per minute.
+  ///
+  /// Requests exceeding the limit are delayed. Pending requests are
+  /// issued in the order of their [`Priority`], so that, for example,
+  /// order submissions are not starved by historical data downloads.
+  #[inline]
+  pub fn rate_limit(&mut self, requests_per_minute: u32) -> &mut Self {
+    self.requests_per_minute = Some(requests_per_minute);
+    self
+  }
+
+  /// Guard each endpoint with a circuit breaker.
+  ///
+  /// After `failure_threshold` consecutive failures of requests to an
+  /// endpoint (connection errors, server errors, and rate limit
+  /// violations), further requests to it fail fast with
+  /// [`RequestError::CircuitOpen`] for `cooldown`. Afterwards, a
+  /// single trial request is issued and, if successful, the endpoint
+  /// is used as usual again. State changes are published as
+  /// [`CircuitEvent`] objects, see [`Client::circuit_events`].
+  #[inline]
+  pub fn circuit_breaker(&mut self, failure_threshold: u32, cooldown: Duration) -> &mut Self {
+    self.circuit_breaker = Some((failure_threshold, cooldown));
+    self
+  }
+
+  /// Enable or disable dry-run mode.
+  ///
+  /// In dry-run mode, mutating requests (such as order submissions,
+  /// changes, and cancellations) are not sent to the API, but logged
+  /// and answered with a simulated successful response. Read-only
+  /// requests are issued as usual. That allows for shadow-running a
+  /// strategy against live data.
+  ///
+  /// Note that only requests pertaining orders and positions,
+  /// withdrawals, and account configuration changes are simulated; all
+  /// other mutating requests fail with
+  /// [`RequestError::NotSimulated`].
+  #[inline]
+  pub fn dry_run(&mut self, dry_run: bool) -> &mut Self {
+    self.dry_run = dry_run;
+    self
+  }
+
+  /// Enable or disable the coalescing of identical GET requests.
+  ///
+  /// If enabled, a GET request identical to one already in flight is
+  /// not issued, but shares the outcome of the latter. That reduces
+  /// pressure on the rate limit when, for example, multiple tasks
+  /// request the same market data concurrently. Requests are only
+  /// considered identical if method, URI, and all headers (including
+  /// credentials) match. Should the shared request fail, the requests
+  /// coalesced with it are issued after all.
+  #[inline]
+  pub fn coalesce_requests(&mut self, coalesce: bool) -> &mut Self {
+    self.coalesce = coalesce;
+    self
+  }
+
   /// Build the final `Client` object.
   pub fn build(&self, api_info: ApiInfo) -> Client {
-    let https = HttpsConnector::new();
-    let client = self.builder.build(https);
+    let connector = self
+      .connector
+      .clone()
+      .unwrap_or_else(|| Connector::new(tls::https_connector()));
+    let client = self.builder.build(connector);
 
-    Client { api_info, client }
+    Client {
+      api_info: RwLock::new(Arc::new(api_info)),
+      client,
+      scheduler: self.requests_per_minute.map(Scheduler::per_minute),
+      shutdown: Shutdown::default(),
+      circuit_breaker: self
+        .circuit_breaker
+        .map(|(threshold, cooldown)| CircuitBreaker::new(threshold, cooldown)),
+      dry_run: self.dry_run,
+      coalescer: self.coalesce.then(Coalescer::default),
+    }
   }
 }
 
@@ -134,7 +352,14 @@ impl Default for Builder {
     let mut builder = HttpClient::builder();
     let _ = builder.pool_max_idle_per_host(0);
 
-    Self { builder }
+    Self {
+      builder,
+      connector: None,
+      requests_per_minute: None,
+      circuit_breaker: None,
+      dry_run: false,
+      coalesce: false,
+    }
   }
 
   #[cfg(not(test))]
@@ -142,6 +367,43 @@ impl Default for Builder {
   fn default() -> Self {
     Self {
       builder: HttpClient::builder(),
+      connector: None,
+      requests_per_minute: None,
+      circuit_breaker: None,
+      dry_run: false,
+      coalesce: false,
+    }
+  }
+}
+
+
+/// State used for shutting down a `Client`.
+#[derive(Debug, Default)]
+struct Shutdown {
+  /// Whether the client has been shut down.
+  shut_down: AtomicBool,
+  /// Notification sent out to in-flight requests on shutdown.
+  signal: Notify,
+  /// The number of requests currently in flight.
+  in_flight: AtomicUsize,
+  /// Notification sent out when the last in-flight request finished.
+  drained: Notify,
+}
+
+/// A guard tracking a single in-flight request.
+struct InFlight<'s>(&'s Shutdown);
+
+impl<'s> InFlight<'s> {
+  fn new(shutdown: &'s Shutdown) -> Self {
+    let _ = shutdown.in_flight.fetch_add(1, Ordering::SeqCst);
+    Self(shutdown)
+  }
+}
+
+impl Drop for InFlight<'_> {
+  fn drop(&mut self) {
+    if self.0.in_flight.fetch_sub(1, Ordering::SeqCst) == 1 {
+      self.0.drained.notify_waiters();
     }
   }
 }
@@ -151,8 +413,13 @@ impl Default for Builder {
 /// interacting with the Alpaca API.
 #[derive(Debug)]
 pub struct Client {
-  api_info: ApiInfo,
-  client: HttpClient<HttpsConnector<HttpConnector>, Body>,
+  api_info: RwLock<Arc<ApiInfo>>,
+  client: HttpClient<Connector, Body>,
+  scheduler: Option<Scheduler>,
+  shutdown: Shutdown,
+  circuit_breaker: Option<CircuitBreaker>,
+  dry_run: bool,
+  coalescer: Option<Coalescer>,
 }
 
 impl Client {
@@ -169,43 +436,28 @@ impl Client {
     Builder::default().build(api_info)
   }
 
-  /// Add "gzip" as an accepted encoding to the request.
-  #[cfg(feature = "gzip")]
-  fn maybe_add_gzip_header(request: &mut Request<Body>) {
-    use http::header::ACCEPT_ENCODING;
-
+  /// Add the compression schemes we support as accepted encodings to
+  /// the request.
+  #[cfg(any(feature = "gzip", feature = "deflate"))]
+  fn maybe_add_encoding_header(request: &mut Request<Body>) {
     let _ = request
       .headers_mut()
-      .insert(ACCEPT_ENCODING, HeaderValue::from_static("gzip"));
+      .insert(ACCEPT_ENCODING, HeaderValue::from_static(ACCEPT_ENCODINGS));
   }
 
   /// An implementation stub not actually doing anything.
-  #[cfg(not(feature = "gzip"))]
-  fn maybe_add_gzip_header(_request: &mut Request<Body>) {}
+  #[cfg(not(any(feature = "gzip", feature = "deflate")))]
+  fn maybe_add_encoding_header(_request: &mut Request<Body>) {}
 
   /// Create a `Request` to the endpoint.
   fn request<R>(&self, input: &R::Input) -> Result<Request<Body>, R::Error>
   where
     R: Endpoint,
   {
-    let mut url = R::base_url()
-      .map(|url| Url::parse(url.as_ref()).expect("endpoint definition contains invalid URL"))
-      .unwrap_or_else(|| self.api_info.api_base_url.clone());
-
-    url.set_path(&R::path(input));
-    url.set_query(R::query(input)?.as_ref().map(AsRef::as_ref));
-
-    let mut request = HttpRequestBuilder::new()
-      .method(R::method())
-      .uri(url.as_str())
-      // Add required authentication information.
-      .header(HDR_KEY_ID, self.api_info.key_id.as_str())
-      .header(HDR_SECRET, self.api_info.secret.as_str())
-      .body(Body::from(
-        R::body(input)?.unwrap_or(Cow::Borrowed(&[0; 0])),
-      ))?;
-
-    Self::maybe_add_gzip_header(&mut request);
+    let request = sans_io::request::<R>(&self.api_info(), input)?;
+    let mut request = request.map(Body::from);
+
+    Self::maybe_add_encoding_header(&mut request);
     Ok(request)
   }
 
@@ -223,41 +475,69 @@ impl Client {
   }
 
   /// Retrieve the HTTP body, possible uncompressing it if it was gzip
-  /// encoded.
-  #[cfg(feature = "gzip")]
+  /// or deflate encoded.
+  #[cfg(any(feature = "gzip", feature = "deflate"))]
   async fn retrieve_body<E>(response: Response<Body>) -> Result<Bytes, RequestError<E>> {
-    use async_compression::futures::bufread::GzipDecoder;
-    use futures::AsyncReadExt as _;
     use http::header::CONTENT_ENCODING;
 
     let (parts, body) = response.into_parts();
     let encoding = parts.headers.get(CONTENT_ENCODING);
 
     let bytes = Self::retrieve_raw_body(body).await?;
-    let bytes = match encoding {
-      Some(value) if value == HeaderValue::from_static("gzip") => {
-        let mut buffer = Vec::new();
-        let _count = GzipDecoder::new(&*bytes).read_to_end(&mut buffer).await?;
-        buffer.into()
-      },
-      _ => bytes,
-    };
-
+    let bytes = decode_body(encoding, bytes).await?;
     Ok(bytes)
   }
 
   /// Retrieve the HTTP body.
-  #[cfg(not(feature = "gzip"))]
+  #[cfg(not(any(feature = "gzip", feature = "deflate")))]
   async fn retrieve_body<E>(response: Response<Body>) -> Result<Bytes, RequestError<E>> {
     let bytes = Self::retrieve_raw_body(response.into_body()).await?;
     Ok(bytes)
   }
 
+  /// Retrieve the default priority of requests to the given endpoint.
+  pub(crate) fn default_priority<R>() -> Priority
+  where
+    R: Endpoint,
+  {
+    match R::base_url() {
+      Some(url) if url == DATA_BASE_URL => Priority::Backfill,
+      _ => Priority::Trading,
+    }
+  }
+
+  /// Wait until a request of the given priority may be issued, if the
+  /// client is rate limited.
+  async fn schedule(&self, priority: Priority) {
+    if let Some(scheduler) = &self.scheduler {
+      scheduler.acquire(priority).await
+    }
+  }
+
   /// Create and issue a request and decode the response.
+  ///
+  /// Requests to the market data API are issued with
+  /// [`Priority::Backfill`], all others with [`Priority::Trading`].
+  #[inline]
   pub fn issue<R>(
     &self,
     input: &R::Input,
   ) -> impl Future<Output = Result<R::Output, RequestError<R::Error>>> + '_
+  where
+    R: Endpoint,
+  {
+    self.issue_with_priority::<R>(input, Self::default_priority::<R>())
+  }
+
+  /// Create and issue a request with the given priority and decode the
+  /// response.
+  ///
+  /// The priority only has an effect if the client is rate limited.
+  pub fn issue_with_priority<R>(
+    &self,
+    input: &R::Input,
+    priority: Priority,
+  ) -> impl Future<Output = Result<R::Output, RequestError<R::Error>>> + '_
   where
     R: Endpoint,
   {
@@ -270,16 +550,378 @@ impl Client {
         method = display(request.method()),
         uri = display(request.uri())
       );
-      self.issue_::<R>(request).instrument(span).await
+      let issue = self.issue_::<R>(request, priority);
+      self.cancelable(issue).instrument(span).await
     }
   }
 
+  /// Issue requests to an endpoint returning its results in pages,
+  /// retrieving one page after the other, and yield the items of all
+  /// of them.
+  ///
+  /// Pages are only requested once all items of the previous one have
+  /// been consumed. The stream ends after the first error.
+  pub fn issue_paged<R>(
+    &self,
+    input: R::Input,
+  ) -> impl Stream<Item = Result<R::Item, RequestError<R::Error>>> + '_
+  where
+    R: Paginated,
+    R::Input: 'static,
+    R::Item: 'static,
+    R::State: 'static,
+  {
+    self.issue_paged_prefetch::<R>(input, 0)
+  }
+
+  /// Issue requests to an endpoint returning its results in pages,
+  /// retrieving up to `lookahead` pages in advance, and yield the items
+  /// of all of them.
+  ///
+  /// Compared to [`issue_paged`][Self::issue_paged], the request for
+  /// the next page is already in flight while the items of the current
+  /// one are being consumed, which shortens the overall time it takes
+  /// to process all of them. Note that pages retrieved ahead of time
+  /// count towards rate limits even if the stream is dropped before
+  /// reaching them. The stream ends after the first error.
+  pub fn issue_paged_prefetch<R>(
+    &self,
+    input: R::Input,
+    lookahead: usize,
+  ) -> impl Stream<Item = Result<R::Item, RequestError<R::Error>>> + '_
+  where
+    R: Paginated,
+    R::Input: 'static,
+    R::Item: 'static,
+    R::State: 'static,
+  {
+    let state = (Some(input), R::State::default());
+    let pages = try_unfold(state, move |(input, mut state)| async move {
+      match input {
+        Some(input) => {
+          let output = self.issue::<R>(&input).await?;
+          let (page, next) = R::paginate(input, output, &mut state);
+          Ok(Some((page, (next, state))))
+        },
+        None => Ok(None),
+      }
+    });
+    Prefetch::new(Box::pin(pages), lookahead)
+  }
+
+  /// Create and issue a request with the given additional headers,
+  /// returning the status code, the response headers, and the raw (but
+  /// decoded) response body without evaluating them.
+  pub(crate) fn issue_raw<R>(
+    &self,
+    input: &R::Input,
+    headers: HeaderMap,
+  ) -> impl Future<Output = Result<(StatusCode, HeaderMap, Bytes), RequestError<R::Error>>> + '_
+  where
+    R: Endpoint,
+  {
+    let result = self.request::<R>(input);
+    async move {
+      let mut request = result.map_err(RequestError::Endpoint)?;
+      request.headers_mut().extend(headers);
+      let span = span!(
+        Level::INFO,
+        "issue",
+        method = display(request.method()),
+        uri = display(request.uri())
+      );
+      let retrieve = self.retrieve::<R>(request, Self::default_priority::<R>());
+      self.cancelable(retrieve).instrument(span).await
+    }
+  }
+
+  /// Create and issue a request, returning the response without
+  /// retrieving its body.
+  ///
+  /// This method is meant for responses with potentially large bodies,
+  /// which can then be streamed instead of being held in memory in
+  /// their entirety. Note that the body is not decoded and that its
+  /// retrieval is not canceled when the client is shut down.
+  pub(crate) fn issue_stream<R>(
+    &self,
+    input: &R::Input,
+  ) -> impl Future<Output = Result<Response<Body>, RequestError<R::Error>>> + '_
+  where
+    R: Endpoint,
+  {
+    let result = self.request::<R>(input);
+    async move {
+      let mut request = result.map_err(RequestError::Endpoint)?;
+      // We hand out the body as-is, so we have to make sure that it is
+      // not encoded.
+      let _ = request.headers_mut().remove(ACCEPT_ENCODING);
+      let span = span!(
+        Level::INFO,
+        "issue",
+        method = display(request.method()),
+        uri = display(request.uri())
+      );
+      let issue = async {
+        self.schedule(Self::default_priority::<R>()).await;
+        let method = request.method().clone();
+        let start = Instant::now();
+        let result = self.send(request).await;
+        let status = result.as_ref().ok().map(Response::status);
+        metric::request(metric::endpoint::<R>(), &method, status, start.elapsed());
+        result
+      };
+      self.cancelable(issue).instrument(span).await
+    }
+  }
+
+  /// Issue an unauthenticated GET request to the given URL, returning
+  /// the response without retrieving its body.
+  ///
+  /// This method is meant for following redirects to resources hosted
+  /// outside of Alpaca's APIs, e.g., pre-signed URLs of documents,
+  /// which must not be sent any credentials.
+  pub(crate) async fn get_stream<E>(&self, url: &str) -> Result<Response<Body>, RequestError<E>>
+  where
+    E: From<http::Error>,
+  {
+    let request = HttpRequestBuilder::new()
+      .method(Method::GET)
+      .uri(url)
+      .body(Body::empty())
+      .map_err(|err| RequestError::Endpoint(E::from(err)))?;
+    let span = span!(Level::INFO, "get", uri = display(request.uri()));
+    self.cancelable(self.send(request)).instrument(span).await
+  }
+
+  /// Send a request, retrieving the response without its body.
+  async fn send<E>(&self, request: Request<Body>) -> Result<Response<Body>, RequestError<E>> {
+    debug!("requesting");
+    trace!(request = debug_request(&request));
+
+    let response = self.client.request(request).await?;
+    debug!(status = debug(&response.status()));
+    trace!(response = debug(&response));
+    Ok(response)
+  }
+
+  /// Run the provided request future to completion, unless the client
+  /// is shut down in the meantime.
+  async fn cancelable<F, T, E>(&self, future: F) -> Result<T, RequestError<E>>
+  where
+    F: Future<Output = Result<T, RequestError<E>>>,
+  {
+    let _guard = InFlight::new(&self.shutdown);
+    let signal = self.shutdown.signal.notified();
+    if self.is_shut_down() {
+      return Err(RequestError::Shutdown)
+    }
+
+    pin_mut!(future);
+    pin_mut!(signal);
+
+    match select(future, signal).await {
+      Either::Left((result, _)) => result,
+      Either::Right(((), _)) => {
+        debug!("request canceled due to shutdown");
+        Err(RequestError::Shutdown)
+      },
+    }
+  }
+
+  /// Shut down the client.
+  ///
+  /// All requests currently in flight are canceled and report
+  /// [`RequestError::Shutdown`], as do all requests issued after the
+  /// fact. The returned future resolves once all in-flight requests
+  /// have been drained.
+  ///
+  /// Note that market data and order update streams are not tied to a
+  /// `Client` and have to be closed separately, e.g., by means of
+  /// [`Subscription::close`][crate::data::v2::stream::Subscription::close].
+  pub async fn shutdown(&self) {
+    self.shutdown.shut_down.store(true, Ordering::SeqCst);
+    self.shutdown.signal.notify_waiters();
+
+    loop {
+      let drained = self.shutdown.drained.notified();
+      if self.shutdown.in_flight.load(Ordering::SeqCst) == 0 {
+        break
+      }
+      drained.await
+    }
+  }
+
+  /// Check whether the client has been shut down.
+  #[inline]
+  pub fn is_shut_down(&self) -> bool {
+    self.shutdown.shut_down.load(Ordering::SeqCst)
+  }
+
   /// Issue a request.
-  #[allow(clippy::cognitive_complexity)]
-  async fn issue_<R>(&self, request: Request<Body>) -> Result<R::Output, RequestError<R::Error>>
+  async fn issue_<R>(
+    &self,
+    request: Request<Body>,
+    priority: Priority,
+  ) -> Result<R::Output, RequestError<R::Error>>
+  where
+    R: Endpoint,
+  {
+    let (status, _, bytes) = self.retrieve::<R>(request, priority).await?;
+    R::evaluate(status, bytes.as_ref()).map_err(RequestError::Endpoint)
+  }
+
+  /// Issue a request with the given priority and retrieve the
+  /// response's status code, headers, and body.
+  ///
+  /// If the client is in dry-run mode, mutating requests are simulated
+  /// instead. If coalescing is enabled, GET requests identical to one
+  /// already in flight share its outcome instead of being issued.
+  async fn retrieve<R>(
+    &self,
+    request: Request<Body>,
+    priority: Priority,
+  ) -> Result<(StatusCode, HeaderMap, Bytes), RequestError<R::Error>>
   where
     R: Endpoint,
   {
+    let endpoint = metric::endpoint::<R>();
+    if let Some(breaker) = &self.circuit_breaker {
+      if !breaker.admit(endpoint) {
+        debug!("circuit breaker open; failing fast");
+        return Err(RequestError::CircuitOpen(endpoint))
+      }
+    }
+
+    let result = match &self.coalescer {
+      Some(coalescer) if request.method() == Method::GET => {
+        let key = coalesce::key(&request);
+        loop {
+          match coalescer.join(key.clone()) {
+            Role::Follower(receiver) => {
+              debug!("coalescing with identical request in flight");
+              if let Some(response) = coalesce::wait(receiver).await {
+                break Ok(response)
+              }
+              // The request we waited for failed or got canceled, so we
+              // have to issue ours after all (or follow another one
+              // that got issued in the meantime).
+            },
+            Role::Leader(lead) => {
+              let result = self.dispatch(request, priority, endpoint).await;
+              if let Ok(response) = &result {
+                let () = lead.finish(response.clone());
+              }
+              break result
+            },
+          }
+        }
+      },
+      _ => self.dispatch(request, priority, endpoint).await,
+    };
+
+    let (status, headers, bytes) = result?;
+    if is_maintenance(status, &headers, &bytes) {
+      warn!(status = debug(&status), "API is down for maintenance");
+      return Err(RequestError::Maintenance {
+        status,
+        retry_after: retry_after(&headers),
+      })
+    }
+    Ok((status, headers, bytes))
+  }
+
+  /// Issue a request with the given priority to the endpoint with the
+  /// given label, recording metrics and informing the circuit breaker
+  /// about the outcome.
+  async fn dispatch<E>(
+    &self,
+    request: Request<Body>,
+    priority: Priority,
+    endpoint: &'static str,
+  ) -> Result<(StatusCode, HeaderMap, Bytes), RequestError<E>> {
+    self.schedule(priority).await;
+
+    let method = request.method().clone();
+    let start = Instant::now();
+    let result = if self.dry_run && dry_run::is_mutating(&method) {
+      self.simulate(request).await
+    } else {
+      self.fetch(request).await
+    };
+
+    let status = result.as_ref().ok().map(|(status, ..)| *status);
+    metric::request(endpoint, &method, status, start.elapsed());
+
+    if let Some(breaker) = &self.circuit_breaker {
+      let success = match status {
+        Some(status) => !status.is_server_error() && status != StatusCode::TOO_MANY_REQUESTS,
+        None => false,
+      };
+      breaker.report(endpoint, success);
+    }
+    result
+  }
+
+  /// Simulate a mutating request instead of issuing it.
+  async fn simulate<E>(
+    &self,
+    request: Request<Body>,
+  ) -> Result<(StatusCode, HeaderMap, Bytes), RequestError<E>> {
+    let (mut parts, body) = request.into_parts();
+    let body = to_bytes(body).await?;
+    info!(
+      method = display(&parts.method),
+      uri = display(&parts.uri),
+      "dry run: simulating request"
+    );
+
+    match dry_run::simulate(&parts.method, parts.uri.path(), &body) {
+      Simulation::Respond(status, body) => Ok((status, HeaderMap::new(), Bytes::from(body))),
+      Simulation::Derive(path, derive) => {
+        let query = parts.uri.query().map(str::to_string);
+        if let Some(path) = path {
+          let uri = PathAndQuery::try_from(path).ok().and_then(|path| {
+            let mut uri = parts.uri.clone().into_parts();
+            uri.path_and_query = Some(path);
+            Uri::from_parts(uri).ok()
+          });
+
+          match uri {
+            Some(uri) => parts.uri = uri,
+            None => {
+              return Err(RequestError::NotSimulated {
+                method: parts.method,
+                path: parts.uri.path().to_string(),
+              })
+            },
+          }
+        }
+        parts.method = Method::GET;
+
+        let request = Request::from_parts(parts, Body::empty());
+        let (status, headers, bytes) = self.fetch(request).await?;
+        if !status.is_success() {
+          return Ok((status, headers, bytes))
+        }
+
+        let body = derive(&bytes, query.as_deref(), &body)
+          .map_err(|err| IoError::new(ErrorKind::InvalidData, err))?;
+        Ok((status, HeaderMap::new(), Bytes::from(body)))
+      },
+      Simulation::Unsupported => Err(RequestError::NotSimulated {
+        method: parts.method,
+        path: parts.uri.path().to_string(),
+      }),
+    }
+  }
+
+  /// Issue a request and retrieve the response's status code, headers,
+  /// and body, unconditionally.
+  #[allow(clippy::cognitive_complexity)]
+  async fn fetch<E>(
+    &self,
+    request: Request<Body>,
+  ) -> Result<(StatusCode, HeaderMap, Bytes), RequestError<E>> {
     debug!("requesting");
     trace!(request = debug_request(&request));
 
@@ -288,14 +930,15 @@ impl Client {
     debug!(status = debug(&status));
     trace!(response = debug(&result));
 
-    let bytes = Self::retrieve_body::<R::Error>(result).await?;
+    let headers = result.headers().clone();
+    let bytes = Self::retrieve_body::<E>(result).await?;
     let body = bytes.as_ref();
     match from_utf8(body) {
       Ok(s) => trace!(body = display(&s)),
       Err(b) => trace!(body = display(&b)),
     }
 
-    R::evaluate(status, body).map_err(RequestError::Endpoint)
+    Ok((status, headers, bytes))
   }
 
   /// Subscribe to the given subscribable in order to receive updates.
@@ -311,13 +954,123 @@ impl Client {
   where
     S: Subscribable<Input = ApiInfo>,
   {
-    S::connect(&self.api_info).await
+    S::connect(&self.api_info()).await
+  }
+
+  /// Subscribe to the given subscribable in order to receive updates,
+  /// invoking `refresh` for new credentials should the server reject
+  /// the client's ones.
+  ///
+  /// Credentials rotated while a stream is connected cause every
+  /// subsequent attempt to reconnect with the same credentials to
+  /// fail. Instead of reporting the error right away, this method
+  /// invokes `refresh` with the rejected [`ApiInfo`] and attempts to
+  /// connect once more with the one it provides. If that succeeds, the
+  /// client adopts the new credentials for all subsequent requests, as
+  /// if by [`set_api_info`][Self::set_api_info]. The authentication
+  /// failure is reported if `refresh` provides no new credentials or
+  /// if they get rejected as well.
+  ///
+  /// Callers retrying failed connections on their own should use
+  /// [`Error::is_auth_failure`] to detect rejected credentials and
+  /// stop retrying.
+  #[instrument(level = "debug", skip(self, refresh))]
+  pub async fn subscribe_with_refresh<S, F, R>(
+    &self,
+    refresh: F,
+  ) -> Result<(S::Stream, S::Subscription), Error>
+  where
+    S: Subscribable<Input = ApiInfo>,
+    F: FnOnce(&ApiInfo) -> R,
+    R: Future<Output = Option<ApiInfo>>,
+  {
+    let api_info = self.api_info();
+    match S::connect(&api_info).await {
+      Err(err) if err.is_auth_failure() => {
+        warn!("credentials were rejected; refreshing them: {}", err);
+        match refresh(&api_info).await {
+          Some(api_info) => {
+            let (stream, subscription) = S::connect(&api_info).await?;
+            let () = self.set_api_info(api_info);
+            Ok((stream, subscription))
+          },
+          None => Err(err),
+        }
+      },
+      result => result,
+    }
+  }
+
+  /// Infer the market data entitlements of the account, e.g., which
+  /// data feed is available and how many symbols can be streamed.
+  ///
+  /// Alpaca does not report these directly and so this method issues a
+  /// request for SIP data to probe for them.
+  #[instrument(level = "debug", skip(self))]
+  pub async fn data_entitlements(
+    &self,
+  ) -> Result<Entitlements, RequestError<last_quote::GetError>> {
+    entitlements::probe(self).await
+  }
+
+  /// Retrieve the price of the most recent trade of an equity, crypto
+  /// currency pair, or option contract.
+  ///
+  /// See [`latest_price`][crate::data::price::latest_price] for
+  /// details.
+  #[instrument(level = "debug", skip(self))]
+  pub async fn latest_price(&self, symbol: &str) -> Result<Price, LatestPriceError> {
+    price::latest_price(self, symbol).await
+  }
+
+  /// Flatten the account: cancel all open orders, wait at most
+  /// `timeout` for the cancellations to be confirmed, and then close
+  /// all open positions.
+  ///
+  /// See [`flatten_all`][crate::api::v2::flatten::flatten_all] for
+  /// details.
+  #[instrument(level = "debug", skip(self))]
+  pub async fn flatten_all(&self, timeout: Duration) -> Result<Report, FlattenError> {
+    flatten::flatten_all(self, timeout).await
+  }
+
+  /// Subscribe to state changes of the client's circuit breaker.
+  ///
+  /// `None` is returned if the client was not configured with a
+  /// circuit breaker. See [`Builder::circuit_breaker`] for details.
+  #[inline]
+  pub fn circuit_events(&self) -> Option<broadcast::Receiver<CircuitEvent>> {
+    self.circuit_breaker.as_ref().map(CircuitBreaker::subscribe)
+  }
+
+  /// Check whether the client is in dry-run mode.
+  ///
+  /// See [`Builder::dry_run`] for details.
+  #[inline]
+  pub fn is_dry_run(&self) -> bool {
+    self.dry_run
   }
 
   /// Retrieve the `ApiInfo` object used by this `Client` instance.
+  ///
+  /// Because the object may be replaced at any time by means of
+  /// [`set_api_info`][Self::set_api_info], a snapshot of the current
+  /// one is returned instead of a reference.
   #[inline]
-  pub fn api_info(&self) -> &ApiInfo {
-    &self.api_info
+  pub fn api_info(&self) -> Arc<ApiInfo> {
+    self.api_info.read().unwrap().clone()
+  }
+
+  /// Replace the `ApiInfo` object used by this `Client` instance, e.g.,
+  /// to rotate API keys in a long-running service.
+  ///
+  /// Requests issued subsequently use the new credentials and URLs,
+  /// while requests already in flight are unaffected. Established
+  /// connections are kept and reused, as are rate limiting and other
+  /// request state. Streams already connected remain authenticated
+  /// with the credentials they were created with.
+  pub fn set_api_info(&self, api_info: ApiInfo) {
+    *self.api_info.write().unwrap() = Arc::new(api_info);
   }
 }
 
@@ -326,11 +1079,32 @@ impl Client {
 mod tests {
   use super::*;
 
-  use http::StatusCode;
+  use std::io::Error as IoError;
+  use std::net::SocketAddr;
+  use std::pin::Pin;
+  use std::sync::Arc;
+  use std::task::Context;
+  use std::task::Poll;
+
+  use futures::future::join;
+  use futures::TryStreamExt as _;
+
+  use http::Uri;
+
+  use hyper::service::Service;
 
   use test_log::test;
 
+  use tokio::net::TcpListener;
+  use tokio::net::TcpStream;
+
+  use url::Url;
+
+  use crate::circuit_breaker::CircuitState;
   use crate::endpoint::ApiError;
+  use crate::live;
+  use crate::test_util::MockServer;
+  use crate::test_util::Response;
   use crate::Str;
 
 
@@ -344,9 +1118,519 @@ mod tests {
     }
   }
 
+  /// Check that requests are issued over connections established by a
+  /// custom connector.
+  #[test(tokio::test)]
+  async fn custom_connector() {
+    /// A connector redirecting all connections to a fixed address.
+    #[derive(Clone)]
+    struct Redirect {
+      addr: SocketAddr,
+      count: Arc<AtomicUsize>,
+    }
+
+    impl Service<Uri> for Redirect {
+      type Response = TcpStream;
+      type Error = IoError;
+      type Future = Pin<Box<dyn Future<Output = Result<TcpStream, IoError>> + Send>>;
+
+      fn poll_ready(&mut self, _ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+      }
+
+      fn call(&mut self, _uri: Uri) -> Self::Future {
+        let _ = self.count.fetch_add(1, Ordering::SeqCst);
+        Box::pin(TcpStream::connect(self.addr))
+      }
+    }
+
+    let server = MockServer::serve([Response::new(StatusCode::NOT_FOUND)]);
+    let count = Arc::new(AtomicUsize::new(0));
+    let connector = Redirect {
+      addr: server.addr(),
+      count: Arc::clone(&count),
+    };
+    // The host is never resolved, as the connector redirects to our
+    // local server.
+    let api_info = ApiInfo::from_parts("https://example.invalid", "key", "secret").unwrap();
+    let client = Client::builder()
+      .connector(Connector::new(connector))
+      .build(api_info);
+
+    let err = client.issue::<GetNotFound>(&()).await.unwrap_err();
+    match err {
+      RequestError::Endpoint(GetNotFoundError::UnexpectedStatus(status, _)) => {
+        assert_eq!(status, StatusCode::NOT_FOUND)
+      },
+      _ => panic!("Received unexpected error: {:?}", err),
+    }
+    assert_eq!(count.load(Ordering::SeqCst), 1);
+    let _requests = server.join();
+  }
+
+  /// Check that requests are assigned the expected default priority.
+  #[test]
+  fn default_request_priority() {
+    use crate::api::v2::account;
+    use crate::data::v2::bars;
+
+    assert_eq!(Client::default_priority::<account::Get>(), Priority::Trading);
+    assert_eq!(Client::default_priority::<bars::Get>(), Priority::Backfill);
+    assert_eq!(Client::default_priority::<GetNotFound>(), Priority::Trading);
+  }
+
+  /// Check that shutting down a client cancels in-flight requests.
+  #[test(tokio::test)]
+  async fn shutdown_cancels_requests() {
+    // A server accepting connections but never responding.
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let api_info = ApiInfo::from_parts(format!("http://{}", addr), "key", "secret").unwrap();
+    let client = Client::new(api_info);
+
+    let request = client.issue::<GetNotFound>(&());
+    let shutdown = async {
+      let _connection = listener.accept().await.unwrap();
+      assert!(!client.is_shut_down());
+      client.shutdown().await
+    };
+
+    let (result, ()) = join(request, shutdown).await;
+    assert!(matches!(result, Err(RequestError::Shutdown)), "{:?}", result);
+
+    let result = client.issue::<GetNotFound>(&()).await;
+    assert!(matches!(result, Err(RequestError::Shutdown)), "{:?}", result);
+    assert!(client.is_shut_down());
+  }
+
+  /// Check that mutating requests are simulated in dry-run mode, while
+  /// read-only ones are issued.
+  #[test(tokio::test)]
+  async fn dry_run_simulates_mutations() {
+    use crate::api::v2::order;
+    use crate::api::v2::positions;
+    use crate::api::v2::watchlist;
+
+    let server = MockServer::serve([Response::json(r#"[{"symbol":"AAPL"}]"#)]);
+    let client = Client::builder().dry_run(true).build(server.api_info());
+    assert!(client.is_dry_run());
+
+    // Order submissions never reach the server.
+    let request =
+      order::OrderReqInit::default().init("AAPL", order::Side::Sell, order::Amount::quantity(1));
+    let order = client.issue::<order::Post>(&request).await.unwrap();
+    assert_eq!(order.symbol, "AAPL");
+    assert_eq!(order.status, order::Status::Accepted);
+
+    // Liquidations are simulated based on the positions actually held.
+    let request = positions::DeleteReq {
+      cancel_orders: true,
+    };
+    let statuses = client.issue::<positions::Delete>(&request).await.unwrap();
+    assert_eq!(statuses.len(), 1);
+    assert_eq!(statuses[0].symbol, "AAPL");
+
+    // Requests we cannot simulate are not issued either.
+    let request = watchlist::CreateReq {
+      name: "dry".to_string(),
+      symbols: Vec::new(),
+    };
+    let err = client.issue::<watchlist::Post>(&request).await.unwrap_err();
+    assert!(
+      matches!(&err, RequestError::NotSimulated { path, .. } if path == "/v2/watchlists"),
+      "{:?}",
+      err
+    );
+
+    let requests = server.join();
+    assert!(
+      requests[0].line.starts_with("GET /v2/positions "),
+      "{:?}",
+      requests
+    );
+  }
+
+  /// Check that the circuit breaker fails requests fast after repeated
+  /// server errors.
+  #[test(tokio::test)]
+  async fn circuit_breaker_fails_fast() {
+    let body = r#"{"code":50300000,"message":"service unavailable"}"#;
+    let server = MockServer::serve([Response::new(StatusCode::SERVICE_UNAVAILABLE).body(body)]);
+    let client = Client::builder()
+      .circuit_breaker(1, Duration::from_secs(60))
+      .build(server.api_info());
+    let mut events = client.circuit_events().unwrap();
+
+    let err = client.issue::<GetNotFound>(&()).await.unwrap_err();
+    assert!(matches!(err, RequestError::Endpoint(..)), "{:?}", err);
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.state, CircuitState::Open);
+
+    let err = client.issue::<GetNotFound>(&()).await.unwrap_err();
+    assert!(
+      matches!(err, RequestError::CircuitOpen(endpoint) if endpoint == event.endpoint),
+      "{:?}",
+      err
+    );
+    let _requests = server.join();
+  }
+
+  /// Check that a maintenance page is reported as such while still
+  /// counting as a failure towards the circuit breaker.
+  #[test(tokio::test)]
+  async fn circuit_breaker_trips_on_maintenance() {
+    let server = MockServer::serve([Response::new(StatusCode::SERVICE_UNAVAILABLE)]);
+    let client = Client::builder()
+      .circuit_breaker(1, Duration::from_secs(60))
+      .build(server.api_info());
+    let mut events = client.circuit_events().unwrap();
+
+    let err = client.issue::<GetNotFound>(&()).await.unwrap_err();
+    let unavailable = StatusCode::SERVICE_UNAVAILABLE;
+    assert!(
+      matches!(err, RequestError::Maintenance { status, .. } if status == unavailable),
+      "{:?}",
+      err
+    );
+    let event = events.try_recv().unwrap();
+    assert_eq!(event.state, CircuitState::Open);
+
+    let err = client.issue::<GetNotFound>(&()).await.unwrap_err();
+    assert!(matches!(err, RequestError::CircuitOpen(..)), "{:?}", err);
+
+    let requests = server.join();
+    assert_eq!(requests.len(), 1);
+  }
+
+  /// Check that we can retrieve all items of a paginated endpoint.
+  #[test(tokio::test)]
+  async fn issue_paged_requests() {
+    use chrono::DateTime;
+    use num_decimal::Num;
+
+    use crate::data::v2::bars;
+
+    let server = MockServer::serve([
+      Response::json(
+        r#"{"bars":[{"t":"2022-02-01T14:30:00Z","o":1,"h":1,"l":1,"c":1,"v":1}],"symbol":"SPY","next_page_token":"abc"}"#,
+      ),
+      Response::json(
+        r#"{"bars":[{"t":"2022-02-01T14:31:00Z","o":2,"h":2,"l":2,"c":2,"v":2}],"symbol":"SPY","next_page_token":null}"#,
+      ),
+    ]);
+    let client = server.client();
+    let start = DateTime::parse_from_rfc3339("2022-02-01T00:00:00Z").unwrap();
+    let end = DateTime::parse_from_rfc3339("2022-02-02T00:00:00Z").unwrap();
+    let request = bars::BarsReqInit::default().init(
+      "SPY",
+      start.into(),
+      end.into(),
+      bars::TimeFrame::OneMinute,
+    );
+
+    let bars = client
+      .issue_paged::<bars::Get>(request)
+      .try_collect::<Vec<_>>()
+      .await
+      .unwrap();
+    assert_eq!(bars.len(), 2);
+    assert_eq!(bars[1].close, Num::from(2));
+
+    let requests = server.join();
+    assert!(!requests[0].line.contains("page_token"), "{:?}", requests);
+    let line = &requests[1].line;
+    assert!(line.contains("page_token=abc"), "{}", line);
+  }
+
+  /// Check that identical GET requests in flight are coalesced into a
+  /// single one.
+  #[test(tokio::test)]
+  async fn coalesce_identical_requests() {
+    // Both requests are registered with the coalescer when first
+    // polled, before the first one can possibly complete. The server
+    // only accepts a single connection, so a second request would never
+    // be answered.
+    let server = MockServer::serve([Response::json("{}")]);
+    let client = Client::builder()
+      .coalesce_requests(true)
+      .build(server.api_info());
+
+    let first = client.issue_raw::<GetNotFound>(&(), HeaderMap::new());
+    let second = client.issue_raw::<GetNotFound>(&(), HeaderMap::new());
+    let (first, second) = join(first, second).await;
+    let (status, _, body) = first.unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, Bytes::from("{}"));
+    assert_eq!(second.unwrap().2, body);
+
+    let requests = server.join();
+    assert_eq!(requests.len(), 1);
+  }
+
+  /// Check that GET requests with differing headers are not coalesced.
+  #[test(tokio::test)]
+  async fn coalesce_only_same_headers() {
+    let server = MockServer::serve([Response::json("{}"), Response::json("{}")]);
+    let client = Client::builder()
+      .coalesce_requests(true)
+      .build(server.api_info());
+
+    let mut headers = HeaderMap::new();
+    let _prev = headers.insert("x-test", HeaderValue::from_static("1"));
+    let first = client.issue_raw::<GetNotFound>(&(), HeaderMap::new());
+    let second = client.issue_raw::<GetNotFound>(&(), headers);
+    let (first, second) = join(first, second).await;
+    let _response = first.unwrap();
+    let _response = second.unwrap();
+
+    let requests = server.join();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+      requests
+        .iter()
+        .filter(|request| request.header("x-test").is_some())
+        .count(),
+      1
+    );
+  }
+
+  /// Check that requests coalesced with a failing one are issued after
+  /// all.
+  #[test(tokio::test)]
+  async fn coalesce_reissue_on_failure() {
+    // A response with conflicting content lengths can't be parsed.
+    let invalid = Response::json("{}").header("content-length", "42");
+    let server = MockServer::serve([invalid, Response::json("{}")]);
+    let client = Client::builder()
+      .coalesce_requests(true)
+      .build(server.api_info());
+
+    let first = client.issue_raw::<GetNotFound>(&(), HeaderMap::new());
+    let second = client.issue_raw::<GetNotFound>(&(), HeaderMap::new());
+    let (first, second) = join(first, second).await;
+    assert!(matches!(first, Err(RequestError::Hyper(..))), "{:?}", first);
+    assert_eq!(second.unwrap().2, Bytes::from("{}"));
+
+    let requests = server.join();
+    assert_eq!(requests.len(), 2);
+  }
+
+  /// Check that we detect maintenance pages served instead of JSON.
+  #[test]
+  fn detect_maintenance() {
+    let mut headers = HeaderMap::new();
+    let status = StatusCode::SERVICE_UNAVAILABLE;
+    assert!(is_maintenance(status, &headers, b""));
+    assert!(is_maintenance(status, &headers, b"upstream connect error"));
+    assert!(!is_maintenance(
+      status,
+      &headers,
+      br#"{"code":50300000,"message":"unavailable"}"#
+    ));
+
+    let status = StatusCode::BAD_GATEWAY;
+    assert!(is_maintenance(
+      status,
+      &headers,
+      b"\n<!DOCTYPE html><html></html>"
+    ));
+    assert!(!is_maintenance(status, &headers, b""));
+    assert!(!is_maintenance(StatusCode::OK, &headers, b"[]"));
+
+    let _prev = headers.insert(
+      CONTENT_TYPE,
+      HeaderValue::from_static("text/html; charset=utf-8"),
+    );
+    assert!(is_maintenance(
+      StatusCode::OK,
+      &headers,
+      b"Down for maintenance"
+    ));
+  }
+
+  /// Check that a maintenance page is reported as such, along with the
+  /// indicated retry delay.
+  #[test(tokio::test)]
+  async fn report_maintenance() {
+    let server = MockServer::serve([Response::new(StatusCode::SERVICE_UNAVAILABLE)
+      .header("content-type", "text/html")
+      .header("retry-after", "120")
+      .body("<html><body>We'll be back soon!</body></html>")]);
+    let client = server.client();
+    let err = client.issue::<GetNotFound>(&()).await.unwrap_err();
+    match err {
+      RequestError::Maintenance {
+        status,
+        retry_after,
+      } => {
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(retry_after, Some(Duration::from_secs(120)));
+      },
+      _ => panic!("unexpected error: {:?}", err),
+    }
+    let _requests = server.join();
+  }
+
+  /// Check that credentials get refreshed when the server rejects
+  /// them upon subscribing to a stream.
+  #[test(tokio::test)]
+  async fn subscribe_with_refreshed_credentials() {
+    use futures::SinkExt as _;
+    use futures::StreamExt as _;
+
+    use websocket_util::test::mock_server;
+    use websocket_util::tungstenite::Message;
+
+    use crate::api::v2::updates::OrderUpdates;
+
+    let rejecting = mock_server(|mut stream| async move {
+      let _auth = stream.next().await.unwrap()?;
+      let response =
+        r#"{"stream":"authorization","data":{"action":"authenticate","status":"unauthorized"}}"#;
+      stream.send(Message::Text(response.to_string())).await?;
+      let _next = stream.next().await;
+      Ok(())
+    })
+    .await;
+    let accepting = mock_server(|mut stream| async move {
+      let auth = stream.next().await.unwrap()?;
+      assert_eq!(
+        auth,
+        Message::Text(
+          r#"{"action":"authenticate","data":{"key_id":"new-key","secret_key":"new-secret"}}"#
+            .to_string()
+        )
+      );
+      let response =
+        r#"{"stream":"authorization","data":{"action":"authenticate","status":"authorized"}}"#;
+      stream.send(Message::Text(response.to_string())).await?;
+      let _listen = stream.next().await.unwrap()?;
+      let response = r#"{"stream":"listening","data":{"streams":["trade_updates"]}}"#;
+      stream.send(Message::Text(response.to_string())).await?;
+      let _next = stream.next().await;
+      Ok(())
+    })
+    .await;
+
+    let mut api_info = ApiInfo::from_parts("http://example.com", "key", "secret").unwrap();
+    api_info.api_stream_url = Url::parse(&format!("ws://{}", rejecting)).unwrap();
+    let client = Client::new(api_info);
+
+    let result = client
+      .subscribe_with_refresh::<OrderUpdates, _, _>(|api_info| {
+        let mut api_info = api_info.clone();
+        api_info.api_stream_url = Url::parse(&format!("ws://{}", accepting)).unwrap();
+        api_info.key_id = "new-key".to_string();
+        api_info.secret = "new-secret".to_string();
+        async move { Some(api_info) }
+      })
+      .await;
+    assert!(result.is_ok());
+    assert_eq!(client.api_info().key_id, "new-key");
+  }
+
+  /// Check that requests use the credentials most recently set.
+  #[test]
+  fn rotate_credentials() {
+    let api_info = ApiInfo::from_parts("http://example.com", "key", "secret").unwrap();
+    let client = Client::new(api_info);
+    let request = client.request::<GetNotFound>(&()).unwrap();
+    assert_eq!(request.headers()[HDR_KEY_ID], "key");
+    assert_eq!(request.headers()[HDR_SECRET], "secret");
+
+    let api_info = ApiInfo::from_parts("http://example.com", "new-key", "new-secret").unwrap();
+    let () = client.set_api_info(api_info);
+    assert_eq!(client.api_info().key_id, "new-key");
+
+    let request = client.request::<GetNotFound>(&()).unwrap();
+    assert_eq!(request.headers()[HDR_KEY_ID], "new-key");
+    assert_eq!(request.headers()[HDR_SECRET], "new-secret");
+  }
+
+  /// Check that requests to the Data API are directed to the configured
+  /// data base URL.
+  #[test]
+  fn request_configured_data_url() {
+    use crate::api::v2::account;
+    use crate::api::API_BASE_URL;
+    use crate::data::v2::last_quote;
+
+    let api_info = ApiInfo::from_parts(API_BASE_URL, "key", "secret")
+      .unwrap()
+      .with_data_base_url("http://localhost:8080")
+      .unwrap();
+    let client = Client::new(api_info);
+
+    let input = last_quote::LastQuoteReqInit::default().init("SPY");
+    let request = client.request::<last_quote::Get>(&input).unwrap();
+    assert_eq!(
+      request.uri().to_string(),
+      "http://localhost:8080/v2/stocks/SPY/quotes/latest?"
+    );
+
+    let request = client.request::<account::Get>(&()).unwrap();
+    assert_eq!(
+      request.uri().to_string(),
+      "https://paper-api.alpaca.markets/v2/account"
+    );
+  }
+
+  /// Check that we can decode a gzip encoded body.
+  #[cfg(feature = "gzip")]
+  #[test(tokio::test)]
+  async fn decode_gzip_body() {
+    use async_compression::futures::bufread::GzipEncoder;
+    use futures::AsyncReadExt as _;
+
+    let body = br#"{"symbol":"AAPL","trades":[]}"#;
+    let mut encoded = Vec::new();
+    let _count = GzipEncoder::new(&body[..])
+      .read_to_end(&mut encoded)
+      .await
+      .unwrap();
+
+    let encoding = HeaderValue::from_static("gzip");
+    let decoded = decode_body(Some(&encoding), encoded.into()).await.unwrap();
+    assert_eq!(decoded.as_ref(), &body[..]);
+  }
+
+  /// Check that we can decode a deflate encoded body.
+  #[cfg(feature = "deflate")]
+  #[test(tokio::test)]
+  async fn decode_deflate_body() {
+    use async_compression::futures::bufread::ZlibEncoder;
+    use futures::AsyncReadExt as _;
+
+    let body = br#"{"symbol":"AAPL","trades":[]}"#;
+    let mut encoded = Vec::new();
+    let _count = ZlibEncoder::new(&body[..])
+      .read_to_end(&mut encoded)
+      .await
+      .unwrap();
+
+    let encoding = HeaderValue::from_static("deflate");
+    let decoded = decode_body(Some(&encoding), encoded.into()).await.unwrap();
+    assert_eq!(decoded.as_ref(), &body[..]);
+  }
+
+  /// Check that bodies with an unknown or no encoding are passed
+  /// through unchanged.
+  #[cfg(any(feature = "gzip", feature = "deflate"))]
+  #[test(tokio::test)]
+  async fn decode_unencoded_body() {
+    let body = Bytes::from_static(b"foobar");
+    let decoded = decode_body(None, body.clone()).await.unwrap();
+    assert_eq!(decoded, body);
+
+    let encoding = HeaderValue::from_static("br");
+    let decoded = decode_body(Some(&encoding), body.clone()).await.unwrap();
+    assert_eq!(decoded, body);
+  }
+
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn unexpected_status_code_return() {
-    let api_info = ApiInfo::from_env().unwrap();
+    let api_info = live::api_info().unwrap();
     let client = Client::builder().max_idle_per_host(0).build(api_info);
     let result = client.issue::<GetNotFound>(&()).await;
     let err = result.unwrap_err();
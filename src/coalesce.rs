@@ -0,0 +1,197 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::Mutex;
+
+use http::HeaderMap;
+use http::Request;
+use http::StatusCode;
+
+use hyper::body::Bytes;
+
+use tokio::sync::broadcast;
+
+
+/// A response, comprised of status code, headers, and body.
+pub(crate) type Response = (StatusCode, HeaderMap, Bytes);
+
+
+/// Derive the key identifying the given request for the purpose of
+/// coalescing.
+///
+/// Besides method and URI, all headers are taken into account, so that
+/// requests are only coalesced if they are issued with the same
+/// credentials and caller provided headers.
+pub(crate) fn key<B>(request: &Request<B>) -> String {
+  let mut headers = request
+    .headers()
+    .iter()
+    .map(|(name, value)| (name.as_str(), value.as_bytes()))
+    .collect::<Vec<_>>();
+  let () = headers.sort();
+
+  let mut key = format!("{} {}", request.method(), request.uri());
+  for (name, value) in headers {
+    let _result = write!(key, "\n{}: {}", name, String::from_utf8_lossy(value));
+  }
+  key
+}
+
+
+/// The role of a request with respect to other identical requests in
+/// flight.
+pub(crate) enum Role<'c> {
+  /// The request is to be issued and its response be shared, by means
+  /// of the contained [`Lead`].
+  Leader(Lead<'c>),
+  /// An identical request is already in flight and its response can be
+  /// waited for using the contained receiver.
+  Follower(broadcast::Receiver<Response>),
+}
+
+
+/// A guard representing a request whose response is shared with
+/// others.
+///
+/// If the guard is dropped without a response being reported (e.g.,
+/// because the request failed or got canceled), all waiting requests
+/// are released and have to be issued by themselves.
+pub(crate) struct Lead<'c> {
+  coalescer: &'c Coalescer,
+  key: String,
+  sender: broadcast::Sender<Response>,
+}
+
+impl Lead<'_> {
+  /// Share the response to the request with all waiting ones.
+  pub(crate) fn finish(self, response: Response) {
+    let () = self.coalescer.remove(&self.key, &self.sender);
+    // There may not be anybody waiting, which is fine.
+    let _result = self.sender.send(response);
+  }
+}
+
+impl Drop for Lead<'_> {
+  fn drop(&mut self) {
+    let () = self.coalescer.remove(&self.key, &self.sender);
+  }
+}
+
+
+/// A type for coalescing identical requests in flight into one.
+#[derive(Debug, Default)]
+pub(crate) struct Coalescer {
+  /// The requests currently in flight, indexed by key.
+  in_flight: Mutex<HashMap<String, broadcast::Sender<Response>>>,
+}
+
+impl Coalescer {
+  /// Determine the role of a request identified by the given key.
+  pub(crate) fn join(&self, key: String) -> Role<'_> {
+    let mut in_flight = self.in_flight.lock().unwrap();
+    if let Some(sender) = in_flight.get(&key) {
+      return Role::Follower(sender.subscribe())
+    }
+
+    let (sender, _) = broadcast::channel(1);
+    let _prev = in_flight.insert(key.clone(), sender.clone());
+    Role::Leader(Lead {
+      coalescer: self,
+      key,
+      sender,
+    })
+  }
+
+  /// Remove the in-flight request with the given key, provided it is
+  /// still the one represented by `sender`.
+  fn remove(&self, key: &str, sender: &broadcast::Sender<Response>) {
+    let mut in_flight = self.in_flight.lock().unwrap();
+    if matches!(in_flight.get(key), Some(current) if current.same_channel(sender)) {
+      let _sender = in_flight.remove(key);
+    }
+  }
+}
+
+
+/// Wait for the response to the request being followed.
+///
+/// `None` is returned if the request failed or got canceled without
+/// reporting a response.
+pub(crate) async fn wait(mut receiver: broadcast::Receiver<Response>) -> Option<Response> {
+  receiver.recv().await.ok()
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use test_log::test;
+
+
+  /// Create a `Response` with the given body.
+  fn response(body: &'static str) -> Response {
+    (StatusCode::OK, HeaderMap::new(), Bytes::from(body))
+  }
+
+
+  /// Check that requests differing in method or headers get different
+  /// keys.
+  #[test]
+  fn derive_key() {
+    let request = |method: &str, key_id: &str| {
+      Request::builder()
+        .method(method)
+        .uri("http://localhost/v2/orders?status=open")
+        .header("apca-api-key-id", key_id)
+        .body(())
+        .unwrap()
+    };
+
+    assert_eq!(key(&request("GET", "a")), key(&request("GET", "a")));
+    assert_ne!(key(&request("GET", "a")), key(&request("HEAD", "a")));
+    assert_ne!(key(&request("GET", "a")), key(&request("GET", "b")));
+  }
+
+  /// Check that identical requests share the response to the first
+  /// one.
+  #[test(tokio::test)]
+  async fn share_outcome() {
+    let coalescer = Coalescer::default();
+    let lead = match coalescer.join("a".to_string()) {
+      Role::Leader(lead) => lead,
+      Role::Follower(..) => panic!("unexpected follower"),
+    };
+    let receiver = match coalescer.join("a".to_string()) {
+      Role::Follower(receiver) => receiver,
+      Role::Leader(..) => panic!("unexpected leader"),
+    };
+    // Requests with a different key are independent.
+    assert!(matches!(coalescer.join("b".to_string()), Role::Leader(..)));
+
+    let () = lead.finish(response("body"));
+    let response = wait(receiver).await.unwrap();
+    assert_eq!(response.2, Bytes::from("body"));
+
+    // Once finished, a subsequent request is issued anew.
+    assert!(matches!(coalescer.join("a".to_string()), Role::Leader(..)));
+  }
+
+  /// Check that followers are released when the leading request gets
+  /// dropped.
+  #[test(tokio::test)]
+  async fn release_on_drop() {
+    let coalescer = Coalescer::default();
+    let lead = coalescer.join("a".to_string());
+    let receiver = match coalescer.join("a".to_string()) {
+      Role::Follower(receiver) => receiver,
+      Role::Leader(..) => panic!("unexpected leader"),
+    };
+
+    drop(lead);
+    assert_eq!(wait(receiver).await, None);
+    assert!(matches!(coalescer.join("a".to_string()), Role::Leader(..)));
+  }
+}
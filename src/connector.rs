@@ -0,0 +1,146 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::error::Error as StdError;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::io::Result as IoResult;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::Context;
+use std::task::Poll;
+
+use futures::future::poll_fn;
+use futures::future::BoxFuture;
+use futures::FutureExt as _;
+
+use http::Uri;
+
+use hyper::client::connect::Connected;
+use hyper::client::connect::Connection;
+use hyper::service::Service;
+
+use tokio::io::AsyncRead;
+use tokio::io::AsyncWrite;
+use tokio::io::ReadBuf;
+
+
+/// The type of error a [`Connector`] may report.
+type BoxError = Box<dyn StdError + Send + Sync>;
+
+/// The type of function establishing a connection.
+type ConnectFn = dyn Fn(Uri) -> BoxFuture<'static, Result<Io, BoxError>> + Send + Sync;
+
+
+/// A trait for the connections a [`Connector`] establishes.
+trait Stream: AsyncRead + AsyncWrite + Connection + Send + Unpin {}
+
+impl<T> Stream for T where T: AsyncRead + AsyncWrite + Connection + Send + Unpin {}
+
+
+/// A type-erased connection as established by a [`Connector`].
+#[doc(hidden)]
+pub struct Io(Box<dyn Stream>);
+
+impl AsyncRead for Io {
+  fn poll_read(
+    mut self: Pin<&mut Self>,
+    ctx: &mut Context<'_>,
+    buf: &mut ReadBuf<'_>,
+  ) -> Poll<IoResult<()>> {
+    Pin::new(&mut *self.0).poll_read(ctx, buf)
+  }
+}
+
+impl AsyncWrite for Io {
+  fn poll_write(
+    mut self: Pin<&mut Self>,
+    ctx: &mut Context<'_>,
+    buf: &[u8],
+  ) -> Poll<IoResult<usize>> {
+    Pin::new(&mut *self.0).poll_write(ctx, buf)
+  }
+
+  fn poll_flush(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<IoResult<()>> {
+    Pin::new(&mut *self.0).poll_flush(ctx)
+  }
+
+  fn poll_shutdown(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<IoResult<()>> {
+    Pin::new(&mut *self.0).poll_shutdown(ctx)
+  }
+}
+
+impl Debug for Io {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+    fmt.debug_struct("Io").finish_non_exhaustive()
+  }
+}
+
+impl Connection for Io {
+  fn connected(&self) -> Connected {
+    self.0.connected()
+  }
+}
+
+
+/// A connector establishing the connections over which a
+/// [`Client`][crate::Client] issues HTTP requests.
+///
+/// By default, a client connects via TCP and uses TLS as per the
+/// configured backend (see the [`tls`][crate::tls] module). A custom
+/// connector allows for routing traffic through other transports, e.g.,
+/// a Unix domain socket leading to a local mock server, or for
+/// instrumenting connections, e.g., for injecting faults.
+///
+/// Any [`hyper`] compatible connector, i.e., a [`Service`] turning a
+/// [`Uri`] into a connection, can be used. It is configured via
+/// the `connector` method of the builder returned by
+/// [`Client::builder`][crate::Client::builder].
+#[derive(Clone)]
+pub struct Connector(Arc<ConnectFn>);
+
+impl Connector {
+  /// Create a new `Connector` wrapping the provided `hyper` compatible
+  /// one.
+  pub fn new<C>(connector: C) -> Self
+  where
+    C: Service<Uri> + Clone + Send + Sync + 'static,
+    C::Response: AsyncRead + AsyncWrite + Connection + Send + Unpin + 'static,
+    C::Future: Send + 'static,
+    C::Error: Into<BoxError>,
+  {
+    let connect = move |uri| {
+      let mut connector = connector.clone();
+      async move {
+        let () = poll_fn(|ctx| connector.poll_ready(ctx))
+          .await
+          .map_err(Into::into)?;
+        let stream = connector.call(uri).await.map_err(Into::into)?;
+        Ok(Io(Box::new(stream)))
+      }
+      .boxed()
+    };
+    Self(Arc::new(connect))
+  }
+}
+
+impl Debug for Connector {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+    fmt.debug_struct("Connector").finish_non_exhaustive()
+  }
+}
+
+impl Service<Uri> for Connector {
+  type Response = Io;
+  type Error = BoxError;
+  type Future = BoxFuture<'static, Result<Io, BoxError>>;
+
+  fn poll_ready(&mut self, _ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    Poll::Ready(Ok(()))
+  }
+
+  fn call(&mut self, uri: Uri) -> Self::Future {
+    (self.0)(uri)
+  }
+}
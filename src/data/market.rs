@@ -0,0 +1,344 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use async_trait::async_trait;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use futures::future::ready;
+use futures::stream::BoxStream;
+use futures::FutureExt as _;
+use futures::StreamExt as _;
+use futures::TryStreamExt as _;
+
+use crate::data::model::Bar;
+use crate::data::price::latest_price_of;
+use crate::data::price::AssetKind;
+use crate::data::price::LatestPriceError;
+use crate::data::price::Price;
+use crate::data::v1beta1::options;
+use crate::data::v1beta3::crypto;
+use crate::data::v2::bars;
+use crate::data::v2::bars::TimeFrame;
+use crate::data::v2::stream;
+use crate::data::v2::Feed;
+use crate::Client;
+use crate::Error;
+use crate::RequestError;
+
+
+/// A stream of bars, each accompanied by the symbol it is for.
+pub type BarStream<E> = BoxStream<'static, Result<(String, Bar), E>>;
+
+
+/// A trait for retrieving historical market data of a certain asset
+/// class.
+#[async_trait]
+pub trait HistoricalData {
+  /// The error reported when retrieving historical data.
+  type Error;
+
+  /// Retrieve all bars for the given symbol in the provided time
+  /// range, in chronological order.
+  async fn bars(
+    &self,
+    client: &Client,
+    symbol: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    timeframe: TimeFrame,
+  ) -> Result<Vec<Bar>, Self::Error>;
+}
+
+
+/// A trait for retrieving the latest market data of a certain asset
+/// class.
+#[async_trait]
+pub trait LatestData {
+  /// The error reported when retrieving the latest data.
+  type Error;
+
+  /// Retrieve the price of the most recent trade of the given symbol.
+  async fn latest_price(&self, client: &Client, symbol: &str) -> Result<Price, Self::Error>;
+}
+
+
+/// A trait for streaming realtime market data of a certain asset
+/// class.
+///
+/// Currently only implemented for [`Stocks`].
+#[async_trait]
+pub trait StreamingData {
+  /// The error reported when streaming data.
+  type Error;
+
+  /// Subscribe to bars for the given symbols.
+  ///
+  /// The returned stream owns the underlying connection, which is
+  /// closed once the stream is dropped.
+  async fn stream_bars(
+    &self,
+    client: &Client,
+    symbols: &[String],
+  ) -> Result<BarStream<Self::Error>, Self::Error>;
+}
+
+
+/// A trait combining historical and latest market data retrieval,
+/// available for all supported asset classes.
+///
+/// Strategy code generic over `MarketData` can be run against stocks,
+/// crypto currencies, and options alike. Realtime streaming is not
+/// part of it, as it is only available for stocks through
+/// [`StreamingData`].
+pub trait MarketData: HistoricalData + LatestData {}
+
+impl<T> MarketData for T where T: HistoricalData + LatestData {}
+
+
+/// Market data for US equities.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Stocks {
+  /// The data feed to use. If not set, the API's default is used for
+  /// historical data and the IEX feed for streaming.
+  pub feed: Option<Feed>,
+}
+
+#[async_trait]
+impl HistoricalData for Stocks {
+  type Error = RequestError<bars::GetError>;
+
+  async fn bars(
+    &self,
+    client: &Client,
+    symbol: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    timeframe: TimeFrame,
+  ) -> Result<Vec<Bar>, Self::Error> {
+    let request = bars::BarsReqInit {
+      feed: self.feed,
+      ..Default::default()
+    }
+    .init(symbol, start, end, timeframe);
+
+    client
+      .issue_paged::<bars::Get>(request)
+      .map_ok(Bar::from)
+      .try_collect()
+      .await
+  }
+}
+
+#[async_trait]
+impl LatestData for Stocks {
+  type Error = LatestPriceError;
+
+  async fn latest_price(&self, client: &Client, symbol: &str) -> Result<Price, Self::Error> {
+    latest_price_of(client, symbol, AssetKind::Equity).await
+  }
+}
+
+/// Bars are streamed from the realtime data feed, with SIP data used
+/// only if explicitly requested.
+#[async_trait]
+impl StreamingData for Stocks {
+  type Error = Error;
+
+  #[allow(clippy::result_large_err)]
+  async fn stream_bars(
+    &self,
+    client: &Client,
+    symbols: &[String],
+  ) -> Result<BarStream<Self::Error>, Self::Error> {
+    let (mut stream, mut subscription) = match self.feed {
+      Some(Feed::SIP) => {
+        client
+          .subscribe::<stream::RealtimeData<stream::SIP>>()
+          .await?
+      },
+      Some(Feed::IEX) | None => {
+        client
+          .subscribe::<stream::RealtimeData<stream::IEX>>()
+          .await?
+      },
+    };
+
+    let mut data = stream::MarketData::default();
+    let () = data.set_bars(symbols.to_vec());
+
+    let subscribe = subscription.subscribe(&data).boxed().fuse();
+    let () = stream::drive(subscribe, &mut stream)
+      .await
+      .map_err(|result| {
+        result
+          .map(|result| Error::Json(result.unwrap_err()))
+          .map_err(Error::WebSocket)
+          .unwrap_or_else(|err| err)
+      })???;
+
+    let stream = stream
+      .map(move |result| {
+        // The subscription has to stay alive for as long as the stream
+        // is in use.
+        let _subscription = &subscription;
+        result
+          .map_err(Error::WebSocket)
+          .and_then(|result| result.map_err(Error::Json))
+      })
+      .try_filter_map(|data| {
+        let bar = match data {
+          stream::Data::Bar(bar) => Some((bar.symbol.clone(), Bar::from(bar))),
+          _ => None,
+        };
+        ready(Ok(bar))
+      })
+      .boxed();
+
+    Ok(stream)
+  }
+}
+
+
+/// Market data for crypto currencies.
+///
+/// Realtime streaming of crypto currency data is not supported.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Crypto;
+
+#[async_trait]
+impl HistoricalData for Crypto {
+  type Error = RequestError<crypto::GetBarsError>;
+
+  async fn bars(
+    &self,
+    client: &Client,
+    symbol: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    timeframe: TimeFrame,
+  ) -> Result<Vec<Bar>, Self::Error> {
+    let request = crypto::BarsReqInit::default().init([symbol], start, end, timeframe);
+
+    client
+      .issue_paged::<crypto::GetBars>(request)
+      .map_ok(|(_symbol, bar)| Bar::from(bar))
+      .try_collect()
+      .await
+  }
+}
+
+#[async_trait]
+impl LatestData for Crypto {
+  type Error = LatestPriceError;
+
+  async fn latest_price(&self, client: &Client, symbol: &str) -> Result<Price, Self::Error> {
+    latest_price_of(client, symbol, AssetKind::Crypto).await
+  }
+}
+
+
+/// Market data for option contracts, identified by their OCC symbols.
+///
+/// Realtime streaming of option data is not supported.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Options;
+
+#[async_trait]
+impl HistoricalData for Options {
+  type Error = RequestError<options::GetBarsError>;
+
+  async fn bars(
+    &self,
+    client: &Client,
+    symbol: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    timeframe: TimeFrame,
+  ) -> Result<Vec<Bar>, Self::Error> {
+    let request = options::BarsReqInit::default().init([symbol], start, end, timeframe);
+
+    client
+      .issue_paged::<options::GetBars>(request)
+      .map_ok(|(_symbol, bar)| Bar::from(bar))
+      .try_collect()
+      .await
+  }
+}
+
+#[async_trait]
+impl LatestData for Options {
+  type Error = LatestPriceError;
+
+  async fn latest_price(&self, client: &Client, symbol: &str) -> Result<Price, Self::Error> {
+    latest_price_of(client, symbol, AssetKind::Option).await
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use num_decimal::Num;
+
+  use test_log::test;
+
+  use crate::test_util::MockServer;
+  use crate::test_util::Response;
+
+
+  /// Compute the total volume traded over the given time range, in a
+  /// fashion agnostic to the asset class.
+  async fn total_volume<M>(
+    market: &M,
+    client: &Client,
+    symbol: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+  ) -> Result<Num, <M as HistoricalData>::Error>
+  where
+    M: MarketData + Sync,
+  {
+    let bars = market
+      .bars(client, symbol, start, end, TimeFrame::OneDay)
+      .await?;
+    let volume = bars
+      .into_iter()
+      .fold(Num::from(0), |sum, bar| sum + bar.volume);
+    Ok(volume)
+  }
+
+
+  /// Check that historical crypto currency bars are retrieved across
+  /// multiple pages and with fractional volume.
+  #[test(tokio::test)]
+  async fn retrieve_paged_crypto_bars() {
+    let server = MockServer::serve([
+      Response::json(
+        r#"{"bars":{"BTC/USD":[{"t":"2022-12-19T00:00:00Z","o":1,"h":2,"l":1,"c":2,"v":0.5}]},"next_page_token":"abc"}"#,
+      ),
+      Response::json(
+        r#"{"bars":{"BTC/USD":[{"t":"2022-12-20T00:00:00Z","o":2,"h":3,"l":2,"c":3,"v":1.25}]},"next_page_token":null}"#,
+      ),
+    ]);
+    let client = server.client();
+
+    let start = DateTime::from_str("2022-12-19T00:00:00Z").unwrap();
+    let end = DateTime::from_str("2022-12-21T00:00:00Z").unwrap();
+    let volume = total_volume(&Crypto, &client, "BTC/USD", start, end)
+      .await
+      .unwrap();
+    assert_eq!(volume, Num::new(175, 100));
+
+    let requests = server.join();
+    for (i, request) in requests.iter().enumerate() {
+      let line = &request.line;
+      assert!(line.starts_with("GET /v1beta3/crypto/us/bars?"), "{}", line);
+      assert_eq!(line.contains("page_token=abc"), i == 1, "{}", line);
+    }
+  }
+}
@@ -1,6 +1,16 @@
 // Copyright (C) 2020-2022 The apca Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+/// Traits for retrieving market data independent of the asset class.
+pub mod market;
+/// Version agnostic definitions of core market data types.
+pub mod model;
+/// Functionality for retrieving the latest price of any kind of asset.
+pub mod price;
+/// Definitions for the v1beta1 version of the Alpaca Data API.
+pub mod v1beta1;
+/// Definitions for the v1beta3 version of the Alpaca Data API.
+pub mod v1beta3;
 /// Definitions for the second version of the Alpaca Data API.
 pub mod v2;
 
@@ -0,0 +1,291 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use num_decimal::Num;
+
+use crate::data::v1beta1::options;
+use crate::data::v1beta3::crypto;
+use crate::data::v2;
+
+
+/// A trade, independent of the version of the API it was reported by.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Trade {
+  /// The time at which the trade happened.
+  pub time: DateTime<Utc>,
+  /// The price at which the trade happened.
+  pub price: Num,
+  /// The number of shares traded.
+  pub size: u64,
+  /// The exchange at which the trade happened.
+  pub exchange: char,
+  /// The conditions of the trade.
+  pub conditions: Vec<char>,
+}
+
+impl From<v2::trades::Trade> for Trade {
+  fn from(trade: v2::trades::Trade) -> Self {
+    Self {
+      time: trade.timestamp,
+      price: trade.price,
+      size: trade.size,
+      exchange: trade.exchange,
+      conditions: trade.trade_conditions,
+    }
+  }
+}
+
+impl From<v2::stream::Trade> for Trade {
+  fn from(trade: v2::stream::Trade) -> Self {
+    Self {
+      time: trade.timestamp,
+      price: trade.trade_price,
+      size: trade.trade_size,
+      exchange: trade.exchange,
+      conditions: trade.trade_conditions,
+    }
+  }
+}
+
+
+/// A quote, independent of the version of the API it was reported by.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Quote {
+  /// The time at which the quote was reported.
+  pub time: DateTime<Utc>,
+  /// The bid price.
+  pub bid_price: Num,
+  /// The bid size.
+  pub bid_size: u64,
+  /// The ask price.
+  pub ask_price: Num,
+  /// The ask size.
+  pub ask_size: u64,
+}
+
+impl From<v2::last_quote::Quote> for Quote {
+  fn from(quote: v2::last_quote::Quote) -> Self {
+    Self {
+      time: quote.time,
+      bid_price: quote.bid_price,
+      bid_size: quote.bid_size,
+      ask_price: quote.ask_price,
+      ask_size: quote.ask_size,
+    }
+  }
+}
+
+impl From<v2::stream::Quote> for Quote {
+  fn from(quote: v2::stream::Quote) -> Self {
+    Self {
+      time: quote.timestamp,
+      bid_price: quote.bid_price,
+      bid_size: quote.bid_size,
+      ask_price: quote.ask_price,
+      ask_size: quote.ask_size,
+    }
+  }
+}
+
+
+/// A bar, independent of the version of the API it was reported by.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Bar {
+  /// The beginning time of the bar.
+  pub time: DateTime<Utc>,
+  /// The open price.
+  pub open: Num,
+  /// The highest price.
+  pub high: Num,
+  /// The lowest price.
+  pub low: Num,
+  /// The close price.
+  pub close: Num,
+  /// The trading volume. Volume may be fractional for crypto
+  /// currencies.
+  pub volume: Num,
+  /// The volume weighted average price, if reported.
+  pub vwap: Option<Num>,
+  /// The number of trades that make up the bar, if reported.
+  pub trade_count: Option<u64>,
+}
+
+impl From<v2::bars::Bar> for Bar {
+  fn from(bar: v2::bars::Bar) -> Self {
+    Self {
+      time: bar.time,
+      open: bar.open,
+      high: bar.high,
+      low: bar.low,
+      close: bar.close,
+      volume: Num::from(bar.volume as u64),
+      vwap: bar.vwap,
+      trade_count: bar.trade_count,
+    }
+  }
+}
+
+impl From<v2::stream::Bar> for Bar {
+  fn from(bar: v2::stream::Bar) -> Self {
+    Self {
+      time: bar.timestamp,
+      open: bar.open_price,
+      high: bar.high_price,
+      low: bar.low_price,
+      close: bar.close_price,
+      volume: Num::from(bar.volume),
+      vwap: None,
+      trade_count: None,
+    }
+  }
+}
+
+impl From<crypto::Bar> for Bar {
+  fn from(bar: crypto::Bar) -> Self {
+    Self {
+      time: bar.time,
+      open: bar.open,
+      high: bar.high,
+      low: bar.low,
+      close: bar.close,
+      volume: bar.volume,
+      vwap: bar.vwap,
+      trade_count: bar.trade_count,
+    }
+  }
+}
+
+impl From<options::Bar> for Bar {
+  fn from(bar: options::Bar) -> Self {
+    Self {
+      time: bar.time,
+      open: bar.open,
+      high: bar.high,
+      low: bar.low,
+      close: bar.close,
+      volume: Num::from(bar.volume),
+      vwap: bar.vwap,
+      trade_count: bar.trade_count,
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::from_str as from_json;
+
+  use test_log::test;
+
+
+  /// Check that historic and streamed trades convert to the same
+  /// version agnostic trade.
+  #[test]
+  fn convert_trades() {
+    let historic = r#"{
+  "t": "2021-02-06T13:04:56.334320128Z",
+  "x": "C",
+  "p": 387.62,
+  "s": 100,
+  "c": [" ", "T"],
+  "i": 52983525029461,
+  "z": "B"
+}"#;
+    let streamed = r#"{
+  "T": "t",
+  "S": "SPY",
+  "i": 52983525029461,
+  "x": "C",
+  "p": 387.62,
+  "s": 100,
+  "t": "2021-02-06T13:04:56.334320128Z",
+  "c": [" ", "T"],
+  "z": "B"
+}"#;
+
+    let historic = Trade::from(from_json::<v2::trades::Trade>(historic).unwrap());
+    let streamed = Trade::from(from_json::<v2::stream::Trade>(streamed).unwrap());
+    assert_eq!(historic, streamed);
+    assert_eq!(historic.price, Num::new(38762, 100));
+    assert_eq!(historic.size, 100);
+    assert_eq!(historic.exchange, 'C');
+    assert_eq!(historic.conditions, vec![' ', 'T']);
+  }
+
+  /// Check that the most recent quote and a streamed quote convert to
+  /// the same version agnostic quote.
+  #[test]
+  fn convert_quotes() {
+    let latest = r#"{
+  "t": "2021-02-06T13:35:08.946977536Z",
+  "ax": "C",
+  "ap": 387.7,
+  "as": 1,
+  "bx": "H",
+  "bp": 387.67,
+  "bs": 1,
+  "c": ["R"]
+}"#;
+    let streamed = r#"{
+  "T": "q",
+  "S": "SPY",
+  "bp": 387.67,
+  "bs": 1,
+  "ap": 387.7,
+  "as": 1,
+  "t": "2021-02-06T13:35:08.946977536Z"
+}"#;
+
+    let latest = Quote::from(from_json::<v2::last_quote::Quote>(latest).unwrap());
+    let streamed = Quote::from(from_json::<v2::stream::Quote>(streamed).unwrap());
+    assert_eq!(latest, streamed);
+    assert_eq!(latest.bid_price, Num::new(38767, 100));
+    assert_eq!(latest.ask_price, Num::new(3877, 10));
+  }
+
+  /// Check that we can convert historic and streamed bars.
+  #[test]
+  fn convert_bars() {
+    let historic = r#"{
+  "t": "2021-02-01T16:01:00Z",
+  "o": 133.32,
+  "h": 133.74,
+  "l": 133.31,
+  "c": 133.5,
+  "v": 9876,
+  "vw": 133.5124,
+  "n": 42
+}"#;
+    let streamed = r#"{
+  "T": "b",
+  "S": "SPY",
+  "o": 133.32,
+  "h": 133.74,
+  "l": 133.31,
+  "c": 133.5,
+  "v": 9876,
+  "t": "2021-02-01T16:01:00Z"
+}"#;
+
+    let historic = Bar::from(from_json::<v2::bars::Bar>(historic).unwrap());
+    let streamed = Bar::from(from_json::<v2::stream::Bar>(streamed).unwrap());
+    assert_eq!(historic.time, streamed.time);
+    assert_eq!(historic.open, streamed.open);
+    assert_eq!(historic.high, streamed.high);
+    assert_eq!(historic.low, streamed.low);
+    assert_eq!(historic.close, streamed.close);
+    assert_eq!(historic.volume, streamed.volume);
+    assert_eq!(historic.vwap, Some(Num::new(1335124, 10000)));
+    assert_eq!(historic.trade_count, Some(42));
+    assert_eq!(streamed.vwap, None);
+    assert_eq!(streamed.trade_count, None);
+  }
+}
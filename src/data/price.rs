@@ -0,0 +1,179 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::str::FromStr as _;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use num_decimal::Num;
+
+use thiserror::Error;
+
+use crate::data::v1beta1::options;
+use crate::data::v1beta1::options::OsiSymbol;
+use crate::data::v1beta3::crypto;
+use crate::data::v2::last_trades;
+use crate::Client;
+use crate::RequestError;
+
+
+/// The kind of asset a symbol refers to, as far as market data are
+/// concerned.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AssetKind {
+  /// A US equity, e.g., `AAPL`.
+  Equity,
+  /// A crypto currency pair, e.g., `BTC/USD`.
+  Crypto,
+  /// An option contract, identified by its OCC symbol, e.g.,
+  /// `AAPL240119C00190000`.
+  Option,
+}
+
+impl AssetKind {
+  /// Infer the kind of asset a symbol refers to from its format.
+  ///
+  /// Crypto currency pairs contain a slash, option contracts follow the
+  /// OCC symbology (a root symbol followed by expiration date, contract
+  /// type, and strike price), and all other symbols are considered
+  /// equities.
+  pub fn infer(symbol: &str) -> Self {
+    if symbol.contains('/') {
+      Self::Crypto
+    } else if OsiSymbol::from_str(symbol).is_ok() {
+      Self::Option
+    } else {
+      Self::Equity
+    }
+  }
+}
+
+
+/// The latest price of an asset, independent of its kind.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Price {
+  /// The kind of asset the price is for.
+  pub kind: AssetKind,
+  /// The price of the most recent trade. For option contracts, the
+  /// price is per share of the underlying.
+  pub price: Num,
+  /// The time of the most recent trade.
+  pub time: DateTime<Utc>,
+}
+
+
+/// An error as reported by [`latest_price`].
+#[derive(Debug, Error)]
+pub enum LatestPriceError {
+  /// Retrieving the latest trade of an equity failed.
+  #[error("failed to retrieve latest equity trade")]
+  Equity(#[source] RequestError<last_trades::GetError>),
+  /// Retrieving the latest trade of a crypto currency pair failed.
+  #[error("failed to retrieve latest crypto trade")]
+  Crypto(#[source] RequestError<crypto::GetLastTradesError>),
+  /// Retrieving the latest trade of an option contract failed.
+  #[error("failed to retrieve latest option trade")]
+  Option(#[source] RequestError<options::GetLastTradesError>),
+  /// No trade was reported for the symbol.
+  #[error("no trade found for {0}")]
+  NotFound(String),
+}
+
+
+/// Retrieve the price of the most recent trade of an asset, routing
+/// the request to the market data endpoint appropriate for the kind of
+/// asset, as inferred by [`AssetKind::infer`].
+pub async fn latest_price(client: &Client, symbol: &str) -> Result<Price, LatestPriceError> {
+  latest_price_of(client, symbol, AssetKind::infer(symbol)).await
+}
+
+
+/// Retrieve the price of the most recent trade of an asset of the
+/// given kind.
+pub(crate) async fn latest_price_of(
+  client: &Client,
+  symbol: &str,
+  kind: AssetKind,
+) -> Result<Price, LatestPriceError> {
+  let not_found = || LatestPriceError::NotFound(symbol.to_string());
+
+  let (price, time) = match kind {
+    AssetKind::Equity => {
+      let request = last_trades::LastTradesReqInit::default().init([symbol]);
+      let mut trades = client
+        .issue::<last_trades::Get>(&request)
+        .await
+        .map_err(LatestPriceError::Equity)?;
+      let trade = trades.remove(symbol).ok_or_else(not_found)?;
+      (trade.price, trade.timestamp)
+    },
+    AssetKind::Crypto => {
+      let request = crypto::LastTradesReqInit::default().init([symbol]);
+      let mut trades = client
+        .issue::<crypto::GetLastTrades>(&request)
+        .await
+        .map_err(LatestPriceError::Crypto)?;
+      let trade = trades.remove(symbol).ok_or_else(not_found)?;
+      (trade.price, trade.time)
+    },
+    AssetKind::Option => {
+      let request = options::LastTradesReqInit::default().init([symbol]);
+      let mut trades = client
+        .issue::<options::GetLastTrades>(&request)
+        .await
+        .map_err(LatestPriceError::Option)?;
+      let trade = trades.remove(symbol).ok_or_else(not_found)?;
+      (trade.price, trade.time)
+    },
+  };
+
+  Ok(Price { kind, price, time })
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use test_log::test;
+
+  use crate::test_util::MockServer;
+  use crate::test_util::Response;
+
+
+  /// Check that we infer the kind of asset from symbols as expected.
+  #[test]
+  fn infer_asset_kind() {
+    assert_eq!(AssetKind::infer("AAPL"), AssetKind::Equity);
+    assert_eq!(AssetKind::infer("BRK.B"), AssetKind::Equity);
+    assert_eq!(AssetKind::infer("BTC/USD"), AssetKind::Crypto);
+    assert_eq!(AssetKind::infer("AAPL240119C00190000"), AssetKind::Option);
+    assert_eq!(AssetKind::infer("SPXW240119P04500000"), AssetKind::Option);
+    assert_eq!(AssetKind::infer("240119C00190000"), AssetKind::Equity);
+    assert_eq!(AssetKind::infer("AAPL240119X00190000"), AssetKind::Equity);
+  }
+
+  /// Check that requests for crypto currency pairs are routed to the
+  /// crypto endpoint.
+  #[test(tokio::test)]
+  async fn route_crypto_request() {
+    let server = MockServer::serve([Response::json(
+      r#"{"trades":{"BTC/USD":{"i":1,"p":16831.25,"s":0.5,"t":"2022-12-19T14:02:24Z","tks":"B"}}}"#,
+    )]);
+    let client = server.client();
+
+    let price = latest_price(&client, "BTC/USD").await.unwrap();
+    assert_eq!(price.kind, AssetKind::Crypto);
+    assert_eq!(price.price, Num::new(1683125, 100));
+
+    let requests = server.join();
+    let line = &requests[0].line;
+    assert!(
+      line.starts_with("GET /v1beta3/crypto/us/latest/trades?symbols=BTC%2FUSD "),
+      "{}",
+      line
+    );
+  }
+}
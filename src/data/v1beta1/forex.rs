@@ -0,0 +1,374 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use num_decimal::Num;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::from_slice as from_json;
+use serde_urlencoded::to_string as to_query;
+
+use crate::api::v2::account_activities::Direction;
+use crate::data::v2::bars::TimeFrame;
+use crate::data::DATA_BASE_URL;
+use crate::endpoint::parse_json;
+use crate::util::datetime_from_str;
+use crate::util::string_slice_to_str;
+use crate::Str;
+
+
+/// A currency conversion rate as reported by the /v1beta1/forex
+/// endpoints.
+///
+/// A rate describes the price of one unit of the base currency (the
+/// first three letters of the currency pair, e.g., `USD` in `USDJPY`)
+/// in terms of the quote currency (the last three letters, e.g.,
+/// `JPY`).
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Rate {
+  /// The time the rate was reported at.
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
+  pub time: DateTime<Utc>,
+  /// The bid price.
+  #[serde(rename = "bp")]
+  pub bid_price: Num,
+  /// The mid price.
+  #[serde(rename = "mp")]
+  pub mid_price: Num,
+  /// The ask price.
+  #[serde(rename = "ap")]
+  pub ask_price: Num,
+}
+
+impl Rate {
+  /// Convert an amount denominated in the base currency into the quote
+  /// currency, using the mid price.
+  #[inline]
+  pub fn convert(&self, amount: &Num) -> Num {
+    amount * &self.mid_price
+  }
+
+  /// Convert an amount denominated in the quote currency into the base
+  /// currency, using the mid price.
+  ///
+  /// `None` is returned if the mid price is zero.
+  pub fn convert_inverse(&self, amount: &Num) -> Option<Num> {
+    if self.mid_price.is_zero() {
+      None
+    } else {
+      Some(amount / &self.mid_price)
+    }
+  }
+}
+
+
+/// A GET request to be made to the /v1beta1/forex/latest/rates
+/// endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LatestRatesReq {
+  /// The currency pairs to retrieve the latest rate for, e.g.,
+  /// `USDJPY`.
+  #[serde(rename = "currency_pairs", serialize_with = "string_slice_to_str")]
+  pub currency_pairs: Vec<String>,
+}
+
+
+/// A helper for initializing [`LatestRatesReq`] objects.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[allow(missing_copy_implementations)]
+pub struct LatestRatesReqInit {
+  #[doc(hidden)]
+  pub _non_exhaustive: (),
+}
+
+impl LatestRatesReqInit {
+  /// Create a [`LatestRatesReq`] from a `LatestRatesReqInit`.
+  #[inline]
+  pub fn init<I, S>(self, currency_pairs: I) -> LatestRatesReq
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    LatestRatesReq {
+      currency_pairs: currency_pairs.into_iter().map(S::into).collect(),
+    }
+  }
+}
+
+
+EndpointNoParse! {
+  /// The representation of a GET request to the
+  /// /v1beta1/forex/latest/rates endpoint.
+  pub GetLatest(LatestRatesReq),
+  Ok => HashMap<String, Rate>, [
+    /// The latest rates were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetLatestError, [
+    /// One of the provided currency pairs was invalid.
+    /* 400 */ BAD_REQUEST => InvalidInput,
+  ]
+
+  fn base_url() -> Option<Str> {
+    Some(DATA_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1beta1/forex/latest/rates".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+
+  fn parse(body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
+    /// A helper object for parsing the response to a `GetLatest`
+    /// request.
+    #[derive(Deserialize)]
+    struct Response {
+      /// A mapping from currency pairs to the latest rate for each.
+      rates: HashMap<String, Rate>,
+    }
+
+    parse_json::<Response>(body).map(|response| response.rates)
+  }
+
+  fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
+    from_json::<Self::ApiError>(body).map_err(|_| body.to_vec())
+  }
+}
+
+
+/// A GET request to be made to the /v1beta1/forex/rates endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RatesReq {
+  /// The currency pairs to retrieve historical rates for, e.g.,
+  /// `USDJPY`.
+  #[serde(rename = "currency_pairs", serialize_with = "string_slice_to_str")]
+  pub currency_pairs: Vec<String>,
+  /// The time frame each reported rate represents.
+  #[serde(rename = "timeframe", skip_serializing_if = "Option::is_none")]
+  pub timeframe: Option<TimeFrame>,
+  /// Filter rates equal to or after this time.
+  #[serde(rename = "start", skip_serializing_if = "Option::is_none")]
+  pub start: Option<DateTime<Utc>>,
+  /// Filter rates equal to or before this time.
+  #[serde(rename = "end", skip_serializing_if = "Option::is_none")]
+  pub end: Option<DateTime<Utc>>,
+  /// The maximum number of rates to be returned per page, across all
+  /// currency pairs.
+  #[serde(rename = "limit", skip_serializing_if = "Option::is_none")]
+  pub limit: Option<usize>,
+  /// The order in which to report rates, with respect to their time.
+  #[serde(rename = "sort", skip_serializing_if = "Option::is_none")]
+  pub sort: Option<Direction>,
+  /// If provided we will pass a page token to continue where we left off.
+  #[serde(rename = "page_token", skip_serializing_if = "Option::is_none")]
+  pub page_token: Option<String>,
+}
+
+
+/// A helper for initializing [`RatesReq`] objects.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RatesReqInit {
+  /// See `RatesReq::timeframe`.
+  pub timeframe: Option<TimeFrame>,
+  /// See `RatesReq::start`.
+  pub start: Option<DateTime<Utc>>,
+  /// See `RatesReq::end`.
+  pub end: Option<DateTime<Utc>>,
+  /// See `RatesReq::limit`.
+  pub limit: Option<usize>,
+  /// See `RatesReq::sort`.
+  pub sort: Option<Direction>,
+  /// See `RatesReq::page_token`.
+  pub page_token: Option<String>,
+  #[doc(hidden)]
+  pub _non_exhaustive: (),
+}
+
+impl RatesReqInit {
+  /// Create a [`RatesReq`] from a `RatesReqInit`.
+  #[inline]
+  pub fn init<I, S>(self, currency_pairs: I) -> RatesReq
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    RatesReq {
+      currency_pairs: currency_pairs.into_iter().map(S::into).collect(),
+      timeframe: self.timeframe,
+      start: self.start,
+      end: self.end,
+      limit: self.limit,
+      sort: self.sort,
+      page_token: self.page_token,
+    }
+  }
+}
+
+
+/// A page of historical rates as returned by the /v1beta1/forex/rates
+/// endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Rates {
+  /// A mapping from currency pairs to their rates.
+  #[serde(rename = "rates", default)]
+  pub rates: HashMap<String, Vec<Rate>>,
+  /// The token to provide to a request to get the next page of rates
+  /// for this request.
+  #[serde(rename = "next_page_token")]
+  pub next_page_token: Option<String>,
+}
+
+
+DataEndpoint! {
+  /// The representation of a GET request to the /v1beta1/forex/rates
+  /// endpoint.
+  pub Get(RatesReq),
+  Ok => Rates, [
+    /// The historical rates were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetError, [
+    /// A query parameter was invalid.
+    /* 400 */ BAD_REQUEST => InvalidInput,
+  ]
+
+  fn base_url() -> Option<Str> {
+    Some(DATA_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1beta1/forex/rates".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use http_endpoint::Endpoint;
+
+  use test_log::test;
+
+  use crate::live;
+
+
+  /// Check that we can serialize requests into query strings.
+  #[test]
+  fn serialize_requests() {
+    let request = LatestRatesReqInit::default().init(["USDJPY", "EURUSD"]);
+    let query = to_query(&request).unwrap();
+    assert_eq!(query, "currency_pairs=USDJPY%2CEURUSD");
+
+    let request = RatesReqInit {
+      timeframe: Some(TimeFrame::OneDay),
+      limit: Some(2),
+      ..Default::default()
+    }
+    .init(["USDJPY"]);
+    let query = to_query(&request).unwrap();
+    assert_eq!(query, "currency_pairs=USDJPY&timeframe=1Day&limit=2");
+  }
+
+  /// Check that we can parse a reference latest rates response.
+  #[test]
+  fn parse_reference_latest_rates() {
+    let response = br#"{
+  "rates": {
+    "USDJPY": {
+      "bp": 127.702,
+      "mp": 127.7035,
+      "ap": 127.705,
+      "t": "2022-04-20T18:29:57.221Z"
+    }
+  }
+}"#;
+
+    let rates = GetLatest::parse(response).unwrap();
+    let rate = &rates["USDJPY"];
+    assert_eq!(rate.bid_price, Num::new(127702, 1000));
+    assert_eq!(rate.mid_price, Num::new(1277035, 10000));
+    assert_eq!(rate.ask_price, Num::new(127705, 1000));
+    assert_eq!(
+      rate.time,
+      DateTime::<Utc>::from_str("2022-04-20T18:29:57.221Z").unwrap()
+    );
+  }
+
+  /// Check that we can parse a reference historical rates response.
+  #[test]
+  fn parse_reference_rates() {
+    let response = br#"{
+  "next_page_token": "VVNESlBZfDIwMjItMDQtMjBUMDA6MDA6MDAuMDAwMDAwMDAwWg==",
+  "rates": {
+    "USDJPY": [
+      {"bp": 127.9, "mp": 127.95, "ap": 128, "t": "2022-04-19T00:00:00Z"},
+      {"bp": 128.6, "mp": 128.65, "ap": 128.7, "t": "2022-04-20T00:00:00Z"}
+    ]
+  }
+}"#;
+
+    let rates = Get::parse(response).unwrap();
+    assert!(rates.next_page_token.is_some());
+    assert_eq!(rates.rates["USDJPY"].len(), 2);
+    assert_eq!(rates.rates["USDJPY"][1].ask_price, Num::new(1287, 10));
+  }
+
+  /// Check that we convert amounts between currencies as expected.
+  #[test]
+  fn convert_amounts() {
+    let rate = Rate {
+      time: Utc::now(),
+      bid_price: Num::new(109, 100),
+      mid_price: Num::new(110, 100),
+      ask_price: Num::new(111, 100),
+    };
+
+    assert_eq!(rate.convert(&Num::from(100)), Num::from(110));
+    assert_eq!(rate.convert_inverse(&Num::from(110)), Some(Num::from(100)));
+
+    let rate = Rate {
+      mid_price: Num::from(0),
+      ..rate
+    };
+    assert_eq!(rate.convert_inverse(&Num::from(110)), None);
+  }
+
+  /// Check that we can retrieve latest and historical rates.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn request_rates() {
+    let client = live::client().unwrap();
+    let request = LatestRatesReqInit::default().init(["USDJPY", "EURUSD"]);
+    let rates = client.issue::<GetLatest>(&request).await.unwrap();
+    assert_eq!(rates.len(), 2);
+    assert!(rates["USDJPY"].mid_price > Num::from(0));
+
+    let request = RatesReqInit {
+      timeframe: Some(TimeFrame::OneDay),
+      limit: Some(5),
+      ..Default::default()
+    }
+    .init(["USDJPY"]);
+    let rates = client.issue::<Get>(&request).await.unwrap();
+    assert!(rates.rates["USDJPY"].len() <= 5);
+  }
+}
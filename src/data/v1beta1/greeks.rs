@@ -0,0 +1,351 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::f64::consts::PI;
+use std::f64::consts::SQRT_2;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use num_decimal::Num;
+
+use crate::data::v1beta1::options::ContractType;
+use crate::data::v1beta1::options::Greeks;
+use crate::data::v1beta1::options::OsiSymbol;
+use crate::data::v1beta1::options::Snapshot;
+
+
+/// The number of seconds in a year, as used for expressing the time to
+/// expiration.
+const SECONDS_PER_YEAR: f64 = 365.0 * 24.0 * 60.0 * 60.0;
+/// The hour (in UTC) at which contracts are assumed to expire on their
+/// expiration date, corresponding to the 4pm Eastern market close.
+const EXPIRATION_HOUR_UTC: u32 = 21;
+/// The range of volatilities considered when searching for the implied
+/// volatility.
+const VOLATILITY_RANGE: (f64, f64) = (1e-4, 5.0);
+/// The maximum number of iterations when searching for the implied
+/// volatility.
+const MAX_ITERATIONS: usize = 200;
+/// The price tolerance at which the implied volatility search stops.
+const TOLERANCE: f64 = 1e-8;
+
+
+/// The coefficients of the polynomial used for approximating the
+/// complementary error function, in order of increasing degree.
+const ERFC_COEFFICIENTS: [f64; 10] = [
+  -1.26551223,
+  1.00002368,
+  0.37409196,
+  0.09678418,
+  -0.18628806,
+  0.27886807,
+  -1.13520398,
+  1.48851587,
+  -0.82215223,
+  0.17087277,
+];
+
+
+/// The complementary error function, with a fractional error of less
+/// than 1.2e-7.
+fn erfc(x: f64) -> f64 {
+  let z = x.abs();
+  let t = 1.0 / (1.0 + 0.5 * z);
+  let poly = ERFC_COEFFICIENTS
+    .iter()
+    .rev()
+    .fold(0.0, |acc, coefficient| acc * t + coefficient);
+  let result = t * (-z * z + poly).exp();
+  if x >= 0.0 {
+    result
+  } else {
+    2.0 - result
+  }
+}
+
+/// The cumulative distribution function of the standard normal
+/// distribution.
+fn cdf(x: f64) -> f64 {
+  0.5 * erfc(-x / SQRT_2)
+}
+
+/// The probability density function of the standard normal
+/// distribution.
+fn pdf(x: f64) -> f64 {
+  (-0.5 * x * x).exp() / (2.0 * PI).sqrt()
+}
+
+
+/// A Black-Scholes model of a European option contract.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Model {
+  /// The type of the contract.
+  type_: ContractType,
+  /// The price of the underlying.
+  spot: f64,
+  /// The strike price of the contract.
+  strike: f64,
+  /// The time to expiration, in years.
+  time: f64,
+  /// The continuously compounded risk-free interest rate, as a factor
+  /// of 1.
+  rate: f64,
+}
+
+impl Model {
+  /// Create a new `Model` for a contract of the given type.
+  ///
+  /// `time` is the time to expiration in years and `rate` the
+  /// risk-free interest rate as a factor of 1 (e.g., `0.05` for 5%).
+  /// `None` is returned if any of the inputs is not usable, e.g.,
+  /// because the contract is expired.
+  pub fn new(type_: ContractType, spot: f64, strike: f64, time: f64, rate: f64) -> Option<Self> {
+    let valid = spot.is_finite()
+      && spot > 0.0
+      && strike.is_finite()
+      && strike > 0.0
+      && time.is_finite()
+      && time > 0.0
+      && rate.is_finite();
+
+    valid.then_some(Self {
+      type_,
+      spot,
+      strike,
+      time,
+      rate,
+    })
+  }
+
+  /// Create a new `Model` for the contract identified by the given
+  /// symbol, as of `now`.
+  ///
+  /// Contracts are assumed to expire at the 4pm Eastern market close
+  /// of their expiration date, approximated as 21:00 UTC.
+  pub fn from_symbol(
+    symbol: &OsiSymbol,
+    spot: &Num,
+    rate: f64,
+    now: DateTime<Utc>,
+  ) -> Option<Self> {
+    let expiration = symbol.expiration().and_hms_opt(EXPIRATION_HOUR_UTC, 0, 0)?;
+    let time = (expiration - now.naive_utc()).num_seconds() as f64 / SECONDS_PER_YEAR;
+
+    Self::new(
+      symbol.type_(),
+      spot.to_f64()?,
+      symbol.strike().to_f64()?,
+      time,
+      rate,
+    )
+  }
+
+  /// Calculate the `d1` and `d2` terms of the Black-Scholes formula.
+  fn d(&self, volatility: f64) -> (f64, f64) {
+    let vol_time = volatility * self.time.sqrt();
+    let d1 = ((self.spot / self.strike).ln()
+      + (self.rate + 0.5 * volatility * volatility) * self.time)
+      / vol_time;
+    (d1, d1 - vol_time)
+  }
+
+  /// Calculate the theoretical price of the contract, per share of
+  /// the underlying, for the given volatility.
+  pub fn price(&self, volatility: f64) -> f64 {
+    let (d1, d2) = self.d(volatility);
+    let discount = self.strike * (-self.rate * self.time).exp();
+
+    match self.type_ {
+      ContractType::Call => self.spot * cdf(d1) - discount * cdf(d2),
+      ContractType::Put => discount * cdf(-d2) - self.spot * cdf(-d1),
+    }
+  }
+
+  /// Calculate the greeks of the contract for the given volatility.
+  pub fn greeks(&self, volatility: f64) -> Greeks {
+    let (d1, d2) = self.d(volatility);
+    let sqrt_time = self.time.sqrt();
+    let discount = self.strike * (-self.rate * self.time).exp();
+    let decay = -self.spot * pdf(d1) * volatility / (2.0 * sqrt_time);
+
+    let (delta, theta, rho) = match self.type_ {
+      ContractType::Call => (
+        cdf(d1),
+        decay - self.rate * discount * cdf(d2),
+        self.time * discount * cdf(d2),
+      ),
+      ContractType::Put => (
+        cdf(d1) - 1.0,
+        decay + self.rate * discount * cdf(-d2),
+        -self.time * discount * cdf(-d2),
+      ),
+    };
+
+    Greeks {
+      delta,
+      gamma: pdf(d1) / (self.spot * volatility * sqrt_time),
+      theta: theta / 365.0,
+      vega: self.spot * pdf(d1) * sqrt_time / 100.0,
+      rho: rho / 100.0,
+    }
+  }
+
+  /// Calculate the volatility implied by the given price of the
+  /// contract.
+  ///
+  /// `None` is returned if the price is outside of the range that can
+  /// be explained by the model.
+  pub fn implied_volatility(&self, price: f64) -> Option<f64> {
+    let (mut low, mut high) = VOLATILITY_RANGE;
+    if !price.is_finite() || price < self.price(low) || price > self.price(high) {
+      return None
+    }
+
+    // The price is monotonically increasing in volatility, so a simple
+    // bisection is guaranteed to converge.
+    for _ in 0..MAX_ITERATIONS {
+      let mid = 0.5 * (low + high);
+      let diff = self.price(mid) - price;
+      if diff.abs() < TOLERANCE {
+        return Some(mid)
+      }
+
+      if diff > 0.0 {
+        high = mid
+      } else {
+        low = mid
+      }
+    }
+    Some(0.5 * (low + high))
+  }
+}
+
+
+impl Snapshot {
+  /// Compute the implied volatility and greeks of the contract if they
+  /// were not reported, as is the case for the indicative feed.
+  ///
+  /// The implied volatility is derived from the mid price of the latest
+  /// quote or, lacking that, the price of the latest trade. `spot` is
+  /// the price of the underlying and `rate` the risk-free interest
+  /// rate, as a factor of 1. Reported values are left untouched. The
+  /// return value indicates whether greeks are available after the
+  /// call.
+  pub fn fill_greeks(
+    &mut self,
+    symbol: &OsiSymbol,
+    spot: &Num,
+    rate: f64,
+    now: DateTime<Utc>,
+  ) -> bool {
+    if self.greeks.is_some() {
+      return true
+    }
+
+    let model = match Model::from_symbol(symbol, spot, rate, now) {
+      Some(model) => model,
+      None => return false,
+    };
+
+    if self.implied_volatility.is_none() {
+      let price = self
+        .latest_quote
+        .as_ref()
+        .map(|quote| quote.mid_price())
+        .or_else(|| self.latest_trade.as_ref().map(|trade| trade.price.clone()))
+        .and_then(|price| price.to_f64());
+
+      self.implied_volatility = price.and_then(|price| model.implied_volatility(price));
+    }
+
+    self.greeks = self
+      .implied_volatility
+      .map(|volatility| model.greeks(volatility));
+    self.greeks.is_some()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use serde_json::from_str as from_json;
+
+  use test_log::test;
+
+
+  /// Check that two floating point values are approximately equal.
+  fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+    assert!(
+      (actual - expected).abs() < tolerance,
+      "{} != {}",
+      actual,
+      expected
+    );
+  }
+
+
+  /// Check that we price contracts and compute greeks as expected.
+  #[test]
+  fn price_and_greeks() {
+    // Reference values as found in Hull, "Options, Futures, and Other
+    // Derivatives", example 15.6.
+    let call = Model::new(ContractType::Call, 42.0, 40.0, 0.5, 0.1).unwrap();
+    let put = Model::new(ContractType::Put, 42.0, 40.0, 0.5, 0.1).unwrap();
+    assert_close(call.price(0.2), 4.76, 0.005);
+    assert_close(put.price(0.2), 0.81, 0.005);
+
+    let greeks = call.greeks(0.2);
+    assert_close(greeks.delta, 0.7791, 0.0001);
+    assert_close(greeks.gamma, 0.0500, 0.0001);
+    assert_close(greeks.vega, 0.0881, 0.0001);
+    let greeks = put.greeks(0.2);
+    assert_close(greeks.delta, -0.2209, 0.0001);
+    assert!(greeks.theta < 0.0);
+    assert!(greeks.rho < 0.0);
+  }
+
+  /// Check that we can recover the volatility from a price.
+  #[test]
+  fn implied_volatility() {
+    let model = Model::new(ContractType::Put, 100.0, 105.0, 0.25, 0.05).unwrap();
+    let price = model.price(0.35);
+    assert_close(model.implied_volatility(price).unwrap(), 0.35, 1e-6);
+    assert_eq!(model.implied_volatility(0.0), None);
+    assert_eq!(model.implied_volatility(1000.0), None);
+  }
+
+  /// Check that missing greeks are filled in from the latest quote.
+  #[test]
+  fn fill_missing_greeks() {
+    let mut snapshot = from_json::<Snapshot>(
+      r#"{
+  "latestQuote": {
+    "ap": 5.1,
+    "as": 3,
+    "bp": 4.9,
+    "bs": 4,
+    "t": "2024-01-02T15:00:00Z"
+  }
+}"#,
+    )
+    .unwrap();
+    let symbol = OsiSymbol::from_str("AAPL240315C00190000").unwrap();
+    let now = DateTime::from_str("2024-01-02T15:00:00Z").unwrap();
+
+    assert!(snapshot.fill_greeks(&symbol, &Num::from(185), 0.05, now));
+    let volatility = snapshot.implied_volatility.unwrap();
+    assert!(volatility > 0.1 && volatility < 0.5, "{}", volatility);
+    let greeks = snapshot.greeks.unwrap();
+    assert!(greeks.delta > 0.0 && greeks.delta < 0.5, "{}", greeks.delta);
+
+    // Expired contracts can not be evaluated.
+    let mut snapshot = from_json::<Snapshot>("{}").unwrap();
+    let now = DateTime::from_str("2024-03-16T00:00:00Z").unwrap();
+    assert!(!snapshot.fill_greeks(&symbol, &Num::from(185), 0.05, now));
+  }
+}
@@ -0,0 +1,14 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+/// Definitions for retrieval of currency conversion rates.
+pub mod forex;
+/// Black-Scholes computation of option greeks and implied volatility.
+#[cfg(feature = "greeks")]
+pub mod greeks;
+/// Definitions for retrieval of news articles.
+pub mod news;
+/// Definitions for retrieval of option contract market data.
+pub mod options;
+/// Definitions for retrieval of most active stocks and market movers.
+pub mod screener;
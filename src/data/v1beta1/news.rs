@@ -0,0 +1,474 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_urlencoded::to_string as to_query;
+
+use crate::api::v2::account_activities::Direction;
+use crate::data::DATA_BASE_URL;
+use crate::util::datetime_from_str;
+use crate::util::string_slice_to_str;
+use crate::util::vec_from_str;
+use crate::Paginated;
+use crate::Str;
+
+
+/// The maximum number of articles the /v1beta1/news endpoint reports
+/// per page.
+pub const MAX_LIMIT: usize = 50;
+
+
+/// A GET request to be issued to the /v1beta1/news endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct NewsReq {
+  /// The symbols for which to retrieve news. News for all symbols are
+  /// retrieved if empty.
+  #[serde(
+    rename = "symbols",
+    serialize_with = "string_slice_to_str",
+    skip_serializing_if = "Vec::is_empty"
+  )]
+  pub symbols: Vec<String>,
+  /// Filter news published at or after this time.
+  #[serde(rename = "start", skip_serializing_if = "Option::is_none")]
+  pub start: Option<DateTime<Utc>>,
+  /// Filter news published at or before this time.
+  #[serde(rename = "end", skip_serializing_if = "Option::is_none")]
+  pub end: Option<DateTime<Utc>>,
+  /// The maximum number of articles to be returned per page.
+  ///
+  /// It can be between 1 and [`MAX_LIMIT`]. Defaults to 10 if the
+  /// provided value is None.
+  #[serde(rename = "limit", skip_serializing_if = "Option::is_none")]
+  pub limit: Option<usize>,
+  /// The order in which to report articles, with respect to the time
+  /// they were published at.
+  #[serde(rename = "sort", skip_serializing_if = "Option::is_none")]
+  pub sort: Option<Direction>,
+  /// Whether to include the full content of articles, as opposed to
+  /// just headline and summary.
+  #[serde(rename = "include_content")]
+  pub include_content: bool,
+  /// Whether to exclude articles without content.
+  #[serde(rename = "exclude_contentless")]
+  pub exclude_contentless: bool,
+  /// If provided we will pass a page token to continue where we left off.
+  #[serde(rename = "page_token", skip_serializing_if = "Option::is_none")]
+  pub page_token: Option<String>,
+}
+
+
+/// A helper for initializing [`NewsReq`] objects.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NewsReqInit {
+  /// See `NewsReq::start`.
+  pub start: Option<DateTime<Utc>>,
+  /// See `NewsReq::end`.
+  pub end: Option<DateTime<Utc>>,
+  /// See `NewsReq::limit`.
+  pub limit: Option<usize>,
+  /// See `NewsReq::sort`.
+  pub sort: Option<Direction>,
+  /// See `NewsReq::include_content`.
+  pub include_content: bool,
+  /// See `NewsReq::exclude_contentless`.
+  pub exclude_contentless: bool,
+  /// See `NewsReq::page_token`.
+  pub page_token: Option<String>,
+  #[doc(hidden)]
+  pub _non_exhaustive: (),
+}
+
+impl NewsReqInit {
+  /// Create a [`NewsReq`] from a `NewsReqInit`.
+  #[inline]
+  pub fn init<I, S>(self, symbols: I) -> NewsReq
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    NewsReq {
+      symbols: symbols.into_iter().map(S::into).collect(),
+      start: self.start,
+      end: self.end,
+      limit: self.limit,
+      sort: self.sort,
+      include_content: self.include_content,
+      exclude_contentless: self.exclude_contentless,
+      page_token: self.page_token,
+    }
+  }
+}
+
+
+/// The size of an image accompanying an article.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ImageSize {
+  /// A thumbnail.
+  #[serde(rename = "thumb")]
+  Thumb,
+  /// A small image.
+  #[serde(rename = "small")]
+  Small,
+  /// A large image.
+  #[serde(rename = "large")]
+  Large,
+  /// Any other image size that we have not accounted for.
+  #[serde(other)]
+  Unknown,
+}
+
+
+/// An image accompanying an article.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Image {
+  /// The size of the image.
+  #[serde(rename = "size")]
+  pub size: ImageSize,
+  /// The URL of the image.
+  #[serde(rename = "url")]
+  pub url: String,
+}
+
+
+/// A news article as returned by the /v1beta1/news endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Article {
+  /// The article's ID.
+  #[serde(rename = "id")]
+  pub id: u64,
+  /// The article's headline.
+  #[serde(rename = "headline")]
+  pub headline: String,
+  /// The article's author.
+  #[serde(rename = "author", default)]
+  pub author: String,
+  /// The time the article was published at.
+  #[serde(rename = "created_at", deserialize_with = "datetime_from_str")]
+  pub created_at: DateTime<Utc>,
+  /// The time the article was last updated at.
+  #[serde(rename = "updated_at", deserialize_with = "datetime_from_str")]
+  pub updated_at: DateTime<Utc>,
+  /// A summary of the article, possibly empty.
+  #[serde(rename = "summary", default)]
+  pub summary: String,
+  /// The article's content, which may contain HTML markup. It is only
+  /// present if requested via `NewsReq::include_content`.
+  ///
+  /// See [`Article::plain_content`] for retrieving it as plain text.
+  #[serde(rename = "content", default)]
+  pub content: String,
+  /// The URL of the original article, if any.
+  #[serde(rename = "url", default)]
+  pub url: Option<String>,
+  /// Images accompanying the article.
+  #[serde(rename = "images", default, deserialize_with = "vec_from_str")]
+  pub images: Vec<Image>,
+  /// The symbols the article pertains to.
+  #[serde(rename = "symbols", default, deserialize_with = "vec_from_str")]
+  pub symbols: Vec<String>,
+  /// The source of the article, e.g., "benzinga".
+  #[serde(rename = "source")]
+  pub source: String,
+}
+
+impl Article {
+  /// Retrieve the article's content as plain text, with HTML tags
+  /// removed and common character entities decoded.
+  pub fn plain_content(&self) -> String {
+    html_to_text(&self.content)
+  }
+}
+
+
+/// Convert HTML markup into plain text, in a best-effort manner.
+///
+/// Tags are removed, with paragraphs and line breaks being turned into
+/// new lines. Common character entities are decoded and runs of white
+/// space are collapsed.
+fn html_to_text(html: &str) -> String {
+  /// Decode a character entity, without the surrounding `&` and `;`.
+  fn decode(entity: &str) -> Option<char> {
+    match entity {
+      "amp" => Some('&'),
+      "lt" => Some('<'),
+      "gt" => Some('>'),
+      "quot" => Some('"'),
+      "apos" => Some('\''),
+      "nbsp" => Some(' '),
+      _ => {
+        let code = entity.strip_prefix('#')?;
+        let code = match code.strip_prefix(|c| c == 'x' || c == 'X') {
+          Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+          None => code.parse().ok()?,
+        };
+        char::from_u32(code)
+      },
+    }
+  }
+
+  let mut text = String::with_capacity(html.len());
+  let mut rest = html;
+
+  while let Some(c) = rest.chars().next() {
+    match c {
+      '<' => {
+        let (tag, remainder) = rest[1..].split_once('>').unwrap_or((&rest[1..], ""));
+        let name = tag
+          .trim_start_matches('/')
+          .split(|c: char| c.is_whitespace() || c == '/')
+          .next()
+          .unwrap_or("")
+          .to_ascii_lowercase();
+        if matches!(
+          name.as_str(),
+          "p" | "br" | "div" | "li" | "h1" | "h2" | "h3" | "tr"
+        ) {
+          text.push('\n');
+        }
+        rest = remainder;
+      },
+      '&' => {
+        let decoded = rest[1..]
+          .split_once(';')
+          .filter(|(entity, _)| entity.len() <= 8)
+          .and_then(|(entity, remainder)| Some((decode(entity)?, remainder)));
+        match decoded {
+          Some((c, remainder)) => {
+            text.push(c);
+            rest = remainder;
+          },
+          None => {
+            text.push('&');
+            rest = &rest[1..];
+          },
+        }
+      },
+      c => {
+        text.push(c);
+        rest = &rest[c.len_utf8()..];
+      },
+    }
+  }
+
+  text
+    .lines()
+    .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+    .filter(|line| !line.is_empty())
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+
+/// A page of news articles as returned by the /v1beta1/news endpoint.
+#[derive(Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct News {
+  /// The list of returned articles.
+  #[serde(rename = "news", deserialize_with = "vec_from_str")]
+  pub news: Vec<Article>,
+  /// The token to provide to a request to get the next page of
+  /// articles for this request.
+  #[serde(rename = "next_page_token")]
+  pub next_page_token: Option<String>,
+}
+
+
+DataEndpoint! {
+  /// The representation of a GET request to the /v1beta1/news endpoint.
+  pub Get(NewsReq),
+  Ok => News, [
+    /// The news were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetError, [
+    /// A query parameter was invalid.
+    /* 400 */ BAD_REQUEST => InvalidInput,
+  ]
+
+  fn base_url() -> Option<Str> {
+    Some(DATA_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1beta1/news".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+}
+
+/// The next page of articles is retrieved using the token reported
+/// along with the current one. That allows for ingesting the entire
+/// archive via [`Client::issue_paged`][crate::Client::issue_paged].
+impl Paginated for Get {
+  type Item = Article;
+  type State = ();
+
+  fn paginate(
+    mut input: Self::Input,
+    output: Self::Output,
+    _state: &mut Self::State,
+  ) -> (Vec<Self::Item>, Option<Self::Input>) {
+    input.page_token = output.next_page_token;
+    let next = input.page_token.is_some().then_some(input);
+    (output.news, next)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use futures::TryStreamExt as _;
+
+  use http_endpoint::Endpoint;
+
+  use serde_json::from_str as from_json;
+
+  use test_log::test;
+
+  use crate::live;
+
+
+  /// Check that we serialize a news request as expected.
+  #[test]
+  fn serialize_request() {
+    let request = NewsReqInit {
+      start: Some(DateTime::from_str("2022-01-01T00:00:00Z").unwrap()),
+      sort: Some(Direction::Ascending),
+      include_content: true,
+      exclude_contentless: true,
+      ..Default::default()
+    }
+    .init(["AAPL", "TSLA"]);
+
+    let query = <Get as Endpoint>::query(&request).unwrap().unwrap();
+    assert_eq!(
+      query,
+      "symbols=AAPL%2CTSLA&start=2022-01-01T00%3A00%3A00Z&sort=asc&include_content=true&exclude_contentless=true"
+    );
+
+    let request = NewsReqInit::default().init(Vec::<String>::new());
+    let query = <Get as Endpoint>::query(&request).unwrap().unwrap();
+    assert_eq!(query, "include_content=false&exclude_contentless=false");
+  }
+
+  /// Verify that we can parse a reference news response.
+  #[test]
+  fn parse_reference_news() {
+    let response = r#"{
+  "news": [
+    {
+      "id": 24843171,
+      "headline": "Apple Leader in Phone Sales in China for Second Straight Month in November With 23.6% Share, According to Market Research Data",
+      "author": "Charles Gross",
+      "created_at": "2021-12-31T11:08:42Z",
+      "updated_at": "2021-12-31T11:08:43Z",
+      "summary": "This headline-only article is meant to show you why a stock is moving, the most difficult aspect of stock trading",
+      "content": "<p>This headline-only article is meant to show you why a stock is moving,&nbsp;the most difficult aspect of stock trading.</p><p>Every day we publish hundreds of headlines &amp; stories.</p>",
+      "url": "https://www.benzinga.com/news/21/12/24843171/apple-leader-in-phone-sales",
+      "images": [
+        {"size": "large", "url": "https://cdn.benzinga.com/files/imagecache/2048x1536xUP/images/story/2012/apple_9.jpeg"},
+        {"size": "thumb", "url": "https://cdn.benzinga.com/files/imagecache/250x187xUP/images/story/2012/apple_9.jpeg"}
+      ],
+      "symbols": ["AAPL"],
+      "source": "benzinga"
+    }
+  ],
+  "next_page_token": "MTY0MDk0ODkyMjAwMDAwMDAwMHwyNDg0MzE3MQ=="
+}"#;
+
+    let news = from_json::<<Get as Endpoint>::Output>(response).unwrap();
+    assert_eq!(news.news.len(), 1);
+    assert!(news.next_page_token.is_some());
+
+    let article = &news.news[0];
+    assert_eq!(article.id, 24843171);
+    assert_eq!(article.author, "Charles Gross");
+    assert_eq!(
+      article.created_at,
+      DateTime::<Utc>::from_str("2021-12-31T11:08:42Z").unwrap()
+    );
+    assert_eq!(article.images.len(), 2);
+    assert_eq!(article.images[1].size, ImageSize::Thumb);
+    assert_eq!(article.symbols, vec!["AAPL".to_string()]);
+    assert_eq!(
+      article.plain_content(),
+      "This headline-only article is meant to show you why a stock is moving, the most \
+       difficult aspect of stock trading.\nEvery day we publish hundreds of headlines & stories."
+    );
+  }
+
+  /// Check that we can convert HTML markup into plain text.
+  #[test]
+  fn convert_html_to_text() {
+    assert_eq!(html_to_text(""), "");
+    assert_eq!(html_to_text("plain"), "plain");
+    assert_eq!(
+      html_to_text("<div class=\"x\">a &lt;b&gt;<br/>c&#39;s &#x41; &unknown; &</div>"),
+      "a <b>\nc's A &unknown; &"
+    );
+    assert_eq!(
+      html_to_text("<ul><li>one</li><li>two</li></ul>"),
+      "one\ntwo"
+    );
+  }
+
+  /// Check that the next page is requested using the reported token.
+  #[test]
+  fn paginate_news() {
+    let request = NewsReqInit::default().init(["AAPL"]);
+    let news = News {
+      news: Vec::new(),
+      next_page_token: Some("abc".to_string()),
+    };
+    let (articles, next) = Get::paginate(request.clone(), news, &mut ());
+    assert!(articles.is_empty());
+    assert_eq!(next.unwrap().page_token, Some("abc".to_string()));
+
+    let news = News {
+      news: Vec::new(),
+      next_page_token: None,
+    };
+    let (_, next) = Get::paginate(request, news, &mut ());
+    assert_eq!(next, None);
+  }
+
+  /// Check that we can retrieve multiple pages of news, including their
+  /// content.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn request_news() {
+    let client = live::client().unwrap();
+    let request = NewsReqInit {
+      start: Some(DateTime::from_str("2021-12-01T00:00:00Z").unwrap()),
+      end: Some(DateTime::from_str("2021-12-03T00:00:00Z").unwrap()),
+      limit: Some(5),
+      include_content: true,
+      exclude_contentless: true,
+      ..Default::default()
+    }
+    .init(["AAPL"]);
+
+    let articles = client
+      .issue_paged::<Get>(request)
+      .try_collect::<Vec<_>>()
+      .await
+      .unwrap();
+    assert!(articles.len() > 5);
+    assert!(articles.iter().all(|article| !article.content.is_empty()));
+  }
+}
@@ -0,0 +1,876 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::str::FromStr;
+
+use chrono::DateTime;
+use chrono::Datelike as _;
+use chrono::NaiveDate;
+use chrono::Utc;
+
+use num_decimal::Num;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::from_slice as from_json;
+use serde_urlencoded::to_string as to_query;
+
+use thiserror::Error;
+
+use crate::api::v2::asset;
+use crate::data::v2::bars::TimeFrame;
+use crate::data::DATA_BASE_URL;
+use crate::endpoint::parse_json;
+use crate::util::datetime_from_str;
+use crate::util::string_slice_to_str;
+use crate::Paginated;
+use crate::Str;
+
+
+/// The maximum length of the root symbol of an OCC option symbol.
+const MAX_ROOT_LEN: usize = 6;
+/// The number of strike price units per dollar in an OCC option
+/// symbol.
+const STRIKE_UNITS: u64 = 1000;
+/// The exclusive upper bound of the encoded strike price.
+const MAX_STRIKE_UNITS: u64 = 100_000_000;
+
+
+/// The type of an option contract.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ContractType {
+  /// A call option.
+  Call,
+  /// A put option.
+  Put,
+}
+
+impl ContractType {
+  /// Retrieve the character representing the contract type in an OCC
+  /// option symbol.
+  #[inline]
+  pub fn as_char(self) -> char {
+    match self {
+      Self::Call => 'C',
+      Self::Put => 'P',
+    }
+  }
+}
+
+
+/// An error as reported when parsing or building an [`OsiSymbol`].
+#[derive(Clone, Debug, Eq, Error, PartialEq)]
+pub enum OsiSymbolError {
+  /// The symbol is not of the expected length or not ASCII.
+  #[error("the option symbol is of an invalid format")]
+  InvalidFormat,
+  /// The root symbol of the underlying is invalid.
+  #[error("the underlying symbol is invalid: {0}")]
+  InvalidUnderlying(String),
+  /// The expiration date is invalid.
+  #[error("the expiration date is invalid: {0}")]
+  InvalidExpiration(String),
+  /// The contract type is neither call nor put.
+  #[error("the contract type is invalid: {0}")]
+  InvalidType(char),
+  /// The strike price is invalid or can not be represented.
+  #[error("the strike price is invalid: {0}")]
+  InvalidStrike(String),
+}
+
+
+/// An option contract symbol according to the OCC Options Symbology
+/// Initiative (OSI), e.g., `AAPL240119C00190000`.
+///
+/// The symbol consists of the root symbol of the underlying (up to six
+/// characters), the expiration date as `YYMMDD`, the contract type
+/// (`C` or `P`), and the strike price in thousandths of a dollar,
+/// padded to eight digits.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct OsiSymbol {
+  /// The root symbol of the underlying.
+  underlying: String,
+  /// The expiration date of the contract.
+  expiration: NaiveDate,
+  /// The type of the contract.
+  type_: ContractType,
+  /// The strike price of the contract.
+  strike: Num,
+}
+
+impl OsiSymbol {
+  /// Create an `OsiSymbol` from its constituents, validating that they
+  /// can be represented as an OCC symbol.
+  pub fn new<S>(
+    underlying: S,
+    expiration: NaiveDate,
+    type_: ContractType,
+    strike: Num,
+  ) -> Result<Self, OsiSymbolError>
+  where
+    S: Into<String>,
+  {
+    let underlying = underlying.into();
+    if underlying.is_empty()
+      || underlying.len() > MAX_ROOT_LEN
+      || !underlying
+        .bytes()
+        .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+    {
+      return Err(OsiSymbolError::InvalidUnderlying(underlying))
+    }
+
+    if !(2000..2100).contains(&expiration.year()) {
+      return Err(OsiSymbolError::InvalidExpiration(expiration.to_string()))
+    }
+
+    let units = &strike * STRIKE_UNITS;
+    let valid = units.fract().is_zero()
+      && units
+        .to_u64()
+        .map(|units| units > 0 && units < MAX_STRIKE_UNITS)
+        .unwrap_or(false);
+    if !valid {
+      return Err(OsiSymbolError::InvalidStrike(strike.to_string()))
+    }
+
+    Ok(Self {
+      underlying,
+      expiration,
+      type_,
+      strike,
+    })
+  }
+
+  /// Retrieve the root symbol of the underlying.
+  #[inline]
+  pub fn underlying(&self) -> &str {
+    &self.underlying
+  }
+
+  /// Retrieve the expiration date of the contract.
+  #[inline]
+  pub fn expiration(&self) -> NaiveDate {
+    self.expiration
+  }
+
+  /// Retrieve the type of the contract.
+  #[inline]
+  pub fn type_(&self) -> ContractType {
+    self.type_
+  }
+
+  /// Retrieve the strike price of the contract.
+  #[inline]
+  pub fn strike(&self) -> &Num {
+    &self.strike
+  }
+}
+
+impl FromStr for OsiSymbol {
+  type Err = OsiSymbolError;
+
+  fn from_str(symbol: &str) -> Result<Self, Self::Err> {
+    // Expiration date, contract type, and strike price.
+    const SUFFIX_LEN: usize = 6 + 1 + 8;
+
+    if !symbol.is_ascii() || symbol.len() <= SUFFIX_LEN {
+      return Err(OsiSymbolError::InvalidFormat)
+    }
+
+    let (underlying, suffix) = symbol.split_at(symbol.len() - SUFFIX_LEN);
+    let (date, rest) = suffix.split_at(6);
+    let (type_, strike) = rest.split_at(1);
+
+    let expiration = date
+      .bytes()
+      .all(|b| b.is_ascii_digit())
+      .then(|| NaiveDate::parse_from_str(&format!("20{}", date), "%Y%m%d").ok())
+      .flatten()
+      .ok_or_else(|| OsiSymbolError::InvalidExpiration(date.to_string()))?;
+
+    let type_ = match type_ {
+      "C" => ContractType::Call,
+      "P" => ContractType::Put,
+      _ => {
+        let type_ = type_.chars().next().unwrap_or_default();
+        return Err(OsiSymbolError::InvalidType(type_))
+      },
+    };
+
+    let units = strike
+      .bytes()
+      .all(|b| b.is_ascii_digit())
+      .then(|| u64::from_str(strike).ok())
+      .flatten()
+      .ok_or_else(|| OsiSymbolError::InvalidStrike(strike.to_string()))?;
+
+    Self::new(underlying, expiration, type_, Num::new(units, STRIKE_UNITS))
+  }
+}
+
+impl Display for OsiSymbol {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+    // Validated upon construction to be a whole number of units.
+    let units = (&self.strike * STRIKE_UNITS).to_u64().unwrap_or_default();
+    write!(
+      fmt,
+      "{}{}{}{:08}",
+      self.underlying,
+      self.expiration.format("%y%m%d"),
+      self.type_.as_char(),
+      units
+    )
+  }
+}
+
+impl From<OsiSymbol> for String {
+  #[inline]
+  fn from(symbol: OsiSymbol) -> Self {
+    symbol.to_string()
+  }
+}
+
+impl From<OsiSymbol> for asset::Symbol {
+  #[inline]
+  fn from(symbol: OsiSymbol) -> Self {
+    Self::Sym(symbol.to_string())
+  }
+}
+
+
+/// An option contract trade as returned by the
+/// /v1beta1/options/trades/latest endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Trade {
+  /// The time at which the trade happened.
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
+  pub time: DateTime<Utc>,
+  /// The exchange where the trade happened.
+  #[serde(rename = "x")]
+  pub exchange: char,
+  /// The trade's price, per share of the underlying.
+  #[serde(rename = "p")]
+  pub price: Num,
+  /// The trade's size, in contracts.
+  #[serde(rename = "s")]
+  pub size: u64,
+  /// The trade condition.
+  #[serde(rename = "c")]
+  pub condition: String,
+}
+
+
+/// A GET request to be made to the /v1beta1/options/trades/latest
+/// endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LastTradesReq {
+  /// The OCC symbols of the contracts to retrieve the last trade for.
+  #[serde(rename = "symbols", serialize_with = "string_slice_to_str")]
+  pub symbols: Vec<String>,
+}
+
+
+/// A helper for initializing [`LastTradesReq`] objects.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[allow(missing_copy_implementations)]
+pub struct LastTradesReqInit {
+  #[doc(hidden)]
+  pub _non_exhaustive: (),
+}
+
+impl LastTradesReqInit {
+  /// Create a [`LastTradesReq`] from a `LastTradesReqInit`.
+  #[inline]
+  pub fn init<I, S>(self, symbols: I) -> LastTradesReq
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    LastTradesReq {
+      symbols: symbols.into_iter().map(S::into).collect(),
+    }
+  }
+}
+
+
+EndpointNoParse! {
+  /// The representation of a GET request to the
+  /// /v1beta1/options/trades/latest endpoint.
+  pub GetLastTrades(LastTradesReq),
+  Ok => HashMap<String, Trade>, [
+    /// The last trades were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetLastTradesError, [
+    /// One of the provided symbols was invalid.
+    /* 400 */ BAD_REQUEST => InvalidInput,
+  ]
+
+  fn base_url() -> Option<Str> {
+    Some(DATA_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1beta1/options/trades/latest".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+
+  fn parse(body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
+    /// A helper object for parsing the response to a `GetLastTrades`
+    /// request.
+    #[derive(Deserialize)]
+    struct Response {
+      /// A mapping from symbols to the latest trade for each.
+      trades: HashMap<String, Trade>,
+    }
+
+    parse_json::<Response>(body).map(|response| response.trades)
+  }
+
+  fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
+    from_json::<Self::ApiError>(body).map_err(|_| body.to_vec())
+  }
+}
+
+
+/// A bar as returned by the /v1beta1/options/bars endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Bar {
+  /// The beginning time of this bar.
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
+  pub time: DateTime<Utc>,
+  /// The open price.
+  #[serde(rename = "o")]
+  pub open: Num,
+  /// The close price.
+  #[serde(rename = "c")]
+  pub close: Num,
+  /// The highest price.
+  #[serde(rename = "h")]
+  pub high: Num,
+  /// The lowest price.
+  #[serde(rename = "l")]
+  pub low: Num,
+  /// The traded volume, in contracts.
+  #[serde(rename = "v")]
+  pub volume: u64,
+  /// The volume weighted average price.
+  #[serde(rename = "vw", default)]
+  pub vwap: Option<Num>,
+  /// The number of trades that make up this bar.
+  #[serde(rename = "n", default)]
+  pub trade_count: Option<u64>,
+}
+
+
+/// A GET request to be made to the /v1beta1/options/bars endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BarsReq {
+  /// The OCC symbols of the contracts to retrieve bars for.
+  #[serde(rename = "symbols", serialize_with = "string_slice_to_str")]
+  pub symbols: Vec<String>,
+  /// The time frame for the bars.
+  #[serde(rename = "timeframe")]
+  pub timeframe: TimeFrame,
+  /// Filter bars equal to or after this time.
+  #[serde(rename = "start")]
+  pub start: DateTime<Utc>,
+  /// Filter bars equal to or before this time.
+  #[serde(rename = "end")]
+  pub end: DateTime<Utc>,
+  /// The maximum number of bars to be returned per page, across all
+  /// symbols.
+  #[serde(rename = "limit", skip_serializing_if = "Option::is_none")]
+  pub limit: Option<usize>,
+  /// If provided we will pass a page token to continue where we left off.
+  #[serde(rename = "page_token", skip_serializing_if = "Option::is_none")]
+  pub page_token: Option<String>,
+}
+
+
+/// A helper for initializing [`BarsReq`] objects.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BarsReqInit {
+  /// See `BarsReq::limit`.
+  pub limit: Option<usize>,
+  /// See `BarsReq::page_token`.
+  pub page_token: Option<String>,
+  #[doc(hidden)]
+  pub _non_exhaustive: (),
+}
+
+impl BarsReqInit {
+  /// Create a [`BarsReq`] from a `BarsReqInit`.
+  #[inline]
+  pub fn init<I, S>(
+    self,
+    symbols: I,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    timeframe: TimeFrame,
+  ) -> BarsReq
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    BarsReq {
+      symbols: symbols.into_iter().map(S::into).collect(),
+      timeframe,
+      start,
+      end,
+      limit: self.limit,
+      page_token: self.page_token,
+    }
+  }
+}
+
+
+/// A page of bars as returned by the /v1beta1/options/bars endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Bars {
+  /// A mapping from symbols to their bars.
+  #[serde(rename = "bars", default)]
+  pub bars: HashMap<String, Vec<Bar>>,
+  /// The token to provide to a request to get the next page of bars
+  /// for this request.
+  #[serde(rename = "next_page_token")]
+  pub next_page_token: Option<String>,
+}
+
+
+DataEndpoint! {
+  /// The representation of a GET request to the /v1beta1/options/bars
+  /// endpoint.
+  pub GetBars(BarsReq),
+  Ok => Bars, [
+    /// The bars were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetBarsError, [
+    /// A query parameter was invalid.
+    /* 400 */ BAD_REQUEST => InvalidInput,
+  ]
+
+  fn base_url() -> Option<Str> {
+    Some(DATA_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1beta1/options/bars".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+}
+
+/// Bars are paginated by token and, as they may cover multiple
+/// contracts, reported along with the contract's symbol.
+impl Paginated for GetBars {
+  type Item = (String, Bar);
+  type State = ();
+
+  fn paginate(
+    mut input: Self::Input,
+    output: Self::Output,
+    _state: &mut Self::State,
+  ) -> (Vec<Self::Item>, Option<Self::Input>) {
+    input.page_token = output.next_page_token;
+    let next = input.page_token.is_some().then_some(input);
+
+    let mut bars = output.bars.into_iter().collect::<Vec<_>>();
+    let () = bars.sort_by(|(symbol1, _), (symbol2, _)| symbol1.cmp(symbol2));
+    let bars = bars
+      .into_iter()
+      .flat_map(|(symbol, bars)| bars.into_iter().map(move |bar| (symbol.clone(), bar)))
+      .collect();
+    (bars, next)
+  }
+}
+
+
+/// The option market data feed to use.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum Feed {
+  /// The real-time feed of the Options Price Reporting Authority.
+  ///
+  /// This feed requires a market data subscription.
+  #[serde(rename = "opra")]
+  Opra,
+  /// The free indicative feed, with delayed and modified data.
+  ///
+  /// Greeks and implied volatility are not reported on this feed.
+  #[serde(rename = "indicative")]
+  Indicative,
+}
+
+
+/// An option contract quote as reported as part of a [`Snapshot`].
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Quote {
+  /// The time at which the quote was reported.
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
+  pub time: DateTime<Utc>,
+  /// The bid price, per share of the underlying.
+  #[serde(rename = "bp")]
+  pub bid_price: Num,
+  /// The bid size, in contracts.
+  #[serde(rename = "bs")]
+  pub bid_size: u64,
+  /// The ask price, per share of the underlying.
+  #[serde(rename = "ap")]
+  pub ask_price: Num,
+  /// The ask size, in contracts.
+  #[serde(rename = "as")]
+  pub ask_size: u64,
+}
+
+impl Quote {
+  /// Calculate the mid price of the quote.
+  #[inline]
+  pub fn mid_price(&self) -> Num {
+    (&self.bid_price + &self.ask_price) / 2
+  }
+}
+
+
+/// The greeks of an option contract.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Greeks {
+  /// The change of the option price per unit change of the price of
+  /// the underlying.
+  #[serde(rename = "delta")]
+  pub delta: f64,
+  /// The change of delta per unit change of the price of the
+  /// underlying.
+  #[serde(rename = "gamma")]
+  pub gamma: f64,
+  /// The change of the option price per calendar day.
+  #[serde(rename = "theta")]
+  pub theta: f64,
+  /// The change of the option price per percentage point change of
+  /// volatility.
+  #[serde(rename = "vega")]
+  pub vega: f64,
+  /// The change of the option price per percentage point change of
+  /// the risk-free interest rate.
+  #[serde(rename = "rho")]
+  pub rho: f64,
+}
+
+
+/// A snapshot of an option contract as returned by the
+/// /v1beta1/options/snapshots endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Snapshot {
+  /// The most recent trade.
+  #[serde(rename = "latestTrade", default)]
+  pub latest_trade: Option<Trade>,
+  /// The most recent quote.
+  #[serde(rename = "latestQuote", default)]
+  pub latest_quote: Option<Quote>,
+  /// The implied volatility, as a factor of 1.
+  #[serde(rename = "impliedVolatility", default)]
+  pub implied_volatility: Option<f64>,
+  /// The greeks of the contract.
+  #[serde(rename = "greeks", default)]
+  pub greeks: Option<Greeks>,
+}
+
+
+/// A GET request to be made to the /v1beta1/options/snapshots endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SnapshotsReq {
+  /// The OCC symbols of the contracts to retrieve snapshots for.
+  #[serde(rename = "symbols", serialize_with = "string_slice_to_str")]
+  pub symbols: Vec<String>,
+  /// The data feed to use.
+  #[serde(rename = "feed", skip_serializing_if = "Option::is_none")]
+  pub feed: Option<Feed>,
+}
+
+
+/// A helper for initializing [`SnapshotsReq`] objects.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SnapshotsReqInit {
+  /// See `SnapshotsReq::feed`.
+  pub feed: Option<Feed>,
+  #[doc(hidden)]
+  pub _non_exhaustive: (),
+}
+
+impl SnapshotsReqInit {
+  /// Create a [`SnapshotsReq`] from a `SnapshotsReqInit`.
+  #[inline]
+  pub fn init<I, S>(self, symbols: I) -> SnapshotsReq
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    SnapshotsReq {
+      symbols: symbols.into_iter().map(S::into).collect(),
+      feed: self.feed,
+    }
+  }
+}
+
+
+EndpointNoParse! {
+  /// The representation of a GET request to the
+  /// /v1beta1/options/snapshots endpoint.
+  pub GetSnapshots(SnapshotsReq),
+  Ok => HashMap<String, Snapshot>, [
+    /// The snapshots were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetSnapshotsError, [
+    /// One of the provided symbols was invalid.
+    /* 400 */ BAD_REQUEST => InvalidInput,
+  ]
+
+  fn base_url() -> Option<Str> {
+    Some(DATA_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1beta1/options/snapshots".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+
+  fn parse(body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
+    /// A helper object for parsing the response to a `GetSnapshots`
+    /// request.
+    #[derive(Deserialize)]
+    struct Response {
+      /// A mapping from symbols to the snapshot for each.
+      snapshots: HashMap<String, Snapshot>,
+    }
+
+    parse_json::<Response>(body).map(|response| response.snapshots)
+  }
+
+  fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
+    from_json::<Self::ApiError>(body).map_err(|_| body.to_vec())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use http_endpoint::Endpoint;
+
+  use test_log::test;
+
+  use crate::live;
+
+
+  /// Check that we can parse and format OCC option symbols.
+  #[test]
+  fn parse_format_osi_symbol() {
+    let symbol = OsiSymbol::from_str("AAPL240119C00190000").unwrap();
+    assert_eq!(symbol.underlying(), "AAPL");
+    assert_eq!(
+      symbol.expiration(),
+      NaiveDate::from_ymd_opt(2024, 1, 19).unwrap()
+    );
+    assert_eq!(symbol.type_(), ContractType::Call);
+    assert_eq!(symbol.strike(), &Num::from(190));
+    assert_eq!(symbol.to_string(), "AAPL240119C00190000");
+
+    let symbol = OsiSymbol::from_str("SPXW240119P04512500").unwrap();
+    assert_eq!(symbol.type_(), ContractType::Put);
+    assert_eq!(symbol.strike(), &Num::new(45125, 10));
+    assert_eq!(symbol.to_string(), "SPXW240119P04512500");
+  }
+
+  /// Check that invalid OCC option symbols are rejected.
+  #[test]
+  fn reject_invalid_osi_symbol() {
+    let tests = [
+      ("240119C00190000", OsiSymbolError::InvalidFormat),
+      (
+        "aapl240119C00190000",
+        OsiSymbolError::InvalidUnderlying("aapl".to_string()),
+      ),
+      (
+        "TOOLONG240119C00190000",
+        OsiSymbolError::InvalidUnderlying("TOOLONG".to_string()),
+      ),
+      (
+        "AAPL241319C00190000",
+        OsiSymbolError::InvalidExpiration("241319".to_string()),
+      ),
+      ("AAPL240119X00190000", OsiSymbolError::InvalidType('X')),
+      (
+        "AAPL240119C0019000A",
+        OsiSymbolError::InvalidStrike("0019000A".to_string()),
+      ),
+    ];
+
+    for (symbol, expected) in tests {
+      let err = OsiSymbol::from_str(symbol).unwrap_err();
+      assert_eq!(err, expected, "{}", symbol);
+    }
+  }
+
+  /// Check that we can build OCC option symbols and that strike prices
+  /// are validated.
+  #[test]
+  fn build_osi_symbol() {
+    let expiration = NaiveDate::from_ymd_opt(2024, 3, 15).unwrap();
+    let symbol = OsiSymbol::new("SPY", expiration, ContractType::Put, Num::new(4505, 10)).unwrap();
+    assert_eq!(symbol.to_string(), "SPY240315P00450500");
+    assert_eq!(
+      asset::Symbol::from(symbol),
+      asset::Symbol::Sym("SPY240315P00450500".to_string())
+    );
+
+    let result = OsiSymbol::new("SPY", expiration, ContractType::Call, Num::new(1, 10000));
+    assert_eq!(
+      result.unwrap_err(),
+      OsiSymbolError::InvalidStrike("0.0001".to_string())
+    );
+    let result = OsiSymbol::new("SPY", expiration, ContractType::Call, Num::from(100_000));
+    assert!(result.is_err());
+  }
+
+  /// Check that we can parse a reference latest trades response.
+  #[test]
+  fn parse_reference_trades() {
+    let response = br#"{
+  "trades": {
+    "AAPL240119C00190000": {
+      "c": "I",
+      "p": 2.03,
+      "s": 1,
+      "t": "2023-12-29T20:59:59.000Z",
+      "x": "C"
+    }
+  }
+}"#;
+
+    let trades = GetLastTrades::parse(response).unwrap();
+    let trade = &trades["AAPL240119C00190000"];
+    assert_eq!(trade.price, Num::new(203, 100));
+    assert_eq!(trade.size, 1);
+    assert_eq!(trade.exchange, 'C');
+    assert_eq!(trade.condition, "I");
+  }
+
+  /// Check that we can parse a reference bars response.
+  #[test]
+  fn parse_reference_bars() {
+    let response = br#"{
+  "bars": {
+    "AAPL240119C00190000": [
+      {
+        "c": 2.03,
+        "h": 2.4,
+        "l": 1.98,
+        "n": 212,
+        "o": 2.35,
+        "t": "2023-12-29T05:00:00Z",
+        "v": 1538,
+        "vw": 2.150247
+      }
+    ]
+  },
+  "next_page_token": "QUFQTDI0MDExOUMwMDE5MDAwMHxE"
+}"#;
+
+    let bars = GetBars::parse(response).unwrap();
+    let bar = &bars.bars["AAPL240119C00190000"][0];
+    assert_eq!(bar.high, Num::new(24, 10));
+    assert_eq!(bar.low, Num::new(198, 100));
+    assert_eq!(bar.volume, 1538);
+    assert!(bars.next_page_token.is_some());
+  }
+
+  /// Check that we can parse a reference snapshots response, with
+  /// and without greeks.
+  #[test]
+  fn parse_reference_snapshots() {
+    let response = br#"{
+  "next_page_token": null,
+  "snapshots": {
+    "AAPL240119C00190000": {
+      "greeks": {
+        "delta": 0.5215,
+        "gamma": 0.0473,
+        "rho": 0.0081,
+        "theta": -0.1612,
+        "vega": 0.0937
+      },
+      "impliedVolatility": 0.1824,
+      "latestQuote": {
+        "ap": 2.05,
+        "as": 12,
+        "ax": "C",
+        "bp": 2.01,
+        "bs": 8,
+        "bx": "X",
+        "c": "A",
+        "t": "2023-12-29T20:59:59.8Z"
+      }
+    },
+    "AAPL240119P00190000": {
+      "latestQuote": {
+        "ap": 1.5,
+        "as": 3,
+        "ax": "C",
+        "bp": 1.4,
+        "bs": 4,
+        "bx": "X",
+        "c": "A",
+        "t": "2023-12-29T20:59:59.8Z"
+      }
+    }
+  }
+}"#;
+
+    let snapshots = GetSnapshots::parse(response).unwrap();
+    let call = &snapshots["AAPL240119C00190000"];
+    assert_eq!(call.implied_volatility, Some(0.1824));
+    assert_eq!(call.greeks.unwrap().delta, 0.5215);
+    assert_eq!(
+      call.latest_quote.as_ref().unwrap().mid_price(),
+      Num::new(203, 100)
+    );
+    let put = &snapshots["AAPL240119P00190000"];
+    assert_eq!(put.greeks, None);
+    assert_eq!(put.implied_volatility, None);
+    assert_eq!(put.latest_trade, None);
+  }
+
+  /// Verify that we error out as expected when retrieving the last
+  /// trade of an invalid contract.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn request_invalid_contract() {
+    let client = live::client().unwrap();
+    let request = LastTradesReqInit::default().init(["SPY"]);
+    let result = client.issue::<GetLastTrades>(&request).await;
+    assert!(result.is_err(), "{:?}", result);
+  }
+}
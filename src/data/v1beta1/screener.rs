@@ -0,0 +1,318 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use num_decimal::Num;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_urlencoded::to_string as to_query;
+
+use crate::data::DATA_BASE_URL;
+use crate::util::datetime_from_str;
+use crate::util::vec_from_str;
+use crate::Str;
+
+
+/// The metric by which to rank the most active stocks.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub enum ActivityMetric {
+  /// Rank stocks by traded volume.
+  #[serde(rename = "volume")]
+  Volume,
+  /// Rank stocks by the number of trades.
+  #[serde(rename = "trades")]
+  Trades,
+}
+
+impl Default for ActivityMetric {
+  #[inline]
+  fn default() -> Self {
+    Self::Volume
+  }
+}
+
+
+/// A GET request to be issued to the
+/// /v1beta1/screener/stocks/most-actives endpoint.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct MostActivesReq {
+  /// The metric by which to rank stocks.
+  #[serde(rename = "by")]
+  pub by: ActivityMetric,
+  /// The number of stocks to report. Defaults to 10 if the provided
+  /// value is None.
+  #[serde(rename = "top", skip_serializing_if = "Option::is_none")]
+  pub top: Option<usize>,
+}
+
+
+/// A stock as reported by the /v1beta1/screener/stocks/most-actives
+/// endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct MostActive {
+  /// The stock's symbol.
+  #[serde(rename = "symbol")]
+  pub symbol: String,
+  /// The volume traded during the current day.
+  #[serde(rename = "volume")]
+  pub volume: u64,
+  /// The number of trades during the current day.
+  #[serde(rename = "trade_count")]
+  pub trade_count: u64,
+}
+
+
+/// The response of the /v1beta1/screener/stocks/most-actives endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct MostActives {
+  /// The most active stocks, most active first.
+  #[serde(rename = "most_actives", deserialize_with = "vec_from_str")]
+  pub most_actives: Vec<MostActive>,
+  /// The time the data were last updated at.
+  #[serde(rename = "last_updated", deserialize_with = "datetime_from_str")]
+  pub last_updated: DateTime<Utc>,
+}
+
+
+DataEndpoint! {
+  /// The representation of a GET request to the
+  /// /v1beta1/screener/stocks/most-actives endpoint.
+  pub GetMostActives(MostActivesReq),
+  Ok => MostActives, [
+    /// The most active stocks were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetMostActivesError, [
+    /// A query parameter was invalid.
+    /* 400 */ BAD_REQUEST => InvalidInput,
+  ]
+
+  fn base_url() -> Option<Str> {
+    Some(DATA_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1beta1/screener/stocks/most-actives".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+}
+
+
+/// The type of market to screen for movers.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[non_exhaustive]
+pub enum MarketType {
+  /// Stocks.
+  #[serde(rename = "stocks")]
+  Stocks,
+  /// Crypto currencies.
+  #[serde(rename = "crypto")]
+  Crypto,
+}
+
+impl AsRef<str> for MarketType {
+  #[inline]
+  fn as_ref(&self) -> &'static str {
+    match *self {
+      Self::Stocks => "stocks",
+      Self::Crypto => "crypto",
+    }
+  }
+}
+
+impl Default for MarketType {
+  #[inline]
+  fn default() -> Self {
+    Self::Stocks
+  }
+}
+
+
+/// A GET request to be issued to the
+/// /v1beta1/screener/<market-type>/movers endpoint.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize)]
+pub struct MoversReq {
+  /// The type of market to screen.
+  #[serde(skip)]
+  pub market_type: MarketType,
+  /// The number of gainers and losers to report each. Defaults to 10
+  /// if the provided value is None.
+  #[serde(rename = "top", skip_serializing_if = "Option::is_none")]
+  pub top: Option<usize>,
+}
+
+
+/// A symbol that moved significantly during the current day, as
+/// reported by the /v1beta1/screener/<market-type>/movers endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Mover {
+  /// The symbol.
+  #[serde(rename = "symbol")]
+  pub symbol: String,
+  /// The change in price relative to the previous close, in percent.
+  #[serde(rename = "percent_change")]
+  pub percent_change: Num,
+  /// The absolute change in price relative to the previous close.
+  #[serde(rename = "change")]
+  pub change: Num,
+  /// The current price.
+  #[serde(rename = "price")]
+  pub price: Num,
+}
+
+
+/// The response of the /v1beta1/screener/<market-type>/movers endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Movers {
+  /// The symbols that gained the most, top gainer first.
+  #[serde(rename = "gainers", deserialize_with = "vec_from_str")]
+  pub gainers: Vec<Mover>,
+  /// The symbols that lost the most, top loser first.
+  #[serde(rename = "losers", deserialize_with = "vec_from_str")]
+  pub losers: Vec<Mover>,
+  /// The type of market screened.
+  #[serde(rename = "market_type")]
+  pub market_type: MarketType,
+  /// The time the data were last updated at.
+  #[serde(rename = "last_updated", deserialize_with = "datetime_from_str")]
+  pub last_updated: DateTime<Utc>,
+}
+
+
+DataEndpoint! {
+  /// The representation of a GET request to the
+  /// /v1beta1/screener/<market-type>/movers endpoint.
+  pub GetMovers(MoversReq),
+  Ok => Movers, [
+    /// The market movers were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetMoversError, [
+    /// A query parameter was invalid.
+    /* 400 */ BAD_REQUEST => InvalidInput,
+  ]
+
+  fn base_url() -> Option<Str> {
+    Some(DATA_BASE_URL.into())
+  }
+
+  fn path(input: &Self::Input) -> Str {
+    format!("/v1beta1/screener/{}/movers", input.market_type.as_ref()).into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use http_endpoint::Endpoint;
+
+  use serde_json::from_str as from_json;
+
+  use test_log::test;
+
+  use crate::live;
+
+
+  /// Check that we create the expected paths and queries.
+  #[test]
+  fn request_paths() {
+    let request = MostActivesReq {
+      by: ActivityMetric::Trades,
+      top: Some(5),
+    };
+    let query = GetMostActives::query(&request).unwrap().unwrap();
+    assert_eq!(query, "by=trades&top=5");
+
+    let request = MoversReq {
+      market_type: MarketType::Crypto,
+      top: None,
+    };
+    assert_eq!(GetMovers::path(&request), "/v1beta1/screener/crypto/movers");
+    assert_eq!(GetMovers::query(&request).unwrap().unwrap(), "");
+  }
+
+  /// Verify that we can parse a reference most actives response.
+  #[test]
+  fn parse_reference_most_actives() {
+    let response = r#"{
+  "most_actives": [
+    {"symbol": "TSLA", "volume": 117035834, "trade_count": 1249861},
+    {"symbol": "AAPL", "volume": 91735271, "trade_count": 663133}
+  ],
+  "last_updated": "2022-10-13T19:59:59.9959Z"
+}"#;
+
+    let actives = from_json::<<GetMostActives as Endpoint>::Output>(response).unwrap();
+    assert_eq!(actives.most_actives.len(), 2);
+    assert_eq!(actives.most_actives[0].symbol, "TSLA");
+    assert_eq!(actives.most_actives[0].volume, 117035834);
+    assert_eq!(actives.most_actives[1].trade_count, 663133);
+    assert_eq!(
+      actives.last_updated,
+      DateTime::<Utc>::from_str("2022-10-13T19:59:59.9959Z").unwrap()
+    );
+  }
+
+  /// Verify that we can parse a reference movers response.
+  #[test]
+  fn parse_reference_movers() {
+    let response = r#"{
+  "gainers": [
+    {"symbol": "ABCD", "percent_change": 45.5, "change": 1.82, "price": 5.82}
+  ],
+  "losers": [
+    {"symbol": "EFGH", "percent_change": -30.25, "change": -2.42, "price": 5.58}
+  ],
+  "market_type": "stocks",
+  "last_updated": "2022-10-13T19:59:59.9959Z"
+}"#;
+
+    let movers = from_json::<<GetMovers as Endpoint>::Output>(response).unwrap();
+    assert_eq!(movers.market_type, MarketType::Stocks);
+    assert_eq!(movers.gainers.len(), 1);
+    assert_eq!(movers.gainers[0].percent_change, Num::new(455, 10));
+    assert_eq!(movers.losers[0].symbol, "EFGH");
+    assert_eq!(movers.losers[0].change, Num::new(-242, 100));
+  }
+
+  /// Check that we can retrieve the most active stocks and the market
+  /// movers.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn request_screeners() {
+    let client = live::client().unwrap();
+    let request = MostActivesReq {
+      top: Some(3),
+      ..Default::default()
+    };
+    let actives = client.issue::<GetMostActives>(&request).await.unwrap();
+    assert_eq!(actives.most_actives.len(), 3);
+
+    let request = MoversReq {
+      top: Some(3),
+      ..Default::default()
+    };
+    let movers = client.issue::<GetMovers>(&request).await.unwrap();
+    assert!(movers.gainers.len() <= 3);
+    assert!(movers.losers.len() <= 3);
+  }
+}
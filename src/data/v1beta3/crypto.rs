@@ -0,0 +1,410 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use num_decimal::Num;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::from_slice as from_json;
+use serde_urlencoded::to_string as to_query;
+
+use crate::data::v2::bars::TimeFrame;
+use crate::data::DATA_BASE_URL;
+use crate::endpoint::parse_json;
+use crate::util::datetime_from_str;
+use crate::util::string_slice_to_str;
+use crate::Paginated;
+use crate::Str;
+
+
+/// The side of the taker of a crypto currency trade.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+pub enum TakerSide {
+  /// The taker bought.
+  #[serde(rename = "B")]
+  Buy,
+  /// The taker sold.
+  #[serde(rename = "S")]
+  Sell,
+  /// Any other taker side that we have not accounted for.
+  #[serde(other)]
+  Unknown,
+}
+
+
+/// A crypto currency trade as returned by the
+/// /v1beta3/crypto/us/latest/trades endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Trade {
+  /// The time at which the trade happened.
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
+  pub time: DateTime<Utc>,
+  /// The trade's price.
+  #[serde(rename = "p")]
+  pub price: Num,
+  /// The trade's size, which may be fractional.
+  #[serde(rename = "s")]
+  pub size: Num,
+  /// The trade ID.
+  #[serde(rename = "i")]
+  pub trade_id: u64,
+  /// The side of the taker of the trade.
+  #[serde(rename = "tks")]
+  pub taker_side: TakerSide,
+}
+
+
+/// A GET request to be made to the /v1beta3/crypto/us/latest/trades
+/// endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct LastTradesReq {
+  /// The symbols to retrieve the last trade for, e.g., `BTC/USD`.
+  #[serde(rename = "symbols", serialize_with = "string_slice_to_str")]
+  pub symbols: Vec<String>,
+}
+
+
+/// A helper for initializing [`LastTradesReq`] objects.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[allow(missing_copy_implementations)]
+pub struct LastTradesReqInit {
+  #[doc(hidden)]
+  pub _non_exhaustive: (),
+}
+
+impl LastTradesReqInit {
+  /// Create a [`LastTradesReq`] from a `LastTradesReqInit`.
+  #[inline]
+  pub fn init<I, S>(self, symbols: I) -> LastTradesReq
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    LastTradesReq {
+      symbols: symbols.into_iter().map(S::into).collect(),
+    }
+  }
+}
+
+
+EndpointNoParse! {
+  /// The representation of a GET request to the
+  /// /v1beta3/crypto/us/latest/trades endpoint.
+  pub GetLastTrades(LastTradesReq),
+  Ok => HashMap<String, Trade>, [
+    /// The last trades were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetLastTradesError, [
+    /// One of the provided symbols was invalid.
+    /* 400 */ BAD_REQUEST => InvalidInput,
+  ]
+
+  fn base_url() -> Option<Str> {
+    Some(DATA_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1beta3/crypto/us/latest/trades".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+
+  fn parse(body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
+    /// A helper object for parsing the response to a `GetLastTrades`
+    /// request.
+    #[derive(Deserialize)]
+    struct Response {
+      /// A mapping from symbols to the latest trade for each.
+      trades: HashMap<String, Trade>,
+    }
+
+    parse_json::<Response>(body).map(|response| response.trades)
+  }
+
+  fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
+    from_json::<Self::ApiError>(body).map_err(|_| body.to_vec())
+  }
+}
+
+
+/// A bar as returned by the /v1beta3/crypto/us/bars endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Bar {
+  /// The beginning time of this bar.
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
+  pub time: DateTime<Utc>,
+  /// The open price.
+  #[serde(rename = "o")]
+  pub open: Num,
+  /// The close price.
+  #[serde(rename = "c")]
+  pub close: Num,
+  /// The highest price.
+  #[serde(rename = "h")]
+  pub high: Num,
+  /// The lowest price.
+  #[serde(rename = "l")]
+  pub low: Num,
+  /// The traded volume, which may be fractional.
+  #[serde(rename = "v")]
+  pub volume: Num,
+  /// The volume weighted average price.
+  #[serde(rename = "vw", default)]
+  pub vwap: Option<Num>,
+  /// The number of trades that make up this bar.
+  #[serde(rename = "n", default)]
+  pub trade_count: Option<u64>,
+}
+
+
+/// A GET request to be made to the /v1beta3/crypto/us/bars endpoint.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct BarsReq {
+  /// The symbols to retrieve bars for, e.g., `BTC/USD`.
+  #[serde(rename = "symbols", serialize_with = "string_slice_to_str")]
+  pub symbols: Vec<String>,
+  /// The time frame for the bars.
+  #[serde(rename = "timeframe")]
+  pub timeframe: TimeFrame,
+  /// Filter bars equal to or after this time.
+  #[serde(rename = "start")]
+  pub start: DateTime<Utc>,
+  /// Filter bars equal to or before this time.
+  #[serde(rename = "end")]
+  pub end: DateTime<Utc>,
+  /// The maximum number of bars to be returned per page, across all
+  /// symbols.
+  #[serde(rename = "limit", skip_serializing_if = "Option::is_none")]
+  pub limit: Option<usize>,
+  /// If provided we will pass a page token to continue where we left off.
+  #[serde(rename = "page_token", skip_serializing_if = "Option::is_none")]
+  pub page_token: Option<String>,
+}
+
+
+/// A helper for initializing [`BarsReq`] objects.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BarsReqInit {
+  /// See `BarsReq::limit`.
+  pub limit: Option<usize>,
+  /// See `BarsReq::page_token`.
+  pub page_token: Option<String>,
+  #[doc(hidden)]
+  pub _non_exhaustive: (),
+}
+
+impl BarsReqInit {
+  /// Create a [`BarsReq`] from a `BarsReqInit`.
+  #[inline]
+  pub fn init<I, S>(
+    self,
+    symbols: I,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    timeframe: TimeFrame,
+  ) -> BarsReq
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    BarsReq {
+      symbols: symbols.into_iter().map(S::into).collect(),
+      timeframe,
+      start,
+      end,
+      limit: self.limit,
+      page_token: self.page_token,
+    }
+  }
+}
+
+
+/// A page of bars as returned by the /v1beta3/crypto/us/bars endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Bars {
+  /// A mapping from symbols to their bars.
+  #[serde(rename = "bars", default)]
+  pub bars: HashMap<String, Vec<Bar>>,
+  /// The token to provide to a request to get the next page of bars
+  /// for this request.
+  #[serde(rename = "next_page_token")]
+  pub next_page_token: Option<String>,
+}
+
+
+DataEndpoint! {
+  /// The representation of a GET request to the /v1beta3/crypto/us/bars
+  /// endpoint.
+  pub GetBars(BarsReq),
+  Ok => Bars, [
+    /// The bars were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetBarsError, [
+    /// A query parameter was invalid.
+    /* 400 */ BAD_REQUEST => InvalidInput,
+  ]
+
+  fn base_url() -> Option<Str> {
+    Some(DATA_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v1beta3/crypto/us/bars".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+}
+
+/// Pages are continued by means of the token reported with each. The
+/// bars of each page are reported along with their symbol, ordered by
+/// symbol.
+impl Paginated for GetBars {
+  type Item = (String, Bar);
+  type State = ();
+
+  fn paginate(
+    mut input: Self::Input,
+    output: Self::Output,
+    _state: &mut Self::State,
+  ) -> (Vec<Self::Item>, Option<Self::Input>) {
+    input.page_token = output.next_page_token;
+    let next = input.page_token.is_some().then_some(input);
+
+    let mut bars = output.bars.into_iter().collect::<Vec<_>>();
+    let () = bars.sort_by(|(symbol1, _), (symbol2, _)| symbol1.cmp(symbol2));
+    let bars = bars
+      .into_iter()
+      .flat_map(|(symbol, bars)| bars.into_iter().map(move |bar| (symbol.clone(), bar)))
+      .collect();
+    (bars, next)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use http_endpoint::Endpoint;
+
+  use test_log::test;
+
+  use crate::live;
+
+
+  /// Check that we can serialize a [`LastTradesReq`] into a query
+  /// string.
+  #[test]
+  fn serialize_request() {
+    let request = LastTradesReqInit::default().init(["BTC/USD", "ETH/USD"]);
+    let query = to_query(&request).unwrap();
+    assert_eq!(query, "symbols=BTC%2FUSD%2CETH%2FUSD");
+  }
+
+  /// Check that we can parse a reference latest trades response.
+  #[test]
+  fn parse_reference_trades() {
+    let response = br#"{
+  "trades": {
+    "BTC/USD": {
+      "i": 3447074161,
+      "p": 16831.25,
+      "s": 0.0127,
+      "t": "2022-12-19T14:02:24.337446Z",
+      "tks": "S"
+    }
+  }
+}"#;
+
+    let trades = GetLastTrades::parse(response).unwrap();
+    let trade = &trades["BTC/USD"];
+    assert_eq!(trade.price, Num::new(1683125, 100));
+    assert_eq!(trade.size, Num::new(127, 10000));
+    assert_eq!(trade.trade_id, 3447074161);
+    assert_eq!(trade.taker_side, TakerSide::Sell);
+  }
+
+  /// Check that we can parse a reference bars response.
+  #[test]
+  fn parse_reference_bars() {
+    let response = br#"{
+  "bars": {
+    "BTC/USD": [
+      {
+        "c": 16831.5,
+        "h": 16840,
+        "l": 16825,
+        "n": 14,
+        "o": 16830,
+        "t": "2022-12-19T14:00:00Z",
+        "v": 0.3514,
+        "vw": 16832.4
+      }
+    ]
+  },
+  "next_page_token": null
+}"#;
+
+    let bars = GetBars::parse(response).unwrap();
+    let bar = &bars.bars["BTC/USD"][0];
+    assert_eq!(bar.open, Num::from(16830));
+    assert_eq!(bar.close, Num::new(168315, 10));
+    assert_eq!(bar.volume, Num::new(3514, 10000));
+    assert_eq!(bar.trade_count, Some(14));
+    assert_eq!(bars.next_page_token, None);
+  }
+
+  /// Check that we report the bars of a page ordered by symbol and
+  /// continue with the reported page token.
+  #[test]
+  fn paginate_bars() {
+    let response = br#"{
+  "bars": {
+    "ETH/USD": [{"t": "2022-12-19T14:00:00Z", "o": 1, "h": 1, "l": 1, "c": 1, "v": 2}],
+    "BTC/USD": [
+      {"t": "2022-12-19T14:00:00Z", "o": 1, "h": 1, "l": 1, "c": 1, "v": 1},
+      {"t": "2022-12-19T15:00:00Z", "o": 1, "h": 1, "l": 1, "c": 1, "v": 1}
+    ]
+  },
+  "next_page_token": "abc"
+}"#;
+    let bars = GetBars::parse(response).unwrap();
+    let start = DateTime::from_timestamp(1671458400, 0).unwrap();
+    let request =
+      BarsReqInit::default().init(["BTC/USD", "ETH/USD"], start, start, TimeFrame::OneHour);
+
+    let (bars, next) = GetBars::paginate(request, bars, &mut ());
+    let symbols = bars
+      .iter()
+      .map(|(symbol, _)| symbol.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(symbols, ["BTC/USD", "BTC/USD", "ETH/USD"]);
+    assert_eq!(next.unwrap().page_token.as_deref(), Some("abc"));
+  }
+
+  /// Verify that we can retrieve the last trade of a crypto currency.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn request_last_trades() {
+    let client = live::client().unwrap();
+    let request = LastTradesReqInit::default().init(["BTC/USD"]);
+    let trades = client.issue::<GetLastTrades>(&request).await.unwrap();
+    assert!(trades["BTC/USD"].price > Num::from(0));
+  }
+}
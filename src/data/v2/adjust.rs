@@ -0,0 +1,301 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use chrono::NaiveDate;
+
+use num_decimal::Num;
+
+use crate::api::v2::announcements::Action;
+use crate::api::v2::announcements::Announcement;
+use crate::data::v2::bars::Adjustment;
+use crate::data::v2::bars::Bar;
+
+
+/// A corporate action affecting historical prices of a security.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum CorporateAction {
+  /// A stock split (or reverse split).
+  Split {
+    /// The first date on which the security trades split.
+    ex_date: NaiveDate,
+    /// The number of shares before the split.
+    old_rate: Num,
+    /// The number of shares after the split.
+    new_rate: Num,
+  },
+  /// A cash dividend.
+  Dividend {
+    /// The first date on which the security trades without the
+    /// dividend.
+    ex_date: NaiveDate,
+    /// The cash amount paid per share.
+    cash: Num,
+  },
+}
+
+impl CorporateAction {
+  /// Create a `CorporateAction` from a corporate action announcement.
+  ///
+  /// `None` is returned for announcements not affecting the price of
+  /// the initiating security in a way we can account for, such as
+  /// mergers, stock dividends, or announcements lacking relevant data.
+  pub fn from_announcement(announcement: &Announcement) -> Option<Self> {
+    let ex_date = announcement.ex_date?;
+
+    match &announcement.action {
+      Action::Split { old_rate, new_rate } => {
+        let old_rate = old_rate.clone()?;
+        let new_rate = new_rate.clone()?;
+        if old_rate.is_positive() && new_rate.is_positive() {
+          Some(Self::Split {
+            ex_date,
+            old_rate,
+            new_rate,
+          })
+        } else {
+          None
+        }
+      },
+      Action::Dividend { cash, .. } if announcement.sub_type == "cash" => {
+        let cash = cash.clone()?;
+        if cash.is_positive() {
+          Some(Self::Dividend { ex_date, cash })
+        } else {
+          None
+        }
+      },
+      _ => None,
+    }
+  }
+
+  /// Retrieve the ex-date of the corporate action.
+  #[inline]
+  pub fn ex_date(&self) -> NaiveDate {
+    match self {
+      Self::Split { ex_date, .. } | Self::Dividend { ex_date, .. } => *ex_date,
+    }
+  }
+
+  /// Check whether the corporate action is covered by the provided
+  /// adjustment policy.
+  fn is_covered_by(&self, adjustment: Adjustment) -> bool {
+    matches!(
+      (self, adjustment),
+      (_, Adjustment::All)
+        | (Self::Split { .. }, Adjustment::Split)
+        | (Self::Dividend { .. }, Adjustment::Dividend)
+    )
+  }
+
+  /// Compute the price and volume factors to apply to bars prior to
+  /// the ex-date.
+  fn factors(&self, bars: &[Bar]) -> Option<(Num, Num)> {
+    match self {
+      Self::Split {
+        old_rate, new_rate, ..
+      } => Some((old_rate / new_rate, new_rate / old_rate)),
+      Self::Dividend { ex_date, cash } => {
+        // Dividends are accounted for by a ratio based on the last
+        // close price before the ex-date.
+        let close = &bars
+          .iter()
+          .filter(|bar| bar.time.naive_utc().date() < *ex_date)
+          .max_by_key(|bar| bar.time)?
+          .close;
+
+        if cash < close {
+          Some(((close - cash) / close, Num::from(1)))
+        } else {
+          None
+        }
+      },
+    }
+  }
+}
+
+
+/// Back-adjust raw bars for the provided corporate actions.
+///
+/// All bars dated before a corporate action's ex-date are adjusted,
+/// with bar dates being evaluated in UTC. Splits scale prices and
+/// volumes by the split ratio, while cash dividends scale prices by
+/// the ratio of the dividend to the last close price before the
+/// ex-date. Only actions covered by `adjustment` are taken into
+/// account.
+///
+/// The provided bars are expected to be unadjusted, i.e., retrieved
+/// using [`Adjustment::Raw`].
+pub fn adjust_bars(bars: &mut [Bar], actions: &[CorporateAction], adjustment: Adjustment) {
+  // Factors are computed based on the raw data before applying any of
+  // them, so that the order of actions is irrelevant.
+  let factors = actions
+    .iter()
+    .filter(|action| action.is_covered_by(adjustment))
+    .filter_map(|action| {
+      action
+        .factors(bars)
+        .map(|(price, volume)| (action.ex_date(), price, volume))
+    })
+    .collect::<Vec<_>>();
+
+  for (ex_date, price, volume) in factors {
+    for bar in bars
+      .iter_mut()
+      .filter(|bar| bar.time.naive_utc().date() < ex_date)
+    {
+      bar.open = &bar.open * &price;
+      bar.close = &bar.close * &price;
+      bar.high = &bar.high * &price;
+      bar.low = &bar.low * &price;
+      bar.vwap = bar.vwap.as_ref().map(|vwap| vwap * &price);
+      bar.volume = (&volume * bar.volume)
+        .round()
+        .to_u64()
+        .map(|volume| volume as usize)
+        .unwrap_or(bar.volume);
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use chrono::DateTime;
+  use chrono::Utc;
+
+  use serde_json::from_str as from_json;
+
+
+  /// Create a daily bar with the provided date and close price.
+  fn bar(date: &str, close: i64, volume: usize) -> Bar {
+    Bar {
+      time: DateTime::<Utc>::from_str(&format!("{}T04:00:00Z", date)).unwrap(),
+      open: Num::from(close),
+      close: Num::from(close),
+      high: Num::from(close),
+      low: Num::from(close),
+      volume,
+      vwap: Some(Num::from(close)),
+      trade_count: None,
+    }
+  }
+
+  /// Create a `NaiveDate` from a string.
+  fn date(date: &str) -> NaiveDate {
+    NaiveDate::from_str(date).unwrap()
+  }
+
+
+  /// Check that we can convert announcements into corporate actions.
+  #[test]
+  fn convert_announcement() {
+    let json = r#"{
+  "id": "be3c368a-4c7c-4384-808e-f02c9f5a8afe",
+  "corporate_action_id": "F58684224_XY37",
+  "ca_type": "split",
+  "ca_sub_type": "stock_split",
+  "initiating_symbol": "AAPL",
+  "target_symbol": null,
+  "declaration_date": "2020-07-30",
+  "ex_date": "2020-08-31",
+  "record_date": "2020-08-24",
+  "payable_date": "2020-08-28",
+  "cash": "0",
+  "old_rate": "1",
+  "new_rate": "4"
+}"#;
+    let announcement = from_json::<Announcement>(json).unwrap();
+    let action = CorporateAction::from_announcement(&announcement).unwrap();
+    let expected = CorporateAction::Split {
+      ex_date: date("2020-08-31"),
+      old_rate: Num::from(1),
+      new_rate: Num::from(4),
+    };
+    assert_eq!(action, expected);
+
+    let json = r#"{
+  "id": "7a9a5ba2-6b2a-4d0b-9ba5-7e5c2b1a9e0f",
+  "corporate_action_id": "F58684224_XY38",
+  "ca_type": "merger",
+  "ca_sub_type": "merger_completion",
+  "initiating_symbol": "XYZ",
+  "target_symbol": "ABC",
+  "ex_date": "2020-08-31",
+  "cash": "10",
+  "old_rate": "1",
+  "new_rate": "2"
+}"#;
+    let announcement = from_json::<Announcement>(json).unwrap();
+    assert_eq!(CorporateAction::from_announcement(&announcement), None);
+  }
+
+  /// Check that we adjust bars for splits and dividends as requested.
+  #[test]
+  fn adjust_for_actions() {
+    let raw = vec![
+      bar("2020-08-27", 400, 100),
+      bar("2020-08-28", 500, 100),
+      bar("2020-08-31", 125, 400),
+      bar("2020-09-01", 120, 400),
+    ];
+    let actions = [
+      CorporateAction::Split {
+        ex_date: date("2020-08-31"),
+        old_rate: Num::from(1),
+        new_rate: Num::from(4),
+      },
+      CorporateAction::Dividend {
+        ex_date: date("2020-09-01"),
+        cash: Num::from(5),
+      },
+    ];
+
+    let mut bars = raw.clone();
+    adjust_bars(&mut bars, &actions, Adjustment::Raw);
+    assert_eq!(bars, raw);
+
+    let mut bars = raw.clone();
+    adjust_bars(&mut bars, &actions, Adjustment::Split);
+    assert_eq!(bars[0].close, Num::from(100));
+    assert_eq!(bars[0].vwap, Some(Num::from(100)));
+    assert_eq!(bars[0].volume, 400);
+    assert_eq!(bars[1].close, Num::from(125));
+    assert_eq!(bars[2], raw[2]);
+    assert_eq!(bars[3], raw[3]);
+
+    let mut bars = raw.clone();
+    adjust_bars(&mut bars, &actions, Adjustment::Dividend);
+    // The dividend ratio is (125 - 5) / 125 = 24/25.
+    assert_eq!(bars[0].close, Num::from(384));
+    assert_eq!(bars[0].volume, 100);
+    assert_eq!(bars[2].close, Num::from(120));
+    assert_eq!(bars[3], raw[3]);
+
+    let mut bars = raw.clone();
+    adjust_bars(&mut bars, &actions, Adjustment::All);
+    assert_eq!(bars[0].close, Num::from(96));
+    assert_eq!(bars[0].volume, 400);
+    assert_eq!(bars[1].close, Num::from(120));
+    assert_eq!(bars[2].close, Num::from(120));
+    assert_eq!(bars[3], raw[3]);
+  }
+
+  /// Check that dividends without a preceding bar are ignored.
+  #[test]
+  fn ignore_dividend_without_reference_close() {
+    let raw = vec![bar("2020-09-01", 120, 400)];
+    let actions = [CorporateAction::Dividend {
+      ex_date: date("2020-09-01"),
+      cash: Num::from(5),
+    }];
+
+    let mut bars = raw.clone();
+    adjust_bars(&mut bars, &actions, Adjustment::All);
+    assert_eq!(bars, raw);
+  }
+}
@@ -0,0 +1,365 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use futures::pin_mut;
+use futures::Stream;
+use futures::StreamExt as _;
+
+use serde_json::Error as JsonError;
+
+use tokio::sync::Notify;
+
+use tracing::warn;
+
+use websocket_util::tungstenite::Error as WebSocketError;
+
+use crate::data::v2::stream::Data;
+use crate::Error;
+
+
+/// The policy to apply when a consumer of market data falls behind.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum Policy {
+  /// Buffer all data, without bounds.
+  Unbounded,
+  /// Buffer up to the given number of data items, dropping the oldest
+  /// one to make room for new data.
+  DropOldest(usize),
+  /// Buffer up to the given number of data items, dropping new data
+  /// while the buffer is full.
+  DropNewest(usize),
+  /// Buffer all data, but keep only the latest pending quote per
+  /// symbol. Bars and trades are never coalesced.
+  CoalesceQuotes,
+}
+
+
+/// The mutable state of a [`Buffer`].
+#[derive(Debug, Default)]
+struct State {
+  /// The buffered data.
+  queue: VecDeque<Data>,
+  /// The sequence number of the data item at the front of `queue`.
+  head: u64,
+  /// The sequence numbers of pending quotes, by symbol. Only used
+  /// with `Policy::CoalesceQuotes`.
+  quotes: HashMap<String, u64>,
+  /// The number of data items dropped.
+  dropped: u64,
+  /// The number of quotes replaced by more recent ones.
+  coalesced: u64,
+  /// Whether the producing side is done.
+  closed: bool,
+}
+
+
+/// The state shared between all handles to a [`Buffer`].
+#[derive(Debug)]
+struct Shared {
+  /// The policy to apply.
+  policy: Policy,
+  /// The actual buffer state.
+  state: Mutex<State>,
+  /// Used for waking a waiting consumer.
+  notify: Notify,
+}
+
+
+/// A buffer decoupling the reception of market data from its
+/// consumption, applying a configurable [`Policy`] when the consumer
+/// falls behind.
+///
+/// Data are pushed into the buffer via [`Buffer::push`] or by having
+/// [`Buffer::run`] drive a stream, and retrieved on the consuming side
+/// via [`Buffer::pop`]. A buffer is meant to be used by a single
+/// consumer; handles can be cloned for the producing side.
+#[derive(Clone, Debug)]
+pub struct Buffer {
+  /// The state shared between all handles.
+  shared: Arc<Shared>,
+}
+
+impl Buffer {
+  /// Create a new `Buffer` applying the given policy.
+  pub fn new(policy: Policy) -> Self {
+    Self {
+      shared: Arc::new(Shared {
+        policy,
+        state: Mutex::new(State::default()),
+        notify: Notify::new(),
+      }),
+    }
+  }
+
+  /// Push a data item into the buffer.
+  pub fn push(&self, data: Data) {
+    {
+      let mut state = self.shared.state.lock().unwrap();
+      match self.shared.policy {
+        Policy::Unbounded => state.queue.push_back(data),
+        Policy::DropOldest(capacity) => {
+          if state.queue.len() >= capacity {
+            if state.queue.pop_front().is_some() {
+              state.head += 1;
+            }
+            state.dropped += 1;
+          }
+          if capacity > 0 {
+            state.queue.push_back(data)
+          }
+        },
+        Policy::DropNewest(capacity) => {
+          if state.queue.len() >= capacity {
+            state.dropped += 1;
+          } else {
+            state.queue.push_back(data)
+          }
+        },
+        Policy::CoalesceQuotes => {
+          if let Data::Quote(quote) = &data {
+            if let Some(seq) = state.quotes.get(&quote.symbol) {
+              let idx = (seq - state.head) as usize;
+              state.queue[idx] = data;
+              state.coalesced += 1;
+            } else {
+              let seq = state.head + state.queue.len() as u64;
+              let _ = state.quotes.insert(quote.symbol.clone(), seq);
+              state.queue.push_back(data)
+            }
+          } else {
+            state.queue.push_back(data)
+          }
+        },
+      }
+    }
+    self.shared.notify.notify_one()
+  }
+
+  /// Mark the producing side as done.
+  ///
+  /// Buffered data can still be retrieved, after which [`Buffer::pop`]
+  /// reports the end of data.
+  pub fn close(&self) {
+    self.shared.state.lock().unwrap().closed = true;
+    self.shared.notify.notify_one()
+  }
+
+  /// Retrieve the oldest buffered data item, if any, without waiting.
+  pub fn try_pop(&self) -> Option<Data> {
+    let mut state = self.shared.state.lock().unwrap();
+    let data = state.queue.pop_front()?;
+    let seq = state.head;
+    state.head += 1;
+
+    if let Data::Quote(quote) = &data {
+      if state.quotes.get(&quote.symbol) == Some(&seq) {
+        let _ = state.quotes.remove(&quote.symbol);
+      }
+    }
+    Some(data)
+  }
+
+  /// Retrieve the oldest buffered data item, waiting for one to
+  /// arrive if necessary.
+  ///
+  /// `None` is returned once the buffer got closed and all buffered
+  /// data were retrieved.
+  pub async fn pop(&self) -> Option<Data> {
+    loop {
+      if let Some(data) = self.try_pop() {
+        return Some(data)
+      }
+      if self.shared.state.lock().unwrap().closed {
+        return None
+      }
+      self.shared.notify.notified().await;
+    }
+  }
+
+  /// Push all market data received over the provided stream into the
+  /// buffer, closing it once the stream is exhausted or a websocket
+  /// error is encountered.
+  ///
+  /// Data failing to parse are logged and skipped.
+  pub async fn run<S>(&self, stream: S) -> Result<(), Error>
+  where
+    S: Stream<Item = Result<Result<Data, JsonError>, WebSocketError>>,
+  {
+    pin_mut!(stream);
+
+    let result = loop {
+      match stream.next().await {
+        Some(Ok(Ok(data))) => self.push(data),
+        Some(Ok(Err(err))) => warn!("failed to parse market data: {}", err),
+        Some(Err(err)) => break Err(Error::WebSocket(err)),
+        None => break Ok(()),
+      }
+    };
+
+    self.close();
+    result
+  }
+
+  /// Retrieve the number of currently buffered data items.
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.shared.state.lock().unwrap().queue.len()
+  }
+
+  /// Check whether the buffer is currently empty.
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Retrieve the number of data items dropped so far.
+  #[inline]
+  pub fn dropped(&self) -> u64 {
+    self.shared.state.lock().unwrap().dropped
+  }
+
+  /// Retrieve the number of quotes replaced by more recent ones so far.
+  #[inline]
+  pub fn coalesced(&self) -> u64 {
+    self.shared.state.lock().unwrap().coalesced
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use chrono::DateTime;
+
+  use futures::stream::iter;
+
+  use num_decimal::Num;
+
+  use test_log::test;
+
+  use crate::data::v2::stream::Quote;
+  use crate::data::v2::stream::Trade;
+
+
+  /// Create a trade for the given symbol.
+  fn trade(symbol: &str, id: u64) -> Data {
+    Data::Trade(Trade {
+      symbol: symbol.to_string(),
+      trade_id: id,
+      exchange: 'V',
+      trade_price: Num::from(100),
+      trade_size: 1,
+      timestamp: DateTime::from_str("2022-03-01T14:30:00Z").unwrap(),
+      trade_conditions: Vec::new(),
+      tape: 'A',
+    })
+  }
+
+  /// Create a quote for the given symbol.
+  fn quote(symbol: &str, bid: i32) -> Data {
+    Data::Quote(Quote {
+      symbol: symbol.to_string(),
+      bid_price: Num::from(bid),
+      bid_size: 1,
+      ask_price: Num::from(bid + 1),
+      ask_size: 1,
+      timestamp: DateTime::from_str("2022-03-01T14:30:00Z").unwrap(),
+    })
+  }
+
+  /// Retrieve the trade IDs of all buffered data.
+  fn drain_trades(buffer: &Buffer) -> Vec<u64> {
+    let mut ids = Vec::new();
+    while let Some(data) = buffer.try_pop() {
+      match data {
+        Data::Trade(trade) => ids.push(trade.trade_id),
+        _ => panic!("encountered unexpected data: {:?}", data),
+      }
+    }
+    ids
+  }
+
+
+  /// Check that the drop-oldest and drop-newest policies work as
+  /// expected.
+  #[test]
+  fn drop_policies() {
+    let buffer = Buffer::new(Policy::DropOldest(2));
+    (1..=4).for_each(|id| buffer.push(trade("SPY", id)));
+    assert_eq!(buffer.dropped(), 2);
+    assert_eq!(drain_trades(&buffer), vec![3, 4]);
+
+    let buffer = Buffer::new(Policy::DropNewest(2));
+    (1..=4).for_each(|id| buffer.push(trade("SPY", id)));
+    assert_eq!(buffer.dropped(), 2);
+    assert_eq!(drain_trades(&buffer), vec![1, 2]);
+
+    let buffer = Buffer::new(Policy::Unbounded);
+    (1..=4).for_each(|id| buffer.push(trade("SPY", id)));
+    assert_eq!(buffer.dropped(), 0);
+    assert_eq!(drain_trades(&buffer), vec![1, 2, 3, 4]);
+  }
+
+  /// Check that only the latest pending quote per symbol is kept with
+  /// the coalescing policy.
+  #[test]
+  fn coalesce_quotes() {
+    let buffer = Buffer::new(Policy::CoalesceQuotes);
+    buffer.push(quote("SPY", 1));
+    buffer.push(trade("SPY", 1));
+    buffer.push(quote("AAPL", 10));
+    buffer.push(quote("SPY", 2));
+    buffer.push(quote("SPY", 3));
+    assert_eq!(buffer.len(), 3);
+    assert_eq!(buffer.coalesced(), 2);
+
+    let data = buffer.try_pop().unwrap();
+    assert!(matches!(data, Data::Quote(quote) if quote.bid_price == Num::from(3)));
+
+    // With the pending SPY quote gone, a new one gets queued up again.
+    buffer.push(quote("SPY", 4));
+    assert_eq!(buffer.len(), 3);
+
+    assert!(buffer.try_pop().unwrap().is_trade());
+    let data = buffer.try_pop().unwrap();
+    assert!(matches!(data, Data::Quote(quote) if quote.symbol == "AAPL"));
+    let data = buffer.try_pop().unwrap();
+    assert!(matches!(data, Data::Quote(quote) if quote.bid_price == Num::from(4)));
+    assert!(buffer.try_pop().is_none());
+  }
+
+  /// Check that a consumer receives all data pushed by a stream and is
+  /// informed about the end of it.
+  #[test(tokio::test)]
+  async fn consume_stream() {
+    let buffer = Buffer::new(Policy::Unbounded);
+    let consumer = buffer.clone();
+    let handle = tokio::spawn(async move {
+      let mut ids = Vec::new();
+      while let Some(data) = consumer.pop().await {
+        match data {
+          Data::Trade(trade) => ids.push(trade.trade_id),
+          _ => panic!("encountered unexpected data: {:?}", data),
+        }
+      }
+      ids
+    });
+
+    let stream = iter([
+      Ok(Ok(trade("SPY", 1))),
+      Ok(Ok(trade("SPY", 2))),
+      Ok(Ok(trade("SPY", 3))),
+    ]);
+    let () = buffer.run(stream).await.unwrap();
+    assert_eq!(handle.await.unwrap(), vec![1, 2, 3]);
+  }
+}
@@ -12,7 +12,10 @@ use serde_urlencoded::to_string as to_query;
 
 use crate::data::v2::Feed;
 use crate::data::DATA_BASE_URL;
+use crate::money;
+use crate::util::datetime_from_str;
 use crate::util::vec_from_str;
+use crate::Paginated;
 use crate::Str;
 
 
@@ -132,7 +135,7 @@ impl BarsReqInit {
 #[non_exhaustive]
 pub struct Bar {
   /// The beginning time of this bar.
-  #[serde(rename = "t")]
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
   pub time: DateTime<Utc>,
   /// The open price.
   #[serde(rename = "o")]
@@ -149,6 +152,57 @@ pub struct Bar {
   /// The trading volume.
   #[serde(rename = "v")]
   pub volume: usize,
+  /// The volume weighted average price.
+  #[serde(rename = "vw", default)]
+  pub vwap: Option<Num>,
+  /// The number of trades that make up this bar.
+  #[serde(rename = "n", default)]
+  pub trade_count: Option<u64>,
+}
+
+impl Bar {
+  /// Retrieve the range the price traded in during the bar, i.e., the
+  /// difference between its high and low prices.
+  #[inline]
+  pub fn range(&self) -> Num {
+    &self.high - &self.low
+  }
+
+  /// Retrieve the change of the close price relative to the open price
+  /// (as a factor of 1).
+  ///
+  /// `None` is returned if the open price is zero.
+  pub fn change(&self) -> Option<Num> {
+    money::relative_change(&self.open, &self.close)
+  }
+}
+
+
+/// Compute the volume weighted average price across a set of bars,
+/// e.g., the minute bars of a trading session.
+///
+/// Bars lacking a VWAP are accounted for with their typical price,
+/// i.e., the average of high, low, and close. `None` is returned if
+/// the combined volume of all bars is zero.
+pub fn session_vwap<'b, I>(bars: I) -> Option<Num>
+where
+  I: IntoIterator<Item = &'b Bar>,
+{
+  let (value, volume) = bars
+    .into_iter()
+    .fold((Num::from(0), 0usize), |(value, volume), bar| {
+      let price = match &bar.vwap {
+        Some(vwap) => vwap.clone(),
+        None => (&bar.high + &bar.low + &bar.close) / 3,
+      };
+      (value + price * bar.volume, volume + bar.volume)
+    });
+
+  if volume == 0 {
+    None
+  } else {
+    Some(value / volume)
+  }
 }
 
 
@@ -166,7 +220,7 @@ pub struct Bars {
 }
 
 
-Endpoint! {
+DataEndpoint! {
   /// The representation of a GET request to the /v2/stocks/<symbol>/bars endpoint.
   pub Get(BarsReq),
   Ok => Bars, [
@@ -191,6 +245,23 @@ Endpoint! {
   }
 }
 
+/// The next page of bars is retrieved using the token reported along
+/// with the current one.
+impl Paginated for Get {
+  type Item = Bar;
+  type State = ();
+
+  fn paginate(
+    mut input: Self::Input,
+    output: Self::Output,
+    _state: &mut Self::State,
+  ) -> (Vec<Self::Item>, Option<Self::Input>) {
+    input.page_token = output.next_page_token;
+    let next = input.page_token.is_some().then_some(input);
+    (output.bars, next)
+  }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -204,8 +275,7 @@ mod tests {
 
   use test_log::test;
 
-  use crate::api_info::ApiInfo;
-  use crate::Client;
+  use crate::live;
   use crate::RequestError;
 
 
@@ -220,7 +290,9 @@ mod tests {
         "h": 133.74,
         "l": 133.31,
         "c": 133.5,
-        "v": 9876
+        "v": 9876,
+        "n": 105,
+        "vw": 133.53
       },
       {
         "t": "2021-02-01T16:02:00Z",
@@ -245,15 +317,66 @@ mod tests {
     assert_eq!(bars[0].high, Num::new(13374, 100));
     assert_eq!(bars[0].low, Num::new(13331, 100));
     assert_eq!(bars[0].volume, 9876);
+    assert_eq!(bars[0].vwap, Some(Num::new(13353, 100)));
+    assert_eq!(bars[0].trade_count, Some(105));
+    assert_eq!(bars[1].vwap, None);
+    assert_eq!(bars[1].trade_count, None);
     assert_eq!(res.symbol, "AAPL".to_string());
     assert!(res.next_page_token.is_some())
   }
 
+  /// Check that we compute the volume weighted average price of a set
+  /// of bars correctly.
+  #[test]
+  fn compute_session_vwap() {
+    let bar = |vwap: Option<Num>, volume| Bar {
+      time: DateTime::<Utc>::from_str("2021-02-01T16:01:00Z").unwrap(),
+      open: Num::from(10),
+      close: Num::from(11),
+      high: Num::from(13),
+      low: Num::from(9),
+      volume,
+      vwap,
+      trade_count: None,
+    };
+
+    assert_eq!(session_vwap(&[]), None);
+    assert_eq!(session_vwap(&[bar(Some(Num::from(10)), 0)]), None);
+
+    let bars = [bar(Some(Num::from(10)), 100), bar(Some(Num::from(12)), 300)];
+    assert_eq!(session_vwap(&bars), Some(Num::new(23, 2)));
+
+    // The second bar does not have a VWAP and its typical price of 11
+    // is used instead.
+    let bars = [bar(Some(Num::from(10)), 100), bar(None, 100)];
+    assert_eq!(session_vwap(&bars), Some(Num::new(21, 2)));
+  }
+
+  /// Check that we compute the range and change of a bar correctly.
+  #[test]
+  fn compute_bar_range_and_change() {
+    let mut bar = Bar {
+      time: DateTime::<Utc>::from_str("2021-02-01T16:01:00Z").unwrap(),
+      open: Num::from(10),
+      close: Num::from(11),
+      high: Num::from(13),
+      low: Num::from(9),
+      volume: 100,
+      vwap: None,
+      trade_count: None,
+    };
+    assert_eq!(bar.range(), Num::from(4));
+    assert_eq!(bar.change(), Some(Num::new(1, 10)));
+
+    bar.open = Num::from(0);
+    assert_eq!(bar.change(), None);
+  }
+
   /// Check that we can decode a response containing no bars correctly.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn no_bars() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let start = DateTime::from_str("2021-11-05T00:00:00Z").unwrap();
     let end = DateTime::from_str("2021-11-05T00:00:00Z").unwrap();
     let request = BarsReqInit::default().init("AAPL", start, end, TimeFrame::OneDay);
@@ -264,9 +387,9 @@ mod tests {
 
   /// Check that we can request historic bar data for a stock.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn request_bars() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let start = DateTime::from_str("2018-12-03T21:47:00Z").unwrap();
     let end = DateTime::from_str("2018-12-06T21:47:00Z").unwrap();
     let request = BarsReqInit {
@@ -301,9 +424,9 @@ mod tests {
 
   /// Verify that we can request data through a provided page token.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn can_follow_pagination() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let start = DateTime::from_str("2018-12-03T21:47:00Z").unwrap();
     let end = DateTime::from_str("2018-12-07T21:47:00Z").unwrap();
     let mut request = BarsReqInit {
@@ -329,8 +452,7 @@ mod tests {
   /// Request bars for `AAPL` for a predefined time frame with the
   /// provided adjustment.
   async fn request_with_adjustment(adjustment: Adjustment) -> Bars {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let start = DateTime::from_str("2018-12-03T21:47:00Z").unwrap();
     let end = DateTime::from_str("2018-12-04T21:47:00Z").unwrap();
     let request = BarsReqInit {
@@ -345,6 +467,7 @@ mod tests {
   /// Test requesting of historical stock data with adjustment for
   /// dividends.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn request_with_dividend_adjustment() {
     let res = request_with_adjustment(Adjustment::Dividend);
     let bars = res.await.bars;
@@ -364,6 +487,7 @@ mod tests {
   /// Test requesting of historical stock data with adjustment for stock
   /// splits.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn request_with_split_adjustment() {
     let res = request_with_adjustment(Adjustment::Split);
     let bars = res.await.bars;
@@ -381,6 +505,7 @@ mod tests {
 
   /// Test requesting of historical stock data with all adjustments.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn request_with_all_adjustment() {
     let res = request_with_adjustment(Adjustment::All);
     let bars = res.await.bars;
@@ -399,9 +524,9 @@ mod tests {
   /// Check that we fail as expected when an invalid page token is
   /// specified.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn invalid_page_token() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let start = DateTime::from_str("2018-12-03T21:47:00Z").unwrap();
     let end = DateTime::from_str("2018-12-07T21:47:00Z").unwrap();
@@ -421,9 +546,9 @@ mod tests {
   /// Verify that we error out as expected when attempting to retrieve
   /// aggregate data bars for a non-existent symbol.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn nonexistent_symbol() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let start = DateTime::from_str("2022-02-01T00:00:00Z").unwrap();
     let end = DateTime::from_str("2022-02-20T00:00:00Z").unwrap();
@@ -0,0 +1,362 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use http::HeaderMap;
+use http::StatusCode;
+use http_endpoint::Endpoint;
+
+use serde_urlencoded::to_string as to_query;
+
+use tracing::debug;
+
+use crate::data::v2::bars;
+use crate::data::v2::quotes;
+use crate::data::v2::trades;
+use crate::data::v2::Feed;
+use crate::Client;
+use crate::RequestError;
+
+
+/// The key under which a response is stored in a cache [`Backend`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct Key {
+  /// The name of the endpoint the response was retrieved from, e.g.,
+  /// "bars".
+  pub endpoint: &'static str,
+  /// The symbol the data are for.
+  pub symbol: String,
+  /// The start of the requested time range.
+  pub start: DateTime<Utc>,
+  /// The end of the requested time range.
+  pub end: DateTime<Utc>,
+  /// The data feed used.
+  pub feed: Option<Feed>,
+  /// All request parameters in URL query form, distinguishing requests
+  /// that differ in, say, time frame or page token.
+  pub params: String,
+}
+
+
+/// A trait for endpoints whose responses can be cached.
+pub trait Cacheable: Endpoint {
+  /// Create the cache key for the given request, if it can be cached.
+  fn key(input: &Self::Input) -> Option<Key>;
+}
+
+impl Cacheable for bars::Get {
+  fn key(input: &Self::Input) -> Option<Key> {
+    Some(Key {
+      endpoint: "bars",
+      symbol: input.symbol.clone(),
+      start: input.start,
+      end: input.end,
+      feed: input.feed,
+      params: to_query(input).ok()?,
+    })
+  }
+}
+
+impl Cacheable for quotes::Get {
+  fn key(input: &Self::Input) -> Option<Key> {
+    Some(Key {
+      endpoint: "quotes",
+      symbol: input.symbol.clone(),
+      start: input.start,
+      end: input.end,
+      feed: input.feed,
+      params: to_query(input).ok()?,
+    })
+  }
+}
+
+impl Cacheable for trades::Get {
+  fn key(input: &Self::Input) -> Option<Key> {
+    Some(Key {
+      endpoint: "trades",
+      symbol: input.symbol.to_string(),
+      start: input.start,
+      end: input.end,
+      feed: input.feed,
+      params: to_query(input).ok()?,
+    })
+  }
+}
+
+
+/// A trait representing storage for cached responses.
+///
+/// Responses are stored as raw bodies, allowing for persistent
+/// backends, e.g., on the file system or in an embedded database, to
+/// be plugged in.
+pub trait Backend {
+  /// Retrieve the response body stored under the given key, if any.
+  fn get(&self, key: &Key) -> Option<Vec<u8>>;
+
+  /// Store a response body under the given key.
+  fn put(&self, key: Key, body: Vec<u8>);
+}
+
+
+/// The state of an [`Lru`] backend.
+#[derive(Debug, Default)]
+struct LruState {
+  /// The stored bodies along with their last use.
+  entries: HashMap<Key, (u64, Vec<u8>)>,
+  /// The keys of all stored bodies, ordered by last use.
+  uses: BTreeMap<u64, Key>,
+  /// A counter used for tracking uses.
+  tick: u64,
+}
+
+impl LruState {
+  /// Mark the given entry as used, returning the new use.
+  fn touch(&mut self, last: u64) -> u64 {
+    let key = self.uses.remove(&last).unwrap();
+    self.tick += 1;
+    let _ = self.uses.insert(self.tick, key);
+    self.tick
+  }
+}
+
+
+/// An in-memory [`Backend`] evicting the least recently used response
+/// once a given number of responses is stored.
+#[derive(Debug)]
+pub struct Lru {
+  /// The maximum number of responses to store.
+  capacity: usize,
+  /// The actual cache state.
+  state: Mutex<LruState>,
+}
+
+impl Lru {
+  /// Create a new `Lru` backend storing at most `capacity` responses.
+  #[inline]
+  pub fn new(capacity: usize) -> Self {
+    Self {
+      capacity,
+      state: Mutex::new(LruState::default()),
+    }
+  }
+
+  /// Retrieve the number of responses currently stored.
+  #[inline]
+  pub fn len(&self) -> usize {
+    self.state.lock().unwrap().entries.len()
+  }
+
+  /// Check whether no responses are currently stored.
+  #[inline]
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+impl Backend for Lru {
+  fn get(&self, key: &Key) -> Option<Vec<u8>> {
+    let mut state = self.state.lock().unwrap();
+    let last = state.entries.get(key)?.0;
+    let tick = state.touch(last);
+    let entry = state.entries.get_mut(key).unwrap();
+    entry.0 = tick;
+    Some(entry.1.clone())
+  }
+
+  fn put(&self, key: Key, body: Vec<u8>) {
+    if self.capacity == 0 {
+      return
+    }
+
+    let mut state = self.state.lock().unwrap();
+    if let Some((last, _)) = state.entries.remove(&key) {
+      let _ = state.uses.remove(&last);
+    }
+
+    while state.entries.len() >= self.capacity {
+      let (_, oldest) = state.uses.pop_first().unwrap();
+      let _ = state.entries.remove(&oldest);
+    }
+
+    state.tick += 1;
+    let tick = state.tick;
+    let _ = state.uses.insert(tick, key.clone());
+    let _ = state.entries.insert(key, (tick, body));
+  }
+}
+
+
+/// A client wrapper caching responses of historical market data
+/// endpoints, so that repeated requests for identical ranges, e.g.,
+/// across multiple backtest runs, are not downloaded over and over.
+///
+/// Only requests for ranges ending in the past are cached, as data
+/// for ranges extending into the future may still change.
+#[derive(Debug)]
+pub struct CachedClient<B = Lru> {
+  /// The client used for issuing requests on cache misses.
+  client: Client,
+  /// The backend storing cached responses.
+  backend: B,
+}
+
+impl<B> CachedClient<B>
+where
+  B: Backend,
+{
+  /// Create a new `CachedClient` using the provided `Client` for
+  /// retrieving data and `backend` for caching them.
+  #[inline]
+  pub fn new(client: Client, backend: B) -> Self {
+    Self { client, backend }
+  }
+
+  /// Issue a request, serving it from the cache if possible.
+  pub async fn issue<R>(&self, input: &R::Input) -> Result<R::Output, RequestError<R::Error>>
+  where
+    R: Cacheable,
+  {
+    let now = Utc::now();
+    let key = R::key(input).filter(|key| key.end <= now);
+
+    if let Some(key) = &key {
+      if let Some(body) = self.backend.get(key) {
+        debug!(endpoint = key.endpoint, symbol = key.symbol.as_str(), "cache hit");
+        return R::evaluate(StatusCode::OK, &body).map_err(RequestError::Endpoint)
+      }
+    }
+
+    let (status, _, body) = self.client.issue_raw::<R>(input, HeaderMap::new()).await?;
+    let output = R::evaluate(status, &body).map_err(RequestError::Endpoint)?;
+
+    if let Some(key) = key {
+      self.backend.put(key, body.to_vec());
+    }
+    Ok(output)
+  }
+
+  /// Retrieve the `Client` used for issuing requests.
+  #[inline]
+  pub fn client(&self) -> &Client {
+    &self.client
+  }
+
+  /// Retrieve the cache backend in use.
+  #[inline]
+  pub fn backend(&self) -> &B {
+    &self.backend
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use num_decimal::Num;
+
+  use test_log::test;
+
+  use crate::api_info::ApiInfo;
+
+
+  /// Create a key for a bars request for the given symbol.
+  fn key(symbol: &str) -> Key {
+    let start = DateTime::from_str("2021-11-05T00:00:00Z").unwrap();
+    let end = DateTime::from_str("2021-11-05T00:00:00Z").unwrap();
+    let request = bars::BarsReqInit::default().init(symbol, start, end, bars::TimeFrame::OneDay);
+    bars::Get::key(&request).unwrap()
+  }
+
+
+  /// Check that cache keys distinguish differing requests.
+  #[test]
+  fn distinct_keys() {
+    let start = DateTime::from_str("2021-11-05T00:00:00Z").unwrap();
+    let end = DateTime::from_str("2021-11-06T00:00:00Z").unwrap();
+    let request = bars::BarsReqInit::default().init("SPY", start, end, bars::TimeFrame::OneDay);
+    let key1 = bars::Get::key(&request).unwrap();
+
+    let request = bars::BarsReqInit {
+      feed: Some(Feed::SIP),
+      ..Default::default()
+    }
+    .init("SPY", start, end, bars::TimeFrame::OneDay);
+    let key2 = bars::Get::key(&request).unwrap();
+    assert_ne!(key1, key2);
+
+    let request = bars::BarsReqInit::default().init("SPY", start, end, bars::TimeFrame::OneHour);
+    let key3 = bars::Get::key(&request).unwrap();
+    assert_ne!(key1, key3);
+    assert_ne!(key2, key3);
+  }
+
+  /// Check that the `Lru` backend evicts the least recently used
+  /// response.
+  #[test]
+  fn lru_eviction() {
+    let lru = Lru::new(2);
+    lru.put(key("SPY"), b"spy".to_vec());
+    lru.put(key("AAPL"), b"aapl".to_vec());
+    assert_eq!(lru.len(), 2);
+
+    // Use SPY so that AAPL becomes the least recently used entry.
+    assert_eq!(lru.get(&key("SPY")), Some(b"spy".to_vec()));
+
+    lru.put(key("MSFT"), b"msft".to_vec());
+    assert_eq!(lru.len(), 2);
+    assert_eq!(lru.get(&key("AAPL")), None);
+    assert_eq!(lru.get(&key("SPY")), Some(b"spy".to_vec()));
+    assert_eq!(lru.get(&key("MSFT")), Some(b"msft".to_vec()));
+
+    // Storing a body for an existing key replaces it.
+    lru.put(key("SPY"), b"spy2".to_vec());
+    assert_eq!(lru.len(), 2);
+    assert_eq!(lru.get(&key("SPY")), Some(b"spy2".to_vec()));
+  }
+
+  /// Check that a cached response is served without contacting the
+  /// server.
+  #[test(tokio::test)]
+  async fn serve_from_cache() {
+    let response = br#"{
+  "bars": [{
+    "t": "2021-11-05T04:00:00Z",
+    "o": 469.28,
+    "h": 470.65,
+    "l": 466.92,
+    "c": 468.53,
+    "v": 66524076
+  }],
+  "symbol": "SPY",
+  "next_page_token": null
+}"#;
+
+    // Point the client at an address nobody listens on, to make sure
+    // that we never reach out to the server.
+    let api_info = ApiInfo::from_parts("http://127.0.0.1:1", "key", "secret")
+      .unwrap()
+      .with_data_base_url("http://127.0.0.1:1")
+      .unwrap();
+    let backend = Lru::new(8);
+    backend.put(key("SPY"), response.to_vec());
+    let client = CachedClient::new(Client::new(api_info), backend);
+
+    let start = DateTime::from_str("2021-11-05T00:00:00Z").unwrap();
+    let end = DateTime::from_str("2021-11-05T00:00:00Z").unwrap();
+    let request = bars::BarsReqInit::default().init("SPY", start, end, bars::TimeFrame::OneDay);
+    let bars = client.issue::<bars::Get>(&request).await.unwrap();
+    assert_eq!(bars.symbol, "SPY");
+    assert_eq!(bars.bars.len(), 1);
+    assert_eq!(bars.bars[0].close, Num::new(46853, 100));
+  }
+}
@@ -0,0 +1,225 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::time::Duration;
+
+use futures::task::Context;
+use futures::task::Poll;
+use futures::Stream;
+use futures::StreamExt as _;
+
+use serde_json::Error as JsonError;
+
+use tokio::time::interval_at;
+use tokio::time::Instant;
+use tokio::time::Interval;
+use tokio::time::MissedTickBehavior;
+
+use websocket_util::tungstenite::Error as WebSocketError;
+
+use crate::data::v2::stream::Data;
+use crate::data::v2::stream::Quote;
+
+
+/// The type of item produced by a conflated stream, the same as that
+/// of a realtime market data stream.
+pub type Item = Result<Result<Data, JsonError>, WebSocketError>;
+
+
+/// A stream emitting at most one quote per symbol and interval.
+#[derive(Debug)]
+#[must_use = "streams do nothing unless polled"]
+struct Conflate<S> {
+  /// The wrapped stream.
+  inner: Pin<Box<S>>,
+  /// The interval at which pending quotes are emitted.
+  interval: Interval,
+  /// The latest quote received per symbol since quotes were last
+  /// emitted.
+  pending: HashMap<String, Quote>,
+  /// The symbols with a pending quote, in the order in which the first
+  /// of their quotes was received.
+  symbols: VecDeque<String>,
+  /// Quotes ready to be emitted.
+  ready: VecDeque<Quote>,
+  /// Whether the wrapped stream is exhausted.
+  done: bool,
+}
+
+impl<S> Conflate<S> {
+  /// Move all pending quotes over to be emitted.
+  fn flush(&mut self) {
+    while let Some(symbol) = self.symbols.pop_front() {
+      if let Some(quote) = self.pending.remove(&symbol) {
+        let () = self.ready.push_back(quote);
+      }
+    }
+  }
+}
+
+impl<S> Stream for Conflate<S>
+where
+  S: Stream<Item = Item>,
+{
+  type Item = Item;
+
+  fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    loop {
+      if let Some(quote) = self.ready.pop_front() {
+        break Poll::Ready(Some(Ok(Ok(Data::Quote(quote)))))
+      }
+
+      if !self.done {
+        match self.inner.poll_next_unpin(ctx) {
+          Poll::Ready(Some(Ok(Ok(Data::Quote(quote))))) => {
+            if !self.pending.contains_key(&quote.symbol) {
+              let () = self.symbols.push_back(quote.symbol.clone());
+            }
+            let _prev = self.pending.insert(quote.symbol.clone(), quote);
+            continue
+          },
+          Poll::Ready(Some(item)) => break Poll::Ready(Some(item)),
+          Poll::Ready(None) => {
+            // Emit the latest state of all symbols before we are done.
+            self.done = true;
+            self.flush();
+            continue
+          },
+          Poll::Pending => (),
+        }
+      } else {
+        break Poll::Ready(None)
+      }
+
+      match self.interval.poll_tick(ctx) {
+        Poll::Ready(..) => {
+          self.flush();
+          // Continue above, emitting the quotes that became ready or
+          // making sure that we get woken up for the next tick.
+        },
+        Poll::Pending => break Poll::Pending,
+      }
+    }
+  }
+}
+
+
+/// Conflate the quotes of a realtime market data stream, emitting at
+/// most one quote per symbol every `interval`: the latest one received
+/// in the meantime.
+///
+/// Quotes are held back until the end of the interval in which they
+/// were received. Data other than quotes as well as errors are passed
+/// through as they arrive and, hence, may overtake quotes received
+/// earlier. Once the wrapped stream is exhausted, pending quotes are
+/// emitted right away.
+///
+/// Conflation trades timeliness for a fraction of the processing
+/// costs, which pays off for consumers only interested in a
+/// slow-moving view of the market, e.g., for display purposes.
+///
+/// # Panics
+/// This function panics if `interval` is zero or if it is not called
+/// from within the context of a Tokio runtime.
+pub fn conflate<S>(stream: S, interval: Duration) -> impl Stream<Item = Item>
+where
+  S: Stream<Item = Item>,
+{
+  let mut interval = interval_at(Instant::now() + interval, interval);
+  let () = interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+  Conflate {
+    inner: Box::pin(stream),
+    interval,
+    pending: HashMap::new(),
+    symbols: VecDeque::new(),
+    ready: VecDeque::new(),
+    done: false,
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use chrono::DateTime;
+
+  use num_decimal::Num;
+
+  use test_log::test;
+
+  use crate::data::v2::replay::replay;
+  use crate::data::v2::replay::Speed;
+  use crate::data::v2::stream::Trade;
+
+
+  /// Create a quote for the given symbol at the given time.
+  fn quote(symbol: &str, bid: i32, time: &str) -> Data {
+    Data::Quote(Quote {
+      symbol: symbol.to_string(),
+      bid_price: Num::from(bid),
+      bid_size: 1,
+      ask_price: Num::from(bid + 1),
+      ask_size: 1,
+      timestamp: DateTime::from_str(time).unwrap(),
+    })
+  }
+
+  /// Create a trade for the given symbol at the given time.
+  fn trade(symbol: &str, time: &str) -> Data {
+    Data::Trade(Trade {
+      symbol: symbol.to_string(),
+      trade_id: 1,
+      exchange: 'V',
+      trade_price: Num::from(100),
+      trade_size: 1,
+      timestamp: DateTime::from_str(time).unwrap(),
+      trade_conditions: Vec::new(),
+      tape: 'A',
+    })
+  }
+
+
+  /// Check that we emit only the latest quote per symbol and interval.
+  #[test(tokio::test(start_paused = true))]
+  async fn conflate_quotes() {
+    let data = [
+      quote("SPY", 1, "2022-03-01T14:30:00.000Z"),
+      quote("AAPL", 10, "2022-03-01T14:30:00.050Z"),
+      quote("SPY", 2, "2022-03-01T14:30:00.100Z"),
+      trade("SPY", "2022-03-01T14:30:00.150Z"),
+      quote("SPY", 3, "2022-03-01T14:30:00.200Z"),
+      // The following quotes fall into the second interval.
+      quote("SPY", 4, "2022-03-01T14:30:00.300Z"),
+      quote("SPY", 5, "2022-03-01T14:30:00.400Z"),
+      quote("AAPL", 11, "2022-03-01T14:30:00.450Z"),
+    ];
+    let stream = replay(data, Speed::Original);
+    let items = conflate(stream, Duration::from_millis(250))
+      .map(|item| item.unwrap().unwrap())
+      .collect::<Vec<_>>()
+      .await;
+
+    // The trade is passed through right away.
+    assert!(items[0].is_trade());
+
+    let expected = [("SPY", 3), ("AAPL", 10), ("SPY", 5), ("AAPL", 11)];
+    assert_eq!(items.len(), 1 + expected.len());
+
+    for (data, (symbol, bid)) in items[1..].iter().zip(expected) {
+      match data {
+        Data::Quote(quote) => {
+          assert_eq!(quote.symbol, symbol);
+          assert_eq!(quote.bid_price, Num::from(bid));
+        },
+        _ => panic!("encountered unexpected data: {:?}", data),
+      }
+    }
+  }
+}
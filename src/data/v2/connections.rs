@@ -0,0 +1,140 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::BTreeMap;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+use thiserror::Error;
+
+use url::Url;
+
+
+/// The maximum number of concurrent connections per account and
+/// endpoint, with `usize::MAX` representing no limit.
+static LIMIT: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// The number of currently open connections, keyed by account key ID
+/// and endpoint URL.
+static CONNECTIONS: Mutex<BTreeMap<(String, String), usize>> = Mutex::new(BTreeMap::new());
+
+
+/// Set the maximum number of realtime market data streams that may be
+/// open concurrently for a single account and endpoint within this
+/// process.
+///
+/// Alpaca allows only a single connection per endpoint for most
+/// subscription plans and disconnects older connections when this
+/// limit is exceeded. Setting a limit of one makes us refuse to connect
+/// a second stream to the same endpoint, instead of having an existing
+/// one disconnected. Use [`Hub`][crate::data::v2::hub::Hub] for sharing
+/// a single stream between multiple consumers in that case. By default
+/// and when `None` is provided, no limit is enforced.
+pub fn set_connection_limit(limit: Option<usize>) {
+  LIMIT.store(limit.unwrap_or(usize::MAX), Ordering::SeqCst)
+}
+
+/// Retrieve the number of realtime market data streams currently open
+/// within this process for the account with the given key ID.
+pub fn open_connections(key_id: &str) -> usize {
+  CONNECTIONS
+    .lock()
+    .unwrap()
+    .iter()
+    .filter(|((key, _), _)| key == key_id)
+    .map(|(_, count)| count)
+    .sum()
+}
+
+
+/// An error reported when connecting a realtime market data stream
+/// would exceed the limit set via [`set_connection_limit`].
+#[derive(Clone, Debug, Error, PartialEq)]
+#[error(
+  "refusing to open more than {limit} concurrent stream(s) to {url}: Alpaca would disconnect an \
+   existing one; share a single stream (e.g., using a `Hub`) or adjust the limit"
+)]
+#[non_exhaustive]
+pub struct ConnectionLimitError {
+  /// The configured connection limit.
+  pub limit: usize,
+  /// The URL of the endpoint the stream was to connect to.
+  pub url: Url,
+}
+
+
+/// A handle representing an open realtime market data stream, keeping
+/// it registered until dropped.
+#[derive(Debug)]
+pub(crate) struct Handle {
+  /// The key under which the connection is registered.
+  key: (String, String),
+}
+
+impl Handle {
+  /// Register a new connection for the given account and endpoint,
+  /// failing if doing so would exceed the configured limit.
+  pub(crate) fn acquire(key_id: &str, url: &Url) -> Result<Self, ConnectionLimitError> {
+    let key = (key_id.to_string(), url.to_string());
+    let limit = LIMIT.load(Ordering::SeqCst);
+
+    let mut connections = CONNECTIONS.lock().unwrap();
+    let count = connections.entry(key.clone()).or_default();
+    if *count >= limit {
+      return Err(ConnectionLimitError {
+        limit,
+        url: url.clone(),
+      })
+    }
+    *count += 1;
+    Ok(Self { key })
+  }
+}
+
+impl Drop for Handle {
+  fn drop(&mut self) {
+    let mut connections = CONNECTIONS.lock().unwrap();
+    if let Some(count) = connections.get_mut(&self.key) {
+      *count -= 1;
+      if *count == 0 {
+        let _ = connections.remove(&self.key);
+      }
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use test_log::test;
+
+
+  /// Check that we refuse to exceed the connection limit.
+  #[test]
+  fn enforce_connection_limit() {
+    let key_id = "enforce_connection_limit";
+    let url = Url::parse("wss://stream.data.alpaca.markets/v2/iex").unwrap();
+    let other = Url::parse("wss://stream.data.alpaca.markets/v2/sip").unwrap();
+
+    let () = set_connection_limit(Some(1));
+
+    let handle = Handle::acquire(key_id, &url).unwrap();
+    let err = Handle::acquire(key_id, &url).unwrap_err();
+    assert_eq!(err.limit, 1);
+    assert_eq!(err.url, url);
+
+    // Connections to other endpoints are tracked separately.
+    let _other = Handle::acquire(key_id, &other).unwrap();
+    assert_eq!(open_connections(key_id), 2);
+
+    drop(handle);
+    assert_eq!(open_connections(key_id), 1);
+    let _handle = Handle::acquire(key_id, &url).unwrap();
+
+    let () = set_connection_limit(None);
+    let _handle = Handle::acquire(key_id, &url).unwrap();
+  }
+}
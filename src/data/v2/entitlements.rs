@@ -0,0 +1,131 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use http::StatusCode;
+
+use crate::data::v2::last_quote;
+use crate::data::v2::Feed;
+use crate::Client;
+use crate::RequestError;
+
+
+/// The symbol we use for probing market data entitlements.
+const PROBE_SYMBOL: &str = "SPY";
+
+/// The maximum number of symbols that can be subscribed to over a
+/// realtime stream using the IEX feed.
+const IEX_MAX_STREAM_SYMBOLS: usize = 30;
+
+
+/// The market data entitlements of an account.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Entitlements {
+  /// The most comprehensive data feed the account has access to.
+  pub feed: Feed,
+  /// The maximum number of symbols that can be subscribed to over a
+  /// realtime market data stream, if limited.
+  pub max_stream_symbols: Option<usize>,
+}
+
+impl Entitlements {
+  /// Create an `Entitlements` object for the given data feed.
+  fn from_feed(feed: Feed) -> Self {
+    let max_stream_symbols = match feed {
+      Feed::IEX => Some(IEX_MAX_STREAM_SYMBOLS),
+      Feed::SIP => None,
+    };
+
+    Self {
+      feed,
+      max_stream_symbols,
+    }
+  }
+
+  /// Check whether the account has access to SIP data.
+  #[inline]
+  pub fn has_sip(&self) -> bool {
+    self.feed == Feed::SIP
+  }
+}
+
+
+/// Infer entitlements from the result of a request for SIP data.
+fn classify(
+  result: Result<last_quote::Quote, RequestError<last_quote::GetError>>,
+) -> Result<Entitlements, RequestError<last_quote::GetError>> {
+  match result {
+    Ok(..) => Ok(Entitlements::from_feed(Feed::SIP)),
+    // Depending on the endpoint version, Alpaca reports a lack of
+    // access to the SIP feed either as invalid input or as a
+    // permission error.
+    Err(RequestError::Endpoint(last_quote::GetError::InvalidInput(..)))
+    | Err(RequestError::Endpoint(last_quote::GetError::UnexpectedStatus(
+      StatusCode::FORBIDDEN,
+      ..,
+    ))) => Ok(Entitlements::from_feed(Feed::IEX)),
+    Err(err) => Err(err),
+  }
+}
+
+
+/// Infer the market data entitlements of the account the provided
+/// client is configured for.
+///
+/// Alpaca does not report the market data plan of an account directly
+/// and so we probe for it by requesting SIP data.
+pub(crate) async fn probe(
+  client: &Client,
+) -> Result<Entitlements, RequestError<last_quote::GetError>> {
+  let request = last_quote::LastQuoteReqInit {
+    feed: Some(Feed::SIP),
+    ..Default::default()
+  }
+  .init(PROBE_SYMBOL);
+
+  let result = client.issue::<last_quote::Get>(&request).await;
+  classify(result)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use test_log::test;
+
+  use crate::live;
+
+
+  /// Check that we infer the expected entitlements from probe results.
+  #[test]
+  fn classify_probe_results() {
+    let err = last_quote::GetError::InvalidInput(Err(Vec::new()));
+    let entitlements = classify(Err(RequestError::Endpoint(err))).unwrap();
+    assert_eq!(entitlements.feed, Feed::IEX);
+    assert_eq!(entitlements.max_stream_symbols, Some(IEX_MAX_STREAM_SYMBOLS));
+    assert!(!entitlements.has_sip());
+
+    let err = last_quote::GetError::UnexpectedStatus(StatusCode::FORBIDDEN, Err(Vec::new()));
+    let entitlements = classify(Err(RequestError::Endpoint(err))).unwrap();
+    assert_eq!(entitlements.feed, Feed::IEX);
+
+    let err = last_quote::GetError::AuthenticationFailed(Err(Vec::new()));
+    let err = classify(Err(RequestError::Endpoint(err))).unwrap_err();
+    assert!(matches!(
+      err,
+      RequestError::Endpoint(last_quote::GetError::AuthenticationFailed(..))
+    ));
+  }
+
+  /// Check that we can probe the market data entitlements of the
+  /// account.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn probe_entitlements() {
+    let client = live::client().unwrap();
+
+    let entitlements = client.data_entitlements().await.unwrap();
+    assert_eq!(entitlements.has_sip(), entitlements.max_stream_symbols.is_none());
+  }
+}
@@ -5,7 +5,7 @@ use serde::Serialize;
 
 
 /// An enumeration of the different supported data feeds.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize)]
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, Serialize)]
 #[non_exhaustive]
 pub enum Feed {
   /// Use the Investors Exchange (IEX) as the data source.
@@ -0,0 +1,323 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use futures::pin_mut;
+use futures::Stream;
+use futures::StreamExt as _;
+
+use serde_json::Error as JsonError;
+
+use tokio::sync::broadcast::channel;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::broadcast::Sender;
+use tokio::sync::watch;
+
+use tracing::warn;
+
+use websocket_util::tungstenite::Error as WebSocketError;
+
+use crate::data::v2::stream::Data;
+use crate::data::v2::stream::Quote;
+use crate::Error;
+
+
+/// A hub fanning out realtime market data received over a single
+/// stream to an arbitrary number of consumers.
+///
+/// Alpaca limits the number of concurrent websocket connections per
+/// account. A `Hub` allows for multiple independent tasks (e.g., a
+/// strategy, a logger, and a user interface) to each receive the
+/// decoded market data from a single connection.
+///
+/// Each consumer retrieves its own [`Receiver`] via
+/// [`Hub::subscribe`]. Receivers buffer up to the capacity the hub was
+/// created with; a receiver falling behind by more than that misses
+/// the oldest data and is informed about that fact by a
+/// [`RecvError::Lagged`][tokio::sync::broadcast::error::RecvError::Lagged]
+/// error.
+///
+/// Consumers interested in a single symbol only can retrieve a
+/// receiver for just that via [`Hub::symbol_channel`]. Consumers
+/// merely interested in sampling the most recent quote for a symbol
+/// can do so via [`Hub::latest_quote`], without having to keep up with
+/// the data stream.
+#[derive(Clone, Debug)]
+pub struct Hub {
+  /// The number of data items buffered per receiver.
+  capacity: usize,
+  /// The sender used for broadcasting data to all receivers.
+  sender: Sender<Data>,
+  /// Senders for data pertaining to individual symbols.
+  symbols: Arc<Mutex<HashMap<String, Sender<Data>>>>,
+  /// Senders for the latest quote of individual symbols.
+  quotes: Arc<Mutex<HashMap<String, watch::Sender<Option<Quote>>>>>,
+}
+
+impl Hub {
+  /// Create a new `Hub` buffering up to `capacity` data items per
+  /// receiver.
+  ///
+  /// # Panics
+  /// This constructor panics if `capacity` is zero.
+  #[inline]
+  pub fn new(capacity: usize) -> Self {
+    let (sender, _) = channel(capacity);
+    Self {
+      capacity,
+      sender,
+      symbols: Arc::new(Mutex::new(HashMap::new())),
+      quotes: Arc::new(Mutex::new(HashMap::new())),
+    }
+  }
+
+  /// Create a new receiver of all market data passing through the hub
+  /// from now on.
+  #[inline]
+  pub fn subscribe(&self) -> Receiver<Data> {
+    self.sender.subscribe()
+  }
+
+  /// Create a new receiver of the market data for the given symbol
+  /// passing through the hub from now on.
+  pub fn symbol_channel(&self, symbol: &str) -> Receiver<Data> {
+    let mut symbols = self.symbols.lock().unwrap();
+    if let Some(sender) = symbols.get(symbol) {
+      sender.subscribe()
+    } else {
+      let (sender, receiver) = channel(self.capacity);
+      let _ = symbols.insert(symbol.to_string(), sender);
+      receiver
+    }
+  }
+
+  /// Retrieve a receiver of the latest quote for the given symbol.
+  ///
+  /// The receiver always holds the most recent quote that passed
+  /// through the hub, or `None` if none has been received for the
+  /// symbol so far. Intermediate quotes are skipped if they are not
+  /// observed in time.
+  pub fn latest_quote(&self, symbol: &str) -> watch::Receiver<Option<Quote>> {
+    let mut quotes = self.quotes.lock().unwrap();
+    quotes
+      .entry(symbol.to_string())
+      .or_insert_with(|| watch::channel(None).0)
+      .subscribe()
+  }
+
+  /// Remember the given quote as the latest one for its symbol.
+  fn update_quote(&self, quote: &Quote) {
+    let mut quotes = self.quotes.lock().unwrap();
+    if let Some(sender) = quotes.get(&quote.symbol) {
+      let _prev = sender.send_replace(Some(quote.clone()));
+    } else {
+      let (sender, _) = watch::channel(Some(quote.clone()));
+      let _prev = quotes.insert(quote.symbol.clone(), sender);
+    }
+  }
+
+  /// Route data to the receivers for the symbol it pertains to.
+  fn route(&self, data: &Data) {
+    if let Data::Quote(quote) = data {
+      self.update_quote(quote);
+    }
+
+    let mut symbols = self.symbols.lock().unwrap();
+    if let Some(sender) = symbols.get(data.symbol()) {
+      // Sending fails if all receivers for the symbol are gone, in
+      // which case we no longer need to route data for it.
+      if sender.send(data.clone()).is_err() {
+        let _ = symbols.remove(data.symbol());
+      }
+    }
+  }
+
+  /// Retrieve the number of currently active receivers of all market
+  /// data.
+  #[inline]
+  pub fn receiver_count(&self) -> usize {
+    self.sender.receiver_count()
+  }
+
+  /// Forward all market data received over the provided stream to the
+  /// hub's receivers.
+  ///
+  /// Note that the stream needs to be driven by this method for
+  /// subscription changes to take effect, so it is best run in a task
+  /// of its own. Data failing to parse are logged and skipped.
+  ///
+  /// This method returns once the stream is exhausted or a websocket
+  /// error is encountered.
+  pub async fn run<S>(&self, stream: S) -> Result<(), Error>
+  where
+    S: Stream<Item = Result<Result<Data, JsonError>, WebSocketError>>,
+  {
+    pin_mut!(stream);
+
+    while let Some(result) = stream.next().await {
+      match result? {
+        // Sending only fails if there are no receivers, in which case
+        // we simply drop the data.
+        Ok(data) => {
+          self.route(&data);
+          let _ = self.sender.send(data);
+        },
+        Err(err) => warn!("failed to parse market data: {}", err),
+      }
+    }
+    Ok(())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::str::FromStr as _;
+
+  use chrono::DateTime;
+
+  use futures::stream::iter;
+
+  use num_decimal::Num;
+
+  use serde_json::from_str as json_from_str;
+
+  use test_log::test;
+
+  use tokio::sync::broadcast::error::TryRecvError;
+
+  use crate::data::v2::stream::Trade;
+
+
+  /// Create a trade for the given symbol.
+  fn trade(symbol: &str) -> Data {
+    Data::Trade(Trade {
+      symbol: symbol.to_string(),
+      trade_id: 1,
+      exchange: 'V',
+      trade_price: Num::from(100),
+      trade_size: 1,
+      timestamp: DateTime::from_str("2022-03-01T14:30:00Z").unwrap(),
+      trade_conditions: Vec::new(),
+      tape: 'A',
+    })
+  }
+
+
+  /// Check that all receivers see all data.
+  #[test(tokio::test)]
+  async fn fan_out() {
+    let hub = Hub::new(16);
+    let mut first = hub.subscribe();
+    let mut second = hub.subscribe();
+    assert_eq!(hub.receiver_count(), 2);
+
+    let stream = iter([Ok(Ok(trade("SPY"))), Ok(Ok(trade("AAPL")))]);
+    let () = hub.run(stream).await.unwrap();
+
+    for receiver in [&mut first, &mut second] {
+      let data = receiver.recv().await.unwrap();
+      assert!(matches!(data, Data::Trade(trade) if trade.symbol == "SPY"));
+      let data = receiver.recv().await.unwrap();
+      assert!(matches!(data, Data::Trade(trade) if trade.symbol == "AAPL"));
+      assert_eq!(receiver.try_recv().unwrap_err(), TryRecvError::Empty);
+    }
+  }
+
+  /// Check that symbol channels only see data for their symbol.
+  #[test(tokio::test)]
+  async fn symbol_routing() {
+    let hub = Hub::new(16);
+    let mut all = hub.subscribe();
+    let mut spy = hub.symbol_channel("SPY");
+    let mut spy2 = hub.symbol_channel("SPY");
+    let msft = hub.symbol_channel("MSFT");
+    drop(msft);
+
+    let stream = iter([
+      Ok(Ok(trade("SPY"))),
+      Ok(Ok(trade("AAPL"))),
+      Ok(Ok(trade("MSFT"))),
+      Ok(Ok(trade("SPY"))),
+    ]);
+    let () = hub.run(stream).await.unwrap();
+
+    for receiver in [&mut spy, &mut spy2] {
+      for _ in 0..2 {
+        let data = receiver.recv().await.unwrap();
+        assert_eq!(data.symbol(), "SPY");
+      }
+      assert_eq!(receiver.try_recv().unwrap_err(), TryRecvError::Empty);
+    }
+
+    for _ in 0..4 {
+      let _data = all.recv().await.unwrap();
+    }
+
+    // The channel for MSFT should have been cleaned up.
+    assert!(!hub.symbols.lock().unwrap().contains_key("MSFT"));
+  }
+
+  /// Check that the latest quote per symbol can be sampled.
+  #[test(tokio::test)]
+  async fn sample_latest_quote() {
+    let quote = |symbol: &str, bid_price| {
+      Data::Quote(Quote {
+        symbol: symbol.to_string(),
+        bid_price: Num::from(bid_price),
+        bid_size: 1,
+        ask_price: Num::from(bid_price + 1),
+        ask_size: 1,
+        timestamp: DateTime::from_str("2022-03-01T14:30:00Z").unwrap(),
+      })
+    };
+
+    let hub = Hub::new(16);
+    let spy = hub.latest_quote("SPY");
+    assert_eq!(*spy.borrow(), None);
+
+    let stream = iter([
+      Ok(Ok(quote("SPY", 100))),
+      Ok(Ok(quote("AAPL", 150))),
+      Ok(Ok(trade("SPY"))),
+      Ok(Ok(quote("SPY", 101))),
+    ]);
+    let () = hub.run(stream).await.unwrap();
+
+    let latest = spy.borrow().clone().unwrap();
+    assert_eq!(latest.bid_price, Num::from(101));
+
+    // Quotes are retained for symbols nobody asked about yet.
+    let aapl = hub.latest_quote("AAPL");
+    let latest = aapl.borrow().clone().unwrap();
+    assert_eq!(latest.bid_price, Num::from(150));
+    assert_eq!(*hub.latest_quote("MSFT").borrow(), None);
+  }
+
+  /// Check that JSON errors are skipped and websocket errors end
+  /// forwarding.
+  #[test(tokio::test)]
+  async fn error_handling() {
+    let hub = Hub::new(16);
+    let mut receiver = hub.subscribe();
+
+    let json_err = json_from_str::<()>("{").unwrap_err();
+    let stream = iter([
+      Ok(Err(json_err)),
+      Ok(Ok(trade("SPY"))),
+      Err(WebSocketError::AlreadyClosed),
+      Ok(Ok(trade("AAPL"))),
+    ]);
+    let err = hub.run(stream).await.unwrap_err();
+    assert!(matches!(err, Error::WebSocket(..)), "{:?}", err);
+
+    let data = receiver.recv().await.unwrap();
+    assert!(matches!(data, Data::Trade(trade) if trade.symbol == "SPY"));
+    assert_eq!(receiver.try_recv().unwrap_err(), TryRecvError::Empty);
+  }
+}
@@ -13,6 +13,8 @@ use serde_urlencoded::to_string as to_query;
 
 use crate::data::v2::Feed;
 use crate::data::DATA_BASE_URL;
+use crate::endpoint::parse_json;
+use crate::util::datetime_from_str;
 use crate::Str;
 
 
@@ -59,7 +61,7 @@ impl LastQuoteReqInit {
 #[non_exhaustive]
 pub struct Quote {
   /// The time stamp of this quote.
-  #[serde(rename = "t")]
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
   pub time: DateTime<Utc>,
   /// The ask price.
   #[serde(rename = "ap")]
@@ -115,9 +117,7 @@ EndpointNoParse! {
 
     // We are not interested in the actual `Response` object. Clients
     // can keep track of what symbol they requested a quote for.
-    from_json::<Response>(body)
-      .map(|response| response.quote)
-      .map_err(Self::ConversionError::from)
+    parse_json::<Response>(body).map(|response| response.quote)
   }
 
   fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
@@ -134,8 +134,7 @@ mod tests {
 
   use test_log::test;
 
-  use crate::api_info::ApiInfo;
-  use crate::Client;
+  use crate::live;
   use crate::RequestError;
 
 
@@ -169,9 +168,9 @@ mod tests {
 
   /// Verify that we can retrieve the last quote for an asset.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn request_last_quote() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let req = LastQuoteReqInit::default().init("SPY");
     let quote = client.issue::<Get>(&req).await.unwrap();
@@ -183,9 +182,9 @@ mod tests {
 
   /// Verify that we can specify the SIP feed as the data source to use.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn sip_feed() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let req = LastQuoteReq {
       symbol: "SPY".to_string(),
@@ -204,9 +203,9 @@ mod tests {
 
   /// Verify that we can properly parse a reference bar response.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn nonexistent_symbol() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let req = LastQuoteReqInit::default().init("ABC123");
     let err = client.issue::<Get>(&req).await.unwrap_err();
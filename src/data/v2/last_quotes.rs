@@ -0,0 +1,197 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::from_slice as from_json;
+use serde_urlencoded::to_string as to_query;
+
+use crate::data::v2::Feed;
+use crate::data::DATA_BASE_URL;
+use crate::endpoint::parse_json;
+use crate::util::string_slice_to_str;
+use crate::Str;
+
+/// A quote as returned by the /v2/stocks/quotes/latest endpoint.
+pub use super::last_quote::Quote;
+
+
+/// A GET request to be made to the /v2/stocks/quotes/latest endpoint.
+#[derive(Clone, Serialize, PartialEq, Debug)]
+pub struct LastQuotesReq {
+  /// The symbols to retrieve the last quote for.
+  #[serde(rename = "symbols", serialize_with = "string_slice_to_str")]
+  pub symbols: Vec<String>,
+  /// The data feed to use.
+  #[serde(rename = "feed")]
+  pub feed: Option<Feed>,
+}
+
+
+/// A helper for initializing [`LastQuotesReq`] objects.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[allow(missing_copy_implementations)]
+pub struct LastQuotesReqInit {
+  /// See `LastQuotesReq::feed`.
+  pub feed: Option<Feed>,
+  #[doc(hidden)]
+  pub _non_exhaustive: (),
+}
+
+impl LastQuotesReqInit {
+  /// Create a [`LastQuotesReq`] from a `LastQuotesReqInit`.
+  #[inline]
+  pub fn init<I, S>(self, symbols: I) -> LastQuotesReq
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    LastQuotesReq {
+      symbols: symbols.into_iter().map(S::into).collect(),
+      feed: self.feed,
+    }
+  }
+}
+
+
+EndpointNoParse! {
+  /// The representation of a GET request to the
+  /// /v2/stocks/quotes/latest endpoint.
+  pub Get(LastQuotesReq),
+  Ok => HashMap<String, Quote>, [
+    /// The last quotes were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetError, [
+    /// One of the provided symbols was invalid or not found or the data
+    /// feed is not supported.
+    /* 422 */ UNPROCESSABLE_ENTITY => InvalidInput,
+  ]
+
+  fn base_url() -> Option<Str> {
+    Some(DATA_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v2/stocks/quotes/latest".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+
+  fn parse(body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
+    /// A helper object for parsing the response to a `Get` request.
+    #[derive(Deserialize)]
+    struct Response {
+      /// A mapping from symbols to the latest quote for each.
+      quotes: HashMap<String, Quote>,
+    }
+
+    parse_json::<Response>(body).map(|response| response.quotes)
+  }
+
+  fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
+    from_json::<Self::ApiError>(body).map_err(|_| body.to_vec())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use chrono::Duration;
+  use chrono::Utc;
+
+  use http_endpoint::Endpoint;
+
+  use num_decimal::Num;
+
+  use test_log::test;
+
+  use crate::live;
+  use crate::RequestError;
+
+
+  /// Check that we can serialize a [`LastQuotesReq`] into a query
+  /// string.
+  #[test]
+  fn serialize_request() {
+    let request = LastQuotesReqInit::default().init(["SPY", "AAPL"]);
+    let query = to_query(&request).unwrap();
+    assert_eq!(query, "symbols=SPY%2CAAPL");
+  }
+
+  /// Check that we can parse the reference quotes from the
+  /// documentation.
+  #[test]
+  fn parse_reference_quotes() {
+    let response = br#"{
+  "quotes": {
+    "TSLA": {
+      "t": "2022-04-12T17:26:45.009288296Z",
+      "ax": "V",
+      "ap": 1020,
+      "as": 3,
+      "bx": "V",
+      "bp": 990,
+      "bs": 5,
+      "c": ["R"],
+      "z": "C"
+    },
+    "AAPL": {
+      "t": "2022-04-12T17:26:44.962998616Z",
+      "ax": "V",
+      "ap": 170,
+      "as": 1,
+      "bx": "V",
+      "bp": 168.03,
+      "bs": 1,
+      "c": ["R"],
+      "z": "C"
+    }
+  }
+}"#;
+
+    let quotes = Get::parse(response).unwrap();
+    assert_eq!(quotes.len(), 2);
+    assert_eq!(quotes["TSLA"].ask_price, Num::from(1020));
+    assert_eq!(quotes["TSLA"].bid_size, 5);
+    assert_eq!(quotes["AAPL"].bid_price, Num::new(16803, 100));
+  }
+
+  /// Verify that we can retrieve the last quotes for multiple assets.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn request_last_quotes() {
+    let client = live::client().unwrap();
+
+    let req = LastQuotesReqInit::default().init(["SPY", "MSFT"]);
+    let quotes = client.issue::<Get>(&req).await.unwrap();
+    assert_eq!(quotes.len(), 2);
+
+    for symbol in ["SPY", "MSFT"] {
+      let quote = &quotes[symbol];
+      assert!(quote.time >= Utc::now() - Duration::weeks(2));
+    }
+  }
+
+  /// Verify that we error out as expected when attempting to retrieve
+  /// the last quotes for a non-existent symbol.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn nonexistent_symbol() {
+    let client = live::client().unwrap();
+
+    let req = LastQuotesReqInit::default().init(["SPY", "ABC123"]);
+    let err = client.issue::<Get>(&req).await.unwrap_err();
+    match err {
+      RequestError::Endpoint(GetError::InvalidInput(_)) => (),
+      _ => panic!("Received unexpected error: {:?}", err),
+    };
+  }
+}
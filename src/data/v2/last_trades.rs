@@ -0,0 +1,194 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::from_slice as from_json;
+use serde_urlencoded::to_string as to_query;
+
+use crate::data::v2::Feed;
+use crate::data::DATA_BASE_URL;
+use crate::endpoint::parse_json;
+use crate::util::string_slice_to_str;
+use crate::Str;
+
+/// A trade as returned by the /v2/stocks/trades/latest endpoint.
+pub use super::trades::Trade;
+
+
+/// A GET request to be made to the /v2/stocks/trades/latest endpoint.
+#[derive(Clone, Serialize, PartialEq, Debug)]
+pub struct LastTradesReq {
+  /// The symbols to retrieve the last trade for.
+  #[serde(rename = "symbols", serialize_with = "string_slice_to_str")]
+  pub symbols: Vec<String>,
+  /// The data feed to use.
+  #[serde(rename = "feed")]
+  pub feed: Option<Feed>,
+}
+
+
+/// A helper for initializing [`LastTradesReq`] objects.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[allow(missing_copy_implementations)]
+pub struct LastTradesReqInit {
+  /// See `LastTradesReq::feed`.
+  pub feed: Option<Feed>,
+  #[doc(hidden)]
+  pub _non_exhaustive: (),
+}
+
+impl LastTradesReqInit {
+  /// Create a [`LastTradesReq`] from a `LastTradesReqInit`.
+  #[inline]
+  pub fn init<I, S>(self, symbols: I) -> LastTradesReq
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    LastTradesReq {
+      symbols: symbols.into_iter().map(S::into).collect(),
+      feed: self.feed,
+    }
+  }
+}
+
+
+EndpointNoParse! {
+  /// The representation of a GET request to the
+  /// /v2/stocks/trades/latest endpoint.
+  pub Get(LastTradesReq),
+  Ok => HashMap<String, Trade>, [
+    /// The last trades were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetError, [
+    /// One of the provided symbols was invalid or not found or the data
+    /// feed is not supported.
+    /* 422 */ UNPROCESSABLE_ENTITY => InvalidInput,
+  ]
+
+  fn base_url() -> Option<Str> {
+    Some(DATA_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v2/stocks/trades/latest".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+
+  fn parse(body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
+    /// A helper object for parsing the response to a `Get` request.
+    #[derive(Deserialize)]
+    struct Response {
+      /// A mapping from symbols to the latest trade for each.
+      trades: HashMap<String, Trade>,
+    }
+
+    parse_json::<Response>(body).map(|response| response.trades)
+  }
+
+  fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
+    from_json::<Self::ApiError>(body).map_err(|_| body.to_vec())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use chrono::Duration;
+  use chrono::Utc;
+
+  use http_endpoint::Endpoint;
+
+  use num_decimal::Num;
+
+  use test_log::test;
+
+  use crate::live;
+  use crate::RequestError;
+
+
+  /// Check that we can serialize a [`LastTradesReq`] into a query
+  /// string.
+  #[test]
+  fn serialize_request() {
+    let request = LastTradesReqInit::default().init(["SPY", "AAPL"]);
+    let query = to_query(&request).unwrap();
+    assert_eq!(query, "symbols=SPY%2CAAPL");
+  }
+
+  /// Check that we can parse the reference trades from the
+  /// documentation.
+  #[test]
+  fn parse_reference_trades() {
+    let response = br#"{
+  "trades": {
+    "TSLA": {
+      "t": "2022-04-12T17:05:06.936423531Z",
+      "x": "V",
+      "p": 995,
+      "s": 100,
+      "c": ["@"],
+      "i": 10741,
+      "z": "C"
+    },
+    "AAPL": {
+      "t": "2022-04-12T17:05:17.428334819Z",
+      "x": "V",
+      "p": 167.86,
+      "s": 99,
+      "c": ["@", "I"],
+      "i": 3358,
+      "z": "C"
+    }
+  }
+}"#;
+
+    let trades = Get::parse(response).unwrap();
+    assert_eq!(trades.len(), 2);
+    assert_eq!(trades["TSLA"].price, Num::from(995));
+    assert_eq!(trades["TSLA"].size, 100);
+    assert_eq!(trades["AAPL"].price, Num::new(16786, 100));
+    assert_eq!(trades["AAPL"].trade_conditions, vec!['@', 'I']);
+  }
+
+  /// Verify that we can retrieve the last trades for multiple assets.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn request_last_trades() {
+    let client = live::client().unwrap();
+
+    let req = LastTradesReqInit::default().init(["SPY", "MSFT"]);
+    let trades = client.issue::<Get>(&req).await.unwrap();
+    assert_eq!(trades.len(), 2);
+
+    for symbol in ["SPY", "MSFT"] {
+      let trade = &trades[symbol];
+      assert!(trade.timestamp >= Utc::now() - Duration::weeks(2));
+    }
+  }
+
+  /// Verify that we error out as expected when attempting to retrieve
+  /// the last trades for a non-existent symbol.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn nonexistent_symbol() {
+    let client = live::client().unwrap();
+
+    let req = LastTradesReqInit::default().init(["SPY", "ABC123"]);
+    let err = client.issue::<Get>(&req).await.unwrap_err();
+    match err {
+      RequestError::Endpoint(GetError::InvalidInput(_)) => (),
+      _ => panic!("Received unexpected error: {:?}", err),
+    };
+  }
+}
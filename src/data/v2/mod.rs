@@ -4,12 +4,53 @@
 mod feed;
 mod unfold;
 
+/// Functionality for adjusting historical bars for corporate actions.
+pub mod adjust;
+/// Functionality for applying backpressure policies to consumers of
+/// realtime market data.
+pub mod backpressure;
 /// Definitions for retrieval of market data bars.
 pub mod bars;
+/// Functionality for caching historical market data.
+pub mod cache;
+/// Functionality for conflating the quotes of realtime market data
+/// streams.
+pub mod conflate;
+/// Functionality for tracking the number of open realtime market data
+/// streams.
+pub mod connections;
+/// Functionality for inferring the market data entitlements of an
+/// account.
+pub mod entitlements;
+/// Functionality for fanning out realtime market data to multiple
+/// consumers.
+pub mod hub;
 /// Functionality for retrieval of the most recent quote.
 pub mod last_quote;
+/// Functionality for retrieval of the most recent quotes for multiple
+/// symbols.
+pub mod last_quotes;
+/// Functionality for retrieval of the most recent trades for multiple
+/// symbols.
+pub mod last_trades;
+/// Functionality for parsing market data payloads, e.g., as captured
+/// out-of-band.
+pub mod parse;
 /// Functionality for retrieving historic quotes.
 pub mod quotes;
+/// Functionality for replaying historic market data through the
+/// interface of a realtime stream.
+pub mod replay;
+/// Functionality for retrieving market data snapshots.
+pub mod snapshots;
+/// Functionality for abstracting over providers of historical market
+/// data.
+pub mod source;
+/// Definitions for statistics about realtime market data streams.
+pub mod stats;
+/// Functionality for tapping into the raw messages of realtime market
+/// data streams.
+pub mod tap;
 /// Functionality for retrieving historic trades.
 pub mod trades;
 /// Definitions for real-time streaming of market data.
@@ -0,0 +1,112 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use crate::data::v2::bars::Bars;
+use crate::data::v2::quotes::Quotes;
+use crate::data::v2::stream::Data;
+use crate::data::v2::stream::DataMessage;
+use crate::data::v2::trades::Trades;
+use crate::endpoint::parse_json;
+use crate::ConversionError;
+
+
+/// Parse a page of historic trades, as returned by the
+/// /v2/stocks/<symbol>/trades endpoint.
+#[inline]
+pub fn from_json_trade_page(json: &[u8]) -> Result<Trades, ConversionError> {
+  parse_json::<Trades>(json)
+}
+
+/// Parse a page of historic quotes, as returned by the
+/// /v2/stocks/<symbol>/quotes endpoint.
+#[inline]
+pub fn from_json_quote_page(json: &[u8]) -> Result<Quotes, ConversionError> {
+  parse_json::<Quotes>(json)
+}
+
+/// Parse a page of historic bars, as returned by the
+/// /v2/stocks/<symbol>/bars endpoint.
+#[inline]
+pub fn from_json_bar_page(json: &[u8]) -> Result<Bars, ConversionError> {
+  parse_json::<Bars>(json)
+}
+
+/// Parse a single message as received over a realtime market data
+/// stream.
+///
+/// A message is a JSON array of items. Control messages contained in
+/// it, e.g., subscription confirmations, are skipped.
+pub fn from_stream_message(message: &[u8]) -> Result<Vec<Data>, ConversionError> {
+  let messages = parse_json::<Vec<DataMessage>>(message)?;
+  let data = messages
+    .into_iter()
+    .filter_map(|message| message.into_data().ok())
+    .collect();
+  Ok(data)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use num_decimal::Num;
+
+  use test_log::test;
+
+
+  /// Check that we can parse the trade page of our corpus.
+  #[test]
+  fn parse_trade_page_corpus() {
+    let page = from_json_trade_page(include_bytes!("corpus/trades.json")).unwrap();
+    assert_eq!(page.symbol, "AAPL");
+    assert_eq!(page.trades.len(), 2);
+    assert_eq!(page.trades[0].price, Num::new(16804, 100));
+    assert_eq!(page.trades[1].size, 100);
+    assert!(page.next_page_token.is_some());
+  }
+
+  /// Check that we can parse the quote page of our corpus.
+  #[test]
+  fn parse_quote_page_corpus() {
+    let page = from_json_quote_page(include_bytes!("corpus/quotes.json")).unwrap();
+    assert_eq!(page.symbol, "AAPL");
+    assert_eq!(page.quotes.len(), 2);
+    assert_eq!(page.quotes[0].bid_price, Num::new(16798, 100));
+    assert_eq!(page.quotes[1].ask_size, 3);
+    assert_eq!(page.next_page_token, None);
+  }
+
+  /// Check that we can parse the bar page of our corpus.
+  #[test]
+  fn parse_bar_page_corpus() {
+    let page = from_json_bar_page(include_bytes!("corpus/bars.json")).unwrap();
+    assert_eq!(page.symbol, "AAPL");
+    assert_eq!(page.bars.len(), 2);
+    assert_eq!(page.bars[0].open, Num::new(13332, 100));
+    assert_eq!(page.bars[1].trade_count, Some(29));
+  }
+
+  /// Check that we can parse the stream message of our corpus.
+  #[test]
+  fn parse_stream_message_corpus() {
+    let data = from_stream_message(include_bytes!("corpus/stream.json")).unwrap();
+    assert_eq!(data.len(), 3);
+    assert!(data[0].is_bar());
+    assert_eq!(data[0].symbol(), "SPY");
+    assert!(data[1].is_quote());
+    assert_eq!(data[1].symbol(), "NVDA");
+    assert!(data[2].is_trade());
+    assert_eq!(data[2].symbol(), "AAPL");
+  }
+
+  /// Check that malformed input is reported as an error.
+  #[test]
+  fn parse_malformed() {
+    assert!(from_json_trade_page(b"").is_err());
+    assert!(from_json_quote_page(b"{\"quotes\":").is_err());
+    assert!(from_json_bar_page(b"[]").is_err());
+    assert!(from_stream_message(b"[{\"T\":\"t\"}]").is_err());
+    assert!(from_stream_message(b"{}").is_err());
+  }
+}
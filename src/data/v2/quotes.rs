@@ -11,6 +11,7 @@ use serde_urlencoded::to_string as to_query;
 use crate::data::v2::Feed;
 use crate::data::DATA_BASE_URL;
 use crate::util::vec_from_str;
+use crate::Paginated;
 use crate::Str;
 
 /// A quote as returned by the /v2/stocks/<symbol>/quotes endpoint.
@@ -93,7 +94,7 @@ pub struct QuotesReq {
 }
 
 
-Endpoint! {
+DataEndpoint! {
   /// The representation of a GET request to the
   /// /v2/stocks/<symbol>/quotes endpoint.
   pub Get(QuotesReq),
@@ -120,6 +121,23 @@ Endpoint! {
   }
 }
 
+/// The next page of quotes is retrieved using the token reported along
+/// with the current one.
+impl Paginated for Get {
+  type Item = Quote;
+  type State = ();
+
+  fn paginate(
+    mut input: Self::Input,
+    output: Self::Output,
+    _state: &mut Self::State,
+  ) -> (Vec<Self::Item>, Option<Self::Input>) {
+    input.page_token = output.next_page_token;
+    let next = input.page_token.is_some().then_some(input);
+    (output.quotes, next)
+  }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -131,16 +149,15 @@ mod tests {
 
   use test_log::test;
 
-  use crate::api_info::ApiInfo;
-  use crate::Client;
+  use crate::live;
   use crate::RequestError;
 
 
   /// Check that we can retrieve quotes for a specific time frame.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn request_quotes() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let start = DateTime::from_str("2022-01-04T13:35:59Z").unwrap();
     let end = DateTime::from_str("2022-01-04T13:36:00Z").unwrap();
@@ -162,9 +179,9 @@ mod tests {
   /// Verify that we error out as expected when attempting to retrieve
   /// the quotes for a non-existent symbol.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn nonexistent_symbol() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let start = DateTime::from_str("2022-01-04T13:35:59Z").unwrap();
     let end = DateTime::from_str("2022-01-04T13:36:00Z").unwrap();
@@ -179,9 +196,9 @@ mod tests {
   /// Check that we fail as expected when an invalid page token is
   /// specified.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn invalid_page_token() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let start = DateTime::from_str("2022-01-04T13:35:59Z").unwrap();
     let end = DateTime::from_str("2022-01-04T13:36:00Z").unwrap();
@@ -200,9 +217,9 @@ mod tests {
 
   /// Check that we can page quotes as expected.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn page_quotes() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let start = DateTime::from_str("2022-01-04T13:35:00Z").unwrap();
     let end = DateTime::from_str("2022-01-04T13:36:00Z").unwrap();
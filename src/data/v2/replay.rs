@@ -0,0 +1,261 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use futures::stream::unfold;
+use futures::Stream;
+
+use serde_json::from_slice as json_from_slice;
+use serde_json::Error as JsonError;
+
+use tokio::time::sleep_until;
+use tokio::time::Instant;
+
+use websocket_util::tungstenite::Error as WebSocketError;
+
+use crate::data::v2::quotes;
+use crate::data::v2::stream::Data;
+use crate::data::v2::stream::DataMessage;
+use crate::data::v2::stream::Quote;
+use crate::data::v2::stream::Trade;
+use crate::data::v2::trades;
+
+
+/// The type of item produced by a replay stream.
+///
+/// It is the same as that of a realtime market data stream, allowing
+/// consumers to work with either.
+pub type Item = Result<Result<Data, JsonError>, WebSocketError>;
+
+
+/// The speed at which to replay market data.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Speed {
+  /// Replay data as fast as possible.
+  Unpaced,
+  /// Replay data with the original time between items.
+  Original,
+  /// Replay data with the original time between items divided by the
+  /// given factor.
+  Accelerated(u32),
+}
+
+impl Speed {
+  /// Retrieve the factor by which to divide the time between items, if
+  /// replay is paced at all.
+  fn factor(&self) -> Option<u32> {
+    match self {
+      Self::Unpaced => None,
+      Self::Original => Some(1),
+      Self::Accelerated(factor) => Some((*factor).max(1)),
+    }
+  }
+}
+
+
+/// Decode a single raw stream message, skipping control messages.
+fn decode(message: &[u8]) -> Vec<Result<Data, JsonError>> {
+  match json_from_slice::<Vec<DataMessage>>(message) {
+    Ok(messages) => messages
+      .into_iter()
+      .filter_map(|message| message.into_data().ok())
+      .map(Ok)
+      .collect(),
+    Err(err) => vec![Err(err)],
+  }
+}
+
+
+/// Pace the provided items according to their time stamps.
+fn pace<I>(items: I, speed: Speed) -> impl Stream<Item = Item>
+where
+  I: Iterator<Item = Result<Data, JsonError>>,
+{
+  // The point in time at which we replayed the first item, along with
+  // its time stamp.
+  let origin = None::<(Instant, DateTime<Utc>)>;
+
+  unfold((items, origin), move |(mut items, mut origin)| async move {
+    let item = items.next()?;
+    if let (Ok(data), Some(factor)) = (&item, speed.factor()) {
+      let timestamp = *data.timestamp();
+      match origin {
+        Some((start, first)) => {
+          // Items out of order are replayed right away.
+          let offset = (timestamp - first).to_std().unwrap_or_default() / factor;
+          sleep_until(start + offset).await
+        },
+        None => origin = Some((Instant::now(), timestamp)),
+      }
+    }
+    Some((Ok(item), (items, origin)))
+  })
+}
+
+
+/// Replay the provided market data, in order, at the given speed.
+pub fn replay<I>(data: I, speed: Speed) -> impl Stream<Item = Item>
+where
+  I: IntoIterator<Item = Data>,
+{
+  pace(data.into_iter().map(Ok), speed)
+}
+
+/// Replay raw messages as archived from a realtime market data stream
+/// (e.g., using a raw message tap) at the given speed.
+///
+/// Messages that fail to decode are reported as errors, just as they
+/// would be by a realtime stream. Control messages are skipped.
+pub fn replay_messages<I>(messages: I, speed: Speed) -> impl Stream<Item = Item>
+where
+  I: IntoIterator,
+  I::Item: AsRef<[u8]>,
+{
+  let items = messages
+    .into_iter()
+    .flat_map(|message| decode(message.as_ref()));
+  pace(items, speed)
+}
+
+
+/// Convert historic trades for the given symbol into market data as
+/// received over a realtime stream.
+pub fn from_trades<'t, I>(symbol: &str, trades: I) -> Vec<Data>
+where
+  I: IntoIterator<Item = &'t trades::Trade>,
+{
+  trades
+    .into_iter()
+    .map(|trade| {
+      Data::Trade(Trade {
+        symbol: symbol.to_string(),
+        trade_id: trade.trade_id,
+        exchange: trade.exchange,
+        trade_price: trade.price.clone(),
+        trade_size: trade.size,
+        timestamp: trade.timestamp,
+        trade_conditions: trade.trade_conditions.clone(),
+        tape: trade.tape,
+      })
+    })
+    .collect()
+}
+
+/// Convert historic quotes for the given symbol into market data as
+/// received over a realtime stream.
+pub fn from_quotes<'q, I>(symbol: &str, quotes: I) -> Vec<Data>
+where
+  I: IntoIterator<Item = &'q quotes::Quote>,
+{
+  quotes
+    .into_iter()
+    .map(|quote| {
+      Data::Quote(Quote {
+        symbol: symbol.to_string(),
+        bid_price: quote.bid_price.clone(),
+        bid_size: quote.bid_size,
+        ask_price: quote.ask_price.clone(),
+        ask_size: quote.ask_size,
+        timestamp: quote.time,
+      })
+    })
+    .collect()
+}
+
+/// Merge multiple sequences of market data, e.g., trades and quotes for
+/// a set of symbols, into a single one ordered by time stamp.
+///
+/// Items with equal time stamps retain their relative order.
+pub fn merge<I>(sources: I) -> Vec<Data>
+where
+  I: IntoIterator<Item = Vec<Data>>,
+{
+  let mut data = sources.into_iter().flatten().collect::<Vec<_>>();
+  data.sort_by_key(|data| *data.timestamp());
+  data
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::time::Duration;
+
+  use futures::StreamExt as _;
+
+  use serde_json::from_str as from_json;
+
+  use test_log::test;
+
+
+  /// Create two historic trades, two seconds apart.
+  fn trades() -> Vec<trades::Trade> {
+    let response = r#"[
+  {"t":"2022-04-11T12:00:36Z","x":"V","p":168.04,"s":50,"c":["@"],"i":1,"z":"C"},
+  {"t":"2022-04-11T12:00:38Z","x":"V","p":168.1,"s":100,"c":["@"],"i":2,"z":"C"}
+]"#;
+    from_json(response).unwrap()
+  }
+
+  /// Collect all items of a replay stream, along with the time it took.
+  async fn collect<S>(stream: S) -> (Vec<Item>, Duration)
+  where
+    S: Stream<Item = Item>,
+  {
+    let start = Instant::now();
+    let items = stream.collect::<Vec<_>>().await;
+    (items, start.elapsed())
+  }
+
+
+  /// Check that historic trades and quotes are merged in order.
+  #[test]
+  fn merge_trades_and_quotes() {
+    let response = r#"[
+  {"t":"2022-04-11T12:00:37Z","ax":"V","ap":168.15,"as":1,"bx":"P","bp":167.98,"bs":2}
+]"#;
+    let quotes = from_json::<Vec<quotes::Quote>>(response).unwrap();
+
+    let data = merge([from_trades("AAPL", &trades()), from_quotes("AAPL", &quotes)]);
+    assert_eq!(data.len(), 3);
+    assert!(data[0].is_trade());
+    assert!(data[1].is_quote());
+    assert!(data[2].is_trade());
+    assert!(data.iter().all(|data| data.symbol() == "AAPL"));
+  }
+
+  /// Check that we pace replayed data as requested.
+  #[test(tokio::test(start_paused = true))]
+  async fn pace_replay() {
+    let data = from_trades("AAPL", &trades());
+
+    let (items, elapsed) = collect(replay(data.clone(), Speed::Original)).await;
+    assert_eq!(items.len(), 2);
+    assert_eq!(elapsed, Duration::from_secs(2));
+
+    let (_, elapsed) = collect(replay(data.clone(), Speed::Accelerated(4))).await;
+    assert_eq!(elapsed, Duration::from_millis(500));
+
+    let (_, elapsed) = collect(replay(data, Speed::Unpaced)).await;
+    assert_eq!(elapsed, Duration::from_secs(0));
+  }
+
+  /// Check that we can replay archived raw stream messages.
+  #[test(tokio::test(start_paused = true))]
+  async fn replay_raw_messages() {
+    let messages = [&include_bytes!("corpus/stream.json")[..], b"[{"];
+
+    let (items, _) = collect(replay_messages(messages, Speed::Original)).await;
+    assert_eq!(items.len(), 4);
+
+    let data = items[0].as_ref().unwrap().as_ref().unwrap();
+    assert!(data.is_bar());
+    let data = items[2].as_ref().unwrap().as_ref().unwrap();
+    assert!(data.is_trade());
+    assert!(items[3].as_ref().unwrap().is_err());
+  }
+}
@@ -0,0 +1,295 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+
+use num_decimal::Num;
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_urlencoded::to_string as to_query;
+
+use crate::data::v2::bars::Bar;
+use crate::data::v2::last_quote::Quote;
+use crate::data::v2::trades::Trade;
+use crate::data::v2::Feed;
+use crate::data::DATA_BASE_URL;
+use crate::money;
+use crate::util::string_slice_to_str;
+use crate::Str;
+
+
+/// A GET request to be made to the /v2/stocks/snapshots endpoint.
+#[derive(Clone, Serialize, PartialEq, Debug)]
+pub struct SnapshotsReq {
+  /// The symbols to retrieve snapshots for.
+  #[serde(rename = "symbols", serialize_with = "string_slice_to_str")]
+  pub symbols: Vec<String>,
+  /// The data feed to use.
+  #[serde(rename = "feed")]
+  pub feed: Option<Feed>,
+}
+
+
+/// A helper for initializing [`SnapshotsReq`] objects.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[allow(missing_copy_implementations)]
+pub struct SnapshotsReqInit {
+  /// See `SnapshotsReq::feed`.
+  pub feed: Option<Feed>,
+  #[doc(hidden)]
+  pub _non_exhaustive: (),
+}
+
+impl SnapshotsReqInit {
+  /// Create a [`SnapshotsReq`] from a `SnapshotsReqInit`.
+  #[inline]
+  pub fn init<I, S>(self, symbols: I) -> SnapshotsReq
+  where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+  {
+    SnapshotsReq {
+      symbols: symbols.into_iter().map(S::into).collect(),
+      feed: self.feed,
+    }
+  }
+}
+
+
+/// A snapshot of the market data for a single symbol, as returned by
+/// the /v2/stocks/snapshots endpoint.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[non_exhaustive]
+pub struct Snapshot {
+  /// The latest trade.
+  #[serde(rename = "latestTrade")]
+  pub latest_trade: Option<Trade>,
+  /// The latest quote.
+  #[serde(rename = "latestQuote")]
+  pub latest_quote: Option<Quote>,
+  /// The latest minute bar.
+  #[serde(rename = "minuteBar")]
+  pub minute_bar: Option<Bar>,
+  /// The daily bar of the current (or most recent) trading session.
+  #[serde(rename = "dailyBar")]
+  pub daily_bar: Option<Bar>,
+  /// The daily bar of the trading session preceding the one
+  /// represented by `daily_bar`.
+  #[serde(rename = "prevDailyBar")]
+  pub prev_daily_bar: Option<Bar>,
+}
+
+impl Snapshot {
+  /// Retrieve the change of the daily bar's close price relative to
+  /// the close price of the previous session (as a factor of 1).
+  ///
+  /// `None` is returned if either of the two daily bars is not
+  /// available or if the previous close price is zero.
+  pub fn change_since_prev_close(&self) -> Option<Num> {
+    let current = self.daily_bar.as_ref()?;
+    let previous = self.prev_daily_bar.as_ref()?;
+
+    money::relative_change(&previous.close, &current.close)
+  }
+
+  /// Retrieve the gap of the daily bar's open price relative to the
+  /// close price of the previous session (as a factor of 1).
+  ///
+  /// `None` is returned if either of the two daily bars is not
+  /// available or if the previous close price is zero.
+  pub fn gap_since_prev_close(&self) -> Option<Num> {
+    let current = self.daily_bar.as_ref()?;
+    let previous = self.prev_daily_bar.as_ref()?;
+
+    money::relative_change(&previous.close, &current.open)
+  }
+
+  /// Retrieve the range the price traded in during the current (or
+  /// most recent) trading session.
+  #[inline]
+  pub fn intraday_range(&self) -> Option<Num> {
+    self.daily_bar.as_ref().map(Bar::range)
+  }
+
+  /// Retrieve the time elapsed between the latest trade and `now`.
+  #[inline]
+  pub fn latest_trade_age(&self, now: DateTime<Utc>) -> Option<Duration> {
+    self
+      .latest_trade
+      .as_ref()
+      .map(|trade| now - trade.timestamp)
+  }
+}
+
+
+DataEndpoint! {
+  /// The representation of a GET request to the /v2/stocks/snapshots
+  /// endpoint.
+  pub Get(SnapshotsReq),
+  Ok => HashMap<String, Snapshot>, [
+    /// The snapshots were retrieved successfully.
+    /* 200 */ OK,
+  ],
+  Err => GetError, [
+    /// One of the provided symbols was invalid or not found or the data
+    /// feed is not supported.
+    /* 422 */ UNPROCESSABLE_ENTITY => InvalidInput,
+  ]
+
+  fn base_url() -> Option<Str> {
+    Some(DATA_BASE_URL.into())
+  }
+
+  #[inline]
+  fn path(_input: &Self::Input) -> Str {
+    "/v2/stocks/snapshots".into()
+  }
+
+  fn query(input: &Self::Input) -> Result<Option<Str>, Self::ConversionError> {
+    Ok(Some(to_query(input)?.into()))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use http_endpoint::Endpoint;
+
+  use test_log::test;
+
+  use crate::live;
+  use crate::RequestError;
+
+
+  /// Check that we can parse a reference snapshots response.
+  #[test]
+  fn parse_reference_snapshots() {
+    let response = br#"{
+  "AAPL": {
+    "latestTrade": {
+      "t": "2021-05-11T20:00:00.435997104Z",
+      "x": "Q",
+      "p": 125.91,
+      "s": 5589631,
+      "c": ["@", "M"],
+      "i": 179430,
+      "z": "C"
+    },
+    "latestQuote": {
+      "t": "2021-05-11T21:59:59.96093952Z",
+      "ax": "P",
+      "ap": 126.06,
+      "as": 2,
+      "bx": "P",
+      "bp": 126,
+      "bs": 2,
+      "c": ["R"]
+    },
+    "minuteBar": {
+      "t": "2021-05-11T22:02:00Z",
+      "o": 126.02,
+      "h": 126.02,
+      "l": 126.02,
+      "c": 126.02,
+      "v": 605
+    },
+    "dailyBar": {
+      "t": "2021-05-11T04:00:00Z",
+      "o": 123.5,
+      "h": 126.27,
+      "l": 122.77,
+      "c": 126,
+      "v": 123296034
+    },
+    "prevDailyBar": {
+      "t": "2021-05-10T04:00:00Z",
+      "o": 129.41,
+      "h": 129.54,
+      "l": 126.81,
+      "c": 126.85,
+      "v": 88071229
+    }
+  },
+  "MSFT": {
+    "latestTrade": null,
+    "latestQuote": null,
+    "minuteBar": null,
+    "dailyBar": null,
+    "prevDailyBar": null
+  }
+}"#;
+
+    let snapshots = Get::parse(response).unwrap();
+    assert_eq!(snapshots.len(), 2);
+
+    let aapl = &snapshots["AAPL"];
+    assert_eq!(
+      aapl.latest_trade.as_ref().unwrap().price,
+      Num::new(12591, 100)
+    );
+    assert_eq!(
+      aapl.latest_quote.as_ref().unwrap().bid_price,
+      Num::from(126)
+    );
+    assert_eq!(aapl.daily_bar.as_ref().unwrap().close, Num::from(126));
+    assert_eq!(
+      aapl.prev_daily_bar.as_ref().unwrap().close,
+      Num::new(12685, 100)
+    );
+    assert_eq!(aapl.change_since_prev_close(), Some(Num::new(-85, 12685)));
+    assert_eq!(aapl.gap_since_prev_close(), Some(Num::new(-335, 12685)));
+    assert_eq!(aapl.intraday_range(), Some(Num::new(350, 100)));
+    let now = DateTime::parse_from_rfc3339("2021-05-11T20:01:00.435997104Z").unwrap();
+    assert_eq!(
+      aapl.latest_trade_age(now.into()),
+      Some(Duration::minutes(1))
+    );
+
+    let msft = &snapshots["MSFT"];
+    assert_eq!(msft.daily_bar, None);
+    assert_eq!(msft.change_since_prev_close(), None);
+    assert_eq!(msft.gap_since_prev_close(), None);
+    assert_eq!(msft.intraday_range(), None);
+    assert_eq!(msft.latest_trade_age(Utc::now()), None);
+  }
+
+  /// Check that we can retrieve snapshots for multiple symbols.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn request_snapshots() {
+    let client = live::client().unwrap();
+
+    let req = SnapshotsReqInit::default().init(["SPY", "MSFT"]);
+    let snapshots = client.issue::<Get>(&req).await.unwrap();
+    assert_eq!(snapshots.len(), 2);
+
+    for symbol in ["SPY", "MSFT"] {
+      let snapshot = &snapshots[symbol];
+      assert!(snapshot.daily_bar.is_some());
+      assert!(snapshot.prev_daily_bar.is_some());
+      assert!(snapshot.change_since_prev_close().is_some());
+    }
+  }
+
+  /// Verify that we error out as expected when attempting to retrieve
+  /// snapshots for an invalid symbol.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn invalid_symbol() {
+    let client = live::client().unwrap();
+
+    let req = SnapshotsReqInit::default().init(["SPY", "ABC123"]);
+    let err = client.issue::<Get>(&req).await.unwrap_err();
+    match err {
+      RequestError::Endpoint(GetError::InvalidInput(_)) => (),
+      _ => panic!("Received unexpected error: {:?}", err),
+    };
+  }
+}
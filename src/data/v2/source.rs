@@ -0,0 +1,437 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::io::Error as IoError;
+use std::path::Path;
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+
+use chrono::DateTime;
+use chrono::Utc;
+
+use serde::de::DeserializeOwned;
+use serde_json::from_slice as from_json;
+use serde_json::Error as JsonError;
+use serde_variant::to_variant_name;
+use serde_variant::UnsupportedType;
+
+use thiserror::Error;
+
+use tokio::fs::read;
+
+use crate::data::v2::bars;
+use crate::data::v2::last_quote;
+use crate::data::v2::quotes;
+use crate::data::v2::trades;
+use crate::Client;
+use crate::RequestError;
+
+
+/// A trait abstracting over providers of historical market data.
+///
+/// The trait is implemented by [`Client`], retrieving data from the
+/// Alpaca API, and by [`FileSource`], reading data from the local file
+/// system. It allows, for example, backtesting frameworks to swap data
+/// providers without being written against concrete endpoints.
+#[async_trait]
+pub trait DataSource {
+  /// The error reported by the data source.
+  type Error;
+
+  /// Retrieve a page of bars.
+  async fn get_bars(&self, request: &bars::BarsReq) -> Result<bars::Bars, Self::Error>;
+
+  /// Retrieve a page of trades.
+  async fn get_trades(&self, request: &trades::TradesReq) -> Result<trades::Trades, Self::Error>;
+
+  /// Retrieve a page of quotes.
+  async fn get_quotes(&self, request: &quotes::QuotesReq) -> Result<quotes::Quotes, Self::Error>;
+
+  /// Retrieve the most recent quote for the given symbol.
+  async fn latest_quote(&self, symbol: &str) -> Result<quotes::Quote, Self::Error>;
+}
+
+
+/// An error as reported by the [`DataSource`] implementation of
+/// [`Client`].
+#[derive(Debug, Error)]
+pub enum ClientError {
+  /// Bars could not be retrieved.
+  #[error("failed to retrieve bars")]
+  Bars(#[source] RequestError<bars::GetError>),
+  /// Trades could not be retrieved.
+  #[error("failed to retrieve trades")]
+  Trades(#[source] RequestError<trades::GetError>),
+  /// Quotes could not be retrieved.
+  #[error("failed to retrieve quotes")]
+  Quotes(#[source] RequestError<quotes::GetError>),
+  /// The latest quote could not be retrieved.
+  #[error("failed to retrieve latest quote")]
+  LatestQuote(#[source] RequestError<last_quote::GetError>),
+}
+
+
+#[async_trait]
+impl DataSource for Client {
+  type Error = ClientError;
+
+  async fn get_bars(&self, request: &bars::BarsReq) -> Result<bars::Bars, Self::Error> {
+    self
+      .issue::<bars::Get>(request)
+      .await
+      .map_err(ClientError::Bars)
+  }
+
+  async fn get_trades(&self, request: &trades::TradesReq) -> Result<trades::Trades, Self::Error> {
+    self
+      .issue::<trades::Get>(request)
+      .await
+      .map_err(ClientError::Trades)
+  }
+
+  async fn get_quotes(&self, request: &quotes::QuotesReq) -> Result<quotes::Quotes, Self::Error> {
+    self
+      .issue::<quotes::Get>(request)
+      .await
+      .map_err(ClientError::Quotes)
+  }
+
+  async fn latest_quote(&self, symbol: &str) -> Result<quotes::Quote, Self::Error> {
+    let request = last_quote::LastQuoteReqInit::default().init(symbol);
+    self
+      .issue::<last_quote::Get>(&request)
+      .await
+      .map_err(ClientError::LatestQuote)
+  }
+}
+
+
+/// An error as reported by [`FileSource`].
+#[derive(Debug, Error)]
+pub enum FileSourceError {
+  /// A data file could not be read.
+  #[error("failed to read {}", .0.display())]
+  Io(PathBuf, #[source] IoError),
+  /// A data file could not be parsed.
+  #[error("failed to parse {}", .0.display())]
+  Json(PathBuf, #[source] JsonError),
+  /// The provided page token is not valid.
+  #[error("invalid page token: {0}")]
+  InvalidPageToken(String),
+  /// The provided symbol cannot be mapped to a data file.
+  #[error("invalid symbol: {0}")]
+  InvalidSymbol(String),
+  /// The provided time frame cannot be mapped to a data directory.
+  #[error("invalid time frame")]
+  InvalidTimeFrame(#[source] UnsupportedType),
+  /// No data are available for the given symbol.
+  #[error("no data available for {0}")]
+  NoData(String),
+}
+
+
+/// A [`DataSource`] reading market data from JSON files on the local
+/// file system.
+///
+/// Data are expected to be stored in the format reported by the
+/// respective API endpoint, with each file containing an array of all
+/// items for a symbol, in chronological order:
+/// - bars in `<root>/bars/<time-frame>/<symbol>.json`, with
+///   `<time-frame>` being, e.g., `1Day`
+/// - trades in `<root>/trades/<symbol>.json`
+/// - quotes in `<root>/quotes/<symbol>.json`
+///
+/// Requests are served by filtering the stored data by time range and
+/// paginating according to the requested limit. Adjustments and data
+/// feeds are not accounted for: data are reported as stored.
+#[derive(Clone, Debug)]
+pub struct FileSource {
+  /// The root directory of the source.
+  root: PathBuf,
+}
+
+impl FileSource {
+  /// The number of items reported per page if no limit is requested,
+  /// mirroring the API's default.
+  const DEFAULT_LIMIT: usize = 1000;
+
+  /// Create a new `FileSource` reading data from the given directory.
+  #[inline]
+  pub fn new(root: impl Into<PathBuf>) -> Self {
+    Self { root: root.into() }
+  }
+
+  /// Retrieve the root directory of the source.
+  #[inline]
+  pub fn root(&self) -> &Path {
+    &self.root
+  }
+
+  /// Assemble the path to the data file for the given symbol in the
+  /// given directory, relative to the root.
+  ///
+  /// Symbols are restricted to ASCII alphanumerics and `-`, `_`, and
+  /// `.` (but not as first character), so that they cannot refer to
+  /// files outside of the directory.
+  fn path(&self, dir: &Path, symbol: &str) -> Result<PathBuf, FileSourceError> {
+    let valid = !symbol.is_empty()
+      && !symbol.starts_with('.')
+      && symbol
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+    if !valid {
+      return Err(FileSourceError::InvalidSymbol(symbol.to_string()))
+    }
+    Ok(self.root.join(dir).join(format!("{}.json", symbol)))
+  }
+
+  /// Load all items stored in the file at the given path.
+  async fn load<T>(path: PathBuf) -> Result<Vec<T>, FileSourceError>
+  where
+    T: DeserializeOwned,
+  {
+    let data = match read(&path).await {
+      Ok(data) => data,
+      Err(err) => return Err(FileSourceError::Io(path, err)),
+    };
+    from_json::<Vec<T>>(&data).map_err(|err| FileSourceError::Json(path, err))
+  }
+
+  /// Retrieve the page of items in the given time range that the page
+  /// token refers to.
+  fn page<T, F>(
+    items: Vec<T>,
+    time: F,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+    limit: Option<usize>,
+    page_token: Option<&String>,
+  ) -> Result<(Vec<T>, Option<String>), FileSourceError>
+  where
+    F: Fn(&T) -> &DateTime<Utc>,
+  {
+    let offset = match page_token {
+      Some(token) => token
+        .parse::<usize>()
+        .map_err(|_| FileSourceError::InvalidPageToken(token.clone()))?,
+      None => 0,
+    };
+    let limit = limit.unwrap_or(Self::DEFAULT_LIMIT);
+
+    let mut items = items
+      .into_iter()
+      .filter(|item| time(item) >= start && time(item) <= end)
+      .skip(offset)
+      .take(limit + 1)
+      .collect::<Vec<_>>();
+
+    let next_page_token = if items.len() > limit {
+      items.truncate(limit);
+      Some((offset + limit).to_string())
+    } else {
+      None
+    };
+    Ok((items, next_page_token))
+  }
+}
+
+#[async_trait]
+impl DataSource for FileSource {
+  type Error = FileSourceError;
+
+  async fn get_bars(&self, request: &bars::BarsReq) -> Result<bars::Bars, Self::Error> {
+    let timeframe =
+      to_variant_name(&request.timeframe).map_err(FileSourceError::InvalidTimeFrame)?;
+    let dir = Path::new("bars").join(timeframe);
+    let path = self.path(&dir, &request.symbol)?;
+    let bars = Self::load::<bars::Bar>(path).await?;
+    let (bars, next_page_token) = Self::page(
+      bars,
+      |bar| &bar.time,
+      &request.start,
+      &request.end,
+      request.limit,
+      request.page_token.as_ref(),
+    )?;
+
+    Ok(bars::Bars {
+      bars,
+      symbol: request.symbol.clone(),
+      next_page_token,
+    })
+  }
+
+  async fn get_trades(&self, request: &trades::TradesReq) -> Result<trades::Trades, Self::Error> {
+    let path = self.path(Path::new("trades"), &request.symbol)?;
+    let trades = Self::load::<trades::Trade>(path).await?;
+    let (trades, next_page_token) = Self::page(
+      trades,
+      |trade| &trade.timestamp,
+      &request.start,
+      &request.end,
+      request.limit,
+      request.page_token.as_ref(),
+    )?;
+
+    Ok(trades::Trades {
+      trades,
+      symbol: request.symbol.clone(),
+      next_page_token,
+    })
+  }
+
+  async fn get_quotes(&self, request: &quotes::QuotesReq) -> Result<quotes::Quotes, Self::Error> {
+    let path = self.path(Path::new("quotes"), &request.symbol)?;
+    let quotes = Self::load::<quotes::Quote>(path).await?;
+    let (quotes, next_page_token) = Self::page(
+      quotes,
+      |quote| &quote.time,
+      &request.start,
+      &request.end,
+      request.limit,
+      request.page_token.as_ref(),
+    )?;
+
+    Ok(quotes::Quotes {
+      quotes,
+      symbol: request.symbol.clone(),
+      next_page_token,
+    })
+  }
+
+  /// Retrieve the last quote stored for the given symbol.
+  async fn latest_quote(&self, symbol: &str) -> Result<quotes::Quote, Self::Error> {
+    let path = self.path(Path::new("quotes"), symbol)?;
+    Self::load::<quotes::Quote>(path)
+      .await?
+      .pop()
+      .ok_or_else(|| FileSourceError::NoData(symbol.to_string()))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::env::temp_dir;
+  use std::fmt::Debug;
+  use std::fs::create_dir_all;
+  use std::fs::remove_dir_all;
+  use std::fs::write;
+  use std::process::id as process_id;
+  use std::str::FromStr as _;
+
+  use num_decimal::Num;
+
+  use test_log::test;
+
+  use crate::live;
+
+
+  /// Create a `FileSource` in a fresh temporary directory, populated
+  /// with daily bars and quotes for SPY.
+  fn source(name: &str) -> FileSource {
+    let root = temp_dir().join(format!("apca-source-{}-{}", name, process_id()));
+    let _ = remove_dir_all(&root);
+
+    let bars = root.join("bars").join("1Day");
+    create_dir_all(&bars).unwrap();
+    write(
+      bars.join("SPY.json"),
+      br#"[
+  {"t": "2021-11-03T04:00:00Z", "o": 460.0, "h": 466.0, "l": 459.0, "c": 465.0, "v": 100},
+  {"t": "2021-11-04T04:00:00Z", "o": 465.0, "h": 468.0, "l": 464.0, "c": 467.0, "v": 200},
+  {"t": "2021-11-05T04:00:00Z", "o": 469.28, "h": 470.65, "l": 466.92, "c": 468.53, "v": 300}
+]"#,
+    )
+    .unwrap();
+
+    let quotes = root.join("quotes");
+    create_dir_all(&quotes).unwrap();
+    write(
+      quotes.join("SPY.json"),
+      br#"[
+  {"t": "2021-11-05T15:00:00Z", "ap": 468.6, "as": 1, "bp": 468.5, "bs": 2},
+  {"t": "2021-11-05T15:00:01Z", "ap": 468.7, "as": 3, "bp": 468.6, "bs": 4}
+]"#,
+    )
+    .unwrap();
+
+    FileSource::new(root)
+  }
+
+
+  /// Check that we can page through bars stored in a `FileSource`.
+  #[test(tokio::test)]
+  async fn page_file_source_bars() {
+    let source = source("bars");
+    let start = DateTime::from_str("2021-11-04T00:00:00Z").unwrap();
+    let end = DateTime::from_str("2021-11-06T00:00:00Z").unwrap();
+    let mut request = bars::BarsReqInit {
+      limit: Some(1),
+      ..Default::default()
+    }
+    .init("SPY", start, end, bars::TimeFrame::OneDay);
+
+    let bars = source.get_bars(&request).await.unwrap();
+    assert_eq!(bars.symbol, "SPY");
+    assert_eq!(bars.bars.len(), 1);
+    assert_eq!(bars.bars[0].close, Num::from(467));
+    assert_eq!(bars.next_page_token.as_deref(), Some("1"));
+
+    request.page_token = bars.next_page_token;
+    let bars = source.get_bars(&request).await.unwrap();
+    assert_eq!(bars.bars.len(), 1);
+    assert_eq!(bars.bars[0].close, Num::new(46853, 100));
+    assert_eq!(bars.next_page_token, None);
+
+    request.page_token = Some("foo".to_string());
+    let err = source.get_bars(&request).await.unwrap_err();
+    assert!(matches!(err, FileSourceError::InvalidPageToken(..)));
+
+    let _ = remove_dir_all(source.root());
+  }
+
+  /// Check that we report the last stored quote as the latest one.
+  #[test(tokio::test)]
+  async fn file_source_latest_quote() {
+    let source = source("quotes");
+    let quote = source.latest_quote("SPY").await.unwrap();
+    assert_eq!(quote.ask_price, Num::new(4687, 10));
+    assert_eq!(quote.bid_size, 4);
+
+    let err = source.latest_quote("AAPL").await.unwrap_err();
+    assert!(matches!(err, FileSourceError::Io(..)));
+
+    for symbol in ["../quotes/SPY", "", ".SPY", "SPY/../SPY"] {
+      let err = source.latest_quote(symbol).await.unwrap_err();
+      assert!(
+        matches!(err, FileSourceError::InvalidSymbol(..)),
+        "{:?}",
+        err
+      );
+    }
+
+    let _ = remove_dir_all(source.root());
+  }
+
+  /// Check that a `Client` can be used as a `DataSource`.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn client_latest_quote() {
+    async fn latest<S>(source: &S) -> quotes::Quote
+    where
+      S: DataSource,
+      S::Error: Debug,
+    {
+      source.latest_quote("SPY").await.unwrap()
+    }
+
+    let client = live::client().unwrap();
+    let quote = latest(&*client).await;
+    assert!(quote.ask_price.is_positive());
+  }
+}
@@ -0,0 +1,229 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::sync::atomic::AtomicI64;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::Utc;
+
+
+/// Counters tracking the messages received over a realtime market data
+/// stream, updated as messages arrive.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+  /// The number of bars received.
+  bars: AtomicU64,
+  /// The number of quotes received.
+  quotes: AtomicU64,
+  /// The number of trades received.
+  trades: AtomicU64,
+  /// The number of other market data messages received.
+  other: AtomicU64,
+  /// The number of control messages received.
+  control: AtomicU64,
+  /// The number of bytes received.
+  bytes: AtomicU64,
+  /// The number of messages that failed to decode.
+  decode_errors: AtomicU64,
+  /// The number of latency samples taken.
+  latency_count: AtomicU64,
+  /// The sum of all latency samples, in microseconds.
+  latency_sum: AtomicI64,
+  /// The maximum latency sample, in microseconds.
+  latency_max: AtomicI64,
+  /// The most recent latency sample, in microseconds.
+  latency_last: AtomicI64,
+  /// The offset to add to the local time to correct for clock skew,
+  /// in microseconds.
+  clock_offset: AtomicI64,
+}
+
+impl Counters {
+  /// Account for the receipt of a websocket message of the given size.
+  pub(crate) fn add_bytes(&self, count: usize) {
+    let _ = self.bytes.fetch_add(count as u64, Ordering::Relaxed);
+  }
+
+  /// Account for a message that failed to decode.
+  pub(crate) fn add_decode_error(&self) {
+    let _ = self.decode_errors.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Account for the receipt of a bar.
+  pub(crate) fn add_bar(&self) {
+    let _ = self.bars.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Account for the receipt of a quote with the given time stamp.
+  pub(crate) fn add_quote(&self, timestamp: &DateTime<Utc>) {
+    let _ = self.quotes.fetch_add(1, Ordering::Relaxed);
+    self.add_latency(timestamp)
+  }
+
+  /// Account for the receipt of a trade with the given time stamp.
+  pub(crate) fn add_trade(&self, timestamp: &DateTime<Utc>) {
+    let _ = self.trades.fetch_add(1, Ordering::Relaxed);
+    self.add_latency(timestamp)
+  }
+
+  /// Account for the receipt of any other market data message.
+  pub(crate) fn add_other(&self) {
+    let _ = self.other.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Account for the receipt of a control message.
+  pub(crate) fn add_control(&self) {
+    let _ = self.control.fetch_add(1, Ordering::Relaxed);
+  }
+
+  /// Set the offset to add to the local time when computing latencies,
+  /// correcting for clock skew.
+  pub(crate) fn set_clock_offset(&self, offset: Duration) {
+    let offset = offset.num_microseconds().unwrap_or(i64::MAX);
+    self.clock_offset.store(offset, Ordering::Relaxed);
+  }
+
+  /// Take a latency sample for a message with the given (exchange)
+  /// time stamp, received now.
+  fn add_latency(&self, timestamp: &DateTime<Utc>) {
+    let offset = Duration::microseconds(self.clock_offset.load(Ordering::Relaxed));
+    let latency = (Utc::now() + offset - *timestamp)
+      .num_microseconds()
+      .unwrap_or(i64::MAX);
+
+    // Counters are only ever updated by the task polling the stream,
+    // so there is no contention to worry about here.
+    if self.latency_count.fetch_add(1, Ordering::Relaxed) == 0 {
+      self.latency_max.store(latency, Ordering::Relaxed);
+    } else {
+      let _ = self.latency_max.fetch_max(latency, Ordering::Relaxed);
+    }
+    let _ = self.latency_sum.fetch_add(latency, Ordering::Relaxed);
+    self.latency_last.store(latency, Ordering::Relaxed);
+  }
+
+  /// Take a snapshot of the current counter values.
+  pub(crate) fn snapshot(&self) -> Stats {
+    let latency_count = self.latency_count.load(Ordering::Relaxed);
+    let latency = |value: i64| {
+      if latency_count > 0 {
+        Some(Duration::microseconds(value))
+      } else {
+        None
+      }
+    };
+
+    let latency_sum = self.latency_sum.load(Ordering::Relaxed);
+    let mean_latency = latency(latency_sum / (latency_count.max(1) as i64));
+
+    Stats {
+      bars: self.bars.load(Ordering::Relaxed),
+      quotes: self.quotes.load(Ordering::Relaxed),
+      trades: self.trades.load(Ordering::Relaxed),
+      other: self.other.load(Ordering::Relaxed),
+      control: self.control.load(Ordering::Relaxed),
+      bytes: self.bytes.load(Ordering::Relaxed),
+      decode_errors: self.decode_errors.load(Ordering::Relaxed),
+      last_latency: latency(self.latency_last.load(Ordering::Relaxed)),
+      mean_latency,
+      max_latency: latency(self.latency_max.load(Ordering::Relaxed)),
+    }
+  }
+}
+
+
+/// Statistics about the messages received over a realtime market data
+/// stream.
+///
+/// Latencies are computed from the exchange time stamps of quotes and
+/// trades and the local time at which they were received, so they are
+/// subject to clock skew between the exchange and the local system.
+/// Skew can be corrected for by providing a clock offset via
+/// [`Subscription::set_clock_offset`][crate::data::v2::stream::Subscription::set_clock_offset].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Stats {
+  /// The number of bars received.
+  pub bars: u64,
+  /// The number of quotes received.
+  pub quotes: u64,
+  /// The number of trades received.
+  pub trades: u64,
+  /// The number of other market data messages received, e.g., trading
+  /// statuses.
+  pub other: u64,
+  /// The number of control messages received.
+  pub control: u64,
+  /// The number of bytes received.
+  pub bytes: u64,
+  /// The number of messages that failed to decode.
+  pub decode_errors: u64,
+  /// The latency of the most recently received quote or trade.
+  pub last_latency: Option<Duration>,
+  /// The mean latency of all quotes and trades received.
+  pub mean_latency: Option<Duration>,
+  /// The maximum latency of all quotes and trades received.
+  pub max_latency: Option<Duration>,
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use test_log::test;
+
+
+  /// Check that counters are reflected in snapshots as expected.
+  #[test]
+  fn count_messages() {
+    let counters = Counters::default();
+    assert_eq!(counters.snapshot(), Stats::default());
+
+    counters.add_bytes(42);
+    counters.add_bytes(8);
+    counters.add_bar();
+    counters.add_control();
+    counters.add_other();
+    counters.add_decode_error();
+    counters.add_quote(&(Utc::now() - Duration::seconds(2)));
+    counters.add_trade(&(Utc::now() - Duration::seconds(1)));
+
+    let stats = counters.snapshot();
+    assert_eq!(stats.bytes, 50);
+    assert_eq!(stats.bars, 1);
+    assert_eq!(stats.quotes, 1);
+    assert_eq!(stats.trades, 1);
+    assert_eq!(stats.control, 1);
+    assert_eq!(stats.other, 1);
+    assert_eq!(stats.decode_errors, 1);
+
+    let last = stats.last_latency.unwrap();
+    let mean = stats.mean_latency.unwrap();
+    let max = stats.max_latency.unwrap();
+    assert!(last >= Duration::seconds(1) && last < Duration::seconds(2), "{}", last);
+    assert!(mean > last && mean < max, "{}", mean);
+    assert!(max >= Duration::seconds(2), "{}", max);
+  }
+
+  /// Check that latencies are corrected for the configured clock
+  /// offset.
+  #[test]
+  fn correct_clock_skew() {
+    let counters = Counters::default();
+    counters.set_clock_offset(Duration::seconds(10));
+    counters.add_quote(&Utc::now());
+
+    let latency = counters.snapshot().last_latency.unwrap();
+    assert!(latency >= Duration::seconds(10) && latency < Duration::seconds(11), "{}", latency);
+
+    counters.set_clock_offset(Duration::seconds(-10));
+    counters.add_trade(&(Utc::now() - Duration::seconds(11)));
+
+    let latency = counters.snapshot().last_latency.unwrap();
+    assert!(latency >= Duration::seconds(1) && latency < Duration::seconds(2), "{}", latency);
+  }
+}
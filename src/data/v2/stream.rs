@@ -6,10 +6,12 @@ use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::marker::PhantomData;
 use std::ops::Deref;
+use std::sync::Arc;
 
 use async_trait::async_trait;
 
 use chrono::DateTime;
+use chrono::Duration;
 use chrono::Utc;
 
 use futures::stream::Fuse;
@@ -47,11 +49,19 @@ use websocket_util::tungstenite::Error as WebSocketError;
 use websocket_util::wrap;
 use websocket_util::wrap::Wrapper;
 
+use super::connections::Handle;
+use super::stats::Counters;
+use super::stats::Stats;
+use super::tap::Tap;
+use super::tap::TapMode;
 use super::unfold::Unfold;
 
+use crate::metric;
 use crate::subscribable::Subscribable;
+use crate::util::datetime_from_str;
 use crate::websocket::connect;
 use crate::websocket::MessageResult;
+use crate::websocket::SharedSink;
 use crate::ApiInfo;
 use crate::Error;
 use crate::Str;
@@ -123,6 +133,30 @@ impl Source for SIP {
 impl private::Sealed for SIP {}
 
 
+/// Use Alpaca's test stream as the data source.
+///
+/// The test stream is available around the clock and publishes
+/// deterministic, synthetic market data for the `FAKEPACA` symbol. It
+/// is intended for developing and testing streaming consumers outside
+/// of regular market hours.
+#[derive(Clone, Copy, Debug)]
+pub enum Test {}
+
+impl Test {
+  /// The symbol for which the test stream publishes data.
+  pub const SYMBOL: &'static str = "FAKEPACA";
+}
+
+impl Source for Test {
+  #[inline]
+  fn as_str() -> &'static str {
+    "test"
+  }
+}
+
+impl private::Sealed for Test {}
+
+
 /// A symbol.
 pub type Symbol = Str;
 
@@ -200,7 +234,7 @@ pub struct Bar {
   #[serde(rename = "v")]
   pub volume: u64,
   /// The bar's time stamp.
-  #[serde(rename = "t")]
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
   pub timestamp: DateTime<Utc>,
 }
 
@@ -224,7 +258,7 @@ pub struct Quote {
   #[serde(rename = "as")]
   pub ask_size: u64,
   /// The quote's time stamp.
-  #[serde(rename = "t")]
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
   pub timestamp: DateTime<Utc>,
 }
 
@@ -248,7 +282,7 @@ pub struct Trade {
   #[serde(rename = "s")]
   pub trade_size: u64,
   /// The trade's time stamp.
-  #[serde(rename = "t")]
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
   pub timestamp: DateTime<Utc>,
   /// Trade condition.
   #[serde(rename = "c")]
@@ -259,6 +293,176 @@ pub struct Trade {
 }
 
 
+/// A correction of a previously reported trade.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TradeCorrection {
+  /// The symbol of the corrected trade.
+  #[serde(rename = "S")]
+  pub symbol: String,
+  /// The exchange where the original trade occurred.
+  #[serde(rename = "x")]
+  pub exchange: char,
+  /// The ID of the original trade.
+  #[serde(rename = "oi")]
+  pub original_trade_id: u64,
+  /// The price of the original trade.
+  #[serde(rename = "op")]
+  pub original_trade_price: Num,
+  /// The size of the original trade.
+  #[serde(rename = "os")]
+  pub original_trade_size: u64,
+  /// The conditions of the original trade.
+  #[serde(rename = "oc")]
+  pub original_trade_conditions: Vec<char>,
+  /// The ID of the corrected trade.
+  #[serde(rename = "ci")]
+  pub corrected_trade_id: u64,
+  /// The price of the corrected trade.
+  #[serde(rename = "cp")]
+  pub corrected_trade_price: Num,
+  /// The size of the corrected trade.
+  #[serde(rename = "cs")]
+  pub corrected_trade_size: u64,
+  /// The conditions of the corrected trade.
+  #[serde(rename = "cc")]
+  pub corrected_trade_conditions: Vec<char>,
+  /// The correction's time stamp.
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
+  pub timestamp: DateTime<Utc>,
+  /// Tape.
+  #[serde(rename = "z")]
+  pub tape: char,
+}
+
+
+/// The action a [`TradeCancel`] message reports.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[non_exhaustive]
+pub enum CancelAction {
+  /// The trade was canceled.
+  #[serde(rename = "C")]
+  Cancel,
+  /// The trade was reported in error.
+  #[serde(rename = "E")]
+  Error,
+}
+
+
+/// A cancellation of a previously reported trade or a notification
+/// that it was reported in error.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TradeCancel {
+  /// The symbol of the canceled trade.
+  #[serde(rename = "S")]
+  pub symbol: String,
+  /// The ID of the canceled trade.
+  #[serde(rename = "i")]
+  pub trade_id: u64,
+  /// The exchange where the canceled trade occurred.
+  #[serde(rename = "x")]
+  pub exchange: char,
+  /// The price of the canceled trade.
+  #[serde(rename = "p")]
+  pub trade_price: Num,
+  /// The size of the canceled trade.
+  #[serde(rename = "s")]
+  pub trade_size: u64,
+  /// Whether the trade was canceled or reported in error.
+  #[serde(rename = "a")]
+  pub action: CancelAction,
+  /// The message's time stamp.
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
+  pub timestamp: DateTime<Utc>,
+  /// Tape.
+  #[serde(rename = "z")]
+  pub tape: char,
+}
+
+
+/// A trading status message for an equity, reporting, for example,
+/// trading halts and resumptions.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TradingStatus {
+  /// The symbol the status pertains to.
+  #[serde(rename = "S")]
+  pub symbol: String,
+  /// The status code, as defined by the respective tape's plan.
+  #[serde(rename = "sc")]
+  pub status_code: String,
+  /// A human readable description of the status code.
+  #[serde(rename = "sm")]
+  pub status_message: String,
+  /// The reason code, as defined by the respective tape's plan.
+  #[serde(rename = "rc")]
+  pub reason_code: String,
+  /// A human readable description of the reason code.
+  #[serde(rename = "rm")]
+  pub reason_message: String,
+  /// The status' time stamp.
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
+  pub timestamp: DateTime<Utc>,
+  /// Tape.
+  #[serde(rename = "z")]
+  pub tape: char,
+}
+
+impl TradingStatus {
+  /// Check whether the status indicates that trading in the symbol is
+  /// halted or paused.
+  ///
+  /// Trading halts are reported as "2" (CTA) or "H" (UTP), volatility
+  /// trading pauses as "P" (UTP).
+  #[inline]
+  pub fn is_halted(&self) -> bool {
+    matches!(self.status_code.as_str(), "2" | "H" | "P")
+  }
+}
+
+
+/// A limit up-limit down (LULD) message for an equity, reporting the
+/// price band within which trading is permitted.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Luld {
+  /// The symbol the price band pertains to.
+  #[serde(rename = "S")]
+  pub symbol: String,
+  /// The upper bound of the price band.
+  #[serde(rename = "u")]
+  pub limit_up_price: Num,
+  /// The lower bound of the price band.
+  #[serde(rename = "d")]
+  pub limit_down_price: Num,
+  /// The LULD indicator.
+  #[serde(rename = "i")]
+  pub indicator: char,
+  /// The message's time stamp.
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
+  pub timestamp: DateTime<Utc>,
+  /// Tape.
+  #[serde(rename = "z")]
+  pub tape: char,
+}
+
+
+/// An order imbalance message for an equity, reporting auction
+/// imbalance data around the opening and closing auctions.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Imbalance {
+  /// The symbol the imbalance pertains to.
+  #[serde(rename = "S")]
+  pub symbol: String,
+  /// The auction's reference price.
+  #[serde(rename = "p")]
+  pub reference_price: Num,
+  /// The message's time stamp.
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
+  pub timestamp: DateTime<Utc>,
+  /// Tape.
+  #[serde(rename = "z")]
+  pub tape: char,
+}
+
+
 /// An error as reported by the Alpaca Stream API.
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize, ThisError)]
 #[error("{message} ({code})")]
@@ -272,6 +476,100 @@ pub struct StreamApiError {
 }
 
 
+/// A typed representation of an error reported by the Alpaca Stream
+/// API, as decoded from its numeric code.
+#[derive(Clone, Debug, PartialEq, ThisError)]
+#[non_exhaustive]
+pub enum StreamError {
+  /// A request sent to the server could not be parsed.
+  #[error("the server failed to parse a request (400)")]
+  InvalidSyntax,
+  /// A request requiring authentication was sent before
+  /// authenticating.
+  #[error("the connection is not authenticated (401)")]
+  NotAuthenticated,
+  /// Authentication failed.
+  #[error("authentication failed; check the API key ID and secret (402)")]
+  AuthFailed,
+  /// An authentication request was sent on an already authenticated
+  /// connection.
+  #[error("the connection is already authenticated (403)")]
+  AlreadyAuthenticated,
+  /// The client did not authenticate in time after connecting.
+  #[error("authentication timed out; authenticate right after connecting (404)")]
+  AuthTimeout,
+  /// The number of subscribed symbols exceeds the limit of the plan.
+  #[error("symbol limit exceeded; subscribe to fewer symbols or upgrade the plan (405)")]
+  SymbolLimitExceeded,
+  /// The number of concurrent connections exceeds the limit of the
+  /// plan.
+  #[error(
+    "connection limit exceeded; close other streams of the account or share a single one (406)"
+  )]
+  ConnectionLimitExceeded,
+  /// The client does not consume messages quickly enough.
+  #[error("client is too slow; consume messages more quickly or subscribe to less data (407)")]
+  SlowClient,
+  /// The account does not have access to the v2 API.
+  #[error("the v2 API is not enabled for the account (408)")]
+  V2NotEnabled,
+  /// The subscription plan does not permit access to the requested
+  /// data, e.g., when requesting SIP data on a free plan.
+  #[error("insufficient subscription; use the IEX feed or upgrade the plan (409)")]
+  InsufficientSubscription,
+  /// The requested subscription is not supported by the feed.
+  #[error("the requested subscription is not supported by the feed (410)")]
+  InvalidSubscription,
+  /// The server encountered an internal error.
+  #[error("the server reported an internal error (500)")]
+  Internal,
+  /// An error with a code not known to us.
+  #[error(transparent)]
+  Other(StreamApiError),
+}
+
+impl StreamError {
+  /// Retrieve the numeric code of the error, as reported by the server.
+  pub fn code(&self) -> u64 {
+    match self {
+      Self::InvalidSyntax => 400,
+      Self::NotAuthenticated => 401,
+      Self::AuthFailed => 402,
+      Self::AlreadyAuthenticated => 403,
+      Self::AuthTimeout => 404,
+      Self::SymbolLimitExceeded => 405,
+      Self::ConnectionLimitExceeded => 406,
+      Self::SlowClient => 407,
+      Self::V2NotEnabled => 408,
+      Self::InsufficientSubscription => 409,
+      Self::InvalidSubscription => 410,
+      Self::Internal => 500,
+      Self::Other(error) => error.code,
+    }
+  }
+}
+
+impl From<StreamApiError> for StreamError {
+  fn from(error: StreamApiError) -> Self {
+    match error.code {
+      400 => Self::InvalidSyntax,
+      401 => Self::NotAuthenticated,
+      402 => Self::AuthFailed,
+      403 => Self::AlreadyAuthenticated,
+      404 => Self::AuthTimeout,
+      405 => Self::SymbolLimitExceeded,
+      406 => Self::ConnectionLimitExceeded,
+      407 => Self::SlowClient,
+      408 => Self::V2NotEnabled,
+      409 => Self::InsufficientSubscription,
+      410 => Self::InvalidSubscription,
+      500 => Self::Internal,
+      _ => Self::Other(error),
+    }
+  }
+}
+
+
 /// An enum representing the different messages we may receive over our
 /// websocket channel.
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
@@ -288,6 +586,24 @@ pub enum DataMessage {
   /// A variant representing a trade for a given symbol.
   #[serde(rename = "t")]
   Trade(Trade),
+  /// A variant representing a correction of a previously reported
+  /// trade.
+  #[serde(rename = "c")]
+  TradeCorrection(TradeCorrection),
+  /// A variant representing the cancellation of a previously reported
+  /// trade.
+  #[serde(rename = "x")]
+  TradeCancel(TradeCancel),
+  /// A variant representing a trading status update for a given
+  /// symbol.
+  #[serde(rename = "s")]
+  TradingStatus(TradingStatus),
+  /// A variant representing a LULD price band for a given symbol.
+  #[serde(rename = "l")]
+  Luld(Luld),
+  /// A variant representing an order imbalance for a given symbol.
+  #[serde(rename = "i")]
+  Imbalance(Imbalance),
   /// A control message describing the current list of subscriptions.
   #[serde(rename = "subscription")]
   Subscription(MarketData),
@@ -301,8 +617,30 @@ pub enum DataMessage {
 }
 
 
+impl DataMessage {
+  /// Convert the message into a data item or, if it does not represent
+  /// one, a control message.
+  #[allow(clippy::result_large_err)]
+  pub(crate) fn into_data(self) -> Result<Data, ControlMessage> {
+    match self {
+      Self::Bar(bar) => Ok(Data::Bar(bar)),
+      Self::Quote(quote) => Ok(Data::Quote(quote)),
+      Self::Trade(trade) => Ok(Data::Trade(trade)),
+      Self::TradeCorrection(correction) => Ok(Data::TradeCorrection(correction)),
+      Self::TradeCancel(cancel) => Ok(Data::TradeCancel(cancel)),
+      Self::TradingStatus(status) => Ok(Data::TradingStatus(status)),
+      Self::Luld(luld) => Ok(Data::Luld(luld)),
+      Self::Imbalance(imbalance) => Ok(Data::Imbalance(imbalance)),
+      Self::Subscription(data) => Err(ControlMessage::Subscription(data)),
+      Self::Success => Err(ControlMessage::Success),
+      Self::Error(error) => Err(ControlMessage::Error(error)),
+    }
+  }
+}
+
+
 /// A data item as received over our websocket channel.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 #[non_exhaustive]
 pub enum Data {
   /// A variant representing aggregate data for a given symbol.
@@ -311,9 +649,52 @@ pub enum Data {
   Quote(Quote),
   /// A variant representing trade data for a given symbol.
   Trade(Trade),
+  /// A variant representing a correction of a previously reported
+  /// trade.
+  TradeCorrection(TradeCorrection),
+  /// A variant representing the cancellation of a previously reported
+  /// trade.
+  TradeCancel(TradeCancel),
+  /// A variant representing a trading status update for a given
+  /// symbol.
+  TradingStatus(TradingStatus),
+  /// A variant representing a LULD price band for a given symbol.
+  Luld(Luld),
+  /// A variant representing an order imbalance for a given symbol.
+  Imbalance(Imbalance),
 }
 
 impl Data {
+  /// Retrieve the symbol the data are for.
+  #[inline]
+  pub fn symbol(&self) -> &str {
+    match self {
+      Self::Bar(bar) => &bar.symbol,
+      Self::Quote(quote) => &quote.symbol,
+      Self::Trade(trade) => &trade.symbol,
+      Self::TradeCorrection(correction) => &correction.symbol,
+      Self::TradeCancel(cancel) => &cancel.symbol,
+      Self::TradingStatus(status) => &status.symbol,
+      Self::Luld(luld) => &luld.symbol,
+      Self::Imbalance(imbalance) => &imbalance.symbol,
+    }
+  }
+
+  /// Retrieve the time stamp of the data.
+  #[inline]
+  pub fn timestamp(&self) -> &DateTime<Utc> {
+    match self {
+      Self::Bar(bar) => &bar.timestamp,
+      Self::Quote(quote) => &quote.timestamp,
+      Self::Trade(trade) => &trade.timestamp,
+      Self::TradeCorrection(correction) => &correction.timestamp,
+      Self::TradeCancel(cancel) => &cancel.timestamp,
+      Self::TradingStatus(status) => &status.timestamp,
+      Self::Luld(luld) => &luld.timestamp,
+      Self::Imbalance(imbalance) => &imbalance.timestamp,
+    }
+  }
+
   /// Check whether this object is of the `Bar` variant.
   #[inline]
   pub fn is_bar(&self) -> bool {
@@ -331,6 +712,36 @@ impl Data {
   pub fn is_trade(&self) -> bool {
     matches!(self, Self::Trade(..))
   }
+
+  /// Check whether this object is of the `TradeCorrection` variant.
+  #[inline]
+  pub fn is_trade_correction(&self) -> bool {
+    matches!(self, Self::TradeCorrection(..))
+  }
+
+  /// Check whether this object is of the `TradeCancel` variant.
+  #[inline]
+  pub fn is_trade_cancel(&self) -> bool {
+    matches!(self, Self::TradeCancel(..))
+  }
+
+  /// Check whether this object is of the `TradingStatus` variant.
+  #[inline]
+  pub fn is_trading_status(&self) -> bool {
+    matches!(self, Self::TradingStatus(..))
+  }
+
+  /// Check whether this object is of the `Luld` variant.
+  #[inline]
+  pub fn is_luld(&self) -> bool {
+    matches!(self, Self::Luld(..))
+  }
+
+  /// Check whether this object is of the `Imbalance` variant.
+  #[inline]
+  pub fn is_imbalance(&self) -> bool {
+    matches!(self, Self::Imbalance(..))
+  }
 }
 
 
@@ -348,6 +759,22 @@ pub enum ControlMessage {
 }
 
 
+/// Retrieve the kind of a data message, as used for labeling metrics.
+fn message_kind(message: &DataMessage) -> &'static str {
+  match message {
+    DataMessage::Bar(..) => "bar",
+    DataMessage::Quote(..) => "quote",
+    DataMessage::Trade(..) => "trade",
+    DataMessage::TradeCorrection(..) => "trade_correction",
+    DataMessage::TradeCancel(..) => "trade_cancel",
+    DataMessage::TradingStatus(..) => "trading_status",
+    DataMessage::Luld(..) => "luld",
+    DataMessage::Imbalance(..) => "imbalance",
+    DataMessage::Subscription(..) | DataMessage::Success | DataMessage::Error(..) => "control",
+  }
+}
+
+
 /// A websocket message that we tried to parse.
 type ParsedMessage = MessageResult<Result<DataMessage, JsonError>, WebSocketError>;
 
@@ -357,21 +784,9 @@ impl subscribe::Message for ParsedMessage {
 
   fn classify(self) -> subscribe::Classification<Self::UserMessage, Self::ControlMessage> {
     match self {
-      MessageResult::Ok(Ok(message)) => match message {
-        DataMessage::Bar(bar) => subscribe::Classification::UserMessage(Ok(Ok(Data::Bar(bar)))),
-        DataMessage::Quote(quote) => {
-          subscribe::Classification::UserMessage(Ok(Ok(Data::Quote(quote))))
-        },
-        DataMessage::Trade(trade) => {
-          subscribe::Classification::UserMessage(Ok(Ok(Data::Trade(trade))))
-        },
-        DataMessage::Subscription(data) => {
-          subscribe::Classification::ControlMessage(ControlMessage::Subscription(data))
-        },
-        DataMessage::Success => subscribe::Classification::ControlMessage(ControlMessage::Success),
-        DataMessage::Error(error) => {
-          subscribe::Classification::ControlMessage(ControlMessage::Error(error))
-        },
+      MessageResult::Ok(Ok(message)) => match message.into_data() {
+        Ok(data) => subscribe::Classification::UserMessage(Ok(Ok(data))),
+        Err(control) => subscribe::Classification::ControlMessage(control),
       },
       // JSON errors are directly passed through.
       MessageResult::Ok(Err(err)) => subscribe::Classification::UserMessage(Ok(Err(err))),
@@ -524,6 +939,21 @@ pub struct MarketData {
   /// The trades to subscribe to.
   #[serde(default)]
   pub trades: Symbols,
+  /// The trading statuses to subscribe to.
+  ///
+  /// Trading statuses are only available with the SIP source.
+  #[serde(default, skip_serializing_if = "Symbols::is_empty")]
+  pub statuses: Symbols,
+  /// The LULD price bands to subscribe to.
+  ///
+  /// LULD price bands are only available with the SIP source.
+  #[serde(default, skip_serializing_if = "Symbols::is_empty")]
+  pub lulds: Symbols,
+  /// The order imbalances to subscribe to.
+  ///
+  /// Order imbalances are only available with the SIP source.
+  #[serde(default, skip_serializing_if = "Symbols::is_empty")]
+  pub imbalances: Symbols,
 }
 
 impl MarketData {
@@ -556,6 +986,36 @@ impl MarketData {
   {
     self.trades = Symbols::List(symbols.into());
   }
+
+  /// A convenience function for setting the
+  /// [`statuses`][MarketData::statuses] member.
+  #[inline]
+  pub fn set_statuses<S>(&mut self, symbols: S)
+  where
+    S: Into<SymbolList>,
+  {
+    self.statuses = Symbols::List(symbols.into());
+  }
+
+  /// A convenience function for setting the [`lulds`][MarketData::lulds]
+  /// member.
+  #[inline]
+  pub fn set_lulds<S>(&mut self, symbols: S)
+  where
+    S: Into<SymbolList>,
+  {
+    self.lulds = Symbols::List(symbols.into());
+  }
+
+  /// A convenience function for setting the
+  /// [`imbalances`][MarketData::imbalances] member.
+  #[inline]
+  pub fn set_imbalances<S>(&mut self, symbols: S)
+  where
+    S: Into<SymbolList>,
+  {
+    self.imbalances = Symbols::List(symbols.into());
+  }
 }
 
 
@@ -592,6 +1052,8 @@ pub enum Request<'d> {
 ///   [`unsubscribe`][Subscription::unsubscribe] operation to resolve,
 ///   the associated [`MessageStream`] stream needs to be polled;
 ///   consider using the [`drive`] function for that purpose
+/// - the same holds for [`close`][Subscription::close]; once it
+///   resolved, the stream should be polled until exhausted
 #[derive(Debug)]
 pub struct Subscription<S> {
   /// Our internally used subscription object for sending control
@@ -599,17 +1061,70 @@ pub struct Subscription<S> {
   subscription: subscribe::Subscription<S, ParsedMessage, wrap::Message>,
   /// The currently active individual market data subscriptions.
   subscriptions: MarketData,
+  /// Counters tracking the messages received over the stream.
+  counters: Arc<Counters>,
+  /// The tap receiving raw messages arriving over the stream.
+  tap: Arc<Tap>,
+  /// A handle to the sink used by `subscription`, allowing us to close
+  /// the connection.
+  sink: S,
 }
 
 impl<S> Subscription<S> {
   /// Create a `Subscription` object wrapping the `websocket_util` based one.
   #[inline]
-  fn new(subscription: subscribe::Subscription<S, ParsedMessage, wrap::Message>) -> Self {
+  fn new(
+    subscription: subscribe::Subscription<S, ParsedMessage, wrap::Message>,
+    sink: S,
+    counters: Arc<Counters>,
+    tap: Arc<Tap>,
+  ) -> Self {
     Self {
       subscription,
       subscriptions: MarketData::default(),
+      counters,
+      tap,
+      sink,
     }
   }
+
+  /// Retrieve statistics about the messages received over the
+  /// associated stream so far.
+  #[inline]
+  pub fn stats(&self) -> Stats {
+    self.counters.snapshot()
+  }
+
+  /// Set the offset to add to the local time when computing the
+  /// latencies reported as part of [`Stats`], correcting for skew
+  /// between the local clock and Alpaca's.
+  ///
+  /// The offset can be measured using
+  /// [`clock::measure_skew`][crate::api::v2::clock::measure_skew].
+  #[inline]
+  pub fn set_clock_offset(&self, offset: Duration) {
+    self.counters.set_clock_offset(offset)
+  }
+
+  /// Install a tap receiving the raw bytes of each message arriving
+  /// over the associated stream from now on, e.g., for archiving the
+  /// untouched feed.
+  ///
+  /// Depending on `mode`, decoded market data continues to be
+  /// forwarded through the stream or not. A previously installed tap
+  /// is replaced.
+  pub fn set_raw_tap<F>(&self, mode: TapMode, tap: F)
+  where
+    F: FnMut(&[u8]) + Send + 'static,
+  {
+    self.tap.set(mode, Box::new(tap))
+  }
+
+  /// Remove the currently installed raw message tap, if any.
+  #[inline]
+  pub fn clear_raw_tap(&self) {
+    self.tap.clear()
+  }
 }
 
 impl<S> Subscription<S>
@@ -639,13 +1154,7 @@ where
         Ok(ControlMessage::Subscription(..)) => Ok(Err(Error::Str(
           "server responded with unexpected subscription message".into(),
         ))),
-        Ok(ControlMessage::Error(error)) => Ok(Err(Error::Str(
-          format!(
-            "failed to authenticate with server: {} ({})",
-            error.message, error.code
-          )
-          .into(),
-        ))),
+        Ok(ControlMessage::Error(error)) => Ok(Err(Error::Stream(error.into()))),
         Err(()) => Ok(Err(Error::Str("failed to authenticate with server".into()))),
       },
       None => Ok(Err(Error::Str(
@@ -672,9 +1181,7 @@ where
           self.subscriptions = data;
           Ok(Ok(()))
         },
-        Ok(ControlMessage::Error(error)) => Ok(Err(Error::Str(
-          format!("failed to subscribe: {}", error).into(),
-        ))),
+        Ok(ControlMessage::Error(error)) => Ok(Err(Error::Stream(error.into()))),
         Ok(_) => Ok(Err(Error::Str(
           "server responded with unexpected message".into(),
         ))),
@@ -717,10 +1224,35 @@ where
   }
 }
 
+impl<S> Subscription<SharedSink<S>>
+where
+  S: Sink<wrap::Message> + Unpin,
+{
+  /// Gracefully shut down the connection.
+  ///
+  /// All active market data subscriptions are canceled before the
+  /// websocket is closed, sending a close frame to the server. The
+  /// associated stream will be exhausted once the server acknowledged
+  /// the close.
+  pub async fn close(&mut self) -> Result<Result<(), Error>, S::Error> {
+    if self.subscriptions != MarketData::default() {
+      let subscriptions = self.subscriptions.clone();
+      if let Err(err) = self.unsubscribe(&subscriptions).await? {
+        return Ok(Err(err))
+      }
+    }
+    self.sink.close().await.map(Ok)
+  }
+}
+
 
-type ParseFn = fn(
-  Result<wrap::Message, WebSocketError>,
-) -> Result<Result<Vec<DataMessage>, JsonError>, WebSocketError>;
+type ParseFn = Box<
+  dyn FnMut(
+      Result<wrap::Message, WebSocketError>,
+    ) -> Result<Result<Vec<DataMessage>, JsonError>, WebSocketError>
+    + Send
+    + Sync,
+>;
 type MapFn = fn(Result<Result<DataMessage, JsonError>, WebSocketError>) -> ParsedMessage;
 type Stream = Map<
   Unfold<Map<Wrapper<WebSocketStream<MaybeTlsStream<TcpStream>>>, ParseFn>, DataMessage, JsonError>,
@@ -742,16 +1274,69 @@ where
   S: Source,
 {
   type Input = ApiInfo;
-  type Subscription = Subscription<SplitSink<Stream, wrap::Message>>;
+  type Subscription = Subscription<SharedSink<SplitSink<Stream, wrap::Message>>>;
   type Stream = Fuse<MessageStream<SplitStream<Stream>, ParsedMessage>>;
 
+  #[allow(clippy::result_large_err)]
   async fn connect(api_info: &Self::Input) -> Result<(Self::Stream, Self::Subscription), Error> {
     fn parse(
       result: Result<wrap::Message, WebSocketError>,
+      source: &'static str,
+      counters: &Counters,
+      tap: &Tap,
     ) -> Result<Result<Vec<DataMessage>, JsonError>, WebSocketError> {
-      result.map(|message| match message {
-        wrap::Message::Text(string) => json_from_str::<Vec<DataMessage>>(&string),
-        wrap::Message::Binary(data) => json_from_slice::<Vec<DataMessage>>(&data),
+      result.map(|message| {
+        let (result, forward) = match message {
+          wrap::Message::Text(string) => {
+            counters.add_bytes(string.len());
+            let forward = tap.report(string.as_bytes());
+            (json_from_str::<Vec<DataMessage>>(&string), forward)
+          },
+          wrap::Message::Binary(data) => {
+            counters.add_bytes(data.len());
+            let forward = tap.report(&data);
+            (json_from_slice::<Vec<DataMessage>>(&data), forward)
+          },
+        };
+
+        match &result {
+          Ok(messages) => messages.iter().for_each(|message| match message {
+            DataMessage::Bar(..) => counters.add_bar(),
+            DataMessage::Quote(quote) => counters.add_quote(&quote.timestamp),
+            DataMessage::Trade(trade) => counters.add_trade(&trade.timestamp),
+            DataMessage::TradeCorrection(..)
+            | DataMessage::TradeCancel(..)
+            | DataMessage::TradingStatus(..)
+            | DataMessage::Luld(..)
+            | DataMessage::Imbalance(..) => counters.add_other(),
+            DataMessage::Subscription(..) | DataMessage::Success | DataMessage::Error(..) => {
+              counters.add_control()
+            },
+          }),
+          Err(..) => counters.add_decode_error(),
+        }
+
+        if let Ok(messages) = &result {
+          messages
+            .iter()
+            .for_each(|message| metric::stream_messages(source, message_kind(message), 1))
+        }
+
+        if forward {
+          result
+        } else {
+          // Control messages are still needed for managing the
+          // subscription.
+          result.map(|mut messages| {
+            messages.retain(|message| {
+              matches!(
+                message,
+                DataMessage::Subscription(..) | DataMessage::Success | DataMessage::Error(..)
+              )
+            });
+            messages
+          })
+        }
       })
     }
 
@@ -765,12 +1350,27 @@ where
     let mut url = url.clone();
     url.set_path(&format!("v2/{}", S::as_str()));
 
-    let stream =
-      Unfold::new(connect(&url).await?.map(parse as ParseFn)).map(MessageResult::from as MapFn);
+    let connection = Handle::acquire(key_id, &url)?;
+
+    let counters = Arc::new(Counters::default());
+    let tap = Arc::new(Tap::default());
+    let parse = {
+      let counters = Arc::clone(&counters);
+      let tap = Arc::clone(&tap);
+      // The parse function lives as long as the connection does and so
+      // it owns the handle registering the connection as open.
+      Box::new(move |result| {
+        let _connection = &connection;
+        parse(result, S::as_str(), &counters, &tap)
+      }) as ParseFn
+    };
+
+    let stream = Unfold::new(connect(&url).await?.map(parse)).map(MessageResult::from as MapFn);
     let (send, recv) = stream.split();
-    let (stream, subscription) = subscribe::subscribe(recv, send);
+    let send = SharedSink::new(send);
+    let (stream, subscription) = subscribe::subscribe(recv, send.clone());
     let mut stream = stream.fuse();
-    let mut subscription = Subscription::new(subscription);
+    let mut subscription = Subscription::new(subscription, send, counters, tap);
 
     let connect = subscription.subscription.read().boxed().fuse();
     let message = drive(connect, &mut stream).await.map_err(|result| {
@@ -813,6 +1413,7 @@ mod tests {
   use super::*;
 
   use std::str::FromStr;
+  use std::sync::Mutex;
   use std::time::Duration;
 
   use chrono::DateTime;
@@ -820,6 +1421,9 @@ mod tests {
   use futures::SinkExt as _;
   use futures::TryStreamExt as _;
 
+  // `serial` drops `ignore` attributes, so we only serialize tests when
+  // they are actually run.
+  #[cfg(feature = "live-test")]
   use serial_test::serial;
 
   use serde_json::from_str as json_from_str;
@@ -832,6 +1436,7 @@ mod tests {
   use websocket_util::tungstenite::Message;
 
   use crate::api::API_BASE_URL;
+  use crate::live;
   use crate::websocket::test::mock_stream;
   use crate::Client;
 
@@ -973,6 +1578,161 @@ mod tests {
     );
   }
 
+  /// Check that we can serialize and deserialize a
+  /// [`TradeCorrection`].
+  #[test]
+  fn serialize_deserialize_trade_correction() {
+    let json = r#"{
+  "T": "c",
+  "S": "EEM",
+  "x": "M",
+  "oi": 52983525033527,
+  "op": 39.1582,
+  "os": 440000,
+  "oc": [" ", "7", "V"],
+  "ci": 52983525034326,
+  "cp": 39.1809,
+  "cs": 440000,
+  "cc": [" ", "7", "V"],
+  "t": "2021-02-22T16:44:41.452Z",
+  "z": "B"
+}"#;
+
+    let message = json_from_str::<DataMessage>(json).unwrap();
+    let correction = match &message {
+      DataMessage::TradeCorrection(correction) => correction,
+      _ => panic!("Decoded unexpected message variant: {:?}", message),
+    };
+    assert_eq!(correction.symbol, "EEM");
+    assert_eq!(correction.original_trade_id, 52983525033527);
+    assert_eq!(correction.original_trade_price, Num::new(391582, 10000));
+    assert_eq!(correction.corrected_trade_id, 52983525034326);
+    assert_eq!(correction.corrected_trade_price, Num::new(391809, 10000));
+    assert_eq!(correction.corrected_trade_conditions, vec![' ', '7', 'V']);
+
+    assert_eq!(
+      json_from_str::<DataMessage>(&to_json(&message).unwrap()).unwrap(),
+      message
+    );
+  }
+
+  /// Check that we can serialize and deserialize a [`TradeCancel`].
+  #[test]
+  fn serialize_deserialize_trade_cancel() {
+    let json = r#"{
+  "T": "x",
+  "S": "AAPL",
+  "i": 354,
+  "x": "V",
+  "p": 127.84,
+  "s": 100,
+  "a": "C",
+  "t": "2021-02-22T16:44:41.452Z",
+  "z": "C"
+}"#;
+
+    let message = json_from_str::<DataMessage>(json).unwrap();
+    let cancel = match &message {
+      DataMessage::TradeCancel(cancel) => cancel,
+      _ => panic!("Decoded unexpected message variant: {:?}", message),
+    };
+    assert_eq!(cancel.symbol, "AAPL");
+    assert_eq!(cancel.trade_id, 354);
+    assert_eq!(cancel.trade_price, Num::new(12784, 100));
+    assert_eq!(cancel.action, CancelAction::Cancel);
+
+    assert_eq!(
+      json_from_str::<DataMessage>(&to_json(&message).unwrap()).unwrap(),
+      message
+    );
+  }
+
+  /// Check that we can serialize and deserialize a [`TradingStatus`].
+  #[test]
+  fn serialize_deserialize_trading_status() {
+    let json = r#"{
+  "T": "s",
+  "S": "AAPL",
+  "sc": "H",
+  "sm": "Trading Halt",
+  "rc": "T12",
+  "rm": "Trading Halted; For information requested by NASDAQ",
+  "t": "2021-02-22T16:44:41.452Z",
+  "z": "C"
+}"#;
+
+    let message = json_from_str::<DataMessage>(json).unwrap();
+    let status = match &message {
+      DataMessage::TradingStatus(status) => status,
+      _ => panic!("Decoded unexpected message variant: {:?}", message),
+    };
+    assert_eq!(status.symbol, "AAPL");
+    assert_eq!(status.status_code, "H");
+    assert_eq!(status.reason_code, "T12");
+    assert_eq!(status.tape, 'C');
+    assert!(status.is_halted());
+
+    assert_eq!(
+      json_from_str::<DataMessage>(&to_json(&message).unwrap()).unwrap(),
+      message
+    );
+  }
+
+  /// Check that we can serialize and deserialize a [`Luld`].
+  #[test]
+  fn serialize_deserialize_luld() {
+    let json = r#"{
+  "T": "l",
+  "S": "AAPL",
+  "u": 147.71,
+  "d": 133.65,
+  "i": "B",
+  "t": "2021-02-22T16:44:41.452Z",
+  "z": "C"
+}"#;
+
+    let message = json_from_str::<DataMessage>(json).unwrap();
+    let luld = match &message {
+      DataMessage::Luld(luld) => luld,
+      _ => panic!("Decoded unexpected message variant: {:?}", message),
+    };
+    assert_eq!(luld.symbol, "AAPL");
+    assert_eq!(luld.limit_up_price, Num::new(14771, 100));
+    assert_eq!(luld.limit_down_price, Num::new(13365, 100));
+    assert_eq!(luld.indicator, 'B');
+
+    assert_eq!(
+      json_from_str::<DataMessage>(&to_json(&message).unwrap()).unwrap(),
+      message
+    );
+  }
+
+  /// Check that we can serialize and deserialize an [`Imbalance`].
+  #[test]
+  fn serialize_deserialize_imbalance() {
+    let json = r#"{
+  "T": "i",
+  "S": "INAQU",
+  "p": 9.12,
+  "t": "2021-02-22T15:59:59.997Z",
+  "z": "C"
+}"#;
+
+    let message = json_from_str::<DataMessage>(json).unwrap();
+    let imbalance = match &message {
+      DataMessage::Imbalance(imbalance) => imbalance,
+      _ => panic!("Decoded unexpected message variant: {:?}", message),
+    };
+    assert_eq!(imbalance.symbol, "INAQU");
+    assert_eq!(imbalance.reference_price, Num::new(912, 100));
+    assert_eq!(imbalance.tape, 'C');
+
+    assert_eq!(
+      json_from_str::<DataMessage>(&to_json(&message).unwrap()).unwrap(),
+      message
+    );
+  }
+
   /// Check that we can serialize and deserialize the
   /// [`DataMessage::Success`] variant.
   #[test]
@@ -1025,6 +1785,36 @@ mod tests {
     );
   }
 
+  /// Check that we map stream API error codes to typed errors.
+  #[test]
+  fn map_stream_error_codes() {
+    let error = |code| StreamApiError {
+      code,
+      message: "message".to_string(),
+    };
+
+    for code in (400..=410).chain([500]) {
+      let stream_error = StreamError::from(error(code));
+      assert!(!matches!(stream_error, StreamError::Other(..)));
+      assert_eq!(stream_error.code(), code);
+    }
+
+    assert_eq!(StreamError::from(error(402)), StreamError::AuthFailed);
+    assert_eq!(
+      StreamError::from(error(406)),
+      StreamError::ConnectionLimitExceeded
+    );
+    assert_eq!(
+      StreamError::from(error(409)),
+      StreamError::InsufficientSubscription
+    );
+
+    let stream_error = StreamError::from(error(499));
+    assert_eq!(stream_error, StreamError::Other(error(499)));
+    assert_eq!(stream_error.code(), 499);
+    assert_eq!(stream_error.to_string(), "message (499)");
+  }
+
   /// Check that we can serialize and deserialize the
   /// [`Request::Authenticate`] variant properly.
   #[test]
@@ -1051,6 +1841,16 @@ mod tests {
     let expected = r#"{"action":"subscribe","bars":["AAPL","VOO"],"quotes":[],"trades":[]}"#;
     assert_eq!(json, expected);
     assert_eq!(json_from_str::<Request<'_>>(&json).unwrap(), request);
+
+    data.set_statuses(["AAPL"]);
+    data.set_lulds(["VOO"]);
+    data.set_imbalances(["SPY"]);
+    let request = Request::Subscribe(Cow::Borrowed(&data));
+
+    let json = to_json(&request).unwrap();
+    let expected = r#"{"action":"subscribe","bars":["AAPL","VOO"],"quotes":[],"trades":[],"statuses":["AAPL"],"lulds":["VOO"],"imbalances":["SPY"]}"#;
+    assert_eq!(json, expected);
+    assert_eq!(json_from_str::<Request<'_>>(&json).unwrap(), request);
   }
 
   /// Check that we can serialize and deserialize the
@@ -1144,6 +1944,167 @@ mod tests {
       .unwrap();
   }
 
+  /// Check that we can gracefully close a subscription.
+  #[test(tokio::test)]
+  async fn close_subscription() {
+    const UNSUB_REQ: &str =
+      r#"{"action":"unsubscribe","bars":["AAPL","VOO"],"quotes":[],"trades":[]}"#;
+    const UNSUB_RESP: &str = r#"[{"T":"subscription","bars":[]}]"#;
+
+    async fn test(mut stream: WebSocketStream) -> Result<(), WebSocketError> {
+      stream.send(Message::Text(CONN_RESP.to_string())).await?;
+      // Authentication.
+      assert_eq!(
+        stream.next().await.unwrap()?,
+        Message::Text(AUTH_REQ.to_string()),
+      );
+      stream.send(Message::Text(AUTH_RESP.to_string())).await?;
+
+      // Subscription.
+      assert_eq!(
+        stream.next().await.unwrap()?,
+        Message::Text(SUB_REQ.to_string()),
+      );
+      stream.send(Message::Text(SUB_RESP.to_string())).await?;
+
+      // Unsubscription as part of closing.
+      assert_eq!(
+        stream.next().await.unwrap()?,
+        Message::Text(UNSUB_REQ.to_string()),
+      );
+      stream.send(Message::Text(UNSUB_RESP.to_string())).await?;
+
+      assert!(matches!(stream.next().await.unwrap()?, Message::Close(..)));
+      // Drive the stream to complete the closing handshake.
+      assert!(stream.next().await.is_none());
+      Ok(())
+    }
+
+    let (mut stream, mut subscription) =
+      mock_stream::<RealtimeData<IEX>, _, _>(test).await.unwrap();
+
+    let mut data = MarketData::default();
+    data.set_bars(["AAPL", "VOO"]);
+
+    let subscribe = subscription.subscribe(&data).boxed_local().fuse();
+    let () = drive(subscribe, &mut stream)
+      .await
+      .unwrap()
+      .unwrap()
+      .unwrap();
+
+    let close = subscription.close().boxed_local().fuse();
+    let () = drive(close, &mut stream).await.unwrap().unwrap().unwrap();
+    assert_eq!(subscription.subscriptions(), &MarketData::default());
+
+    let result = timeout(Duration::from_secs(5), stream.next()).await;
+    assert!(matches!(result, Ok(None)), "{:?}", result);
+  }
+
+  /// Check that we keep statistics about the messages received.
+  #[test(tokio::test)]
+  async fn track_stats() {
+    const BAR: &str = r#"[{"T":"b","S":"AAPL","o":1,"h":2,"l":0.5,"c":1.5,"v":10,"t":"2021-02-22T19:15:00Z"}]"#;
+
+    async fn test(mut stream: WebSocketStream) -> Result<(), WebSocketError> {
+      stream.send(Message::Text(CONN_RESP.to_string())).await?;
+      let _ = stream.next().await.unwrap()?;
+      stream.send(Message::Text(AUTH_RESP.to_string())).await?;
+      let _ = stream.next().await.unwrap()?;
+      stream.send(Message::Text(SUB_RESP.to_string())).await?;
+      stream.send(Message::Text(BAR.to_string())).await?;
+      stream.send(Message::Text("[{".to_string())).await?;
+      stream.send(Message::Close(None)).await?;
+      Ok(())
+    }
+
+    let (mut stream, mut subscription) =
+      mock_stream::<RealtimeData<IEX>, _, _>(test).await.unwrap();
+
+    let mut data = MarketData::default();
+    data.set_bars(["AAPL", "VOO"]);
+
+    let subscribe = subscription.subscribe(&data).boxed_local().fuse();
+    let () = drive(subscribe, &mut stream)
+      .await
+      .unwrap()
+      .unwrap()
+      .unwrap();
+
+    while stream.next().await.is_some() {}
+
+    let stats = subscription.stats();
+    assert_eq!(stats.bars, 1);
+    assert_eq!(stats.quotes, 0);
+    assert_eq!(stats.trades, 0);
+    assert_eq!(stats.control, 3);
+    assert_eq!(stats.decode_errors, 1);
+    assert_eq!(
+      stats.bytes,
+      (CONN_RESP.len() + AUTH_RESP.len() + SUB_RESP.len() + BAR.len() + 2) as u64
+    );
+    assert_eq!(stats.last_latency, None);
+  }
+
+  /// Check that raw messages are reported to an installed tap.
+  #[test(tokio::test)]
+  async fn tap_raw_messages() {
+    const BAR: &str = r#"[{"T":"b","S":"AAPL","o":1,"h":2,"l":0.5,"c":1.5,"v":10,"t":"2021-02-22T19:15:00Z"}]"#;
+
+    async fn test(mut stream: WebSocketStream) -> Result<(), WebSocketError> {
+      stream.send(Message::Text(CONN_RESP.to_string())).await?;
+      let _ = stream.next().await.unwrap()?;
+      stream.send(Message::Text(AUTH_RESP.to_string())).await?;
+      let _ = stream.next().await.unwrap()?;
+      stream.send(Message::Text(SUB_RESP.to_string())).await?;
+      stream.send(Message::Text(BAR.to_string())).await?;
+      stream.send(Message::Close(None)).await?;
+      Ok(())
+    }
+
+    async fn tap(mode: TapMode) -> (Vec<Vec<u8>>, usize) {
+      let (mut stream, mut subscription) =
+        mock_stream::<RealtimeData<IEX>, _, _>(test).await.unwrap();
+
+      let raw = Arc::new(Mutex::new(Vec::new()));
+      let () = {
+        let raw = Arc::clone(&raw);
+        subscription.set_raw_tap(mode, move |message| {
+          raw.lock().unwrap().push(message.to_vec())
+        })
+      };
+
+      let mut data = MarketData::default();
+      data.set_bars(["AAPL", "VOO"]);
+
+      // Only poll the subscription future once to send the request and
+      // then read the stream directly; `drive` would swallow the bar.
+      let mut subscribe = subscription.subscribe(&data).boxed_local();
+      assert!((&mut subscribe).now_or_never().is_none());
+
+      let mut count = 0;
+      while let Some(result) = stream.next().await {
+        let _data = result.unwrap().unwrap();
+        count += 1;
+      }
+
+      let () = subscribe.await.unwrap().unwrap();
+      subscription.clear_raw_tap();
+      let raw = raw.lock().unwrap().clone();
+      (raw, count)
+    }
+
+    let expected = vec![SUB_RESP.as_bytes().to_vec(), BAR.as_bytes().to_vec()];
+
+    let (raw, count) = tap(TapMode::Alongside).await;
+    assert_eq!(raw, expected);
+    assert_eq!(count, 1);
+
+    let (raw, count) = tap(TapMode::Instead).await;
+    assert_eq!(raw, expected);
+    assert_eq!(count, 0);
+  }
+
   /// Check that we correctly handle errors reported as part of
   /// subscription.
   #[test(tokio::test)]
@@ -1180,7 +2141,7 @@ mod tests {
       .unwrap_err();
 
     match error {
-      Error::Str(ref e) if e == "failed to subscribe: invalid syntax (400)" => {},
+      Error::Stream(StreamError::InvalidSyntax) => {},
       e => panic!("received unexpected error: {}", e),
     }
   }
@@ -1188,10 +2149,10 @@ mod tests {
   /// Check that we can adjust the current market data subscription on
   /// the fly.
   #[test(tokio::test)]
-  #[serial(realtime_data)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  #[cfg_attr(feature = "live-test", serial(realtime_data))]
   async fn subscribe_resubscribe() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let (mut stream, mut subscription) = client.subscribe::<RealtimeData<IEX>>().await.unwrap();
 
     let mut data = MarketData::default();
@@ -1226,10 +2187,10 @@ mod tests {
   /// open or not and as such we can only try on a best-effort basis to
   /// receive and decode updates.
   #[test(tokio::test)]
-  #[serial(realtime_data)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  #[cfg_attr(feature = "live-test", serial(realtime_data))]
   async fn stream_market_data_updates() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let (mut stream, mut subscription) = client.subscribe::<RealtimeData<IEX>>().await.unwrap();
 
     let data = MarketData {
@@ -1267,10 +2228,10 @@ mod tests {
   /// open or not and as such we can only try on a best-effort basis to
   /// receive and decode updates.
   #[test(tokio::test)]
-  #[serial(realtime_data)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  #[cfg_attr(feature = "live-test", serial(realtime_data))]
   async fn stream_quotes() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let (mut stream, mut subscription) = client.subscribe::<RealtimeData<IEX>>().await.unwrap();
 
     let mut data = MarketData::default();
@@ -1300,10 +2261,10 @@ mod tests {
 
   /// Check that we can stream realtime stock trades.
   #[test(tokio::test)]
-  #[serial(realtime_data)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  #[cfg_attr(feature = "live-test", serial(realtime_data))]
   async fn stream_trades() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let (mut stream, mut subscription) = client.subscribe::<RealtimeData<IEX>>().await.unwrap();
 
     let mut data = MarketData::default();
@@ -1331,13 +2292,45 @@ mod tests {
     }
   }
 
+  /// Check that we can stream synthetic trades from the test stream,
+  /// irrespective of whether the market is open or not.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  #[cfg_attr(feature = "live-test", serial(realtime_data))]
+  async fn stream_test_trades() {
+    let client = live::client().unwrap();
+    let (mut stream, mut subscription) = client.subscribe::<RealtimeData<Test>>().await.unwrap();
+
+    let mut data = MarketData::default();
+    data.set_trades([Test::SYMBOL]);
+
+    let subscribe = subscription.subscribe(&data).boxed_local().fuse();
+    let () = drive(subscribe, &mut stream)
+      .await
+      .unwrap()
+      .unwrap()
+      .unwrap();
+
+    let trade = timeout(Duration::from_secs(30), stream.next())
+      .await
+      .unwrap()
+      .unwrap()
+      .unwrap()
+      .unwrap();
+
+    match trade {
+      Data::Trade(trade) => assert_eq!(trade.symbol, Test::SYMBOL),
+      _ => panic!("received unexpected data: {:?}", trade),
+    }
+  }
+
   /// Check that the Alpaca API reports no error when unsubscribing
   /// from a symbol not currently subscribed to.
   #[test(tokio::test)]
-  #[serial(realtime_data)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  #[cfg_attr(feature = "live-test", serial(realtime_data))]
   async fn unsubscribe_not_subscribed_symbol() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let (mut stream, mut subscription) = client.subscribe::<RealtimeData<IEX>>().await.unwrap();
 
     let mut data = MarketData::default();
@@ -1356,14 +2349,15 @@ mod tests {
   /// Test that we fail as expected when attempting to authenticate for
   /// real time market updates using invalid credentials.
   #[test(tokio::test)]
-  #[serial(realtime_data)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  #[cfg_attr(feature = "live-test", serial(realtime_data))]
   async fn stream_with_invalid_credentials() {
     let api_info = ApiInfo::from_parts(API_BASE_URL, "invalid", "invalid-too").unwrap();
     let client = Client::new(api_info);
     let err = client.subscribe::<RealtimeData<IEX>>().await.unwrap_err();
 
     match err {
-      Error::Str(ref e) if e.starts_with("failed to authenticate with server") => (),
+      Error::Stream(StreamError::AuthFailed) => (),
       e => panic!("received unexpected error: {}", e),
     }
   }
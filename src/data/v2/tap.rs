@@ -0,0 +1,140 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+use std::sync::Mutex;
+
+
+/// The type of function receiving raw websocket messages.
+type TapFn = Box<dyn FnMut(&[u8]) + Send>;
+
+
+/// The mode in which a raw message tap operates.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum TapMode {
+  /// Raw messages are reported to the tap in addition to market data
+  /// being decoded and forwarded through the stream as usual.
+  Alongside,
+  /// Raw messages are reported to the tap instead of market data being
+  /// forwarded through the stream.
+  ///
+  /// Control messages are still decoded and handled, so that the
+  /// subscription continues to work as usual.
+  Instead,
+}
+
+
+/// The state of a [`Tap`].
+#[derive(Default)]
+struct State {
+  /// The currently installed tap function, if any, along with the mode
+  /// it operates in.
+  tap: Option<(TapMode, TapFn)>,
+  /// A counter incremented whenever a tap function is installed or
+  /// removed.
+  generation: u64,
+}
+
+
+/// A tap receiving the raw messages arriving over a realtime market
+/// data stream, before they are decoded.
+#[derive(Default)]
+pub(crate) struct Tap {
+  /// The state of the tap.
+  state: Mutex<State>,
+}
+
+impl Tap {
+  /// Install a tap function, replacing any previously installed one.
+  pub(crate) fn set(&self, mode: TapMode, tap: TapFn) {
+    let mut state = self.state.lock().unwrap();
+    state.tap = Some((mode, tap));
+    state.generation += 1;
+  }
+
+  /// Remove the currently installed tap function, if any.
+  pub(crate) fn clear(&self) {
+    let mut state = self.state.lock().unwrap();
+    state.tap = None;
+    state.generation += 1;
+  }
+
+  /// Report a raw message to the installed tap function, if any.
+  ///
+  /// The return value indicates whether decoded market data should be
+  /// forwarded through the stream.
+  pub(crate) fn report(&self, message: &[u8]) -> bool {
+    // Invoke the tap function without holding the lock, so that it can
+    // install or remove a tap itself.
+    let (generation, tap) = {
+      let mut state = self.state.lock().unwrap();
+      (state.generation, state.tap.take())
+    };
+
+    match tap {
+      Some((mode, mut tap)) => {
+        let () = tap(message);
+
+        let mut state = self.state.lock().unwrap();
+        // Only put the function back if it was not replaced or removed
+        // in the meantime.
+        if state.generation == generation {
+          state.tap = Some((mode, tap));
+        }
+        mode == TapMode::Alongside
+      },
+      None => true,
+    }
+  }
+}
+
+impl Debug for Tap {
+  fn fmt(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+    let state = self.state.lock().unwrap();
+    let mode = state.tap.as_ref().map(|(mode, _)| *mode);
+    fmt.debug_struct("Tap").field("mode", &mode).finish()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::Arc;
+  use std::sync::Weak;
+
+  use test_log::test;
+
+
+  /// Check that a tap function can install and remove taps on the tap
+  /// invoking it.
+  #[test]
+  fn reenter_tap() {
+    let tap = Arc::new(Tap::default());
+    let weak = Arc::downgrade(&tap);
+    let () = tap.set(
+      TapMode::Instead,
+      Box::new(move |_| {
+        let tap = Weak::upgrade(&weak).unwrap();
+        let () = tap.set(TapMode::Alongside, Box::new(|_| ()));
+      }),
+    );
+
+    assert!(!tap.report(b"message"));
+    assert!(tap.report(b"anything"));
+    assert!(format!("{:?}", tap).contains("Alongside"));
+
+    let weak = Arc::downgrade(&tap);
+    let () = tap.set(
+      TapMode::Instead,
+      Box::new(move |_| Weak::upgrade(&weak).unwrap().clear()),
+    );
+    assert!(!tap.report(b"message"));
+    assert!(tap.report(b"anything"));
+    assert!(format!("{:?}", tap).contains("None"));
+  }
+}
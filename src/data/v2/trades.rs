@@ -12,7 +12,9 @@ use serde_urlencoded::to_string as to_query;
 
 use crate::data::v2::Feed;
 use crate::data::DATA_BASE_URL;
+use crate::util::datetime_from_str;
 use crate::util::vec_from_str;
+use crate::Paginated;
 use crate::Str;
 
 /// The symbol.
@@ -89,7 +91,7 @@ impl TradesReqInit {
 #[non_exhaustive]
 pub struct Trade {
   /// Timestamp in RFC-3339 format with nanosecond precision.
-  #[serde(rename = "t")]
+  #[serde(rename = "t", deserialize_with = "datetime_from_str")]
   pub timestamp: DateTime<Utc>,
   /// The exchange where the trade happened.
   /// Alpaca internal code described in
@@ -127,7 +129,7 @@ pub struct Trades {
   pub next_page_token: Option<String>,
 }
 
-Endpoint! {
+DataEndpoint! {
   /// The representation of a GET request to the /v2/stocks/<symbol>/trades endpoint.
   pub Get(TradesReq),
   Ok => Trades, [
@@ -152,6 +154,23 @@ Endpoint! {
   }
 }
 
+/// The next page of trades is retrieved using the token reported along
+/// with the current one.
+impl Paginated for Get {
+  type Item = Trade;
+  type State = ();
+
+  fn paginate(
+    mut input: Self::Input,
+    output: Self::Output,
+    _state: &mut Self::State,
+  ) -> (Vec<Self::Item>, Option<Self::Input>) {
+    input.page_token = output.next_page_token;
+    let next = input.page_token.is_some().then_some(input);
+    (output.trades, next)
+  }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -165,8 +184,7 @@ mod tests {
 
   use test_log::test;
 
-  use crate::api_info::ApiInfo;
-  use crate::Client;
+  use crate::live;
   use crate::RequestError;
 
 
@@ -213,9 +231,9 @@ mod tests {
 
   /// Check that we can decode a response containing no trades correctly.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn no_trades() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let start = DateTime::from_str("2021-11-05T00:00:00Z").unwrap();
     let end = DateTime::from_str("2021-11-05T00:00:00Z").unwrap();
     let request = TradesReqInit::default().init("AAPL", start, end);
@@ -226,9 +244,9 @@ mod tests {
 
   /// Check that we can request historic trade data for a stock.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn request_trades() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let start = DateTime::from_str("2018-12-03T21:47:00Z").unwrap();
     let end = DateTime::from_str("2018-12-06T21:47:00Z").unwrap();
     let request = TradesReqInit {
@@ -257,9 +275,9 @@ mod tests {
 
   /// Verify that we can request data through a provided page token.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn can_follow_pagination() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
     let start = DateTime::from_str("2018-12-03T21:47:00Z").unwrap();
     let end = DateTime::from_str("2018-12-07T21:47:00Z").unwrap();
     let mut request = TradesReqInit {
@@ -285,9 +303,9 @@ mod tests {
   /// Check that we fail as expected when an invalid page token is
   /// specified.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn invalid_page_token() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let start = DateTime::from_str("2018-12-03T21:47:00Z").unwrap();
     let end = DateTime::from_str("2018-12-07T21:47:00Z").unwrap();
@@ -307,9 +325,9 @@ mod tests {
   /// Verify that we error out as expected when attempting to retrieve
   /// aggregate data trades for a non-existent symbol.
   #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
   async fn nonexistent_symbol() {
-    let api_info = ApiInfo::from_env().unwrap();
-    let client = Client::new(api_info);
+    let client = live::client().unwrap();
 
     let start = DateTime::from_str("2022-02-01T00:00:00Z").unwrap();
     let end = DateTime::from_str("2022-02-20T00:00:00Z").unwrap();
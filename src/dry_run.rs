@@ -0,0 +1,445 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+#[cfg(feature = "extra-fields")]
+use std::collections::BTreeMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use chrono::Utc;
+
+use http::Method;
+use http::StatusCode;
+
+use num_decimal::Num;
+
+use serde::Deserialize;
+use serde_json::from_slice as from_json;
+use serde_json::json;
+use serde_json::to_vec as to_json;
+use serde_json::Error as JsonError;
+use serde_json::Value;
+use serde_urlencoded::from_str as from_query;
+
+use uuid::Uuid;
+
+use crate::api::v2::asset;
+use crate::api::v2::order;
+use crate::api::v2::order::Amount;
+use crate::api::v2::order::Order;
+use crate::api::v2::order::OrderReq;
+use crate::api::v2::order::OrderReqInit;
+use crate::api::v2::position;
+use crate::api::v2::position::Position;
+use crate::api::v2::wallets::TransferReq;
+
+
+/// A function deriving the simulated response to a request from the
+/// body of the response to the GET request it was turned into and
+/// the request's query and body.
+pub(crate) type DeriveFn = fn(&[u8], Option<&str>, &[u8]) -> Result<Vec<u8>, JsonError>;
+
+
+/// The simulated handling of a mutating request.
+pub(crate) enum Simulation {
+  /// Respond with the given status code and body.
+  Respond(StatusCode, Vec<u8>),
+  /// Issue a GET request to the given path and query (or the original
+  /// one, if `None`) instead and derive the response from its outcome.
+  /// Errors are passed through as they are.
+  Derive(Option<String>, DeriveFn),
+  /// The request cannot be simulated and should be failed.
+  Unsupported,
+}
+
+
+/// Check whether the given method may change any state.
+pub(crate) fn is_mutating(method: &Method) -> bool {
+  !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+
+/// Create a new and unique ID, e.g., for an order.
+///
+/// IDs are derived from the current time and a counter, as we do not
+/// have a random number generator at our disposal.
+fn new_id() -> Uuid {
+  static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+  let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+  let now = Utc::now();
+  let nanos = now.timestamp() as u128 * 1_000_000_000 + u128::from(now.timestamp_subsec_nanos());
+  Uuid::from_u128((nanos << 64) | u128::from(count))
+}
+
+
+/// Create an order as accepted in response to the given request.
+fn accept(request: OrderReq, asset_class: asset::Class, asset_id: asset::Id) -> Order {
+  let id = order::Id(new_id());
+  let now = Utc::now();
+
+  Order {
+    id,
+    client_order_id: request
+      .client_order_id
+      .unwrap_or_else(|| id.as_hyphenated().to_string()),
+    status: order::Status::Accepted,
+    created_at: now,
+    updated_at: Some(now),
+    submitted_at: Some(now),
+    filled_at: None,
+    expired_at: None,
+    canceled_at: None,
+    asset_class,
+    asset_id,
+    symbol: request.symbol.to_string(),
+    amount: request.amount,
+    filled_quantity: Num::from(0),
+    type_: request.type_,
+    class: request.class,
+    side: request.side,
+    time_in_force: request.time_in_force,
+    limit_price: request.limit_price,
+    stop_price: request.stop_price,
+    trail_price: request.trail_price,
+    trail_percent: request.trail_percent,
+    average_fill_price: None,
+    extended_hours: request.extended_hours,
+    legs: Vec::new(),
+    replaced_by: None,
+    replaces: None,
+    position_intent: request.position_intent,
+    local_currency: None,
+    #[cfg(feature = "extra-fields")]
+    extra: BTreeMap::new(),
+  }
+}
+
+
+/// Simulate the acceptance of an order.
+fn submit(body: &[u8]) -> Result<Vec<u8>, JsonError> {
+  let request = from_json::<OrderReq>(body)?;
+  let asset_class = if request.symbol.to_string().contains('/') {
+    asset::Class::Crypto
+  } else {
+    asset::Class::UsEquity
+  };
+
+  let order = accept(request, asset_class, asset::Id(Uuid::nil()));
+  to_json(&order)
+}
+
+
+/// Simulate the closing of a position, by submitting a market order
+/// for (a part of) the position as currently reported.
+fn close(position: &[u8], query: Option<&str>, _body: &[u8]) -> Result<Vec<u8>, JsonError> {
+  #[derive(Default, Deserialize)]
+  struct CloseAmount {
+    #[serde(rename = "qty")]
+    quantity: Option<Num>,
+    #[serde(rename = "percentage")]
+    percentage: Option<Num>,
+  }
+
+  let position = from_json::<Position>(position)?;
+  let amount = match query {
+    Some(query) => from_query::<CloseAmount>(query).map_err(serde::de::Error::custom)?,
+    None => CloseAmount::default(),
+  };
+  let quantity = match amount {
+    CloseAmount {
+      quantity: Some(quantity),
+      ..
+    } => quantity,
+    CloseAmount {
+      percentage: Some(percentage),
+      ..
+    } => position.quantity * percentage / Num::from(100),
+    _ => position.quantity,
+  };
+  let side = match position.side {
+    position::Side::Long => order::Side::Sell,
+    position::Side::Short => order::Side::Buy,
+  };
+
+  let amount = Amount::quantity(quantity);
+  let request = OrderReqInit::default().init(position.symbol, side, amount);
+  let order = accept(request, position.asset_class, position.asset_id);
+  to_json(&order)
+}
+
+
+/// Simulate the request of a withdrawal.
+fn transfer(body: &[u8]) -> Result<Vec<u8>, JsonError> {
+  let request = from_json::<TransferReq>(body)?;
+  let transfer = json!({
+    "id": new_id(),
+    "tx_hash": null,
+    "direction": "OUTGOING",
+    "status": "PROCESSING",
+    "amount": request.amount,
+    "chain": "",
+    "asset": request.asset,
+    "from_address": null,
+    "to_address": request.address,
+    "created_at": Utc::now(),
+  });
+  to_json(&transfer)
+}
+
+
+/// Simulate the change of an order, by applying the change request to
+/// the order as currently reported.
+fn change(order: &[u8], _query: Option<&str>, body: &[u8]) -> Result<Vec<u8>, JsonError> {
+  let mut order = from_json::<Value>(order)?;
+  let request = from_json::<Value>(body)?;
+
+  if let (Value::Object(order), Value::Object(request)) = (&mut order, request) {
+    let trail = if matches!(order.get("trail_percent"), Some(value) if !value.is_null()) {
+      "trail_percent"
+    } else {
+      "trail_price"
+    };
+
+    for (key, value) in request {
+      if value.is_null() {
+        continue
+      }
+      let key = if key == "trail" {
+        trail.to_string()
+      } else {
+        key
+      };
+      let _prev = order.insert(key, value);
+    }
+    let _prev = order.insert("updated_at".to_string(), json!(Utc::now()));
+  }
+  to_json(&order)
+}
+
+
+/// Simulate the cancellation of all open orders.
+fn cancel_all(orders: &[u8], _query: Option<&str>, _body: &[u8]) -> Result<Vec<u8>, JsonError> {
+  let orders = from_json::<Vec<Value>>(orders)?;
+  let statuses = orders
+    .into_iter()
+    .map(|order| json!({"id": order["id"], "status": 200}))
+    .collect::<Vec<_>>();
+  to_json(&statuses)
+}
+
+
+/// Simulate the liquidation of all open positions.
+fn liquidate_all(
+  positions: &[u8],
+  _query: Option<&str>,
+  _body: &[u8],
+) -> Result<Vec<u8>, JsonError> {
+  let positions = from_json::<Vec<Value>>(positions)?;
+  let statuses = positions
+    .into_iter()
+    .map(|position| json!({"symbol": position["symbol"], "status": 200}))
+    .collect::<Vec<_>>();
+  to_json(&statuses)
+}
+
+
+/// Respond with the outcome of a simulation that is based solely on
+/// the request's body.
+fn respond(result: Result<Vec<u8>, JsonError>) -> Simulation {
+  match result {
+    Ok(body) => Simulation::Respond(StatusCode::OK, body),
+    Err(err) => {
+      let body = json!({"code": 42210000, "message": err.to_string()});
+      Simulation::Respond(StatusCode::UNPROCESSABLE_ENTITY, body.to_string().into())
+    },
+  }
+}
+
+
+/// Extract the last segment of the given path if it starts with the
+/// given prefix.
+fn segment<'p>(path: &'p str, prefix: &str) -> Option<&'p str> {
+  path
+    .strip_prefix(prefix)
+    .filter(|segment| !segment.is_empty() && !segment.contains('/'))
+}
+
+
+/// Determine how to simulate the request with the given method, path,
+/// and body.
+///
+/// Order submissions, changes, and cancellations, position closings
+/// and liquidations, withdrawals, and account configuration changes
+/// are simulated. All other mutating requests are not supported.
+pub(crate) fn simulate(method: &Method, path: &str, body: &[u8]) -> Simulation {
+  let order = segment(path, "/v2/orders/");
+  let position = segment(path, "/v2/positions/");
+
+  match (method, path) {
+    (&Method::POST, "/v2/orders") => respond(submit(body)),
+    (&Method::PATCH, _) if order.is_some() => Simulation::Derive(None, change),
+    (&Method::DELETE, _) if order.is_some() => {
+      Simulation::Respond(StatusCode::NO_CONTENT, Vec::new())
+    },
+    (&Method::DELETE, "/v2/orders") => {
+      let path = "/v2/orders?status=open&limit=500".to_string();
+      Simulation::Derive(Some(path), cancel_all)
+    },
+    (&Method::DELETE, "/v2/positions") => {
+      Simulation::Derive(Some("/v2/positions".to_string()), liquidate_all)
+    },
+    (&Method::DELETE, _) if position.is_some() => Simulation::Derive(Some(path.to_string()), close),
+    (&Method::POST, "/v2/wallets/transfers") => respond(transfer(body)),
+    // The account configuration is always updated as a whole, so the
+    // request is what would be reported back.
+    (&Method::PATCH, "/v2/account/configurations") => {
+      Simulation::Respond(StatusCode::OK, body.to_vec())
+    },
+    _ => Simulation::Unsupported,
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::from_slice;
+
+  use crate::api::v2::order::Side;
+  use crate::api::v2::orders::CancelStatus;
+  use crate::api::v2::wallets::Transfer;
+
+
+  /// Check that we simulate order submissions.
+  #[test]
+  fn simulate_submission() {
+    let request = OrderReqInit {
+      client_order_id: Some("my-order".to_string()),
+      ..Default::default()
+    }
+    .init("AAPL", Side::Buy, Amount::quantity(5));
+    let body = to_json(&request).unwrap();
+
+    let (status, body) = match simulate(&Method::POST, "/v2/orders", &body) {
+      Simulation::Respond(status, body) => (status, body),
+      Simulation::Derive(..) | Simulation::Unsupported => panic!("unexpected simulation"),
+    };
+    assert_eq!(status, StatusCode::OK);
+
+    let order = from_slice::<Order>(&body).unwrap();
+    assert_eq!(order.symbol, "AAPL");
+    assert_eq!(order.client_order_id, "my-order");
+    assert_eq!(order.status, order::Status::Accepted);
+    assert_eq!(order.amount, Amount::quantity(5));
+    assert_ne!(order.id.0, new_id());
+  }
+
+  /// Check that we simulate order changes by applying them to the
+  /// current order.
+  #[test]
+  fn simulate_change() {
+    let order = json!({"id": "1", "qty": "1", "limit_price": "10", "trail_percent": null});
+    let request = json!({"qty": "2", "limit_price": null, "trail": "5"});
+    let order = to_json(&order).unwrap();
+    let changed = change(&order, None, &to_json(&request).unwrap()).unwrap();
+    let changed = from_slice::<Value>(&changed).unwrap();
+
+    assert_eq!(changed["qty"], json!("2"));
+    assert_eq!(changed["limit_price"], json!("10"));
+    assert_eq!(changed["trail_price"], json!("5"));
+  }
+
+  /// Check that we simulate the cancellation of all orders.
+  #[test]
+  fn simulate_cancel_all() {
+    let path = match simulate(&Method::DELETE, "/v2/orders", b"") {
+      Simulation::Derive(path, _) => path,
+      Simulation::Respond(..) | Simulation::Unsupported => panic!("unexpected simulation"),
+    };
+    assert_eq!(path.as_deref(), Some("/v2/orders?status=open&limit=500"));
+
+    let orders = br#"[{"id":"904837e3-3b76-47ec-b432-046db621571b"}]"#;
+    let statuses = cancel_all(orders, None, b"").unwrap();
+    let statuses = from_slice::<Vec<CancelStatus>>(&statuses).unwrap();
+    assert_eq!(statuses.len(), 1);
+    assert_eq!(statuses[0].status, 200);
+  }
+
+  /// Check that we simulate the closing of a position by submitting an
+  /// order for the requested amount of it.
+  #[test]
+  fn simulate_close() {
+    let path = match simulate(&Method::DELETE, "/v2/positions/AAPL", b"") {
+      Simulation::Derive(path, _) => path,
+      Simulation::Respond(..) | Simulation::Unsupported => panic!("unexpected simulation"),
+    };
+    assert_eq!(path.as_deref(), Some("/v2/positions/AAPL"));
+
+    let position = json!({
+      "asset_id": "b0b6dd9d-8b9b-48a9-ba46-b9d54906e415",
+      "symbol": "AAPL",
+      "exchange": "NASDAQ",
+      "asset_class": "us_equity",
+      "avg_entry_price": "100",
+      "qty": "-8",
+      "side": "short",
+      "market_value": "-800",
+      "cost_basis": "-800",
+      "unrealized_pl": "0",
+      "unrealized_plpc": "0",
+      "unrealized_intraday_pl": "0",
+      "unrealized_intraday_plpc": "0",
+      "current_price": "100",
+      "lastday_price": "100",
+      "change_today": "0",
+    });
+    let position = to_json(&position).unwrap();
+
+    let order = close(&position, None, b"").unwrap();
+    let order = from_slice::<Order>(&order).unwrap();
+    assert_eq!(order.symbol, "AAPL");
+    assert_eq!(order.side, Side::Buy);
+    assert_eq!(order.amount, Amount::quantity(8));
+    assert_eq!(order.type_, order::Type::Market);
+
+    let order = close(&position, Some("percentage=25"), b"").unwrap();
+    let order = from_slice::<Order>(&order).unwrap();
+    assert_eq!(order.amount, Amount::quantity(2));
+  }
+
+  /// Check that we simulate withdrawals and account configuration
+  /// changes based on the request, and refuse to simulate anything
+  /// else.
+  #[test]
+  fn simulate_other() {
+    let request = json!({"amount": "0.5", "address": "0x42", "asset": "ETH"});
+    let body = to_json(&request).unwrap();
+    let body = match simulate(&Method::POST, "/v2/wallets/transfers", &body) {
+      Simulation::Respond(StatusCode::OK, body) => body,
+      _ => panic!("unexpected simulation"),
+    };
+    let transfer = from_slice::<Transfer>(&body).unwrap();
+    assert_eq!(transfer.amount, Num::new(5, 10));
+    assert_eq!(transfer.asset, "ETH");
+    assert_eq!(transfer.to_address.as_deref(), Some("0x42"));
+
+    let body = br#"{"no_shorting":true}"#;
+    let simulation = simulate(&Method::PATCH, "/v2/account/configurations", body);
+    assert!(matches!(&simulation, Simulation::Respond(StatusCode::OK, echo) if echo == body));
+
+    let simulation = simulate(&Method::POST, "/v2/watchlists", b"{}");
+    assert!(matches!(simulation, Simulation::Unsupported));
+  }
+
+  /// Check that we only treat methods that may change state as
+  /// mutating.
+  #[test]
+  fn mutating_methods() {
+    assert!(!is_mutating(&Method::GET));
+    assert!(is_mutating(&Method::POST));
+    assert!(is_mutating(&Method::PATCH));
+    assert!(is_mutating(&Method::DELETE));
+  }
+}
@@ -1,6 +1,7 @@
 // Copyright (C) 2019-2022 The apca Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use serde_json::Error as JsonError;
 use serde_urlencoded::ser::Error as UrlEncodeError;
@@ -15,6 +16,63 @@ pub enum ConversionError {
   /// A variant used when we fail to URL-encode a piece of data.
   #[error("failed to URL-encode data")]
   UrlEncode(#[from] UrlEncodeError),
+  /// A variant used when a JSON conversion using `simd-json` failed.
+  #[cfg(feature = "simd-json")]
+  #[error("failed to convert from JSON")]
+  SimdJson(#[from] simd_json::Error),
+}
+
+
+/// Substitute JSON `null` for an empty response body.
+///
+/// Endpoints responding with an empty body (e.g., with "204 No
+/// Content") can thereby use `()` or an `Option` as their output type,
+/// without requiring custom parsing logic.
+fn or_null(body: &[u8]) -> &[u8] {
+  if body.iter().all(u8::is_ascii_whitespace) {
+    b"null"
+  } else {
+    body
+  }
+}
+
+
+/// Parse a JSON response body into an object of the given type using
+/// `serde_json`.
+///
+/// An empty body is treated as JSON `null`.
+pub(crate) fn parse_body<T>(body: &[u8]) -> Result<T, JsonError>
+where
+  T: DeserializeOwned,
+{
+  serde_json::from_slice::<T>(or_null(body))
+}
+
+
+/// Parse a JSON response body into an object of the given type.
+///
+/// If the `simd-json` feature is enabled, parsing happens using
+/// `simd-json`. Otherwise `serde_json` is used. An empty body is
+/// treated as JSON `null`.
+#[cfg(feature = "simd-json")]
+pub(crate) fn parse_json<T>(body: &[u8]) -> Result<T, ConversionError>
+where
+  T: DeserializeOwned,
+{
+  // `simd-json` parses in-place and so it requires a mutable buffer.
+  let mut body = or_null(body).to_vec();
+  simd_json::serde::from_slice::<T>(&mut body).map_err(ConversionError::from)
+}
+
+/// Parse a JSON response body into an object of the given type.
+///
+/// An empty body is treated as JSON `null`.
+#[cfg(not(feature = "simd-json"))]
+pub(crate) fn parse_json<T>(body: &[u8]) -> Result<T, ConversionError>
+where
+  T: DeserializeOwned,
+{
+  parse_body::<T>(body).map_err(ConversionError::from)
 }
 
 use thiserror::Error;
@@ -71,13 +129,18 @@ macro_rules! EndpointNoParse {
 
 /// A macro used for defining the properties for a request to a
 /// particular HTTP endpoint.
+///
+/// Responses are parsed as JSON, with an empty body being treated as
+/// `null`. Endpoints not responding with any data can hence use `()`
+/// as their output type and ones that only do so sometimes an
+/// `Option`.
 macro_rules! Endpoint {
   ( $($input:tt)* ) => {
     EndpointNoParse! {
       $($input)*
 
       fn parse(body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
-        ::serde_json::from_slice::<Self::Output>(body).map_err(Self::ConversionError::from)
+        crate::endpoint::parse_body::<Self::Output>(body).map_err(Self::ConversionError::from)
       }
 
       fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
@@ -86,3 +149,45 @@ macro_rules! Endpoint {
     }
   };
 }
+
+/// A macro used for defining the properties for a request to a
+/// particular HTTP endpoint returning potentially large amounts of
+/// market data.
+///
+/// In contrast to endpoints defined using [`Endpoint`], responses are
+/// parsed using the `simd-json` crate if the corresponding feature is
+/// enabled.
+macro_rules! DataEndpoint {
+  ( $($input:tt)* ) => {
+    EndpointNoParse! {
+      $($input)*
+
+      fn parse(body: &[u8]) -> Result<Self::Output, Self::ConversionError> {
+        crate::endpoint::parse_json::<Self::Output>(body)
+      }
+
+      fn parse_err(body: &[u8]) -> Result<Self::ApiError, Vec<u8>> {
+        ::serde_json::from_slice::<Self::ApiError>(body).map_err(|_| body.to_vec())
+      }
+    }
+  };
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Check that we can parse empty bodies into unit and optional
+  /// outputs.
+  #[test]
+  fn parse_empty_body() {
+    let () = parse_body::<()>(b"").unwrap();
+    let () = parse_json::<()>(b" \n").unwrap();
+    assert_eq!(parse_body::<Option<u64>>(b"").unwrap(), None);
+    assert_eq!(parse_body::<Option<u64>>(b"42").unwrap(), Some(42));
+    assert_eq!(parse_json::<Option<u64>>(b"").unwrap(), None);
+    assert!(parse_body::<u64>(b"").is_err());
+  }
+}
@@ -7,8 +7,10 @@ use std::fmt::Formatter;
 use std::fmt::Result as FmtResult;
 use std::io::Error as IoError;
 use std::str::from_utf8;
+use std::time::Duration;
 
 use http::Error as HttpError;
+use http::Method;
 use http::StatusCode as HttpStatusCode;
 use hyper::Error as HyperError;
 use serde_json::Error as JsonError;
@@ -16,11 +18,14 @@ use thiserror::Error;
 use url::ParseError;
 use websocket_util::tungstenite::Error as WebSocketError;
 
+use crate::data::v2::connections::ConnectionLimitError;
+use crate::data::v2::stream::StreamError;
 use crate::Str;
 
 
 /// An error encountered while issuing a request.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum RequestError<E> {
   /// An endpoint reported error.
   #[error("the endpoint reported an error")]
@@ -39,6 +44,33 @@ pub enum RequestError<E> {
     #[source]
     IoError,
   ),
+  /// The request was canceled because the client was shut down.
+  #[error("the client has been shut down")]
+  Shutdown,
+  /// The request was not issued, because the circuit breaker guarding
+  /// the endpoint is open after repeated failures.
+  #[error("the circuit breaker for {0} is open")]
+  CircuitOpen(&'static str),
+  /// The API is down for maintenance, as signaled by an HTML page or
+  /// an otherwise unparsable "503 Service Unavailable" response being
+  /// served instead of JSON.
+  #[error("the API is unavailable due to maintenance ({status})")]
+  Maintenance {
+    /// The HTTP status code of the response.
+    status: HttpStatusCode,
+    /// The time after which to retry, as indicated by the server by
+    /// means of the `Retry-After` header.
+    retry_after: Option<Duration>,
+  },
+  /// The mutating request cannot be simulated in dry-run mode and was
+  /// not issued.
+  #[error("dry run: cannot simulate {method} request to {path}")]
+  NotSimulated {
+    /// The request's method.
+    method: Method,
+    /// The path the request was made to.
+    path: String,
+  },
 }
 
 
@@ -77,6 +109,21 @@ pub enum Error {
     #[source]
     JsonError,
   ),
+  /// Connecting a realtime market data stream would exceed the
+  /// configured connection limit.
+  #[error("the stream connection limit was reached")]
+  ConnectionLimit(
+    #[from]
+    #[source]
+    ConnectionLimitError,
+  ),
+  /// An error reported by the Alpaca Stream API.
+  #[error("the stream API reported an error")]
+  Stream(
+    #[from]
+    #[source]
+    StreamError,
+  ),
   /// An error directly originating in this crate.
   #[error("{0}")]
   Str(Str),
@@ -95,3 +142,21 @@ pub enum Error {
     WebSocketError,
   ),
 }
+
+impl Error {
+  /// Check whether the error signals that the server rejected the
+  /// credentials used.
+  ///
+  /// Retrying with the same credentials is futile in this case, as
+  /// they most likely got rotated or revoked.
+  pub fn is_auth_failure(&self) -> bool {
+    match self {
+      Self::Stream(StreamError::AuthFailed) => true,
+      Self::WebSocket(WebSocketError::Http(response)) => matches!(
+        response.status(),
+        HttpStatusCode::UNAUTHORIZED | HttpStatusCode::FORBIDDEN
+      ),
+      _ => false,
+    }
+  }
+}
@@ -62,20 +62,56 @@ pub mod api;
 /// A module for retrieving market data.
 pub mod data;
 
+/// A module providing a harness for tests interacting with the Alpaca
+/// API.
+pub mod live;
+/// A module providing helpers for money math on `Num` values.
+pub mod money;
+
+/// A module for creating requests and evaluating responses without
+/// performing any I/O, for use with custom transports.
+pub mod sans_io;
+/// A module for detecting changes to the schema of API responses.
+#[cfg(feature = "extra-fields")]
+pub mod schema;
+/// A module for persisting the state of stateful helpers.
+pub mod state;
+/// A module for configuring the TLS layer used for HTTP requests and
+/// websocket connections.
+pub mod tls;
+
 mod api_info;
+mod circuit_breaker;
 mod client;
+mod coalesce;
+mod connector;
+mod dry_run;
 mod error;
+mod metric;
+mod multi_client;
+mod paginated;
+mod rate_limit;
+mod scheduler;
 mod subscribable;
+#[cfg(test)]
+mod test_util;
 mod util;
 mod websocket;
 
 use std::borrow::Cow;
 
 pub use crate::api_info::ApiInfo;
+pub use crate::circuit_breaker::CircuitEvent;
+pub use crate::circuit_breaker::CircuitState;
 pub use crate::client::Client;
+pub use crate::connector::Connector;
 pub use crate::endpoint::ApiError;
+pub use crate::endpoint::ConversionError;
 pub use crate::error::Error;
 pub use crate::error::RequestError;
+pub use crate::multi_client::MultiClient;
+pub use crate::paginated::Paginated;
+pub use crate::scheduler::Priority;
 pub use crate::subscribable::Subscribable;
 
 type Str = Cow<'static, str>;
@@ -0,0 +1,146 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::ops::Deref;
+use std::sync::OnceLock;
+
+use http_endpoint::Endpoint;
+
+use thiserror::Error;
+
+use url::Url;
+
+use crate::api_info::ApiInfo;
+use crate::client::Client;
+use crate::error::RequestError;
+use crate::rate_limit::RateLimiter;
+
+
+/// The host of the paper trading API.
+const PAPER_API_HOST: &str = "paper-api.alpaca.markets";
+
+/// The number of requests per minute the shared rate limiter permits.
+///
+/// This value is below the 200 requests per minute Alpaca allows, in
+/// order to leave some headroom for requests not paced by us, e.g.,
+/// from other processes using the same account.
+const REQUESTS_PER_MINUTE: u32 = 150;
+
+
+/// An error as reported when setting up a live test.
+#[derive(Debug, Error)]
+pub enum Error {
+  /// API information could not be retrieved from the environment.
+  #[error("failed to retrieve API information from environment")]
+  Env(#[source] crate::Error),
+  /// The API information does not refer to a paper trading account.
+  #[error("refusing to run live test against non-paper trading API at {0}")]
+  NotPaper(Url),
+}
+
+
+/// Check whether the provided API information refers to a paper
+/// trading account.
+#[inline]
+pub fn is_paper(api_info: &ApiInfo) -> bool {
+  api_info.api_base_url.host_str() == Some(PAPER_API_HOST)
+}
+
+
+/// Retrieve API information from the environment, making sure that it
+/// refers to a paper trading account.
+///
+/// See [`ApiInfo::from_env`] for the environment variables used.
+#[allow(clippy::result_large_err)]
+pub fn api_info() -> Result<ApiInfo, Error> {
+  let api_info = ApiInfo::from_env().map_err(Error::Env)?;
+  if !is_paper(&api_info) {
+    return Err(Error::NotPaper(api_info.api_base_url))
+  }
+  Ok(api_info)
+}
+
+
+/// Create a [`TestClient`] for the paper trading account configured in
+/// the environment.
+#[allow(clippy::result_large_err)]
+pub fn client() -> Result<TestClient, Error> {
+  let api_info = api_info()?;
+  Ok(TestClient {
+    client: Client::new(api_info),
+  })
+}
+
+
+/// Wait until the shared, process wide rate limiter permits the next
+/// request.
+///
+/// Tests running concurrently in the same process and pacing their
+/// requests through this function collectively stay within Alpaca's
+/// rate limit.
+pub async fn pace() {
+  static LIMITER: OnceLock<RateLimiter> = OnceLock::new();
+
+  LIMITER
+    .get_or_init(|| RateLimiter::per_minute(REQUESTS_PER_MINUTE))
+    .acquire()
+    .await
+}
+
+
+/// A [`Client`] for usage in live tests.
+///
+/// Requests issued through [`TestClient::issue`] are paced using the
+/// shared rate limiter (see [`pace`]). All other functionality is
+/// available through the underlying `Client`.
+#[derive(Debug)]
+pub struct TestClient {
+  client: Client,
+}
+
+impl TestClient {
+  /// Issue a request, waiting for the shared rate limiter first.
+  pub async fn issue<R>(&self, input: &R::Input) -> Result<R::Output, RequestError<R::Error>>
+  where
+    R: Endpoint,
+  {
+    pace().await;
+    self.client.issue::<R>(input).await
+  }
+
+  /// Retrieve the underlying `Client`.
+  #[inline]
+  pub fn into_inner(self) -> Client {
+    self.client
+  }
+}
+
+impl Deref for TestClient {
+  type Target = Client;
+
+  #[inline]
+  fn deref(&self) -> &Self::Target {
+    &self.client
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use test_log::test;
+
+  use crate::api::API_BASE_URL;
+
+
+  /// Check that we correctly identify paper trading API information.
+  #[test]
+  fn detect_paper_account() {
+    let api_info = ApiInfo::from_parts(API_BASE_URL, "key", "secret").unwrap();
+    assert!(is_paper(&api_info));
+
+    let api_info = ApiInfo::from_parts("https://api.alpaca.markets", "key", "secret").unwrap();
+    assert!(!is_paper(&api_info));
+  }
+}
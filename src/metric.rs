@@ -0,0 +1,201 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::any::type_name;
+use std::time::Duration;
+
+use http::Method;
+use http::StatusCode;
+
+
+/// The name of the counter of issued requests.
+#[cfg(feature = "metrics")]
+const REQUESTS: &str = "apca_requests_total";
+/// The name of the counter of failed requests.
+#[cfg(feature = "metrics")]
+const REQUEST_ERRORS: &str = "apca_request_errors_total";
+/// The name of the histogram of request latencies.
+#[cfg(feature = "metrics")]
+const REQUEST_DURATION: &str = "apca_request_duration_seconds";
+/// The name of the counter of received stream messages.
+#[cfg(feature = "metrics")]
+const STREAM_MESSAGES: &str = "apca_stream_messages_total";
+
+
+/// Retrieve the label identifying the endpoint `R`, e.g.,
+/// `api::v2::order::Post`.
+pub(crate) fn endpoint<R>() -> &'static str {
+  let name = type_name::<R>();
+  name.strip_prefix("apca::").unwrap_or(name)
+}
+
+
+/// Record the outcome of a request to the endpoint with the given
+/// label.
+///
+/// `status` is the status code of the response, if one was received.
+#[cfg(feature = "metrics")]
+pub(crate) fn request(
+  endpoint: &'static str,
+  method: &Method,
+  status: Option<StatusCode>,
+  latency: Duration,
+) {
+  use metrics::counter;
+  use metrics::histogram;
+
+  let method = method.to_string();
+  let code = status
+    .map(|status| status.as_u16().to_string())
+    .unwrap_or_else(|| "none".to_string());
+
+  counter!(
+    REQUESTS,
+    "endpoint" => endpoint,
+    "method" => method.clone(),
+    "status" => code.clone()
+  )
+  .increment(1);
+  histogram!(REQUEST_DURATION, "endpoint" => endpoint, "method" => method.clone())
+    .record(latency.as_secs_f64());
+
+  if !status.map(|status| status.is_success()).unwrap_or(false) {
+    counter!(
+      REQUEST_ERRORS,
+      "endpoint" => endpoint,
+      "method" => method,
+      "status" => code
+    )
+    .increment(1);
+  }
+}
+
+/// An implementation stub not actually doing anything.
+#[cfg(not(feature = "metrics"))]
+#[inline]
+pub(crate) fn request(
+  _endpoint: &'static str,
+  _method: &Method,
+  _status: Option<StatusCode>,
+  _latency: Duration,
+) {
+}
+
+
+/// Record the receipt of `count` messages of the given kind on the
+/// stream with the given name.
+#[cfg(feature = "metrics")]
+pub(crate) fn stream_messages(stream: &'static str, kind: &'static str, count: u64) {
+  use metrics::counter;
+
+  counter!(STREAM_MESSAGES, "stream" => stream, "kind" => kind).increment(count);
+}
+
+/// An implementation stub not actually doing anything.
+#[cfg(not(feature = "metrics"))]
+#[inline]
+pub(crate) fn stream_messages(_stream: &'static str, _kind: &'static str, _count: u64) {}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::api::v2::order;
+
+
+  /// Check that we derive the expected endpoint labels.
+  #[test]
+  fn endpoint_label() {
+    assert_eq!(endpoint::<order::Post>(), "api::v2::order::Post");
+  }
+
+  /// Check that we emit the expected metrics for requests.
+  #[cfg(feature = "metrics")]
+  #[test]
+  fn emit_request_metrics() {
+    use std::sync::Mutex;
+
+    use metrics::with_local_recorder;
+    use metrics::Counter;
+    use metrics::Gauge;
+    use metrics::Histogram;
+    use metrics::Key;
+    use metrics::KeyName;
+    use metrics::Metadata;
+    use metrics::Recorder;
+    use metrics::SharedString;
+    use metrics::Unit;
+
+    /// A recorder remembering the keys of all registered metrics.
+    #[derive(Default)]
+    struct Keys(Mutex<Vec<String>>);
+
+    impl Keys {
+      fn add(&self, key: &Key) {
+        let labels = key
+          .labels()
+          .map(|label| format!("{}={}", label.key(), label.value()))
+          .collect::<Vec<_>>()
+          .join(",");
+        self
+          .0
+          .lock()
+          .unwrap()
+          .push(format!("{}{{{}}}", key.name(), labels))
+      }
+    }
+
+    impl Recorder for Keys {
+      fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+      fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+      fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+      fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        self.add(key);
+        Counter::noop()
+      }
+
+      fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+        self.add(key);
+        Gauge::noop()
+      }
+
+      fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+        self.add(key);
+        Histogram::noop()
+      }
+    }
+
+    let keys = Keys::default();
+    let () = with_local_recorder(&keys, || {
+      let endpoint = endpoint::<order::Post>();
+      request(
+        endpoint,
+        &Method::POST,
+        Some(StatusCode::OK),
+        Duration::from_millis(5),
+      );
+      request(
+        endpoint,
+        &Method::POST,
+        Some(StatusCode::FORBIDDEN),
+        Duration::from_millis(5),
+      );
+      stream_messages("iex", "trade", 3);
+    });
+
+    let keys = keys.0.into_inner().unwrap();
+    assert_eq!(
+      keys,
+      vec![
+        "apca_requests_total{endpoint=api::v2::order::Post,method=POST,status=200}",
+        "apca_request_duration_seconds{endpoint=api::v2::order::Post,method=POST}",
+        "apca_requests_total{endpoint=api::v2::order::Post,method=POST,status=403}",
+        "apca_request_duration_seconds{endpoint=api::v2::order::Post,method=POST}",
+        "apca_request_errors_total{endpoint=api::v2::order::Post,method=POST,status=403}",
+        "apca_stream_messages_total{stream=iex,kind=trade}",
+      ]
+    );
+  }
+}
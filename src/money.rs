@@ -0,0 +1,215 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use num_decimal::Num;
+
+use crate::api::v2::asset;
+
+
+/// The number of basis points in a whole.
+const BPS_PER_WHOLE: i32 = 10000;
+
+
+/// Round a price to the nearest multiple of the given increment, e.g.,
+/// the tick size of the asset it is for.
+///
+/// Prices exactly half way between two multiples are rounded away from
+/// zero. The price is reported unchanged if the increment is not
+/// positive.
+pub fn round_to_increment(price: &Num, increment: &Num) -> Num {
+  if !increment.is_positive() {
+    return price.clone()
+  }
+  (price / increment).round() * increment
+}
+
+
+/// Check whether a price is a multiple of the given increment.
+pub fn is_multiple_of(price: &Num, increment: &Num) -> bool {
+  !increment.is_positive() || (price / increment).fract().is_zero()
+}
+
+
+/// Retrieve the minimum price increment ("tick size") at which an
+/// asset of the given class can be traded at the given price.
+///
+/// US equities follow Rule 612 of Regulation NMS: prices of $1.00 and
+/// above are quoted in increments of $0.01, prices below $1.00 in
+/// increments of $0.0001. `None` is returned for other asset classes,
+/// for which increments are asset specific.
+pub fn tick_size(price: &Num, class: asset::Class) -> Option<Num> {
+  match class {
+    asset::Class::UsEquity if price >= &Num::from(1) => Some(Num::new(1, 100)),
+    asset::Class::UsEquity => Some(Num::new(1, 10000)),
+    asset::Class::Crypto | asset::Class::Unknown => None,
+  }
+}
+
+
+/// Round a price to the nearest valid tick for an asset of the given
+/// class.
+///
+/// The price is reported unchanged if no tick size is known for the
+/// asset class. See [`tick_size`] for details.
+pub fn round_to_tick(price: &Num, class: asset::Class) -> Num {
+  match tick_size(price, class) {
+    Some(tick) => round_to_increment(price, &tick),
+    None => price.clone(),
+  }
+}
+
+
+/// Check whether a price is a valid tick for an asset of the given
+/// class.
+///
+/// Prices for asset classes without known tick size are always
+/// considered valid.
+pub fn is_on_tick(price: &Num, class: asset::Class) -> bool {
+  match tick_size(price, class) {
+    Some(tick) => is_multiple_of(price, &tick),
+    None => true,
+  }
+}
+
+
+/// Compute the notional value of a quantity at the given price.
+#[inline]
+pub fn notional(price: &Num, quantity: &Num) -> Num {
+  price * quantity
+}
+
+
+/// Compute the change from one value to another, relative to the
+/// former (as a factor of 1).
+///
+/// `None` is returned if `from` is zero.
+pub fn relative_change(from: &Num, to: &Num) -> Option<Num> {
+  if from.is_zero() {
+    None
+  } else {
+    Some((to - from) / from)
+  }
+}
+
+
+/// Compute the change from one value to another, relative to the
+/// former, in percent.
+///
+/// `None` is returned if `from` is zero.
+#[inline]
+pub fn percent_change(from: &Num, to: &Num) -> Option<Num> {
+  relative_change(from, to).map(|change| change * 100)
+}
+
+
+/// Compute the change from one value to another, relative to the
+/// former, in basis points.
+///
+/// `None` is returned if `from` is zero.
+#[inline]
+pub fn bps_change(from: &Num, to: &Num) -> Option<Num> {
+  relative_change(from, to).map(|change| change * BPS_PER_WHOLE)
+}
+
+
+/// Check whether `value` is within the given number of basis points of
+/// `reference`, in either direction.
+///
+/// A zero `reference` is only considered to be within range of itself.
+pub fn within_bps(reference: &Num, value: &Num, bps: impl Into<Num>) -> bool {
+  match bps_change(reference, value) {
+    Some(change) => {
+      let change = if change.is_negative() { -change } else { change };
+      change <= bps.into()
+    },
+    None => value.is_zero(),
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+
+  /// Check that we round prices to increments as expected.
+  #[test]
+  fn round_prices_to_increment() {
+    let cent = Num::new(1, 100);
+    assert_eq!(
+      round_to_increment(&Num::new(101234, 1000), &cent),
+      Num::new(10123, 100)
+    );
+    assert_eq!(
+      round_to_increment(&Num::new(101235, 1000), &cent),
+      Num::new(10124, 100)
+    );
+    assert_eq!(
+      round_to_increment(&Num::new(12, 10), &Num::new(1, 4)),
+      Num::new(5, 4)
+    );
+    assert_eq!(
+      round_to_increment(&Num::new(101235, 1000), &Num::from(0)),
+      Num::new(101235, 1000)
+    );
+
+    assert!(is_multiple_of(&Num::new(10125, 100), &cent));
+    assert!(!is_multiple_of(&Num::new(101235, 1000), &cent));
+    assert!(is_multiple_of(&Num::new(101235, 1000), &Num::from(0)));
+  }
+
+  /// Check that we round prices to valid ticks.
+  #[test]
+  fn round_prices_to_tick() {
+    let equity = asset::Class::UsEquity;
+    assert_eq!(tick_size(&Num::from(1), equity), Some(Num::new(1, 100)));
+    assert_eq!(
+      tick_size(&Num::new(99, 100), equity),
+      Some(Num::new(1, 10000))
+    );
+    assert_eq!(tick_size(&Num::from(1), asset::Class::Crypto), None);
+
+    assert_eq!(
+      round_to_tick(&Num::new(101234, 1000), equity),
+      Num::new(10123, 100)
+    );
+    assert_eq!(
+      round_to_tick(&Num::new(512345, 1000000), equity),
+      Num::new(5123, 10000)
+    );
+    assert_eq!(
+      round_to_tick(&Num::new(101234, 1000), asset::Class::Crypto),
+      Num::new(101234, 1000)
+    );
+
+    assert!(is_on_tick(&Num::new(10123, 100), equity));
+    assert!(!is_on_tick(&Num::new(101234, 1000), equity));
+    assert!(is_on_tick(&Num::new(5123, 10000), equity));
+    assert!(!is_on_tick(&Num::new(51235, 100000), equity));
+    assert!(is_on_tick(&Num::new(101234, 1000), asset::Class::Crypto));
+  }
+
+  /// Check that we compute relative changes correctly.
+  #[test]
+  fn compute_changes() {
+    let from = Num::from(200);
+    let to = Num::from(205);
+    assert_eq!(notional(&to, &Num::from(3)), Num::from(615));
+    assert_eq!(relative_change(&from, &to), Some(Num::new(1, 40)));
+    assert_eq!(percent_change(&from, &to), Some(Num::new(5, 2)));
+    assert_eq!(bps_change(&from, &to), Some(Num::from(250)));
+    assert_eq!(bps_change(&to, &from), Some(Num::new(-50000, 205)));
+    assert_eq!(relative_change(&Num::from(0), &to), None);
+  }
+
+  /// Check that we correctly compare values in terms of basis points.
+  #[test]
+  fn compare_within_bps() {
+    let reference = Num::from(100);
+    assert!(within_bps(&reference, &Num::new(1001, 10), 10));
+    assert!(within_bps(&reference, &Num::new(999, 10), 10));
+    assert!(!within_bps(&reference, &Num::new(1002, 10), 10));
+    assert!(within_bps(&Num::from(0), &Num::from(0), 0));
+    assert!(!within_bps(&Num::from(0), &Num::from(1), 10));
+  }
+}
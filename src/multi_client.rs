@@ -0,0 +1,253 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use futures::future::join_all;
+
+use http_endpoint::Endpoint;
+
+use crate::api_info::ApiInfo;
+use crate::client::Client;
+use crate::error::RequestError;
+use crate::scheduler::Priority;
+use crate::scheduler::Scheduler;
+
+
+/// The number of requests per minute Alpaca permits for a single
+/// account.
+const DEFAULT_REQUESTS_PER_MINUTE: u32 = 200;
+
+
+/// A single account managed by a `MultiClient`.
+#[derive(Debug)]
+struct Profile {
+  /// The name of the profile.
+  name: String,
+  /// The client used for issuing requests on behalf of the account.
+  client: Client,
+  /// The scheduler rate limiting requests on behalf of the account.
+  scheduler: Scheduler,
+}
+
+
+/// A client for interacting with the Alpaca API on behalf of multiple
+/// accounts, e.g., a paper and a live account shadowing each other or
+/// separate accounts for multiple strategies.
+///
+/// Each account is identified by a name and requests issued on its
+/// behalf are rate limited separately. Pending requests are issued in
+/// the order of their [`Priority`].
+#[derive(Debug, Default)]
+pub struct MultiClient {
+  profiles: Vec<Profile>,
+}
+
+impl MultiClient {
+  /// Create a new `MultiClient` without any accounts.
+  #[inline]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Add an account with the given name, using the default rate limit
+  /// of 200 requests per minute.
+  ///
+  /// An account previously added under the same name is replaced.
+  pub fn add<S>(&mut self, name: S, api_info: ApiInfo) -> &mut Self
+  where
+    S: Into<String>,
+  {
+    self.add_client(name, Client::new(api_info), DEFAULT_REQUESTS_PER_MINUTE)
+  }
+
+  /// Add an account with the given name, using the provided `Client`
+  /// and limiting requests to the given number per minute.
+  ///
+  /// An account previously added under the same name is replaced.
+  pub fn add_client<S>(&mut self, name: S, client: Client, requests_per_minute: u32) -> &mut Self
+  where
+    S: Into<String>,
+  {
+    let name = name.into();
+    self.profiles.retain(|profile| profile.name != name);
+    self.profiles.push(Profile {
+      name,
+      client,
+      scheduler: Scheduler::per_minute(requests_per_minute),
+    });
+    self
+  }
+
+  /// Retrieve an iterator over the names of all accounts, in the order
+  /// in which they were added.
+  pub fn names(&self) -> impl Iterator<Item = &str> {
+    self.profiles.iter().map(|profile| profile.name.as_str())
+  }
+
+  /// Retrieve the `Client` of the account with the given name.
+  ///
+  /// Note that requests issued directly through the returned client
+  /// are not subject to rate limiting.
+  pub fn client(&self, name: &str) -> Option<&Client> {
+    self.profile(name).map(|profile| &profile.client)
+  }
+
+  fn profile(&self, name: &str) -> Option<&Profile> {
+    self.profiles.iter().find(|profile| profile.name == name)
+  }
+
+  /// Issue a request on behalf of the account with the given name.
+  ///
+  /// The request is issued with the default priority, as described
+  /// for [`Client::issue`]. `None` is returned if no such account
+  /// exists.
+  pub async fn issue<R>(
+    &self,
+    name: &str,
+    input: &R::Input,
+  ) -> Option<Result<R::Output, RequestError<R::Error>>>
+  where
+    R: Endpoint,
+  {
+    self
+      .issue_with_priority::<R>(name, input, Client::default_priority::<R>())
+      .await
+  }
+
+  /// Issue a request with the given priority on behalf of the account
+  /// with the given name.
+  ///
+  /// `None` is returned if no such account exists.
+  pub async fn issue_with_priority<R>(
+    &self,
+    name: &str,
+    input: &R::Input,
+    priority: Priority,
+  ) -> Option<Result<R::Output, RequestError<R::Error>>>
+  where
+    R: Endpoint,
+  {
+    let profile = self.profile(name)?;
+    profile.scheduler.acquire(priority).await;
+    Some(profile.client.issue::<R>(input).await)
+  }
+
+  /// Issue the same request on behalf of all accounts concurrently.
+  ///
+  /// Results are reported alongside the account name, in the order in
+  /// which accounts were added.
+  pub async fn issue_all<R>(
+    &self,
+    input: &R::Input,
+  ) -> Vec<(&str, Result<R::Output, RequestError<R::Error>>)>
+  where
+    R: Endpoint,
+  {
+    let futures = self.profiles.iter().map(|profile| async move {
+      let priority = Client::default_priority::<R>();
+      profile.scheduler.acquire(priority).await;
+      let result = profile.client.issue::<R>(input).await;
+      (profile.name.as_str(), result)
+    });
+
+    join_all(futures).await
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use futures::future::join;
+
+  use http::StatusCode;
+
+  use test_log::test;
+
+  use crate::api::v2::account;
+  use crate::api::v2::clock;
+  use crate::api::API_BASE_URL;
+  use crate::live;
+  use crate::test_util::MockServer;
+  use crate::test_util::Response;
+
+
+  /// Check that accounts are managed as expected.
+  #[test]
+  fn manage_accounts() {
+    let api_info = ApiInfo::from_parts(API_BASE_URL, "key1", "secret1").unwrap();
+    let mut client = MultiClient::new();
+    let _ = client
+      .add("paper", api_info.clone())
+      .add("shadow", api_info.clone());
+
+    assert_eq!(client.names().collect::<Vec<_>>(), vec!["paper", "shadow"]);
+    assert!(client.client("live").is_none());
+
+    let other = ApiInfo::from_parts(API_BASE_URL, "key2", "secret2").unwrap();
+    let _ = client.add("paper", other);
+    assert_eq!(client.names().collect::<Vec<_>>(), vec!["shadow", "paper"]);
+    assert_eq!(client.client("paper").unwrap().api_info().key_id, "key2");
+  }
+
+  /// Check that issuing a request for an unknown account reports
+  /// `None`.
+  #[test(tokio::test)]
+  async fn issue_unknown_account() {
+    let client = MultiClient::new();
+    let result = client.issue::<account::Get>("live", &()).await;
+    assert!(result.is_none());
+  }
+
+  /// Check that pending requests of a higher priority are issued
+  /// before those of a lower one.
+  #[test(tokio::test)]
+  async fn issue_by_priority() {
+    let server = MockServer::start(3, |_| Some(Response::new(StatusCode::NOT_FOUND)));
+    let mut client = MultiClient::new();
+    let _ = client.add_client("paper", server.client(), 600);
+
+    // Use up the current slot, so that the following requests have to
+    // wait for theirs.
+    let _result = client
+      .issue_with_priority::<clock::Get>("paper", &(), Priority::Backfill)
+      .await
+      .unwrap();
+
+    let (backfill, trading) = join(
+      client.issue_with_priority::<clock::Get>("paper", &(), Priority::Backfill),
+      client.issue_with_priority::<account::Get>("paper", &(), Priority::Trading),
+    )
+    .await;
+    assert!(backfill.is_some());
+    assert!(trading.is_some());
+
+    let requests = server.join();
+    let lines = requests
+      .iter()
+      .map(|request| &request.line)
+      .collect::<Vec<_>>();
+    assert!(lines[1].starts_with("GET /v2/account "), "{:?}", lines);
+    assert!(lines[2].starts_with("GET /v2/clock "), "{:?}", lines);
+  }
+
+  /// Check that we can issue a request on behalf of multiple accounts.
+  #[test(tokio::test)]
+  #[cfg_attr(not(feature = "live-test"), ignore = "requires live-test feature")]
+  async fn issue_to_all_accounts() {
+    let api_info = live::api_info().unwrap();
+    let mut client = MultiClient::new();
+    let _ = client
+      .add("first", api_info.clone())
+      .add("second", api_info);
+
+    let results = client.issue_all::<account::Get>(&()).await;
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].0, "first");
+    assert_eq!(results[1].0, "second");
+
+    let first = results[0].1.as_ref().unwrap();
+    let second = results[1].1.as_ref().unwrap();
+    assert_eq!(first.id, second.id);
+  }
+}
@@ -0,0 +1,152 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::vec::IntoIter;
+
+use futures::task::Context;
+use futures::task::Poll;
+use futures::Stream;
+use futures::StreamExt as _;
+
+use http_endpoint::Endpoint;
+
+
+/// A trait for endpoints returning their results in pages, each of
+/// which has to be retrieved by means of a separate request.
+///
+/// The market data API hands out a token for retrieving the next page,
+/// whereas the trading API expects requests to be continued from the
+/// last item received (identified by ID or time stamp). Implementations
+/// of this trait hide these differences, so that all such endpoints
+/// can be used with [`Client::issue_paged`][crate::Client::issue_paged].
+pub trait Paginated: Endpoint {
+  /// The type of the items making up a page.
+  type Item;
+
+  /// State carried over from one page to the next, in addition to the
+  /// input of the request. Endpoints for which the input suffices use
+  /// `()`.
+  type State: Default;
+
+  /// Split the output of a request into the page of items it contains
+  /// and the input for the request retrieving the next page, if there
+  /// is one.
+  fn paginate(
+    input: Self::Input,
+    output: Self::Output,
+    state: &mut Self::State,
+  ) -> (Vec<Self::Item>, Option<Self::Input>);
+}
+
+
+/// A stream adapter delivering the items of the pages yielded by the
+/// wrapped stream one by one, while eagerly retrieving up to
+/// `lookahead` further pages.
+///
+/// Retrieval of pages only makes progress while the adapter itself is
+/// being polled, but because every item handed out entails a poll, the
+/// request for the next page is in flight while the caller is busy
+/// with the items of the current one.
+#[derive(Debug)]
+#[must_use = "streams do nothing unless polled"]
+pub(crate) struct Prefetch<S, T, E> {
+  /// The wrapped stream of pages.
+  inner: S,
+  /// The pages received but not yet (fully) forwarded, front first.
+  pages: VecDeque<Result<IntoIter<T>, E>>,
+  /// The number of pages to retrieve in addition to the one currently
+  /// being forwarded.
+  lookahead: usize,
+  /// Whether the wrapped stream is exhausted.
+  done: bool,
+}
+
+impl<S, T, E> Prefetch<S, T, E> {
+  /// Create a new `Prefetch` object wrapping the provided stream.
+  pub(crate) fn new(inner: S, lookahead: usize) -> Self {
+    Self {
+      inner,
+      pages: VecDeque::new(),
+      lookahead,
+      done: false,
+    }
+  }
+}
+
+// We never hand out pinned references to the buffered pages.
+impl<S, T, E> Unpin for Prefetch<S, T, E> where S: Unpin {}
+
+impl<S, T, E> Stream for Prefetch<S, T, E>
+where
+  S: Stream<Item = Result<Vec<T>, E>> + Unpin,
+{
+  type Item = Result<T, E>;
+
+  fn poll_next(mut self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    loop {
+      while !self.done && self.pages.len() <= self.lookahead {
+        match self.inner.poll_next_unpin(ctx) {
+          Poll::Ready(Some(page)) => {
+            let page = page.map(Vec::into_iter);
+            let () = self.pages.push_back(page);
+          },
+          Poll::Ready(None) => self.done = true,
+          // The next page is still in flight. We will be woken up once
+          // it arrives.
+          Poll::Pending => break,
+        }
+      }
+
+      match self.pages.pop_front() {
+        Some(Ok(mut items)) => {
+          if let Some(item) = items.next() {
+            let () = self.pages.push_front(Ok(items));
+            break Poll::Ready(Some(Ok(item)))
+          }
+          // The page is exhausted. Continue with the next one, if any,
+          // making sure to request another one.
+        },
+        Some(Err(err)) => break Poll::Ready(Some(Err(err))),
+        None if self.done => break Poll::Ready(None),
+        None => break Poll::Pending,
+      }
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::cell::Cell;
+
+  use futures::stream::iter;
+
+  use test_log::test;
+
+
+  /// Check that `Prefetch` retrieves pages ahead of time, up to the
+  /// configured lookahead.
+  #[test(tokio::test)]
+  async fn prefetch_pages() {
+    let pages = [Ok(vec![1, 2]), Ok(vec![]), Ok(vec![3]), Err("error")];
+    let fetched = Cell::new(0);
+
+    for lookahead in 0..3 {
+      let () = fetched.set(0);
+      let inner = iter(pages.clone()).inspect(|_| fetched.set(fetched.get() + 1));
+      let mut stream = Prefetch::new(inner, lookahead);
+
+      assert_eq!(stream.next().await, Some(Ok(1)));
+      assert_eq!(fetched.get(), 1 + lookahead);
+      assert_eq!(stream.next().await, Some(Ok(2)));
+      assert_eq!(stream.next().await, Some(Ok(3)));
+      assert_eq!(stream.next().await, Some(Err("error")));
+      assert_eq!(stream.next().await, None);
+      assert_eq!(fetched.get(), pages.len());
+    }
+  }
+}
@@ -0,0 +1,94 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::sleep_until;
+use tokio::time::Instant;
+
+
+/// A rate limiter spacing out operations evenly, so that no more than a
+/// given number of them happen per minute.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+  /// The minimum interval between two operations.
+  interval: Duration,
+  /// The earliest point in time at which the next operation may
+  /// happen.
+  next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+  /// Create a new `RateLimiter` permitting the given number of
+  /// operations per minute.
+  pub(crate) fn per_minute(count: u32) -> Self {
+    Self {
+      interval: Duration::from_secs(60) / count.max(1),
+      next: Mutex::new(Instant::now()),
+    }
+  }
+
+  /// Claim the next slot if an operation is permitted to happen right
+  /// away, or report the point in time at which the next one is.
+  pub(crate) fn try_acquire(&self) -> Result<(), Instant> {
+    let mut next = self.next.lock().unwrap();
+    let now = Instant::now();
+    if *next <= now {
+      *next = now + self.interval;
+      Ok(())
+    } else {
+      Err(*next)
+    }
+  }
+
+  /// Wait until the next operation is permitted to happen.
+  pub(crate) async fn acquire(&self) {
+    let slot = {
+      let mut next = self.next.lock().unwrap();
+      let slot = (*next).max(Instant::now());
+      *next = slot + self.interval;
+      slot
+    };
+
+    sleep_until(slot).await
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use test_log::test;
+
+
+  /// Check that operations are spaced out as expected.
+  #[test(tokio::test(start_paused = true))]
+  async fn space_operations() {
+    let limiter = RateLimiter::per_minute(120);
+    let start = Instant::now();
+
+    limiter.acquire().await;
+    assert_eq!(start.elapsed(), Duration::from_secs(0));
+    limiter.acquire().await;
+    assert_eq!(start.elapsed(), Duration::from_millis(500));
+    limiter.acquire().await;
+    assert_eq!(start.elapsed(), Duration::from_millis(1000));
+  }
+
+  /// Check that we can claim slots without waiting.
+  #[test(tokio::test(start_paused = true))]
+  async fn try_claim_slot() {
+    let limiter = RateLimiter::per_minute(120);
+    let start = Instant::now();
+
+    let interval = Duration::from_millis(500);
+    assert_eq!(limiter.try_acquire(), Ok(()));
+    assert_eq!(limiter.try_acquire(), Err(start + interval));
+
+    limiter.acquire().await;
+    assert_eq!(start.elapsed(), interval);
+    assert_eq!(limiter.try_acquire(), Err(start + 2 * interval));
+  }
+}
@@ -0,0 +1,134 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use http::header::AUTHORIZATION;
+use http::request::Builder as HttpRequestBuilder;
+use http::Request;
+use http::StatusCode;
+use http_endpoint::Endpoint;
+
+use url::Url;
+
+use crate::api::BROKER_API_BASE_URL;
+use crate::api::HDR_KEY_ID;
+use crate::api::HDR_SECRET;
+use crate::api_info::ApiInfo;
+use crate::data::DATA_BASE_URL;
+
+
+/// Create the full HTTP request to the endpoint `R`, including method,
+/// URL, authentication headers, and body, without issuing it.
+///
+/// The request is equivalent to the one that
+/// [`Client::issue`][crate::Client::issue] would send for the same
+/// [`ApiInfo`] and input, except that it does not ask for a compressed
+/// response. That allows for sending it using a custom transport,
+/// while still benefiting from the typed request modeling of this
+/// crate.
+pub fn request<R>(api_info: &ApiInfo, input: &R::Input) -> Result<Request<Vec<u8>>, R::Error>
+where
+  R: Endpoint,
+{
+  let (mut url, broker) = match R::base_url() {
+    // Data and Broker API endpoints report the default base URL, but
+    // we honor whatever the user configured instead.
+    Some(url) if url == DATA_BASE_URL => (api_info.data_base_url.clone(), false),
+    Some(url) if url == BROKER_API_BASE_URL => (api_info.broker_base_url.clone(), true),
+    Some(url) => (
+      Url::parse(url.as_ref()).expect("endpoint definition contains invalid URL"),
+      false,
+    ),
+    None => (api_info.api_base_url.clone(), false),
+  };
+
+  url.set_path(&R::path(input));
+  url.set_query(R::query(input)?.as_ref().map(AsRef::as_ref));
+
+  let builder = HttpRequestBuilder::new()
+    .method(R::method())
+    .uri(url.as_str());
+
+  // Add required authentication information. The Broker API uses
+  // HTTP basic authentication, all others custom headers.
+  let builder = if broker {
+    builder.header(AUTHORIZATION, api_info.broker_authorization())
+  } else {
+    builder
+      .header(HDR_KEY_ID, api_info.key_id.as_str())
+      .header(HDR_SECRET, api_info.secret.as_str())
+  };
+
+  let body = R::body(input)?.map(|body| body.into_owned());
+  let request = builder.body(body.unwrap_or_default())?;
+  Ok(request)
+}
+
+
+/// Evaluate a response to a request to the endpoint `R`, given its
+/// status code and (already decompressed) body, converting it into
+/// the endpoint's output or error.
+///
+/// This is the counterpart to [`request`], allowing for responses
+/// retrieved by means of a custom transport or recorded earlier to be
+/// decoded into the typed representation of this crate.
+#[inline]
+pub fn parse_response<R>(status: StatusCode, body: &[u8]) -> Result<R::Output, R::Error>
+where
+  R: Endpoint,
+{
+  R::evaluate(status, body)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use http::Method;
+
+  use serde_json::from_slice as from_json;
+  use serde_json::Value;
+
+  use crate::api::v2::clock;
+  use crate::api::v2::order;
+  use crate::api::v2::order::Amount;
+  use crate::api::v2::order::Side;
+  use crate::api::API_BASE_URL;
+
+
+  /// Check that we can create a request without issuing it.
+  #[test]
+  fn create_request() {
+    let api_info = ApiInfo::from_parts(API_BASE_URL, "key", "secret").unwrap();
+    let input = order::OrderReqInit::default().init("SPY", Side::Buy, Amount::quantity(1));
+    let request = request::<order::Post>(&api_info, &input).unwrap();
+
+    assert_eq!(request.method(), Method::POST);
+    assert_eq!(
+      request.uri().to_string(),
+      "https://paper-api.alpaca.markets/v2/orders"
+    );
+    assert_eq!(request.headers()[HDR_KEY_ID], "key");
+    assert_eq!(request.headers()[HDR_SECRET], "secret");
+
+    let body = from_json::<Value>(request.body()).unwrap();
+    assert_eq!(body["symbol"], "SPY");
+    assert_eq!(body["side"], "buy");
+  }
+
+  /// Check that we can evaluate responses received elsewhere.
+  #[test]
+  fn parse_responses() {
+    let body = br#"{"timestamp":"2018-04-01T12:00:00.000Z","is_open":true,
+      "next_open":"2018-04-01T12:00:00.000Z","next_close":"2018-04-01T12:00:00.000Z"}"#;
+    let clock = parse_response::<clock::Get>(StatusCode::OK, body).unwrap();
+    assert!(clock.open);
+
+    let body = br#"{"code":40410000,"message":"order not found"}"#;
+    let err = parse_response::<order::Get>(StatusCode::NOT_FOUND, body).unwrap_err();
+    match err {
+      order::GetError::NotFound(Ok(error)) => assert_eq!(error.message, "order not found"),
+      _ => panic!("unexpected error: {:?}", err),
+    }
+  }
+}
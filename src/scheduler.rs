@@ -0,0 +1,197 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+use futures::future::select;
+use futures::pin_mut;
+
+use tokio::sync::Notify;
+use tokio::time::sleep_until;
+
+use crate::rate_limit::RateLimiter;
+
+
+/// The priority class of a request issued by a [`Client`][crate::Client].
+///
+/// If the client is rate limited, pending requests of a higher priority
+/// are issued before those of a lower one, irrespective of the order in
+/// which they were issued. Requests of the same priority are issued in
+/// order.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum Priority {
+  /// Latency sensitive requests, e.g., order submissions. This is the
+  /// default for requests to the trading API.
+  Trading,
+  /// Requests supporting realtime streaming, e.g., retrieving the
+  /// latest quote when subscribing to a symbol.
+  StreamControl,
+  /// Bulk requests, e.g., historical market data downloads. This is
+  /// the default for requests to the market data API.
+  Backfill,
+}
+
+
+/// The state of a `Scheduler`.
+#[derive(Debug, Default)]
+struct State {
+  /// The pending requests, ordered by priority and then sequence
+  /// number.
+  pending: BTreeSet<(Priority, u64)>,
+  /// The sequence number to assign to the next pending request.
+  sequence: u64,
+}
+
+
+/// A rate limiter spacing out requests evenly, while granting requests
+/// slots in the order of their priority.
+#[derive(Debug)]
+pub(crate) struct Scheduler {
+  /// The rate limiter handing out slots.
+  limiter: RateLimiter,
+  /// The scheduler's mutable state.
+  state: Mutex<State>,
+  /// Notification sent out whenever the set of pending requests
+  /// changed.
+  changed: Notify,
+}
+
+/// A guard representing a pending request, deregistering it when
+/// dropped.
+struct Pending<'s> {
+  scheduler: &'s Scheduler,
+  key: (Priority, u64),
+}
+
+impl Drop for Pending<'_> {
+  fn drop(&mut self) {
+    let mut state = self.scheduler.state.lock().unwrap();
+    let _ = state.pending.remove(&self.key);
+    drop(state);
+    self.scheduler.changed.notify_waiters();
+  }
+}
+
+impl Scheduler {
+  /// Create a new `Scheduler` permitting the given number of requests
+  /// per minute.
+  pub(crate) fn per_minute(count: u32) -> Self {
+    Self {
+      limiter: RateLimiter::per_minute(count),
+      state: Mutex::new(State::default()),
+      changed: Notify::new(),
+    }
+  }
+
+  /// Wait until a request of the given priority is permitted to be
+  /// issued.
+  pub(crate) async fn acquire(&self, priority: Priority) {
+    let pending = {
+      let mut state = self.state.lock().unwrap();
+      let key = (priority, state.sequence);
+      state.sequence += 1;
+      let _ = state.pending.insert(key);
+      Pending {
+        scheduler: self,
+        key,
+      }
+    };
+
+    loop {
+      let changed = self.changed.notified();
+      let deadline = {
+        let state = self.state.lock().unwrap();
+        if state.pending.iter().next() == Some(&pending.key) {
+          match self.limiter.try_acquire() {
+            Ok(()) => break,
+            Err(next) => Some(next),
+          }
+        } else {
+          None
+        }
+      };
+
+      match deadline {
+        Some(deadline) => {
+          let sleep = sleep_until(deadline);
+          pin_mut!(sleep);
+          pin_mut!(changed);
+          let _ = select(sleep, changed).await;
+        },
+        None => changed.await,
+      }
+    }
+    // Dropping the guard deregisters the request and notifies the next
+    // one in line.
+    drop(pending)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::time::Duration;
+
+  use futures::future::join3;
+  use futures::FutureExt as _;
+
+  use test_log::test;
+
+  use tokio::time::Instant;
+
+
+  /// Check that requests are spaced out and granted slots by priority.
+  #[test(tokio::test(start_paused = true))]
+  async fn schedule_by_priority() {
+    let scheduler = Scheduler::per_minute(60);
+    let start = Instant::now();
+    let order = Mutex::new(Vec::new());
+
+    let request = |priority| {
+      let scheduler = &scheduler;
+      let order = &order;
+      async move {
+        scheduler.acquire(priority).await;
+        order.lock().unwrap().push((priority, start.elapsed()));
+      }
+    };
+
+    scheduler.acquire(Priority::Backfill).await;
+    assert_eq!(start.elapsed(), Duration::from_secs(0));
+
+    let ((), (), ()) = join3(
+      request(Priority::Backfill),
+      request(Priority::StreamControl),
+      request(Priority::Trading),
+    )
+    .await;
+
+    let expected = vec![
+      (Priority::Trading, Duration::from_secs(1)),
+      (Priority::StreamControl, Duration::from_secs(2)),
+      (Priority::Backfill, Duration::from_secs(3)),
+    ];
+    assert_eq!(*order.lock().unwrap(), expected);
+  }
+
+  /// Check that a canceled request does not hold up others.
+  #[test(tokio::test(start_paused = true))]
+  async fn cancel_pending_request() {
+    let scheduler = Scheduler::per_minute(60);
+    let start = Instant::now();
+
+    scheduler.acquire(Priority::Backfill).await;
+    {
+      let trading = scheduler.acquire(Priority::Trading);
+      pin_mut!(trading);
+      assert!(trading.as_mut().now_or_never().is_none());
+    }
+
+    scheduler.acquire(Priority::Backfill).await;
+    assert_eq!(start.elapsed(), Duration::from_secs(1));
+  }
+}
@@ -0,0 +1,155 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::any::type_name;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use serde::Deserialize;
+use serde::Deserializer;
+use serde_json::Value;
+
+use tracing::warn;
+
+
+/// The type of a hook invoked for each unknown field encountered.
+type Hook = Arc<dyn Fn(&UnknownField<'_>) + Send + Sync>;
+
+/// The currently installed unknown field hook, if any.
+static HOOK: RwLock<Option<Hook>> = RwLock::new(None);
+
+
+/// A field reported by the API that is not modeled explicitly by the
+/// type it was encountered on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct UnknownField<'a> {
+  /// The fully qualified name of the type being deserialized, e.g.,
+  /// `apca::api::v2::account::Account`.
+  pub type_: &'static str,
+  /// The name of the unknown field.
+  pub name: &'a str,
+  /// The value of the unknown field.
+  pub value: &'a Value,
+}
+
+
+/// Install a hook to be invoked for each field in an API response that
+/// is not modeled explicitly.
+///
+/// By default unknown fields are captured silently (in the `extra`
+/// member of the respective type). Installing a hook is a means for
+/// detecting when Alpaca changes its payloads. The hook is global and
+/// replaces any previously installed one.
+pub fn set_unknown_field_hook<F>(hook: F)
+where
+  F: Fn(&UnknownField<'_>) + Send + Sync + 'static,
+{
+  *HOOK.write().unwrap_or_else(|err| err.into_inner()) = Some(Arc::new(hook));
+}
+
+/// Install a hook emitting a warning via `tracing` for each unknown
+/// field encountered.
+pub fn warn_on_unknown_fields() {
+  set_unknown_field_hook(|field| {
+    warn!(
+      type_ = field.type_,
+      field = field.name,
+      value = %field.value,
+      "encountered unknown field"
+    )
+  })
+}
+
+/// Remove the currently installed unknown field hook, if any, reverting
+/// to silently capturing unknown fields.
+pub fn clear_unknown_field_hook() {
+  *HOOK.write().unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+
+/// Deserialize the "extra" fields of a `T`, reporting each of them to
+/// the installed unknown field hook.
+pub(crate) fn capture<'de, T, D>(deserializer: D) -> Result<BTreeMap<String, Value>, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  let extra = BTreeMap::<String, Value>::deserialize(deserializer)?;
+  if !extra.is_empty() {
+    // Clone the hook so that we do not hold the lock while invoking
+    // it, which would deadlock should it (un)install a hook itself.
+    let hook = HOOK
+      .read()
+      .unwrap_or_else(|err| err.into_inner())
+      .as_ref()
+      .cloned();
+
+    if let Some(hook) = hook {
+      for (name, value) in &extra {
+        let field = UnknownField {
+          type_: type_name::<T>(),
+          name,
+          value,
+        };
+        hook(&field)
+      }
+    }
+  }
+  Ok(extra)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::Mutex;
+
+  use serde_json::from_str as from_json;
+
+  use test_log::test;
+
+  use crate::api::v2::asset::Asset;
+
+
+  /// Check that the installed hook is invoked for unknown fields.
+  #[test]
+  fn report_unknown_fields() {
+    static SEEN: Mutex<Vec<(&'static str, String, Value)>> = Mutex::new(Vec::new());
+
+    let response = r#"{
+  "id": "904837e3-3b76-47ec-b432-046db621571b",
+  "class": "us_equity",
+  "exchange": "NASDAQ",
+  "symbol": "AAPL",
+  "status": "active",
+  "tradable": true,
+  "marginable": true,
+  "shortable": true,
+  "easy_to_borrow": true,
+  "fractionable": true,
+  "schema_drift_test_field": 42
+}"#;
+
+    set_unknown_field_hook(|field| {
+      SEEN.lock().unwrap().push((
+        field.type_,
+        field.name.to_string(),
+        field.value.clone(),
+      ))
+    });
+    let asset = from_json::<Asset>(response).unwrap();
+    clear_unknown_field_hook();
+
+    assert_eq!(asset.extra.len(), 1);
+    // Other tests may deserialize objects with unknown fields
+    // concurrently, so only check for the presence of ours.
+    let seen = SEEN.lock().unwrap();
+    assert!(seen.contains(&(
+      type_name::<Asset>(),
+      "schema_drift_test_field".to_string(),
+      Value::from(42),
+    )));
+  }
+}
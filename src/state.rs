@@ -0,0 +1,327 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::fs::TryLockError;
+use std::io::Error as IoError;
+use std::io::ErrorKind;
+use std::io::Result as IoResult;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use tokio::fs;
+use tokio::fs::OpenOptions;
+use tokio::time::sleep;
+
+
+/// The interval at which to retry acquiring a lock held by another
+/// process.
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+
+/// A trait for persisting small blobs of state under string keys.
+///
+/// Stateful helpers, such as the
+/// [`trailing::Manager`][crate::api::v2::trailing::Manager] or the
+/// [`AccountSync`][crate::api::v2::sync::AccountSync], persist their
+/// state via this trait, each under a key of their own. Implementing it once
+/// for a database of choice hence makes it usable by all of them.
+///
+/// Operations are asynchronous, so that implementations performing
+/// I/O do not block the runtime the helpers are used on.
+#[async_trait]
+pub trait StateStore {
+  /// Retrieve the blob stored under the given key, if any.
+  async fn get(&self, key: &str) -> IoResult<Option<Vec<u8>>>;
+
+  /// Store a blob under the given key, replacing any previous one.
+  async fn put(&self, key: &str, value: &[u8]) -> IoResult<()>;
+
+  /// Store a blob under the given key, but only if the one currently
+  /// stored equals `expected`, with `None` meaning that none is.
+  ///
+  /// The check and the update have to happen atomically. The return
+  /// value indicates whether the blob was stored.
+  async fn compare_and_swap(
+    &self,
+    key: &str,
+    expected: Option<&[u8]>,
+    value: &[u8],
+  ) -> IoResult<bool>;
+}
+
+#[async_trait]
+impl<S> StateStore for &S
+where
+  S: StateStore + Sync + ?Sized,
+{
+  #[inline]
+  async fn get(&self, key: &str) -> IoResult<Option<Vec<u8>>> {
+    (**self).get(key).await
+  }
+
+  #[inline]
+  async fn put(&self, key: &str, value: &[u8]) -> IoResult<()> {
+    (**self).put(key, value).await
+  }
+
+  #[inline]
+  async fn compare_and_swap(
+    &self,
+    key: &str,
+    expected: Option<&[u8]>,
+    value: &[u8],
+  ) -> IoResult<bool> {
+    (**self).compare_and_swap(key, expected, value).await
+  }
+}
+
+
+/// A [`StateStore`] keeping state in memory only.
+#[derive(Debug, Default)]
+pub struct MemoryStore(Mutex<HashMap<String, Vec<u8>>>);
+
+#[async_trait]
+impl StateStore for MemoryStore {
+  async fn get(&self, key: &str) -> IoResult<Option<Vec<u8>>> {
+    Ok(self.0.lock().unwrap().get(key).cloned())
+  }
+
+  async fn put(&self, key: &str, value: &[u8]) -> IoResult<()> {
+    let mut blobs = self.0.lock().unwrap();
+    let _prev = blobs.insert(key.to_string(), value.to_vec());
+    Ok(())
+  }
+
+  async fn compare_and_swap(
+    &self,
+    key: &str,
+    expected: Option<&[u8]>,
+    value: &[u8],
+  ) -> IoResult<bool> {
+    let mut blobs = self.0.lock().unwrap();
+    if blobs.get(key).map(Vec::as_slice) != expected {
+      return Ok(false)
+    }
+
+    let _prev = blobs.insert(key.to_string(), value.to_vec());
+    Ok(true)
+  }
+}
+
+
+/// A [`StateStore`] persisting state in files in a directory, one per
+/// key.
+///
+/// Keys may only consist of ASCII alphanumeric characters, `-`, `_`,
+/// and `.`, and must not start with a `.`. Blobs are written to a
+/// temporary file first, which then replaces the actual one, so that a
+/// crash never leaves behind partial state. Updates are serialized by
+/// means of an advisory lock on a hidden file next to the actual one,
+/// making compare-and-swap operations atomic even with respect to
+/// other processes sharing the directory. The operating system
+/// releases the lock should a process terminate while holding it.
+#[derive(Debug)]
+pub struct FileStore {
+  /// The directory containing the files.
+  dir: PathBuf,
+}
+
+impl FileStore {
+  /// Create a new `FileStore` persisting state in the given directory,
+  /// which is created as needed.
+  #[inline]
+  pub fn new(dir: impl Into<PathBuf>) -> Self {
+    Self { dir: dir.into() }
+  }
+
+  /// Retrieve the path of the file storing the blob with the given key.
+  fn path(&self, key: &str) -> IoResult<PathBuf> {
+    let valid = !key.is_empty()
+      && !key.starts_with('.')
+      && key
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+    if !valid {
+      let err = IoError::new(ErrorKind::InvalidInput, format!("invalid key: {}", key));
+      return Err(err)
+    }
+    Ok(self.dir.join(key))
+  }
+
+  /// Retrieve the path of an auxiliary file for the blob with the given
+  /// key.
+  ///
+  /// Such files are hidden, so that they cannot clash with the files
+  /// storing blobs.
+  fn aux_path(&self, key: &str, extension: &str) -> IoResult<PathBuf> {
+    let _path = self.path(key)?;
+    Ok(self.dir.join(format!(".{}.{}", key, extension)))
+  }
+
+  /// Acquire the lock for updating the blob with the given key.
+  ///
+  /// The lock is held until the returned file is dropped.
+  async fn lock(&self, key: &str) -> IoResult<File> {
+    let path = self.aux_path(key, "lock")?;
+    let () = fs::create_dir_all(&self.dir).await?;
+    let file = OpenOptions::new()
+      .create(true)
+      .truncate(false)
+      .write(true)
+      .open(&path)
+      .await?
+      .into_std()
+      .await;
+
+    loop {
+      match file.try_lock() {
+        Ok(()) => break Ok(file),
+        Err(TryLockError::WouldBlock) => sleep(LOCK_RETRY_INTERVAL).await,
+        Err(TryLockError::Error(err)) => break Err(err),
+      }
+    }
+  }
+
+  /// Read the blob with the given key.
+  async fn read(&self, key: &str) -> IoResult<Option<Vec<u8>>> {
+    match fs::read(self.path(key)?).await {
+      Ok(value) => Ok(Some(value)),
+      Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+      Err(err) => Err(err),
+    }
+  }
+
+  /// Write the blob with the given key.
+  async fn write(&self, key: &str, value: &[u8]) -> IoResult<()> {
+    let path = self.path(key)?;
+    let temp = self.aux_path(key, "tmp")?;
+
+    let () = fs::write(&temp, value).await?;
+    fs::rename(&temp, &path).await
+  }
+}
+
+#[async_trait]
+impl StateStore for FileStore {
+  async fn get(&self, key: &str) -> IoResult<Option<Vec<u8>>> {
+    self.read(key).await
+  }
+
+  async fn put(&self, key: &str, value: &[u8]) -> IoResult<()> {
+    let _lock = self.lock(key).await?;
+    self.write(key, value).await
+  }
+
+  async fn compare_and_swap(
+    &self,
+    key: &str,
+    expected: Option<&[u8]>,
+    value: &[u8],
+  ) -> IoResult<bool> {
+    let _lock = self.lock(key).await?;
+    if self.read(key).await?.as_deref() != expected {
+      return Ok(false)
+    }
+
+    let () = self.write(key, value).await?;
+    Ok(true)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::env::temp_dir;
+
+  use test_log::test;
+
+  use tokio::time::timeout;
+
+  use uuid::Uuid;
+
+
+  /// Exercise the basic operations of the given store.
+  async fn check_store<S>(store: &S)
+  where
+    S: StateStore,
+  {
+    assert_eq!(store.get("state").await.unwrap(), None);
+    assert!(!store
+      .compare_and_swap("state", Some(b"1"), b"2")
+      .await
+      .unwrap());
+    assert!(store.compare_and_swap("state", None, b"1").await.unwrap());
+    assert_eq!(store.get("state").await.unwrap(), Some(b"1".to_vec()));
+
+    assert!(!store.compare_and_swap("state", None, b"2").await.unwrap());
+    assert!(store
+      .compare_and_swap("state", Some(b"1"), b"2")
+      .await
+      .unwrap());
+    assert_eq!(store.get("state").await.unwrap(), Some(b"2".to_vec()));
+
+    let () = store.put("other.json", b"3").await.unwrap();
+    assert_eq!(store.get("other.json").await.unwrap(), Some(b"3".to_vec()));
+    assert_eq!(store.get("state").await.unwrap(), Some(b"2".to_vec()));
+  }
+
+
+  /// Create the path to a directory not existing yet.
+  fn new_dir() -> PathBuf {
+    temp_dir().join(format!("apca-{}", Uuid::new_v4().as_simple()))
+  }
+
+
+  /// Check that a `MemoryStore` behaves as expected.
+  #[test(tokio::test)]
+  async fn memory_store() {
+    check_store(&MemoryStore::default()).await
+  }
+
+  /// Check that a `FileStore` behaves as expected and rejects keys
+  /// not usable as file names.
+  #[test(tokio::test)]
+  async fn file_store() {
+    let dir = new_dir();
+    let store = FileStore::new(&dir);
+    let () = check_store(&store).await;
+
+    for key in ["", ".hidden", "../state", "a/b"] {
+      let err = store.put(key, b"").await.unwrap_err();
+      assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+    let () = fs::remove_dir_all(&dir).await.unwrap();
+  }
+
+  /// Check that updates by a `FileStore` wait for the lock held by
+  /// another one using the same directory.
+  #[test(tokio::test)]
+  async fn file_store_locking() {
+    let dir = new_dir();
+    let store = FileStore::new(&dir);
+    let other = FileStore::new(&dir);
+
+    let lock = store.lock("state").await.unwrap();
+    let result = timeout(
+      Duration::from_millis(50),
+      other.compare_and_swap("state", None, b"1"),
+    )
+    .await;
+    assert!(result.is_err());
+    assert_eq!(store.get("state").await.unwrap(), None);
+
+    let () = drop(lock);
+    assert!(other.compare_and_swap("state", None, b"1").await.unwrap());
+    assert!(!store.compare_and_swap("state", None, b"2").await.unwrap());
+    assert_eq!(store.get("state").await.unwrap(), Some(b"1".to_vec()));
+    let () = fs::remove_dir_all(&dir).await.unwrap();
+  }
+}
@@ -0,0 +1,317 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::io::Read as _;
+use std::io::Write as _;
+use std::net::SocketAddr;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::thread::spawn;
+use std::thread::JoinHandle;
+
+use http::StatusCode;
+
+use serde_json::from_str as from_json;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::api::v2::account::Account;
+use crate::api::v2::order::Order;
+use crate::ApiInfo;
+use crate::Client;
+
+
+/// Create the JSON representation of an accepted market order for a
+/// single share of AAPL, with the provided fields overriding the
+/// defaults.
+pub(crate) fn order_json(fields: Value) -> Value {
+  let mut order = json!({
+    "id": "904837e3-3b76-47ec-b432-046db621571b",
+    "client_order_id": "904837e3-3b76-47ec-b432-046db621571b",
+    "created_at": "2018-10-05T05:48:59Z",
+    "updated_at": "2018-10-05T05:48:59Z",
+    "submitted_at": "2018-10-05T05:48:59Z",
+    "filled_at": null,
+    "expired_at": null,
+    "canceled_at": null,
+    "asset_id": "904837e3-3b76-47ec-b432-046db621571b",
+    "symbol": "AAPL",
+    "asset_class": "us_equity",
+    "qty": "1",
+    "filled_qty": "0",
+    "type": "market",
+    "order_class": "simple",
+    "side": "buy",
+    "time_in_force": "day",
+    "limit_price": null,
+    "stop_price": null,
+    "filled_avg_price": null,
+    "status": "accepted",
+    "extended_hours": false,
+    "legs": null
+  });
+
+  if let (Value::Object(order), Value::Object(fields)) = (&mut order, fields) {
+    let () = order.extend(fields);
+  }
+  order
+}
+
+
+/// Create an accepted market order for a single share of AAPL, with
+/// the provided fields overriding the defaults.
+pub(crate) fn order(fields: Value) -> Order {
+  // Orders contain borrowed data and cannot be deserialized from a
+  // `Value` directly.
+  from_json(&order_json(fields).to_string()).unwrap()
+}
+
+
+/// Create the JSON representation of an active account without any
+/// funds, with the provided fields overriding the defaults.
+pub(crate) fn account_json(fields: Value) -> Value {
+  let mut account = json!({
+    "id": "904837e3-3b76-47ec-b432-046db621571b",
+    "status": "ACTIVE",
+    "currency": "USD",
+    "buying_power": "0",
+    "cash": "0",
+    "pattern_day_trader": false,
+    "trade_suspended_by_user": false,
+    "trading_blocked": false,
+    "transfers_blocked": false,
+    "account_blocked": false,
+    "created_at": "2018-10-01T13:35:25Z",
+    "shorting_enabled": true,
+    "multiplier": "2",
+    "long_market_value": "0",
+    "short_market_value": "0",
+    "equity": "0",
+    "last_equity": "0",
+    "initial_margin": "0",
+    "maintenance_margin": "0",
+    "daytrade_count": 0
+  });
+
+  if let (Value::Object(account), Value::Object(fields)) = (&mut account, fields) {
+    let () = account.extend(fields);
+  }
+  account
+}
+
+
+/// Create an active account without any funds, with the provided
+/// fields overriding the defaults.
+pub(crate) fn account(fields: Value) -> Account {
+  from_json(&account_json(fields).to_string()).unwrap()
+}
+
+
+/// An HTTP request as received by a [`MockServer`].
+#[derive(Clone, Debug)]
+pub(crate) struct Request {
+  /// The request line, e.g., `GET /v2/orders HTTP/1.1`.
+  pub(crate) line: String,
+  /// The headers, with names in lower case.
+  pub(crate) headers: Vec<(String, String)>,
+  /// The body.
+  pub(crate) body: Vec<u8>,
+}
+
+impl Request {
+  /// Read a request from the given stream.
+  fn read(stream: &mut TcpStream) -> Self {
+    let mut data = Vec::new();
+    let mut buffer = [0; 4096];
+    let end = loop {
+      if let Some(end) = data.windows(4).position(|window| window == b"\r\n\r\n") {
+        break end
+      }
+      let count = stream.read(&mut buffer).unwrap();
+      assert!(count > 0, "connection closed before end of header");
+      let () = data.extend_from_slice(&buffer[..count]);
+    };
+
+    let head = String::from_utf8(data[..end].to_vec()).unwrap();
+    let mut lines = head.split("\r\n");
+    let line = lines.next().unwrap().to_string();
+    let headers = lines
+      .filter_map(|line| line.split_once(':'))
+      .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+      .collect::<Vec<_>>();
+
+    let mut request = Self {
+      line,
+      headers,
+      body: data[end + 4..].to_vec(),
+    };
+    let length = request
+      .header("content-length")
+      .map(|length| length.parse::<usize>().unwrap())
+      .unwrap_or(0);
+
+    while request.body.len() < length {
+      let count = stream.read(&mut buffer).unwrap();
+      assert!(count > 0, "connection closed before end of body");
+      let () = request.body.extend_from_slice(&buffer[..count]);
+    }
+    request
+  }
+
+  /// Retrieve the value of the header with the given (lower case)
+  /// name, if present.
+  pub(crate) fn header(&self, name: &str) -> Option<&str> {
+    self
+      .headers
+      .iter()
+      .find(|(key, _)| key == name)
+      .map(|(_, value)| value.as_str())
+  }
+}
+
+
+/// An HTTP response to be served by a [`MockServer`].
+#[derive(Clone, Debug)]
+pub(crate) struct Response {
+  /// The status code.
+  status: StatusCode,
+  /// Additional headers.
+  headers: Vec<(String, String)>,
+  /// The body.
+  body: Vec<u8>,
+}
+
+impl Response {
+  /// Create a response with the given status and an empty body.
+  pub(crate) fn new(status: StatusCode) -> Self {
+    Self {
+      status,
+      headers: Vec::new(),
+      body: Vec::new(),
+    }
+  }
+
+  /// Create a `200 OK` response with the given JSON body.
+  pub(crate) fn json(body: impl Into<String>) -> Self {
+    Self::new(StatusCode::OK)
+      .header("content-type", "application/json")
+      .body(body.into())
+  }
+
+  /// Add a header to the response.
+  pub(crate) fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+    let () = self.headers.push((name.to_string(), value.into()));
+    self
+  }
+
+  /// Set the body of the response.
+  pub(crate) fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+    self.body = body.into();
+    self
+  }
+
+  /// Write the response to the given stream.
+  fn write(&self, stream: &mut TcpStream) {
+    let mut data = format!(
+      "HTTP/1.1 {} {}\r\n",
+      self.status.as_u16(),
+      self.status.canonical_reason().unwrap_or("")
+    );
+    for (name, value) in &self.headers {
+      let () = data.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    let () = data.push_str(&format!(
+      "content-length: {}\r\nconnection: close\r\n\r\n",
+      self.body.len()
+    ));
+
+    let () = stream.write_all(data.as_bytes()).unwrap();
+    let () = stream.write_all(&self.body).unwrap();
+  }
+}
+
+
+/// A local HTTP server serving canned responses, one per connection.
+#[derive(Debug)]
+pub(crate) struct MockServer {
+  /// The address the server is listening on.
+  addr: SocketAddr,
+  /// The thread serving requests, reporting all requests received.
+  thread: JoinHandle<Vec<Request>>,
+}
+
+impl MockServer {
+  /// Start a server accepting `count` connections, responding to the
+  /// request received on each with the response created by `handler`.
+  ///
+  /// Connections for which the handler returns `None` are left
+  /// unanswered, but kept open until the server is done.
+  pub(crate) fn start<F>(count: usize, mut handler: F) -> Self
+  where
+    F: FnMut(&Request) -> Option<Response> + Send + 'static,
+  {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let thread = spawn(move || {
+      let mut requests = Vec::with_capacity(count);
+      let mut pending = Vec::new();
+
+      for _ in 0..count {
+        let (mut stream, _) = listener.accept().unwrap();
+        let request = Request::read(&mut stream);
+        match handler(&request) {
+          Some(response) => response.write(&mut stream),
+          None => pending.push(stream),
+        }
+        let () = requests.push(request);
+      }
+      requests
+    });
+
+    Self { addr, thread }
+  }
+
+  /// Start a server serving the given responses in order, one per
+  /// connection.
+  pub(crate) fn serve<I>(responses: I) -> Self
+  where
+    I: IntoIterator<Item = Response>,
+  {
+    let responses = responses.into_iter().collect::<Vec<_>>();
+    let count = responses.len();
+    let mut responses = responses.into_iter();
+    Self::start(count, move |_| responses.next())
+  }
+
+  /// Retrieve the address the server is listening on.
+  #[inline]
+  pub(crate) fn addr(&self) -> SocketAddr {
+    self.addr
+  }
+
+  /// Retrieve the URL of the server.
+  pub(crate) fn url(&self) -> String {
+    format!("http://{}", self.addr)
+  }
+
+  /// Create an `ApiInfo` object directing trading and market data
+  /// requests to the server.
+  pub(crate) fn api_info(&self) -> ApiInfo {
+    let url = self.url();
+    ApiInfo::from_parts(&url, "key", "secret")
+      .unwrap()
+      .with_data_base_url(&url)
+      .unwrap()
+  }
+
+  /// Create a client issuing trading and market data requests against
+  /// the server.
+  pub(crate) fn client(&self) -> Client {
+    Client::new(self.api_info())
+  }
+
+  /// Wait for the server to finish, retrieving all requests received.
+  pub(crate) fn join(self) -> Vec<Request> {
+    self.thread.join().unwrap()
+  }
+}
@@ -0,0 +1,211 @@
+// Copyright (C) 2022 The apca Developers
+// SPDX-License-Identifier: GPL-3.0-or-later
+
+use std::sync::Mutex;
+
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+use hyper::client::HttpConnector;
+
+use tungstenite::Connector;
+
+use crate::Error;
+
+#[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+compile_error!("either the `rustls` or the `native-tls` feature needs to be enabled");
+
+
+/// Additional DER encoded root certificates to trust.
+static ROOT_CERTIFICATES: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+
+
+/// Trust the provided DER encoded root certificate, in addition to the
+/// default ones, e.g., for routing traffic through a TLS intercepting
+/// proxy.
+///
+/// The certificate is used by all [`Client`][crate::Client] objects
+/// created and all streams connected from now on.
+#[allow(clippy::result_large_err)]
+pub fn add_root_certificate(der: &[u8]) -> Result<(), Error> {
+  let () = backend::validate(der)?;
+  ROOT_CERTIFICATES.lock().unwrap().push(der.to_vec());
+  Ok(())
+}
+
+/// Trust all root certificates contained in the provided PEM data, in
+/// addition to the default ones.
+///
+/// On success, the number of certificates added is returned. See
+/// [`add_root_certificate`] for details.
+#[allow(clippy::result_large_err)]
+pub fn add_root_certificates_pem(pem: &[u8]) -> Result<usize, Error> {
+  let certs = rustls_pemfile::certs(&mut &*pem)
+    .map_err(|err| Error::Str(format!("failed to parse PEM data: {}", err).into()))?;
+  if certs.is_empty() {
+    return Err(Error::Str("PEM data contains no certificates".into()))
+  }
+
+  for der in &certs {
+    let () = backend::validate(der)?;
+  }
+  let count = certs.len();
+  ROOT_CERTIFICATES.lock().unwrap().extend(certs);
+  Ok(count)
+}
+
+
+/// The connector used for establishing HTTPS connections.
+pub(crate) type HttpsConnector = backend::HttpsConnector;
+
+/// Create a connector for establishing HTTPS connections.
+#[inline]
+pub(crate) fn https_connector() -> HttpsConnector {
+  backend::https_connector()
+}
+
+/// Create a connector for establishing websocket connections.
+#[inline]
+pub(crate) fn websocket_connector() -> Connector {
+  backend::websocket_connector()
+}
+
+
+#[cfg(feature = "native-tls")]
+mod backend {
+  use super::*;
+
+  use native_tls::Certificate;
+  use native_tls::TlsConnector;
+
+  pub(crate) type HttpsConnector = hyper_tls::HttpsConnector<HttpConnector>;
+
+
+  /// Check that the provided DER encoded certificate is valid.
+  #[allow(clippy::result_large_err)]
+  pub(super) fn validate(der: &[u8]) -> Result<(), Error> {
+    Certificate::from_der(der)
+      .map(|_| ())
+      .map_err(|err| Error::Str(format!("invalid certificate: {}", err).into()))
+  }
+
+  fn tls_connector() -> TlsConnector {
+    let mut builder = TlsConnector::builder();
+    for der in ROOT_CERTIFICATES.lock().unwrap().iter() {
+      // Certificates got validated when they were added.
+      let _ = builder.add_root_certificate(Certificate::from_der(der).unwrap());
+    }
+    builder.build().expect("failed to initialize native TLS")
+  }
+
+  pub(super) fn https_connector() -> HttpsConnector {
+    let mut http = HttpConnector::new();
+    let () = http.enforce_http(false);
+    hyper_tls::HttpsConnector::from((http, tls_connector().into()))
+  }
+
+  pub(super) fn websocket_connector() -> Connector {
+    Connector::NativeTls(tls_connector())
+  }
+}
+
+
+#[cfg(all(feature = "rustls", not(feature = "native-tls")))]
+mod backend {
+  use super::*;
+
+  use std::sync::Arc;
+
+  use hyper_rustls::HttpsConnectorBuilder;
+
+  use rustls::Certificate;
+  use rustls::ClientConfig;
+  use rustls::OwnedTrustAnchor;
+  use rustls::RootCertStore;
+
+  pub(crate) type HttpsConnector = hyper_rustls::HttpsConnector<HttpConnector>;
+
+
+  /// Check that the provided DER encoded certificate is valid.
+  #[allow(clippy::result_large_err)]
+  pub(super) fn validate(der: &[u8]) -> Result<(), Error> {
+    RootCertStore::empty()
+      .add(&Certificate(der.to_vec()))
+      .map_err(|err| Error::Str(format!("invalid certificate: {}", err).into()))
+  }
+
+  fn client_config() -> ClientConfig {
+    let mut roots = RootCertStore::empty();
+    roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|anchor| {
+      OwnedTrustAnchor::from_subject_spki_name_constraints(
+        anchor.subject,
+        anchor.spki,
+        anchor.name_constraints,
+      )
+    }));
+    for der in ROOT_CERTIFICATES.lock().unwrap().iter() {
+      // Certificates got validated when they were added.
+      let () = roots.add(&Certificate(der.clone())).unwrap();
+    }
+
+    ClientConfig::builder()
+      .with_safe_defaults()
+      .with_root_certificates(roots)
+      .with_no_client_auth()
+  }
+
+  pub(super) fn https_connector() -> HttpsConnector {
+    HttpsConnectorBuilder::new()
+      .with_tls_config(client_config())
+      .https_or_http()
+      .enable_http1()
+      .build()
+  }
+
+  pub(super) fn websocket_connector() -> Connector {
+    Connector::Rustls(Arc::new(client_config()))
+  }
+}
+
+
+/// A stand-in used when no TLS backend is enabled, merely keeping the
+/// remainder of the crate compiling so that only the `compile_error!`
+/// above is reported.
+#[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+mod backend {
+  use super::*;
+
+  pub(crate) type HttpsConnector = crate::connector::Connector;
+
+
+  #[allow(clippy::result_large_err)]
+  pub(super) fn validate(_der: &[u8]) -> Result<(), Error> {
+    unreachable!()
+  }
+
+  pub(super) fn https_connector() -> HttpsConnector {
+    unreachable!()
+  }
+
+  pub(super) fn websocket_connector() -> Connector {
+    unreachable!()
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use test_log::test;
+
+
+  /// Check that we reject invalid certificates.
+  #[test]
+  fn reject_invalid_certificates() {
+    assert!(add_root_certificate(b"invalid").is_err());
+    assert!(add_root_certificates_pem(b"").is_err());
+
+    let pem = b"-----BEGIN CERTIFICATE-----\naW52YWxpZA==\n-----END CERTIFICATE-----\n";
+    assert!(add_root_certificates_pem(pem).is_err());
+    assert!(ROOT_CERTIFICATES.lock().unwrap().is_empty());
+  }
+}
@@ -1,10 +1,21 @@
 // Copyright (C) 2020-2022 The apca Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::fmt::Formatter;
+use std::fmt::Result as FmtResult;
+
+use chrono::DateTime;
+use chrono::NaiveDateTime;
+use chrono::TimeZone as _;
+use chrono::Utc;
+
 use crate::Str;
 
 use num_decimal::Num;
 
+use serde::de::Error;
+use serde::de::IntoDeserializer as _;
+use serde::de::Visitor;
 use serde::Deserialize;
 use serde::Deserializer;
 use serde::Serialize;
@@ -22,6 +33,163 @@ where
 }
 
 
+/// Parse a timestamp as reported by the API.
+///
+/// Different endpoints report timestamps with differing precision
+/// (anywhere from seconds to nanoseconds) and with either a `Z` suffix
+/// or a numeric offset, which may or may not contain a colon. A space
+/// instead of the `T` separating date and time is accepted as well and
+/// timestamps lacking an offset altogether are interpreted as UTC.
+pub(crate) fn parse_datetime(s: &str) -> Option<DateTime<Utc>> {
+  let s = s.trim();
+  // The vast majority of timestamps are proper RFC 3339 ones, so try
+  // that first.
+  if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+    return Some(time.with_timezone(&Utc))
+  }
+
+  let (with_offset, without_offset) = match s.as_bytes().get(10) {
+    Some(b' ') => ("%Y-%m-%d %H:%M:%S%.f%z", "%Y-%m-%d %H:%M:%S%.f"),
+    Some(b't') => ("%Y-%m-%dt%H:%M:%S%.f%z", "%Y-%m-%dt%H:%M:%S%.f"),
+    _ => ("%Y-%m-%dT%H:%M:%S%.f%z", "%Y-%m-%dT%H:%M:%S%.f"),
+  };
+
+  if let Ok(time) = DateTime::parse_from_str(s, with_offset) {
+    return Some(time.with_timezone(&Utc))
+  }
+  NaiveDateTime::parse_from_str(s, without_offset)
+    .ok()
+    .map(|time| Utc.from_utc_datetime(&time))
+}
+
+
+/// Deserialize a `DateTime<Utc>` from a timestamp string in any of the
+/// formats supported by [`parse_datetime`].
+pub(crate) fn datetime_from_str<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  struct TimestampVisitor;
+
+  impl<'de> Visitor<'de> for TimestampVisitor {
+    type Value = DateTime<Utc>;
+
+    fn expecting(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+      fmt.write_str("an RFC 3339 timestamp")
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+    where
+      E: Error,
+    {
+      parse_datetime(s).ok_or_else(|| E::custom(format!("invalid timestamp: {}", s)))
+    }
+  }
+
+  deserializer.deserialize_str(TimestampVisitor)
+}
+
+
+/// Deserialize an `Option<DateTime<Utc>>` from a timestamp string (that
+/// could be `null`) in any of the formats supported by
+/// [`parse_datetime`].
+pub(crate) fn optional_datetime_from_str<'de, D>(
+  deserializer: D,
+) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  struct Timestamp(DateTime<Utc>);
+
+  impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+      D: Deserializer<'de>,
+    {
+      datetime_from_str(deserializer).map(Timestamp)
+    }
+  }
+
+  let time = Option::<Timestamp>::deserialize(deserializer)?;
+  Ok(time.map(|Timestamp(time)| time))
+}
+
+
+/// Deserialize an optional `Num` from a number or numeric string,
+/// treating `null` as well as an empty string as absent.
+pub(crate) fn optional_num_from_str_or_null<'de, D>(
+  deserializer: D,
+) -> Result<Option<Num>, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  struct NumVisitor;
+
+  impl<'de> Visitor<'de> for NumVisitor {
+    type Value = Option<Num>;
+
+    fn expecting(&self, fmt: &mut Formatter<'_>) -> FmtResult {
+      fmt.write_str("a number, a numeric string, an empty string, or null")
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E>
+    where
+      E: Error,
+    {
+      Ok(None)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+      E: Error,
+    {
+      Ok(None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+      D: Deserializer<'de>,
+    {
+      deserializer.deserialize_any(self)
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
+    where
+      E: Error,
+    {
+      if s.trim().is_empty() {
+        Ok(None)
+      } else {
+        Num::deserialize(s.into_deserializer()).map(Some)
+      }
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+    where
+      E: Error,
+    {
+      Num::deserialize(value.into_deserializer()).map(Some)
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+    where
+      E: Error,
+    {
+      Num::deserialize(value.into_deserializer()).map(Some)
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Self::Value, E>
+    where
+      E: Error,
+    {
+      Num::deserialize(value.into_deserializer()).map(Some)
+    }
+  }
+
+  deserializer.deserialize_any(NumVisitor)
+}
+
+
 /// Deserialize a `Vec` from a string that could contain a `null`.
 pub(crate) fn vec_from_str<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
 where
@@ -33,6 +201,19 @@ where
 }
 
 
+/// Deserialize an optional (flattened) `T`, treating a `T` that equals
+/// its default, i.e., one for which none of the fields were present, as
+/// absent.
+pub(crate) fn optional_flattened<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+  D: Deserializer<'de>,
+  T: Deserialize<'de> + Default + PartialEq,
+{
+  let value = T::deserialize(deserializer)?;
+  Ok((value != T::default()).then_some(value))
+}
+
+
 /// Deserialize a `Vec<String>` from a string (that could be `null`)
 /// with comma separated elements.
 pub(crate) fn vec_from_comma_separated_str<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
@@ -109,3 +290,126 @@ where
 
   slice_to_str(slice, name_fn, serializer)
 }
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::from_str as from_json;
+
+
+  /// Parse a timestamp, expecting success.
+  fn parse(s: &str) -> DateTime<Utc> {
+    parse_datetime(s).unwrap_or_else(|| panic!("failed to parse {}", s))
+  }
+
+
+  /// Check that we can parse timestamps with second precision.
+  #[test]
+  fn parse_seconds() {
+    let time = parse("2022-03-04T14:30:05Z");
+    assert_eq!(time.timestamp(), 1646404205);
+    assert_eq!(time.timestamp_subsec_nanos(), 0);
+  }
+
+  /// Check that we can parse timestamps with millisecond precision.
+  #[test]
+  fn parse_millis() {
+    let time = parse("2022-03-04T14:30:05.123Z");
+    assert_eq!(time.timestamp(), 1646404205);
+    assert_eq!(time.timestamp_subsec_nanos(), 123_000_000);
+  }
+
+  /// Check that we can parse timestamps with microsecond precision.
+  #[test]
+  fn parse_micros() {
+    let time = parse("2022-03-04T14:30:05.123456Z");
+    assert_eq!(time.timestamp_subsec_nanos(), 123_456_000);
+  }
+
+  /// Check that we can parse timestamps with nanosecond precision.
+  #[test]
+  fn parse_nanos() {
+    let time = parse("2022-03-04T14:30:05.123456789Z");
+    assert_eq!(time.timestamp_subsec_nanos(), 123_456_789);
+  }
+
+  /// Check that we can parse timestamps with numeric offsets.
+  #[test]
+  fn parse_offsets() {
+    let expected = parse("2022-03-04T14:30:05.5Z");
+    assert_eq!(parse("2022-03-04T14:30:05.5+00:00"), expected);
+    assert_eq!(parse("2022-03-04T14:30:05.5+0000"), expected);
+    assert_eq!(parse("2022-03-04T09:30:05.5-05:00"), expected);
+    assert_eq!(parse("2022-03-04T09:30:05.5-0500"), expected);
+    assert_eq!(parse("2022-03-04 14:30:05.5+00:00"), expected);
+    assert_eq!(parse("2022-03-04 14:30:05.5+0000"), expected);
+    assert_eq!(parse("2022-03-04t14:30:05.5-0000"), expected);
+  }
+
+  /// Check that timestamps without offset are interpreted as UTC.
+  #[test]
+  fn parse_without_offset() {
+    assert_eq!(
+      parse("2022-03-04T14:30:05.000001"),
+      parse("2022-03-04T14:30:05.000001Z")
+    );
+    assert_eq!(parse("2022-03-04T14:30:05"), parse("2022-03-04T14:30:05Z"));
+    assert_eq!(parse("2022-03-04 14:30:05"), parse("2022-03-04T14:30:05Z"));
+  }
+
+  /// Check that we reject invalid timestamps.
+  #[test]
+  fn parse_invalid() {
+    assert_eq!(parse_datetime(""), None);
+    assert_eq!(parse_datetime("2022-03-04"), None);
+    assert_eq!(parse_datetime("2022-03-04T25:30:05Z"), None);
+    assert_eq!(parse_datetime("not a timestamp"), None);
+    assert_eq!(parse_datetime("2022-03-0ä 14:30:05"), None);
+  }
+
+  /// Check that we can deserialize optional numbers from all the
+  /// representations the API uses.
+  #[test]
+  fn deserialize_optional_num() {
+    #[derive(Deserialize)]
+    struct Price {
+      #[serde(default, deserialize_with = "optional_num_from_str_or_null")]
+      price: Option<Num>,
+    }
+
+    let price = |json| from_json::<Price>(json).map(|price| price.price);
+    assert_eq!(
+      price(r#"{"price": "12.5"}"#).unwrap(),
+      Some(Num::new(25, 2))
+    );
+    assert_eq!(price(r#"{"price": 12.5}"#).unwrap(), Some(Num::new(25, 2)));
+    assert_eq!(price(r#"{"price": -3}"#).unwrap(), Some(Num::from(-3)));
+    assert_eq!(price(r#"{"price": 7}"#).unwrap(), Some(Num::from(7)));
+    assert_eq!(price(r#"{"price": ""}"#).unwrap(), None);
+    assert_eq!(price(r#"{"price": " "}"#).unwrap(), None);
+    assert_eq!(price(r#"{"price": null}"#).unwrap(), None);
+    assert_eq!(price(r#"{}"#).unwrap(), None);
+    assert!(price(r#"{"price": "n/a"}"#).is_err());
+    assert!(price(r#"{"price": true}"#).is_err());
+  }
+
+  /// Check that we can deserialize optional timestamps.
+  #[test]
+  fn deserialize_optional_datetime() {
+    #[derive(Deserialize)]
+    struct Times {
+      #[serde(default, deserialize_with = "optional_datetime_from_str")]
+      time: Option<DateTime<Utc>>,
+    }
+
+    let times = from_json::<Times>(r#"{"time": "2022-03-04T14:30:05+00:00"}"#).unwrap();
+    assert_eq!(times.time, Some(parse("2022-03-04T14:30:05Z")));
+    let times = from_json::<Times>(r#"{"time": null}"#).unwrap();
+    assert_eq!(times.time, None);
+    let times = from_json::<Times>(r#"{}"#).unwrap();
+    assert_eq!(times.time, None);
+    assert!(from_json::<Times>(r#"{"time": "yesterday"}"#).is_err());
+  }
+}
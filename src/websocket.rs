@@ -1,6 +1,15 @@
 // Copyright (C) 2019-2022 The apca Developers
 // SPDX-License-Identifier: GPL-3.0-or-later
 
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+
+use futures::future::poll_fn;
+use futures::Sink;
+
 use url::Url;
 
 use tokio::net::TcpStream;
@@ -11,12 +20,16 @@ use tracing::trace;
 use tracing::Level;
 use tracing_futures::Instrument;
 
-use tungstenite::connect_async;
+#[cfg(any(feature = "rustls", feature = "native-tls"))]
+use tungstenite::connect_async_tls_with_config;
+#[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+use tungstenite::Connector;
 use tungstenite::MaybeTlsStream;
 use tungstenite::WebSocketStream;
 
 use websocket_util::wrap::Wrapper;
 
+use crate::tls;
 use crate::Error;
 
 
@@ -42,6 +55,73 @@ impl<T, E> From<Result<T, E>> for MessageResult<T, E> {
 }
 
 
+/// A sink that can be shared between multiple owners.
+///
+/// We hand one instance to the `websocket_util` subscription object
+/// while retaining another one, so that we can close the underlying
+/// connection ourselves.
+#[derive(Debug)]
+#[doc(hidden)]
+pub struct SharedSink<S>(Arc<Mutex<S>>);
+
+impl<S> SharedSink<S> {
+  /// Create a new `SharedSink` wrapping the provided one.
+  pub(crate) fn new(sink: S) -> Self {
+    Self(Arc::new(Mutex::new(sink)))
+  }
+
+  /// Close the wrapped sink, flushing all pending data.
+  pub(crate) async fn close<I>(&self) -> Result<(), S::Error>
+  where
+    S: Sink<I> + Unpin,
+  {
+    poll_fn(|ctx| Pin::new(&mut *self.0.lock().unwrap()).poll_close(ctx)).await
+  }
+}
+
+impl<S> Clone for SharedSink<S> {
+  fn clone(&self) -> Self {
+    Self(Arc::clone(&self.0))
+  }
+}
+
+impl<S, I> Sink<I> for SharedSink<S>
+where
+  S: Sink<I> + Unpin,
+{
+  type Error = S::Error;
+
+  fn poll_ready(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    Pin::new(&mut *self.0.lock().unwrap()).poll_ready(ctx)
+  }
+
+  fn start_send(self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
+    Pin::new(&mut *self.0.lock().unwrap()).start_send(item)
+  }
+
+  fn poll_flush(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    Pin::new(&mut *self.0.lock().unwrap()).poll_flush(ctx)
+  }
+
+  fn poll_close(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    Pin::new(&mut *self.0.lock().unwrap()).poll_close(ctx)
+  }
+}
+
+
+/// A stand-in used when no TLS backend is enabled, merely keeping the
+/// crate compiling so that only the `compile_error!` in `tls` is
+/// reported.
+#[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+async fn connect_async_tls_with_config(
+  _url: &Url,
+  _config: Option<()>,
+  _connector: Option<Connector>,
+) -> Result<(WebSocketStream<MaybeTlsStream<TcpStream>>, ()), Error> {
+  unreachable!()
+}
+
+
 /// Internal function to connect to websocket server.
 async fn connect_internal(url: &Url) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>, Error> {
   let span = span!(Level::DEBUG, "stream");
@@ -52,7 +132,8 @@ async fn connect_internal(url: &Url) -> Result<WebSocketStream<MaybeTlsStream<Tc
     // We just ignore the response & headers that are sent along after
     // the connection is made. Alpaca does not seem to be using them,
     // really.
-    let (stream, response) = connect_async(url).await?;
+    let connector = tls::websocket_connector();
+    let (stream, response) = connect_async_tls_with_config(url, None, Some(connector)).await?;
     debug!("connection successful");
     trace!(response = debug(&response));
 
@@ -113,6 +194,7 @@ pub(crate) mod test {
       api_stream_url: stream_url.clone(),
       data_base_url: Url::parse("http://example.com").unwrap(),
       data_stream_base_url: stream_url.clone(),
+      broker_base_url: Url::parse("http://example.com").unwrap(),
       key_id: KEY_ID.to_string(),
       secret: SECRET.to_string(),
     };